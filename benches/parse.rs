@@ -0,0 +1,74 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use pbrt4::{param::Param, Scene};
+
+/// Triangle mesh with `count` vertices (and as many triangles) with 6 digit coordinates.
+fn mesh(count: usize) -> String {
+    let mut seed = 1u32;
+    let mut next = || {
+        // Xorshift, deterministic across runs.
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        seed as f32 / u32::MAX as f32 * 2.0 - 1.0
+    };
+
+    let positions = (0..count * 3)
+        .map(|_| format!("{:.6}", next()))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let normals = (0..count * 3)
+        .map(|_| format!("{:.6}", next()))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let indices = (0..count * 3)
+        .map(|i| (i % count).to_string())
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    format!(
+        "WorldBegin\nShape \"trianglemesh\"\n  \"point3 P\" [ {positions} ]\n  \"normal N\" [ {normals} ]\n  \"integer indices\" [ {indices} ]\n"
+    )
+}
+
+fn floats(c: &mut Criterion) {
+    let values = (0..300_000)
+        .map(|i| format!("{:.6}", (i as f32 * 0.37).sin()))
+        .collect::<Vec<_>>()
+        .join(" ");
+    let param = Param::new("point3 P", &values).unwrap();
+
+    let mut group = c.benchmark_group("floats");
+    group.throughput(Throughput::Bytes(values.len() as u64));
+
+    group.bench_function("str::parse", |b| {
+        b.iter(|| {
+            black_box(&values)
+                .split_whitespace()
+                .map(str::parse::<f32>)
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap()
+        })
+    });
+    group.bench_function("Param::floats", |b| {
+        b.iter(|| black_box(&param).floats().unwrap())
+    });
+
+    group.finish();
+}
+
+fn trianglemesh(c: &mut Criterion) {
+    let data = mesh(100_000);
+
+    let mut group = c.benchmark_group("trianglemesh");
+    group.throughput(Throughput::Bytes(data.len() as u64));
+    group.sample_size(20);
+
+    group.bench_function("Scene::load", |b| {
+        b.iter(|| Scene::load(black_box(&data), None).unwrap())
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, floats, trianglemesh);
+criterion_main!(benches);
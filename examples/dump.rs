@@ -12,6 +12,8 @@ fn main() -> Result<()> {
     println!("Loading scene: {}", &path);
     let scene = Scene::from_file(&path)?;
 
+    println!("{}", scene.summary());
+
     println!("Global options: {:#?}", scene.options);
 
     if let Some(camera) = scene.camera {
@@ -0,0 +1,19 @@
+use std::{env, fs};
+
+use pbrt4::{
+    format::{format, FormatOptions},
+    Result,
+};
+
+/// Formats a pbrt file and prints it to stdout.
+/// Usage:
+/// `❯ cargo run --example fmt -- ./assets/kroken/materials.pbrt`
+///
+fn main() -> Result<()> {
+    let path = env::args().nth(1).expect("Path to pbrt file expected");
+
+    let src = fs::read_to_string(path)?;
+    print!("{}", format(&src, &FormatOptions::default())?);
+
+    Ok(())
+}
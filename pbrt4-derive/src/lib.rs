@@ -0,0 +1,284 @@
+//! Derive macros for the [pbrt4](https://crates.io/crates/pbrt4) crate.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{
+    parse_macro_input, Attribute, Data, DeriveInput, Error, Expr, Fields, Ident, LitStr, Result,
+};
+
+/// Derive parameter extraction.
+///
+/// For structs, implements `pbrt4::param::FromParams`: each named field is read from the
+/// parameter with the same name (underscores removed, so `max_depth` reads `"maxdepth"`).
+///
+/// For enums, implements `pbrt4::param::FromTypedParams`: the variant is selected by
+/// the type string (the lowercase variant name), fields are read as for structs.
+/// Empty enums are not supported.
+///
+/// Both also implement `param_names`, listing the parameters which are read
+/// (see `ParamList::unused`).
+///
+/// Field attributes:
+/// - `#[param(name = "L")]` overrides the parameter name.
+/// - `#[param(default = expr)]` is used when the parameter is missing, `expr` may refer to
+///   previously declared fields. Without it, `Default::default()` is used.
+/// - `#[param(required)]` fails with `MissingRequiredParameter` when the parameter is missing.
+/// - `#[param(skip)]` doesn't read the field, it's initialized with the default value.
+///
+/// Enum variants with a single unnamed field read it as a whole, the field type must
+/// implement `FromParams`.
+///
+/// Variant attributes:
+/// - `#[param(name = "goniometric")]` overrides the type string.
+#[proc_macro_derive(FromParams, attributes(param))]
+pub fn derive_from_params(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    let result = match &input.data {
+        Data::Struct(data) => derive_struct(&input, &data.fields),
+        Data::Enum(data) => derive_enum(&input, data.variants.iter()),
+        Data::Union(_) => Err(Error::new_spanned(
+            &input.ident,
+            "FromParams can't be derived for unions",
+        )),
+    };
+
+    result.unwrap_or_else(Error::into_compile_error).into()
+}
+
+fn derive_struct(input: &DeriveInput, fields: &Fields) -> Result<TokenStream2> {
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let Constructor { body, names } = construct(quote!(Self), fields)?;
+
+    Ok(quote! {
+        impl #impl_generics ::pbrt4::param::FromParams for #ident #ty_generics #where_clause {
+            fn from_params(params: &::pbrt4::param::ParamList<'_>) -> ::pbrt4::Result<Self> {
+                #body
+            }
+
+            fn param_names() -> ::std::vec::Vec<&'static str> {
+                #names
+            }
+        }
+    })
+}
+
+fn derive_enum<'a>(
+    input: &DeriveInput,
+    variants: impl Iterator<Item = &'a syn::Variant>,
+) -> Result<TokenStream2> {
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut arms = Vec::new();
+    let mut names = Vec::new();
+    let mut param_names = Vec::new();
+
+    for variant in variants {
+        let attrs = Attrs::parse(&variant.attrs)?;
+        let name = attrs
+            .name
+            .unwrap_or_else(|| variant.ident.to_string().to_lowercase());
+
+        let variant_ident = &variant.ident;
+        let cfgs = cfg_attrs(&variant.attrs);
+        let Constructor {
+            body,
+            names: variant_names,
+        } = construct(quote!(Self::#variant_ident), &variant.fields)?;
+
+        arms.push(quote! {
+            #(#cfgs)*
+            #name => { #body }
+        });
+
+        param_names.push(quote! {
+            #(#cfgs)*
+            #name => { #variant_names }
+        });
+
+        names.push(quote! {
+            #(#cfgs)*
+            Self::#variant_ident { .. } => #name,
+        });
+    }
+
+    Ok(quote! {
+        impl #impl_generics ::pbrt4::param::FromTypedParams for #ident #ty_generics #where_clause {
+            fn from_typed_params(
+                ty: &str,
+                params: &::pbrt4::param::ParamList<'_>,
+            ) -> ::pbrt4::Result<Self> {
+                match ty {
+                    #(#arms)*
+                    _ => Err(::pbrt4::Error::InvalidObjectType),
+                }
+            }
+
+            fn type_name(&self) -> &'static str {
+                match self {
+                    #(#names)*
+                }
+            }
+
+            fn param_names(ty: &str) -> ::core::option::Option<::std::vec::Vec<&'static str>> {
+                let names = match ty {
+                    #(#param_names)*
+                    _ => return ::core::option::Option::None,
+                };
+
+                ::core::option::Option::Some(names)
+            }
+        }
+    })
+}
+
+/// Generated code for a struct or an enum variant.
+struct Constructor {
+    /// Reads the fields and constructs the value.
+    body: TokenStream2,
+    /// Evaluates to the names of the parameters which are read.
+    names: TokenStream2,
+}
+
+/// Generate code reading `fields` and constructing `path` from them.
+fn construct(path: TokenStream2, fields: &Fields) -> Result<Constructor> {
+    let fields = match fields {
+        Fields::Named(fields) => &fields.named,
+        Fields::Unit => {
+            return Ok(Constructor {
+                body: quote!(Ok(#path)),
+                names: quote!(::std::vec::Vec::new()),
+            })
+        }
+        Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+            let ty = &fields.unnamed[0].ty;
+            return Ok(Constructor {
+                body: quote!(Ok(#path(::pbrt4::param::FromParams::from_params(params)?))),
+                names: quote!(<#ty as ::pbrt4::param::FromParams>::param_names()),
+            });
+        }
+        Fields::Unnamed(fields) => {
+            return Err(Error::new_spanned(
+                fields,
+                "FromParams requires named fields or a single unnamed field",
+            ))
+        }
+    };
+
+    let mut lets = Vec::new();
+    let mut inits = Vec::new();
+    let mut names = Vec::new();
+
+    for field in fields {
+        let ident = field.ident.as_ref().expect("Named field");
+        let ty = &field.ty;
+        let attrs = Attrs::parse(&field.attrs)?;
+        let cfgs = cfg_attrs(&field.attrs);
+
+        let default = match (&attrs.default, attrs.required) {
+            (Some(_), true) => {
+                return Err(Error::new_spanned(
+                    ident,
+                    "`default` and `required` are mutually exclusive",
+                ))
+            }
+            (Some(default), false) => quote!(#default),
+            (None, true) => quote!(return Err(::pbrt4::Error::MissingRequiredParameter)),
+            (None, false) => quote!(::core::default::Default::default()),
+        };
+
+        let value = if attrs.skip {
+            default
+        } else {
+            let name = attrs.name.unwrap_or_else(|| param_name(ident));
+
+            names.push(quote! {
+                #(#cfgs)*
+                names.push(#name);
+            });
+
+            quote! {
+                match params.get(#name) {
+                    Some(param) => ::pbrt4::param::FromParam::from_param(param)?,
+                    None => #default,
+                }
+            }
+        };
+
+        lets.push(quote! {
+            #(#cfgs)*
+            let #ident: #ty = #value;
+        });
+
+        inits.push(quote! {
+            #(#cfgs)*
+            #ident
+        });
+    }
+
+    Ok(Constructor {
+        body: quote! {
+            #(#lets)*
+            Ok(#path { #(#inits),* })
+        },
+        names: quote! {{
+            #[allow(unused_mut)]
+            let mut names = ::std::vec::Vec::new();
+            #(#names)*
+            names
+        }},
+    })
+}
+
+/// Default parameter name for a field: the field name without underscores.
+fn param_name(ident: &Ident) -> String {
+    let name = ident.to_string();
+    name.trim_start_matches("r#").replace('_', "")
+}
+
+/// Forward `#[cfg]` attributes, so conditionally compiled fields are handled.
+fn cfg_attrs(attrs: &[Attribute]) -> Vec<&Attribute> {
+    attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("cfg"))
+        .collect()
+}
+
+/// Parsed `#[param(...)]` attributes.
+#[derive(Default)]
+struct Attrs {
+    name: Option<String>,
+    default: Option<Expr>,
+    required: bool,
+    skip: bool,
+}
+
+impl Attrs {
+    fn parse(attrs: &[Attribute]) -> Result<Self> {
+        let mut out = Attrs::default();
+
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("param")) {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("name") {
+                    out.name = Some(meta.value()?.parse::<LitStr>()?.value());
+                } else if meta.path.is_ident("default") {
+                    out.default = Some(meta.value()?.parse::<Expr>()?);
+                } else if meta.path.is_ident("required") {
+                    out.required = true;
+                } else if meta.path.is_ident("skip") {
+                    out.skip = true;
+                } else {
+                    return Err(meta.error("unsupported param attribute"));
+                }
+
+                Ok(())
+            })?;
+        }
+
+        Ok(out)
+    }
+}
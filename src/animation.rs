@@ -0,0 +1,190 @@
+//! Animated transformations for motion blur.
+
+use crate::math::{float, Mat4, Quat, Vec3};
+
+/// A transformation varying over the shutter interval.
+///
+/// Interpolation follows pbrt: both matrices are decomposed into translation, rotation
+/// and scale, translation and scale are interpolated linearly, rotation is interpolated with slerp.
+///
+/// ```
+/// # use pbrt4::{animation::AnimatedTransform, math::{Mat4, Vec3}};
+/// let start = Mat4::IDENTITY;
+/// let end = Mat4::from_translation(Vec3::new(2.0, 0.0, 0.0));
+///
+/// let animated = AnimatedTransform::new(start, 0.0, end, 1.0);
+/// assert_eq!(animated.interpolate(0.5), Mat4::from_translation(Vec3::new(1.0, 0.0, 0.0)));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AnimatedTransform {
+    start: Mat4,
+    end: Mat4,
+    start_time: f32,
+    end_time: f32,
+    /// Decomposed start and end transformations if animated.
+    components: Option<[Components; 2]>,
+}
+
+/// Translation, rotation and scale components of a transformation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Components {
+    translation: Vec3,
+    rotation: Quat,
+    scale: Mat4,
+}
+
+impl Components {
+    /// Decompose `m` into `T * R * S`.
+    ///
+    /// Rotation is extracted with polar decomposition, so `S` may contain shear.
+    fn new(m: &Mat4) -> Self {
+        let translation = Vec3::new(m.get(0, 3), m.get(1, 3), m.get(2, 3));
+        let (r, scale) = m.polar_decompose();
+
+        Components {
+            translation,
+            rotation: Quat::from_mat4(&r),
+            scale,
+        }
+    }
+}
+
+impl AnimatedTransform {
+    /// Create an animated transformation going from `start` at `start_time`
+    /// to `end` at `end_time`.
+    pub fn new(start: Mat4, start_time: f32, end: Mat4, end_time: f32) -> Self {
+        let components = (start != end).then(|| {
+            let start = Components::new(&start);
+            let mut end = Components::new(&end);
+
+            // Interpolate along the shortest path.
+            if start.rotation.dot(end.rotation) < 0.0 {
+                end.rotation = -end.rotation;
+            }
+
+            [start, end]
+        });
+
+        AnimatedTransform {
+            start,
+            end,
+            start_time,
+            end_time,
+            components,
+        }
+    }
+
+    /// Create a transformation which doesn't change over time.
+    pub fn fixed(m: Mat4) -> Self {
+        Self::new(m, 0.0, m, 1.0)
+    }
+
+    /// Transformation at the start of the interval.
+    pub fn start(&self) -> Mat4 {
+        self.start
+    }
+
+    /// Transformation at the end of the interval.
+    pub fn end(&self) -> Mat4 {
+        self.end
+    }
+
+    pub fn start_time(&self) -> f32 {
+        self.start_time
+    }
+
+    pub fn end_time(&self) -> f32 {
+        self.end_time
+    }
+
+    /// Returns `true` if start and end transformations are different.
+    pub fn is_animated(&self) -> bool {
+        self.components.is_some()
+    }
+
+    /// Transformation at `time`.
+    ///
+    /// Times outside of the interval are clamped.
+    pub fn interpolate(&self, time: f32) -> Mat4 {
+        let Some([start, end]) = &self.components else {
+            return self.start;
+        };
+
+        if time <= self.start_time {
+            return self.start;
+        }
+
+        if time >= self.end_time {
+            return self.end;
+        }
+
+        let t = float((time - self.start_time) / (self.end_time - self.start_time));
+
+        let translation = start.translation * (1.0 - t) + end.translation * t;
+        let rotation = start.rotation.slerp(end.rotation, t);
+
+        let mut scale = start.scale.to_cols_array();
+        for (value, end) in scale.iter_mut().zip(end.scale.to_cols_array()) {
+            *value = *value * (1.0 - t) + end * t;
+        }
+        let scale = Mat4::from_cols_array(&scale);
+
+        Mat4::from_translation(translation) * rotation.to_mat4() * scale
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_mat_eq(a: Mat4, b: Mat4) {
+        for (x, y) in a.to_cols_array().iter().zip(b.to_cols_array().iter()) {
+            assert!((x - y).abs() < 1e-4, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn decompose() {
+        let m = Mat4::from_translation(Vec3::new(1.0, 2.0, 3.0))
+            * Mat4::from_axis_angle(Vec3::new(0.0, 1.0, 1.0), 0.7)
+            * Mat4::from_scale(Vec3::new(2.0, 3.0, 4.0));
+
+        let c = Components::new(&m);
+
+        assert_eq!(c.translation, Vec3::new(1.0, 2.0, 3.0));
+        assert_mat_eq(
+            c.rotation.to_mat4(),
+            Mat4::from_axis_angle(Vec3::new(0.0, 1.0, 1.0), 0.7),
+        );
+        assert_mat_eq(c.scale, Mat4::from_scale(Vec3::new(2.0, 3.0, 4.0)));
+    }
+
+    #[test]
+    fn interpolate() {
+        let axis = Vec3::new(0.0, 0.0, 1.0);
+
+        let start = Mat4::from_scale(Vec3::new(1.0, 1.0, 1.0));
+        let end = Mat4::from_translation(Vec3::new(0.0, 0.0, 4.0))
+            * Mat4::from_axis_angle(axis, float(std::f32::consts::FRAC_PI_2))
+            * Mat4::from_scale(Vec3::new(3.0, 3.0, 3.0));
+
+        let animated = AnimatedTransform::new(start, 1.0, end, 3.0);
+        assert!(animated.is_animated());
+
+        assert_eq!(animated.interpolate(0.0), start);
+        assert_eq!(animated.interpolate(5.0), end);
+
+        assert_mat_eq(
+            animated.interpolate(2.0),
+            Mat4::from_translation(Vec3::new(0.0, 0.0, 2.0))
+                * Mat4::from_axis_angle(axis, float(std::f32::consts::FRAC_PI_4))
+                * Mat4::from_scale(Vec3::new(2.0, 2.0, 2.0)),
+        );
+
+        let fixed = AnimatedTransform::fixed(end);
+        assert!(!fixed.is_animated());
+        assert_eq!(fixed.interpolate(0.5), end);
+    }
+}
@@ -0,0 +1,193 @@
+//! Command line companion of the `pbrt4` crate.
+//!
+//! Usage:
+//! `❯ cargo run --features cli -- info ./assets/kroken/camera-1.pbrt`
+
+use std::{env, fs, path::Path, process::ExitCode};
+
+use pbrt4::{
+    format::{format, FormatOptions},
+    validate::Severity,
+    DependencyKind, LoadOptions, Result, Scene,
+};
+
+const USAGE: &str = "\
+Usage: pbrt4 <command> [options] <file>
+
+Commands:
+    info       Print statistics of a scene
+    validate   Check a scene for problems, fails if errors are found
+    deps       List files referenced by a scene
+    fmt        Pretty-print a scene file
+
+Options:
+    --missing       deps: list only files that don't exist, fails if there are any
+    --write         fmt: rewrite the file in place instead of printing it
+    --indent <N>    fmt: number of spaces per nesting level (default 4)
+    --width <N>     fmt: maximum line width (default 100)
+    --no-comments   fmt: drop comments
+";
+
+/// Parsed command line arguments.
+#[derive(Default)]
+struct Args {
+    command: String,
+    path: String,
+    missing: bool,
+    write: bool,
+    format: FormatOptions,
+}
+
+fn parse_args() -> std::result::Result<Args, String> {
+    let mut args = Args::default();
+    let mut positional = Vec::new();
+
+    let mut iter = env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut number = |name: &str| {
+            iter.next()
+                .and_then(|value| value.parse::<usize>().ok())
+                .ok_or_else(|| format!("{} expects a number", name))
+        };
+
+        match arg.as_str() {
+            "--missing" => args.missing = true,
+            "--write" => args.write = true,
+            "--indent" => args.format.indent = number("--indent")?,
+            "--width" => args.format.max_width = number("--width")?,
+            "--no-comments" => args.format.keep_comments = false,
+            "-h" | "--help" => return Err(String::new()),
+            _ if arg.starts_with('-') => return Err(format!("Unknown option: {}", arg)),
+            _ => positional.push(arg),
+        }
+    }
+
+    let [command, path]: [String; 2] = positional
+        .try_into()
+        .map_err(|_| "Expected a command and a file".to_string())?;
+
+    args.command = command;
+    args.path = path;
+    Ok(args)
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(message) => {
+            if !message.is_empty() {
+                eprintln!("{}\n", message);
+            }
+            eprint!("{}", USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    let result = match args.command.as_str() {
+        "info" => info(&args),
+        "validate" => validate(&args),
+        "deps" => deps(&args),
+        "fmt" => fmt(&args),
+        command => {
+            eprint!("Unknown command: {}\n\n{}", command, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(err) => {
+            eprintln!("{}: {}", args.path, err);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Load a scene, reporting unresolved references as issues rather than failing.
+fn load(path: &str) -> Result<Scene> {
+    let options = LoadOptions {
+        lenient: true,
+        ..Default::default()
+    };
+
+    Scene::from_file_with_options(path, &options)
+}
+
+fn info(args: &Args) -> Result<bool> {
+    let scene = load(&args.path)?;
+
+    println!("{}", scene.summary());
+
+    let instancing = scene.instancing_report();
+    if !instancing.objects.is_empty() {
+        println!("{}", instancing);
+    }
+
+    Ok(true)
+}
+
+fn validate(args: &Args) -> Result<bool> {
+    let scene = load(&args.path)?;
+    let issues = scene.validate();
+
+    for issue in &issues {
+        println!("{}", issue);
+    }
+
+    let errors = issues
+        .iter()
+        .filter(|issue| issue.severity == Severity::Error)
+        .count();
+    eprintln!("{} error(s), {} warning(s)", errors, issues.len() - errors);
+
+    Ok(errors == 0)
+}
+
+fn deps(args: &Args) -> Result<bool> {
+    let scene = load(&args.path)?;
+
+    let mut ok = true;
+    for dependency in &scene.dependencies {
+        let exists = dependency.path.exists();
+        if args.missing && exists {
+            continue;
+        }
+
+        ok &= exists;
+        println!(
+            "{:<8} {}",
+            kind_name(dependency.kind),
+            dependency.path.display()
+        );
+    }
+
+    Ok(!args.missing || ok)
+}
+
+fn fmt(args: &Args) -> Result<bool> {
+    let src = fs::read_to_string(&args.path)?;
+    let formatted = format(&src, &args.format)?;
+
+    if args.write {
+        if formatted != src {
+            fs::write(Path::new(&args.path), formatted)?;
+        }
+    } else {
+        print!("{}", formatted);
+    }
+
+    Ok(true)
+}
+
+fn kind_name(kind: DependencyKind) -> &'static str {
+    match kind {
+        DependencyKind::Include => "include",
+        DependencyKind::Import => "import",
+        DependencyKind::Mesh => "mesh",
+        DependencyKind::Image => "image",
+        DependencyKind::Spectrum => "spectrum",
+        DependencyKind::Volume => "volume",
+        DependencyKind::LensFile => "lens",
+    }
+}
@@ -0,0 +1,602 @@
+//! Programmatic scene construction.
+
+use std::collections::HashMap;
+
+use crate::{
+    math::{Float, Mat4, Vec3},
+    param::TexRef,
+    types::{
+        Accelerator, AreaLight, Camera, ColorSpace, Film, Filter, Integrator, Light, Material,
+        MaterialType, Options, Sampler, Shape, Texture,
+    },
+    CameraEntity, Error, InstanceEntity, LightEntity, Object, Result, Scene, ShapeEntity,
+};
+
+/// Graphics state saved and restored by [SceneBuilder::attribute_begin] / [SceneBuilder::attribute_end].
+#[derive(Debug, Default, Clone)]
+struct State {
+    transform: Mat4,
+    reverse_orientation: bool,
+    color_space: ColorSpace,
+    material_index: Option<usize>,
+    area_light_index: Option<usize>,
+}
+
+/// Builds a [Scene] in code, following the semantics of the scene file directives.
+///
+/// The builder keeps a graphics state like the loader: transformation methods modify the
+/// current transformation matrix (CTM), and entities pick up the CTM, the current material,
+/// area light and color space when they're added. Write the result with [Scene::write].
+///
+/// Transformations are not animated, the end of shutter interval transformations are
+/// the same as the start ones.
+///
+/// ```
+/// # use pbrt4::{MaterialBuilder, SceneBuilder, ShapeBuilder, WriteOptions};
+/// # use pbrt4::{math::Vec3, param::{ParamList, TexRef, Spectrum}, types::*};
+/// let mut builder = SceneBuilder::new();
+///
+/// builder
+///     .look_at(Vec3::new(0.0, 0.0, -5.0), Vec3::ZERO, Vec3::new(0.0, 1.0, 0.0))
+///     .camera(Camera::new("perspective", ParamList::default())?);
+///
+/// let red = builder.material(MaterialBuilder::new(MaterialType::Diffuse {
+///     reflectance: TexRef::Value(Spectrum::Rgb([0.8, 0.1, 0.1])),
+/// }));
+///
+/// builder.attribute_begin().translate(Vec3::new(0.0, 1.0, 0.0));
+/// builder.shape(Shape::new("sphere", ParamList::default())?);
+/// builder.attribute_end()?;
+///
+/// let scene = builder.build();
+/// assert_eq!(scene.shapes[0].material_index, Some(red));
+///
+/// let text = scene.to_pbrt_string(&WriteOptions::default())?;
+/// # Ok::<(), pbrt4::Error>(())
+/// ```
+#[derive(Default)]
+pub struct SceneBuilder {
+    scene: Scene,
+    state: State,
+    states_stack: Vec<State>,
+    /// Object being defined between `object_begin` and `object_end`.
+    active_object: Option<Object>,
+    named_materials: HashMap<String, usize>,
+}
+
+impl SceneBuilder {
+    pub fn new() -> Self {
+        SceneBuilder {
+            scene: Scene {
+                end_time: 1.0,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    }
+
+    /// Finish building.
+    ///
+    /// An object which is not ended with [SceneBuilder::object_end] is finished as is.
+    pub fn build(mut self) -> Scene {
+        if let Some(object) = self.active_object.take() {
+            self.scene.objects.push(object);
+        }
+
+        self.scene
+    }
+
+    /// Save the graphics state, like `AttributeBegin`.
+    pub fn attribute_begin(&mut self) -> &mut Self {
+        self.states_stack.push(self.state.clone());
+        self
+    }
+
+    /// Restore the graphics state saved by the matching [SceneBuilder::attribute_begin].
+    pub fn attribute_end(&mut self) -> Result<&mut Self> {
+        self.state = self.states_stack.pop().ok_or(Error::TooManyEndAttributes)?;
+        Ok(self)
+    }
+
+    /// Flip the orientation of subsequent shapes, like `ReverseOrientation`.
+    pub fn reverse_orientation(&mut self) -> &mut Self {
+        self.state.reverse_orientation = !self.state.reverse_orientation;
+        self
+    }
+
+    /// Reset the CTM to identity.
+    pub fn identity(&mut self) -> &mut Self {
+        self.state.transform = Mat4::IDENTITY;
+        self
+    }
+
+    /// Reset the CTM to `m`, like `Transform`.
+    pub fn transform(&mut self, m: Mat4) -> &mut Self {
+        self.state.transform = m;
+        self
+    }
+
+    /// Multiply the CTM with `m`, like `ConcatTransform`.
+    pub fn concat_transform(&mut self, m: Mat4) -> &mut Self {
+        self.state.transform *= m;
+        self
+    }
+
+    pub fn translate(&mut self, v: Vec3) -> &mut Self {
+        self.concat_transform(Mat4::from_translation(v))
+    }
+
+    pub fn scale(&mut self, v: Vec3) -> &mut Self {
+        self.concat_transform(Mat4::from_scale(v))
+    }
+
+    /// Rotate by `angle` degrees around `axis`, like `Rotate`.
+    pub fn rotate(&mut self, angle: Float, axis: Vec3) -> &mut Self {
+        self.concat_transform(Mat4::from_axis_angle(axis, angle.to_radians()))
+    }
+
+    /// Multiply the CTM with a viewing transformation, like `LookAt`.
+    pub fn look_at(&mut self, eye: Vec3, look_at: Vec3, up: Vec3) -> &mut Self {
+        self.concat_transform(Mat4::look_at_lh(eye, look_at, up))
+    }
+
+    /// Color space of RGB values of subsequently added entities, like `ColorSpace`.
+    pub fn color_space(&mut self, color_space: ColorSpace) -> &mut Self {
+        self.state.color_space = color_space;
+        self
+    }
+
+    pub fn options(&mut self, options: Options) -> &mut Self {
+        self.scene.options = options;
+        self
+    }
+
+    /// Set the camera, the CTM defines the world to camera transformation.
+    pub fn camera(&mut self, camera: Camera) -> &mut Self {
+        let transform = self.state.transform.inverse();
+
+        self.scene.camera = Some(CameraEntity {
+            params: camera,
+            transform,
+            transform_end: transform,
+            exterior_medium_index: None,
+        });
+        self
+    }
+
+    pub fn film(&mut self, film: Film) -> &mut Self {
+        self.scene.film = Some(film);
+        self
+    }
+
+    pub fn sampler(&mut self, sampler: Sampler) -> &mut Self {
+        self.scene.sampler = Some(sampler);
+        self
+    }
+
+    pub fn filter(&mut self, filter: Filter) -> &mut Self {
+        self.scene.filter = Some(filter);
+        self
+    }
+
+    pub fn integrator(&mut self, integrator: Integrator) -> &mut Self {
+        self.scene.integrator = Some(integrator);
+        self
+    }
+
+    pub fn accelerator(&mut self, accelerator: Accelerator) -> &mut Self {
+        self.scene.accelerator = Some(accelerator);
+        self
+    }
+
+    /// Add a named texture, returns its index to refer to with [TexRef::Texture].
+    pub fn texture(&mut self, mut texture: Texture) -> usize {
+        texture.color_space = self.state.color_space;
+
+        self.scene.textures.push(texture);
+        self.scene.textures.len() - 1
+    }
+
+    /// Add a material and make it the current one, like `Material`.
+    ///
+    /// Returns the index of the material in [Scene::materials].
+    pub fn material(&mut self, material: impl Into<Material>) -> usize {
+        let index = self.add_material(material.into());
+        self.state.material_index = Some(index);
+        index
+    }
+
+    /// Add a named material, like `MakeNamedMaterial`. The current material doesn't change.
+    pub fn make_named_material(&mut self, name: &str, material: impl Into<Material>) -> usize {
+        let mut material = material.into();
+        material.name = Some(name.to_string());
+
+        let index = self.add_material(material);
+        self.named_materials.insert(name.to_string(), index);
+        index
+    }
+
+    /// Make a named material the current one, like `NamedMaterial`.
+    pub fn named_material(&mut self, name: &str) -> Result<&mut Self> {
+        let index = self
+            .named_materials
+            .get(name)
+            .copied()
+            .ok_or_else(|| Error::MaterialNotFound(name.to_string()))?;
+
+        self.state.material_index = Some(index);
+        Ok(self)
+    }
+
+    fn add_material(&mut self, mut material: Material) -> usize {
+        material.color_space = self.state.color_space;
+
+        self.scene.materials.push(material);
+        self.scene.materials.len() - 1
+    }
+
+    /// Add a light source at the CTM.
+    pub fn light_source(&mut self, light: Light) -> usize {
+        self.scene.lights.push(LightEntity {
+            params: light,
+            transform: self.state.transform,
+            transform_end: self.state.transform,
+            color_space: self.state.color_space,
+            exterior_medium_index: None,
+        });
+        self.scene.lights.len() - 1
+    }
+
+    /// Make subsequent shapes emit light, like `AreaLightSource`.
+    pub fn area_light_source(&mut self, area_light: AreaLight) -> usize {
+        self.scene.area_lights.push(area_light);
+
+        let index = self.scene.area_lights.len() - 1;
+        self.state.area_light_index = Some(index);
+        index
+    }
+
+    /// Add a shape with the current graphics state, returns its index in [Scene::shapes].
+    pub fn shape(&mut self, shape: impl Into<ShapeBuilder>) -> usize {
+        let ShapeBuilder {
+            shape,
+            transform,
+            material_index,
+            area_light_index,
+            reverse_orientation,
+        } = shape.into();
+
+        let transform = self.state.transform * transform;
+        let object_index = self.active_object.as_mut().map(|object| {
+            object.shape_start.get_or_insert(self.scene.shapes.len());
+            object.shape_count += 1;
+            self.scene.objects.len()
+        });
+
+        self.scene.shapes.push(ShapeEntity {
+            params: shape,
+            transform,
+            transform_end: transform,
+            reverse_orientation: self.state.reverse_orientation != reverse_orientation,
+            material_index: material_index.or(self.state.material_index),
+            area_light_index: area_light_index.or(self.state.area_light_index),
+            object_index,
+            interior_medium_index: None,
+            exterior_medium_index: None,
+            bounds: None,
+        });
+        self.scene.shapes.len() - 1
+    }
+
+    /// Start defining an object, like `ObjectBegin`.
+    ///
+    /// Subsequent shapes are added to the object until [SceneBuilder::object_end].
+    pub fn object_begin(&mut self, name: &str) -> Result<&mut Self> {
+        if self.active_object.is_some() {
+            return Err(Error::NestedObjects);
+        }
+
+        self.attribute_begin();
+        self.active_object = Some(Object {
+            name: name.to_string(),
+            shape_start: None,
+            shape_count: 0,
+            object_to_instance: self.state.transform,
+        });
+        Ok(self)
+    }
+
+    pub fn object_end(&mut self) -> Result<&mut Self> {
+        let object = self.active_object.take().ok_or(Error::ElementNotAllowed)?;
+        self.scene.objects.push(object);
+
+        self.attribute_end()
+    }
+
+    /// Instance a previously defined object at the CTM, like `ObjectInstance`.
+    pub fn object_instance(&mut self, name: &str) -> Result<&mut Self> {
+        // Instances can't be used inside of object definitions.
+        if self.active_object.is_some() {
+            return Err(Error::ElementNotAllowed);
+        }
+
+        let (object_index, _) = self.scene.object_by_name(name).ok_or(Error::NotFound)?;
+
+        self.scene.instances.push(InstanceEntity {
+            instance_to_world: self.state.transform,
+            instance_to_world_end: self.state.transform,
+            object_index,
+            area_light_index: self.state.area_light_index,
+            reverse_orientation: self.state.reverse_orientation,
+        });
+        Ok(self)
+    }
+}
+
+/// A shape to add with [SceneBuilder::shape], with optional overrides of the graphics state.
+#[derive(Debug)]
+pub struct ShapeBuilder {
+    shape: Shape,
+    transform: Mat4,
+    material_index: Option<usize>,
+    area_light_index: Option<usize>,
+    reverse_orientation: bool,
+}
+
+impl ShapeBuilder {
+    pub fn new(shape: Shape) -> Self {
+        ShapeBuilder {
+            shape,
+            transform: Mat4::IDENTITY,
+            material_index: None,
+            area_light_index: None,
+            reverse_orientation: false,
+        }
+    }
+
+    /// Object to CTM transformation of the shape.
+    pub fn transform(mut self, transform: Mat4) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    /// Use the material at `index` instead of the current one.
+    pub fn material(mut self, index: usize) -> Self {
+        self.material_index = Some(index);
+        self
+    }
+
+    /// Use the area light at `index` instead of the current one.
+    pub fn area_light(mut self, index: usize) -> Self {
+        self.area_light_index = Some(index);
+        self
+    }
+
+    /// Flip the orientation relative to the current reverse-orientation setting.
+    pub fn reverse_orientation(mut self) -> Self {
+        self.reverse_orientation = !self.reverse_orientation;
+        self
+    }
+}
+
+impl From<Shape> for ShapeBuilder {
+    fn from(shape: Shape) -> Self {
+        ShapeBuilder::new(shape)
+    }
+}
+
+/// A material to add with [SceneBuilder::material] or [SceneBuilder::make_named_material].
+#[derive(Debug)]
+pub struct MaterialBuilder {
+    material: Material,
+}
+
+impl MaterialBuilder {
+    pub fn new(ty: MaterialType) -> Self {
+        MaterialBuilder {
+            material: Material {
+                ty,
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Float texture used to displace the surface.
+    pub fn displacement(mut self, displacement: TexRef<f32>) -> Self {
+        self.material.displacement = Some(displacement);
+        self
+    }
+
+    /// Image file with a tangent space normal map.
+    pub fn normal_map(mut self, filename: &str) -> Self {
+        self.material.normal_map = Some(filename.to_string());
+        self
+    }
+
+    pub fn build(self) -> Material {
+        self.material
+    }
+}
+
+impl From<MaterialBuilder> for Material {
+    fn from(builder: MaterialBuilder) -> Self {
+        builder.build()
+    }
+}
+
+impl From<MaterialType> for Material {
+    fn from(ty: MaterialType) -> Self {
+        MaterialBuilder::new(ty).build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{param::ParamList, WriteOptions};
+
+    fn sphere() -> Shape {
+        Shape::new("sphere", ParamList::default()).unwrap()
+    }
+
+    #[test]
+    fn graphics_state() -> Result<()> {
+        let mut builder = SceneBuilder::new();
+
+        let matte = builder.material(MaterialType::default());
+        let gold = builder.make_named_material("gold", MaterialType::default());
+        builder.area_light_source(AreaLight::new("diffuse", ParamList::default())?);
+
+        builder
+            .attribute_begin()
+            .translate(Vec3::new(1.0, 2.0, 3.0))
+            .reverse_orientation()
+            .named_material("gold")?;
+        builder.shape(sphere());
+        builder.attribute_end()?;
+
+        builder.shape(
+            ShapeBuilder::new(sphere())
+                .material(gold)
+                .reverse_orientation(),
+        );
+        assert!(matches!(
+            builder.attribute_end(),
+            Err(Error::TooManyEndAttributes)
+        ));
+        assert!(matches!(
+            builder.named_material("silver"),
+            Err(Error::MaterialNotFound(_))
+        ));
+
+        let scene = builder.build();
+
+        let first = &scene.shapes[0];
+        assert_eq!(
+            first.transform,
+            Mat4::from_translation(Vec3::new(1.0, 2.0, 3.0))
+        );
+        assert!(first.reverse_orientation);
+        assert_eq!(first.material_index, Some(gold));
+        assert_eq!(first.area_light_index, Some(0));
+
+        let second = &scene.shapes[1];
+        assert_eq!(second.transform, Mat4::IDENTITY);
+        assert!(second.reverse_orientation);
+        assert_eq!(second.material_index, Some(gold));
+        assert_ne!(second.material_index, Some(matte));
+
+        Ok(())
+    }
+
+    #[test]
+    fn transforms() {
+        let mut builder = SceneBuilder::new();
+
+        builder.rotate(90.0, Vec3::new(0.0, 0.0, 1.0));
+        builder.shape(sphere());
+
+        builder
+            .identity()
+            .scale(Vec3::new(2.0, 2.0, 2.0))
+            .concat_transform(Mat4::from_translation(Vec3::ONE));
+        builder.shape(ShapeBuilder::new(sphere()).transform(Mat4::from_scale(Vec3::ONE * 0.5)));
+
+        let scene = builder.build();
+
+        let p = scene.shapes[0]
+            .transform
+            .transform_point3(Vec3::new(1.0, 0.0, 0.0));
+        assert!((p - Vec3::new(0.0, 1.0, 0.0)).length() < 1e-6);
+
+        let p = scene.shapes[1].transform.transform_point3(Vec3::ZERO);
+        assert_eq!(p, Vec3::new(2.0, 2.0, 2.0));
+        let p = scene.shapes[1].transform.transform_point3(Vec3::ONE);
+        assert_eq!(p, Vec3::new(3.0, 3.0, 3.0));
+    }
+
+    #[test]
+    fn objects() -> Result<()> {
+        let mut builder = SceneBuilder::new();
+
+        builder.shape(sphere());
+        builder.object_begin("pair")?;
+        assert!(matches!(
+            builder.object_begin("nested"),
+            Err(Error::NestedObjects)
+        ));
+        builder.shape(sphere());
+        builder.shape(sphere());
+        builder.object_end()?;
+
+        builder
+            .translate(Vec3::new(0.0, 0.0, 5.0))
+            .object_instance("pair")?;
+        assert!(matches!(
+            builder.object_instance("missing"),
+            Err(Error::NotFound)
+        ));
+        assert!(matches!(
+            builder.object_end(),
+            Err(Error::ElementNotAllowed)
+        ));
+
+        let scene = builder.build();
+        assert_eq!(scene.objects[0].shape_range(), Some(1..3));
+        assert_eq!(scene.object_shapes(0).len(), 2);
+        assert_eq!(scene.shapes[0].object_index, None);
+        assert_eq!(scene.shapes[2].object_index, Some(0));
+        assert_eq!(scene.instances[0].object_index, 0);
+        assert_eq!(
+            scene.instances[0].instance_to_world,
+            Mat4::from_translation(Vec3::new(0.0, 0.0, 5.0))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn write() -> Result<()> {
+        let mut builder = SceneBuilder::new();
+
+        builder
+            .look_at(
+                Vec3::new(0.0, 0.0, -5.0),
+                Vec3::ZERO,
+                Vec3::new(0.0, 1.0, 0.0),
+            )
+            .camera(Camera::new("perspective", ParamList::default())?)
+            .identity();
+
+        let bumps = builder.texture(Texture::new(
+            "bumps",
+            "float",
+            "windy",
+            ParamList::default(),
+            &HashMap::new(),
+        )?);
+        builder.color_space(ColorSpace::Rec2020);
+        builder.material(
+            MaterialBuilder::new(MaterialType::default())
+                .displacement(TexRef::Texture(bumps))
+                .normal_map("normals.png"),
+        );
+        builder.light_source(Light::Distant);
+        builder.shape(sphere());
+
+        let scene = builder.build();
+        let reloaded = Scene::load(&scene.to_pbrt_string(&WriteOptions::default())?, None)?;
+
+        let camera = reloaded.camera.as_ref().unwrap();
+        let p = camera.transform.transform_point3(Vec3::ZERO);
+        assert!((p - Vec3::new(0.0, 0.0, -5.0)).length() < 1e-5);
+
+        assert_eq!(reloaded.textures[0].name, "bumps");
+        assert_eq!(reloaded.materials[0].displacement, Some(TexRef::Texture(0)));
+        assert_eq!(reloaded.materials[0].color_space, ColorSpace::Rec2020);
+        assert_eq!(reloaded.lights[0].color_space, ColorSpace::Rec2020);
+        assert_eq!(reloaded.shapes[0].material_index, Some(0));
+
+        Ok(())
+    }
+}
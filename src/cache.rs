@@ -0,0 +1,203 @@
+//! Geometry cache shared between scene loads.
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::SystemTime,
+};
+
+#[cfg(feature = "nanovdb")]
+use crate::nanovdb::NanoVdbFile;
+#[cfg(feature = "ply")]
+use crate::ply::PlyMesh;
+use crate::Result;
+
+/// Identifies a particular version of a file on disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct FileStamp {
+    modified: Option<SystemTime>,
+    len: u64,
+}
+
+impl FileStamp {
+    fn new(path: &Path) -> Result<Self> {
+        let metadata = fs::metadata(path)?;
+
+        Ok(FileStamp {
+            modified: metadata.modified().ok(),
+            len: metadata.len(),
+        })
+    }
+}
+
+/// Parsed files along with the version they were parsed from, keyed by canonical path.
+type Entries<T> = Mutex<HashMap<PathBuf, (FileStamp, Arc<T>)>>;
+
+/// Get a parsed file from `entries`, parsing it with `read` if it's not cached yet
+/// or if the file has changed since it was cached.
+fn get_or_read<T>(
+    entries: &Entries<T>,
+    path: &Path,
+    read: impl FnOnce(&Path) -> Result<T>,
+) -> Result<Arc<T>> {
+    let path = fs::canonicalize(path)?;
+    let stamp = FileStamp::new(&path)?;
+
+    if let Some((cached_stamp, value)) = entries.lock().unwrap().get(&path) {
+        if *cached_stamp == stamp {
+            return Ok(Arc::clone(value));
+        }
+    }
+
+    // Parse without holding the lock, so other threads can use the cache meanwhile.
+    let value = Arc::new(read(&path)?);
+
+    entries
+        .lock()
+        .unwrap()
+        .insert(path, (stamp, Arc::clone(&value)));
+
+    Ok(value)
+}
+
+/// A thread-safe cache of parsed geometry files.
+///
+/// Scene variants (wedges, turntables, etc) often reference the same heavy geometry.
+/// Sharing a cache between loads (via [crate::LoadOptions::geometry_cache]) makes sure
+/// each file is parsed only once.
+///
+/// Entries are keyed by the canonical file path and invalidated when the file's
+/// modification time or size changes.
+///
+/// PLY meshes are cached with the `ply` feature, NanoVDB grid metadata with the `nanovdb` feature.
+#[derive(Debug, Default)]
+pub struct GeometryCache {
+    #[cfg(feature = "ply")]
+    meshes: Entries<PlyMesh>,
+    #[cfg(feature = "nanovdb")]
+    grids: Entries<NanoVdbFile>,
+}
+
+impl GeometryCache {
+    /// Create an empty cache.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get a PLY mesh from the cache, loading it from disk if it's not cached yet
+    /// or if the file has changed since it was cached.
+    #[cfg(feature = "ply")]
+    pub fn ply<P: AsRef<Path>>(&self, path: P) -> Result<Arc<PlyMesh>> {
+        get_or_read(&self.meshes, path.as_ref(), |path| PlyMesh::from_file(path))
+    }
+
+    /// Get the grid metadata of a NanoVDB file from the cache, see [GeometryCache::ply].
+    #[cfg(feature = "nanovdb")]
+    pub fn nanovdb<P: AsRef<Path>>(&self, path: P) -> Result<Arc<NanoVdbFile>> {
+        get_or_read(&self.grids, path.as_ref(), |path| {
+            NanoVdbFile::from_file(path)
+        })
+    }
+
+    /// Returns the number of cached files.
+    pub fn len(&self) -> usize {
+        #[allow(unused_mut)]
+        let mut len = 0;
+        #[cfg(feature = "ply")]
+        {
+            len += self.meshes.lock().unwrap().len();
+        }
+        #[cfg(feature = "nanovdb")]
+        {
+            len += self.grids.lock().unwrap().len();
+        }
+        len
+    }
+
+    /// Returns `true` if the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Remove all cached entries.
+    pub fn clear(&self) {
+        #[cfg(feature = "ply")]
+        self.meshes.lock().unwrap().clear();
+        #[cfg(feature = "nanovdb")]
+        self.grids.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tempdir::TempDir;
+
+    #[cfg(feature = "ply")]
+    const TRIANGLE: &str = "ply
+format ascii 1.0
+element vertex 3
+property float x
+property float y
+property float z
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0
+1 0 0
+0 1 0
+3 0 1 2
+";
+
+    #[cfg(feature = "ply")]
+    #[test]
+    fn reuse_meshes() -> Result<()> {
+        let temp_dir = TempDir::new("pbrt-cache-")?;
+        let path = temp_dir.path().join("mesh.ply");
+        fs::write(&path, TRIANGLE)?;
+
+        let cache = GeometryCache::new();
+
+        let first = cache.ply(&path)?;
+        let second = cache.ply(&path)?;
+
+        assert!(Arc::ptr_eq(&first, &second));
+        assert_eq!(cache.len(), 1);
+
+        // Modified file should be reloaded.
+        fs::write(&path, TRIANGLE.replace("1 0 0\n", "10 0 0\n"))?;
+
+        let third = cache.ply(&path)?;
+        assert_eq!(third.positions[3], 10.0);
+        assert_eq!(cache.len(), 1);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "nanovdb")]
+    #[test]
+    fn reuse_grids() -> Result<()> {
+        use crate::nanovdb::tests::write_file;
+
+        let temp_dir = TempDir::new("pbrt-cache-")?;
+        let path = temp_dir.path().join("smoke.nvdb");
+        fs::write(&path, write_file(&[("density", 1)]))?;
+
+        let cache = GeometryCache::new();
+
+        let first = cache.nanovdb(&path)?;
+        let second = cache.nanovdb(&path)?;
+
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(first.grid("density").is_some());
+        assert_eq!(cache.len(), 1);
+
+        cache.clear();
+        assert!(cache.is_empty());
+
+        Ok(())
+    }
+}
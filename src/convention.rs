@@ -0,0 +1,395 @@
+//! Conversion between coordinate system conventions.
+
+#[cfg(feature = "ply")]
+use std::sync::Arc;
+
+use crate::{
+    math::{float, narrow, Float, Mat4, Vec3},
+    types::Shape,
+    Scene,
+};
+
+/// Handedness of a coordinate system.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Handedness {
+    /// pbrt, Unity, DirectX.
+    #[default]
+    Left,
+    /// OpenGL, glTF, Blender, USD.
+    Right,
+}
+
+/// Axis pointing up.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum UpAxis {
+    #[default]
+    Y,
+    Z,
+}
+
+/// World space coordinate system convention, see [Scene::convert_coordinates].
+///
+/// In all conventions +x points right. With +y up, +z points away from the viewer in
+/// left-handed and towards the viewer in right-handed conventions. With +z up, +y points
+/// away from the viewer in right-handed and towards the viewer in left-handed conventions.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Convention {
+    pub handedness: Handedness,
+    pub up: UpAxis,
+}
+
+impl Convention {
+    /// Left-handed, +y up: pbrt's camera space convention, assumed for scenes by
+    /// [Scene::convert_coordinates].
+    pub const PBRT: Convention = Convention {
+        handedness: Handedness::Left,
+        up: UpAxis::Y,
+    };
+
+    /// Right-handed, +y up (glTF, OpenGL).
+    pub const Y_UP_RIGHT_HANDED: Convention = Convention {
+        handedness: Handedness::Right,
+        up: UpAxis::Y,
+    };
+
+    /// Right-handed, +z up (Blender).
+    pub const Z_UP_RIGHT_HANDED: Convention = Convention {
+        handedness: Handedness::Right,
+        up: UpAxis::Z,
+    };
+
+    /// Change of basis from coordinates in `self` to coordinates in `to`.
+    pub fn conversion_to(&self, to: &Convention) -> Mat4 {
+        to.basis().transpose() * self.basis()
+    }
+
+    /// Maps coordinates in the convention to (right, up, away from the viewer) coordinates.
+    fn basis(&self) -> Mat4 {
+        // Columns are the directions of the x, y and z axes.
+        let [x, y, z]: [[Float; 3]; 3] = match (self.up, self.handedness) {
+            (UpAxis::Y, Handedness::Left) => [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]],
+            (UpAxis::Y, Handedness::Right) => [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, -1.0]],
+            (UpAxis::Z, Handedness::Left) => [[1.0, 0.0, 0.0], [0.0, 0.0, -1.0], [0.0, 1.0, 0.0]],
+            (UpAxis::Z, Handedness::Right) => [[1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.0, 1.0, 0.0]],
+        };
+
+        Mat4::from_cols_array(&[
+            x[0], x[1], x[2], 0.0, y[0], y[1], y[2], 0.0, z[0], z[1], z[2], 0.0, 0.0, 0.0, 0.0, 1.0,
+        ])
+    }
+
+    /// Maps pbrt's camera space (+z forward) to the convention's camera space, which looks
+    /// along -z in right-handed conventions.
+    fn camera_basis(&self) -> Mat4 {
+        match self.handedness {
+            Handedness::Left => Mat4::IDENTITY,
+            Handedness::Right => Mat4::from_scale(Vec3::new(1.0, 1.0, -1.0)),
+        }
+    }
+}
+
+impl Scene {
+    /// Convert a scene from pbrt's convention ([Convention::PBRT]) to `convention`,
+    /// see [Scene::convert_coordinates_from].
+    pub fn convert_coordinates(&mut self, convention: Convention) {
+        self.convert_coordinates_from(Convention::PBRT, convention)
+    }
+
+    /// Rewrite the scene authored in the `from` convention to the `to` convention,
+    /// e.g. before importing it to an engine.
+    ///
+    /// World and instance space transformations are changed to the new basis. Mesh data
+    /// (positions, normals and tangents of triangle meshes, bilinear meshes, subdivision
+    /// surfaces, curves and loaded PLY meshes) is converted to the new basis as well, so their
+    /// transformations keep the same rotation and scale, and triangles are re-wound if
+    /// handedness changes so they keep facing the same way.
+    ///
+    /// Object spaces of analytic shapes (spheres, disks and cylinders are defined around +z),
+    /// light, texture and medium spaces can't be converted, their transformations are only multiplied
+    /// with the change of basis.
+    ///
+    /// The camera to world transformation maps the camera space of `to`: the camera looks
+    /// along +z in left-handed and along -z in right-handed conventions, with +y up.
+    pub fn convert_coordinates_from(&mut self, from: Convention, to: Convention) {
+        if from == to {
+            return;
+        }
+
+        let basis = from.conversion_to(&to);
+        let inverse = basis.transpose();
+        let flip = basis.determinant() < 0.0;
+
+        // Both sides of the transformation use the new basis.
+        let convert = |m: &mut Mat4| *m = basis * *m * inverse;
+
+        if let Some(camera) = &mut self.camera {
+            let camera_basis = from.camera_basis() * to.camera_basis();
+
+            camera.transform = basis * camera.transform * camera_basis;
+            camera.transform_end = basis * camera.transform_end * camera_basis;
+        }
+
+        for light in &mut self.lights {
+            light.transform = basis * light.transform;
+            light.transform_end = basis * light.transform_end;
+        }
+
+        for texture in &mut self.textures {
+            texture.transform = basis * texture.transform;
+        }
+
+        for medium in &mut self.mediums {
+            medium.transform = basis * medium.transform;
+        }
+
+        for shape in &mut self.shapes {
+            if convert_shape(&mut shape.params, &basis, flip) {
+                convert(&mut shape.transform);
+                convert(&mut shape.transform_end);
+            } else {
+                shape.transform = basis * shape.transform;
+                shape.transform_end = basis * shape.transform_end;
+            }
+
+            if shape.bounds.is_some() {
+                shape.bounds = shape
+                    .params
+                    .bounds()
+                    .map(|bounds| bounds.transform(&shape.transform));
+            }
+        }
+
+        for object in &mut self.objects {
+            convert(&mut object.object_to_instance);
+        }
+
+        for instance in &mut self.instances {
+            convert(&mut instance.instance_to_world);
+            convert(&mut instance.instance_to_world_end);
+        }
+    }
+}
+
+/// Convert object space mesh data, returns `false` if the shape's object space can't be converted.
+fn convert_shape(shape: &mut Shape, basis: &Mat4, flip: bool) -> bool {
+    match shape {
+        Shape::TriangleMesh {
+            indices,
+            positions,
+            normals,
+            tangents,
+            ..
+        } => {
+            convert_points(positions, basis);
+            convert_vectors(normals, basis);
+            convert_vectors(tangents, basis);
+
+            if flip {
+                rewind(indices, 3);
+            }
+        }
+        Shape::LoopSubdiv {
+            indices, positions, ..
+        } => {
+            convert_points(positions, basis);
+
+            if flip {
+                rewind(indices, 3);
+            }
+        }
+        Shape::BilinearMesh {
+            indices,
+            positions,
+            normals,
+            ..
+        } => {
+            convert_points(positions, basis);
+            convert_vectors(normals, basis);
+
+            if flip {
+                // A single patch may omit indices.
+                if indices.is_empty() && positions.len() == 12 {
+                    *indices = vec![0, 1, 2, 3];
+                }
+
+                // Swapping p10 and p01 swaps u and v, which flips the surface normal.
+                rewind(indices, 4);
+            }
+        }
+        Shape::Curve {
+            positions, normals, ..
+        } => {
+            convert_points(positions, basis);
+            convert_vectors(normals, basis);
+        }
+        #[cfg(feature = "ply")]
+        Shape::PlyMesh {
+            mesh: Some(mesh), ..
+        } => {
+            let mesh = Arc::make_mut(mesh);
+
+            convert_points(&mut mesh.positions, basis);
+            convert_vectors(&mut mesh.normals, basis);
+
+            if flip {
+                rewind(&mut mesh.indices, 3);
+            }
+        }
+        Shape::Cylinder { .. } | Shape::Disk { .. } | Shape::Sphere { .. } => return false,
+        Shape::PlyMesh { .. } => return false,
+    }
+
+    true
+}
+
+fn convert_points(points: &mut [Float], basis: &Mat4) {
+    for p in points.chunks_exact_mut(3) {
+        let v = basis.transform_point3(Vec3::new(p[0], p[1], p[2]));
+        p.copy_from_slice(&v.to_array());
+    }
+}
+
+/// The change of basis is orthogonal, so normals transform like vectors.
+fn convert_vectors(vectors: &mut [f32], basis: &Mat4) {
+    for v in vectors.chunks_exact_mut(3) {
+        let r = basis.transform_vector3(Vec3::new(float(v[0]), float(v[1]), float(v[2])));
+        v.copy_from_slice(&r.to_array().map(narrow));
+    }
+}
+
+/// Swap the second and the third vertex of each face of `n` vertices.
+fn rewind(indices: &mut [i32], n: usize) {
+    for face in indices.chunks_exact_mut(n) {
+        face.swap(1, 2);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Result;
+
+    fn assert_near(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn conversion() {
+        let m = Convention::PBRT.conversion_to(&Convention::Z_UP_RIGHT_HANDED);
+
+        // Up and away from the viewer.
+        assert_eq!(
+            m.transform_vector3(Vec3::new(0.0, 1.0, 0.0)),
+            Vec3::new(0.0, 0.0, 1.0)
+        );
+        assert_eq!(
+            m.transform_vector3(Vec3::new(0.0, 0.0, 1.0)),
+            Vec3::new(0.0, 1.0, 0.0)
+        );
+        assert!(m.determinant() < 0.0);
+
+        let m = Convention::PBRT.conversion_to(&Convention::Y_UP_RIGHT_HANDED);
+        assert_eq!(m, Mat4::from_scale(Vec3::new(1.0, 1.0, -1.0)));
+
+        let z_up_left = Convention {
+            handedness: Handedness::Left,
+            up: UpAxis::Z,
+        };
+        let m = Convention::Z_UP_RIGHT_HANDED.conversion_to(&z_up_left);
+        assert_eq!(m, Mat4::from_scale(Vec3::new(1.0, -1.0, 1.0)));
+        assert_eq!(z_up_left.conversion_to(&z_up_left), Mat4::IDENTITY);
+    }
+
+    #[test]
+    fn convert_scene() -> Result<()> {
+        let data = r#"
+LookAt 0 2 -5  0 2 0  0 1 0
+Camera "perspective"
+WorldBegin
+LightSource "distant"
+Translate 1 2 3
+Rotate 90 0 1 0
+Shape "trianglemesh" "point3 P" [ 0 0 0  1 0 0  0 1 0 ] "integer indices" [ 0 1 2 ]
+    "normal N" [ 0 0 -1  0 0 -1  0 0 -1 ]
+Shape "sphere"
+"#;
+
+        let original = Scene::load(data, None)?;
+        let mut scene = Scene::load(data, None)?;
+        scene.convert_coordinates(Convention::Z_UP_RIGHT_HANDED);
+
+        let basis = Convention::PBRT.conversion_to(&Convention::Z_UP_RIGHT_HANDED);
+
+        // World space positions are converted.
+        let Shape::TriangleMesh {
+            indices,
+            positions,
+            normals,
+            ..
+        } = &scene.shapes[0].params
+        else {
+            panic!("Unexpected shape type");
+        };
+        assert_eq!(indices, &[0, 2, 1]);
+        assert_eq!(&positions[3..6], &[1.0, 0.0, 0.0]);
+        assert_eq!(&positions[6..9], &[0.0, 0.0, 1.0]);
+        assert_eq!(&normals[0..3], &[0.0, -1.0, 0.0]);
+
+        let world = |shape: &crate::ShapeEntity, p: Vec3| shape.transform.transform_point3(p);
+        assert_near(
+            world(&scene.shapes[0], Vec3::new(0.0, 0.0, 1.0)),
+            basis.transform_point3(world(&original.shapes[0], Vec3::new(0.0, 1.0, 0.0))),
+        );
+
+        // Mesh transforms stay rotations, analytic shapes are mirrored.
+        assert!(scene.shapes[0].transform.determinant() > 0.0);
+        assert!(!scene.shapes[0].decompose().shear);
+        assert!(scene.shapes[1].transform.determinant() < 0.0);
+        assert_near(
+            world(&scene.shapes[1], Vec3::new(0.0, 0.0, 1.0)),
+            basis.transform_point3(world(&original.shapes[1], Vec3::new(0.0, 0.0, 1.0))),
+        );
+
+        // The camera is at z = 2 and looks along -z of its right-handed camera space.
+        let camera = scene.camera.as_ref().unwrap();
+        assert_near(
+            camera.transform.transform_point3(Vec3::ZERO),
+            Vec3::new(0.0, -5.0, 2.0),
+        );
+        assert_near(
+            camera
+                .transform
+                .transform_vector3(Vec3::new(0.0, 0.0, -1.0)),
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+        assert_near(
+            camera.transform.transform_vector3(Vec3::new(0.0, 1.0, 0.0)),
+            Vec3::new(0.0, 0.0, 1.0),
+        );
+        assert!(camera.transform.determinant() > 0.0);
+
+        assert_eq!(scene.lights[0].transform, basis);
+
+        // Converting back restores the scene.
+        scene.convert_coordinates_from(Convention::Z_UP_RIGHT_HANDED, Convention::PBRT);
+        let Shape::TriangleMesh { indices, .. } = &scene.shapes[0].params else {
+            panic!("Unexpected shape type");
+        };
+        assert_eq!(indices, &[0, 1, 2]);
+        assert_near(
+            scene
+                .camera
+                .as_ref()
+                .unwrap()
+                .transform
+                .transform_point3(Vec3::ZERO),
+            Vec3::new(0.0, 2.0, -5.0),
+        );
+        assert_eq!(scene.shapes[1].transform, original.shapes[1].transform);
+
+        Ok(())
+    }
+}
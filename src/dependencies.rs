@@ -0,0 +1,179 @@
+//! External files referenced by scenes.
+
+use std::{
+    env,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    param::{ParamList, ParamType},
+    scene::resolve_path,
+    Element, Result,
+};
+
+/// What an external file is used for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DependencyKind {
+    /// Scene file referenced by `Include`.
+    Include,
+    /// Scene file referenced by `Import`.
+    Import,
+    /// PLY mesh of a `"plymesh"` shape.
+    Mesh,
+    /// Image used by a texture, a light (environment maps, goniometric and projection
+    /// images) or a material's normal map.
+    Image,
+    /// Measured spectral distribution.
+    Spectrum,
+    /// Volume grid of a medium (e.g. NanoVDB).
+    Volume,
+    /// Lens description of the realistic camera.
+    LensFile,
+}
+
+/// An external file referenced by a scene, see [crate::Scene::dependencies].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Dependency {
+    pub kind: DependencyKind,
+    /// Absolute path of the file. The file is not required to exist.
+    pub path: PathBuf,
+}
+
+/// Collect files referenced by an element.
+pub(crate) fn element_dependencies(
+    element: &Element,
+    working_directory: Option<&Path>,
+) -> Result<Vec<Dependency>> {
+    let mut out = Vec::new();
+
+    let mut add = |kind: DependencyKind, path: &str| -> Result<()> {
+        let mut path = resolve_path(working_directory, path)?;
+        if !path.is_absolute() {
+            path = env::current_dir()?.join(path);
+        }
+
+        out.push(Dependency { kind, path });
+        Ok(())
+    };
+
+    // Files referenced by string parameters: (parameter name, kind).
+    let (files, params): (&[(&str, DependencyKind)], Option<&ParamList>) = match element {
+        Element::Include(path) => return add(DependencyKind::Include, path).map(|_| out),
+        Element::Import(path) => return add(DependencyKind::Import, path).map(|_| out),
+        Element::Shape { name, params } if name == "plymesh" => {
+            (&[("filename", DependencyKind::Mesh)], Some(params))
+        }
+        Element::Shape { name, params } if name == "bilinearmesh" => {
+            (&[("emissionfilename", DependencyKind::Image)], Some(params))
+        }
+        Element::Texture { params, .. }
+        | Element::LightSource { params, .. }
+        | Element::AreaLightSource { params, .. } => {
+            (&[("filename", DependencyKind::Image)], Some(params))
+        }
+        Element::Material { params, .. } | Element::MakeNamedMaterial { params, .. } => {
+            (&[("normalmap", DependencyKind::Image)], Some(params))
+        }
+        Element::MakeNamedMedium { params, .. } => {
+            (&[("filename", DependencyKind::Volume)], Some(params))
+        }
+        Element::Camera { params, .. } => (&[("lensfile", DependencyKind::LensFile)], Some(params)),
+        Element::Shape { params, .. }
+        | Element::Film { params, .. }
+        | Element::Sampler { params, .. }
+        | Element::Integrator { params, .. }
+        | Element::Accelerator { params, .. }
+        | Element::PixelFilter { params, .. }
+        | Element::Attribute { params, .. } => (&[], Some(params)),
+        _ => (&[], None),
+    };
+
+    let Some(params) = params else {
+        return Ok(out);
+    };
+
+    for (name, kind) in files {
+        if let Some(param) = params.get(name) {
+            if param.ty == ParamType::String {
+                add(*kind, &param.string())?;
+            }
+        }
+    }
+
+    // Spectrum parameters are either lists of values, names of built-in spectra,
+    // or file names. Built-in names have no extension.
+    for param in params.iter() {
+        if param.ty != ParamType::Spectrum {
+            continue;
+        }
+
+        let value = param.string();
+        if param.value().starts_with('"') && Path::new(&*value).extension().is_some() {
+            add(DependencyKind::Spectrum, &value)?;
+        }
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::Scene;
+
+    #[test]
+    fn dependencies() -> Result<()> {
+        let data = r#"
+Camera "realistic" "string lensfile" "lenses/wide.dat"
+WorldBegin
+Include "lights.pbrt"
+LightSource "infinite" "string filename" "sky.exr"
+Texture "wood" "spectrum" "imagemap" "string filename" "textures/wood.png"
+Material "conductor" "spectrum eta" "metal-Cu-eta" "spectrum k" "spectra/cu-k.spd"
+Material "diffuse" "spectrum reflectance" [ 300 0.3 800 0.6 ] "string normalmap" "normal.png"
+MakeNamedMedium "smoke" "string type" "nanovdb" "string filename" "smoke.nvdb"
+Shape "plymesh" "string filename" "/meshes/car.ply"
+Shape "plymesh" "string filename" "/meshes/car.ply"
+        "#;
+
+        let temp_dir = TempDir::new("pbrt-dependencies-")?;
+        let directory = temp_dir.path();
+
+        fs::write(
+            directory.join("lights.pbrt"),
+            r#"LightSource "goniometric" "string filename" "ies.exr""#,
+        )?;
+
+        let scene = Scene::load(data, Some(directory))?;
+
+        let dependencies = scene
+            .dependencies
+            .iter()
+            .map(|d| (d.kind, d.path.clone()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            dependencies,
+            [
+                (DependencyKind::LensFile, directory.join("lenses/wide.dat")),
+                (DependencyKind::Include, directory.join("lights.pbrt")),
+                (DependencyKind::Image, directory.join("ies.exr")),
+                (DependencyKind::Image, directory.join("sky.exr")),
+                (DependencyKind::Image, directory.join("textures/wood.png")),
+                (DependencyKind::Spectrum, directory.join("spectra/cu-k.spd")),
+                (DependencyKind::Image, directory.join("normal.png")),
+                (DependencyKind::Volume, directory.join("smoke.nvdb")),
+                // Duplicates are removed.
+                (DependencyKind::Mesh, PathBuf::from("/meshes/car.ply")),
+            ]
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,577 @@
+//! Source structure of scene files.
+//!
+//! A [Document] keeps the statements of the main scene file and of all included
+//! (or imported) files, so scenes can be edited and written back without inlining
+//! includes. Enabled via [crate::LoadOptions::keep_ast].
+//!
+//! The model is lossless: each statement keeps the whitespace and comments preceding it,
+//! and its text can be viewed as [SyntaxToken]s with their own trivia. Writing a file back
+//! reproduces the source byte for byte, except for the edited statements.
+
+use std::{
+    collections::HashMap,
+    fs,
+    io::Write,
+    mem,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    scan_directives, scene::resolve_path, tokenizer::Tokenizer, Directive, Element, Error,
+    LoadOptions, Parser, Result, Strictness,
+};
+
+/// A token of a statement along with the whitespace and comments preceding it.
+///
+/// Concatenating the tokens of a statement gives the statement text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SyntaxToken<'a> {
+    /// Whitespace and comments preceding the token, empty for the directive.
+    pub leading: &'a str,
+    /// A directive, a quoted string, a bracket or a bare value.
+    pub text: &'a str,
+}
+
+/// A single directive along with its arguments.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Statement {
+    directive: Directive,
+    /// Whitespace and comments preceding the statement.
+    leading: String,
+    /// Statement text.
+    text: String,
+    /// Index of the included file for `Include` and `Import` statements.
+    file_index: Option<usize>,
+}
+
+impl Statement {
+    pub fn directive(&self) -> Directive {
+        self.directive
+    }
+
+    /// Statement text, from the directive keyword to its last argument.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Whitespace and comments preceding the statement.
+    pub fn leading(&self) -> &str {
+        &self.leading
+    }
+
+    /// Tokens of the statement text, including comments between arguments.
+    pub fn tokens(&self) -> impl Iterator<Item = SyntaxToken<'_>> {
+        let mut tokenizer = Tokenizer::new(&self.text);
+        let mut last = 0;
+
+        std::iter::from_fn(move || {
+            tokenizer.next()?;
+
+            let start = tokenizer.token_start();
+            let end = tokenizer.offset();
+            let token = SyntaxToken {
+                leading: &self.text[last..start],
+                text: &self.text[start..end],
+            };
+
+            last = end;
+            Some(token)
+        })
+    }
+
+    /// Index in [Document::files] of the file referenced by an `Include` or `Import` statement.
+    pub fn file_index(&self) -> Option<usize> {
+        self.file_index
+    }
+}
+
+/// Statements of a single scene file.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SourceFile {
+    /// File path, `None` for the main file.
+    path: Option<PathBuf>,
+    statements: Vec<Statement>,
+    /// Whitespace and comments after the last statement.
+    trailing: String,
+    modified: bool,
+}
+
+impl SourceFile {
+    /// Split a scene file into statements, without resolving includes.
+    ///
+    /// Use [crate::LoadOptions::keep_ast] to get the statements of all files of a scene.
+    pub fn parse(src: &str) -> Self {
+        Self::new(None, src)
+    }
+
+    fn new(path: Option<PathBuf>, src: &str) -> Self {
+        let mut statements = Vec::new();
+        let mut last = 0;
+
+        for (directive, span) in scan_directives(src) {
+            statements.push(Statement {
+                directive,
+                leading: src[last..span.start].to_string(),
+                text: src[span.clone()].to_string(),
+                file_index: None,
+            });
+
+            last = span.end;
+        }
+
+        SourceFile {
+            path,
+            statements,
+            trailing: src[last..].to_string(),
+            modified: false,
+        }
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    pub fn statements(&self) -> &[Statement] {
+        &self.statements
+    }
+
+    /// Whitespace and comments after the last statement.
+    pub fn trailing(&self) -> &str {
+        &self.trailing
+    }
+
+    /// Whether the file was edited since loading.
+    pub fn is_modified(&self) -> bool {
+        self.modified
+    }
+
+    /// Replace the statement at `index` with `text`, which must contain a single directive.
+    ///
+    /// Comments preceding the original statement are kept.
+    pub fn replace(&mut self, index: usize, text: &str) -> Result<()> {
+        let directive = single_directive(text)?;
+
+        let statement = &mut self.statements[index];
+        statement.directive = directive;
+        statement.text = text.trim().to_string();
+        statement.file_index = None;
+
+        self.modified = true;
+        Ok(())
+    }
+
+    /// Replace the token at `token` (see [Statement::tokens]) of the statement at `index`
+    /// with `text`, which must be a single token.
+    ///
+    /// Whitespace and comments around the token are kept. Panics if `token` is out of range.
+    pub fn replace_token(&mut self, index: usize, token: usize, text: &str) -> Result<()> {
+        let mut tokenizer = Tokenizer::new(text);
+        match (tokenizer.next(), tokenizer.next()) {
+            (Some(value), None) if value.value() == text => {}
+            _ => return Err(Error::UnexpectedToken),
+        }
+
+        let statement = &self.statements[index];
+        let mut tokens = statement.tokens().collect::<Vec<_>>();
+        tokens[token].text = text;
+
+        let text = tokens
+            .iter()
+            .flat_map(|token| [token.leading, token.text])
+            .collect::<String>();
+        let directive = single_directive(&text)?;
+
+        let statement = &mut self.statements[index];
+        statement.directive = directive;
+        statement.text = text;
+        statement.file_index = None;
+
+        self.modified = true;
+        Ok(())
+    }
+
+    /// Insert a new statement at `index`.
+    pub fn insert(&mut self, index: usize, text: &str) -> Result<()> {
+        let directive = single_directive(text)?;
+
+        self.statements.insert(
+            index,
+            Statement {
+                directive,
+                leading: String::from("\n"),
+                text: text.trim().to_string(),
+                file_index: None,
+            },
+        );
+
+        self.modified = true;
+        Ok(())
+    }
+
+    /// Remove the statement at `index`, along with its preceding comments.
+    pub fn remove(&mut self, index: usize) -> Statement {
+        self.modified = true;
+        self.statements.remove(index)
+    }
+
+    /// Write the file, unmodified statements are written exactly as in the source.
+    pub fn write<W: Write>(&self, out: &mut W) -> Result<()> {
+        self.write_with(out, |_| None)
+    }
+
+    /// Write the file, replacing `Include` / `Import` paths with `rename` where it returns `Some`.
+    fn write_with<W: Write>(
+        &self,
+        out: &mut W,
+        mut rename: impl FnMut(&Statement) -> Option<String>,
+    ) -> Result<()> {
+        for statement in &self.statements {
+            out.write_all(statement.leading.as_bytes())?;
+
+            match rename(statement) {
+                Some(path) => write!(out, "{} \"{}\"", statement.directive, path)?,
+                None => out.write_all(statement.text.as_bytes())?,
+            }
+        }
+
+        out.write_all(self.trailing.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// Scene files and their statements.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Document {
+    /// The main file is always first, files included more than once appear once.
+    files: Vec<SourceFile>,
+}
+
+impl Document {
+    /// Scan the main file and, recursively, all included and imported files.
+    pub(crate) fn new(
+        main: &str,
+        working_directory: Option<&Path>,
+        options: &LoadOptions,
+    ) -> Result<Self> {
+        let mut document = Document { files: Vec::new() };
+        let mut file_indices = HashMap::new();
+
+        document.add_file(
+            SourceFile::new(None, main),
+            working_directory,
+            options,
+            &mut file_indices,
+        )?;
+
+        Ok(document)
+    }
+
+    fn add_file(
+        &mut self,
+        mut file: SourceFile,
+        working_directory: Option<&Path>,
+        options: &LoadOptions,
+        file_indices: &mut HashMap<PathBuf, usize>,
+    ) -> Result<usize> {
+        let index = self.files.len();
+
+        if let Some(path) = &file.path {
+            file_indices.insert(path.clone(), index);
+        }
+
+        // Reserve the slot, so included files get higher indices.
+        let statements = mem::take(&mut file.statements);
+        self.files.push(file);
+
+        let mut resolved = Vec::with_capacity(statements.len());
+
+        for mut statement in statements {
+            if matches!(statement.directive, Directive::Include | Directive::Import) {
+                let path = include_path(&statement.text, options)?;
+                let path = resolve_path(working_directory, &path)?;
+
+                statement.file_index = Some(match file_indices.get(&path) {
+                    Some(index) => *index,
+                    None => {
+                        let src = match options.read_include(&path) {
+                            Ok(src) => src,
+                            // Reported while loading the scene.
+                            Err(_) if options.parse.missing_includes != Strictness::Error => {
+                                String::new()
+                            }
+                            Err(err) => return Err(err),
+                        };
+                        let file = SourceFile::new(Some(path), &src);
+
+                        self.add_file(file, working_directory, options, file_indices)?
+                    }
+                });
+            }
+
+            resolved.push(statement);
+        }
+
+        self.files[index].statements = resolved;
+        Ok(index)
+    }
+
+    pub fn files(&self) -> &[SourceFile] {
+        &self.files
+    }
+
+    pub fn files_mut(&mut self) -> &mut [SourceFile] {
+        &mut self.files
+    }
+
+    /// Main scene file.
+    pub fn main(&self) -> &SourceFile {
+        &self.files[0]
+    }
+
+    /// Write the document, keeping `Include` and `Import` boundaries intact.
+    ///
+    /// The main file is written to `main`. Included files which were modified (or which
+    /// include modified files) are written next to the originals with `suffix` appended to
+    /// the file name (before the extension), and the statements referencing them are updated.
+    /// Unmodified files are referenced as is and never rewritten.
+    ///
+    /// Returns paths of the written included files.
+    pub fn write<W: Write>(&self, main: &mut W, suffix: &str) -> Result<Vec<PathBuf>> {
+        let dirty = self.dirty();
+
+        // New paths of rewritten files.
+        let renamed = |statement: &Statement| -> Option<String> {
+            let index = statement.file_index?;
+            if !dirty[index] {
+                return None;
+            }
+
+            // Keep the path as written in the scene, only change the file name.
+            let path = include_path(&statement.text, &LoadOptions::default()).ok()?;
+            Some(with_suffix(&path, suffix))
+        };
+
+        let mut written = Vec::new();
+
+        for (index, file) in self.files.iter().enumerate().skip(1) {
+            if !dirty[index] {
+                continue;
+            }
+
+            let path = file.path.as_ref().ok_or(Error::NotFound)?;
+            let path = PathBuf::from(with_suffix(&path.to_string_lossy(), suffix));
+
+            let mut out = Vec::new();
+            file.write_with(&mut out, renamed)?;
+            fs::write(&path, out)?;
+
+            written.push(path);
+        }
+
+        self.main().write_with(main, renamed)?;
+
+        Ok(written)
+    }
+
+    /// Whether each file needs to be rewritten: either it's modified or one of its includes is.
+    fn dirty(&self) -> Vec<bool> {
+        let mut dirty = self.files.iter().map(|f| f.modified).collect::<Vec<_>>();
+
+        // Propagate to including files until nothing changes.
+        loop {
+            let mut changed = false;
+
+            for (index, file) in self.files.iter().enumerate() {
+                if dirty[index] {
+                    continue;
+                }
+
+                if file
+                    .statements
+                    .iter()
+                    .any(|s| s.file_index.map_or(false, |i| dirty[i]))
+                {
+                    dirty[index] = true;
+                    changed = true;
+                }
+            }
+
+            if !changed {
+                return dirty;
+            }
+        }
+    }
+}
+
+/// Parse the path of an `Include` or `Import` statement.
+fn include_path(text: &str, options: &LoadOptions) -> Result<String> {
+    let text = options.preprocess(text)?;
+
+    match Parser::new(&text).parse_next()? {
+        Element::Include(path) | Element::Import(path) => Ok(path.to_string()),
+        _ => Err(Error::UnexpectedToken),
+    }
+}
+
+/// Insert `suffix` before the extension(s) of the path's file name.
+fn with_suffix(path: &str, suffix: &str) -> String {
+    let name_start = path.rfind(['/', '\\']).map_or(0, |i| i + 1);
+
+    match path[name_start..].find('.') {
+        Some(dot) => {
+            let (stem, ext) = path.split_at(name_start + dot);
+            format!("{}{}{}", stem, suffix, ext)
+        }
+        None => format!("{}{}", path, suffix),
+    }
+}
+
+/// Parse a single directive statement.
+fn single_directive(text: &str) -> Result<Directive> {
+    let mut scanner = scan_directives(text);
+
+    match (scanner.next(), scanner.next()) {
+        (Some((directive, _)), None) => Ok(directive),
+        _ => Err(Error::UnexpectedToken),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::Scene;
+
+    #[test]
+    fn suffix() {
+        assert_eq!(
+            with_suffix("geometry/car.pbrt", "_1"),
+            "geometry/car_1.pbrt"
+        );
+        assert_eq!(with_suffix("car.pbrt.gz", "_1"), "car_1.pbrt.gz");
+        assert_eq!(with_suffix("./dir/car", "_1"), "./dir/car_1");
+    }
+
+    #[test]
+    fn lossless() -> Result<()> {
+        let src = "# Header\r\nLookAt 0 0 -5  0 0 0  0 1 0 # eye\n\nShape \"sphere\"\n    \"float radius\" [ 1 # unit\n    ]\n# end";
+
+        let mut file = SourceFile::parse(src);
+        assert_eq!(file.statements().len(), 2);
+        assert_eq!(file.statements()[0].leading(), "# Header\r\n");
+        assert_eq!(file.trailing(), "\n# end");
+
+        let mut out = Vec::new();
+        file.write(&mut out)?;
+        assert_eq!(String::from_utf8(out).unwrap(), src);
+
+        let shape = &file.statements()[1];
+        let tokens = shape.tokens().collect::<Vec<_>>();
+        assert_eq!(tokens.len(), 6);
+        assert_eq!(
+            tokens[4],
+            SyntaxToken {
+                leading: " ",
+                text: "1"
+            }
+        );
+        assert_eq!(tokens[5].leading, " # unit\n    ");
+        assert_eq!(
+            tokens
+                .iter()
+                .flat_map(|t| [t.leading, t.text])
+                .collect::<String>(),
+            shape.text()
+        );
+
+        // Comments inside and around the statement are kept.
+        file.replace_token(1, 4, "2.5")?;
+        assert!(file.is_modified());
+
+        let mut out = Vec::new();
+        file.write(&mut out)?;
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            src.replace("[ 1 #", "[ 2.5 #")
+        );
+
+        assert!(matches!(
+            file.replace_token(1, 4, "2 3"),
+            Err(Error::UnexpectedToken)
+        ));
+        assert!(matches!(
+            file.replace_token(1, 4, "WorldBegin"),
+            Err(Error::UnexpectedToken)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn write_structure() -> Result<()> {
+        let temp_dir = TempDir::new("pbrt-document-")?;
+        let temp_path = temp_dir.path();
+
+        let materials = "# Materials\nMakeNamedMaterial \"red\" \"string type\" \"diffuse\"\n";
+        let geometry = "Shape \"sphere\" # big\n";
+
+        fs::write(temp_path.join("materials.pbrt"), materials)?;
+        fs::write(temp_path.join("geometry.pbrt"), geometry)?;
+        fs::write(temp_path.join("scene.pbrt"), "Include \"materials.pbrt\"\n")?;
+
+        let main = "WorldBegin\n# Comment\nInclude \"scene.pbrt\"\nImport \"geometry.pbrt\"\n";
+
+        let options = LoadOptions {
+            keep_ast: true,
+            ..Default::default()
+        };
+
+        let mut scene = Scene::load_with_options(main, Some(temp_path), &options)?;
+        let document = scene.document.as_mut().unwrap();
+
+        assert_eq!(document.files().len(), 4);
+
+        // Unmodified documents are written as is.
+        let mut out = Vec::new();
+        assert!(document.write(&mut out, "_edited")?.is_empty());
+        assert_eq!(String::from_utf8(out).unwrap(), main);
+
+        let index = document
+            .files()
+            .iter()
+            .position(|f| f.path() == Some(&temp_path.join("materials.pbrt")))
+            .unwrap();
+
+        document.files_mut()[index]
+            .replace(0, "MakeNamedMaterial \"red\" \"string type\" \"conductor\"")?;
+
+        let mut out = Vec::new();
+        let written = document.write(&mut out, "_edited")?;
+
+        assert_eq!(
+            written,
+            [
+                temp_path.join("scene_edited.pbrt"),
+                temp_path.join("materials_edited.pbrt")
+            ]
+        );
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "WorldBegin\n# Comment\nInclude \"scene_edited.pbrt\"\nImport \"geometry.pbrt\"\n"
+        );
+        assert_eq!(
+            fs::read_to_string(temp_path.join("materials_edited.pbrt"))?,
+            "# Materials\nMakeNamedMaterial \"red\" \"string type\" \"conductor\"\n"
+        );
+
+        // Geometry is not touched.
+        assert!(!temp_path.join("geometry_edited.pbrt").exists());
+
+        Ok(())
+    }
+}
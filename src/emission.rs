@@ -0,0 +1,134 @@
+//! Emissive-material view of area lights.
+
+use crate::{
+    param::Spectrum,
+    types::{AreaLight, Material},
+    Scene, ShapeEntity,
+};
+
+/// Emission of an area-lit shape, expressed as a material property.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Emission {
+    /// Spectral distribution of emitted radiance (`"L"`).
+    ///
+    /// `None` means the default, the color space's standard illuminant.
+    pub spectrum: Option<Spectrum>,
+    /// Scale factor applied to `spectrum`.
+    pub scale: f32,
+    /// Whether both sides of the surface emit light.
+    pub two_sided: bool,
+    /// Image describing spatially-varying emission.
+    pub filename: Option<String>,
+}
+
+impl Emission {
+    /// Create an emission from an area light.
+    pub fn new(area_light: &AreaLight) -> Self {
+        let AreaLight::Diffuse {
+            filename,
+            two_sided,
+            spectrum,
+            scale,
+        } = area_light;
+
+        Emission {
+            spectrum: spectrum.clone(),
+            scale: *scale,
+            two_sided: *two_sided,
+            filename: filename.clone(),
+        }
+    }
+
+    /// Effective emitted radiance in RGB (`L` multiplied by `scale`).
+    ///
+    /// The default illuminant is treated as white, other spectra (e.g. blackbody emitters)
+    /// are converted with [Spectrum::srgb]. Returns `None` for spectra which are not
+    /// available, such as files that were not loaded.
+    pub fn rgb(&self) -> Option<[f32; 3]> {
+        let rgb = match &self.spectrum {
+            Some(spectrum) => spectrum.srgb()?,
+            None => [1.0; 3],
+        };
+
+        Some(rgb.map(|c| c * self.scale))
+    }
+}
+
+/// A shape along with its material and area light emission.
+#[derive(Debug, Clone, Copy)]
+pub struct Surface<'a> {
+    /// Index in [Scene::shapes].
+    pub shape_index: usize,
+    pub shape: &'a ShapeEntity,
+    pub material: Option<&'a Material>,
+    /// `Some` if the shape is an area light.
+    pub area_light: Option<&'a AreaLight>,
+}
+
+impl<'a> Surface<'a> {
+    /// Effective emission of the surface, `None` if the shape doesn't emit light.
+    pub fn emission(&self) -> Option<Emission> {
+        self.area_light.map(Emission::new)
+    }
+}
+
+impl Scene {
+    /// Iterate over shapes with their materials and area lights merged into a single view.
+    ///
+    /// Many formats model emission as a material property rather than as a light attached
+    /// to geometry, this simplifies converting to them.
+    pub fn surfaces(&self) -> impl Iterator<Item = Surface<'_>> {
+        self.shapes
+            .iter()
+            .enumerate()
+            .map(|(shape_index, shape)| Surface {
+                shape_index,
+                shape,
+                material: shape.material_index.and_then(|i| self.materials.get(i)),
+                area_light: shape.area_light_index.and_then(|i| self.area_lights.get(i)),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{types::MaterialType, Result, Scene};
+
+    #[test]
+    fn surfaces() -> Result<()> {
+        let data = r#"
+WorldBegin
+Material "diffuse"
+Shape "sphere"
+AttributeBegin
+  AreaLightSource "diffuse" "rgb L" [ 1 2 3 ] "float scale" 2 "bool twosided" true
+  Shape "disk"
+AttributeEnd
+AttributeBegin
+  AreaLightSource "diffuse" "blackbody L" 5500
+  Shape "disk"
+AttributeEnd
+        "#;
+
+        let scene = Scene::load(data, None)?;
+        let surfaces = scene.surfaces().collect::<Vec<_>>();
+
+        assert_eq!(surfaces.len(), 3);
+        assert!(surfaces
+            .iter()
+            .all(|s| matches!(s.material.unwrap().ty, MaterialType::Diffuse { .. })));
+
+        assert!(surfaces[0].emission().is_none());
+
+        let emission = surfaces[1].emission().unwrap();
+        assert!(emission.two_sided);
+        assert_eq!(emission.rgb(), Some([2.0, 4.0, 6.0]));
+
+        // Blackbody emitters are normalized to Y = 1, 5500K is slightly warm.
+        let [r, g, b] = surfaces[2].emission().unwrap().rgb().unwrap();
+        assert!(r > g && g > b && b > 0.0);
+
+        Ok(())
+    }
+}
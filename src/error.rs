@@ -1,6 +1,7 @@
 use std::{
-    io,
+    fmt, io,
     num::{ParseFloatError, ParseIntError},
+    path::PathBuf,
     str::ParseBoolError,
 };
 
@@ -50,8 +51,24 @@ pub enum Error {
     #[error("Unable to parse option value")]
     InvalidOptionValue,
 
-    #[error("Unsupported coordinate system")]
-    UnknownCoordinateSystem,
+    /// `Option` directive sets an option which is not supported by pbrt.
+    #[error("Unknown option: {0}")]
+    UnknownOption(String),
+
+    /// Coordinate system is not supported or was not defined with `CoordinateSystem`.
+    #[error("Unknown coordinate system: {0}")]
+    UnknownCoordinateSystem(String),
+
+    /// `ActiveTransform` argument is not one of `StartTime`, `EndTime` or `All`.
+    #[error("Unknown active transform: {0}")]
+    UnknownActiveTransform(String),
+
+    #[error("Unsupported color space")]
+    UnknownColorSpace,
+
+    /// Spectrum parameter refers to a spectrum which is not built into pbrt.
+    #[error("Unknown named spectrum: {0}")]
+    UnknownSpectrum(String),
 
     #[error("Invalid parameter name")]
     InvalidParamName,
@@ -87,9 +104,219 @@ pub enum Error {
     #[error("Required param is missing")]
     MissingRequiredParameter,
 
+    /// `NamedMaterial` refers to a material which is not defined.
+    #[error("Named material is not defined: {0}")]
+    MaterialNotFound(String),
+
+    /// Texture parameter refers to a texture which is not defined.
+    #[error("Named texture is not defined: {0}")]
+    TextureNotFound(String),
+
     #[error("Nested object attributes are not allowed")]
     NestedObjects,
 
     #[error("Not found")]
     NotFound,
+
+    /// PLY file is malformed or uses unsupported features.
+    #[error("Invalid PLY file: {0}")]
+    Ply(String),
+
+    /// NanoVDB file is malformed, or doesn't have the grids a medium needs.
+    #[error("Invalid NanoVDB file: {0}")]
+    NanoVdb(String),
+
+    /// OBJ file is malformed.
+    #[error("Invalid OBJ file: {0}")]
+    Obj(String),
+
+    /// SPD (sampled spectrum) file is malformed.
+    #[error("Invalid SPD file: {0}")]
+    Spd(String),
+
+    /// glTF document couldn't be written or read.
+    #[error("glTF error: {0}")]
+    Gltf(String),
+
+    /// Scene references a `{$name}` variable which is not defined.
+    #[error("Undefined variable: {0}")]
+    UndefinedVariable(String),
+
+    /// Unbalanced or malformed `#ifdef` / `#ifndef` / `#else` / `#endif` section.
+    #[error("Invalid conditional section")]
+    InvalidConditional,
+
+    /// Imported file defines a named entity which already exists in the importing scope.
+    #[error("Imported file redefines {0:?}")]
+    DuplicatedName(String),
+
+    /// Mesh data of a shape is inconsistent (e.g. indices are out of range), `parameter`
+    /// is the name of the offending mesh parameter.
+    #[error("Shape {shape} has invalid mesh \"{parameter}\": {reason}")]
+    InvalidMesh {
+        shape: usize,
+        parameter: &'static str,
+        reason: &'static str,
+    },
+
+    /// Mesh data is inconsistent, `parameter` is the name of the offending mesh parameter.
+    #[error("Invalid mesh \"{parameter}\": {reason}")]
+    InvalidMeshData {
+        parameter: &'static str,
+        reason: &'static str,
+    },
+
+    /// Number of values doesn't match the parameter type, e.g. `"point3 P"` with 4 values
+    /// or `"rgb L"` with 2 values.
+    #[error("Parameter \"{name}\" has {count} values, expected {expected}")]
+    InvalidValueCount {
+        /// Parameter type and name.
+        name: String,
+        count: usize,
+        expected: String,
+    },
+
+    /// [crate::Scene::flatten_instances] would create more shapes than allowed.
+    #[error("Flattening instances creates {count} shapes, the limit is {limit}")]
+    TooManyInstancedShapes { count: usize, limit: usize },
+
+    /// Quoted string contains bytes which are not valid UTF-8, see [crate::decode_bytes].
+    #[error("Invalid UTF-8 sequence in a string")]
+    InvalidUtf8,
+
+    /// A file includes or imports itself, directly or through other files.
+    ///
+    /// Contains the chain of files, starting and ending with the same file.
+    #[error("Include cycle: {}", display_chain(.0))]
+    IncludeCycle(Vec<PathBuf>),
+
+    /// Included or imported file is compressed with gzip, which is not supported.
+    #[error("Compressed file is not supported: {}", .0.display())]
+    CompressedInclude(PathBuf),
+
+    /// Included and imported files are nested deeper than
+    /// [crate::LoadOptions::max_include_depth].
+    #[error("Include depth exceeds the limit of {0}")]
+    IncludeDepthExceeded(usize),
+
+    /// Loading was cancelled with [crate::LoadOptions::cancel].
+    #[error("Loading cancelled")]
+    Cancelled,
+
+    /// Error along with the scene file location where it occurred.
+    ///
+    /// Errors returned by [crate::Parser] and [crate::Scene::load] are wrapped into this variant,
+    /// use [Error::inner] to match the underlying error.
+    #[error("{location}: {source}")]
+    Located {
+        location: Box<Location>,
+        source: Box<Error>,
+    },
+}
+
+impl Error {
+    /// Location in the scene file, if known.
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            Error::Located { location, .. } => Some(location),
+            _ => None,
+        }
+    }
+
+    /// The error without location information.
+    pub fn inner(&self) -> &Error {
+        match self {
+            Error::Located { source, .. } => source.inner(),
+            err => err,
+        }
+    }
+
+    /// Consume the error and return it without location information.
+    pub fn into_inner(self) -> Error {
+        match self {
+            Error::Located { source, .. } => source.into_inner(),
+            err => err,
+        }
+    }
+
+    /// Attach a location, unless the error already has one.
+    pub(crate) fn at(self, location: impl FnOnce() -> Location) -> Error {
+        match self {
+            Error::Located { .. } => self,
+            err => Error::Located {
+                location: Box::new(location()),
+                source: Box::new(err),
+            },
+        }
+    }
+}
+
+#[cfg(feature = "gltf")]
+impl From<gltf::Error> for Error {
+    fn from(err: gltf::Error) -> Self {
+        match err {
+            gltf::Error::Io(err) => Error::Io(err),
+            err => Error::Gltf(err.to_string()),
+        }
+    }
+}
+
+/// Position within a scene file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Location {
+    /// File path, `None` if the scene was loaded from a string.
+    pub file: Option<PathBuf>,
+    /// Line number, starting from 1.
+    pub line: usize,
+    /// Column number in characters, starting from 1.
+    pub column: usize,
+    /// Files which (transitively) include `file` via `Include` or `Import`, outermost first.
+    pub include_stack: Vec<PathBuf>,
+}
+
+impl Location {
+    /// Location of the byte `offset` within `src`.
+    pub(crate) fn new(src: &str, offset: usize) -> Self {
+        let before = &src.as_bytes()[..offset.min(src.len())];
+
+        let line_start = before
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map_or(0, |pos| pos + 1);
+
+        Location {
+            file: None,
+            line: before.iter().filter(|&&b| b == b'\n').count() + 1,
+            column: String::from_utf8_lossy(&before[line_start..])
+                .chars()
+                .count()
+                + 1,
+            include_stack: Vec::new(),
+        }
+    }
+}
+
+fn display_chain(chain: &[PathBuf]) -> String {
+    chain
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file.display())?;
+        }
+
+        write!(f, "{}:{}", self.line, self.column)?;
+
+        for path in self.include_stack.iter().rev() {
+            write!(f, " (included from {})", path.display())?;
+        }
+
+        Ok(())
+    }
 }
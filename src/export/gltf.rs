@@ -0,0 +1,626 @@
+//! glTF 2.0 export, intended for previewing scenes in standard viewers.
+//!
+//! The conversion is best-effort:
+//! - triangle and PLY meshes are exported as is, analytic shapes (spheres, disks and cylinders)
+//!   are tessellated with [Shape::tessellate];
+//! - object instances share the object's meshes;
+//! - perspective and orthographic cameras are exported, other camera types are skipped;
+//! - materials are approximated with the metallic-roughness model based on their type
+//!   and constant parameters (reflectance, roughness and index of refraction);
+//! - lights, textures and media are not exported.
+//!
+//! pbrt uses a left-handed coordinate system, so all nodes are placed under a root node
+//! which mirrors the z axis.
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    io::Write,
+    path::PathBuf,
+};
+
+use ::gltf::{
+    binary,
+    json::{
+        self,
+        accessor::{ComponentType, GenericComponentType, Type},
+        buffer::Target,
+        extensions,
+        material::{AlphaMode, PbrBaseColorFactor, PbrMetallicRoughness, StrengthFactor},
+        mesh::{Mode, Semantic},
+        validation::{Checked, USize64},
+        Index,
+    },
+};
+
+use super::{film_aspect, Pbr};
+use crate::{
+    math::{narrow, Float, Mat4, Vec3},
+    param::FromTypedParams,
+    types::{Camera, Film, Material, Shape},
+    Error, Result, Scene, ShapeEntity,
+};
+
+/// Near clipping plane of exported cameras.
+const ZNEAR: f32 = 0.01;
+
+/// Far clipping plane of exported orthographic cameras (perspective cameras use an infinite
+/// projection).
+const ORTHOGRAPHIC_ZFAR: f32 = 1.0e4;
+
+/// Options that control glTF export.
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    /// Number of subdivisions around the z axis used to tessellate analytic shapes,
+    /// see [Shape::tessellate].
+    pub tessellation_segments: u32,
+    /// Directory to resolve PLY meshes which were not loaded with the scene
+    /// (usually the scene file's directory), see [Shape::load_mesh].
+    ///
+    /// PLY meshes are skipped unless the `ply` feature is enabled.
+    pub working_directory: Option<PathBuf>,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        ExportOptions {
+            tessellation_segments: 32,
+            working_directory: None,
+        }
+    }
+}
+
+/// Exported glTF document along with its binary data.
+#[derive(Debug, Clone)]
+pub struct GltfDocument {
+    pub root: json::Root,
+    /// Contents of the only buffer (`root.buffers[0]`), empty if the scene has no geometry.
+    pub buffer: Vec<u8>,
+}
+
+impl GltfDocument {
+    /// Write the document as a binary glTF (`.glb`) file.
+    pub fn write_glb<W: Write>(&self, out: W) -> Result<()> {
+        let json = self
+            .root
+            .to_vec()
+            .map_err(|err| Error::Gltf(err.to_string()))?;
+
+        let glb = binary::Glb {
+            header: binary::Header {
+                magic: *b"glTF",
+                version: 2,
+                // Computed by the writer.
+                length: 0,
+            },
+            json: json.into(),
+            bin: (!self.buffer.is_empty()).then(|| self.buffer.as_slice().into()),
+        };
+
+        glb.to_writer(out)?;
+        Ok(())
+    }
+}
+
+/// Convert a loaded scene to a glTF document.
+///
+/// Returns [Error::InvalidMesh] if a mesh has indices out of range.
+pub fn export(scene: &Scene, options: &ExportOptions) -> Result<GltfDocument> {
+    let mut exporter = Exporter {
+        options,
+        root: json::Root::default(),
+        buffer: Vec::new(),
+        meshes: HashMap::new(),
+        default_material: None,
+    };
+
+    exporter.root.asset.generator = Some(format!("pbrt4 {}", env!("CARGO_PKG_VERSION")));
+
+    // Keep indices, so shapes can refer to materials directly.
+    for material in &scene.materials {
+        exporter.root.push(material_for(material));
+    }
+
+    let mut nodes = Vec::new();
+
+    if let Some(entity) = &scene.camera {
+        if let Some(camera) = camera_for(&entity.params, scene.film.as_ref()) {
+            let camera = exporter.root.push(camera);
+
+            // pbrt cameras look along +z, glTF cameras look along -z.
+            let transform = entity.transform * Mat4::from_scale(Vec3::new(1.0, 1.0, -1.0));
+
+            nodes.push(exporter.root.push(json::Node {
+                camera: Some(camera),
+                matrix: matrix(&transform),
+                ..Default::default()
+            }));
+        }
+    }
+
+    for (index, shape) in scene.shapes.iter().enumerate() {
+        if shape.object_index.is_some() {
+            continue;
+        }
+
+        if let Some(node) = exporter.shape_node(index, shape, None)? {
+            nodes.push(node);
+        }
+    }
+
+    for instance in &scene.instances {
+        let Some(object) = scene.objects.get(instance.object_index) else {
+            continue;
+        };
+
+        let mut children = Vec::new();
+        for index in object.shape_range().unwrap_or_default() {
+            let shape = &scene.shapes[index];
+            if let Some(node) = exporter.shape_node(index, shape, Some(&object.name))? {
+                children.push(node);
+            }
+        }
+
+        nodes.push(exporter.root.push(json::Node {
+            name: Some(object.name.clone()),
+            matrix: matrix(&instance.instance_to_world),
+            children: Some(children),
+            ..Default::default()
+        }));
+    }
+
+    // Convert from left-handed to right-handed coordinates.
+    let root_node = exporter.root.push(json::Node {
+        matrix: matrix(&Mat4::from_scale(Vec3::new(1.0, 1.0, -1.0))),
+        children: Some(nodes),
+        ..Default::default()
+    });
+
+    let gltf_scene = exporter.root.push(json::Scene {
+        extensions: None,
+        extras: Default::default(),
+        name: None,
+        nodes: vec![root_node],
+    });
+    exporter.root.scene = Some(gltf_scene);
+
+    if !exporter.buffer.is_empty() {
+        exporter.root.push(json::Buffer {
+            byte_length: USize64(exporter.buffer.len() as u64),
+            name: None,
+            uri: None,
+            extensions: None,
+            extras: Default::default(),
+        });
+    }
+
+    let uses_transmission = exporter.root.materials.iter().any(|material| {
+        material
+            .extensions
+            .as_ref()
+            .map_or(false, |ext| ext.transmission.is_some())
+    });
+
+    if uses_transmission {
+        exporter.root.extensions_used = vec![
+            "KHR_materials_transmission".to_string(),
+            "KHR_materials_ior".to_string(),
+        ];
+    }
+
+    Ok(GltfDocument {
+        root: exporter.root,
+        buffer: exporter.buffer,
+    })
+}
+
+struct Exporter<'a> {
+    options: &'a ExportOptions,
+    root: json::Root,
+    buffer: Vec<u8>,
+    /// glTF meshes by shape index, `None` if the shape has no geometry to export.
+    meshes: HashMap<usize, Option<Index<json::Mesh>>>,
+    /// Material used for shapes without a material (pbrt's default is `"diffuse"`).
+    default_material: Option<Index<json::Material>>,
+}
+
+impl<'a> Exporter<'a> {
+    /// Create a node for the shape at `index`, `None` if the shape can't be exported.
+    ///
+    /// Meshes of object shapes are named after the object.
+    fn shape_node(
+        &mut self,
+        index: usize,
+        entity: &ShapeEntity,
+        name: Option<&str>,
+    ) -> Result<Option<Index<json::Node>>> {
+        let mesh = match self.meshes.get(&index) {
+            Some(mesh) => *mesh,
+            None => {
+                let mesh = self.mesh(index, entity)?;
+                if let Some(mesh) = mesh {
+                    self.root.meshes[mesh.value()].name = name.map(str::to_string);
+                }
+                self.meshes.insert(index, mesh);
+                mesh
+            }
+        };
+
+        Ok(mesh.map(|mesh| {
+            self.root.push(json::Node {
+                mesh: Some(mesh),
+                matrix: matrix(&entity.transform),
+                ..Default::default()
+            })
+        }))
+    }
+
+    fn mesh(&mut self, index: usize, entity: &ShapeEntity) -> Result<Option<Index<json::Mesh>>> {
+        let tessellated = entity.params.tessellate(self.options.tessellation_segments);
+
+        match tessellated.as_ref().unwrap_or(&entity.params) {
+            Shape::TriangleMesh {
+                indices,
+                positions,
+                normals,
+                uvs,
+                ..
+            } => self.primitive(index, entity, positions, normals, uvs, indices),
+            #[cfg(feature = "ply")]
+            shape @ Shape::PlyMesh { .. } => {
+                let mesh = shape
+                    .load_mesh(self.options.working_directory.as_deref())?
+                    .expect("PLY mesh shape must have a mesh");
+
+                self.primitive(
+                    index,
+                    entity,
+                    &mesh.positions,
+                    &mesh.normals,
+                    &mesh.uvs,
+                    &mesh.indices,
+                )
+            }
+            _ => Ok(None),
+        }
+    }
+
+    fn primitive(
+        &mut self,
+        index: usize,
+        entity: &ShapeEntity,
+        positions: &[Float],
+        normals: &[f32],
+        uvs: &[f32],
+        indices: &[i32],
+    ) -> Result<Option<Index<json::Mesh>>> {
+        let vertex_count = positions.len() / 3;
+        if vertex_count == 0 {
+            return Ok(None);
+        }
+
+        let invalid = |reason| Error::InvalidMesh {
+            shape: index,
+            parameter: "indices",
+            reason,
+        };
+
+        if indices.len() % 3 != 0 {
+            return Err(invalid("number of indices is not a multiple of 3"));
+        }
+
+        let indices = indices
+            .iter()
+            .map(|&i| {
+                u32::try_from(i)
+                    .ok()
+                    .filter(|&i| (i as usize) < vertex_count)
+                    .ok_or_else(|| invalid("vertex index out of range"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let positions = positions.iter().map(|&v| narrow(v)).collect::<Vec<_>>();
+
+        let mut attributes = BTreeMap::new();
+
+        let accessor = self.floats(&positions, Type::Vec3, true);
+        attributes.insert(Checked::Valid(Semantic::Positions), accessor);
+
+        if normals.len() == vertex_count * 3 {
+            let accessor = self.floats(normals, Type::Vec3, false);
+            attributes.insert(Checked::Valid(Semantic::Normals), accessor);
+        }
+
+        if uvs.len() == vertex_count * 2 {
+            // pbrt's v axis points up, glTF's points down.
+            let uvs = uvs
+                .chunks_exact(2)
+                .flat_map(|uv| [uv[0], 1.0 - uv[1]])
+                .collect::<Vec<_>>();
+
+            let accessor = self.floats(&uvs, Type::Vec2, false);
+            attributes.insert(Checked::Valid(Semantic::TexCoords(0)), accessor);
+        }
+
+        let indices = (!indices.is_empty()).then(|| self.indices(&indices));
+
+        let material = match entity.material_index {
+            Some(material) => Index::new(material as u32),
+            None => *self
+                .default_material
+                .get_or_insert_with(|| self.root.push(material_for(&Material::default()))),
+        };
+
+        let mesh = self.root.push(json::Mesh {
+            extensions: None,
+            extras: Default::default(),
+            name: None,
+            primitives: vec![json::mesh::Primitive {
+                attributes,
+                extensions: None,
+                extras: Default::default(),
+                indices,
+                material: Some(material),
+                mode: Checked::Valid(Mode::Triangles),
+                targets: None,
+            }],
+            weights: None,
+        });
+
+        Ok(Some(mesh))
+    }
+
+    /// Add an accessor for float data, with bounds if `bounds` is set.
+    fn floats(&mut self, values: &[f32], ty: Type, bounds: bool) -> Index<json::Accessor> {
+        let components = ty.multiplicity();
+
+        let (min, max) = if bounds {
+            let mut min = vec![f32::INFINITY; components];
+            let mut max = vec![f32::NEG_INFINITY; components];
+
+            for chunk in values.chunks_exact(components) {
+                for (i, &v) in chunk.iter().enumerate() {
+                    min[i] = min[i].min(v);
+                    max[i] = max[i].max(v);
+                }
+            }
+
+            (Some(json::Value::from(min)), Some(json::Value::from(max)))
+        } else {
+            (None, None)
+        };
+
+        let view = self.view(values.iter().map(|v| v.to_le_bytes()), Target::ArrayBuffer);
+
+        self.root.push(json::Accessor {
+            buffer_view: Some(view),
+            byte_offset: None,
+            count: USize64((values.len() / components) as u64),
+            component_type: Checked::Valid(GenericComponentType(ComponentType::F32)),
+            extensions: None,
+            extras: Default::default(),
+            type_: Checked::Valid(ty),
+            min,
+            max,
+            name: None,
+            normalized: false,
+            sparse: None,
+        })
+    }
+
+    fn indices(&mut self, indices: &[u32]) -> Index<json::Accessor> {
+        let view = self.view(
+            indices.iter().map(|i| i.to_le_bytes()),
+            Target::ElementArrayBuffer,
+        );
+
+        self.root.push(json::Accessor {
+            buffer_view: Some(view),
+            byte_offset: None,
+            count: USize64(indices.len() as u64),
+            component_type: Checked::Valid(GenericComponentType(ComponentType::U32)),
+            extensions: None,
+            extras: Default::default(),
+            type_: Checked::Valid(Type::Scalar),
+            min: None,
+            max: None,
+            name: None,
+            normalized: false,
+            sparse: None,
+        })
+    }
+
+    /// Append 4 byte values to the buffer.
+    fn view(
+        &mut self,
+        values: impl Iterator<Item = [u8; 4]>,
+        target: Target,
+    ) -> Index<json::buffer::View> {
+        let offset = self.buffer.len();
+        self.buffer.extend(values.flatten());
+
+        self.root.push(json::buffer::View {
+            // The buffer is added after all views.
+            buffer: Index::new(0),
+            byte_length: USize64((self.buffer.len() - offset) as u64),
+            byte_offset: Some(USize64(offset as u64)),
+            byte_stride: None,
+            name: None,
+            target: Some(Checked::Valid(target)),
+            extensions: None,
+            extras: Default::default(),
+        })
+    }
+}
+
+fn matrix(m: &Mat4) -> Option<[f32; 16]> {
+    (*m != Mat4::IDENTITY).then(|| m.to_cols_array().map(narrow))
+}
+
+fn camera_for(camera: &Camera, film: Option<&Film>) -> Option<json::Camera> {
+    let aspect = film_aspect(film);
+
+    let (ty, perspective, orthographic) = match *camera {
+        Camera::Perspective { fov, .. } => {
+            // pbrt's field of view spans the shorter image axis.
+            let fov = fov.to_radians();
+            let yfov = if aspect >= 1.0 {
+                fov
+            } else {
+                2.0 * ((fov / 2.0).tan() / aspect).atan()
+            };
+
+            let perspective = json::camera::Perspective {
+                aspect_ratio: Some(aspect),
+                yfov,
+                zfar: None,
+                znear: ZNEAR,
+                extensions: None,
+                extras: Default::default(),
+            };
+
+            (json::camera::Type::Perspective, Some(perspective), None)
+        }
+        Camera::Orthographic { .. } => {
+            let [x0, x1, y0, y1] = camera.screen_window(aspect)?;
+            let (xmag, ymag) = ((x1 - x0) / 2.0, (y1 - y0) / 2.0);
+
+            let orthographic = json::camera::Orthographic {
+                xmag,
+                ymag,
+                zfar: ORTHOGRAPHIC_ZFAR,
+                znear: ZNEAR,
+                extensions: None,
+                extras: Default::default(),
+            };
+
+            (json::camera::Type::Orthographic, None, Some(orthographic))
+        }
+        Camera::Realistic { .. } | Camera::Spherical { .. } => return None,
+    };
+
+    Some(json::Camera {
+        name: None,
+        orthographic,
+        perspective,
+        type_: Checked::Valid(ty),
+        extensions: None,
+        extras: Default::default(),
+    })
+}
+
+/// Approximate a material with the metallic-roughness model, see [Pbr].
+fn material_for(material: &Material) -> json::Material {
+    let pbr = Pbr::new(material);
+    let [r, g, b] = pbr.base_color;
+
+    json::Material {
+        name: Some(
+            material
+                .name
+                .clone()
+                .unwrap_or_else(|| material.ty.type_name().to_string()),
+        ),
+        pbr_metallic_roughness: PbrMetallicRoughness {
+            base_color_factor: PbrBaseColorFactor([r, g, b, pbr.opacity]),
+            metallic_factor: StrengthFactor(pbr.metallic),
+            roughness_factor: StrengthFactor(pbr.roughness),
+            ..Default::default()
+        },
+        alpha_mode: Checked::Valid(if pbr.opacity < 1.0 {
+            AlphaMode::Blend
+        } else {
+            AlphaMode::Opaque
+        }),
+        // pbrt surfaces are visible from both sides.
+        double_sided: true,
+        extensions: pbr.ior.map(transmission),
+        ..Default::default()
+    }
+}
+
+/// Transmission and index of refraction extensions of a dielectric.
+fn transmission(ior: f32) -> extensions::material::Material {
+    // Other fields depend on the enabled `gltf` crate features.
+    #[allow(clippy::needless_update)]
+    extensions::material::Material {
+        transmission: Some(extensions::material::Transmission {
+            transmission_factor: extensions::material::TransmissionFactor(1.0),
+            transmission_texture: None,
+            extras: Default::default(),
+        }),
+        ior: Some(extensions::material::Ior {
+            ior: extensions::material::IndexOfRefraction(ior),
+            extras: Default::default(),
+        }),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export_glb() -> Result<()> {
+        let data = r#"
+LookAt 0 0 -5  0 0 0  0 1 0
+Camera "perspective" "float fov" 60
+Film "rgb" "integer xresolution" 200 "integer yresolution" 400
+WorldBegin
+Material "dielectric"
+Shape "sphere" "float radius" 2
+AttributeBegin
+  Material "conductor"
+  ObjectBegin "tri"
+    Shape "trianglemesh" "point3 P" [ 0 0 0  1 0 0  0 1 0 ] "integer indices" [ 0 1 2 ]
+  ObjectEnd
+AttributeEnd
+Translate 1 0 0
+ObjectInstance "tri"
+Translate 1 0 0
+ObjectInstance "tri"
+        "#;
+
+        let scene = Scene::load(data, None)?;
+        let document = export(&scene, &ExportOptions::default())?;
+
+        let mut glb = Vec::new();
+        document.write_glb(&mut glb)?;
+
+        // Parse and validate the document.
+        let gltf = ::gltf::Gltf::from_slice(&glb)?;
+
+        assert_eq!(gltf.materials().count(), 2);
+        assert!(gltf
+            .extensions_used()
+            .any(|ext| ext == "KHR_materials_transmission"));
+
+        // Both instances share the triangle mesh.
+        assert_eq!(gltf.meshes().count(), 2);
+
+        let camera = gltf.cameras().next().unwrap();
+        let ::gltf::camera::Projection::Perspective(perspective) = camera.projection() else {
+            panic!("Expected perspective camera");
+        };
+        assert_eq!(perspective.aspect_ratio(), Some(0.5));
+        assert!(perspective.yfov() > 60f32.to_radians());
+
+        let root = gltf.default_scene().unwrap().nodes().next().unwrap();
+        assert_eq!(root.transform().decomposed().2, [1.0, 1.0, -1.0]);
+
+        let nodes = root.children().collect::<Vec<_>>();
+        assert_eq!(nodes.len(), 4);
+        assert!(nodes[0].camera().is_some());
+
+        let sphere = nodes[1].mesh().unwrap().primitives().next().unwrap();
+        assert_eq!(sphere.material().index(), Some(0));
+        assert_eq!(sphere.bounding_box().max[0], 2.0);
+        assert!(sphere.get(&::gltf::Semantic::Normals).is_some());
+
+        let instance = nodes[3].children().next().unwrap();
+        assert_eq!(instance.mesh().unwrap().index(), 1);
+        assert_eq!(nodes[3].transform().decomposed().0, [2.0, 0.0, 0.0]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,112 @@
+//! Conversion of loaded scenes to other formats.
+
+#[cfg(feature = "gltf")]
+pub mod gltf;
+#[cfg(feature = "usd")]
+pub mod usd;
+
+use crate::{
+    param::{Spectrum, TexRef},
+    types::{Eta, Film, Material, MaterialType},
+};
+
+/// Aspect ratio (width / height) of the film, 1 if unknown.
+fn film_aspect(film: Option<&Film>) -> f32 {
+    film.map(|film| film.xresolution as f32 / film.yresolution as f32)
+        .filter(|aspect| aspect.is_finite() && *aspect > 0.0)
+        .unwrap_or(1.0)
+}
+
+/// Approximation of a material with the metallic-roughness model, shared by the exporters.
+///
+/// Constant reflectance, roughness and index of refraction values are used,
+/// textured parameters fall back to pbrt's defaults.
+struct Pbr {
+    base_color: [f32; 3],
+    opacity: f32,
+    metallic: f32,
+    roughness: f32,
+    /// Index of refraction of transmissive (dielectric) materials.
+    ior: Option<f32>,
+}
+
+impl Pbr {
+    fn new(material: &Material) -> Pbr {
+        let pbr = |base_color: [f32; 3], metallic: f32, roughness: f32| Pbr {
+            base_color,
+            opacity: 1.0,
+            metallic,
+            roughness: roughness.clamp(0.0, 1.0),
+            ior: None,
+        };
+
+        let color = |spectrum: Option<&TexRef<Spectrum>>, default: [f32; 3]| match spectrum {
+            Some(TexRef::Value(spectrum)) => spectrum.srgb().unwrap_or(default),
+            _ => default,
+        };
+
+        let roughness = |roughness: &TexRef<f32>, default: f32| match roughness {
+            TexRef::Value(roughness) => *roughness,
+            TexRef::Texture(_) => default,
+        };
+
+        let ior = |eta: &Eta| match eta {
+            Eta::Constant(eta) => *eta,
+            // Wavelength dependent, use the typical glass value.
+            Eta::Spectrum(_) => 1.5,
+        };
+
+        // pbrt defaults to copper.
+        let copper = [0.955, 0.638, 0.538];
+
+        match &material.ty {
+            MaterialType::Conductor {
+                reflectance,
+                u_roughness,
+                ..
+            } => pbr(
+                color(reflectance.as_ref(), copper),
+                1.0,
+                roughness(u_roughness, 0.0),
+            ),
+            MaterialType::CoatedConductor {
+                reflectance,
+                interface_u_roughness,
+                ..
+            } => pbr(
+                color(reflectance.as_ref(), copper),
+                1.0,
+                roughness(interface_u_roughness, 0.0),
+            ),
+            MaterialType::Dielectric {
+                eta, u_roughness, ..
+            } => Pbr {
+                ior: Some(ior(eta)),
+                ..pbr([1.0; 3], 0.0, roughness(u_roughness, 0.0))
+            },
+            MaterialType::ThinDielectric { eta } => Pbr {
+                ior: Some(ior(eta)),
+                ..pbr([1.0; 3], 0.0, 0.0)
+            },
+            // Invisible surface, only marks a boundary between media.
+            MaterialType::Interface => Pbr {
+                opacity: 0.0,
+                ..pbr([1.0; 3], 0.0, 1.0)
+            },
+            MaterialType::CoatedDiffuse {
+                reflectance,
+                u_roughness,
+                ..
+            } => pbr(
+                color(Some(reflectance), [0.5; 3]),
+                0.0,
+                roughness(u_roughness, 0.0),
+            ),
+            MaterialType::Diffuse { reflectance }
+            | MaterialType::DiffuseTransmission { reflectance, .. } => {
+                pbr(color(Some(reflectance), [0.5; 3]), 0.0, 1.0)
+            }
+            _ => pbr([0.5; 3], 0.0, 1.0),
+        }
+    }
+}
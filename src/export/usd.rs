@@ -0,0 +1,669 @@
+//! USD export, writes scenes as `.usda` text layers for USD-based pipelines.
+//!
+//! The conversion is best-effort:
+//! - shapes become `Mesh` prims with their transformation at the start of the shutter
+//!   interval, analytic shapes are tessellated with
+//!   [Shape::tessellate](crate::types::Shape::tessellate), curves are skipped;
+//! - objects are written as abstract prims under `/Objects` and instanced by instanceable
+//!   prims which reference them;
+//! - perspective and orthographic cameras become `Camera` prims, other camera types are skipped;
+//! - point, spot and goniometric lights become `SphereLight` prims treated as points, distant
+//!   lights `DistantLight` and infinite lights `DomeLight` prims (pbrt's equal-area environment
+//!   maps are referenced as is), projection lights are skipped;
+//! - area lights are applied to the emitting meshes with `MeshLightAPI`;
+//! - materials are approximated with `UsdPreviewSurface` shaders based on their type
+//!   and constant parameters (reflectance, roughness and index of refraction);
+//! - textures and media are not exported.
+//!
+//! pbrt uses a left-handed coordinate system, so the `/World` prim mirrors the z axis.
+//! Prims are named after the index of the entity in the scene (e.g. `/World/Materials/Material_0`),
+//! names from the scene file are kept as `displayName` metadata.
+
+use std::{fmt::Display, io::Write, path::PathBuf};
+
+use super::{film_aspect, Pbr};
+use crate::{
+    math::{float, Mat4, Vec3},
+    mesh::TriangleMeshData,
+    param::Spectrum,
+    types::{AreaLight, Camera, Light, Material},
+    LightEntity, Result, Scene, ShapeEntity,
+};
+
+/// Focal length of exported perspective cameras, in tenths of a scene unit.
+/// Apertures are computed from the field of view.
+const FOCAL_LENGTH: f32 = 50.0;
+
+/// Near and far clipping planes of exported cameras.
+const CLIPPING_RANGE: [f32; 2] = [0.01, 1.0e4];
+
+/// Options that control USD export.
+#[derive(Debug, Clone)]
+pub struct ExportOptions {
+    /// Number of subdivisions around the z axis used to tessellate analytic shapes,
+    /// see [Shape::tessellate](crate::types::Shape::tessellate).
+    pub tessellation_segments: u32,
+    /// Directory to resolve PLY meshes which were not loaded with the scene
+    /// (usually the scene file's directory), see
+    /// [Shape::load_mesh](crate::types::Shape::load_mesh).
+    ///
+    /// PLY meshes are skipped unless the `ply` feature is enabled.
+    pub working_directory: Option<PathBuf>,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        ExportOptions {
+            tessellation_segments: 32,
+            working_directory: None,
+        }
+    }
+}
+
+/// Write a loaded scene as a `.usda` layer.
+///
+/// Returns [crate::Error::InvalidMeshData] if a mesh has indices out of range.
+pub fn export<W: Write>(scene: &Scene, out: W, options: &ExportOptions) -> Result<()> {
+    Exporter {
+        out,
+        options,
+        indent: 0,
+    }
+    .scene(scene)
+}
+
+/// Write a loaded scene as a `.usda` layer to a string, see [export].
+pub fn export_string(scene: &Scene, options: &ExportOptions) -> Result<String> {
+    let mut out = Vec::new();
+    export(scene, &mut out, options)?;
+
+    Ok(String::from_utf8(out).expect("Exporter must produce valid UTF-8"))
+}
+
+struct Exporter<'a, W> {
+    out: W,
+    options: &'a ExportOptions,
+    indent: usize,
+}
+
+impl<'a, W: Write> Exporter<'a, W> {
+    fn line(&mut self, line: impl Display) -> Result<()> {
+        writeln!(self.out, "{:indent$}{}", "", line, indent = self.indent * 4)?;
+        Ok(())
+    }
+
+    /// Begin a prim with optional metadata, must be closed with [Exporter::end].
+    fn begin(&mut self, header: impl Display, metadata: &[String]) -> Result<()> {
+        self.line(header)?;
+
+        if !metadata.is_empty() {
+            self.line("(")?;
+            self.indent += 1;
+            for line in metadata {
+                self.line(line)?;
+            }
+            self.indent -= 1;
+            self.line(")")?;
+        }
+
+        self.line("{")?;
+        self.indent += 1;
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<()> {
+        self.indent -= 1;
+        self.line("}")
+    }
+
+    fn scene(&mut self, scene: &Scene) -> Result<()> {
+        self.line("#usda 1.0")?;
+        self.line("(")?;
+        self.indent += 1;
+        self.line("defaultPrim = \"World\"")?;
+        self.line(format_args!(
+            "doc = \"Exported by pbrt4 {}\"",
+            env!("CARGO_PKG_VERSION")
+        ))?;
+        self.line("metersPerUnit = 1")?;
+        self.line("upAxis = \"Y\"")?;
+        self.indent -= 1;
+        self.line(")")?;
+        self.line("")?;
+
+        self.begin("def Xform \"World\"", &[])?;
+
+        // Convert from left-handed to right-handed coordinates.
+        self.transform(&Mat4::from_scale(Vec3::new(1.0, 1.0, -1.0)))?;
+
+        self.materials(scene)?;
+
+        if let Some(entity) = &scene.camera {
+            let aspect = film_aspect(scene.film.as_ref());
+            // pbrt cameras look along +z, USD cameras look along -z.
+            let transform = entity.transform * Mat4::from_scale(Vec3::new(1.0, 1.0, -1.0));
+            self.camera(&entity.params, &transform, aspect)?;
+        }
+
+        for (index, light) in scene.lights.iter().enumerate() {
+            self.light(index, light)?;
+        }
+
+        for (index, shape) in scene.shapes.iter().enumerate() {
+            if shape.object_index.is_none() {
+                self.shape(scene, index, shape)?;
+            }
+        }
+
+        for (index, instance) in scene.instances.iter().enumerate() {
+            if instance.object_index >= scene.objects.len() {
+                continue;
+            }
+
+            self.begin(
+                format_args!("def Xform \"Instance_{}\"", index),
+                &[
+                    "instanceable = true".to_string(),
+                    format!(
+                        "prepend references = </Objects/Object_{}>",
+                        instance.object_index
+                    ),
+                ],
+            )?;
+            self.transform(&instance.instance_to_world)?;
+            self.end()?;
+        }
+
+        self.end()?;
+
+        if !scene.objects.is_empty() {
+            self.line("")?;
+
+            // Abstract prims are not rendered, only instanced.
+            self.begin("class Scope \"Objects\"", &[])?;
+
+            for (index, object) in scene.objects.iter().enumerate() {
+                self.begin(
+                    format_args!("def Xform \"Object_{}\"", index),
+                    &[display_name(&object.name)],
+                )?;
+
+                for shape_index in object.shape_range().unwrap_or_default() {
+                    self.shape(scene, shape_index, &scene.shapes[shape_index])?;
+                }
+
+                self.end()?;
+            }
+
+            self.end()?;
+        }
+
+        Ok(())
+    }
+
+    fn materials(&mut self, scene: &Scene) -> Result<()> {
+        let needs_default = scene
+            .shapes
+            .iter()
+            .any(|shape| shape.material_index.is_none());
+
+        if scene.materials.is_empty() && !needs_default {
+            return Ok(());
+        }
+
+        self.begin("def Scope \"Materials\"", &[])?;
+
+        for (index, material) in scene.materials.iter().enumerate() {
+            self.material(&format!("Material_{}", index), material)?;
+        }
+
+        // pbrt's default material is `"diffuse"`.
+        if needs_default {
+            self.material("Default", &Material::default())?;
+        }
+
+        self.end()
+    }
+
+    /// Write a material with a `UsdPreviewSurface` shader, see [Pbr].
+    fn material(&mut self, prim: &str, material: &Material) -> Result<()> {
+        let pbr = Pbr::new(material);
+
+        let metadata = material
+            .name
+            .as_deref()
+            .map(display_name)
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        self.begin(format_args!("def Material \"{}\"", prim), &metadata)?;
+        self.line(format_args!(
+            "token outputs:surface.connect = </World/Materials/{}/PreviewSurface.outputs:surface>",
+            prim
+        ))?;
+
+        self.begin("def Shader \"PreviewSurface\"", &[])?;
+        self.line("uniform token info:id = \"UsdPreviewSurface\"")?;
+        self.line(format_args!(
+            "color3f inputs:diffuseColor = {}",
+            tuple(&pbr.base_color)
+        ))?;
+        self.line(format_args!("float inputs:metallic = {}", pbr.metallic))?;
+        self.line(format_args!("float inputs:roughness = {}", pbr.roughness))?;
+        self.line(format_args!("float inputs:opacity = {}", pbr.opacity))?;
+        if let Some(ior) = pbr.ior {
+            self.line(format_args!("float inputs:ior = {}", ior))?;
+        }
+        self.line("token outputs:surface")?;
+        self.end()?;
+
+        self.end()
+    }
+
+    fn camera(&mut self, camera: &Camera, transform: &Mat4, aspect: f32) -> Result<()> {
+        let Some([x0, x1, y0, y1]) = camera.screen_window(aspect) else {
+            return Ok(());
+        };
+
+        // Size of the screen window unit on the film back: USD apertures are in tenths of
+        // a scene unit.
+        let (projection, scale) = match *camera {
+            Camera::Perspective { fov, .. } => {
+                ("perspective", (fov.to_radians() / 2.0).tan() * FOCAL_LENGTH)
+            }
+            _ => ("orthographic", 10.0),
+        };
+
+        self.begin("def Camera \"Camera\"", &[])?;
+        self.transform(transform)?;
+        self.line(format_args!("token projection = \"{}\"", projection))?;
+        self.line(format_args!("float focalLength = {}", FOCAL_LENGTH))?;
+        self.line(format_args!(
+            "float horizontalAperture = {}",
+            (x1 - x0) * scale
+        ))?;
+        self.line(format_args!(
+            "float verticalAperture = {}",
+            (y1 - y0) * scale
+        ))?;
+
+        let (offset_x, offset_y) = ((x0 + x1) / 2.0 * scale, (y0 + y1) / 2.0 * scale);
+        if offset_x != 0.0 {
+            self.line(format_args!(
+                "float horizontalApertureOffset = {}",
+                offset_x
+            ))?;
+        }
+        if offset_y != 0.0 {
+            self.line(format_args!("float verticalApertureOffset = {}", offset_y))?;
+        }
+
+        self.line(format_args!(
+            "float2 clippingRange = {}",
+            tuple(&CLIPPING_RANGE)
+        ))?;
+
+        // Depth of field of the thin lens model, the f-number is the ratio of the focal length
+        // to the aperture diameter.
+        if let (Camera::Perspective { .. }, Some(lens_radius)) = (camera, camera.lens_radius()) {
+            if lens_radius > 0.0 {
+                let f_stop = FOCAL_LENGTH / 10.0 / (2.0 * lens_radius);
+                self.line(format_args!("float fStop = {}", f_stop))?;
+                if let Some(focal_distance) = camera.focal_distance() {
+                    self.line(format_args!("float focusDistance = {}", focal_distance))?;
+                }
+            }
+        }
+
+        self.line(format_args!(
+            "double shutter:open = {}",
+            camera.shutter_open()
+        ))?;
+        self.line(format_args!(
+            "double shutter:close = {}",
+            camera.shutter_close()
+        ))?;
+
+        self.end()
+    }
+
+    fn light(&mut self, index: usize, entity: &LightEntity) -> Result<()> {
+        let light = &entity.params;
+
+        let (ty, local, scale) = match *light {
+            Light::Point { from, scale, .. } => (
+                "SphereLight",
+                Mat4::from_translation(Vec3::from(from.map(float))),
+                scale,
+            ),
+            Light::Spot {
+                from, to, scale, ..
+            } => ("SphereLight", aim(from, to), scale),
+            Light::GonioPhotometric { scale, .. } => ("SphereLight", Mat4::IDENTITY, scale),
+            // pbrt's distant light emits along +z, USD's along -z.
+            Light::Distant => (
+                "DistantLight",
+                Mat4::from_scale(Vec3::new(-1.0, 1.0, -1.0)),
+                1.0,
+            ),
+            Light::Infinite { .. } => ("DomeLight", Mat4::IDENTITY, 1.0),
+            Light::Projection { .. } => return Ok(()),
+        };
+
+        let metadata = match light {
+            Light::Spot { .. } => vec!["prepend apiSchemas = [\"ShapingAPI\"]".to_string()],
+            _ => Vec::new(),
+        };
+
+        self.begin(format_args!("def {} \"Light_{}\"", ty, index), &metadata)?;
+        self.transform(&(entity.transform * local))?;
+
+        let color = light
+            .spectrum()
+            .and_then(Spectrum::srgb)
+            .unwrap_or([1.0; 3]);
+        self.line(format_args!("color3f inputs:color = {}", tuple(&color)))?;
+        self.line(format_args!("float inputs:intensity = {}", scale))?;
+
+        if ty == "SphereLight" {
+            self.line("float inputs:radius = 0")?;
+            self.line("bool treatAsPoint = 1")?;
+        }
+
+        if let Light::Spot {
+            cone_angle,
+            cone_delta_angle,
+            ..
+        } = *light
+        {
+            let softness = if cone_angle > 0.0 {
+                (cone_delta_angle / cone_angle).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+
+            self.line(format_args!(
+                "float inputs:shaping:cone:angle = {}",
+                cone_angle
+            ))?;
+            self.line(format_args!(
+                "float inputs:shaping:cone:softness = {}",
+                softness
+            ))?;
+        }
+
+        if let Light::Infinite {
+            filename: Some(filename),
+            ..
+        } = light
+        {
+            self.line(format_args!(
+                "asset inputs:texture:file = {}",
+                asset(filename)
+            ))?;
+        }
+
+        self.end()
+    }
+
+    fn shape(&mut self, scene: &Scene, index: usize, entity: &ShapeEntity) -> Result<()> {
+        let Some(mesh) = self.mesh(entity)? else {
+            return Ok(());
+        };
+
+        if mesh.vertex_count() == 0 {
+            return Ok(());
+        }
+
+        let area_light = entity
+            .area_light_index
+            .and_then(|index| scene.area_lights.get(index));
+
+        let schemas = if area_light.is_some() {
+            "\"MaterialBindingAPI\", \"MeshLightAPI\""
+        } else {
+            "\"MaterialBindingAPI\""
+        };
+
+        self.begin(
+            format_args!("def Mesh \"Shape_{}\"", index),
+            &[format!("prepend apiSchemas = [{}]", schemas)],
+        )?;
+        self.transform(&entity.transform)?;
+
+        // pbrt surfaces are visible from both sides.
+        self.line("uniform bool doubleSided = 1")?;
+        if entity.reverse_orientation {
+            self.line("uniform token orientation = \"leftHanded\"")?;
+        }
+        self.line("uniform token subdivisionScheme = \"none\"")?;
+
+        self.line(format_args!(
+            "int[] faceVertexCounts = [{}]",
+            join(mesh.indices.iter().map(|_| 3))
+        ))?;
+        self.line(format_args!(
+            "int[] faceVertexIndices = [{}]",
+            join(mesh.indices.iter().flatten())
+        ))?;
+        self.line(format_args!(
+            "point3f[] points = [{}]",
+            join(mesh.positions.iter().map(|p| tuple(p)))
+        ))?;
+
+        if !mesh.normals.is_empty() {
+            self.line(format_args!(
+                "normal3f[] normals = [{}] (",
+                join(mesh.normals.iter().map(|n| tuple(n)))
+            ))?;
+            self.vertex_interpolation()?;
+        }
+
+        if !mesh.uvs.is_empty() {
+            self.line(format_args!(
+                "texCoord2f[] primvars:st = [{}] (",
+                join(mesh.uvs.iter().map(|uv| tuple(uv)))
+            ))?;
+            self.vertex_interpolation()?;
+        }
+
+        match entity.material_index {
+            Some(material) => self.line(format_args!(
+                "rel material:binding = </World/Materials/Material_{}>",
+                material
+            ))?,
+            None => self.line("rel material:binding = </World/Materials/Default>")?,
+        }
+
+        if let Some(AreaLight::Diffuse {
+            spectrum, scale, ..
+        }) = area_light
+        {
+            let color = spectrum
+                .as_ref()
+                .and_then(Spectrum::srgb)
+                .unwrap_or([1.0; 3]);
+            self.line(format_args!("color3f inputs:color = {}", tuple(&color)))?;
+            self.line(format_args!("float inputs:intensity = {}", scale))?;
+        }
+
+        self.end()
+    }
+
+    /// Close the metadata of a per-vertex attribute.
+    fn vertex_interpolation(&mut self) -> Result<()> {
+        self.indent += 1;
+        self.line("interpolation = \"vertex\"")?;
+        self.indent -= 1;
+        self.line(")")
+    }
+
+    /// Triangle mesh of a shape, `None` if the shape can't be exported.
+    fn mesh(&self, entity: &ShapeEntity) -> Result<Option<TriangleMeshData>> {
+        let tessellated = entity.params.tessellate(self.options.tessellation_segments);
+        let shape = tessellated.as_ref().unwrap_or(&entity.params);
+
+        #[cfg(feature = "ply")]
+        if let crate::types::Shape::PlyMesh { mesh: None, .. } = shape {
+            let mesh = shape
+                .load_mesh(self.options.working_directory.as_deref())?
+                .expect("PLY mesh shape must have a mesh");
+
+            return TriangleMeshData::from_arrays(
+                &mesh.positions,
+                &mesh.normals,
+                &[],
+                &mesh.uvs,
+                &mesh.indices,
+                &mesh.face_indices,
+            )
+            .map(Some);
+        }
+
+        shape.mesh_data()
+    }
+
+    /// Write a transformation, nothing for identity.
+    ///
+    /// USD transforms row vectors, so rows of USD matrices are columns of [Mat4].
+    fn transform(&mut self, m: &Mat4) -> Result<()> {
+        if *m == Mat4::IDENTITY {
+            return Ok(());
+        }
+
+        let rows = m.to_cols_array();
+        self.line(format_args!(
+            "matrix4d xformOp:transform = ( {} )",
+            join(rows.chunks_exact(4).map(tuple))
+        ))?;
+        self.line("uniform token[] xformOpOrder = [\"xformOp:transform\"]")
+    }
+}
+
+/// Light to world transformation of a light at `from` aimed at `to`, along -z as USD lights.
+fn aim(from: [f32; 3], to: [f32; 3]) -> Mat4 {
+    let from = Vec3::from(from.map(float));
+    let to = Vec3::from(to.map(float));
+
+    let dir = (to - from).normalize();
+    if !dir.length().is_finite() || dir.length() == 0.0 {
+        return Mat4::from_translation(from);
+    }
+
+    let up = if dir.y.abs() > 0.99 {
+        Vec3::new(1.0, 0.0, 0.0)
+    } else {
+        Vec3::new(0.0, 1.0, 0.0)
+    };
+
+    // The inverse of the view matrix maps +z to the light direction.
+    Mat4::look_at_lh(from, to, up).inverse() * Mat4::from_scale(Vec3::new(-1.0, 1.0, -1.0))
+}
+
+fn join<T: Display>(items: impl Iterator<Item = T>) -> String {
+    items
+        .map(|item| item.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn tuple<T: Display>(values: &[T]) -> String {
+    format!("({})", join(values.iter()))
+}
+
+fn quote(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+
+    format!("\"{}\"", escaped)
+}
+
+fn display_name(name: &str) -> String {
+    format!("displayName = {}", quote(name))
+}
+
+/// Asset path, paths containing `@` use the triple-delimited form.
+fn asset(path: &str) -> String {
+    if path.contains('@') {
+        format!("@@@{}@@@", path)
+    } else {
+        format!("@{}@", path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn export_usda() -> Result<()> {
+        let data = r#"
+LookAt 0 0 -5  0 0 0  0 1 0
+Camera "perspective" "float fov" 60 "float lensradius" 0.5 "float focaldistance" 5
+Film "rgb" "integer xresolution" 200 "integer yresolution" 400
+WorldBegin
+LightSource "spot" "point3 from" [ 0 5 0 ] "point3 to" [ 0 0 0 ] "float scale" 2
+LightSource "infinite" "string filename" "sky.exr"
+MakeNamedMaterial "glass" "string type" "dielectric"
+NamedMaterial "glass"
+Shape "sphere" "float radius" 2
+AttributeBegin
+  AreaLightSource "diffuse" "rgb L" [ 4 4 4 ]
+  Material "conductor"
+  ObjectBegin "tri"
+    Shape "trianglemesh" "point3 P" [ 0 0 0  1 0 0  0 1 0 ] "integer indices" [ 0 1 2 ]
+      "point2 uv" [ 0 0  1 0  0 1 ]
+  ObjectEnd
+AttributeEnd
+Translate 1 0 0
+ObjectInstance "tri"
+        "#;
+
+        let scene = Scene::load(data, None)?;
+        let usda = export_string(&scene, &ExportOptions::default())?;
+
+        assert!(usda.starts_with("#usda 1.0\n"));
+        assert_eq!(usda.matches('{').count(), usda.matches('}').count());
+
+        let lines = usda.lines().map(str::trim).collect::<Vec<_>>();
+        let has = |line: &str| lines.contains(&line);
+
+        assert!(has("def Material \"Material_0\""));
+        assert!(has("displayName = \"glass\""));
+        assert!(has("float inputs:ior = 1.5"));
+        assert!(has("rel material:binding = </World/Materials/Material_0>"));
+
+        assert!(has("token projection = \"perspective\""));
+        assert!(has("float fStop = 5"));
+        assert!(has("float focusDistance = 5"));
+
+        assert!(has("def SphereLight \"Light_0\""));
+        assert!(has("prepend apiSchemas = [\"ShapingAPI\"]"));
+        assert!(has("float inputs:intensity = 2"));
+        assert!(has("asset inputs:texture:file = @sky.exr@"));
+
+        assert!(has("def Mesh \"Shape_0\""));
+        assert!(has(
+            "prepend apiSchemas = [\"MaterialBindingAPI\", \"MeshLightAPI\"]"
+        ));
+        assert!(has("color3f inputs:color = (4, 4, 4)"));
+        assert!(has("texCoord2f[] primvars:st = [(0, 0), (1, 0), (0, 1)] ("));
+        assert!(has("int[] faceVertexIndices = [0, 1, 2]"));
+
+        assert!(has("prepend references = </Objects/Object_0>"));
+        assert!(has("matrix4d xformOp:transform = ( (1, 0, 0, 0), (0, 1, 0, 0), (0, 0, 1, 0), (1, 0, 0, 1) )"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn spot_light_aim() {
+        // Lights emit along -z.
+        let m = aim([0.0, 5.0, 0.0], [0.0, 0.0, 0.0]);
+        let dir = m.transform_vector3(Vec3::new(0.0, 0.0, -1.0));
+        assert!((dir - Vec3::new(0.0, -1.0, 0.0)).length() < 1e-5);
+        assert!((m.transform_point3(Vec3::ZERO) - Vec3::new(0.0, 5.0, 0.0)).length() < 1e-5);
+    }
+}
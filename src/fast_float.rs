@@ -0,0 +1,252 @@
+//! Fast parsing of float arrays.
+//!
+//! Mesh parameters may contain millions of values, most of them short decimals like `0.125`
+//! or `-3.5e-2`. These are parsed with Clinger's fast path: when the decimal mantissa and
+//! the power of ten are exactly representable as `f64`, a single multiplication or division
+//! gives the correctly rounded result. Everything else (long mantissas, large exponents,
+//! `inf`, `nan` and malformed input) falls back to [str::parse], so results are identical.
+
+use std::num::ParseFloatError;
+
+/// Largest mantissa exactly representable as `f64`.
+const MAX_MANTISSA: u64 = 1 << 53;
+
+/// Powers of ten exactly representable as `f64`.
+const POWERS: [f64; 23] = [
+    1e0, 1e1, 1e2, 1e3, 1e4, 1e5, 1e6, 1e7, 1e8, 1e9, 1e10, 1e11, 1e12, 1e13, 1e14, 1e15, 1e16,
+    1e17, 1e18, 1e19, 1e20, 1e21, 1e22,
+];
+
+/// Float types which can be parsed with the fast path.
+pub(crate) trait FastFloat: Sized {
+    fn parse_fast(str: &str) -> Result<Self, ParseFloatError>;
+}
+
+impl FastFloat for f64 {
+    fn parse_fast(str: &str) -> Result<Self, ParseFloatError> {
+        match fast_path(str.as_bytes()) {
+            Some(value) => Ok(value),
+            None => str.parse(),
+        }
+    }
+}
+
+impl FastFloat for f32 {
+    fn parse_fast(str: &str) -> Result<Self, ParseFloatError> {
+        // Rounding to f64 and then to f32 is only incorrect when the f64 lands exactly
+        // halfway between two f32 values, the dropped 29 bits are 1 followed by zeros then.
+        match fast_path(str.as_bytes()) {
+            Some(value) if value.to_bits() & 0x1fff_ffff != 0x1000_0000 => Ok(value as f32),
+            _ => str.parse(),
+        }
+    }
+}
+
+/// Parse whitespace separated floats.
+pub(crate) fn parse_floats<T: FastFloat>(str: &str) -> Result<Vec<T>, ParseFloatError> {
+    let bytes = str.as_bytes();
+    let mut values = Vec::new();
+    let mut pos = 0;
+
+    // Same as `split_ascii_whitespace`, but without the iterator overhead.
+    loop {
+        while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+
+        if pos == bytes.len() {
+            break;
+        }
+
+        let start = pos;
+        while pos < bytes.len() && !bytes[pos].is_ascii_whitespace() {
+            pos += 1;
+        }
+
+        values.push(T::parse_fast(&str[start..pos])?);
+    }
+
+    Ok(values)
+}
+
+/// Correctly rounded value of a simple decimal number (`[+-]digits[.digits][(e|E)[+-]digits]`),
+/// if the result can be computed exactly.
+fn fast_path(bytes: &[u8]) -> Option<f64> {
+    let mut pos = 0;
+
+    let negative = match bytes.first() {
+        Some(b'-') => {
+            pos += 1;
+            true
+        }
+        Some(b'+') => {
+            pos += 1;
+            false
+        }
+        _ => false,
+    };
+
+    let mut mantissa = 0u64;
+    let mut exponent = 0i32;
+
+    let integer_start = pos;
+    while let Some(&byte @ b'0'..=b'9') = bytes.get(pos) {
+        mantissa = mantissa
+            .wrapping_mul(10)
+            .wrapping_add(u64::from(byte - b'0'));
+        pos += 1;
+    }
+    let mut digit_count = pos - integer_start;
+
+    if let Some(b'.') = bytes.get(pos) {
+        pos += 1;
+
+        let fraction_start = pos;
+        while let Some(&byte @ b'0'..=b'9') = bytes.get(pos) {
+            mantissa = mantissa
+                .wrapping_mul(10)
+                .wrapping_add(u64::from(byte - b'0'));
+            pos += 1;
+        }
+
+        exponent = -((pos - fraction_start) as i32);
+        digit_count += pos - fraction_start;
+    }
+
+    // Up to 19 digits fit u64 (leading zeros are counted too, which is fine for the fast path).
+    if digit_count == 0 || digit_count > 19 {
+        return None;
+    }
+
+    if let Some(b'e' | b'E') = bytes.get(pos) {
+        pos += 1;
+
+        let negative = match bytes.get(pos) {
+            Some(b'-') => {
+                pos += 1;
+                true
+            }
+            Some(b'+') => {
+                pos += 1;
+                false
+            }
+            _ => false,
+        };
+
+        let exponent_start = pos;
+        let mut explicit = 0i32;
+        while let Some(&byte @ b'0'..=b'9') = bytes.get(pos) {
+            // Way out of the fast path range.
+            if explicit > 1000 {
+                return None;
+            }
+
+            explicit = explicit * 10 + i32::from(byte - b'0');
+            pos += 1;
+        }
+
+        if pos == exponent_start {
+            return None;
+        }
+
+        exponent += if negative { -explicit } else { explicit };
+    }
+
+    if pos != bytes.len() || mantissa > MAX_MANTISSA {
+        return None;
+    }
+
+    let power = *POWERS.get(exponent.unsigned_abs() as usize)?;
+    let value = if exponent < 0 {
+        mantissa as f64 / power
+    } else {
+        mantissa as f64 * power
+    };
+
+    Some(if negative { -value } else { value })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_std() {
+        let cases = [
+            "0",
+            "-0",
+            "+1",
+            "1.",
+            ".5",
+            "0.125",
+            "-3.5e-2",
+            "1E10",
+            "1e+3",
+            "0.1",
+            "0.3",
+            "3.14159265358979",
+            "123456789012345678901234",
+            "1e-50",
+            "1e39",
+            "-1e400",
+            "inf",
+            "-infinity",
+            "NaN",
+            "0.000000000000000000001",
+            "16777217",
+            "9007199254740993",
+            "1.00000017881393432617187499",
+            "1.000000178813934326171875",
+            "2.5e-7",
+            "7e22",
+        ];
+
+        for case in cases {
+            let expected = case.parse::<f64>().unwrap();
+            assert_eq!(
+                f64::parse_fast(case).unwrap().to_bits(),
+                expected.to_bits(),
+                "{case}"
+            );
+
+            let expected = case.parse::<f32>().unwrap();
+            let actual = f32::parse_fast(case).unwrap();
+            assert!(
+                actual.to_bits() == expected.to_bits() || (actual.is_nan() && expected.is_nan()),
+                "{case}"
+            );
+        }
+
+        for case in [
+            "", "-", ".", "e5", "1e", "1e+", "1.2.3", "--1", "1 2", "0x10",
+        ] {
+            assert!(f32::parse_fast(case).is_err(), "{case}");
+            assert!(f64::parse_fast(case).is_err(), "{case}");
+        }
+    }
+
+    #[test]
+    fn exhaustive_short_decimals() {
+        // All 4 digit values with a few exponents.
+        for mantissa in 0..10000 {
+            for exponent in [-12, -7, -4, -1, 0, 3, 9] {
+                let case = format!("{mantissa}e{exponent}");
+                assert_eq!(
+                    f32::parse_fast(&case).unwrap().to_bits(),
+                    case.parse::<f32>().unwrap().to_bits(),
+                    "{case}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn parse_list() {
+        assert_eq!(
+            parse_floats::<f32>(" 1 -2.5\n\t3e1 ").unwrap(),
+            [1.0, -2.5, 30.0]
+        );
+        assert!(parse_floats::<f32>("").unwrap().is_empty());
+        assert!(parse_floats::<f64>("1 x 2").is_err());
+    }
+}
@@ -0,0 +1,220 @@
+//! Post-processing of loaded scenes.
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use crate::{types::Shape, Error, Result, Scene, ShapeEntity};
+
+impl Scene {
+    /// Prepare a loaded scene for rendering.
+    ///
+    /// This validates mesh data and computes [ShapeEntity::bounds] for every shape.
+    /// With the `rayon` feature enabled, shapes are processed in parallel.
+    ///
+    /// Returns [Error::InvalidMesh] for the first shape with inconsistent mesh data. All shapes
+    /// are processed before, so the reported shape doesn't depend on the thread scheduling.
+    pub fn finalize(&mut self) -> Result<()> {
+        #[cfg(feature = "rayon")]
+        let shapes = self.shapes.par_iter_mut();
+        #[cfg(not(feature = "rayon"))]
+        let shapes = self.shapes.iter_mut();
+
+        let invalid = shapes
+            .enumerate()
+            .filter_map(|(index, entity)| finalize_shape(entity).err().map(|err| (index, err)))
+            .min_by_key(|(index, _)| *index);
+
+        match invalid {
+            Some((shape, (parameter, reason))) => Err(Error::InvalidMesh {
+                shape,
+                parameter,
+                reason,
+            }),
+            None => Ok(()),
+        }
+    }
+}
+
+fn finalize_shape(entity: &mut ShapeEntity) -> MeshResult {
+    validate_mesh(&entity.params)?;
+
+    entity.bounds = entity
+        .params
+        .bounds()
+        .map(|bounds| bounds.transform(&entity.transform));
+
+    Ok(())
+}
+
+/// Offending parameter name and the reason.
+type MeshResult = std::result::Result<(), (&'static str, &'static str)>;
+
+/// Check that mesh indices and per-vertex arrays are consistent.
+pub(crate) fn validate_mesh(shape: &Shape) -> MeshResult {
+    let (indices, positions, normals, tangents, uvs, face_indices): (
+        _,
+        _,
+        &[f32],
+        &[f32],
+        &[f32],
+        &[i32],
+    ) = match shape {
+        Shape::TriangleMesh {
+            indices,
+            positions,
+            normals,
+            tangents,
+            uvs,
+            face_indices,
+            ..
+        } => (indices, positions, normals, tangents, uvs, face_indices),
+        Shape::BilinearMesh {
+            indices,
+            positions,
+            normals,
+            uvs,
+            face_indices,
+            ..
+        } => (indices, positions, normals, &[], uvs, face_indices),
+        Shape::LoopSubdiv {
+            indices, positions, ..
+        } => (indices, positions, &[], &[], &[], &[]),
+        _ => return Ok(()),
+    };
+
+    if positions.len() % 3 != 0 {
+        return Err(("P", "number of positions is not a multiple of 3"));
+    }
+
+    match shape {
+        Shape::BilinearMesh { .. } => {
+            // A single patch doesn't need indices.
+            if indices.is_empty() && positions.len() != 12 {
+                return Err(("indices", "indices are required for more than one patch"));
+            }
+
+            if indices.len() % 4 != 0 {
+                return Err(("indices", "number of indices is not a multiple of 4"));
+            }
+        }
+        _ => {
+            if indices.len() % 3 != 0 {
+                return Err(("indices", "number of indices is not a multiple of 3"));
+            }
+        }
+    }
+
+    let vertex_count = positions.len() / 3;
+    let face_count = match shape {
+        Shape::BilinearMesh { .. } if indices.is_empty() => 1,
+        Shape::BilinearMesh { .. } => indices.len() / 4,
+        _ => indices.len() / 3,
+    };
+
+    if indices
+        .iter()
+        .any(|index| *index < 0 || *index as usize >= vertex_count)
+    {
+        return Err(("indices", "vertex index is out of range"));
+    }
+
+    if !normals.is_empty() && normals.len() != positions.len() {
+        return Err(("N", "number of normals doesn't match number of positions"));
+    }
+
+    if !tangents.is_empty() && tangents.len() != positions.len() {
+        return Err(("S", "number of tangents doesn't match number of positions"));
+    }
+
+    if !uvs.is_empty() && uvs.len() != vertex_count * 2 {
+        return Err(("uv", "number of uvs doesn't match number of positions"));
+    }
+
+    if !face_indices.is_empty() && face_indices.len() != face_count {
+        return Err((
+            "faceIndices",
+            "number of face indices doesn't match number of faces",
+        ));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::math::{Bounds3, Vec3};
+
+    #[test]
+    fn finalize() -> Result<()> {
+        let data = r#"
+WorldBegin
+Translate 0 0 5
+Shape "sphere" "float radius" 2
+Shape "trianglemesh" "point3 P" [ 0 0 0 1 0 0 0 1 0 ] "integer indices" [ 0 1 2 ]
+        "#;
+
+        let mut scene = Scene::load(data, None)?;
+        assert!(scene.shapes[0].bounds.is_none());
+
+        scene.finalize()?;
+
+        assert_eq!(
+            scene.shapes[0].bounds,
+            Some(Bounds3::new(
+                Vec3::new(-2.0, -2.0, 3.0),
+                Vec3::new(2.0, 2.0, 7.0)
+            ))
+        );
+        assert_eq!(
+            scene.shapes[1].bounds,
+            Some(Bounds3::new(
+                Vec3::new(0.0, 0.0, 5.0),
+                Vec3::new(1.0, 1.0, 5.0)
+            ))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_mesh() -> Result<()> {
+        let data = r#"
+WorldBegin
+Shape "sphere"
+Shape "trianglemesh" "point3 P" [ 0 0 0 1 0 0 0 1 0 ] "integer indices" [ 0 1 3 ]
+Shape "trianglemesh" "point3 P" [ 0 0 0 1 0 0 0 1 0 ] "integer indices" [ 0 1 4 ]
+        "#;
+
+        let mut scene = Scene::load(data, None)?;
+
+        assert!(matches!(
+            scene.finalize(),
+            Err(Error::InvalidMesh {
+                shape: 1,
+                parameter: "indices",
+                ..
+            })
+        ));
+
+        let data = r#"
+WorldBegin
+Shape "trianglemesh" "point3 P" [ 0 0 0 1 0 0 0 1 0 ] "integer indices" [ 0 1 2 ]
+    "integer faceIndices" [ 0 1 ]
+        "#;
+
+        let mut scene = Scene::load(data, None)?;
+
+        assert!(matches!(
+            scene.finalize(),
+            Err(Error::InvalidMesh {
+                shape: 0,
+                parameter: "faceIndices",
+                reason: "number of face indices doesn't match number of faces"
+            })
+        ));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,477 @@
+//! Pretty-printer for scene files.
+//!
+//! [format()] re-emits the statements of a scene file with consistent indentation inside
+//! `AttributeBegin` / `AttributeEnd` and `ObjectBegin` / `ObjectEnd` blocks, one parameter
+//! per line for statements with several parameters, and normalized numbers.
+//! Included files are not followed.
+
+use std::{fmt::Write, str::FromStr};
+
+use crate::{
+    param::ParamType, scan_directives, token::Token, tokenizer::Tokenizer, Directive, Error,
+    Parser, Result,
+};
+
+/// Options that control how scene files are formatted.
+#[derive(Debug, Clone)]
+pub struct FormatOptions {
+    /// Number of spaces per indentation level.
+    pub indent: usize,
+    /// Statements longer than this are split into one parameter per line,
+    /// and arrays longer than this are wrapped.
+    pub max_width: usize,
+    /// Keep comments (including preprocessor conditionals like `#ifdef`).
+    ///
+    /// Comments between the arguments of a statement are moved before the statement.
+    pub keep_comments: bool,
+}
+
+impl Default for FormatOptions {
+    fn default() -> Self {
+        FormatOptions {
+            indent: 4,
+            max_width: 100,
+            keep_comments: true,
+        }
+    }
+}
+
+/// Format a scene file.
+///
+/// The input is parsed first, so malformed files are reported instead of being
+/// formatted partially.
+///
+/// ```
+/// # use pbrt4::format::{format, FormatOptions};
+/// let src = "AttributeBegin\nTranslate 1.0 0.50 0\nShape \"sphere\" \"float radius\" 2.000\nAttributeEnd";
+///
+/// assert_eq!(
+///     format(src, &FormatOptions::default())?,
+///     "AttributeBegin\n    Translate 1 0.5 0\n    Shape \"sphere\" \"float radius\" 2\nAttributeEnd\n"
+/// );
+/// # Ok::<(), pbrt4::Error>(())
+/// ```
+pub fn format(src: &str, options: &FormatOptions) -> Result<String> {
+    let mut parser = Parser::new(src);
+    loop {
+        match parser.parse_next() {
+            Ok(_) => {}
+            Err(Error::EndOfFile) => break,
+            Err(err) => return Err(err),
+        }
+    }
+
+    let mut formatter = Formatter {
+        out: String::with_capacity(src.len()),
+        options,
+        depth: 0,
+    };
+
+    let mut last = 0;
+    for (directive, span) in scan_directives(src) {
+        formatter.leading(&src[last..span.start]);
+
+        if matches!(directive, Directive::AttributeEnd | Directive::ObjectEnd) {
+            formatter.depth = formatter.depth.saturating_sub(1);
+        }
+
+        formatter.statement(&src[span.clone()]);
+
+        if matches!(
+            directive,
+            Directive::AttributeBegin | Directive::ObjectBegin
+        ) {
+            formatter.depth += 1;
+        }
+
+        last = span.end;
+    }
+
+    formatter.leading(&src[last..]);
+
+    Ok(formatter.out)
+}
+
+struct Formatter<'a> {
+    out: String,
+    options: &'a FormatOptions,
+    /// Block nesting level.
+    depth: usize,
+}
+
+/// A parameter of a statement: the declaration and its values.
+struct FormattedParam {
+    decl: String,
+    values: Vec<String>,
+    /// Whether the values are enclosed in brackets.
+    array: bool,
+}
+
+impl FormattedParam {
+    fn line(&self) -> String {
+        if self.values.is_empty() && !self.array {
+            self.decl.clone()
+        } else if self.array {
+            format!("{} [ {} ]", self.decl, self.values.join(" "))
+        } else {
+            format!("{} {}", self.decl, self.values.join(" "))
+        }
+    }
+}
+
+impl Formatter<'_> {
+    fn line(&mut self, depth: usize, line: &str) {
+        let indent = depth * self.options.indent;
+        let _ = writeln!(self.out, "{:indent$}{}", "", line, indent = indent);
+    }
+
+    /// Whitespace and comments between statements.
+    fn leading(&mut self, text: &str) {
+        let mut lines = text.split('\n');
+
+        // The rest of the previous statement's line.
+        let first = lines.next().unwrap_or_default().trim();
+        if self.options.keep_comments && first.starts_with('#') {
+            if self.out.is_empty() {
+                self.line(self.depth, first);
+            } else {
+                self.out.pop();
+                let _ = writeln!(self.out, " {}", first);
+            }
+        }
+
+        // The last segment is the indentation of the next statement, not a line.
+        let lines = lines.collect::<Vec<_>>();
+        let lines = match lines.split_last() {
+            Some((last, lines)) if last.trim().is_empty() => lines,
+            _ => &lines[..],
+        };
+
+        let mut blank = false;
+        for line in lines {
+            let line = line.trim();
+
+            if line.is_empty() {
+                blank = true;
+                continue;
+            }
+
+            if line.starts_with('#') && !self.options.keep_comments {
+                continue;
+            }
+
+            self.blank_line(&mut blank);
+            self.line(self.depth, line);
+        }
+
+        self.blank_line(&mut blank);
+    }
+
+    /// Emit a pending blank line, except at the start of the output.
+    fn blank_line(&mut self, blank: &mut bool) {
+        if *blank && !self.out.is_empty() && !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+
+        *blank = false;
+    }
+
+    fn statement(&mut self, text: &str) {
+        let mut tokenizer = Tokenizer::new(text);
+        let mut tokens = Vec::new();
+        let mut comments = Vec::new();
+
+        // Tokens of the statement, collecting comments between them.
+        let mut last = 0;
+        while let Some(token) = tokenizer.next() {
+            let start = tokenizer.token_start();
+            comments.extend(
+                text[last..start]
+                    .lines()
+                    .filter_map(|line| line.find('#').map(|pos| line[pos..].trim_end())),
+            );
+
+            tokens.push(token);
+            last = tokenizer.offset();
+        }
+
+        if self.options.keep_comments {
+            for comment in comments {
+                self.line(self.depth, comment);
+            }
+        }
+
+        let mut tokens = tokens.into_iter();
+        let mut head = Vec::new();
+        let mut params = Vec::new();
+
+        while let Some(token) = tokens.next() {
+            if let Some(decl) = param_decl(&token) {
+                let mut param = FormattedParam {
+                    decl,
+                    values: Vec::new(),
+                    array: false,
+                };
+
+                match tokens.next() {
+                    Some(token) if token.is_open_brace() => {
+                        param.array = true;
+                        for token in tokens.by_ref() {
+                            if token.is_close_brace() {
+                                break;
+                            }
+                            param.values.push(value(&token));
+                        }
+                    }
+                    Some(token) => param.values.push(value(&token)),
+                    None => {}
+                }
+
+                params.push(param);
+            } else if !params.is_empty() {
+                // Stray tokens after parameters are kept in place.
+                params.push(FormattedParam {
+                    decl: value(&token),
+                    values: Vec::new(),
+                    array: false,
+                });
+            } else {
+                head.push(value(&token));
+            }
+        }
+
+        let head = head.join(" ");
+
+        let single = params.first().map(FormattedParam::line);
+        if params.len() <= 1 {
+            let line = match &single {
+                Some(param) => format!("{} {}", head, param),
+                None => head.clone(),
+            };
+
+            if self.depth * self.options.indent + line.len() <= self.options.max_width {
+                self.line(self.depth, &line);
+                return;
+            }
+        }
+
+        self.line(self.depth, &head);
+        for param in params {
+            self.param(&param);
+        }
+    }
+
+    /// Write a parameter on its own line, wrapping long arrays.
+    fn param(&mut self, param: &FormattedParam) {
+        let depth = self.depth + 1;
+
+        let line = param.line();
+        if !param.array || depth * self.options.indent + line.len() <= self.options.max_width {
+            self.line(depth, &line);
+            return;
+        }
+
+        self.line(depth, &format!("{} [", param.decl));
+
+        let width = self
+            .options
+            .max_width
+            .saturating_sub((depth + 1) * self.options.indent);
+        let mut current = String::new();
+        for value in &param.values {
+            if !current.is_empty() && current.len() + 1 + value.len() > width {
+                self.line(depth + 1, &current);
+                current.clear();
+            }
+
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(value);
+        }
+
+        if !current.is_empty() {
+            self.line(depth + 1, &current);
+        }
+
+        self.line(depth, "]");
+    }
+}
+
+/// Normalized parameter declaration, if the token is one (like `"float fov"`).
+fn param_decl(token: &Token) -> Option<String> {
+    let decl = token.unquote_raw()?;
+
+    let mut words = decl.split_whitespace();
+    let (ty, name) = (words.next()?, words.next()?);
+    if words.next().is_some() || ParamType::from_str(ty).is_err() {
+        return None;
+    }
+
+    Some(format!("\"{} {}\"", ty, name))
+}
+
+/// Token with numbers normalized, other tokens are kept as is.
+fn value(token: &Token) -> String {
+    let value = token.value();
+
+    let is_number = value.bytes().next().map_or(false, |byte| {
+        byte.is_ascii_digit() || b"+-.".contains(&byte)
+    });
+    if !is_number {
+        return value.to_string();
+    }
+
+    if let Ok(value) = value.parse::<i64>() {
+        return value.to_string();
+    }
+
+    match value.parse::<f64>() {
+        Ok(number) if number.is_finite() => {
+            let magnitude = number.abs();
+            if magnitude != 0.0 && !(1e-5..1e16).contains(&magnitude) {
+                format!("{:e}", number)
+            } else {
+                number.to_string()
+            }
+        }
+        _ => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fmt(src: &str) -> String {
+        format(src, &FormatOptions::default()).unwrap()
+    }
+
+    #[test]
+    fn indentation() {
+        let src = r#"
+WorldBegin
+AttributeBegin
+      ObjectBegin "tri"
+  Shape "trianglemesh" "integer indices" [0 1 2]
+ObjectEnd
+      AttributeEnd
+ObjectInstance "tri"
+"#;
+
+        assert_eq!(
+            fmt(src),
+            r#"WorldBegin
+AttributeBegin
+    ObjectBegin "tri"
+        Shape "trianglemesh" "integer indices" [ 0 1 2 ]
+    ObjectEnd
+AttributeEnd
+ObjectInstance "tri"
+"#
+        );
+    }
+
+    #[test]
+    fn numbers() {
+        assert_eq!(
+            fmt("Transform [1.0 0 0 0  0 1.000 0 0 0 0 1 0 -0.50 .25 +3 1]"),
+            "Transform [ 1 0 0 0 0 1 0 0 0 0 1 0 -0.5 0.25 3 1 ]\n"
+        );
+        assert_eq!(
+            fmt("Shape \"sphere\" \"float radius\" [ 1e-7 ] \"float zmax\" 1e20"),
+            "Shape \"sphere\"\n    \"float radius\" [ 1e-7 ]\n    \"float zmax\" 1e20\n"
+        );
+        assert_eq!(value(&Token::new("007")), "7");
+        assert_eq!(value(&Token::new("\"1.0\"")), "\"1.0\"");
+    }
+
+    #[test]
+    fn parameters() {
+        let src = r#"Material "coateddiffuse" "rgb   reflectance" [.5 .5 .5] "float roughness" 0
+"string note" "a  b""#;
+
+        assert_eq!(
+            fmt(src),
+            r#"Material "coateddiffuse"
+    "rgb reflectance" [ 0.5 0.5 0.5 ]
+    "float roughness" 0
+    "string note" "a  b"
+"#
+        );
+
+        let options = FormatOptions {
+            max_width: 30,
+            ..Default::default()
+        };
+        let src = "Shape \"trianglemesh\" \"point3 P\" [0 0 0 1 0 0 0 1 0 1 1 1 2 2 2 3 3 3]";
+        assert_eq!(
+            format(src, &options).unwrap(),
+            r#"Shape "trianglemesh"
+    "point3 P" [
+        0 0 0 1 0 0 0 1 0 1 1
+        1 2 2 2 3 3 3
+    ]
+"#
+        );
+    }
+
+    #[test]
+    fn comments() {
+        let src = r#"# Scene
+
+Camera "perspective" # main camera
+
+
+#ifdef PREVIEW
+Shape "sphere" # inline
+   "float radius" 1
+#endif
+"#;
+
+        assert_eq!(
+            fmt(src),
+            r#"# Scene
+
+Camera "perspective" # main camera
+
+#ifdef PREVIEW
+# inline
+Shape "sphere" "float radius" 1
+#endif
+"#
+        );
+
+        let options = FormatOptions {
+            keep_comments: false,
+            ..Default::default()
+        };
+        assert_eq!(
+            format(src, &options).unwrap(),
+            "Camera \"perspective\"\n\nShape \"sphere\" \"float radius\" 1\n"
+        );
+    }
+
+    #[test]
+    fn idempotent() {
+        for path in [
+            "assets/disney-cloud/disney-cloud.pbrt",
+            "assets/kroken/camera-1.pbrt",
+            "assets/kroken/materials.pbrt",
+        ] {
+            let src = std::fs::read_to_string(path).unwrap();
+            let formatted = fmt(&src);
+
+            assert_eq!(fmt(&formatted), formatted, "{}", path);
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(format(
+            "Shape \"sphere\" \"float radius\" [ 1",
+            &FormatOptions::default()
+        )
+        .is_err());
+    }
+}
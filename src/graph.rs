@@ -0,0 +1,396 @@
+//! Hierarchical scene loading.
+
+use std::{collections::HashSet, fs, path::Path};
+
+use crate::{
+    math::Mat4,
+    scene::{decode, visit_impl},
+    types::{
+        Accelerator, AreaLight, Film, Filter, Integrator, Material, Medium, Options, Sampler,
+        Texture,
+    },
+    validate::ValidationIssue,
+    CameraEntity, Dependency, InstanceEntity, LightEntity, LoadOptions, Object, Result,
+    SceneVisitor, ShapeEntity,
+};
+
+/// An `AttributeBegin` / `AttributeEnd` block (or an object definition) and its content.
+///
+/// Entities keep the world space transformations they get in [crate::Scene], so they don't
+/// depend on the node hierarchy.
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Node {
+    /// Object name if the node is an object definition, see [SceneGraph::objects].
+    pub name: Option<String>,
+    /// The CTM at the beginning of the block, relative to the parent node's
+    /// [Node::world_transform].
+    pub transform: Mat4,
+    /// The CTM at the beginning of the block.
+    pub world_transform: Mat4,
+    /// Index in [SceneGraph::materials] of the material inherited from the enclosing block.
+    pub material_index: Option<usize>,
+    pub shapes: Vec<ShapeEntity>,
+    pub lights: Vec<LightEntity>,
+    pub instances: Vec<InstanceEntity>,
+    /// Nested blocks, in the order they appear in the scene.
+    pub children: Vec<Node>,
+}
+
+impl Node {
+    /// Visit this node and all of its descendants, depth-first.
+    pub fn walk<'a>(&'a self, f: &mut impl FnMut(&'a Node)) {
+        f(self);
+
+        for child in &self.children {
+            child.walk(f);
+        }
+    }
+}
+
+/// A scene which keeps the attribute block hierarchy of the scene files,
+/// unlike [crate::Scene] where all shapes are stored in a flat list.
+///
+/// Named entities (textures, materials, media, area lights) are stored in lists,
+/// and referred to by index just like in [crate::Scene].
+#[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SceneGraph {
+    /// Shutter open time, set by `TransformTimes` (0 by default).
+    pub start_time: f32,
+    /// Shutter close time, set by `TransformTimes` (1 by default).
+    pub end_time: f32,
+    pub options: Options,
+    pub camera: Option<CameraEntity>,
+    pub film: Option<Film>,
+    pub integrator: Option<Integrator>,
+    pub accelerator: Option<Accelerator>,
+    pub sampler: Option<Sampler>,
+    pub filter: Option<Filter>,
+    pub textures: Vec<Texture>,
+    pub materials: Vec<Material>,
+    pub area_lights: Vec<AreaLight>,
+    pub mediums: Vec<Medium>,
+    /// Top level of the scene, entities outside of any attribute block.
+    pub root: Node,
+    /// Object definitions, indexed by [InstanceEntity::object_index].
+    ///
+    /// Objects are detached from the block they are defined in, since they are only
+    /// rendered through instances.
+    pub objects: Vec<Node>,
+    /// External files referenced by the scene, see [crate::Scene::dependencies].
+    pub dependencies: Vec<Dependency>,
+    /// Problems found while loading, see [crate::Scene::issues].
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl SceneGraph {
+    /// Load a scene hierarchy from a file at path.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<SceneGraph> {
+        Self::from_file_with_options(path, &LoadOptions::default())
+    }
+
+    /// Load a scene hierarchy from a file at path using custom load options.
+    pub fn from_file_with_options<P: AsRef<Path>>(
+        path: P,
+        options: &LoadOptions,
+    ) -> Result<SceneGraph> {
+        let path = path.as_ref();
+        let data = fs::read(path)?;
+
+        let (data, issues) = decode(&data, Some(path), options)?;
+        let mut graph = Self::load_impl(&data, path.parent(), Some(path), options)?;
+        graph.issues.splice(0..0, issues);
+
+        Ok(graph)
+    }
+
+    /// Load a scene hierarchy from a string slice.
+    ///
+    /// See [crate::Scene::load] for arguments description.
+    pub fn load(data: &str, working_directory: Option<&Path>) -> Result<SceneGraph> {
+        Self::load_with_options(data, working_directory, &LoadOptions::default())
+    }
+
+    /// Load a scene hierarchy from a string slice using custom load options.
+    ///
+    /// [LoadOptions::keep_ast] is ignored.
+    pub fn load_with_options(
+        data: &str,
+        working_directory: Option<&Path>,
+        options: &LoadOptions,
+    ) -> Result<SceneGraph> {
+        Self::load_impl(data, working_directory, None, options)
+    }
+
+    fn load_impl(
+        data: &str,
+        working_directory: Option<&Path>,
+        file: Option<&Path>,
+        options: &LoadOptions,
+    ) -> Result<SceneGraph> {
+        let mut builder = GraphBuilder {
+            graph: SceneGraph {
+                end_time: 1.0,
+                ..Default::default()
+            },
+            stack: Vec::new(),
+            object: None,
+            dependencies: HashSet::new(),
+        };
+
+        visit_impl(data, working_directory, file, options, &mut builder)?;
+
+        // Close unterminated blocks, they're reported in issues.
+        while !builder.stack.is_empty() {
+            builder.end_node();
+        }
+
+        Ok(builder.graph)
+    }
+}
+
+/// Builds a [SceneGraph] from visited entities.
+struct GraphBuilder {
+    graph: SceneGraph,
+    /// Open blocks, innermost last.
+    stack: Vec<Node>,
+    /// Depth of the object definition in `stack` and the object's index, set at `ObjectEnd`.
+    object: Option<(usize, usize)>,
+    /// Dependencies added to [SceneGraph::dependencies] so far.
+    dependencies: HashSet<Dependency>,
+}
+
+impl GraphBuilder {
+    /// Innermost open block.
+    fn current(&mut self) -> &mut Node {
+        self.stack.last_mut().unwrap_or(&mut self.graph.root)
+    }
+
+    /// Close the innermost block and attach it to its parent (or to objects).
+    fn end_node(&mut self) {
+        let Some(mut node) = self.stack.pop() else {
+            return;
+        };
+
+        if let Some((depth, index)) = self.object {
+            if depth == self.stack.len() {
+                self.object = None;
+
+                // Objects are defined in order, but keep indices valid regardless.
+                let objects = &mut self.graph.objects;
+                if objects.len() <= index {
+                    objects.resize_with(index + 1, Node::default);
+                }
+                objects[index] = node;
+                return;
+            }
+        }
+
+        let parent = self.current();
+        node.transform = parent.world_transform.inverse() * node.world_transform;
+        parent.children.push(node);
+    }
+}
+
+impl SceneVisitor for GraphBuilder {
+    fn on_options(&mut self, options: &Options) -> Result<()> {
+        self.graph.options = options.clone();
+        Ok(())
+    }
+
+    fn on_transform_times(&mut self, start: f32, end: f32) -> Result<()> {
+        self.graph.start_time = start;
+        self.graph.end_time = end;
+        Ok(())
+    }
+
+    fn on_camera(&mut self, camera: CameraEntity) -> Result<()> {
+        self.graph.camera = Some(camera);
+        Ok(())
+    }
+
+    fn on_film(&mut self, film: Film) -> Result<()> {
+        self.graph.film = Some(film);
+        Ok(())
+    }
+
+    fn on_sampler(&mut self, sampler: Sampler) -> Result<()> {
+        self.graph.sampler = Some(sampler);
+        Ok(())
+    }
+
+    fn on_filter(&mut self, filter: Filter) -> Result<()> {
+        self.graph.filter = Some(filter);
+        Ok(())
+    }
+
+    fn on_integrator(&mut self, integrator: Integrator) -> Result<()> {
+        self.graph.integrator = Some(integrator);
+        Ok(())
+    }
+
+    fn on_accelerator(&mut self, accelerator: Accelerator) -> Result<()> {
+        self.graph.accelerator = Some(accelerator);
+        Ok(())
+    }
+
+    fn on_texture(&mut self, _index: usize, texture: Texture) -> Result<()> {
+        self.graph.textures.push(texture);
+        Ok(())
+    }
+
+    fn on_material(&mut self, _index: usize, material: Material) -> Result<()> {
+        self.graph.materials.push(material);
+        Ok(())
+    }
+
+    fn on_medium(&mut self, _index: usize, medium: Medium) -> Result<()> {
+        self.graph.mediums.push(medium);
+        Ok(())
+    }
+
+    fn on_light(&mut self, light: LightEntity) -> Result<()> {
+        self.current().lights.push(light);
+        Ok(())
+    }
+
+    fn on_area_light(&mut self, _index: usize, area_light: AreaLight) -> Result<()> {
+        self.graph.area_lights.push(area_light);
+        Ok(())
+    }
+
+    fn on_shape(&mut self, _index: usize, shape: ShapeEntity) -> Result<()> {
+        self.current().shapes.push(shape);
+        Ok(())
+    }
+
+    fn on_object(&mut self, index: usize, object: Object) -> Result<()> {
+        // The object's block is still open, it's closed by the following `on_attribute_end`
+        // (or when loading is done if `ObjectEnd` is missing).
+        let depth = self.stack.len().saturating_sub(1);
+        self.object = Some((depth, index));
+        self.current().name = Some(object.name);
+        Ok(())
+    }
+
+    fn on_instance(&mut self, instance: InstanceEntity) -> Result<()> {
+        self.current().instances.push(instance);
+        Ok(())
+    }
+
+    fn on_attribute_begin(&mut self, transform: Mat4, material_index: Option<usize>) -> Result<()> {
+        self.stack.push(Node {
+            world_transform: transform,
+            material_index,
+            ..Default::default()
+        });
+        Ok(())
+    }
+
+    fn on_attribute_end(&mut self) -> Result<()> {
+        self.end_node();
+        Ok(())
+    }
+
+    fn on_dependency(&mut self, dependency: Dependency) -> Result<()> {
+        if self.dependencies.insert(dependency.clone()) {
+            self.graph.dependencies.push(dependency);
+        }
+        Ok(())
+    }
+
+    fn on_issue(&mut self, issue: ValidationIssue) -> Result<()> {
+        self.graph.issues.push(issue);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::Vec3;
+
+    #[test]
+    fn load() -> Result<()> {
+        let data = r#"
+WorldBegin
+LightSource "distant"
+Material "diffuse"
+Shape "sphere"
+AttributeBegin
+    Translate 1 0 0
+    Material "conductor"
+    Shape "disk"
+    AttributeBegin
+        Translate 0 2 0
+        Shape "sphere"
+        ObjectBegin "prop"
+            Shape "cylinder"
+        ObjectEnd
+    AttributeEnd
+    ObjectInstance "prop"
+AttributeEnd
+AttributeBegin
+AttributeEnd
+"#;
+
+        let graph = SceneGraph::load(data, None)?;
+        let root = &graph.root;
+
+        assert_eq!(graph.materials.len(), 2);
+        assert_eq!(root.lights.len(), 1);
+        assert_eq!(root.shapes.len(), 1);
+        assert_eq!(root.children.len(), 2);
+
+        let outer = &root.children[0];
+        assert_eq!(outer.transform, Mat4::IDENTITY);
+        assert_eq!(outer.material_index, Some(0));
+        assert_eq!(outer.shapes[0].material_index, Some(1));
+        assert_eq!(
+            outer.shapes[0].transform,
+            Mat4::from_translation(Vec3::new(1.0, 0.0, 0.0))
+        );
+        assert_eq!(outer.instances.len(), 1);
+        assert_eq!(outer.instances[0].object_index, 0);
+
+        let inner = &outer.children[0];
+        assert_eq!(
+            inner.transform,
+            Mat4::from_translation(Vec3::new(1.0, 0.0, 0.0))
+        );
+        assert_eq!(inner.material_index, Some(1));
+        assert_eq!(inner.shapes.len(), 1);
+        assert!(inner.children.is_empty());
+
+        assert_eq!(graph.objects.len(), 1);
+        let object = &graph.objects[0];
+        assert_eq!(object.name.as_deref(), Some("prop"));
+        assert_eq!(object.shapes[0].object_index, Some(0));
+        assert_eq!(
+            object.world_transform,
+            Mat4::from_translation(Vec3::new(1.0, 2.0, 0.0))
+        );
+
+        let mut count = 0;
+        root.walk(&mut |_| count += 1);
+        assert_eq!(count, 4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn unclosed() -> Result<()> {
+        let graph = SceneGraph::load(
+            "WorldBegin\nAttributeBegin\nObjectBegin \"a\"\nShape \"sphere\"",
+            None,
+        )?;
+
+        assert_eq!(graph.root.children.len(), 1);
+        assert_eq!(graph.objects[0].name.as_deref(), Some("a"));
+        assert_eq!(graph.objects[0].shapes.len(), 1);
+        assert_eq!(graph.issues.len(), 2);
+
+        Ok(())
+    }
+}
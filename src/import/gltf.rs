@@ -0,0 +1,419 @@
+//! glTF 2.0 import.
+//!
+//! The conversion is the inverse of [crate::export::gltf]:
+//! - triangle primitives become `"trianglemesh"` shapes, other primitive modes are skipped;
+//! - meshes referenced by more than one node become objects, and the nodes become instances;
+//! - the first perspective or orthographic camera becomes the scene camera;
+//! - materials are mapped to `"diffuse"`, `"conductor"` or `"dielectric"` with the base color,
+//!   roughness and index of refraction of the metallic-roughness model;
+//! - lights, textures and animations are not imported.
+//!
+//! glTF uses a right-handed coordinate system, so the z axis is mirrored.
+
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+use ::gltf::{buffer, camera::Projection, mesh::Mode, Document, Gltf, Node};
+
+use crate::{
+    math::{Float, Mat4, Vec3},
+    param::{Spectrum, TexRef},
+    types::{Camera, ColorSpace, Eta, Film, Material, MaterialType, Shape},
+    CameraEntity, InstanceEntity, Object, Result, Scene, ShapeEntity,
+};
+
+impl Scene {
+    /// Load a glTF (`.gltf` or `.glb`) file.
+    ///
+    /// External buffers are resolved relative to the file's directory.
+    pub fn from_gltf<P: AsRef<Path>>(path: P) -> Result<Scene> {
+        let path = path.as_ref();
+        let data = fs::read(path)?;
+
+        Self::from_gltf_slice(&data, path.parent())
+    }
+
+    /// Load a glTF document from memory.
+    ///
+    /// `working_directory` is used to resolve external buffers, see [Scene::from_gltf].
+    pub fn from_gltf_slice(data: &[u8], working_directory: Option<&Path>) -> Result<Scene> {
+        let Gltf { document, blob } = Gltf::from_slice(data)?;
+        let buffers = ::gltf::import_buffers(&document, working_directory, blob)?;
+
+        Importer {
+            buffers: &buffers,
+            scene: Scene::default(),
+            objects: HashMap::new(),
+        }
+        .import(&document)
+    }
+}
+
+struct Importer<'a> {
+    buffers: &'a [buffer::Data],
+    scene: Scene,
+    /// Object index by glTF mesh index, for meshes referenced by more than one node.
+    objects: HashMap<usize, usize>,
+}
+
+impl<'a> Importer<'a> {
+    fn import(mut self, document: &Document) -> Result<Scene> {
+        let Some(gltf_scene) = document
+            .default_scene()
+            .or_else(|| document.scenes().next())
+        else {
+            return Ok(self.scene);
+        };
+
+        self.scene.materials = document
+            .materials()
+            .map(|material| Material {
+                ty: material_type(&material),
+                color_space: ColorSpace::Srgb,
+                ..Default::default()
+            })
+            .collect();
+
+        // Find shared meshes, these are imported as objects.
+        let mut references = HashMap::<usize, usize>::new();
+        let mut stack = gltf_scene.nodes().collect::<Vec<_>>();
+        while let Some(node) = stack.pop() {
+            if let Some(mesh) = node.mesh() {
+                *references.entry(mesh.index()).or_default() += 1;
+            }
+            stack.extend(node.children());
+        }
+
+        let mut names = HashSet::new();
+        for mesh in document.meshes() {
+            if references.get(&mesh.index()).copied().unwrap_or_default() < 2 {
+                continue;
+            }
+
+            let object_index = self.scene.objects.len();
+            let shape_start = self.scene.shapes.len();
+
+            self.mesh_shapes(&mesh, Mat4::IDENTITY, Some(object_index))?;
+
+            let shape_count = self.scene.shapes.len() - shape_start;
+
+            let name = match mesh.name() {
+                Some(name) if !names.contains(name) => name.to_string(),
+                _ => format!("mesh{}", mesh.index()),
+            };
+            names.insert(name.clone());
+
+            self.scene.objects.push(Object {
+                name,
+                shape_start: (shape_count > 0).then_some(shape_start),
+                shape_count,
+                object_to_instance: Mat4::IDENTITY,
+            });
+            self.objects.insert(mesh.index(), object_index);
+        }
+
+        // Convert from right-handed to left-handed coordinates.
+        let world = Mat4::from_scale(Vec3::new(1.0, 1.0, -1.0));
+        for node in gltf_scene.nodes() {
+            self.node(&node, world)?;
+        }
+
+        Ok(self.scene)
+    }
+
+    fn node(&mut self, node: &Node, parent: Mat4) -> Result<()> {
+        let local = node.transform().matrix();
+        let local = Mat4::from_cols_array(&std::array::from_fn(|i| local[i / 4][i % 4] as Float));
+        let transform = parent * local;
+
+        if let Some(mesh) = node.mesh() {
+            match self.objects.get(&mesh.index()) {
+                Some(&object_index) => self.scene.instances.push(InstanceEntity {
+                    instance_to_world: transform,
+                    instance_to_world_end: transform,
+                    object_index,
+                    area_light_index: None,
+                    reverse_orientation: false,
+                }),
+                None => self.mesh_shapes(&mesh, transform, None)?,
+            }
+        }
+
+        if let Some(camera) = node.camera() {
+            if self.scene.camera.is_none() {
+                self.camera(&camera, transform);
+            }
+        }
+
+        for child in node.children() {
+            self.node(&child, transform)?;
+        }
+
+        Ok(())
+    }
+
+    /// Add a shape for each triangle primitive of `mesh`.
+    fn mesh_shapes(
+        &mut self,
+        mesh: &::gltf::Mesh,
+        transform: Mat4,
+        object_index: Option<usize>,
+    ) -> Result<()> {
+        for primitive in mesh.primitives() {
+            if primitive.mode() != Mode::Triangles {
+                continue;
+            }
+
+            let reader = primitive.reader(|buffer| Some(&self.buffers[buffer.index()]));
+
+            let Some(positions) = reader.read_positions() else {
+                continue;
+            };
+
+            let positions = positions.flatten().map(|v| v as Float).collect::<Vec<_>>();
+
+            let indices = match reader.read_indices() {
+                Some(indices) => indices.into_u32().map(|i| i as i32).collect(),
+                None => (0..(positions.len() / 3) as i32).collect(),
+            };
+
+            let normals = reader
+                .read_normals()
+                .map(|normals| normals.flatten().collect())
+                .unwrap_or_default();
+
+            // glTF's v axis points down, pbrt's points up.
+            let uvs = reader
+                .read_tex_coords(0)
+                .map(|uvs| uvs.into_f32().flat_map(|[u, v]| [u, 1.0 - v]).collect())
+                .unwrap_or_default();
+
+            self.scene.shapes.push(ShapeEntity {
+                params: Shape::TriangleMesh {
+                    alpha: 1.0,
+                    indices,
+                    positions,
+                    normals,
+                    tangents: Vec::new(),
+                    uvs,
+                    face_indices: Vec::new(),
+                },
+                transform,
+                transform_end: transform,
+                reverse_orientation: false,
+                material_index: primitive.material().index(),
+                area_light_index: None,
+                object_index,
+                interior_medium_index: None,
+                exterior_medium_index: None,
+                bounds: None,
+            });
+        }
+
+        Ok(())
+    }
+
+    fn camera(&mut self, camera: &::gltf::Camera, transform: Mat4) {
+        let (params, aspect) = match camera.projection() {
+            Projection::Perspective(perspective) => {
+                let aspect = perspective.aspect_ratio();
+
+                // pbrt's field of view spans the shorter image axis.
+                let yfov = perspective.yfov();
+                let fov = match aspect {
+                    Some(aspect) if aspect < 1.0 => 2.0 * ((yfov / 2.0).tan() * aspect).atan(),
+                    _ => yfov,
+                };
+
+                let camera = Camera::Perspective {
+                    shutter_open: 0.0,
+                    shutter_close: 1.0,
+                    frame_aspect_ratio: None,
+                    screen_window: None,
+                    lens_radius: 0.0,
+                    focal_distance: 1e6,
+                    fov: fov.to_degrees(),
+                };
+
+                (camera, aspect)
+            }
+            Projection::Orthographic(orthographic) => {
+                let (xmag, ymag) = (orthographic.xmag(), orthographic.ymag());
+
+                // Magnifications are half the extent of the view.
+                let camera = Camera::Orthographic {
+                    shutter_open: 0.0,
+                    shutter_close: 1.0,
+                    frame_aspect_ratio: None,
+                    screen_window: Some([-xmag, xmag, -ymag, ymag]),
+                    lens_radius: 0.0,
+                    focal_distance: 1e6,
+                };
+
+                (camera, Some(xmag / ymag))
+            }
+        };
+
+        // glTF cameras look along -z, pbrt cameras look along +z.
+        let transform = transform * Mat4::from_scale(Vec3::new(1.0, 1.0, -1.0));
+
+        self.scene.camera = Some(CameraEntity {
+            params,
+            transform,
+            transform_end: transform,
+            exterior_medium_index: None,
+        });
+
+        if let Some(aspect) = aspect.filter(|aspect| aspect.is_finite() && *aspect > 0.0) {
+            let film = Film::default();
+            self.scene.film = Some(Film {
+                xresolution: (film.yresolution as f32 * aspect).round() as i32,
+                ..film
+            });
+        }
+    }
+}
+
+/// Closest pbrt material for a glTF material.
+fn material_type(material: &::gltf::Material) -> MaterialType {
+    let pbr = material.pbr_metallic_roughness();
+    let [r, g, b, _] = pbr.base_color_factor();
+    let base_color = TexRef::Value(Spectrum::Rgb([r, g, b]));
+    let roughness = TexRef::Value(pbr.roughness_factor());
+
+    if material.transmission().map_or(false, |transmission| {
+        transmission.transmission_factor() > 0.0
+    }) {
+        MaterialType::Dielectric {
+            eta: Eta::Constant(material.ior().unwrap_or(1.5)),
+            u_roughness: roughness.clone(),
+            v_roughness: roughness.clone(),
+            roughness,
+            remap_roughness: true,
+        }
+    } else if pbr.metallic_factor() >= 0.5 {
+        MaterialType::Conductor {
+            eta: TexRef::Value(Spectrum::Named("metal-Cu-eta")),
+            k: TexRef::Value(Spectrum::Named("metal-Cu-k")),
+            reflectance: Some(base_color),
+            u_roughness: roughness.clone(),
+            v_roughness: roughness.clone(),
+            roughness,
+            remap_roughness: true,
+        }
+    } else {
+        MaterialType::Diffuse {
+            reflectance: base_color,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::{
+        export::gltf::{export, ExportOptions},
+        WriteOptions,
+    };
+
+    #[test]
+    fn round_trip() -> Result<()> {
+        let data = r#"
+LookAt 0 1 -5  0 0 0  0 1 0
+Camera "perspective" "float fov" 45
+Film "rgb" "integer xresolution" 300 "integer yresolution" 600
+WorldBegin
+Material "conductor"
+Translate 0 2 0
+Shape "trianglemesh"
+    "point3 P" [ 0 0 0  1 0 0  0 1 0 ]
+    "normal N" [ 0 0 1  0 0 1  0 0 1 ]
+    "point2 uv" [ 0 0  1 0  0 0.25 ]
+    "integer indices" [ 0 1 2 ]
+ObjectBegin "quad"
+  Shape "trianglemesh" "point3 P" [ 0 0 0  1 0 0  1 1 0  0 1 0 ] "integer indices" [ 0 1 2  0 2 3 ]
+ObjectEnd
+ObjectInstance "quad"
+Rotate 90 0 0 1
+ObjectInstance "quad"
+        "#;
+
+        let scene = Scene::load(data, None)?;
+
+        let temp_dir = TempDir::new("pbrt-gltf-")?;
+        let path = temp_dir.path().join("scene.glb");
+        export(&scene, &ExportOptions::default())?.write_glb(fs::File::create(&path)?)?;
+
+        let imported = Scene::from_gltf(&path)?;
+
+        assert_eq!(imported.materials.len(), 1);
+        assert!(matches!(
+            &imported.materials[0].ty,
+            MaterialType::Conductor {
+                reflectance: Some(TexRef::Value(Spectrum::Rgb(rgb))),
+                ..
+            } if rgb[0] > rgb[2]
+        ));
+
+        let camera = imported.camera.as_ref().unwrap();
+        let original = scene.camera.as_ref().unwrap();
+        let Camera::Perspective { fov, .. } = camera.params else {
+            panic!("Expected perspective camera");
+        };
+        assert!((fov - 45.0).abs() < 1e-3);
+        assert_mat_eq(&camera.transform, &original.transform);
+        assert_eq!(imported.film.as_ref().unwrap().xresolution, 360);
+
+        assert_eq!(imported.objects.len(), 1);
+        assert_eq!(imported.object_shapes(0).len(), 1);
+        assert_eq!(imported.instances.len(), 2);
+        for (a, b) in imported.instances.iter().zip(&scene.instances) {
+            // The object's shape transformation is moved to the instance.
+            assert_mat_eq(
+                &(a.instance_to_world * imported.object_shapes(0)[0].transform),
+                &(b.instance_to_world * scene.object_shapes(0)[0].transform),
+            );
+        }
+
+        // Object shapes come first.
+        assert_eq!(imported.shapes.len(), 2);
+        let shape = &imported.shapes[1];
+        assert_eq!(shape.object_index, None);
+        assert_eq!(shape.material_index, Some(0));
+        assert_mat_eq(&shape.transform, &scene.shapes[0].transform);
+
+        let Shape::TriangleMesh {
+            indices,
+            positions,
+            normals,
+            uvs,
+            ..
+        } = &shape.params
+        else {
+            panic!("Expected triangle mesh");
+        };
+
+        assert_eq!(indices, &[0, 1, 2]);
+        assert_eq!(positions.len(), 9);
+        assert_eq!(normals, &[0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(uvs, &[0.0, 0.0, 1.0, 0.0, 0.0, 0.25]);
+
+        let pbrt = imported.to_pbrt_string(&WriteOptions::default())?;
+        assert_eq!(pbrt.matches("ObjectInstance \"quad\"").count(), 2);
+
+        Ok(())
+    }
+
+    fn assert_mat_eq(a: &Mat4, b: &Mat4) {
+        let (a, b) = (a.to_cols_array(), b.to_cols_array());
+        assert!(
+            a.iter().zip(&b).all(|(a, b)| (a - b).abs() < 1e-4),
+            "{a:?} != {b:?}"
+        );
+    }
+}
@@ -0,0 +1,4 @@
+//! Conversion of other formats to scenes.
+
+#[cfg(feature = "gltf")]
+mod gltf;
@@ -1,15 +1,68 @@
 //! PBRT v4 file format parser and loader.
 
+// Allow derive macros to refer to `::pbrt4` from within the crate.
+extern crate self as pbrt4;
+
+pub mod animation;
+mod builder;
+#[cfg(any(feature = "ply", feature = "nanovdb"))]
+pub mod cache;
+mod convention;
+mod dependencies;
+pub mod document;
+mod emission;
 mod error;
+#[cfg(any(feature = "gltf", feature = "usd"))]
+pub mod export;
+mod fast_float;
+mod finalize;
+pub mod format;
+mod graph;
+#[cfg(feature = "gltf")]
+mod import;
+pub mod math;
+mod merge;
+pub mod mesh;
+#[cfg(feature = "mmap")]
+mod mmap;
+#[cfg(feature = "nanovdb")]
+pub mod nanovdb;
+#[cfg(feature = "obj")]
+mod obj;
 pub mod param;
 mod parser;
+#[cfg(feature = "ply")]
+pub mod ply;
+pub mod preprocess;
+mod report;
+mod resolver;
 mod scene;
+pub mod spectrum;
+mod stream;
+mod subdiv;
 mod token;
 mod tokenizer;
 pub mod types;
+pub mod validate;
+mod visitor;
+mod writer;
 
-pub use error::Error;
+pub use builder::*;
+pub use convention::*;
+pub use dependencies::*;
+pub use emission::*;
+pub use error::{Error, Location};
+pub use graph::*;
+pub use merge::*;
+#[cfg(feature = "mmap")]
+pub use mmap::MmapOptIn;
 pub use parser::*;
+pub use report::*;
+pub use resolver::*;
 pub use scene::*;
+pub use stream::*;
+pub use token::Directive;
+pub use visitor::*;
+pub use writer::*;
 
 pub type Result<T> = std::result::Result<T, Error>;
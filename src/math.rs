@@ -0,0 +1,1169 @@
+//! Minimal linear algebra types used to store transformations.
+//!
+//! The scene loader only needs a handful of operations, so instead of depending on
+//! a particular math library, transformations are stored as plain column-major `[Float; 16]`
+//! arrays wrapped in [Mat4] (`[f64; 16]` with the `f64` feature, see [Float]). Conversions
+//! to popular math crates are available behind the corresponding cargo features (`glam`,
+//! `mint`, `nalgebra`).
+
+use std::ops::{Add, Mul, MulAssign, Neg, Sub};
+
+/// Floating point type used for transformations and vertex positions.
+///
+/// This is `f32` by default. Enable the `f64` feature to parse and store transformations
+/// and positions in double precision (e.g. for large-world scenes with city-scale coordinates).
+#[cfg(not(feature = "f64"))]
+pub type Float = f32;
+
+/// Floating point type used for transformations and vertex positions.
+///
+/// This is `f64` because the `f64` feature is enabled.
+#[cfg(feature = "f64")]
+pub type Float = f64;
+
+/// Widen `f32` values (e.g. shape parameters) to [Float].
+#[allow(clippy::useless_conversion)]
+#[inline]
+pub(crate) fn float(v: f32) -> Float {
+    v.into()
+}
+
+/// Narrow [Float] values to `f32`, e.g. for formats which only store single precision.
+#[allow(clippy::unnecessary_cast)]
+#[inline]
+pub(crate) fn narrow(v: Float) -> f32 {
+    v as f32
+}
+
+/// A 3-dimensional vector.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Vec3 {
+    pub x: Float,
+    pub y: Float,
+    pub z: Float,
+}
+
+impl Vec3 {
+    /// All zeros.
+    pub const ZERO: Vec3 = Vec3::new(0.0, 0.0, 0.0);
+    /// All ones.
+    pub const ONE: Vec3 = Vec3::new(1.0, 1.0, 1.0);
+
+    /// Create a new vector.
+    pub const fn new(x: Float, y: Float, z: Float) -> Self {
+        Vec3 { x, y, z }
+    }
+
+    /// Returns the dot product of `self` and `rhs`.
+    pub fn dot(self, rhs: Vec3) -> Float {
+        self.x * rhs.x + self.y * rhs.y + self.z * rhs.z
+    }
+
+    /// Returns the cross product of `self` and `rhs`.
+    pub fn cross(self, rhs: Vec3) -> Vec3 {
+        Vec3::new(
+            self.y * rhs.z - self.z * rhs.y,
+            self.z * rhs.x - self.x * rhs.z,
+            self.x * rhs.y - self.y * rhs.x,
+        )
+    }
+
+    /// Returns the length of the vector.
+    pub fn length(self) -> Float {
+        self.dot(self).sqrt()
+    }
+
+    /// Returns a vector with the same direction and length 1.
+    ///
+    /// The result is non-finite if the vector length is zero.
+    pub fn normalize(self) -> Vec3 {
+        self * (1.0 / self.length())
+    }
+
+    /// Returns the vector as an array.
+    pub fn to_array(self) -> [Float; 3] {
+        [self.x, self.y, self.z]
+    }
+}
+
+impl From<[Float; 3]> for Vec3 {
+    fn from([x, y, z]: [Float; 3]) -> Self {
+        Vec3::new(x, y, z)
+    }
+}
+
+impl From<Vec3> for [Float; 3] {
+    fn from(v: Vec3) -> Self {
+        v.to_array()
+    }
+}
+
+impl Add for Vec3 {
+    type Output = Vec3;
+
+    fn add(self, rhs: Vec3) -> Vec3 {
+        Vec3::new(self.x + rhs.x, self.y + rhs.y, self.z + rhs.z)
+    }
+}
+
+impl Sub for Vec3 {
+    type Output = Vec3;
+
+    fn sub(self, rhs: Vec3) -> Vec3 {
+        Vec3::new(self.x - rhs.x, self.y - rhs.y, self.z - rhs.z)
+    }
+}
+
+impl Mul<Float> for Vec3 {
+    type Output = Vec3;
+
+    fn mul(self, rhs: Float) -> Vec3 {
+        Vec3::new(self.x * rhs, self.y * rhs, self.z * rhs)
+    }
+}
+
+impl Neg for Vec3 {
+    type Output = Vec3;
+
+    fn neg(self) -> Vec3 {
+        Vec3::new(-self.x, -self.y, -self.z)
+    }
+}
+
+/// A 4x4 transformation matrix stored in column-major order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mat4([Float; 16]);
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Mat4::IDENTITY
+    }
+}
+
+impl Mat4 {
+    /// The identity matrix.
+    pub const IDENTITY: Mat4 = Mat4([
+        1.0, 0.0, 0.0, 0.0, //
+        0.0, 1.0, 0.0, 0.0, //
+        0.0, 0.0, 1.0, 0.0, //
+        0.0, 0.0, 0.0, 1.0, //
+    ]);
+
+    /// Create a matrix from a column-major array.
+    pub const fn from_cols_array(m: &[Float; 16]) -> Self {
+        Mat4(*m)
+    }
+
+    /// Returns the matrix as a column-major array.
+    pub const fn to_cols_array(&self) -> [Float; 16] {
+        self.0
+    }
+
+    /// Returns the element at `row` and `col`.
+    pub fn get(&self, row: usize, col: usize) -> Float {
+        self.0[col * 4 + row]
+    }
+
+    /// Create a translation matrix.
+    pub fn from_translation(v: Vec3) -> Self {
+        let mut m = Mat4::IDENTITY;
+        m.0[12] = v.x;
+        m.0[13] = v.y;
+        m.0[14] = v.z;
+        m
+    }
+
+    /// Create a non-uniform scale matrix.
+    pub fn from_scale(v: Vec3) -> Self {
+        let mut m = Mat4::IDENTITY;
+        m.0[0] = v.x;
+        m.0[5] = v.y;
+        m.0[10] = v.z;
+        m
+    }
+
+    /// Create a rotation matrix around `axis` by `angle` (in radians).
+    ///
+    /// The axis doesn't have to be normalized.
+    pub fn from_axis_angle(axis: Vec3, angle: Float) -> Self {
+        let Vec3 { x, y, z } = axis.normalize();
+
+        let (sin, cos) = angle.sin_cos();
+        let omc = 1.0 - cos;
+
+        Mat4([
+            x * x * omc + cos,
+            x * y * omc + z * sin,
+            x * z * omc - y * sin,
+            0.0,
+            x * y * omc - z * sin,
+            y * y * omc + cos,
+            y * z * omc + x * sin,
+            0.0,
+            x * z * omc + y * sin,
+            y * z * omc - x * sin,
+            z * z * omc + cos,
+            0.0,
+            0.0,
+            0.0,
+            0.0,
+            1.0,
+        ])
+    }
+
+    /// Create a left-handed view matrix for a camera at `eye` looking at `center`.
+    ///
+    /// This matches pbrt's `LookAt` directive and returns the world to camera transformation.
+    /// Like pbrt, returns identity if `up` is parallel to the viewing direction.
+    pub fn look_at_lh(eye: Vec3, center: Vec3, up: Vec3) -> Self {
+        let f = (center - eye).normalize();
+        let s = up.cross(f);
+        if s.length() == 0.0 || !s.length().is_finite() {
+            return Mat4::IDENTITY;
+        }
+
+        let s = s.normalize();
+        let u = f.cross(s);
+
+        Mat4([
+            s.x,
+            u.x,
+            f.x,
+            0.0,
+            s.y,
+            u.y,
+            f.y,
+            0.0,
+            s.z,
+            u.z,
+            f.z,
+            0.0,
+            -s.dot(eye),
+            -u.dot(eye),
+            -f.dot(eye),
+            1.0,
+        ])
+    }
+
+    /// Returns the transpose of the matrix.
+    pub fn transpose(&self) -> Mat4 {
+        let mut m = [0.0; 16];
+        for row in 0..4 {
+            for col in 0..4 {
+                m[row * 4 + col] = self.get(row, col);
+            }
+        }
+        Mat4(m)
+    }
+
+    /// Returns the determinant of the matrix.
+    pub fn determinant(&self) -> Float {
+        let (c, s) = self.cofactors();
+        s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0]
+    }
+
+    /// Returns the inverse of the matrix.
+    ///
+    /// The result is non-finite if the matrix is singular.
+    pub fn inverse(&self) -> Mat4 {
+        let m = &self.0;
+        let (c, s) = self.cofactors();
+
+        let det = s[0] * c[5] - s[1] * c[4] + s[2] * c[3] + s[3] * c[2] - s[4] * c[1] + s[5] * c[0];
+        let inv_det = 1.0 / det;
+
+        // Element (row, col) of a column-major matrix `m` is m[col * 4 + row].
+        let a = |row: usize, col: usize| m[col * 4 + row];
+
+        let mut r = [0.0; 16];
+        let mut set = |row: usize, col: usize, v: Float| r[col * 4 + row] = v * inv_det;
+
+        set(0, 0, a(1, 1) * c[5] - a(1, 2) * c[4] + a(1, 3) * c[3]);
+        set(0, 1, -a(0, 1) * c[5] + a(0, 2) * c[4] - a(0, 3) * c[3]);
+        set(0, 2, a(3, 1) * s[5] - a(3, 2) * s[4] + a(3, 3) * s[3]);
+        set(0, 3, -a(2, 1) * s[5] + a(2, 2) * s[4] - a(2, 3) * s[3]);
+
+        set(1, 0, -a(1, 0) * c[5] + a(1, 2) * c[2] - a(1, 3) * c[1]);
+        set(1, 1, a(0, 0) * c[5] - a(0, 2) * c[2] + a(0, 3) * c[1]);
+        set(1, 2, -a(3, 0) * s[5] + a(3, 2) * s[2] - a(3, 3) * s[1]);
+        set(1, 3, a(2, 0) * s[5] - a(2, 2) * s[2] + a(2, 3) * s[1]);
+
+        set(2, 0, a(1, 0) * c[4] - a(1, 1) * c[2] + a(1, 3) * c[0]);
+        set(2, 1, -a(0, 0) * c[4] + a(0, 1) * c[2] - a(0, 3) * c[0]);
+        set(2, 2, a(3, 0) * s[4] - a(3, 1) * s[2] + a(3, 3) * s[0]);
+        set(2, 3, -a(2, 0) * s[4] + a(2, 1) * s[2] - a(2, 3) * s[0]);
+
+        set(3, 0, -a(1, 0) * c[3] + a(1, 1) * c[1] - a(1, 2) * c[0]);
+        set(3, 1, a(0, 0) * c[3] - a(0, 1) * c[1] + a(0, 2) * c[0]);
+        set(3, 2, -a(3, 0) * s[3] + a(3, 1) * s[1] - a(3, 2) * s[0]);
+        set(3, 3, a(2, 0) * s[3] - a(2, 1) * s[1] + a(2, 2) * s[0]);
+
+        Mat4(r)
+    }
+
+    /// 2x2 sub-determinants used by [Mat4::inverse] and [Mat4::determinant].
+    ///
+    /// `s` are built from the first two rows, `c` from the last two rows.
+    fn cofactors(&self) -> ([Float; 6], [Float; 6]) {
+        let a = |row: usize, col: usize| self.0[col * 4 + row];
+
+        let s = [
+            a(0, 0) * a(1, 1) - a(1, 0) * a(0, 1),
+            a(0, 0) * a(1, 2) - a(1, 0) * a(0, 2),
+            a(0, 0) * a(1, 3) - a(1, 0) * a(0, 3),
+            a(0, 1) * a(1, 2) - a(1, 1) * a(0, 2),
+            a(0, 1) * a(1, 3) - a(1, 1) * a(0, 3),
+            a(0, 2) * a(1, 3) - a(1, 2) * a(0, 3),
+        ];
+
+        let c = [
+            a(2, 0) * a(3, 1) - a(3, 0) * a(2, 1),
+            a(2, 0) * a(3, 2) - a(3, 0) * a(2, 2),
+            a(2, 0) * a(3, 3) - a(3, 0) * a(2, 3),
+            a(2, 1) * a(3, 2) - a(3, 1) * a(2, 2),
+            a(2, 1) * a(3, 3) - a(3, 1) * a(2, 3),
+            a(2, 2) * a(3, 3) - a(3, 2) * a(2, 3),
+        ];
+
+        (c, s)
+    }
+
+    /// Transform a point (applies translation and perspective divide).
+    pub fn transform_point3(&self, p: Vec3) -> Vec3 {
+        let m = &self.0;
+
+        let x = m[0] * p.x + m[4] * p.y + m[8] * p.z + m[12];
+        let y = m[1] * p.x + m[5] * p.y + m[9] * p.z + m[13];
+        let z = m[2] * p.x + m[6] * p.y + m[10] * p.z + m[14];
+        let w = m[3] * p.x + m[7] * p.y + m[11] * p.z + m[15];
+
+        if w == 1.0 {
+            Vec3::new(x, y, z)
+        } else {
+            Vec3::new(x / w, y / w, z / w)
+        }
+    }
+
+    /// Transform a direction vector (ignores translation).
+    pub fn transform_vector3(&self, v: Vec3) -> Vec3 {
+        let m = &self.0;
+
+        Vec3::new(
+            m[0] * v.x + m[4] * v.y + m[8] * v.z,
+            m[1] * v.x + m[5] * v.y + m[9] * v.z,
+            m[2] * v.x + m[6] * v.y + m[10] * v.z,
+        )
+    }
+
+    /// Returns `true` if all elements are finite.
+    pub fn is_finite(&self) -> bool {
+        self.0.iter().all(|v| v.is_finite())
+    }
+
+    /// Decompose the transformation into translation, rotation and scale,
+    /// e.g. to populate a scene graph of a game engine.
+    ///
+    /// ```
+    /// # use pbrt4::math::{Mat4, Vec3};
+    /// let m = Mat4::from_translation(Vec3::new(1.0, 2.0, 3.0))
+    ///     * Mat4::from_scale(Vec3::new(2.0, 2.0, 2.0));
+    ///
+    /// let d = m.decompose();
+    /// assert_eq!(d.translation, Vec3::new(1.0, 2.0, 3.0));
+    /// assert!((d.scale - Vec3::new(2.0, 2.0, 2.0)).length() < 1e-5);
+    /// assert!(!d.shear);
+    /// ```
+    pub fn decompose(&self) -> Decomposition {
+        let (rotation, stretch) = self.polar_decompose();
+
+        let scale = Vec3::new(stretch.get(0, 0), stretch.get(1, 1), stretch.get(2, 2));
+
+        // Off-diagonal elements of the stretch matrix are shear.
+        let tolerance = 1e-4 * scale.x.abs().max(scale.y.abs()).max(scale.z.abs());
+        let sheared = (0..3)
+            .flat_map(|row| (0..3).map(move |col| (row, col)))
+            .any(|(row, col)| row != col && stretch.get(row, col).abs() > tolerance);
+        let projective = (0..4).any(|col| self.get(3, col) != if col == 3 { 1.0 } else { 0.0 });
+
+        Decomposition {
+            translation: Vec3::new(self.get(0, 3), self.get(1, 3), self.get(2, 3)),
+            rotation: Quat::from_mat4(&rotation),
+            scale,
+            shear: sheared || projective,
+        }
+    }
+
+    /// Polar decomposition of the upper 3x3 part into `R * S`, where `R` is a rotation and
+    /// `S` is a stretch matrix (scale and shear).
+    ///
+    /// Transformations which flip handedness are mirrored along x in `S`, so `R` is always
+    /// a proper rotation.
+    pub(crate) fn polar_decompose(&self) -> (Mat4, Mat4) {
+        let mut cols = self.0;
+        cols[12..15].fill(0.0);
+        cols[3] = 0.0;
+        cols[7] = 0.0;
+        cols[11] = 0.0;
+        cols[15] = 1.0;
+        let upper = Mat4(cols);
+
+        // Average the matrix with its inverse transpose until convergence.
+        let mut r = upper;
+        for _ in 0..100 {
+            let r_it = r.inverse().transpose();
+
+            let mut next = r.0;
+            for (value, it) in next.iter_mut().zip(r_it.0) {
+                *value = 0.5 * (*value + it);
+            }
+            let next = Mat4(next);
+
+            let norm = (0..3)
+                .map(|row| {
+                    (0..3)
+                        .map(|col| (r.get(row, col) - next.get(row, col)).abs())
+                        .sum::<Float>()
+                })
+                .fold(0.0, Float::max);
+
+            r = next;
+
+            if norm < 0.0001 {
+                break;
+            }
+        }
+
+        if r.determinant() < 0.0 {
+            r *= Mat4::from_scale(Vec3::new(-1.0, 1.0, 1.0));
+        }
+
+        let s = r.inverse() * upper;
+        (r, s)
+    }
+}
+
+/// Translation, rotation and scale of a transformation, see [Mat4::decompose].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Decomposition {
+    pub translation: Vec3,
+    pub rotation: Quat,
+    /// Scale along the rotated axes.
+    ///
+    /// Transformations which flip handedness have negative x scale.
+    pub scale: Vec3,
+    /// `true` if the transformation can't be represented by translation, rotation and scale
+    /// because it has shear or a projective part. [Decomposition::scale] is approximate then.
+    pub shear: bool,
+}
+
+impl Decomposition {
+    /// Compose the transformation back, translation after rotation after scale.
+    pub fn to_mat4(&self) -> Mat4 {
+        Mat4::from_translation(self.translation)
+            * self.rotation.to_mat4()
+            * Mat4::from_scale(self.scale)
+    }
+}
+
+/// A rotation quaternion.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quat {
+    pub x: Float,
+    pub y: Float,
+    pub z: Float,
+    pub w: Float,
+}
+
+impl Default for Quat {
+    fn default() -> Self {
+        Quat::IDENTITY
+    }
+}
+
+impl Quat {
+    /// No rotation.
+    pub const IDENTITY: Quat = Quat {
+        x: 0.0,
+        y: 0.0,
+        z: 0.0,
+        w: 1.0,
+    };
+
+    /// Create a quaternion from the rotation part (upper 3x3) of `m`.
+    ///
+    /// The upper 3x3 part of `m` must be a pure rotation.
+    pub fn from_mat4(m: &Mat4) -> Quat {
+        let trace = m.get(0, 0) + m.get(1, 1) + m.get(2, 2);
+
+        let q = if trace > 0.0 {
+            let s = (trace + 1.0).sqrt() * 2.0;
+            Quat {
+                x: (m.get(2, 1) - m.get(1, 2)) / s,
+                y: (m.get(0, 2) - m.get(2, 0)) / s,
+                z: (m.get(1, 0) - m.get(0, 1)) / s,
+                w: 0.25 * s,
+            }
+        } else if m.get(0, 0) > m.get(1, 1) && m.get(0, 0) > m.get(2, 2) {
+            let s = (1.0 + m.get(0, 0) - m.get(1, 1) - m.get(2, 2)).sqrt() * 2.0;
+            Quat {
+                x: 0.25 * s,
+                y: (m.get(0, 1) + m.get(1, 0)) / s,
+                z: (m.get(0, 2) + m.get(2, 0)) / s,
+                w: (m.get(2, 1) - m.get(1, 2)) / s,
+            }
+        } else if m.get(1, 1) > m.get(2, 2) {
+            let s = (1.0 + m.get(1, 1) - m.get(0, 0) - m.get(2, 2)).sqrt() * 2.0;
+            Quat {
+                x: (m.get(0, 1) + m.get(1, 0)) / s,
+                y: 0.25 * s,
+                z: (m.get(1, 2) + m.get(2, 1)) / s,
+                w: (m.get(0, 2) - m.get(2, 0)) / s,
+            }
+        } else {
+            let s = (1.0 + m.get(2, 2) - m.get(0, 0) - m.get(1, 1)).sqrt() * 2.0;
+            Quat {
+                x: (m.get(0, 2) + m.get(2, 0)) / s,
+                y: (m.get(1, 2) + m.get(2, 1)) / s,
+                z: 0.25 * s,
+                w: (m.get(1, 0) - m.get(0, 1)) / s,
+            }
+        };
+
+        q.normalize()
+    }
+
+    /// Returns a rotation matrix.
+    pub fn to_mat4(&self) -> Mat4 {
+        let Quat { x, y, z, w } = *self;
+
+        Mat4([
+            1.0 - 2.0 * (y * y + z * z),
+            2.0 * (x * y + z * w),
+            2.0 * (x * z - y * w),
+            0.0,
+            2.0 * (x * y - z * w),
+            1.0 - 2.0 * (x * x + z * z),
+            2.0 * (y * z + x * w),
+            0.0,
+            2.0 * (x * z + y * w),
+            2.0 * (y * z - x * w),
+            1.0 - 2.0 * (x * x + y * y),
+            0.0,
+            0.0,
+            0.0,
+            0.0,
+            1.0,
+        ])
+    }
+
+    /// Returns the dot product of `self` and `rhs`.
+    pub fn dot(&self, rhs: Quat) -> Float {
+        self.x * rhs.x + self.y * rhs.y + self.z * rhs.z + self.w * rhs.w
+    }
+
+    /// Returns a unit quaternion.
+    pub fn normalize(&self) -> Quat {
+        let len = self.dot(*self).sqrt();
+        Quat {
+            x: self.x / len,
+            y: self.y / len,
+            z: self.z / len,
+            w: self.w / len,
+        }
+    }
+
+    /// Spherical linear interpolation between `self` (at `t = 0`) and `rhs` (at `t = 1`).
+    pub fn slerp(&self, rhs: Quat, t: Float) -> Quat {
+        let lerp = |a: &Quat, b: &Quat, t: Float| Quat {
+            x: a.x * (1.0 - t) + b.x * t,
+            y: a.y * (1.0 - t) + b.y * t,
+            z: a.z * (1.0 - t) + b.z * t,
+            w: a.w * (1.0 - t) + b.w * t,
+        };
+
+        let cos_theta = self.dot(rhs);
+
+        // Nearly parallel, fall back to linear interpolation.
+        if cos_theta > 0.9995 {
+            return lerp(self, &rhs, t).normalize();
+        }
+
+        let theta = cos_theta.clamp(-1.0, 1.0).acos();
+        let theta_p = theta * t;
+
+        let perp = Quat {
+            x: rhs.x - self.x * cos_theta,
+            y: rhs.y - self.y * cos_theta,
+            z: rhs.z - self.z * cos_theta,
+            w: rhs.w - self.w * cos_theta,
+        }
+        .normalize();
+
+        let (sin, cos) = theta_p.sin_cos();
+
+        Quat {
+            x: self.x * cos + perp.x * sin,
+            y: self.y * cos + perp.y * sin,
+            z: self.z * cos + perp.z * sin,
+            w: self.w * cos + perp.w * sin,
+        }
+    }
+}
+
+impl Neg for Quat {
+    type Output = Quat;
+
+    fn neg(self) -> Quat {
+        Quat {
+            x: -self.x,
+            y: -self.y,
+            z: -self.z,
+            w: -self.w,
+        }
+    }
+}
+
+/// An axis-aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Bounds3 {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Default for Bounds3 {
+    fn default() -> Self {
+        Bounds3::EMPTY
+    }
+}
+
+impl Bounds3 {
+    /// Empty bounds, the union with any point results in that point.
+    pub const EMPTY: Bounds3 = Bounds3 {
+        min: Vec3::new(Float::INFINITY, Float::INFINITY, Float::INFINITY),
+        max: Vec3::new(
+            Float::NEG_INFINITY,
+            Float::NEG_INFINITY,
+            Float::NEG_INFINITY,
+        ),
+    };
+
+    /// Create bounds from two corner points.
+    pub fn new(a: Vec3, b: Vec3) -> Self {
+        Bounds3 {
+            min: Vec3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z)),
+            max: Vec3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z)),
+        }
+    }
+
+    /// Compute bounds of a flat `[x, y, z, x, y, z, ...]` array of points.
+    pub fn from_points(points: &[Float]) -> Self {
+        points.chunks_exact(3).fold(Bounds3::EMPTY, |b, p| {
+            b.include(Vec3::new(p[0], p[1], p[2]))
+        })
+    }
+
+    /// Returns `true` if the bounds contain no points.
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    /// Returns bounds extended to include point `p`.
+    pub fn include(self, p: Vec3) -> Bounds3 {
+        Bounds3 {
+            min: Vec3::new(
+                self.min.x.min(p.x),
+                self.min.y.min(p.y),
+                self.min.z.min(p.z),
+            ),
+            max: Vec3::new(
+                self.max.x.max(p.x),
+                self.max.y.max(p.y),
+                self.max.z.max(p.z),
+            ),
+        }
+    }
+
+    /// Returns the union of two bounds.
+    pub fn union(self, other: Bounds3) -> Bounds3 {
+        if other.is_empty() {
+            return self;
+        }
+
+        self.include(other.min).include(other.max)
+    }
+
+    /// Returns the bounds of the 8 transformed corners.
+    pub fn transform(&self, m: &Mat4) -> Bounds3 {
+        if self.is_empty() {
+            return *self;
+        }
+
+        (0..8).fold(Bounds3::EMPTY, |b, i| {
+            let corner = Vec3::new(
+                if i & 1 == 0 { self.min.x } else { self.max.x },
+                if i & 2 == 0 { self.min.y } else { self.max.y },
+                if i & 4 == 0 { self.min.z } else { self.max.z },
+            );
+            b.include(m.transform_point3(corner))
+        })
+    }
+}
+
+impl From<[Float; 16]> for Mat4 {
+    fn from(m: [Float; 16]) -> Self {
+        Mat4(m)
+    }
+}
+
+impl From<Mat4> for [Float; 16] {
+    fn from(m: Mat4) -> Self {
+        m.0
+    }
+}
+
+impl AsRef<[Float; 16]> for Mat4 {
+    fn as_ref(&self) -> &[Float; 16] {
+        &self.0
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    fn mul(self, rhs: Mat4) -> Mat4 {
+        let mut r = [0.0; 16];
+
+        for col in 0..4 {
+            for row in 0..4 {
+                r[col * 4 + row] = (0..4).map(|k| self.get(row, k) * rhs.get(k, col)).sum();
+            }
+        }
+
+        Mat4(r)
+    }
+}
+
+impl MulAssign for Mat4 {
+    fn mul_assign(&mut self, rhs: Mat4) {
+        *self = *self * rhs;
+    }
+}
+
+#[cfg(feature = "glam")]
+mod glam_impl {
+    use super::{Mat4, Quat, Vec3};
+
+    #[cfg(feature = "f64")]
+    use glam::{DMat4 as GlamMat4, DQuat as GlamQuat, DVec3 as GlamVec3};
+    #[cfg(not(feature = "f64"))]
+    use glam::{Mat4 as GlamMat4, Quat as GlamQuat, Vec3 as GlamVec3};
+
+    impl From<Mat4> for GlamMat4 {
+        fn from(m: Mat4) -> Self {
+            GlamMat4::from_cols_array(&m.0)
+        }
+    }
+
+    impl From<GlamMat4> for Mat4 {
+        fn from(m: GlamMat4) -> Self {
+            Mat4(m.to_cols_array())
+        }
+    }
+
+    impl From<Vec3> for GlamVec3 {
+        fn from(v: Vec3) -> Self {
+            GlamVec3::new(v.x, v.y, v.z)
+        }
+    }
+
+    impl From<GlamVec3> for Vec3 {
+        fn from(v: GlamVec3) -> Self {
+            Vec3::new(v.x, v.y, v.z)
+        }
+    }
+
+    impl From<Quat> for GlamQuat {
+        fn from(q: Quat) -> Self {
+            GlamQuat::from_xyzw(q.x, q.y, q.z, q.w)
+        }
+    }
+
+    impl From<GlamQuat> for Quat {
+        fn from(q: GlamQuat) -> Self {
+            Quat {
+                x: q.x,
+                y: q.y,
+                z: q.z,
+                w: q.w,
+            }
+        }
+    }
+}
+
+#[cfg(feature = "nalgebra")]
+mod nalgebra_impl {
+    use super::{Float, Mat4, Quat, Vec3};
+
+    impl From<Mat4> for nalgebra::Matrix4<Float> {
+        fn from(m: Mat4) -> Self {
+            nalgebra::Matrix4::from_column_slice(&m.0)
+        }
+    }
+
+    impl From<nalgebra::Matrix4<Float>> for Mat4 {
+        fn from(m: nalgebra::Matrix4<Float>) -> Self {
+            let mut cols = [0.0; 16];
+            cols.copy_from_slice(m.as_slice());
+            Mat4(cols)
+        }
+    }
+
+    /// Transforms are not guaranteed to be affine (e.g. perspective camera transforms),
+    /// so they convert to a projective transformation.
+    impl From<Mat4> for nalgebra::Projective3<Float> {
+        fn from(m: Mat4) -> Self {
+            nalgebra::Projective3::from_matrix_unchecked(m.into())
+        }
+    }
+
+    impl From<Vec3> for nalgebra::Point3<Float> {
+        fn from(v: Vec3) -> Self {
+            nalgebra::Point3::new(v.x, v.y, v.z)
+        }
+    }
+
+    impl From<nalgebra::Point3<Float>> for Vec3 {
+        fn from(p: nalgebra::Point3<Float>) -> Self {
+            Vec3::new(p.x, p.y, p.z)
+        }
+    }
+
+    impl From<Vec3> for nalgebra::Vector3<Float> {
+        fn from(v: Vec3) -> Self {
+            nalgebra::Vector3::new(v.x, v.y, v.z)
+        }
+    }
+
+    impl From<nalgebra::Vector3<Float>> for Vec3 {
+        fn from(v: nalgebra::Vector3<Float>) -> Self {
+            Vec3::new(v.x, v.y, v.z)
+        }
+    }
+
+    /// Normals, e.g. `nalgebra::Vector3::z_axis()`.
+    impl From<nalgebra::Unit<nalgebra::Vector3<Float>>> for Vec3 {
+        fn from(v: nalgebra::Unit<nalgebra::Vector3<Float>>) -> Self {
+            v.into_inner().into()
+        }
+    }
+
+    /// Quaternions of [super::Decomposition] are normalized already.
+    impl From<Quat> for nalgebra::UnitQuaternion<Float> {
+        fn from(q: Quat) -> Self {
+            nalgebra::Unit::new_unchecked(nalgebra::Quaternion::new(q.w, q.x, q.y, q.z))
+        }
+    }
+
+    impl From<nalgebra::UnitQuaternion<Float>> for Quat {
+        fn from(q: nalgebra::UnitQuaternion<Float>) -> Self {
+            Quat {
+                x: q.i,
+                y: q.j,
+                z: q.k,
+                w: q.w,
+            }
+        }
+    }
+}
+
+#[cfg(feature = "mint")]
+mod mint_impl {
+    use super::{Float, Mat4, Quat, Vec3};
+
+    impl From<Mat4> for mint::ColumnMatrix4<Float> {
+        fn from(m: Mat4) -> Self {
+            m.0.into()
+        }
+    }
+
+    impl From<mint::ColumnMatrix4<Float>> for Mat4 {
+        fn from(m: mint::ColumnMatrix4<Float>) -> Self {
+            Mat4(m.into())
+        }
+    }
+
+    impl mint::IntoMint for Mat4 {
+        type MintType = mint::ColumnMatrix4<Float>;
+    }
+
+    impl From<Vec3> for mint::Vector3<Float> {
+        fn from(v: Vec3) -> Self {
+            mint::Vector3 {
+                x: v.x,
+                y: v.y,
+                z: v.z,
+            }
+        }
+    }
+
+    impl From<mint::Vector3<Float>> for Vec3 {
+        fn from(v: mint::Vector3<Float>) -> Self {
+            Vec3::new(v.x, v.y, v.z)
+        }
+    }
+
+    impl From<Vec3> for mint::Point3<Float> {
+        fn from(v: Vec3) -> Self {
+            mint::Point3 {
+                x: v.x,
+                y: v.y,
+                z: v.z,
+            }
+        }
+    }
+
+    impl From<mint::Point3<Float>> for Vec3 {
+        fn from(p: mint::Point3<Float>) -> Self {
+            Vec3::new(p.x, p.y, p.z)
+        }
+    }
+
+    impl mint::IntoMint for Vec3 {
+        type MintType = mint::Vector3<Float>;
+    }
+
+    impl From<Quat> for mint::Quaternion<Float> {
+        fn from(q: Quat) -> Self {
+            mint::Quaternion {
+                v: mint::Vector3 {
+                    x: q.x,
+                    y: q.y,
+                    z: q.z,
+                },
+                s: q.w,
+            }
+        }
+    }
+
+    impl From<mint::Quaternion<Float>> for Quat {
+        fn from(q: mint::Quaternion<Float>) -> Self {
+            Quat {
+                x: q.v.x,
+                y: q.v.y,
+                z: q.v.z,
+                w: q.s,
+            }
+        }
+    }
+
+    impl mint::IntoMint for Quat {
+        type MintType = mint::Quaternion<Float>;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_mat_eq(a: Mat4, b: Mat4) {
+        for (x, y) in a.to_cols_array().iter().zip(b.to_cols_array().iter()) {
+            assert!((x - y).abs() < 1e-5, "{:?} != {:?}", a, b);
+        }
+    }
+
+    #[test]
+    fn inverse() {
+        let m = Mat4::from_translation(Vec3::new(1.0, 2.0, 3.0))
+            * Mat4::from_axis_angle(Vec3::new(0.0, 1.0, 1.0), 0.7)
+            * Mat4::from_scale(Vec3::new(2.0, 3.0, 4.0));
+
+        assert_mat_eq(m * m.inverse(), Mat4::IDENTITY);
+        assert_mat_eq(m.inverse() * m, Mat4::IDENTITY);
+
+        assert!((m.determinant() - 24.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn singular_inverse() {
+        let m = Mat4::from_scale(Vec3::new(1.0, 0.0, 1.0));
+
+        assert_eq!(m.determinant(), 0.0);
+        assert!(!m.inverse().is_finite());
+    }
+
+    #[test]
+    fn transform_point() {
+        let m = Mat4::from_translation(Vec3::new(1.0, 2.0, 3.0))
+            * Mat4::from_scale(Vec3::new(2.0, 2.0, 2.0));
+
+        assert_eq!(
+            m.transform_point3(Vec3::new(1.0, 1.0, 1.0)),
+            Vec3::new(3.0, 4.0, 5.0)
+        );
+        assert_eq!(
+            m.transform_vector3(Vec3::new(1.0, 1.0, 1.0)),
+            Vec3::new(2.0, 2.0, 2.0)
+        );
+    }
+
+    #[test]
+    fn decompose() {
+        let rotation = Mat4::from_axis_angle(Vec3::new(0.0, 1.0, 1.0), 0.7);
+        let m = Mat4::from_translation(Vec3::new(1.0, 2.0, 3.0))
+            * rotation
+            * Mat4::from_scale(Vec3::new(2.0, 3.0, 4.0));
+
+        let d = m.decompose();
+        assert_eq!(d.translation, Vec3::new(1.0, 2.0, 3.0));
+        assert_mat_eq(d.rotation.to_mat4(), rotation);
+        assert!((d.scale - Vec3::new(2.0, 3.0, 4.0)).length() < 1e-5);
+        assert!(!d.shear);
+        assert_mat_eq(d.to_mat4(), m);
+
+        // Mirrored.
+        let m = rotation * Mat4::from_scale(Vec3::new(-2.0, 1.0, 1.0));
+        let d = m.decompose();
+        assert_mat_eq(d.rotation.to_mat4(), rotation);
+        assert!((d.scale - Vec3::new(-2.0, 1.0, 1.0)).length() < 1e-5);
+        assert_mat_eq(d.to_mat4(), m);
+
+        let mut shear = Mat4::IDENTITY.to_cols_array();
+        shear[4] = 0.5;
+        assert!(Mat4::from_cols_array(&shear).decompose().shear);
+
+        let mut projective = Mat4::IDENTITY.to_cols_array();
+        projective[11] = 1.0;
+        assert!(Mat4::from_cols_array(&projective).decompose().shear);
+    }
+
+    #[test]
+    fn quat_round_trip() {
+        let m = Mat4::from_axis_angle(Vec3::new(1.0, 2.0, 3.0), 1.2);
+        assert_mat_eq(Quat::from_mat4(&m).to_mat4(), m);
+
+        let m = Mat4::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), 3.1);
+        assert_mat_eq(Quat::from_mat4(&m).to_mat4(), m);
+    }
+
+    #[test]
+    fn transform_bounds() {
+        let b = Bounds3::new(Vec3::new(-1.0, -1.0, -1.0), Vec3::ONE);
+        assert!(Bounds3::EMPTY.is_empty());
+        assert_eq!(Bounds3::EMPTY.union(b), b);
+
+        let m = Mat4::from_translation(Vec3::new(10.0, 0.0, 0.0))
+            * Mat4::from_scale(Vec3::new(2.0, 1.0, 1.0));
+        assert_eq!(
+            b.transform(&m),
+            Bounds3::new(Vec3::new(8.0, -1.0, -1.0), Vec3::new(12.0, 1.0, 1.0))
+        );
+    }
+
+    #[test]
+    fn look_at() {
+        let m = Mat4::look_at_lh(
+            Vec3::new(0.0, 0.0, -5.0),
+            Vec3::ZERO,
+            Vec3::new(0.0, 1.0, 0.0),
+        );
+
+        // Camera looks down +z in camera space.
+        let p = m.transform_point3(Vec3::ZERO);
+        assert_mat_eq(
+            Mat4::from_translation(p),
+            Mat4::from_translation(Vec3::new(0.0, 0.0, 5.0)),
+        );
+    }
+
+    #[cfg(feature = "glam")]
+    #[test]
+    fn glam_parity() {
+        #[cfg(feature = "f64")]
+        use glam::{DMat4 as GlamMat4, DQuat as GlamQuat, DVec3 as GlamVec3};
+        #[cfg(not(feature = "f64"))]
+        use glam::{Mat4 as GlamMat4, Quat as GlamQuat, Vec3 as GlamVec3};
+
+        let axis = GlamVec3::new(1.0, 2.0, 3.0);
+
+        let ours = Mat4::from_axis_angle(axis.into(), 0.3)
+            * Mat4::look_at_lh(
+                Vec3::new(1.0, 2.0, 3.0),
+                Vec3::new(0.0, 0.5, 0.0),
+                Vec3::new(0.0, 1.0, 0.0),
+            );
+        let theirs = GlamMat4::from_axis_angle(axis.normalize(), 0.3)
+            * GlamMat4::look_at_lh(
+                GlamVec3::new(1.0, 2.0, 3.0),
+                GlamVec3::new(0.0, 0.5, 0.0),
+                GlamVec3::new(0.0, 1.0, 0.0),
+            );
+
+        assert_mat_eq(ours, theirs.into());
+        assert_mat_eq(ours.inverse(), theirs.inverse().into());
+
+        let q = Quat::from_mat4(&Mat4::from_axis_angle(axis.into(), 0.3));
+        let theirs = GlamQuat::from_axis_angle(axis.normalize(), 0.3);
+        assert!(GlamQuat::from(q).abs_diff_eq(theirs, 1e-5));
+        assert_eq!(Quat::from(GlamQuat::from(q)), q);
+    }
+
+    #[cfg(feature = "nalgebra")]
+    #[test]
+    fn nalgebra_parity() {
+        let m = Mat4::from_translation(Vec3::new(1.0, 2.0, 3.0))
+            * Mat4::from_axis_angle(Vec3::new(0.0, 1.0, 0.0), 0.5);
+
+        let n: nalgebra::Matrix4<Float> = m.into();
+        assert_eq!(n[(0, 3)], 1.0);
+        assert_eq!(n[(1, 3)], 2.0);
+        assert_eq!(n[(2, 3)], 3.0);
+        assert_eq!(Mat4::from(n), m);
+
+        let p = Vec3::new(4.0, 5.0, 6.0);
+        let transformed = nalgebra::Projective3::from(m).transform_point(&p.into());
+        assert_eq!(Vec3::from(transformed), m.transform_point3(p));
+
+        let v: nalgebra::Vector3<Float> = p.into();
+        assert_eq!(Vec3::from(v), p);
+        assert_eq!(
+            Vec3::from(nalgebra::Vector3::z_axis()),
+            Vec3::new(0.0, 0.0, 1.0)
+        );
+
+        let q: nalgebra::UnitQuaternion<Float> = m.decompose().rotation.into();
+        let expected = nalgebra::UnitQuaternion::from_axis_angle(&nalgebra::Vector3::y_axis(), 0.5);
+        assert!(q.angle_to(&expected) < 1e-5);
+        assert_eq!(Quat::from(q), m.decompose().rotation);
+    }
+
+    #[cfg(feature = "mint")]
+    #[test]
+    fn mint_round_trip() {
+        let m = Mat4::from_translation(Vec3::new(1.0, 2.0, 3.0));
+
+        let cols: mint::ColumnMatrix4<Float> = m.into();
+        assert_eq!(cols.w.x, 1.0);
+        assert_eq!(cols.w.y, 2.0);
+        assert_eq!(cols.w.z, 3.0);
+        assert_eq!(Mat4::from(cols), m);
+
+        let v = Vec3::new(1.0, 2.0, 3.0);
+        assert_eq!(Vec3::from(mint::Vector3::from(v)), v);
+        assert_eq!(Vec3::from(mint::Point3::from(v)), v);
+
+        let q = Quat {
+            x: 0.0,
+            y: 0.6,
+            z: 0.0,
+            w: 0.8,
+        };
+        let quaternion: mint::Quaternion<Float> = q.into();
+        assert_eq!(quaternion.s, 0.8);
+        assert_eq!(Quat::from(quaternion), q);
+    }
+}
@@ -0,0 +1,329 @@
+//! Combining scenes.
+
+use std::collections::HashSet;
+
+use crate::{math::Mat4, types::MaterialType, validate::EntityRef, Scene};
+
+/// Options that control how scenes are merged, see [Scene::merge_with_options].
+#[derive(Debug, Default, Clone)]
+pub struct MergeOptions {
+    /// Transformation applied to the merged content: the camera (if taken from the merged
+    /// scene), lights, shapes which are not part of objects and object instances.
+    pub transform: Option<Mat4>,
+}
+
+impl Scene {
+    /// Merge the content of `other` into this scene.
+    ///
+    /// See [Scene::merge_with_options].
+    pub fn merge(&mut self, other: Scene) {
+        self.merge_with_options(other, &MergeOptions::default())
+    }
+
+    /// Merge the content of `other` into this scene, e.g. to combine a camera and lighting
+    /// rig with geometry defined in another file.
+    ///
+    /// Entities of `other` are appended, and the indices they refer to each other by
+    /// (materials, textures, area lights, media and objects) are updated accordingly.
+    /// Named textures, materials and objects of `other` whose names are already taken are
+    /// renamed with a numeric suffix (`"name.1"`), references to renamed materials by
+    /// `mix` materials are updated.
+    ///
+    /// The camera, film, sampler, filter, integrator and accelerator of `other` are only used
+    /// if this scene doesn't define them. Options, the shutter interval and
+    /// [Scene::document] of this scene are kept.
+    pub fn merge_with_options(&mut self, mut other: Scene, options: &MergeOptions) {
+        let transform = |m: &mut Mat4| {
+            if let Some(root) = &options.transform {
+                *m = *root * *m;
+            }
+        };
+
+        let textures = self.textures.len();
+        let materials = self.materials.len();
+        let lights = self.lights.len();
+        let area_lights = self.area_lights.len();
+        let mediums = self.mediums.len();
+        let shapes = self.shapes.len();
+        let objects = self.objects.len();
+        let instances = self.instances.len();
+
+        let offset = |index: &mut Option<usize>, offset: usize| {
+            if let Some(index) = index {
+                *index += offset;
+            }
+        };
+
+        if self.camera.is_none() {
+            if let Some(camera) = &mut other.camera {
+                transform(&mut camera.transform);
+                transform(&mut camera.transform_end);
+                offset(&mut camera.exterior_medium_index, mediums);
+            }
+
+            self.camera = other.camera;
+        }
+
+        self.film = self.film.take().or(other.film);
+        self.sampler = self.sampler.take().or(other.sampler);
+        self.filter = self.filter.take().or(other.filter);
+        self.integrator = self.integrator.take().or(other.integrator);
+        self.accelerator = self.accelerator.take().or(other.accelerator);
+
+        let mut names = self
+            .textures
+            .iter()
+            .map(|texture| texture.name.clone())
+            .collect::<HashSet<_>>();
+        for mut texture in other.textures {
+            transform(&mut texture.transform);
+            texture.name = unique_name(texture.name, &mut names);
+            texture
+                .class
+                .texture_indices_mut(&mut |index| *index += textures);
+            self.textures.push(texture);
+        }
+
+        let mut names = self
+            .materials
+            .iter()
+            .filter_map(|material| material.name.clone())
+            .collect::<HashSet<_>>();
+        let mut renamed = Vec::new();
+        for material in &mut other.materials {
+            if let Some(name) = material.name.take() {
+                let unique = unique_name(name.clone(), &mut names);
+                if unique != name {
+                    renamed.push((name, unique.clone()));
+                }
+
+                material.name = Some(unique);
+            }
+        }
+
+        for mut material in other.materials {
+            material.texture_indices_mut(&mut |index| *index += textures);
+
+            if let MaterialType::Mix { materials, .. } = &mut material.ty {
+                for name in materials {
+                    if let Some((_, unique)) = renamed.iter().find(|(old, _)| old == name) {
+                        *name = unique.clone();
+                    }
+                }
+            }
+
+            self.materials.push(material);
+        }
+
+        for mut light in other.lights {
+            transform(&mut light.transform);
+            transform(&mut light.transform_end);
+            offset(&mut light.exterior_medium_index, mediums);
+            self.lights.push(light);
+        }
+
+        self.area_lights.extend(other.area_lights);
+
+        let mut names = self
+            .mediums
+            .iter()
+            .map(|medium| medium.name.clone())
+            .collect::<HashSet<_>>();
+        for mut medium in other.mediums {
+            transform(&mut medium.transform);
+            medium.name = unique_name(medium.name, &mut names);
+            self.mediums.push(medium);
+        }
+
+        for mut shape in other.shapes {
+            // Shapes of objects are placed by instances.
+            if shape.object_index.is_none() {
+                transform(&mut shape.transform);
+                transform(&mut shape.transform_end);
+
+                if shape.bounds.is_some() {
+                    shape.bounds = shape
+                        .params
+                        .bounds()
+                        .map(|bounds| bounds.transform(&shape.transform));
+                }
+            }
+
+            offset(&mut shape.material_index, materials);
+            offset(&mut shape.area_light_index, area_lights);
+            offset(&mut shape.object_index, objects);
+            offset(&mut shape.interior_medium_index, mediums);
+            offset(&mut shape.exterior_medium_index, mediums);
+            self.shapes.push(shape);
+        }
+
+        let mut names = self
+            .objects
+            .iter()
+            .map(|object| object.name.clone())
+            .collect::<HashSet<_>>();
+        for mut object in other.objects {
+            object.name = unique_name(object.name, &mut names);
+            offset(&mut object.shape_start, shapes);
+            self.objects.push(object);
+        }
+
+        for mut instance in other.instances {
+            transform(&mut instance.instance_to_world);
+            transform(&mut instance.instance_to_world_end);
+            instance.object_index += objects;
+            offset(&mut instance.area_light_index, area_lights);
+            self.instances.push(instance);
+        }
+
+        let dependencies = self.dependencies.iter().cloned().collect::<HashSet<_>>();
+        self.dependencies.extend(
+            other
+                .dependencies
+                .into_iter()
+                .filter(|dependency| !dependencies.contains(dependency)),
+        );
+
+        for mut issue in other.issues {
+            issue.entity = match issue.entity {
+                EntityRef::Material(index) => EntityRef::Material(index + materials),
+                EntityRef::Shape(index) => EntityRef::Shape(index + shapes),
+                EntityRef::Light(index) => EntityRef::Light(index + lights),
+                EntityRef::AreaLight(index) => EntityRef::AreaLight(index + area_lights),
+                EntityRef::Object(index) => EntityRef::Object(index + objects),
+                EntityRef::Instance(index) => EntityRef::Instance(index + instances),
+                entity => entity,
+            };
+            self.issues.push(issue);
+        }
+    }
+}
+
+/// `name`, or `name` with the first free numeric suffix if it's taken. The result is marked as taken.
+fn unique_name(name: String, taken: &mut HashSet<String>) -> String {
+    let name = if taken.contains(&name) {
+        (1..)
+            .map(|n| format!("{}.{}", name, n))
+            .find(|candidate| !taken.contains(candidate))
+            .expect("Unique name must exist")
+    } else {
+        name
+    };
+
+    taken.insert(name.clone());
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{math::Vec3, param::TexRef, Result};
+
+    #[test]
+    fn merge() -> Result<()> {
+        let rig = r#"
+MakeNamedMedium "fog" "string type" "homogeneous"
+LookAt 0 0 -5  0 0 0  0 1 0
+Camera "perspective"
+WorldBegin
+LightSource "distant"
+Texture "grain" "float" "windy"
+MakeNamedMaterial "metal" "string type" "conductor"
+ObjectBegin "prop"
+Shape "sphere"
+ObjectEnd
+"#;
+
+        let geometry = r#"
+Camera "orthographic"
+Film "rgb" "integer xresolution" 64
+WorldBegin
+MakeNamedMedium "fog" "string type" "homogeneous"
+Texture "grain" "float" "wrinkled"
+Texture "bumps" "float" "scale" "texture tex" "grain"
+MakeNamedMaterial "metal" "string type" "conductor" "texture roughness" "bumps"
+MakeNamedMaterial "blend" "string type" "mix" "string materials" [ "metal" "metal" ]
+AreaLightSource "diffuse"
+NamedMaterial "metal"
+Shape "disk"
+ObjectBegin "prop"
+Shape "sphere"
+ObjectEnd
+ObjectInstance "prop"
+"#;
+
+        let mut scene = Scene::load(rig, None)?;
+        let geometry = Scene::load(geometry, None)?;
+
+        let root = Mat4::from_translation(Vec3::new(0.0, 10.0, 0.0));
+        let options = MergeOptions {
+            transform: Some(root),
+        };
+        scene.merge_with_options(geometry, &options);
+
+        // Rig's camera is kept, the film is taken from the geometry.
+        assert!(matches!(
+            scene.camera.as_ref().unwrap().params,
+            crate::types::Camera::Perspective { .. }
+        ));
+        assert_eq!(scene.film.as_ref().unwrap().xresolution, 64);
+
+        let names = scene
+            .textures
+            .iter()
+            .map(|texture| texture.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["grain", "grain.1", "bumps"]);
+        assert_eq!(scene.textures[0].transform, Mat4::IDENTITY);
+        assert_eq!(scene.textures[1].transform, root);
+        assert!(matches!(
+            scene.textures[2].class,
+            crate::types::TextureClass::Scale {
+                tex: TexRef::Texture(1),
+                ..
+            }
+        ));
+
+        assert_eq!(scene.mediums[0].transform, Mat4::IDENTITY);
+        assert_eq!(scene.mediums[1].name, "fog.1");
+        assert_eq!(scene.mediums[1].transform, root);
+
+        assert_eq!(scene.materials[1].name.as_deref(), Some("metal.1"));
+        assert!(matches!(
+            scene.materials[1].ty,
+            MaterialType::Conductor {
+                roughness: TexRef::Texture(2),
+                ..
+            }
+        ));
+        assert!(matches!(
+            &scene.materials[2].ty,
+            MaterialType::Mix { materials, .. } if materials == &["metal.1", "metal.1"]
+        ));
+
+        let disk = &scene.shapes[1];
+        assert_eq!(disk.material_index, Some(1));
+        assert_eq!(disk.area_light_index, Some(0));
+        assert_eq!(disk.transform, root);
+
+        let sphere = &scene.shapes[2];
+        assert_eq!(sphere.object_index, Some(1));
+        assert_eq!(sphere.transform, Mat4::IDENTITY);
+
+        assert_eq!(scene.objects[1].name, "prop.1");
+        assert_eq!(scene.objects[1].shape_range(), Some(2..3));
+        assert_eq!(scene.instances[0].object_index, 1);
+        assert_eq!(scene.instances[0].instance_to_world, root);
+
+        Ok(())
+    }
+
+    #[test]
+    fn unique_names() {
+        let mut taken = HashSet::from(["a".to_string(), "a.1".to_string()]);
+
+        assert_eq!(unique_name("a".to_string(), &mut taken), "a.2");
+        assert_eq!(unique_name("b".to_string(), &mut taken), "b");
+        assert_eq!(unique_name("b".to_string(), &mut taken), "b.1");
+    }
+}
@@ -0,0 +1,419 @@
+//! Typed triangle mesh data.
+
+use crate::{
+    math::{narrow, Float, Vec3},
+    types::Shape,
+    Error, Result,
+};
+
+/// How face normals are weighted when computing vertex normals,
+/// see [TriangleMeshData::compute_smooth_normals].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum NormalWeighting {
+    /// By triangle area, larger triangles have more influence.
+    #[default]
+    Area,
+    /// By the angle of the triangle at the vertex, which doesn't depend on how
+    /// the surface is triangulated.
+    Angle,
+}
+
+/// Triangle mesh with vertex attributes grouped per vertex, see [Shape::mesh_data].
+///
+/// All per-vertex arrays are either empty or have one element per position.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TriangleMeshData {
+    /// Vertex positions.
+    pub positions: Vec<[Float; 3]>,
+    /// Per-vertex normals, empty if not present.
+    pub normals: Vec<[f32; 3]>,
+    /// Per-vertex tangents, empty if not present.
+    pub tangents: Vec<[f32; 3]>,
+    /// Per-vertex texture coordinates, empty if not present.
+    pub uvs: Vec<[f32; 2]>,
+    /// Vertex indices of each triangle.
+    pub indices: Vec<[u32; 3]>,
+    /// Per-triangle face indices, empty if not present.
+    pub face_indices: Vec<i32>,
+}
+
+impl TriangleMeshData {
+    /// Group flat arrays, as stored by [Shape::TriangleMesh], into per-vertex and per-triangle elements.
+    ///
+    /// Returns [Error::InvalidMeshData] if array lengths don't match or indices are out of range.
+    pub fn from_arrays(
+        positions: &[Float],
+        normals: &[f32],
+        tangents: &[f32],
+        uvs: &[f32],
+        indices: &[i32],
+        face_indices: &[i32],
+    ) -> Result<Self> {
+        let invalid = |parameter, reason| Error::InvalidMeshData { parameter, reason };
+
+        if positions.len() % 3 != 0 {
+            return Err(invalid("P", "number of positions is not a multiple of 3"));
+        }
+
+        if indices.len() % 3 != 0 {
+            return Err(invalid(
+                "indices",
+                "number of indices is not a multiple of 3",
+            ));
+        }
+
+        if indices.iter().any(|index| *index < 0) {
+            return Err(invalid("indices", "vertex index is out of range"));
+        }
+
+        let mesh = TriangleMeshData {
+            positions: positions
+                .chunks_exact(3)
+                .map(|p| [p[0], p[1], p[2]])
+                .collect(),
+            normals: normals
+                .chunks_exact(3)
+                .map(|n| [n[0], n[1], n[2]])
+                .collect(),
+            tangents: tangents
+                .chunks_exact(3)
+                .map(|s| [s[0], s[1], s[2]])
+                .collect(),
+            uvs: uvs.chunks_exact(2).map(|uv| [uv[0], uv[1]]).collect(),
+            indices: indices
+                .chunks_exact(3)
+                .map(|t| [t[0] as u32, t[1] as u32, t[2] as u32])
+                .collect(),
+            face_indices: face_indices.to_vec(),
+        };
+
+        // Leftovers would be dropped by `chunks_exact`.
+        if normals.len() % 3 != 0 {
+            return Err(invalid(
+                "N",
+                "number of normals doesn't match number of positions",
+            ));
+        }
+
+        if tangents.len() % 3 != 0 {
+            return Err(invalid(
+                "S",
+                "number of tangents doesn't match number of positions",
+            ));
+        }
+
+        if uvs.len() % 2 != 0 {
+            return Err(invalid(
+                "uv",
+                "number of uvs doesn't match number of positions",
+            ));
+        }
+
+        mesh.validate()?;
+        Ok(mesh)
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// Check that indices are in range and attribute arrays have matching lengths.
+    pub fn validate(&self) -> Result<()> {
+        let invalid = |parameter, reason| Err(Error::InvalidMeshData { parameter, reason });
+
+        let vertex_count = self.vertex_count();
+
+        if self
+            .indices
+            .iter()
+            .flatten()
+            .any(|i| *i as usize >= vertex_count)
+        {
+            return invalid("indices", "vertex index is out of range");
+        }
+
+        if !self.normals.is_empty() && self.normals.len() != vertex_count {
+            return invalid("N", "number of normals doesn't match number of positions");
+        }
+
+        if !self.tangents.is_empty() && self.tangents.len() != vertex_count {
+            return invalid("S", "number of tangents doesn't match number of positions");
+        }
+
+        if !self.uvs.is_empty() && self.uvs.len() != vertex_count {
+            return invalid("uv", "number of uvs doesn't match number of positions");
+        }
+
+        if !self.face_indices.is_empty() && self.face_indices.len() != self.triangle_count() {
+            return invalid(
+                "faceIndices",
+                "number of face indices doesn't match number of triangles",
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Positions of the vertices of each triangle.
+    pub fn triangles(&self) -> impl Iterator<Item = [[Float; 3]; 3]> + '_ {
+        self.indices
+            .iter()
+            .map(|t| t.map(|index| self.positions[index as usize]))
+    }
+
+    /// Replace [TriangleMeshData::normals] with smooth vertex normals, averaged from the
+    /// normals of adjacent triangles.
+    ///
+    /// Normals face the side from which the triangles' vertices are in counter-clockwise order,
+    /// or the opposite side if `reverse_orientation` is set. Pass [crate::ShapeEntity::reverse_orientation]
+    /// to get the orientation pbrt uses for the shape.
+    /// Vertices which are not used by any (non-degenerate) triangle get zero normals.
+    ///
+    /// Panics if indices are out of range, see [TriangleMeshData::validate].
+    pub fn compute_smooth_normals(
+        &mut self,
+        weighting: NormalWeighting,
+        reverse_orientation: bool,
+    ) {
+        let mut normals = vec![Vec3::ZERO; self.positions.len()];
+
+        for triangle in &self.indices {
+            let p = triangle.map(|index| Vec3::from(self.positions[index as usize]));
+
+            // Length of the cross product is twice the triangle's area.
+            let n = (p[1] - p[0]).cross(p[2] - p[0]);
+            if n.length() == 0.0 {
+                continue;
+            }
+
+            for (corner, index) in triangle.iter().enumerate() {
+                let weight = match weighting {
+                    NormalWeighting::Area => 1.0,
+                    NormalWeighting::Angle => {
+                        let a = p[(corner + 1) % 3] - p[corner];
+                        let b = p[(corner + 2) % 3] - p[corner];
+                        let cos = a.dot(b) / (a.length() * b.length());
+                        cos.clamp(-1.0, 1.0).acos() / n.length()
+                    }
+                };
+
+                let normal = &mut normals[*index as usize];
+                *normal = *normal + n * weight;
+            }
+        }
+
+        let sign = if reverse_orientation { -1.0 } else { 1.0 };
+
+        let normals = normals.into_iter().map(|n| {
+            let length = n.length();
+            if length > 0.0 {
+                (n * (sign / length)).to_array().map(narrow)
+            } else {
+                [0.0; 3]
+            }
+        });
+
+        self.normals = normals.collect();
+    }
+
+    /// Convert to a `"trianglemesh"` shape.
+    pub fn to_shape(&self, alpha: f32) -> Shape {
+        Shape::TriangleMesh {
+            alpha,
+            indices: self.indices.iter().flatten().map(|i| *i as i32).collect(),
+            positions: self.positions.iter().flatten().copied().collect(),
+            normals: self.normals.iter().flatten().copied().collect(),
+            tangents: self.tangents.iter().flatten().copied().collect(),
+            uvs: self.uvs.iter().flatten().copied().collect(),
+            face_indices: self.face_indices.clone(),
+        }
+    }
+}
+
+impl From<TriangleMeshData> for Shape {
+    fn from(mesh: TriangleMeshData) -> Self {
+        mesh.to_shape(1.0)
+    }
+}
+
+impl Shape {
+    /// Get typed mesh data of a `"trianglemesh"` shape, or a `"plymesh"` shape whose
+    /// mesh is loaded.
+    ///
+    /// Returns `None` for other shapes (use [Shape::tessellate] to convert them to
+    /// triangle meshes first) and [Error::InvalidMeshData] if mesh data is inconsistent.
+    pub fn mesh_data(&self) -> Result<Option<TriangleMeshData>> {
+        let mesh = match self {
+            Shape::TriangleMesh {
+                indices,
+                positions,
+                normals,
+                tangents,
+                uvs,
+                face_indices,
+                ..
+            } => TriangleMeshData::from_arrays(
+                positions,
+                normals,
+                tangents,
+                uvs,
+                indices,
+                face_indices,
+            )?,
+            #[cfg(feature = "ply")]
+            Shape::PlyMesh {
+                mesh: Some(mesh), ..
+            } => TriangleMeshData::from_arrays(
+                &mesh.positions,
+                &mesh.normals,
+                &[],
+                &mesh.uvs,
+                &mesh.indices,
+                &mesh.face_indices,
+            )?,
+            _ => return Ok(None),
+        };
+
+        Ok(Some(mesh))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(shape: &str) -> Shape {
+        let data = format!("WorldBegin\n{}", shape);
+        crate::Scene::load(&data, None)
+            .unwrap()
+            .shapes
+            .remove(0)
+            .params
+    }
+
+    #[test]
+    fn mesh_data() -> Result<()> {
+        let shape = parse(
+            r#"Shape "trianglemesh"
+                "point3 P" [ 0 0 0  1 0 0  1 1 0  0 1 0 ]
+                "normal N" [ 0 0 1  0 0 1  0 0 1  0 0 1 ]
+                "point2 uv" [ 0 0  1 0  1 1  0 1 ]
+                "integer indices" [ 0 1 2  0 2 3 ]
+                "integer faceIndices" [ 7 8 ]"#,
+        );
+
+        let mesh = shape.mesh_data()?.unwrap();
+        assert_eq!(mesh.vertex_count(), 4);
+        assert_eq!(mesh.triangle_count(), 2);
+        assert_eq!(mesh.positions[2], [1.0, 1.0, 0.0]);
+        assert_eq!(mesh.normals[3], [0.0, 0.0, 1.0]);
+        assert_eq!(mesh.uvs[1], [1.0, 0.0]);
+        assert_eq!(mesh.indices, [[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(mesh.face_indices, [7, 8]);
+        assert!(mesh.tangents.is_empty());
+
+        let triangle = mesh.triangles().nth(1).unwrap();
+        assert_eq!(
+            triangle,
+            [[0.0, 0.0, 0.0], [1.0, 1.0, 0.0], [0.0, 1.0, 0.0]]
+        );
+
+        assert_eq!(mesh.to_shape(1.0).mesh_data()?, Some(mesh));
+        assert_eq!(parse(r#"Shape "sphere""#).mesh_data()?, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn smooth_normals() {
+        let mut mesh = TriangleMeshData {
+            // Two triangles sharing the first vertex: a large one facing +z, a small one facing +y.
+            positions: vec![
+                [0.0, 0.0, 0.0],
+                [2.0, 0.0, 0.0],
+                [0.0, 2.0, 0.0],
+                [0.0, 0.0, 1.0],
+                [1.0, 0.0, 0.0],
+                // Unused.
+                [5.0, 5.0, 5.0],
+            ],
+            indices: vec![[0, 1, 2], [0, 3, 4]],
+            ..Default::default()
+        };
+
+        let assert_near = |a: [f32; 3], b: [f32; 3]| {
+            let d = (0..3).map(|i| (a[i] - b[i]).abs()).fold(0.0, f32::max);
+            assert!(d < 1e-6, "{:?} != {:?}", a, b);
+        };
+
+        mesh.compute_smooth_normals(NormalWeighting::Area, false);
+        assert_eq!(mesh.normals.len(), 6);
+        let length = 17.0f32.sqrt();
+        assert_near(mesh.normals[0], [0.0, 1.0 / length, 4.0 / length]);
+        assert_near(mesh.normals[1], [0.0, 0.0, 1.0]);
+        assert_near(mesh.normals[3], [0.0, 1.0, 0.0]);
+        assert_eq!(mesh.normals[5], [0.0; 3]);
+
+        // Both triangles have a right angle at the shared vertex.
+        mesh.compute_smooth_normals(NormalWeighting::Angle, false);
+        let d = 0.5f32.sqrt();
+        assert_near(mesh.normals[0], [0.0, d, d]);
+        assert_near(mesh.normals[2], [0.0, 0.0, 1.0]);
+
+        mesh.compute_smooth_normals(NormalWeighting::Angle, true);
+        assert_near(mesh.normals[0], [0.0, -d, -d]);
+        assert_near(mesh.normals[4], [0.0, -1.0, 0.0]);
+        assert!(mesh.validate().is_ok());
+    }
+
+    #[test]
+    fn invalid() {
+        let invalid = |shape: &str| match parse(shape).mesh_data() {
+            Err(Error::InvalidMeshData { parameter, .. }) => parameter,
+            result => panic!("Unexpected result: {:?}", result),
+        };
+
+        let p = r#""point3 P" [ 0 0 0  1 0 0  0 1 0 ]"#;
+
+        assert_eq!(
+            invalid(&format!(
+                r#"Shape "trianglemesh" {} "integer indices" [ 0 1 3 ]"#,
+                p
+            )),
+            "indices"
+        );
+        assert_eq!(
+            invalid(&format!(
+                r#"Shape "trianglemesh" {} "integer indices" [ 0 -1 2 ]"#,
+                p
+            )),
+            "indices"
+        );
+        assert_eq!(
+            invalid(&format!(
+                r#"Shape "trianglemesh" {} "integer indices" [ 0 1 2 ] "normal N" [ 0 0 1 ]"#,
+                p
+            )),
+            "N"
+        );
+        assert_eq!(
+            invalid(&format!(
+                r#"Shape "trianglemesh" {} "integer indices" [ 0 1 2 ] "point2 uv" [ 0 0 1 0 ]"#,
+                p
+            )),
+            "uv"
+        );
+        assert_eq!(
+            invalid(&format!(
+                r#"Shape "trianglemesh" {} "integer indices" [ 0 1 2 ] "integer faceIndices" [ 1 2 ]"#,
+                p
+            )),
+            "faceIndices"
+        );
+    }
+}
@@ -0,0 +1,126 @@
+//! Memory-mapped scene loading.
+//!
+//! Large scenes often keep geometry in multi-gigabyte included files, reading those into
+//! strings doubles the peak memory usage. Mapped files are tokenized in place instead,
+//! and the maps are kept alive until loading completes (the [Scene] doesn't borrow from them).
+
+use std::{fs::File, io, ops::Deref, path::Path, str};
+
+use memmap2::Mmap;
+
+use crate::{LoadOptions, Result, Scene};
+
+/// Opt-in to memory-map scene files, see [LoadOptions::mmap].
+///
+/// Can only be created with the unsafe [MmapOptIn::new], as mapped files must not change
+/// while they are in use.
+#[derive(Debug, Clone, Copy)]
+pub struct MmapOptIn(());
+
+impl MmapOptIn {
+    /// # Safety
+    ///
+    /// Scene files read with these options must not be modified (by this or any other
+    /// process) while the scene is loading. Modified files may contain invalid UTF-8 after
+    /// validation, which is undefined behavior.
+    pub unsafe fn new() -> Self {
+        MmapOptIn(())
+    }
+}
+
+/// Memory-mapped file with UTF-8 contents.
+pub(crate) struct MappedStr(Mmap);
+
+impl MappedStr {
+    pub fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path)?;
+
+        // SAFETY: the file must not be modified while it's mapped, which is the safety
+        // requirement of `MmapOptIn::new` and `Scene::from_file_mmap`.
+        let map = unsafe { Mmap::map(&file)? };
+
+        // Same error as `fs::read_to_string`.
+        str::from_utf8(&map).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        Ok(Self(map))
+    }
+}
+
+impl Deref for MappedStr {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        // SAFETY: validated in `MappedStr::open`.
+        unsafe { str::from_utf8_unchecked(&self.0) }
+    }
+}
+
+impl Scene {
+    /// Load a scene from a file at path, memory-mapping it and all included and imported files
+    /// (unless a [LoadOptions::resolver] is set).
+    ///
+    /// Files are copied only when preprocessing (see [LoadOptions::variables] and
+    /// [LoadOptions::defines]) changes them.
+    ///
+    /// # Safety
+    ///
+    /// The files must not be modified (by this or any other process) while the scene is
+    /// loading, otherwise the behavior is undefined.
+    pub unsafe fn from_file_mmap<P: AsRef<Path>>(path: P, options: &LoadOptions) -> Result<Scene> {
+        let path = path.as_ref();
+        let data = MappedStr::open(path)?;
+
+        let options = LoadOptions {
+            // SAFETY: forwarded to the caller.
+            mmap: Some(MmapOptIn::new()),
+            ..options.clone()
+        };
+
+        Self::load_impl(&data, path.parent(), Some(path), &options, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::Error;
+
+    #[test]
+    fn from_file_mmap() -> Result<()> {
+        let temp_dir = TempDir::new("pbrt-mmap-")?;
+        let temp_path = temp_dir.path();
+
+        fs::write(
+            temp_path.join("scene.pbrt"),
+            "WorldBegin\nInclude \"geometry.pbrt\"\nInclude \"empty.pbrt\"",
+        )?;
+        fs::write(
+            temp_path.join("geometry.pbrt"),
+            "Shape \"sphere\" \"float radius\" 2\nShape \"disk\"",
+        )?;
+        fs::write(temp_path.join("empty.pbrt"), "")?;
+
+        let path = temp_path.join("scene.pbrt");
+        // SAFETY: files are not modified while loading.
+        let scene = unsafe { Scene::from_file_mmap(&path, &LoadOptions::default())? };
+        let read = Scene::from_file(&path)?;
+
+        assert_eq!(scene.shapes.len(), 2);
+        assert_eq!(scene.shapes.len(), read.shapes.len());
+
+        fs::write(temp_path.join("geometry.pbrt"), b"Shape \"sphere\" # \xff")?;
+
+        // SAFETY: files are not modified while loading.
+        let Err(err) = (unsafe { Scene::from_file_mmap(&path, &LoadOptions::default()) }) else {
+            panic!("Expected error");
+        };
+        assert!(matches!(err.inner(), Error::Io(err) if err.kind() == io::ErrorKind::InvalidData));
+        assert_eq!(err.location().unwrap().line, 2);
+
+        Ok(())
+    }
+}
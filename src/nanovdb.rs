@@ -0,0 +1,331 @@
+//! NanoVDB grid metadata reader.
+//!
+//! pbrt's `"nanovdb"` medium reads its `density` (and optional `temperature`) grids from
+//! NanoVDB files. This module reads the grid descriptions stored in the file headers (names,
+//! value types, bounds and resolution) without decoding the grids, so volume renderers can
+//! validate files and allocate buffers up front.
+//!
+//! Grid data may be compressed, it's skipped without being read.
+
+use std::{
+    fs::File,
+    io::{self, BufReader, Read},
+    path::Path,
+};
+
+use crate::{
+    math::{Bounds3, Float, Vec3},
+    Error, Result,
+};
+
+/// `"NanoVDB0"`, written by older versions.
+const MAGIC_NUMBER: u64 = 0x3042_4456_6f6e_614e;
+/// `"NanoVDB2"`, written by NanoVDB 32.6 and later.
+const MAGIC_FILE: u64 = 0x3242_4456_6f6e_614e;
+
+/// Major version of the supported metadata layout.
+const MAJOR_VERSION: u32 = 32;
+
+const HEADER_SIZE: usize = 16;
+const METADATA_SIZE: usize = 176;
+
+/// Grids stored in a NanoVDB file.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NanoVdbFile {
+    /// Grids in the order they are stored.
+    pub grids: Vec<GridInfo>,
+}
+
+/// Description of a grid, as stored in the file's metadata.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GridInfo {
+    pub name: String,
+    /// Type of the voxel values.
+    pub grid_type: GridType,
+    /// Bounds of the active voxels in grid space (medium space for pbrt media).
+    pub world_bounds: Bounds3,
+    /// Index of the first active voxel.
+    pub index_min: [i32; 3],
+    /// Index of the last active voxel (inclusive).
+    pub index_max: [i32; 3],
+    /// Size of a voxel in grid space.
+    pub voxel_size: [f64; 3],
+    /// Number of active voxels.
+    pub voxel_count: u64,
+}
+
+impl GridInfo {
+    /// Number of voxels along each axis of the dense grid covering the active voxels.
+    pub fn resolution(&self) -> [u32; 3] {
+        let mut resolution = [0; 3];
+        for (i, axis) in resolution.iter_mut().enumerate() {
+            let len = i64::from(self.index_max[i]) - i64::from(self.index_min[i]) + 1;
+            *axis = len.clamp(0, u32::MAX.into()) as u32;
+        }
+        resolution
+    }
+}
+
+/// Type of the voxel values of a grid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum GridType {
+    Unknown,
+    Float,
+    Double,
+    Int16,
+    Int32,
+    Int64,
+    Vec3f,
+    Vec3d,
+    Mask,
+    Half,
+    UInt32,
+    Boolean,
+    Rgba8,
+    /// Quantized floats, 4 bits per value.
+    Fp4,
+    Fp8,
+    Fp16,
+    /// Quantized floats with variable bit width.
+    FpN,
+    Vec4f,
+    Vec4d,
+    /// Type added by later NanoVDB versions.
+    Other(u32),
+}
+
+impl From<u32> for GridType {
+    fn from(value: u32) -> Self {
+        match value {
+            0 => GridType::Unknown,
+            1 => GridType::Float,
+            2 => GridType::Double,
+            3 => GridType::Int16,
+            4 => GridType::Int32,
+            5 => GridType::Int64,
+            6 => GridType::Vec3f,
+            7 => GridType::Vec3d,
+            8 => GridType::Mask,
+            9 => GridType::Half,
+            10 => GridType::UInt32,
+            11 => GridType::Boolean,
+            12 => GridType::Rgba8,
+            13 => GridType::Fp4,
+            14 => GridType::Fp8,
+            15 => GridType::Fp16,
+            16 => GridType::FpN,
+            17 => GridType::Vec4f,
+            18 => GridType::Vec4d,
+            value => GridType::Other(value),
+        }
+    }
+}
+
+impl NanoVdbFile {
+    /// Read grid metadata from a file at path.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<NanoVdbFile> {
+        let file = File::open(path)?;
+        Self::read(BufReader::new(file))
+    }
+
+    /// Read grid metadata from a reader, grid data is skipped.
+    pub fn read<R: Read>(mut reader: R) -> Result<NanoVdbFile> {
+        let mut grids = Vec::new();
+
+        // Files consist of one or more segments: a header and grid metadata, then grid data.
+        loop {
+            let mut header = [0; HEADER_SIZE];
+            let read = read_full(&mut reader, &mut header)?;
+            if read == 0 && !grids.is_empty() {
+                break;
+            }
+            if read < HEADER_SIZE {
+                return Err(invalid("unexpected end of file"));
+            }
+
+            let magic = u64::from_le_bytes(header[0..8].try_into().unwrap());
+            if magic != MAGIC_NUMBER && magic != MAGIC_FILE {
+                return Err(invalid("not a NanoVDB file"));
+            }
+
+            let version = u32::from_le_bytes(header[8..12].try_into().unwrap());
+            if version >> 21 != MAJOR_VERSION {
+                return Err(invalid(format!(
+                    "unsupported version {}.{}.{}",
+                    version >> 21,
+                    (version >> 10) & 0x7ff,
+                    version & 0x3ff
+                )));
+            }
+
+            let grid_count = u16::from_le_bytes([header[12], header[13]]);
+            let mut data_size = 0u64;
+
+            for _ in 0..grid_count {
+                let (grid, size) = read_metadata(&mut reader)?;
+                grids.push(grid);
+                data_size += size;
+            }
+
+            let skipped = io::copy(&mut reader.by_ref().take(data_size), &mut io::sink())?;
+            if skipped < data_size {
+                return Err(invalid("unexpected end of file"));
+            }
+        }
+
+        Ok(NanoVdbFile { grids })
+    }
+
+    /// Find a grid by name.
+    pub fn grid(&self, name: &str) -> Option<&GridInfo> {
+        self.grids.iter().find(|grid| grid.name == name)
+    }
+}
+
+/// Read grid metadata and its name, returns the size of the grid data in the file.
+fn read_metadata(reader: &mut impl Read) -> Result<(GridInfo, u64)> {
+    let mut data = [0; METADATA_SIZE];
+    if read_full(reader, &mut data)? < METADATA_SIZE {
+        return Err(invalid("unexpected end of file"));
+    }
+
+    let u32_at = |offset: usize| u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+    let u64_at = |offset: usize| u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+    let f64_at = |offset: usize| f64::from_le_bytes(data[offset..offset + 8].try_into().unwrap());
+    let vec3_at = |offset: usize| [f64_at(offset), f64_at(offset + 8), f64_at(offset + 16)];
+    let coord_at = |offset: usize| [0, 4, 8].map(|i| u32_at(offset + i) as i32);
+
+    let file_size = u64_at(8);
+    let name_size = u32_at(136) as usize;
+
+    let mut name = vec![0; name_size];
+    if read_full(reader, &mut name)? < name_size {
+        return Err(invalid("unexpected end of file"));
+    }
+
+    // Names are stored with a terminating zero.
+    if let Some(end) = name.iter().position(|&byte| byte == 0) {
+        name.truncate(end);
+    }
+    let name = String::from_utf8(name).map_err(|_| invalid("grid name is not valid UTF-8"))?;
+
+    let point = |[x, y, z]: [f64; 3]| Vec3::new(x as Float, y as Float, z as Float);
+
+    let grid = GridInfo {
+        name,
+        grid_type: GridType::from(u32_at(32)),
+        world_bounds: Bounds3::new(point(vec3_at(40)), point(vec3_at(64))),
+        index_min: coord_at(88),
+        index_max: coord_at(100),
+        voxel_size: vec3_at(112),
+        voxel_count: u64_at(24),
+    };
+
+    Ok((grid, file_size))
+}
+
+/// Read until `buf` is full or the end of input, returns the number of bytes read.
+fn read_full(reader: &mut impl Read, buf: &mut [u8]) -> Result<usize> {
+    let mut read = 0;
+
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(len) => read += len,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    Ok(read)
+}
+
+fn invalid(message: impl Into<String>) -> Error {
+    Error::NanoVdb(message.into())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Write a NanoVDB file with `grids` of the given names and types, with dummy grid data.
+    pub(crate) fn write_file(grids: &[(&str, u32)]) -> Vec<u8> {
+        let mut out = Vec::new();
+
+        out.extend(MAGIC_FILE.to_le_bytes());
+        out.extend((MAJOR_VERSION << 21 | 6 << 10).to_le_bytes());
+        out.extend((grids.len() as u16).to_le_bytes());
+        out.extend(0u16.to_le_bytes());
+
+        for (name, ty) in grids {
+            let mut meta = Vec::new();
+            meta.extend(64u64.to_le_bytes());
+            meta.extend(64u64.to_le_bytes());
+            meta.extend(0u64.to_le_bytes());
+            meta.extend(1000u64.to_le_bytes());
+            meta.extend(ty.to_le_bytes());
+            meta.extend(2u32.to_le_bytes());
+            for value in [-1.0f64, 0.0, 0.0, 1.0, 2.0, 0.5] {
+                meta.extend(value.to_le_bytes());
+            }
+            for value in [-10i32, 0, 0, 9, 19, 4] {
+                meta.extend(value.to_le_bytes());
+            }
+            for value in [0.1f64, 0.1, 0.1] {
+                meta.extend(value.to_le_bytes());
+            }
+            meta.extend((name.len() as u32 + 1).to_le_bytes());
+            meta.resize(METADATA_SIZE, 0);
+
+            out.extend(meta);
+            out.extend(name.as_bytes());
+            out.push(0);
+        }
+
+        out.extend(vec![0xab; 64 * grids.len()]);
+        out
+    }
+
+    #[test]
+    fn read() -> Result<()> {
+        let mut data = write_file(&[("density", 1), ("temperature", 15)]);
+        data.extend(write_file(&[("velocity", 6)]));
+
+        let file = NanoVdbFile::read(data.as_slice())?;
+        assert_eq!(file.grids.len(), 3);
+
+        let density = file.grid("density").unwrap();
+        assert_eq!(density.grid_type, GridType::Float);
+        assert_eq!(density.resolution(), [20, 20, 5]);
+        assert_eq!(density.index_min, [-10, 0, 0]);
+        assert_eq!(density.voxel_count, 1000);
+        assert_eq!(density.world_bounds.min, Vec3::new(-1.0, 0.0, 0.0));
+        assert_eq!(density.world_bounds.max, Vec3::new(1.0, 2.0, 0.5));
+
+        assert_eq!(file.grid("temperature").unwrap().grid_type, GridType::Fp16);
+        assert_eq!(file.grids[2].grid_type, GridType::Vec3f);
+        assert!(file.grid("missing").is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_files() {
+        let err = NanoVdbFile::read(&b"VDB"[..]).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Invalid NanoVDB file: unexpected end of file"
+        );
+
+        let err = NanoVdbFile::read(&[0; 64][..]).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid NanoVDB file: not a NanoVDB file");
+
+        // Grid data is missing.
+        let mut data = write_file(&[("density", 1)]);
+        data.truncate(data.len() - 1);
+        assert!(NanoVdbFile::read(data.as_slice()).is_err());
+    }
+}
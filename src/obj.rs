@@ -0,0 +1,233 @@
+//! Wavefront OBJ mesh reader.
+//!
+//! Converts OBJ geometry to `"trianglemesh"` shapes, so meshes produced by other tools can be
+//! added to a [crate::Scene] before writing it out.
+//!
+//! Only vertices (`v`), texture coordinates (`vt`), normals (`vn`) and faces (`f`) are read.
+//! All groups and objects are merged into a single mesh, polygons are triangulated as a fan,
+//! and materials, lines and points are ignored. Coordinates are kept as is.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader},
+    path::Path,
+};
+
+use crate::{math::Float, types::Shape, Error, Result};
+
+impl Shape {
+    /// Read an OBJ file at path into a [Shape::TriangleMesh].
+    pub fn from_obj<P: AsRef<Path>>(path: P) -> Result<Shape> {
+        let file = File::open(path)?;
+        Self::read_obj(BufReader::new(file))
+    }
+
+    /// Read OBJ data from a buffered reader into a [Shape::TriangleMesh].
+    ///
+    /// Vertices with the same position, texture coordinates and normal are shared.
+    /// Normals and texture coordinates are only kept if every face vertex has them.
+    pub fn read_obj<R: BufRead>(reader: R) -> Result<Shape> {
+        let mut positions = Vec::<[Float; 3]>::new();
+        let mut uvs = Vec::<[f32; 2]>::new();
+        let mut normals = Vec::<[f32; 3]>::new();
+
+        // Unique (position, uv, normal) triplets, 0-based.
+        let mut vertices = Vec::<[Option<usize>; 3]>::new();
+        let mut lookup = HashMap::new();
+        let mut indices = Vec::new();
+
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            let error = |message: &str| Error::Obj(format!("line {}: {}", number + 1, message));
+
+            let mut tokens = line.split_whitespace();
+            match tokens.next() {
+                Some("v") => positions.push(floats(tokens).ok_or_else(|| error("invalid vertex"))?),
+                Some("vt") => {
+                    // The optional third (w) coordinate is ignored.
+                    let [u, v, _] = floats_with_default(tokens, 0.0)
+                        .ok_or_else(|| error("invalid texture coordinate"))?;
+                    uvs.push([u, v]);
+                }
+                Some("vn") => normals.push(floats(tokens).ok_or_else(|| error("invalid normal"))?),
+                Some("f") => {
+                    let mut face = Vec::new();
+
+                    for token in tokens {
+                        let mut parts = token.split('/');
+
+                        // Resolve 1-based (or negative, relative to the end) indices.
+                        let mut index = |count: usize, required: bool| -> Result<Option<usize>> {
+                            let part = parts.next().filter(|part| !part.is_empty());
+                            let Some(part) = part else {
+                                return if required {
+                                    Err(error("missing vertex index"))
+                                } else {
+                                    Ok(None)
+                                };
+                            };
+
+                            let index = part
+                                .parse::<i64>()
+                                .map_err(|_| error("invalid face index"))?;
+
+                            let resolved = if index < 0 {
+                                count as i64 + index
+                            } else {
+                                index - 1
+                            };
+
+                            if resolved < 0 || resolved >= count as i64 {
+                                return Err(error("face index out of range"));
+                            }
+
+                            Ok(Some(resolved as usize))
+                        };
+
+                        let key = [
+                            index(positions.len(), true)?,
+                            index(uvs.len(), false)?,
+                            index(normals.len(), false)?,
+                        ];
+
+                        let vertex = *lookup.entry(key).or_insert_with(|| {
+                            vertices.push(key);
+                            vertices.len() - 1
+                        });
+
+                        face.push(vertex as i32);
+                    }
+
+                    if face.len() < 3 {
+                        return Err(error("face has less than 3 vertices"));
+                    }
+
+                    for i in 1..face.len() - 1 {
+                        indices.extend_from_slice(&[face[0], face[i], face[i + 1]]);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let has_uvs = vertices.iter().all(|[_, uv, _]| uv.is_some());
+        let has_normals = vertices.iter().all(|[_, _, n]| n.is_some());
+
+        let mut shape_positions = Vec::with_capacity(vertices.len() * 3);
+        let mut shape_uvs = Vec::new();
+        let mut shape_normals = Vec::new();
+
+        for [p, uv, n] in vertices {
+            shape_positions.extend_from_slice(&positions[p.expect("Position is required")]);
+
+            if let (true, Some(uv)) = (has_uvs, uv) {
+                shape_uvs.extend_from_slice(&uvs[uv]);
+            }
+
+            if let (true, Some(n)) = (has_normals, n) {
+                shape_normals.extend_from_slice(&normals[n]);
+            }
+        }
+
+        Ok(Shape::TriangleMesh {
+            alpha: 1.0,
+            indices,
+            positions: shape_positions,
+            normals: shape_normals,
+            tangents: Vec::new(),
+            uvs: shape_uvs,
+            face_indices: Vec::new(),
+        })
+    }
+}
+
+/// Parse exactly 3 floats, ignoring anything after them (like the `w` component).
+fn floats<'a, T: std::str::FromStr>(mut tokens: impl Iterator<Item = &'a str>) -> Option<[T; 3]> {
+    let mut next = || tokens.next()?.parse().ok();
+    Some([next()?, next()?, next()?])
+}
+
+/// Parse up to 3 floats, at least 2 are required.
+fn floats_with_default<'a>(
+    mut tokens: impl Iterator<Item = &'a str>,
+    default: f32,
+) -> Option<[f32; 3]> {
+    let u = tokens.next()?.parse().ok()?;
+    let v = tokens.next()?.parse().ok()?;
+    let w = match tokens.next() {
+        Some(token) => token.parse().ok()?,
+        None => default,
+    };
+
+    Some([u, v, w])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_obj() -> Result<()> {
+        let data = "# Quad with a shared edge
+o quad
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0 1.0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+usemtl default
+f 1/1/1 2/2/1 3/3/1 4/4/1
+f -4/-4/-1 -2/-2/-1 -1/-1/-1
+";
+
+        let Shape::TriangleMesh {
+            indices,
+            positions,
+            normals,
+            uvs,
+            ..
+        } = Shape::read_obj(data.as_bytes())?
+        else {
+            panic!("Expected triangle mesh");
+        };
+
+        // Vertices of the second face are shared with the first one.
+        assert_eq!(indices, [0, 1, 2, 0, 2, 3, 0, 2, 3]);
+        assert_eq!(positions.len(), 12);
+        assert_eq!(&positions[9..], [0.0, 1.0, 0.0]);
+        assert_eq!(normals.len(), 12);
+        assert_eq!(&uvs[4..6], [1.0, 1.0]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_obj_partial_attributes() -> Result<()> {
+        let data = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv 1 1 0\nvn 0 0 1\nf 1//1 2//1 3//1\nf 2 4 3\n";
+
+        let Shape::TriangleMesh {
+            indices,
+            normals,
+            uvs,
+            ..
+        } = Shape::read_obj(data.as_bytes())?
+        else {
+            panic!("Expected triangle mesh");
+        };
+
+        // Vertices with and without normals are different vertices.
+        assert_eq!(indices, [0, 1, 2, 3, 4, 5]);
+        assert!(normals.is_empty());
+        assert!(uvs.is_empty());
+
+        let err = Shape::read_obj("v 0 0 0\nf 1 2 3\n".as_bytes()).unwrap_err();
+        assert!(matches!(err, Error::Obj(message) if message.starts_with("line 2")));
+
+        Ok(())
+    }
+}
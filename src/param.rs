@@ -1,16 +1,30 @@
 //! Parameter management.
 
 use std::{
+    borrow::Cow,
     collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader},
     num::{ParseFloatError, ParseIntError},
+    path::Path,
     result,
     str::{FromStr, ParseBoolError},
 };
 
-use crate::{Error, Result};
+use crate::{
+    fast_float::{parse_floats, FastFloat},
+    math::Float,
+    scene::resolve_path,
+    token::unescape,
+    Error, Result,
+};
+
+/// Derive [FromParams] for structs or [FromTypedParams] for enums.
+pub use pbrt4_derive::FromParams;
 
 /// Parameter type.
 #[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ParamType {
     Boolean,
     Float,
@@ -28,6 +42,43 @@ pub enum ParamType {
     Normal,
 }
 
+impl ParamType {
+    /// Number of values the type expects.
+    fn arity(self) -> Arity {
+        match self {
+            ParamType::Point2 | ParamType::Vector2 => Arity::Tuples(2),
+            // Grid media take arrays of RGB values.
+            ParamType::Point3
+            | ParamType::Vector3
+            | ParamType::Normal3
+            | ParamType::Normal
+            | ParamType::Rgb => Arity::Tuples(3),
+            ParamType::Blackbody => Arity::Exact(1),
+            // Strings may contain whitespace, spectra are either names or value pairs.
+            _ => Arity::Any,
+        }
+    }
+}
+
+/// Expected number of values of a parameter.
+#[derive(Clone, Copy)]
+enum Arity {
+    Any,
+    Exact(usize),
+    Tuples(usize),
+}
+
+impl Arity {
+    /// Returns a description of the expected number of values if `count` doesn't match.
+    fn check(self, count: usize) -> Option<String> {
+        match self {
+            Arity::Exact(n) if count != n => Some(n.to_string()),
+            Arity::Tuples(n) if count % n != 0 => Some(format!("a multiple of {}", n)),
+            _ => None,
+        }
+    }
+}
+
 impl FromStr for ParamType {
     type Err = Error;
 
@@ -54,12 +105,156 @@ impl FromStr for ParamType {
     }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub enum Spectrum {
     //  "rgb L" [ r g b ]
     Rgb([f32; 3]),
     // "blackbody L" 3000
     Blackbody(i32),
+    /// Measured spectrum built into pbrt, e.g. `"spectrum eta" "metal-Au-eta"`.
+    ///
+    /// The name is one of [NAMED_SPECTRA].
+    Named(&'static str),
+    /// Piecewise-linear spectrum as `(wavelength, value)` pairs, wavelengths are in nanometers.
+    ///
+    /// Written inline as `"spectrum L" [ 300 0.3 400 0.6 ]`, or read from a `.spd` file.
+    Sampled(Vec<(f32, f32)>),
+    /// Reference to a `.spd` file which was not loaded, e.g. `"spectrum L" "light.spd"`.
+    ///
+    /// Use [Spectrum::load] or [crate::LoadOptions::load_spectra] to read it.
+    File(String),
+}
+
+/// Names of the spectra built into pbrt-v4, which can be used as values of `spectrum` parameters.
+pub const NAMED_SPECTRA: &[&str] = &[
+    // Glasses (index of refraction).
+    "glass-BK7",
+    "glass-BAF10",
+    "glass-FK51A",
+    "glass-LASF9",
+    "glass-F5",
+    "glass-F10",
+    "glass-F11",
+    // Metals (complex index of refraction).
+    "metal-Ag-eta",
+    "metal-Ag-k",
+    "metal-Al-eta",
+    "metal-Al-k",
+    "metal-Au-eta",
+    "metal-Au-k",
+    "metal-Cu-eta",
+    "metal-Cu-k",
+    "metal-CuZn-eta",
+    "metal-CuZn-k",
+    "metal-MgO-eta",
+    "metal-MgO-k",
+    "metal-TiO2-eta",
+    "metal-TiO2-k",
+    // Standard illuminants.
+    "stdillum-A",
+    "stdillum-D50",
+    "stdillum-D65",
+    "stdillum-F1",
+    "stdillum-F2",
+    "stdillum-F3",
+    "stdillum-F4",
+    "stdillum-F5",
+    "stdillum-F6",
+    "stdillum-F7",
+    "stdillum-F8",
+    "stdillum-F9",
+    "stdillum-F10",
+    "stdillum-F11",
+    "stdillum-F12",
+    "illum-acesD60",
+];
+
+impl Spectrum {
+    /// Find a built-in spectrum by name, see [NAMED_SPECTRA].
+    pub fn named(name: &str) -> Option<Spectrum> {
+        NAMED_SPECTRA
+            .iter()
+            .find(|&&known| known == name)
+            .map(|&name| Spectrum::Named(name))
+    }
+
+    /// Read a `.spd` file into a [Spectrum::Sampled].
+    pub fn from_spd<P: AsRef<Path>>(path: P) -> Result<Spectrum> {
+        let file = File::open(path)?;
+        Self::read_spd(BufReader::new(file))
+    }
+
+    /// Read `.spd` data: whitespace separated wavelength and value pairs, `#` starts a comment.
+    pub fn read_spd<R: BufRead>(reader: R) -> Result<Spectrum> {
+        let mut values = Vec::new();
+
+        for (number, line) in reader.lines().enumerate() {
+            let line = line?;
+            let line = line.split('#').next().unwrap_or_default();
+
+            for token in line.split_whitespace() {
+                let value = token.parse::<f32>().map_err(|_| {
+                    Error::Spd(format!("line {}: invalid value {:?}", number + 1, token))
+                })?;
+                values.push(value);
+            }
+        }
+
+        if values.is_empty() || values.len() % 2 != 0 {
+            return Err(Error::Spd(
+                "expected wavelength and value pairs".to_string(),
+            ));
+        }
+
+        Ok(Spectrum::Sampled(pairs(&values)))
+    }
+
+    /// Get the spectrum with [Spectrum::File] read from disk, other spectra are returned as is.
+    ///
+    /// Relative file names are resolved against `working_directory` (the scene file's directory).
+    pub fn load(&self, working_directory: Option<&Path>) -> Result<Spectrum> {
+        match self {
+            Spectrum::File(filename) => Self::from_spd(resolve_path(working_directory, filename)?),
+            spectrum => Ok(spectrum.clone()),
+        }
+    }
+}
+
+fn pairs(values: &[f32]) -> Vec<(f32, f32)> {
+    values
+        .chunks_exact(2)
+        .map(|pair| (pair[0], pair[1]))
+        .collect()
+}
+
+/// Owned counterpart of [Spectrum], names are resolved against [NAMED_SPECTRA] when deserializing.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(rename = "Spectrum")]
+enum SpectrumRepr {
+    Rgb([f32; 3]),
+    Blackbody(i32),
+    Named(String),
+    Sampled(Vec<(f32, f32)>),
+    File(String),
+}
+
+// Implemented by hand, as derived `Deserialize` would borrow the name from the input.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Spectrum {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> result::Result<Self, D::Error> {
+        use serde::de::Error as _;
+
+        Ok(match SpectrumRepr::deserialize(deserializer)? {
+            SpectrumRepr::Rgb(rgb) => Spectrum::Rgb(rgb),
+            SpectrumRepr::Blackbody(temperature) => Spectrum::Blackbody(temperature),
+            SpectrumRepr::Named(name) => Spectrum::named(&name)
+                .ok_or_else(|| D::Error::custom(Error::UnknownSpectrum(name)))?,
+            SpectrumRepr::Sampled(samples) => Spectrum::Sampled(samples),
+            SpectrumRepr::File(filename) => Spectrum::File(filename),
+        })
+    }
 }
 
 /// Represents a single parsed parameter.
@@ -71,6 +266,8 @@ pub struct Param<'a> {
     pub ty: ParamType,
     /// One or more values.
     value: &'a str,
+    /// Index of the named texture of a `texture` parameter, see [ParamList::resolve_textures].
+    texture: Option<usize>,
 }
 
 impl<'a> Param<'a> {
@@ -83,7 +280,25 @@ impl<'a> Param<'a> {
 
         let name = split.next().ok_or(Error::InvalidParamName)?;
 
-        Ok(Self { name, ty, value })
+        let arity = ty.arity();
+        if !matches!(arity, Arity::Any) {
+            let count = value.split_ascii_whitespace().count();
+
+            if let Some(expected) = arity.check(count) {
+                return Err(Error::InvalidValueCount {
+                    name: format!("{} {}", ty_name, name),
+                    count,
+                    expected,
+                });
+            }
+        }
+
+        Ok(Self {
+            name,
+            ty,
+            value,
+            texture: None,
+        })
     }
 
     pub fn items<T: FromStr>(
@@ -111,10 +326,140 @@ impl<'a> Param<'a> {
             .collect::<result::Result<Vec<T>, <T as FromStr>::Err>>()
     }
 
+    /// Parse all values as floats, this is much faster than [Param::vec] for large arrays.
+    pub fn floats(&self) -> result::Result<Vec<f32>, ParseFloatError> {
+        parse_floats(self.value)
+    }
+
+    /// Parse all values as `f64`, regardless of the `f64` feature.
+    pub fn doubles(&self) -> result::Result<Vec<f64>, ParseFloatError> {
+        parse_floats(self.value)
+    }
+
+    /// Values of a `point2` parameter.
+    pub fn point2s(&self) -> Result<Vec<[f32; 2]>> {
+        self.tuples(&[ParamType::Point2])
+    }
+
+    /// Values of a `point3` parameter, in double precision with the `f64` feature.
+    pub fn point3s(&self) -> Result<Vec<[Float; 3]>> {
+        self.tuples(&[ParamType::Point3])
+    }
+
+    /// Values of a `vector2` parameter.
+    pub fn vector2s(&self) -> Result<Vec<[f32; 2]>> {
+        self.tuples(&[ParamType::Vector2])
+    }
+
+    /// Values of a `vector3` parameter, in double precision with the `f64` feature.
+    pub fn vector3s(&self) -> Result<Vec<[Float; 3]>> {
+        self.tuples(&[ParamType::Vector3])
+    }
+
+    /// Values of a `normal3` (or `normal`) parameter.
+    pub fn normals(&self) -> Result<Vec<[f32; 3]>> {
+        self.tuples(&[ParamType::Normal3, ParamType::Normal])
+    }
+
+    /// Group values into `N`-tuples, the number of values must be a multiple of `N`.
+    fn tuples<T: FastFloat + Copy, const N: usize>(
+        &self,
+        types: &[ParamType],
+    ) -> Result<Vec<[T; N]>> {
+        if !types.contains(&self.ty) {
+            return Err(Error::InvalidParamType);
+        }
+
+        let values = parse_floats::<T>(self.value)?;
+        if values.len() % N != 0 {
+            return Err(Error::ParseSlice);
+        }
+
+        Ok(values
+            .chunks_exact(N)
+            .map(|chunk| chunk.try_into().expect("Chunk must have N values"))
+            .collect())
+    }
+
+    /// Raw value text, as written in the scene file (without brackets).
+    pub fn value(&self) -> &'a str {
+        self.value
+    }
+
+    /// Get the value as a string, surrounding quotes are removed and escape sequences are replaced.
+    pub fn string(&self) -> Cow<'a, str> {
+        let value = self.value;
+        if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+            unescape(&value[1..value.len() - 1])
+        } else {
+            Cow::Borrowed(value)
+        }
+    }
+
+    /// Get all values of a string array, like `"string materials" [ "a" "b" ]`.
+    pub fn strings(&self) -> Vec<Cow<'a, str>> {
+        let value = self.value;
+        let bytes = value.as_bytes();
+        let mut strings = Vec::new();
+        let mut pos = 0;
+
+        loop {
+            while pos < bytes.len() && bytes[pos].is_ascii_whitespace() {
+                pos += 1;
+            }
+
+            if pos == bytes.len() {
+                break;
+            }
+
+            let start = pos;
+            if bytes[pos] == b'"' {
+                pos += 1;
+                while pos < bytes.len() && bytes[pos] != b'"' {
+                    // Skip escaped characters, so escaped quotes don't end the string.
+                    pos += if bytes[pos] == b'\\' { 2 } else { 1 };
+                }
+
+                let end = pos.min(bytes.len());
+                strings.push(unescape(&value[start + 1..end]));
+                pos = end + 1;
+            } else {
+                while pos < bytes.len() && !bytes[pos].is_ascii_whitespace() {
+                    pos += 1;
+                }
+
+                strings.push(Cow::Borrowed(&value[start..pos]));
+            }
+        }
+
+        strings
+    }
+
     pub fn spectrum(&self) -> Result<Spectrum> {
         let res = match self.ty {
             ParamType::Rgb => Spectrum::Rgb(self.rgb()?),
             ParamType::Blackbody => Spectrum::Blackbody(self.single()?),
+            // Names of built-in spectra and files are quoted strings.
+            // Built-in names have no extension.
+            ParamType::Spectrum if self.value.starts_with('"') => {
+                let name = self.string();
+                match Spectrum::named(&name) {
+                    Some(spectrum) => spectrum,
+                    None if Path::new(&*name).extension().is_some() => {
+                        Spectrum::File(name.to_string())
+                    }
+                    None => return Err(Error::UnknownSpectrum(name.to_string())),
+                }
+            }
+            // Inline wavelength and value pairs.
+            ParamType::Spectrum => {
+                let values = self.vec::<f32>()?;
+                if values.is_empty() || values.len() % 2 != 0 {
+                    return Err(Error::ParseSlice);
+                }
+
+                Spectrum::Sampled(pairs(&values))
+            }
             _ => return Err(Error::InvalidObjectType),
         };
 
@@ -151,7 +496,13 @@ impl<'a> ParamList<'a> {
         self.0.is_empty()
     }
 
-    fn vec<T: FromStr>(&self, name: &str) -> result::Result<Option<Vec<T>>, <T as FromStr>::Err> {
+    /// Parse all values of parameter `name` as `T`.
+    ///
+    /// Returns `None` if there is no such parameter.
+    pub fn vec<T: FromStr>(
+        &self,
+        name: &str,
+    ) -> result::Result<Option<Vec<T>>, <T as FromStr>::Err> {
         let res = match self.get(name).map(|param| param.vec()) {
             Some(v) => Some(v?),
             None => None,
@@ -161,13 +512,43 @@ impl<'a> ParamList<'a> {
     }
 
     pub fn floats(&self, name: &str) -> result::Result<Option<Vec<f32>>, ParseFloatError> {
-        self.vec(name)
+        self.get(name).map(Param::floats).transpose()
+    }
+
+    /// Get all values of parameter `name` as `f64`, see [Param::doubles].
+    pub fn doubles(&self, name: &str) -> result::Result<Option<Vec<f64>>, ParseFloatError> {
+        self.get(name).map(Param::doubles).transpose()
     }
 
     pub fn integers(&self, name: &str) -> result::Result<Option<Vec<i32>>, ParseIntError> {
         self.vec(name)
     }
 
+    /// Get `point2` values by name, see [Param::point2s].
+    pub fn point2s(&self, name: &str) -> Result<Option<Vec<[f32; 2]>>> {
+        self.get(name).map(Param::point2s).transpose()
+    }
+
+    /// Get `point3` values by name, see [Param::point3s].
+    pub fn point3s(&self, name: &str) -> Result<Option<Vec<[Float; 3]>>> {
+        self.get(name).map(Param::point3s).transpose()
+    }
+
+    /// Get `vector2` values by name, see [Param::vector2s].
+    pub fn vector2s(&self, name: &str) -> Result<Option<Vec<[f32; 2]>>> {
+        self.get(name).map(Param::vector2s).transpose()
+    }
+
+    /// Get `vector3` values by name, see [Param::vector3s].
+    pub fn vector3s(&self, name: &str) -> Result<Option<Vec<[Float; 3]>>> {
+        self.get(name).map(Param::vector3s).transpose()
+    }
+
+    /// Get `normal3` values by name, see [Param::normals].
+    pub fn normals(&self, name: &str) -> Result<Option<Vec<[f32; 3]>>> {
+        self.get(name).map(Param::normals).transpose()
+    }
+
     fn single<T: FromStr>(&self, name: &str, default: T) -> result::Result<T, <T as FromStr>::Err> {
         self.get(name)
             .map(|p| p.single::<T>())
@@ -193,8 +574,73 @@ impl<'a> ParamList<'a> {
         self.single(name, default)
     }
 
-    pub fn string(&self, name: &str) -> Option<&str> {
-        self.get(name).map(|v| v.value)
+    /// Get a string value by name.
+    ///
+    /// Surrounding quotes are removed and escape sequences are replaced.
+    ///
+    /// Earlier versions returned the raw value text with the quotes, use [Param::value]
+    /// via [ParamList::get] to get it.
+    pub fn string(&self, name: &str) -> Option<Cow<'a, str>> {
+        self.get(name).map(Param::string)
+    }
+
+    /// Extract a typed parameter structure, see [FromParams].
+    pub fn extract<T: FromParams>(&self) -> Result<T> {
+        T::from_params(self)
+    }
+
+    /// Iterate over parameters in arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = &Param<'a>> {
+        self.0.values()
+    }
+
+    /// Iterate over parameter names in arbitrary order.
+    pub fn keys(&self) -> impl Iterator<Item = &'a str> + '_ {
+        self.0.keys().copied()
+    }
+
+    /// Whether there is a parameter with name `name`.
+    pub fn contains(&self, name: &str) -> bool {
+        self.0.contains_key(name)
+    }
+
+    /// Resolve `texture` parameters to indices of named textures, so they can be read as [TexRef].
+    ///
+    /// `textures` maps texture names to their indices. Parameters referring to undefined
+    /// textures are removed, so the default values are used instead.
+    pub fn resolve_textures(&mut self, textures: &HashMap<String, usize>) {
+        self.0.retain(|_, param| {
+            if param.ty != ParamType::Texture {
+                return true;
+            }
+
+            param.texture = textures.get(&*param.string()).copied();
+            param.texture.is_some()
+        });
+    }
+
+    /// Remove a parameter by name, returning it if it was present.
+    pub fn remove(&mut self, name: &str) -> Option<Param<'a>> {
+        self.0.remove(name)
+    }
+
+    /// Parameters not read by [FromParams] implementation of `T`, e.g. to report
+    /// misspelled or unsupported parameters.
+    pub fn unused<T: FromParams>(&self) -> Vec<&Param<'a>> {
+        self.without(&T::param_names())
+    }
+
+    /// Parameters not read by [FromTypedParams] implementation of `T` for type `ty`.
+    ///
+    /// All parameters are returned if the type is unknown.
+    pub fn unused_typed<T: FromTypedParams>(&self, ty: &str) -> Vec<&Param<'a>> {
+        self.without(&T::param_names(ty).unwrap_or_default())
+    }
+
+    fn without(&self, names: &[&str]) -> Vec<&Param<'a>> {
+        self.iter()
+            .filter(|param| !names.contains(&param.name))
+            .collect()
     }
 
     pub fn extend(&mut self, other: &ParamList<'a>) {
@@ -204,6 +650,162 @@ impl<'a> ParamList<'a> {
     }
 }
 
+/// A value which can be read from a single parameter.
+pub trait FromParam: Sized {
+    fn from_param(param: &Param) -> Result<Self>;
+}
+
+/// A structure which can be read from a parameter list.
+///
+/// Usually implemented with `#[derive(FromParams)]`:
+///
+/// ```
+/// use pbrt4::param::{FromParams, Param, ParamList};
+///
+/// #[derive(FromParams)]
+/// struct Glass {
+///     #[param(default = 1.5)]
+///     eta: f32,
+///     #[param(name = "remaproughness", default = true)]
+///     remap: bool,
+///     normalmap: Option<String>,
+/// }
+///
+/// let mut params = ParamList::default();
+/// params.add(Param::new("float eta", "1.33")?)?;
+///
+/// let glass: Glass = params.extract()?;
+/// assert_eq!(glass.eta, 1.33);
+/// assert!(glass.remap);
+/// assert!(glass.normalmap.is_none());
+/// # Ok::<(), pbrt4::Error>(())
+/// ```
+pub trait FromParams: Sized {
+    fn from_params(params: &ParamList) -> Result<Self>;
+
+    /// Names of the parameters read by [FromParams::from_params], see [ParamList::unused].
+    ///
+    /// Implemented by the derive macro, empty by default.
+    fn param_names() -> Vec<&'static str> {
+        Vec::new()
+    }
+}
+
+/// A type selected by a type string (like `Shape "sphere"`) and read from a parameter list.
+///
+/// Usually implemented by deriving [FromParams] for an enum.
+pub trait FromTypedParams: Sized {
+    fn from_typed_params(ty: &str, params: &ParamList) -> Result<Self>;
+
+    /// Type string of the value, e.g. `"sphere"` for spheres.
+    fn type_name(&self) -> &'static str;
+
+    /// Names of the parameters read for type `ty`, or `None` if the type is unknown.
+    ///
+    /// Implemented by the derive macro, `None` by default.
+    fn param_names(_ty: &str) -> Option<Vec<&'static str>> {
+        None
+    }
+}
+
+/// Parameter value which is either given inline or refers to a named texture.
+///
+/// Texture references are read from parameters resolved with [ParamList::resolve_textures].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TexRef<T> {
+    Value(T),
+    /// Index of the texture in [crate::Scene::textures].
+    Texture(usize),
+}
+
+impl<T: FromParam> FromParam for TexRef<T> {
+    fn from_param(param: &Param) -> Result<Self> {
+        match param.ty {
+            ParamType::Texture => param
+                .texture
+                .map(TexRef::Texture)
+                .ok_or_else(|| Error::TextureNotFound(param.string().to_string())),
+            _ => Ok(TexRef::Value(T::from_param(param)?)),
+        }
+    }
+}
+
+macro_rules! impl_from_param {
+    ($($ty:ty),*) => {
+        $(
+            impl FromParam for $ty {
+                fn from_param(param: &Param) -> Result<Self> {
+                    Ok(param.single()?)
+                }
+            }
+
+            impl FromParam for Vec<$ty> {
+                fn from_param(param: &Param) -> Result<Self> {
+                    Ok(param.vec()?)
+                }
+            }
+        )*
+    };
+}
+
+impl_from_param!(i32, bool);
+
+macro_rules! impl_from_float_param {
+    ($($ty:ty),*) => {
+        $(
+            impl FromParam for $ty {
+                fn from_param(param: &Param) -> Result<Self> {
+                    Ok(param.single()?)
+                }
+            }
+
+            impl FromParam for Vec<$ty> {
+                fn from_param(param: &Param) -> Result<Self> {
+                    Ok(parse_floats(param.value())?)
+                }
+            }
+        )*
+    };
+}
+
+impl_from_float_param!(f32, f64);
+
+impl<T, const N: usize> FromParam for [T; N]
+where
+    Vec<T>: FromParam,
+{
+    fn from_param(param: &Param) -> Result<Self> {
+        Vec::<T>::from_param(param)?
+            .try_into()
+            .map_err(|_| Error::ParseSlice)
+    }
+}
+
+impl<T: FromParam> FromParam for Option<T> {
+    fn from_param(param: &Param) -> Result<Self> {
+        T::from_param(param).map(Some)
+    }
+}
+
+impl FromParam for String {
+    fn from_param(param: &Param) -> Result<Self> {
+        Ok(param.string().to_string())
+    }
+}
+
+impl FromParam for Vec<String> {
+    fn from_param(param: &Param) -> Result<Self> {
+        Ok(param.strings().into_iter().map(Cow::into_owned).collect())
+    }
+}
+
+impl FromParam for Spectrum {
+    fn from_param(param: &Param) -> Result<Self> {
+        param.spectrum()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -241,6 +843,34 @@ mod tests {
         assert_eq!(param.vec::<i32>().unwrap(), vec![-1, 0, 1]);
     }
 
+    #[test]
+    fn unquote_string() {
+        let mut list = ParamList::default();
+        list.add(Param::new("string filename", "\"foo.exr\"").unwrap())
+            .unwrap();
+
+        list.add(Param::new("string name", r#""a \"b\"""#).unwrap())
+            .unwrap();
+
+        assert_eq!(list.string("filename"), Some("foo.exr".into()));
+        assert_eq!(list.string("name"), Some(r#"a "b""#.into()));
+        assert_eq!(list.string("missing"), None);
+    }
+
+    #[test]
+    fn string_array() -> Result<()> {
+        let param = Param::new("string materials", r#""a b"  "c \"d\"" e"#)?;
+        assert_eq!(param.strings(), ["a b", r#"c "d""#, "e"]);
+
+        let strings = <[String; 3]>::from_param(&param)?;
+        assert_eq!(strings[2], "e");
+
+        let param = Param::new("string materials", r#""a""#)?;
+        assert!(<[String; 2]>::from_param(&param).is_err());
+
+        Ok(())
+    }
+
     #[test]
     fn parse_blackbody() -> Result<()> {
         let param = Param::new("blackbody I", "5500")?;
@@ -251,6 +881,214 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn parse_named_spectrum() -> Result<()> {
+        let param = Param::new("spectrum eta", "\"metal-Au-eta\"")?;
+        assert_eq!(param.spectrum()?, Spectrum::Named("metal-Au-eta"));
+
+        let param = Param::new("spectrum eta", "\"metal-Xx-eta\"")?;
+        assert!(matches!(
+            param.spectrum(),
+            Err(Error::UnknownSpectrum(name)) if name == "metal-Xx-eta"
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_sampled_spectrum() -> Result<()> {
+        let param = Param::new("spectrum L", "300 0.3 400 0.6 500 0.9")?;
+        assert_eq!(
+            param.spectrum()?,
+            Spectrum::Sampled(vec![(300.0, 0.3), (400.0, 0.6), (500.0, 0.9)])
+        );
+
+        let param = Param::new("spectrum L", "300 0.3 400")?;
+        assert!(matches!(param.spectrum(), Err(Error::ParseSlice)));
+
+        let param = Param::new("spectrum L", "\"spds/light.spd\"")?;
+        assert_eq!(
+            param.spectrum()?,
+            Spectrum::File("spds/light.spd".to_string())
+        );
+
+        let spd = "# Measured\n400 0.5\n500 0.75 # peak\n\n600 0.25\n";
+        assert_eq!(
+            Spectrum::read_spd(spd.as_bytes())?,
+            Spectrum::Sampled(vec![(400.0, 0.5), (500.0, 0.75), (600.0, 0.25)])
+        );
+
+        let err = Spectrum::read_spd("400 0.5\n500 x\n".as_bytes()).unwrap_err();
+        assert!(matches!(err, Error::Spd(message) if message.starts_with("line 2")));
+
+        Ok(())
+    }
+
+    #[derive(Debug, PartialEq, FromParams)]
+    struct Custom {
+        #[param(default = 2.0)]
+        radius: f32,
+        #[param(default = -radius)]
+        z_min: f32,
+        #[param(name = "P")]
+        positions: Vec<Float>,
+        #[param(required)]
+        filename: String,
+        reflectance: Option<TexRef<Spectrum>>,
+        window: [i32; 2],
+        #[param(skip, default = 7)]
+        skipped: i32,
+    }
+
+    #[derive(Debug, PartialEq, FromParams)]
+    enum CustomShape {
+        Sphere {
+            #[param(default = 1.0)]
+            radius: f32,
+        },
+        #[param(name = "trianglemesh")]
+        Mesh,
+    }
+
+    #[test]
+    fn derive_from_params() -> Result<()> {
+        let mut list = ParamList::default();
+        list.add(Param::new("string filename", "\"mesh.ply\"")?)?;
+        list.add(Param::new("point3 P", "0 1 2")?)?;
+        list.add(Param::new("texture reflectance", "\"checks\"")?)?;
+        list.add(Param::new("integer window", "4 5")?)?;
+        list.add(Param::new("float skipped", "1")?)?;
+
+        // Texture references must be resolved first.
+        assert!(matches!(
+            list.extract::<Custom>(),
+            Err(Error::TextureNotFound(name)) if name == "checks"
+        ));
+        list.resolve_textures(&HashMap::from([("checks".to_string(), 3)]));
+
+        assert_eq!(
+            list.extract::<Custom>()?,
+            Custom {
+                radius: 2.0,
+                z_min: -2.0,
+                positions: vec![0.0, 1.0, 2.0],
+                filename: "mesh.ply".to_string(),
+                reflectance: Some(TexRef::Texture(3)),
+                window: [4, 5],
+                skipped: 7,
+            }
+        );
+
+        assert!(matches!(
+            ParamList::default().extract::<Custom>(),
+            Err(Error::MissingRequiredParameter)
+        ));
+
+        assert_eq!(
+            CustomShape::from_typed_params("sphere", &ParamList::default())?,
+            CustomShape::Sphere { radius: 1.0 }
+        );
+        assert_eq!(
+            CustomShape::from_typed_params("trianglemesh", &list)?,
+            CustomShape::Mesh
+        );
+        assert_eq!(CustomShape::Mesh.type_name(), "trianglemesh");
+        assert!(matches!(
+            CustomShape::from_typed_params("disk", &list),
+            Err(Error::InvalidObjectType)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn unused_params() -> Result<()> {
+        use crate::types::{Film, FilmType, Shape};
+
+        let mut list = ParamList::default();
+        list.add(Param::new("float radius", "2")?)?;
+        list.add(Param::new("float raduis", "3")?)?;
+        list.add(Param::new("integer xresolution", "100")?)?;
+
+        assert!(list.contains("radius"));
+        let mut keys = list.keys().collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(keys, ["radius", "raduis", "xresolution"]);
+
+        let unused = list.unused_typed::<Shape>("sphere");
+        let mut names = unused.iter().map(|param| param.name).collect::<Vec<_>>();
+        names.sort();
+        assert_eq!(names, ["raduis", "xresolution"]);
+
+        // Unknown type.
+        assert_eq!(list.unused_typed::<Shape>("teapot").len(), 3);
+
+        assert_eq!(list.unused_typed::<FilmType>("rgb").len(), 3);
+        assert_eq!(list.unused::<Film>().len(), 2);
+
+        assert_eq!(list.remove("raduis").map(|param| param.value()), Some("3"));
+        assert!(!list.contains("raduis"));
+        assert_eq!(list.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn value_count() {
+        assert!(Param::new("point3 P", "0 0 0  1 0 0").is_ok());
+        assert!(Param::new("point3 P", "").is_ok());
+        assert!(Param::new("float f", "1 2 3 4").is_ok());
+
+        let err = Param::new("point3 P", "0 0 0  1").unwrap_err();
+        assert!(matches!(
+            &err,
+            Error::InvalidValueCount { name, count: 4, .. } if name == "point3 P"
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Parameter \"point3 P\" has 4 values, expected a multiple of 3"
+        );
+
+        let err = Param::new("rgb L", "1 0.5").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Parameter \"rgb L\" has 2 values, expected a multiple of 3"
+        );
+
+        assert!(Param::new("point2 uv", "0 0 1").is_err());
+        assert!(Param::new("normal N", "0 0 1 0").is_err());
+        assert!(Param::new("blackbody I", "5500 6500").is_err());
+    }
+
+    #[test]
+    fn parse_tuples() -> Result<()> {
+        let mut list = ParamList::default();
+        list.add(Param::new("point3 P", "0 0 0  1 0 0  0 1 0")?)?;
+        list.add(Param::new("normal N", "0 0 1  0 0 1")?)?;
+        list.add(Param::new("point2 uv", "0 0  1 0")?)?;
+        list.add(Param::new("vector3 dir", "1 2 3")?)?;
+
+        assert_eq!(
+            list.point3s("P")?,
+            Some(vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]])
+        );
+        assert_eq!(list.normals("N")?.unwrap().len(), 2);
+        assert_eq!(list.vector3s("dir")?, Some(vec![[1.0, 2.0, 3.0]]));
+        assert_eq!(list.point3s("missing")?, None);
+
+        assert_eq!(list.point2s("uv")?, Some(vec![[0.0, 0.0], [1.0, 0.0]]));
+        // Type mismatch.
+        assert!(matches!(list.point3s("N"), Err(Error::InvalidParamType)));
+        assert!(matches!(list.vector2s("dir"), Err(Error::InvalidParamType)));
+
+        // Planetary coordinates need double precision.
+        list.add(Param::new("float altitude", "6378137.125")?)?;
+        assert_eq!(list.doubles("altitude")?, Some(vec![6378137.125]));
+        assert_eq!(list.floats("altitude")?, Some(vec![6378137.0]));
+
+        Ok(())
+    }
+
     #[test]
     fn parse_rgb() -> Result<()> {
         let param = Param::new("rgb L", "7 0 7")?;
@@ -1,77 +1,81 @@
 //! Directives parser.
 
+use std::{borrow::Cow, mem, num::ParseFloatError, ops::Range, str::FromStr};
+
 use crate::{
+    math::Float,
     param::{Param, ParamList},
     token::{Directive, Token},
     tokenizer::Tokenizer,
-    Error, Result,
+    Error, Location, Result,
 };
 
 /// Parsed directive.
 #[derive(Debug, PartialEq)]
 pub enum Element<'a> {
-    Include(&'a str),
-    Import(&'a str),
+    Include(Cow<'a, str>),
+    Import(Cow<'a, str>),
     Option(Param<'a>),
     Film {
-        ty: &'a str,
+        ty: Cow<'a, str>,
         params: ParamList<'a>,
     },
     ColorSpace {
-        ty: &'a str,
+        ty: Cow<'a, str>,
     },
     Camera {
-        ty: &'a str,
+        ty: Cow<'a, str>,
         params: ParamList<'a>,
     },
     Sampler {
-        ty: &'a str,
+        ty: Cow<'a, str>,
         params: ParamList<'a>,
     },
     Integrator {
-        ty: &'a str,
+        ty: Cow<'a, str>,
         params: ParamList<'a>,
     },
     Accelerator {
-        ty: &'a str,
+        ty: Cow<'a, str>,
         params: ParamList<'a>,
     },
     CoordinateSystem {
-        name: &'a str,
+        name: Cow<'a, str>,
     },
     CoordSysTransform {
-        name: &'a str,
+        name: Cow<'a, str>,
     },
     PixelFilter {
-        name: &'a str,
+        ty: Cow<'a, str>,
+        params: ParamList<'a>,
     },
     Identity,
     /// `Translate x y z`
     Translate {
-        v: [f32; 3],
+        v: [Float; 3],
     },
     /// `Scale x y z`
     Scale {
-        v: [f32; 3],
+        v: [Float; 3],
     },
-    /// `Rotate angle x y z`
+    /// `Rotate angle x y z`, the angle is in degrees.
     Rotate {
-        angle: f32,
-        v: [f32; 3],
+        angle: Float,
+        v: [Float; 3],
     },
     /// `LookAt eye_x eye_y eye_z look_x look_y look_z up_x up_y up_z`
     LookAt {
-        eye: [f32; 3],
-        look_at: [f32; 3],
-        up: [f32; 3],
+        eye: [Float; 3],
+        look_at: [Float; 3],
+        up: [Float; 3],
     },
     /// `Transform m00 ... m33`
     Transform {
-        m: [f32; 16],
+        m: [Float; 16],
     },
     /// `ConcatTransform m00 .. m33`
     ConcatTransform {
-        m: [f32; 16],
+        m: [Float; 16],
     },
     /// `TransformTimes start end`.
     TransformTimes {
@@ -79,7 +83,7 @@ pub enum Element<'a> {
         end: f32,
     },
     ActiveTransform {
-        ty: &'a str,
+        ty: Cow<'a, str>,
     },
     /// `ReverseOrientation`.
     ReverseOrientation,
@@ -91,75 +95,251 @@ pub enum Element<'a> {
     AttributeEnd,
     /// `Attribute "target" parameter-list`
     Attribute {
-        target: &'a str,
+        target: Cow<'a, str>,
         params: ParamList<'a>,
     },
     LightSource {
-        ty: &'a str,
+        ty: Cow<'a, str>,
         params: ParamList<'a>,
     },
     AreaLightSource {
-        ty: &'a str,
+        ty: Cow<'a, str>,
         params: ParamList<'a>,
     },
     Material {
-        ty: &'a str,
+        ty: Cow<'a, str>,
         params: ParamList<'a>,
     },
     MakeNamedMaterial {
-        name: &'a str,
+        name: Cow<'a, str>,
         params: ParamList<'a>,
     },
     NamedMaterial {
-        name: &'a str,
+        name: Cow<'a, str>,
     },
     /// `Texture "name" "type" "class" [ parameter-list ]`
     Texture {
-        name: &'a str,
-        ty: &'a str,
-        class: &'a str,
+        name: Cow<'a, str>,
+        ty: Cow<'a, str>,
+        class: Cow<'a, str>,
         params: ParamList<'a>,
     },
     /// `Shape "name" parameter-list`
     Shape {
-        name: &'a str,
+        name: Cow<'a, str>,
         params: ParamList<'a>,
     },
     ObjectBegin {
-        name: &'a str,
+        name: Cow<'a, str>,
     },
     ObjectEnd,
     ObjectInstance {
-        name: &'a str,
+        name: Cow<'a, str>,
     },
     MakeNamedMedium {
-        name: &'a str,
+        name: Cow<'a, str>,
         params: ParamList<'a>,
     },
     MediumInterface {
-        interior: &'a str,
-        exterior: &'a str,
+        interior: Cow<'a, str>,
+        exterior: Cow<'a, str>,
     },
 }
 
+/// How a category of malformed input is handled, see [ParseOptions].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Strictness {
+    /// Fail with an error.
+    #[default]
+    Error,
+    /// Recover and report a warning.
+    Warn,
+    /// Recover silently.
+    Ignore,
+}
+
+/// Handling of malformed input by category.
+///
+/// Everything is an error by default.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Unknown directives, they're skipped along with their arguments.
+    pub unknown_directives: Strictness,
+    /// Parameters specified more than once in a parameter list, the last value is used.
+    pub duplicate_params: Strictness,
+    /// Included or imported files which can't be read, they're treated as empty files.
+    pub missing_includes: Strictness,
+    /// Parameter arrays without the closing `]`, which end at the next directive,
+    /// and brackets outside of parameter lists, which are skipped.
+    pub unmatched_braces: Strictness,
+    /// Statements with malformed arguments (e.g. a number which can't be parsed or a missing
+    /// string), they're skipped up to the next directive.
+    pub invalid_statements: Strictness,
+    /// Invalid UTF-8 sequences inside quoted strings, they're replaced with `U+FFFD`,
+    /// see [decode_bytes].
+    pub invalid_utf8: Strictness,
+}
+
+impl ParseOptions {
+    /// Recover from all problems and report warnings.
+    pub fn lenient() -> Self {
+        Self {
+            unknown_directives: Strictness::Warn,
+            duplicate_params: Strictness::Warn,
+            missing_includes: Strictness::Warn,
+            unmatched_braces: Strictness::Warn,
+            invalid_statements: Strictness::Warn,
+            invalid_utf8: Strictness::Warn,
+        }
+    }
+}
+
+/// Parsed directive along with its location in the source, see [Parser::parse_next_spanned].
+#[derive(Debug, PartialEq)]
+pub struct SpannedElement<'a> {
+    pub element: Element<'a>,
+    /// Byte range of the whole statement, from the directive keyword to the end of its last argument.
+    pub span: Range<usize>,
+    /// Byte ranges of parameters (from the quoted type and name to the end of the value),
+    /// in the order they appear in the source.
+    ///
+    /// Parameters specified more than once are listed each time they appear.
+    pub params: Vec<(&'a str, Range<usize>)>,
+}
+
 pub struct Parser<'a> {
     tokenizer: Tokenizer<'a>,
+    /// Offset of the last parsed directive.
+    statement_start: usize,
+    /// Offset of the last parsed parameter.
+    param_start: usize,
+    /// Names and spans of parameters of the last parsed directive.
+    param_spans: Vec<(&'a str, Range<usize>)>,
+    options: ParseOptions,
+    /// Problems the parser recovered from.
+    warnings: Vec<Error>,
 }
 
 impl<'a> Parser<'a> {
     pub fn new(str: &'a str) -> Self {
+        Self::with_options(str, ParseOptions::default())
+    }
+
+    /// Create a parser which recovers from malformed input according to `options`.
+    pub fn with_options(str: &'a str, options: ParseOptions) -> Self {
         let tokenizer = Tokenizer::new(str);
-        Self { tokenizer }
+        Self {
+            tokenizer,
+            statement_start: 0,
+            param_start: 0,
+            param_spans: Vec::new(),
+            options,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Take warnings about problems the parser recovered from so far, see [Strictness::Warn].
+    ///
+    /// Warnings are [Error::Located] errors.
+    pub fn take_warnings(&mut self) -> Vec<Error> {
+        mem::take(&mut self.warnings)
+    }
+
+    /// Fail with `err` or record a warning at `offset`, depending on `strictness`.
+    fn recover(&mut self, strictness: Strictness, err: Error, offset: usize) -> Result<()> {
+        match strictness {
+            Strictness::Error => Err(err.at(|| Location::new(self.tokenizer.source(), offset))),
+            Strictness::Warn => {
+                let location = Location::new(self.tokenizer.source(), offset);
+                self.warnings.push(err.at(|| location));
+                Ok(())
+            }
+            Strictness::Ignore => Ok(()),
+        }
+    }
+
+    /// Byte offset up to which the source has been consumed.
+    pub fn offset(&self) -> usize {
+        self.tokenizer.offset()
+    }
+
+    /// Location of the last parsed (or failed) directive.
+    pub fn statement_location(&self) -> Location {
+        Location::new(self.tokenizer.source(), self.statement_start)
     }
 
     /// Parse next element.
+    ///
+    /// Errors (other than [Error::EndOfFile]) are returned as [Error::Located],
+    /// pointing to the offending token. Malformed statements are skipped instead
+    /// if allowed by [ParseOptions::invalid_statements].
     pub fn parse_next(&mut self) -> Result<Element<'a>> {
-        let Some(next_token) = self.tokenizer.next() else {
-            return Err(Error::EndOfFile);
-        };
+        loop {
+            let err = match self.parse_element() {
+                Ok(element) => return Ok(element),
+                // Errors of other categories are located by `recover` when they aren't recovered from.
+                Err(err @ (Error::EndOfFile | Error::Located { .. })) => return Err(err),
+                Err(err) => err,
+            };
+
+            let offset = self.tokenizer.token_start();
+            self.recover(self.options.invalid_statements, err, offset)?;
+
+            // Resynchronize at the next directive, which may be the offending token itself
+            // (e.g. when arguments are missing).
+            let end = self.tokenizer.offset();
+            if offset > self.statement_start && self.tokenizer.token(offset, end).is_directive() {
+                self.tokenizer.seek(offset);
+            }
+
+            while matches!(self.tokenizer.peek_token(), Some(token) if !token.is_directive()) {
+                self.tokenizer.next();
+            }
+        }
+    }
+
+    /// Parse next element along with the byte ranges of the statement and its parameters
+    /// within the source, e.g. to highlight exact locations in editors and linters.
+    ///
+    /// Errors are the same as of [Parser::parse_next].
+    pub fn parse_next_spanned(&mut self) -> Result<SpannedElement<'a>> {
+        let element = self.parse_next()?;
+
+        Ok(SpannedElement {
+            element,
+            span: self.statement_start..self.tokenizer.offset(),
+            params: mem::take(&mut self.param_spans),
+        })
+    }
+
+    fn parse_element(&mut self) -> Result<Element<'a>> {
+        self.param_spans.clear();
+
+        let directive = loop {
+            let Some(next_token) = self.tokenizer.next() else {
+                return Err(Error::EndOfFile);
+            };
+
+            self.statement_start = self.tokenizer.token_start();
 
-        // Check if token is directive
-        let directive = next_token.directive().ok_or(Error::UnknownDirective)?;
+            // Check if token is directive
+            if let Some(directive) = next_token.directive() {
+                break directive;
+            }
+
+            let (strictness, err) = if next_token.is_open_brace() || next_token.is_close_brace() {
+                (self.options.unmatched_braces, Error::UnexpectedToken)
+            } else {
+                (self.options.unknown_directives, Error::UnknownDirective)
+            };
+
+            self.recover(strictness, err, self.statement_start)?;
+
+            // Skip arguments.
+            while matches!(self.tokenizer.peek_token(), Some(token) if !token.is_directive()) {
+                self.tokenizer.next();
+            }
+        };
 
         let element = match directive {
             Directive::Include => Element::Include(self.read_str()?),
@@ -195,7 +375,8 @@ impl<'a> Parser<'a> {
                 name: self.read_str()?,
             },
             Directive::PixelFilter => Element::PixelFilter {
-                name: self.read_str()?,
+                ty: self.read_str()?,
+                params: self.read_param_list()?,
             },
             Directive::Identity => Element::Identity,
             Directive::Translate => Element::Translate {
@@ -243,9 +424,13 @@ impl<'a> Parser<'a> {
                 start: self.read_float()?,
                 end: self.read_float()?,
             },
-            Directive::ActiveTransform => Element::ActiveTransform {
-                ty: self.read_str()?,
-            },
+            // `ActiveTransform` takes a bare keyword (`StartTime`, `EndTime` or `All`).
+            Directive::ActiveTransform => {
+                let token = self.read_token()?;
+                Element::ActiveTransform {
+                    ty: token.unquote().unwrap_or(Cow::Borrowed(token.value())),
+                }
+            }
             Directive::ReverseOrientation => Element::ReverseOrientation,
             Directive::WorldBegin => Element::WorldBegin,
             Directive::AttributeBegin => Element::AttributeBegin,
@@ -333,15 +518,15 @@ impl<'a> Parser<'a> {
         }
     }
 
-    /// Read token as `f32`.
-    fn read_float(&mut self) -> Result<f32> {
+    /// Read token as `f32` or `f64`.
+    fn read_float<F: FromStr<Err = ParseFloatError>>(&mut self) -> Result<F> {
         let token = self.read_token()?;
-        let parsed = token.parse::<f32>()?;
+        let parsed = token.parse::<F>()?;
         Ok(parsed)
     }
 
     /// Read 3 floats.
-    fn read_point(&mut self) -> Result<[f32; 3]> {
+    fn read_point(&mut self) -> Result<[Float; 3]> {
         let x = self.read_float()?;
         let y = self.read_float()?;
         let z = self.read_float()?;
@@ -350,16 +535,16 @@ impl<'a> Parser<'a> {
     }
 
     /// Read 16 floats.
-    fn read_matrix(&mut self) -> Result<[f32; 16]> {
-        let mut m = [0_f32; 16];
+    fn read_matrix(&mut self) -> Result<[Float; 16]> {
+        let mut m = [0.0; 16];
         for m in &mut m {
             *m = self.read_float()?;
         }
         Ok(m)
     }
 
-    /// Read a quoted string.
-    fn read_str(&mut self) -> Result<&'a str> {
+    /// Read a quoted string, escape sequences are replaced.
+    fn read_str(&mut self) -> Result<Cow<'a, str>> {
         let token = self.read_token()?;
         token.unquote().ok_or(Error::InvalidString)
     }
@@ -371,7 +556,12 @@ impl<'a> Parser<'a> {
     /// - "float scale" [10]
     /// - "float iso" 150
     fn read_param(&mut self) -> Result<Param<'a>> {
-        let type_and_name = self.read_str()?;
+        // Parameter declarations are not expected to contain escape sequences.
+        let type_and_name = self
+            .read_token()?
+            .unquote_raw()
+            .ok_or(Error::InvalidString)?;
+        self.param_start = self.tokenizer.token_start();
 
         let mut start = self.tokenizer.offset();
         let end;
@@ -380,22 +570,32 @@ impl<'a> Parser<'a> {
         let value = self.read_token()?;
 
         if value.is_open_brace() {
+            let open_start = self.tokenizer.token_start();
+
             // Skip brace offset
             start = self.tokenizer.offset();
 
             // Read array of values
             loop {
+                // Got directive or end of file without closing bracket token.
+                let err = match self.tokenizer.peek_token() {
+                    Some(token) if token.is_directive() => Some(Error::UnexpectedToken),
+                    None => Some(Error::NoToken),
+                    Some(_) => None,
+                };
+
+                if let Some(err) = err {
+                    self.recover(self.options.unmatched_braces, err, open_start)?;
+                    end = self.tokenizer.offset();
+                    break;
+                }
+
                 let value = self.read_token()?;
 
                 if value.is_close_brace() {
                     end = self.tokenizer.offset() - 1;
                     break;
                 }
-
-                // Got directive without closing bracket token.
-                if value.is_directive() {
-                    return Err(Error::UnexpectedToken);
-                }
             }
         } else {
             // Single value
@@ -405,6 +605,9 @@ impl<'a> Parser<'a> {
         let token = self.tokenizer.token(start, end);
         let param = Param::new(type_and_name, token.value())?;
 
+        self.param_spans
+            .push((param.name, self.param_start..self.tokenizer.offset()));
+
         Ok(param)
     }
 
@@ -417,7 +620,11 @@ impl<'a> Parser<'a> {
                 // Each parameter starts with a quoted string
                 Some(token) if token.is_quote() => {
                     let param = self.read_param()?;
-                    list.add(param)?;
+
+                    // The last value is kept when recovering.
+                    if let Err(err) = list.add(param) {
+                        self.recover(self.options.duplicate_params, err, self.param_start)?;
+                    }
                 }
                 // Other token, break loop
                 Some(_) => break,
@@ -430,6 +637,154 @@ impl<'a> Parser<'a> {
     }
 }
 
+/// Decode scene file contents which may contain invalid UTF-8, e.g. written by old exporters.
+///
+/// Invalid sequences in comments and outside of quoted strings are replaced with `U+FFFD`
+/// (the latter fail to parse anyway). Inside quoted strings, where they change
+/// names and paths, they're handled according to `strictness`: replaced, replaced with
+/// a warning (an [Error::InvalidUtf8] with its location) or rejected.
+///
+/// [Parser] borrows its input, so bytes are decoded before parsing:
+///
+/// ```
+/// # use pbrt4::{decode_bytes, Element, Parser, Strictness};
+/// let bytes = b"# Caf\xe9\nShape \"sphere\"";
+/// let (src, warnings) = decode_bytes(bytes, Strictness::Error)?;
+///
+/// let mut parser = Parser::new(&src);
+/// assert!(matches!(parser.parse_next()?, Element::Shape { .. }));
+/// assert!(warnings.is_empty());
+/// # Ok::<(), pbrt4::Error>(())
+/// ```
+pub fn decode_bytes(bytes: &[u8], strictness: Strictness) -> Result<(Cow<'_, str>, Vec<Error>)> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum Context {
+        Token,
+        Delimiter,
+        Quoted,
+        Comment,
+    }
+
+    let mut remaining = match std::str::from_utf8(bytes) {
+        Ok(src) => return Ok((Cow::Borrowed(src), Vec::new())),
+        Err(_) => bytes,
+    };
+
+    let mut decoded = String::with_capacity(bytes.len());
+    let mut warnings = Vec::new();
+
+    // Context at the end of `decoded`, tracked like the tokenizer does. Invalid sequences
+    // never contain ASCII bytes, so they don't change it.
+    let mut context = Context::Delimiter;
+    let mut escaped = false;
+
+    let mut track = |valid: &str| {
+        for byte in valid.bytes() {
+            context = match (context, byte) {
+                (Context::Quoted, _) if escaped => {
+                    escaped = false;
+                    Context::Quoted
+                }
+                (Context::Quoted, b'\\') => {
+                    escaped = true;
+                    Context::Quoted
+                }
+                (Context::Quoted, b'"') => Context::Delimiter,
+                (Context::Quoted, _) => Context::Quoted,
+                (Context::Comment, b'\n' | b'\r') => Context::Delimiter,
+                (Context::Comment, _) => Context::Comment,
+                (_, b'"') => Context::Quoted,
+                (Context::Delimiter, b'#') => Context::Comment,
+                (_, b' ' | b'\n' | b'\t' | b'\r' | b'[' | b']') => Context::Delimiter,
+                (_, _) => Context::Token,
+            };
+        }
+
+        context
+    };
+
+    loop {
+        let (valid, invalid) = match std::str::from_utf8(remaining) {
+            Ok(valid) => (valid, None),
+            Err(err) => {
+                let (valid, rest) = remaining.split_at(err.valid_up_to());
+                let len = err.error_len().unwrap_or(rest.len());
+                let valid = std::str::from_utf8(valid).expect("valid_up_to bytes are UTF-8");
+                (valid, Some(len))
+            }
+        };
+
+        decoded.push_str(valid);
+        let context = track(valid);
+
+        let Some(len) = invalid else {
+            break;
+        };
+
+        if context == Context::Quoted {
+            let location = || Location::new(&decoded, decoded.len());
+            match strictness {
+                Strictness::Error => return Err(Error::InvalidUtf8.at(location)),
+                Strictness::Warn => warnings.push(Error::InvalidUtf8.at(location)),
+                Strictness::Ignore => {}
+            }
+        }
+
+        decoded.push(char::REPLACEMENT_CHARACTER);
+        remaining = &remaining[valid.len() + len..];
+    }
+
+    Ok((Cow::Owned(decoded), warnings))
+}
+
+/// Scan directives in `src` without parsing their arguments.
+///
+/// Yields each directive along with the byte range of the whole statement
+/// (the directive keyword followed by all of its arguments) within `src`.
+/// This is much cheaper than full parsing and is useful for tools that only need to
+/// locate specific directives (for instance, to gather `Include` paths).
+///
+/// Scanning doesn't validate arguments, any tokens before the first directive are ignored.
+pub fn scan_directives(src: &str) -> DirectiveScanner<'_> {
+    DirectiveScanner {
+        tokenizer: Tokenizer::new(src),
+        current: None,
+    }
+}
+
+/// Iterator returned by [scan_directives].
+pub struct DirectiveScanner<'a> {
+    tokenizer: Tokenizer<'a>,
+    /// Directive being scanned and its span so far.
+    current: Option<(Directive, Range<usize>)>,
+}
+
+impl<'a> Iterator for DirectiveScanner<'a> {
+    type Item = (Directive, Range<usize>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(token) = self.tokenizer.next() {
+            let end = self.tokenizer.offset();
+            let start = end - token.token_size();
+
+            match token.directive() {
+                Some(directive) => {
+                    if let Some(prev) = self.current.replace((directive, start..end)) {
+                        return Some(prev);
+                    }
+                }
+                None => {
+                    if let Some((_, span)) = &mut self.current {
+                        span.end = end;
+                    }
+                }
+            }
+        }
+
+        self.current.take()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::param::ParamType;
@@ -446,12 +801,27 @@ Import \"geometry/bigcar.pbrt.gz\"
         );
 
         let element = parser.parse_next().unwrap();
-        assert!(matches!(element, Element::Include("geometry/car.pbrt")));
+        assert_eq!(element, Element::Include("geometry/car.pbrt".into()));
+
+        let element = parser.parse_next().unwrap();
+        assert_eq!(element, Element::Import("geometry/bigcar.pbrt.gz".into()));
+    }
+
+    #[test]
+    fn parse_escaped_strings() {
+        let mut parser = Parser::new(r#"Include "my \"car\".pbrt" Shape "sphere""#);
+
+        let element = parser.parse_next().unwrap();
+        assert_eq!(element, Element::Include(r#"my "car".pbrt"#.into()));
 
+        // Strings without escapes borrow the source.
         let element = parser.parse_next().unwrap();
         assert!(matches!(
             element,
-            Element::Import("geometry/bigcar.pbrt.gz")
+            Element::Shape {
+                name: Cow::Borrowed("sphere"),
+                ..
+            }
         ));
     }
 
@@ -551,7 +921,7 @@ LookAt 0 5.5 24 0 11 -10 0 1 0
 
         assert!(matches!(
             parser.parse_next().unwrap(),
-            Element::Film { ty: "rgb", .. }
+            Element::Film { ty, .. } if ty == "rgb"
         ));
 
         assert!(matches!(
@@ -575,4 +945,204 @@ LookAt 0 5.5 24 0 11 -10 0 1 0
 
         assert!(matches!(next, Element::ConcatTransform { .. }));
     }
+
+    #[test]
+    fn parse_recovery() {
+        let src = r#"
+Foo "bar" [ 1 2 ]
+Shape "sphere" "float radius" 1 "float radius" 2
+]
+Shape "trianglemesh" "integer indices" [ 0 1 2
+WorldBegin
+"#;
+
+        // Errors by default.
+        let mut parser = Parser::new(src);
+        let err = parser.parse_next().unwrap_err();
+        assert!(matches!(err.inner(), Error::UnknownDirective));
+        assert_eq!(err.location().unwrap().line, 2);
+
+        let mut parser = Parser::with_options(src, ParseOptions::lenient());
+
+        let Element::Shape { params, .. } = parser.parse_next().unwrap() else {
+            panic!("Expected shape");
+        };
+        assert_eq!(params.float("radius", 0.0).unwrap(), 2.0);
+
+        let Element::Shape { params, .. } = parser.parse_next().unwrap() else {
+            panic!("Expected shape");
+        };
+        assert_eq!(params.integers("indices").unwrap(), Some(vec![0, 1, 2]));
+
+        assert_eq!(parser.parse_next().unwrap(), Element::WorldBegin);
+
+        let lines = parser
+            .take_warnings()
+            .iter()
+            .map(|warning| warning.location().unwrap().line)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, [2, 3, 4, 5]);
+
+        // Unterminated array at the end of file.
+        let mut parser = Parser::new("Shape \"sphere\" \"float radius\" [ 1");
+        assert!(matches!(
+            parser.parse_next().unwrap_err().inner(),
+            Error::NoToken
+        ));
+
+        let options = ParseOptions {
+            unmatched_braces: Strictness::Ignore,
+            ..Default::default()
+        };
+        let mut parser = Parser::with_options("Shape \"sphere\" \"float radius\" [ 1", options);
+        assert!(matches!(
+            parser.parse_next().unwrap(),
+            Element::Shape { .. }
+        ));
+        assert!(parser.take_warnings().is_empty());
+    }
+
+    #[test]
+    fn parse_invalid_statements() {
+        let src = r#"
+Translate 1 x 3
+Shape "sphere" "float radius" [ 1 ]
+Texture "checks"
+Shape "disk" "radius" 2
+Scale 1 2
+WorldBegin
+"#;
+
+        let mut parser = Parser::new(src);
+        let err = parser.parse_next().unwrap_err();
+        assert!(matches!(err.inner(), Error::ParseFloat(_)));
+        assert_eq!(err.location().unwrap().line, 2);
+
+        let options = ParseOptions {
+            invalid_statements: Strictness::Warn,
+            ..Default::default()
+        };
+        let mut parser = Parser::with_options(src, options);
+
+        // Statements are skipped up to the next directive, even if it's where the error is found.
+        assert!(matches!(
+            parser.parse_next().unwrap(),
+            Element::Shape { name, .. } if name == "sphere"
+        ));
+        assert_eq!(parser.parse_next().unwrap(), Element::WorldBegin);
+        assert!(matches!(parser.parse_next(), Err(Error::EndOfFile)));
+
+        let warnings = parser
+            .take_warnings()
+            .iter()
+            .map(|warning| {
+                let location = warning.location().unwrap();
+                (location.line, location.column)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(warnings, [(2, 13), (5, 1), (5, 23), (7, 1)]);
+    }
+
+    #[test]
+    fn decode() -> Result<()> {
+        let bytes = b"# \xff\nShape \"sph\\\"\xe9re\" # \"\xff\n\"float \xff\"";
+
+        assert!(matches!(
+            decode_bytes(b"Shape", Strictness::Error)?,
+            (Cow::Borrowed("Shape"), warnings) if warnings.is_empty()
+        ));
+
+        let err = decode_bytes(bytes, Strictness::Error).unwrap_err();
+        assert!(matches!(err.inner(), Error::InvalidUtf8));
+        assert_eq!(err.location().unwrap().line, 2);
+        assert_eq!(err.location().unwrap().column, 13);
+
+        let (src, warnings) = decode_bytes(bytes, Strictness::Warn)?;
+        assert_eq!(
+            src,
+            "# \u{fffd}\nShape \"sph\\\"\u{fffd}re\" # \"\u{fffd}\n\"float \u{fffd}\""
+        );
+
+        // The comment ends the string, the quote in it is ignored.
+        let lines = warnings
+            .iter()
+            .map(|warning| warning.location().unwrap().line)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, [2, 3]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parse_spanned() -> Result<()> {
+        let src = r#"
+Shape "trianglemesh"
+    "point3 P" [ 0 0 0 1 0 0 0 1 0 ]
+    "integer indices" [ 0 1 2 ] # Comment
+WorldBegin
+Option "bool disablepixeljitter" true
+"#;
+
+        let mut parser = Parser::new(src);
+
+        let shape = parser.parse_next_spanned()?;
+        assert!(matches!(shape.element, Element::Shape { .. }));
+        assert_eq!(
+            &src[shape.span],
+            "Shape \"trianglemesh\"\n    \"point3 P\" [ 0 0 0 1 0 0 0 1 0 ]\n    \"integer indices\" [ 0 1 2 ]"
+        );
+
+        let params = shape
+            .params
+            .into_iter()
+            .map(|(name, span)| (name, &src[span]))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            params,
+            [
+                ("P", "\"point3 P\" [ 0 0 0 1 0 0 0 1 0 ]"),
+                ("indices", "\"integer indices\" [ 0 1 2 ]")
+            ]
+        );
+
+        let world = parser.parse_next_spanned()?;
+        assert_eq!(&src[world.span], "WorldBegin");
+        assert!(world.params.is_empty());
+
+        let option = parser.parse_next_spanned()?;
+        assert_eq!(&src[option.span], "Option \"bool disablepixeljitter\" true");
+        assert_eq!(
+            &src[option.params[0].1.clone()],
+            "\"bool disablepixeljitter\" true"
+        );
+
+        assert!(matches!(parser.parse_next_spanned(), Err(Error::EndOfFile)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn scan() {
+        let src = r#"
+Include "a.pbrt"
+# Comment
+Shape "sphere" "float radius" [ 1 ]
+WorldBegin
+"#;
+
+        let directives = scan_directives(src).collect::<Vec<_>>();
+        assert_eq!(directives.len(), 3);
+
+        let (directive, span) = directives[0].clone();
+        assert_eq!(directive, Directive::Include);
+        assert_eq!(&src[span], "Include \"a.pbrt\"");
+
+        let (directive, span) = directives[1].clone();
+        assert_eq!(directive, Directive::Shape);
+        assert_eq!(&src[span], "Shape \"sphere\" \"float radius\" [ 1 ]");
+
+        let (directive, span) = directives[2].clone();
+        assert_eq!(directive, Directive::WorldBegin);
+        assert_eq!(&src[span], "WorldBegin");
+    }
 }
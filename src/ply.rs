@@ -0,0 +1,1030 @@
+//! PLY mesh reader.
+//!
+//! pbrt can read triangle meshes stored in the PLY file format via the `"plymesh"` shape.
+//! This module implements a small reader for ASCII and binary PLY files that extracts
+//! the data pbrt cares about (positions, normals, texture coordinates and face indices).
+//!
+//! Vertex properties pbrt doesn't know about (such as per-vertex colors or quality values)
+//! are not discarded, but exposed as named attribute arrays via [PlyMesh::attributes].
+//!
+//! For very large files, [visit] delivers vertices and triangles in fixed size chunks
+//! to a [PlyVisitor], so the mesh never has to be held in memory at once.
+
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader, Read},
+    path::Path,
+    str::FromStr,
+};
+
+use crate::{math::Float, Error, Result};
+
+/// Triangle mesh loaded from a PLY file.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PlyMesh {
+    /// Vertex positions, 3 floats per vertex.
+    pub positions: Vec<Float>,
+    /// Per-vertex normals, 3 floats per vertex (empty if not present).
+    pub normals: Vec<f32>,
+    /// Per-vertex texture coordinates, 2 floats per vertex (empty if not present).
+    pub uvs: Vec<f32>,
+    /// Triangle vertex indices, 3 per triangle.
+    ///
+    /// Polygons with more than 3 vertices are triangulated as a fan.
+    pub indices: Vec<i32>,
+    /// Optional per-face indices (the `face_indices` face property), one per triangle.
+    pub face_indices: Vec<i32>,
+    /// Non-standard scalar vertex properties, keyed by property name.
+    ///
+    /// Each array holds one value per vertex.
+    pub attributes: HashMap<String, Vec<f32>>,
+}
+
+impl PlyMesh {
+    /// Read a PLY mesh from a file at path.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<PlyMesh> {
+        let file = File::open(path)?;
+        Self::read(BufReader::new(file))
+    }
+
+    /// Read a PLY mesh from a buffered reader.
+    pub fn read<R: BufRead>(reader: R) -> Result<PlyMesh> {
+        let mut mesh = PlyMesh::default();
+
+        let mut builder = MeshBuilder::new(&mut mesh);
+        visit(reader, DEFAULT_CHUNK_SIZE, &mut builder)?;
+        builder.finish();
+
+        Ok(mesh)
+    }
+
+    /// Returns the number of vertices in the mesh.
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len() / 3
+    }
+
+    /// Returns the number of triangles in the mesh.
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+}
+
+/// Default number of vertices (or faces) delivered per chunk by [PlyMesh::read].
+pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Maximum number of vertices (or faces) storage is reserved for up front.
+///
+/// Counts come from the (untrusted) header, larger meshes grow as the body is read,
+/// so a malformed header fails with an unexpected end of file instead of aborting
+/// on allocation.
+const MAX_RESERVE: usize = 1 << 20;
+
+/// Number of values to reserve for `count` elements with `len` values each.
+fn reserve_len(count: usize, len: usize) -> usize {
+    count.min(MAX_RESERVE) * len
+}
+
+/// Summary of a PLY file, available before any vertex data is read.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PlyInfo {
+    /// Total number of vertices in the file.
+    pub vertex_count: usize,
+    /// Total number of faces in the file (before triangulation).
+    pub face_count: usize,
+    /// Whether vertices have normals.
+    pub has_normals: bool,
+    /// Whether vertices have texture coordinates.
+    pub has_uvs: bool,
+    /// Whether faces have the `face_indices` property.
+    pub has_face_indices: bool,
+    /// Names of non-standard scalar vertex properties,
+    /// in the same order as [VertexChunk::attributes].
+    pub attributes: Vec<String>,
+}
+
+/// A contiguous range of vertices.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct VertexChunk {
+    /// Index of the first vertex in this chunk.
+    pub start: usize,
+    /// Vertex positions, 3 floats per vertex.
+    pub positions: Vec<Float>,
+    /// Vertex normals, 3 floats per vertex (empty if not present).
+    pub normals: Vec<f32>,
+    /// Texture coordinates, 2 floats per vertex (empty if not present).
+    pub uvs: Vec<f32>,
+    /// Custom vertex attributes, one array per name in [PlyInfo::attributes].
+    pub attributes: Vec<Vec<f32>>,
+}
+
+impl VertexChunk {
+    /// Returns the number of vertices in the chunk.
+    pub fn len(&self) -> usize {
+        self.positions.len() / 3
+    }
+
+    /// Returns `true` if the chunk has no vertices.
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    fn clear(&mut self) {
+        self.start += self.len();
+        self.positions.clear();
+        self.normals.clear();
+        self.uvs.clear();
+        self.attributes.iter_mut().for_each(Vec::clear);
+    }
+}
+
+/// A contiguous range of triangles.
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TriangleChunk {
+    /// Index of the first triangle in this chunk.
+    pub start: usize,
+    /// Triangle vertex indices, 3 per triangle.
+    pub indices: Vec<i32>,
+    /// Per-triangle face indices (empty if not present).
+    pub face_indices: Vec<i32>,
+}
+
+impl TriangleChunk {
+    /// Returns the number of triangles in the chunk.
+    pub fn len(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    /// Returns `true` if the chunk has no triangles.
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    fn clear(&mut self) {
+        self.start += self.len();
+        self.indices.clear();
+        self.face_indices.clear();
+    }
+}
+
+/// Receives PLY data incrementally from [visit].
+///
+/// Chunk buffers are reused between calls, so memory usage is bounded by the chunk size
+/// rather than by the size of the mesh.
+pub trait PlyVisitor {
+    /// Called once after the header is parsed, before any data is delivered.
+    fn info(&mut self, _info: &PlyInfo) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called for each range of vertices, in file order.
+    fn vertices(&mut self, chunk: &VertexChunk) -> Result<()>;
+
+    /// Called for each range of triangles, in file order.
+    fn triangles(&mut self, chunk: &TriangleChunk) -> Result<()>;
+}
+
+/// Read a PLY file incrementally, delivering at most `chunk_size` vertices
+/// (or faces) per callback to `visitor`.
+pub fn visit<R: BufRead, V: PlyVisitor>(
+    mut reader: R,
+    chunk_size: usize,
+    visitor: &mut V,
+) -> Result<()> {
+    let header = Header::read(&mut reader)?;
+    let chunk_size = chunk_size.max(1);
+
+    match header.format {
+        Format::Ascii => visit_body(&header, &mut AsciiReader::new(reader), chunk_size, visitor),
+        Format::BinaryLittleEndian => visit_body(
+            &header,
+            &mut BinaryReader::new(reader, false),
+            chunk_size,
+            visitor,
+        ),
+        Format::BinaryBigEndian => visit_body(
+            &header,
+            &mut BinaryReader::new(reader, true),
+            chunk_size,
+            visitor,
+        ),
+    }
+}
+
+fn visit_body(
+    header: &Header,
+    reader: &mut impl ValueReader,
+    chunk_size: usize,
+    visitor: &mut impl PlyVisitor,
+) -> Result<()> {
+    let vertex = header.element("vertex");
+    let face = header.element("face");
+
+    let has_normals = vertex.map_or(false, |e| ["nx", "ny", "nz"].iter().all(|n| e.has(n)));
+    let has_uvs = vertex.map_or(false, |e| {
+        UV_NAMES.iter().any(|(u, v)| e.has(u) && e.has(v))
+    });
+
+    // Map each property to its destination once, instead of matching names per vertex.
+    let mut attributes = Vec::new();
+    let targets = vertex
+        .map(|e| e.properties.as_slice())
+        .unwrap_or_default()
+        .iter()
+        .map(|property| match property.ty {
+            PropertyType::Scalar(ty) => {
+                let target = VertexTarget::new(&property.name, has_normals, has_uvs);
+                let target = target.unwrap_or_else(|| {
+                    attributes.push(property.name.clone());
+                    VertexTarget::Attribute(attributes.len() - 1)
+                });
+                Some((target, ty))
+            }
+            // List properties on vertices are not representable as attributes.
+            PropertyType::List { .. } => None,
+        })
+        .collect::<Vec<_>>();
+
+    let info = PlyInfo {
+        vertex_count: vertex.map_or(0, |e| e.count),
+        face_count: face.map_or(0, |e| e.count),
+        has_normals,
+        has_uvs,
+        has_face_indices: face.map_or(false, |e| e.has("face_indices")),
+        attributes,
+    };
+
+    visitor.info(&info)?;
+
+    for element in &header.elements {
+        match element.name.as_str() {
+            "vertex" => visit_vertices(element, &info, &targets, reader, chunk_size, visitor)?,
+            "face" => visit_faces(element, &info, reader, chunk_size, visitor)?,
+            // Skip elements pbrt doesn't use (edges, materials, etc).
+            _ => {
+                for _ in 0..element.count {
+                    for property in &element.properties {
+                        skip_property(property, reader)?;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn visit_vertices(
+    element: &Element,
+    info: &PlyInfo,
+    targets: &[Option<(VertexTarget, ScalarType)>],
+    reader: &mut impl ValueReader,
+    chunk_size: usize,
+    visitor: &mut impl PlyVisitor,
+) -> Result<()> {
+    let mut chunk = VertexChunk {
+        attributes: vec![Vec::new(); info.attributes.len()],
+        ..Default::default()
+    };
+
+    for _ in 0..element.count {
+        let mut position: [Float; 3] = [0.0; 3];
+        let mut normal = [0.0; 3];
+        let mut uv = [0.0; 2];
+
+        for (property, target) in element.properties.iter().zip(targets) {
+            let Some((target, ty)) = *target else {
+                skip_property(property, reader)?;
+                continue;
+            };
+
+            let value = reader.read(ty)?;
+
+            match target {
+                VertexTarget::Position(i) => position[i] = value as Float,
+                VertexTarget::Normal(i) => normal[i] = value as f32,
+                VertexTarget::Uv(i) => uv[i] = value as f32,
+                VertexTarget::Attribute(i) => chunk.attributes[i].push(value as f32),
+            }
+        }
+
+        chunk.positions.extend_from_slice(&position);
+        if info.has_normals {
+            chunk.normals.extend_from_slice(&normal);
+        }
+        if info.has_uvs {
+            chunk.uvs.extend_from_slice(&uv);
+        }
+
+        if chunk.len() == chunk_size {
+            visitor.vertices(&chunk)?;
+            chunk.clear();
+        }
+    }
+
+    if !chunk.is_empty() {
+        visitor.vertices(&chunk)?;
+    }
+
+    Ok(())
+}
+
+fn visit_faces(
+    element: &Element,
+    info: &PlyInfo,
+    reader: &mut impl ValueReader,
+    chunk_size: usize,
+    visitor: &mut impl PlyVisitor,
+) -> Result<()> {
+    let vertex_count = info.vertex_count as i64;
+
+    let mut chunk = TriangleChunk::default();
+    let mut polygon = Vec::with_capacity(4);
+
+    for _ in 0..element.count {
+        let mut face_index = None;
+        polygon.clear();
+
+        for property in &element.properties {
+            match (property.name.as_str(), property.ty) {
+                ("vertex_indices" | "vertex_index", PropertyType::List { count, item }) => {
+                    let len = reader.read(count)? as usize;
+                    for _ in 0..len {
+                        let index = reader.read(item)? as i64;
+                        if index < 0 || index >= vertex_count {
+                            return Err(Error::Ply(format!(
+                                "vertex index {} is out of range",
+                                index
+                            )));
+                        }
+                        polygon.push(index as i32);
+                    }
+                }
+                ("face_indices", PropertyType::Scalar(ty)) => {
+                    face_index = Some(reader.read(ty)? as i32);
+                }
+                _ => skip_property(property, reader)?,
+            }
+        }
+
+        if polygon.len() < 3 {
+            return Err(Error::Ply(format!(
+                "face with {} vertices is not allowed",
+                polygon.len()
+            )));
+        }
+
+        // Triangulate as a fan around the first vertex.
+        for i in 1..polygon.len() - 1 {
+            chunk
+                .indices
+                .extend_from_slice(&[polygon[0], polygon[i], polygon[i + 1]]);
+
+            if let Some(face_index) = face_index {
+                chunk.face_indices.push(face_index);
+            }
+        }
+
+        if chunk.len() >= chunk_size {
+            visitor.triangles(&chunk)?;
+            chunk.clear();
+        }
+    }
+
+    if !chunk.is_empty() {
+        visitor.triangles(&chunk)?;
+    }
+
+    Ok(())
+}
+
+/// Collects chunks into a [PlyMesh].
+struct MeshBuilder<'a> {
+    mesh: &'a mut PlyMesh,
+    attributes: Vec<Vec<f32>>,
+    names: Vec<String>,
+}
+
+impl<'a> MeshBuilder<'a> {
+    fn new(mesh: &'a mut PlyMesh) -> Self {
+        Self {
+            mesh,
+            attributes: Vec::new(),
+            names: Vec::new(),
+        }
+    }
+
+    fn finish(self) {
+        let attributes = self.names.into_iter().zip(self.attributes);
+        self.mesh.attributes.extend(attributes);
+    }
+}
+
+impl PlyVisitor for MeshBuilder<'_> {
+    fn info(&mut self, info: &PlyInfo) -> Result<()> {
+        let vertices = info.vertex_count;
+
+        self.mesh.positions.reserve(reserve_len(vertices, 3));
+        if info.has_normals {
+            self.mesh.normals.reserve(reserve_len(vertices, 3));
+        }
+        if info.has_uvs {
+            self.mesh.uvs.reserve(reserve_len(vertices, 2));
+        }
+        self.mesh.indices.reserve(reserve_len(info.face_count, 3));
+
+        self.names = info.attributes.clone();
+        self.attributes = vec![Vec::with_capacity(reserve_len(vertices, 1)); info.attributes.len()];
+
+        Ok(())
+    }
+
+    fn vertices(&mut self, chunk: &VertexChunk) -> Result<()> {
+        self.mesh.positions.extend_from_slice(&chunk.positions);
+        self.mesh.normals.extend_from_slice(&chunk.normals);
+        self.mesh.uvs.extend_from_slice(&chunk.uvs);
+
+        for (dst, src) in self.attributes.iter_mut().zip(&chunk.attributes) {
+            dst.extend_from_slice(src);
+        }
+
+        Ok(())
+    }
+
+    fn triangles(&mut self, chunk: &TriangleChunk) -> Result<()> {
+        self.mesh.indices.extend_from_slice(&chunk.indices);
+        self.mesh
+            .face_indices
+            .extend_from_slice(&chunk.face_indices);
+        Ok(())
+    }
+}
+
+/// Supported texture coordinates property names.
+const UV_NAMES: [(&str, &str); 4] = [
+    ("u", "v"),
+    ("s", "t"),
+    ("texture_u", "texture_v"),
+    ("texture_s", "texture_t"),
+];
+
+/// Where a vertex property value ends up.
+#[derive(Debug, Clone, Copy)]
+enum VertexTarget {
+    Position(usize),
+    Normal(usize),
+    Uv(usize),
+    /// Index into the custom attributes list.
+    Attribute(usize),
+}
+
+impl VertexTarget {
+    /// Returns `None` for non-standard properties.
+    fn new(name: &str, has_normals: bool, has_uvs: bool) -> Option<Self> {
+        let target = match name {
+            "x" => VertexTarget::Position(0),
+            "y" => VertexTarget::Position(1),
+            "z" => VertexTarget::Position(2),
+            "nx" if has_normals => VertexTarget::Normal(0),
+            "ny" if has_normals => VertexTarget::Normal(1),
+            "nz" if has_normals => VertexTarget::Normal(2),
+            _ if has_uvs && UV_NAMES.iter().any(|(u, _)| *u == name) => VertexTarget::Uv(0),
+            _ if has_uvs && UV_NAMES.iter().any(|(_, v)| *v == name) => VertexTarget::Uv(1),
+            _ => return None,
+        };
+
+        Some(target)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ScalarType {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl ScalarType {
+    fn size(self) -> usize {
+        match self {
+            ScalarType::I8 | ScalarType::U8 => 1,
+            ScalarType::I16 | ScalarType::U16 => 2,
+            ScalarType::I32 | ScalarType::U32 | ScalarType::F32 => 4,
+            ScalarType::F64 => 8,
+        }
+    }
+}
+
+impl FromStr for ScalarType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let ty = match s {
+            "char" | "int8" => ScalarType::I8,
+            "uchar" | "uint8" => ScalarType::U8,
+            "short" | "int16" => ScalarType::I16,
+            "ushort" | "uint16" => ScalarType::U16,
+            "int" | "int32" => ScalarType::I32,
+            "uint" | "uint32" => ScalarType::U32,
+            "float" | "float32" => ScalarType::F32,
+            "double" | "float64" => ScalarType::F64,
+            _ => return Err(Error::Ply(format!("unknown property type '{}'", s))),
+        };
+
+        Ok(ty)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PropertyType {
+    Scalar(ScalarType),
+    List { count: ScalarType, item: ScalarType },
+}
+
+#[derive(Debug)]
+struct Property {
+    name: String,
+    ty: PropertyType,
+}
+
+#[derive(Debug)]
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+impl Element {
+    fn has(&self, name: &str) -> bool {
+        self.properties.iter().any(|p| p.name == name)
+    }
+}
+
+#[derive(Debug)]
+struct Header {
+    format: Format,
+    elements: Vec<Element>,
+}
+
+impl Header {
+    fn element(&self, name: &str) -> Option<&Element> {
+        self.elements.iter().find(|e| e.name == name)
+    }
+
+    fn read<R: BufRead>(reader: &mut R) -> Result<Header> {
+        let mut line = String::new();
+
+        let mut next_line = |line: &mut String| -> Result<()> {
+            line.clear();
+            if reader.read_line(line)? == 0 {
+                return Err(Error::Ply("unexpected end of header".to_string()));
+            }
+            Ok(())
+        };
+
+        next_line(&mut line)?;
+        if line.trim_end() != "ply" {
+            return Err(Error::Ply("missing 'ply' magic".to_string()));
+        }
+
+        let mut format = None;
+        let mut elements: Vec<Element> = Vec::new();
+
+        loop {
+            next_line(&mut line)?;
+
+            let mut words = line.split_whitespace();
+            let Some(keyword) = words.next() else {
+                continue;
+            };
+
+            match keyword {
+                "format" => {
+                    format = Some(match words.next() {
+                        Some("ascii") => Format::Ascii,
+                        Some("binary_little_endian") => Format::BinaryLittleEndian,
+                        Some("binary_big_endian") => Format::BinaryBigEndian,
+                        _ => return Err(Error::Ply("unsupported format".to_string())),
+                    });
+                }
+                "comment" | "obj_info" => {}
+                "element" => {
+                    let name = words
+                        .next()
+                        .ok_or(Error::Ply("element name expected".to_string()))?;
+                    let count = words
+                        .next()
+                        .ok_or(Error::Ply("element count expected".to_string()))?
+                        .parse()?;
+
+                    elements.push(Element {
+                        name: name.to_string(),
+                        count,
+                        properties: Vec::new(),
+                    });
+                }
+                "property" => {
+                    let element = elements
+                        .last_mut()
+                        .ok_or(Error::Ply("property defined before element".to_string()))?;
+
+                    let ty = match words.next() {
+                        Some("list") => {
+                            let count = parse_type(words.next())?;
+                            let item = parse_type(words.next())?;
+                            PropertyType::List { count, item }
+                        }
+                        ty => PropertyType::Scalar(parse_type(ty)?),
+                    };
+
+                    let name = words
+                        .next()
+                        .ok_or(Error::Ply("property name expected".to_string()))?;
+
+                    element.properties.push(Property {
+                        name: name.to_string(),
+                        ty,
+                    });
+                }
+                "end_header" => break,
+                _ => {
+                    return Err(Error::Ply(format!(
+                        "unexpected header keyword '{}'",
+                        keyword
+                    )))
+                }
+            }
+        }
+
+        let format = format.ok_or(Error::Ply("missing format".to_string()))?;
+
+        Ok(Header { format, elements })
+    }
+}
+
+fn parse_type(ty: Option<&str>) -> Result<ScalarType> {
+    ty.ok_or(Error::Ply("property type expected".to_string()))?
+        .parse()
+}
+
+fn skip_property(property: &Property, reader: &mut impl ValueReader) -> Result<()> {
+    match property.ty {
+        PropertyType::Scalar(ty) => reader.skip(ty, 1),
+        PropertyType::List { count, item } => {
+            let len = reader.read(count)? as usize;
+            reader.skip(item, len)
+        }
+    }
+}
+
+/// Reads individual property values from the PLY body.
+trait ValueReader {
+    fn read(&mut self, ty: ScalarType) -> Result<f64>;
+
+    fn skip(&mut self, ty: ScalarType, count: usize) -> Result<()> {
+        for _ in 0..count {
+            self.read(ty)?;
+        }
+        Ok(())
+    }
+}
+
+struct AsciiReader<R> {
+    reader: R,
+    line: String,
+    offset: usize,
+}
+
+impl<R: BufRead> AsciiReader<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            reader,
+            line: String::new(),
+            offset: 0,
+        }
+    }
+
+    fn next_word(&mut self) -> Result<&str> {
+        loop {
+            let rest = &self.line[self.offset..];
+            let trimmed = rest.trim_start();
+
+            if !trimmed.is_empty() {
+                let start = self.offset + (rest.len() - trimmed.len());
+                let len = trimmed.find(char::is_whitespace).unwrap_or(trimmed.len());
+
+                self.offset = start + len;
+                return Ok(&self.line[start..start + len]);
+            }
+
+            self.line.clear();
+            self.offset = 0;
+
+            if self.reader.read_line(&mut self.line)? == 0 {
+                return Err(Error::Ply("unexpected end of file".to_string()));
+            }
+        }
+    }
+}
+
+impl<R: BufRead> ValueReader for AsciiReader<R> {
+    fn read(&mut self, ty: ScalarType) -> Result<f64> {
+        let word = self.next_word()?;
+
+        let value = match ty {
+            ScalarType::F32 | ScalarType::F64 => word.parse::<f64>()?,
+            _ => word.parse::<i64>()? as f64,
+        };
+
+        Ok(value)
+    }
+}
+
+struct BinaryReader<R> {
+    reader: R,
+    big_endian: bool,
+}
+
+impl<R: Read> BinaryReader<R> {
+    fn new(reader: R, big_endian: bool) -> Self {
+        Self { reader, big_endian }
+    }
+
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut buf = [0_u8; N];
+        self.reader.read_exact(&mut buf)?;
+
+        if self.big_endian {
+            buf.reverse();
+        }
+
+        Ok(buf)
+    }
+}
+
+impl<R: Read> ValueReader for BinaryReader<R> {
+    fn read(&mut self, ty: ScalarType) -> Result<f64> {
+        let value = match ty {
+            ScalarType::I8 => i8::from_le_bytes(self.bytes()?) as f64,
+            ScalarType::U8 => u8::from_le_bytes(self.bytes()?) as f64,
+            ScalarType::I16 => i16::from_le_bytes(self.bytes()?) as f64,
+            ScalarType::U16 => u16::from_le_bytes(self.bytes()?) as f64,
+            ScalarType::I32 => i32::from_le_bytes(self.bytes()?) as f64,
+            ScalarType::U32 => u32::from_le_bytes(self.bytes()?) as f64,
+            ScalarType::F32 => f32::from_le_bytes(self.bytes()?) as f64,
+            ScalarType::F64 => f64::from_le_bytes(self.bytes()?),
+        };
+
+        Ok(value)
+    }
+
+    fn skip(&mut self, ty: ScalarType, count: usize) -> Result<()> {
+        let len = (ty.size() * count) as u64;
+        let skipped = std::io::copy(&mut (&mut self.reader).take(len), &mut std::io::sink())?;
+
+        if skipped != len {
+            return Err(Error::Ply("unexpected end of file".to_string()));
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_ascii() -> Result<()> {
+        let data = "ply
+format ascii 1.0
+comment exported by test
+element vertex 4
+property float x
+property float y
+property float z
+property float nx
+property float ny
+property float nz
+property float u
+property float v
+property uchar red
+property float quality
+element face 1
+property list uchar int vertex_indices
+property int face_indices
+end_header
+0 0 0 0 0 1 0 0 255 0.5
+1 0 0 0 0 1 1 0 128 0.25
+1 1 0 0 0 1 1 1 0 1
+0 1 0 0 0 1 0 1 64 0
+4 0 1 2 3 7
+";
+
+        let mesh = PlyMesh::read(data.as_bytes())?;
+
+        assert_eq!(mesh.vertex_count(), 4);
+        assert_eq!(mesh.normals.len(), 12);
+        assert_eq!(mesh.uvs, vec![0.0, 0.0, 1.0, 0.0, 1.0, 1.0, 0.0, 1.0]);
+
+        // Quad is split into two triangles.
+        assert_eq!(mesh.indices, vec![0, 1, 2, 0, 2, 3]);
+        assert_eq!(mesh.face_indices, vec![7, 7]);
+
+        assert_eq!(mesh.attributes.len(), 2);
+        assert_eq!(mesh.attributes["red"], vec![255.0, 128.0, 0.0, 64.0]);
+        assert_eq!(mesh.attributes["quality"], vec![0.5, 0.25, 1.0, 0.0]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn read_binary() -> Result<()> {
+        let mut data = b"ply
+format binary_little_endian 1.0
+element vertex 3
+property float x
+property float y
+property float z
+property ushort confidence
+element edge 1
+property int vertex1
+property int vertex2
+element face 1
+property list uchar uint vertex_indices
+end_header
+"
+        .to_vec();
+
+        for (i, p) in [[0.0_f32, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]]
+            .iter()
+            .enumerate()
+        {
+            for v in p {
+                data.extend_from_slice(&v.to_le_bytes());
+            }
+            data.extend_from_slice(&(i as u16 * 10).to_le_bytes());
+        }
+
+        // Edge element is skipped.
+        data.extend_from_slice(&0_i32.to_le_bytes());
+        data.extend_from_slice(&1_i32.to_le_bytes());
+
+        data.push(3);
+        for i in [0_u32, 1, 2] {
+            data.extend_from_slice(&i.to_le_bytes());
+        }
+
+        let mesh = PlyMesh::read(data.as_slice())?;
+
+        assert_eq!(
+            mesh.positions,
+            vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0]
+        );
+        assert!(mesh.normals.is_empty());
+        assert!(mesh.uvs.is_empty());
+        assert_eq!(mesh.indices, vec![0, 1, 2]);
+        assert_eq!(mesh.attributes["confidence"], vec![0.0, 10.0, 20.0]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn visit_chunks() -> Result<()> {
+        #[derive(Default)]
+        struct Counter {
+            info: PlyInfo,
+            vertex_chunks: Vec<(usize, usize)>,
+            triangle_chunks: Vec<(usize, usize)>,
+        }
+
+        impl PlyVisitor for Counter {
+            fn info(&mut self, info: &PlyInfo) -> Result<()> {
+                self.info = info.clone();
+                Ok(())
+            }
+
+            fn vertices(&mut self, chunk: &VertexChunk) -> Result<()> {
+                assert_eq!(chunk.attributes[0].len(), chunk.len());
+                self.vertex_chunks.push((chunk.start, chunk.len()));
+                Ok(())
+            }
+
+            fn triangles(&mut self, chunk: &TriangleChunk) -> Result<()> {
+                self.triangle_chunks.push((chunk.start, chunk.len()));
+                Ok(())
+            }
+        }
+
+        let mut data = String::from(
+            "ply
+format ascii 1.0
+element vertex 5
+property float x
+property float y
+property float z
+property float quality
+element face 3
+property list uchar int vertex_indices
+end_header
+",
+        );
+
+        for i in 0..5 {
+            data.push_str(&format!("{} 0 0 1\n", i));
+        }
+        data.push_str("3 0 1 2\n3 1 2 3\n4 0 1 2 3\n");
+
+        let mut counter = Counter::default();
+        visit(data.as_bytes(), 2, &mut counter)?;
+
+        assert_eq!(counter.info.vertex_count, 5);
+        assert_eq!(counter.info.face_count, 3);
+        assert_eq!(counter.info.attributes, vec!["quality".to_string()]);
+
+        assert_eq!(counter.vertex_chunks, vec![(0, 2), (2, 2), (4, 1)]);
+        // Quad is triangulated, so the last chunk contains 2 triangles.
+        assert_eq!(counter.triangle_chunks, vec![(0, 2), (2, 2)]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn index_out_of_range() {
+        let data = "ply
+format ascii 1.0
+element vertex 1
+property float x
+property float y
+property float z
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0
+3 0 1 2
+";
+
+        assert!(matches!(PlyMesh::read(data.as_bytes()), Err(Error::Ply(_))));
+    }
+
+    #[test]
+    fn oversized_header() {
+        let data = "ply
+format ascii 1.0
+element vertex 999999999999999
+property float x
+property float y
+property float z
+element face 999999999999999
+property list uchar int vertex_indices
+end_header
+";
+
+        // The truncated body is reported instead of aborting on allocation.
+        assert!(matches!(PlyMesh::read(data.as_bytes()), Err(Error::Ply(_))));
+    }
+
+    #[test]
+    fn oversized_header_chunked() {
+        let mut data = b"ply
+format binary_little_endian 1.0
+element vertex 999999999999999
+property float x
+property float y
+property float z
+property float quality
+end_header
+"
+        .to_vec();
+        for value in [0.0f32, 1.0, 2.0, 0.5] {
+            data.extend_from_slice(&value.to_le_bytes());
+        }
+
+        // Custom attributes reserve storage from the header count as well.
+        let Err(Error::Io(err)) = PlyMesh::read(data.as_slice()) else {
+            panic!("Expected unexpected end of file");
+        };
+        assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn invalid_magic() {
+        assert!(PlyMesh::read("obj\n".as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,246 @@
+//! Opt-in text preprocessing applied to scene files before parsing.
+//!
+//! Preprocessing is enabled via [crate::LoadOptions] and applies to the main file
+//! as well as to all included files.
+
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+};
+
+use crate::{Error, Result};
+
+/// Replace `{$name}` placeholders in `src` with values from `variables`.
+///
+/// Placeholders inside comments are left untouched.
+/// Returns [Error::UndefinedVariable] if a placeholder refers to a variable missing in `variables`.
+///
+/// ```
+/// # use std::collections::HashMap;
+/// let variables = HashMap::from([("spp".to_string(), "64".to_string())]);
+///
+/// let out = pbrt4::preprocess::substitute(r#"Sampler "halton" "integer pixelsamples" {$spp}"#, &variables)?;
+/// assert_eq!(out, r#"Sampler "halton" "integer pixelsamples" 64"#);
+/// # Ok::<(), pbrt4::Error>(())
+/// ```
+pub fn substitute<'a>(src: &'a str, variables: &HashMap<String, String>) -> Result<Cow<'a, str>> {
+    // Fast path, nothing to substitute.
+    if !src.contains("{$") {
+        return Ok(Cow::Borrowed(src));
+    }
+
+    let mut out = String::with_capacity(src.len());
+
+    for line in src.split_inclusive('\n') {
+        let (code, comment) = split_comment(line);
+
+        let mut rest = code;
+        while let Some(start) = rest.find("{$") {
+            out.push_str(&rest[..start]);
+
+            let placeholder = &rest[start + 2..];
+            let end = placeholder
+                .find('}')
+                .ok_or_else(|| Error::UndefinedVariable(placeholder.trim_end().to_string()))?;
+
+            let name = &placeholder[..end];
+            let value = variables
+                .get(name)
+                .ok_or_else(|| Error::UndefinedVariable(name.to_string()))?;
+
+            out.push_str(value);
+            rest = &placeholder[end + 1..];
+        }
+
+        out.push_str(rest);
+        out.push_str(comment);
+    }
+
+    Ok(Cow::Owned(out))
+}
+
+/// Evaluate `#define` / `#undef` / `#ifdef` / `#ifndef` / `#else` / `#endif` directives in `src`.
+///
+/// Directives are written as comments, so files remain valid for pbrt itself.
+/// `defines` is the initial set of defined names, `#define` and `#undef` only affect the rest
+/// of the current file (they don't propagate to included files).
+///
+/// Lines in disabled sections (and the directive lines themselves) are replaced with empty lines,
+/// so line numbers remain valid.
+///
+/// ```
+/// # use std::collections::HashSet;
+/// let src = "#ifdef PREVIEW\nSampler \"halton\" \"integer pixelsamples\" 4\n#endif\n";
+///
+/// let out = pbrt4::preprocess::conditionals(src, &HashSet::new())?;
+/// assert_eq!(out, "\n\n\n");
+/// # Ok::<(), pbrt4::Error>(())
+/// ```
+pub fn conditionals<'a>(src: &'a str, defines: &HashSet<String>) -> Result<Cow<'a, str>> {
+    // Fast path, no conditional sections.
+    if !src.contains("#if") {
+        return Ok(Cow::Borrowed(src));
+    }
+
+    let mut defines = defines.clone();
+
+    // Whether each nested section is enabled.
+    let mut sections: Vec<bool> = Vec::new();
+
+    let mut out = String::with_capacity(src.len());
+
+    for line in src.split_inclusive('\n') {
+        let enabled = sections.iter().all(|enabled| *enabled);
+
+        let mut words = line.split_whitespace();
+        let directive = words.next().unwrap_or_default();
+        let name = words.next();
+
+        let is_directive = match directive {
+            "#define" | "#undef" | "#ifdef" | "#ifndef" => {
+                let name = name.ok_or(Error::InvalidConditional)?;
+
+                match directive {
+                    "#define" if enabled => {
+                        defines.insert(name.to_string());
+                    }
+                    "#undef" if enabled => {
+                        defines.remove(name);
+                    }
+                    "#ifdef" => sections.push(defines.contains(name)),
+                    "#ifndef" => sections.push(!defines.contains(name)),
+                    _ => {}
+                }
+
+                true
+            }
+            "#else" => {
+                let last = sections.last_mut().ok_or(Error::InvalidConditional)?;
+                *last = !*last;
+                true
+            }
+            "#endif" => {
+                sections.pop().ok_or(Error::InvalidConditional)?;
+                true
+            }
+            _ => false,
+        };
+
+        if is_directive || !enabled {
+            // Keep line breaks to preserve line numbers.
+            if line.ends_with('\n') {
+                out.push('\n');
+            }
+        } else {
+            out.push_str(line);
+        }
+    }
+
+    if !sections.is_empty() {
+        return Err(Error::InvalidConditional);
+    }
+
+    Ok(Cow::Owned(out))
+}
+
+/// Split a line into code and a trailing comment (starting with `#` outside of a quoted string).
+fn split_comment(line: &str) -> (&str, &str) {
+    let mut in_quotes = false;
+    let mut chars = line.char_indices();
+
+    while let Some((index, ch)) = chars.next() {
+        match ch {
+            '"' => in_quotes = !in_quotes,
+            // Skip escaped characters, like \" inside of a string.
+            '\\' if in_quotes => {
+                chars.next();
+            }
+            '#' if !in_quotes => return line.split_at(index),
+            _ => {}
+        }
+    }
+
+    (line, "")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars() -> HashMap<String, String> {
+        HashMap::from([
+            ("res".to_string(), "1920".to_string()),
+            ("file".to_string(), "out.exr".to_string()),
+        ])
+    }
+
+    #[test]
+    fn substitute_placeholders() -> Result<()> {
+        let src = "Film \"rgb\" \"integer xresolution\" {$res} \"string filename\" \"{$file}\"\n";
+
+        assert_eq!(
+            substitute(src, &vars())?,
+            "Film \"rgb\" \"integer xresolution\" 1920 \"string filename\" \"out.exr\"\n"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn skip_comments() -> Result<()> {
+        let src = "# {$unknown}\nScale 1 1 1 # {$unknown}\n";
+        assert_eq!(substitute(src, &vars())?, src);
+
+        Ok(())
+    }
+
+    #[test]
+    fn undefined_variable() {
+        assert!(matches!(
+            substitute("Scale {$scale} 1 1", &vars()),
+            Err(Error::UndefinedVariable(name)) if name == "scale"
+        ));
+    }
+
+    #[test]
+    fn conditional_sections() -> Result<()> {
+        let src = "\
+#define HQ
+#ifdef HQ
+Sampler \"zsobol\" \"integer pixelsamples\" 1024
+#else
+Sampler \"zsobol\" \"integer pixelsamples\" 16
+#endif
+#ifndef PREVIEW
+#ifdef MISSING
+Shape \"sphere\"
+#endif
+WorldBegin
+#endif
+";
+
+        let out = conditionals(src, &HashSet::new())?;
+        assert_eq!(
+            out,
+            "\n\nSampler \"zsobol\" \"integer pixelsamples\" 1024\n\n\n\n\n\n\n\nWorldBegin\n\n"
+        );
+
+        let defines = HashSet::from(["PREVIEW".to_string()]);
+        let out = conditionals(src, &defines)?;
+        assert!(!out.contains("WorldBegin"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn unbalanced_conditionals() {
+        assert!(matches!(
+            conditionals("#ifdef A\n", &HashSet::new()),
+            Err(Error::InvalidConditional)
+        ));
+        assert!(matches!(
+            conditionals("#ifdef A\n#endif\n#endif\n", &HashSet::new()),
+            Err(Error::InvalidConditional)
+        ));
+    }
+}
@@ -0,0 +1,379 @@
+//! Scene reports.
+
+use std::{collections::HashSet, fmt, mem};
+
+use crate::{
+    math::{Bounds3, Float},
+    param::FromTypedParams,
+    types::Shape,
+    Scene,
+};
+
+/// Compact overview of a scene, see [Scene::summary].
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneSummary {
+    /// Film resolution, if `Film` is specified.
+    pub resolution: Option<(i32, i32)>,
+    pub camera: Option<&'static str>,
+    pub integrator: Option<&'static str>,
+    pub sampler: Option<&'static str>,
+    pub shapes: usize,
+    /// Number of light sources, excluding area lights.
+    pub lights: usize,
+    pub area_lights: usize,
+    pub materials: usize,
+    pub textures: usize,
+    pub media: usize,
+    pub objects: usize,
+    pub instances: usize,
+    /// Number of distinct external files referenced by the scene (included files, meshes,
+    /// images, spectra, volumes, lens files), see [Scene::dependencies].
+    pub external_files: usize,
+    /// World space bounds of all shapes (including instances), `None` if there is no geometry
+    /// with known bounds.
+    pub bounds: Option<Bounds3>,
+}
+
+impl SceneSummary {
+    /// Build a summary for `scene`.
+    pub fn new(scene: &Scene) -> Self {
+        // A file can be used in different ways, e.g. an image as a texture and a light.
+        let files = scene
+            .dependencies
+            .iter()
+            .map(|dependency| &dependency.path)
+            .collect::<HashSet<_>>();
+
+        SceneSummary {
+            resolution: scene.film.as_ref().map(|f| (f.xresolution, f.yresolution)),
+            camera: scene.camera.as_ref().map(|c| c.params.type_name()),
+            integrator: scene.integrator.as_ref().map(|i| i.type_name()),
+            sampler: scene.sampler.as_ref().map(|s| s.type_name()),
+            shapes: scene.shapes.len(),
+            lights: scene.lights.len(),
+            area_lights: scene.area_lights.len(),
+            materials: scene.materials.len(),
+            textures: scene.textures.len(),
+            media: scene.mediums.len(),
+            objects: scene.objects.len(),
+            instances: scene.instances.len(),
+            external_files: files.len(),
+            bounds: scene.world_bounds(),
+        }
+    }
+}
+
+impl fmt::Display for SceneSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = |name: Option<&str>| name.unwrap_or("default").to_string();
+
+        match self.resolution {
+            Some((x, y)) => writeln!(f, "Resolution: {}x{}", x, y)?,
+            None => writeln!(f, "Resolution: default")?,
+        }
+
+        writeln!(f, "Camera: {}", name(self.camera))?;
+        writeln!(f, "Integrator: {}", name(self.integrator))?;
+        writeln!(f, "Sampler: {}", name(self.sampler))?;
+        writeln!(
+            f,
+            "Shapes: {}, objects: {}, instances: {}",
+            self.shapes, self.objects, self.instances
+        )?;
+        writeln!(
+            f,
+            "Lights: {}, area lights: {}",
+            self.lights, self.area_lights
+        )?;
+        writeln!(
+            f,
+            "Materials: {}, textures: {}, media: {}",
+            self.materials, self.textures, self.media
+        )?;
+        writeln!(f, "External files: {}", self.external_files)?;
+
+        match &self.bounds {
+            Some(b) => write!(
+                f,
+                "Bounds: [{} {} {}] - [{} {} {}]",
+                b.min.x, b.min.y, b.min.z, b.max.x, b.max.y, b.max.z
+            ),
+            None => write!(f, "Bounds: unknown"),
+        }
+    }
+}
+
+/// Instancing usage of a single object (`ObjectBegin` / `ObjectEnd` block).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ObjectUsage {
+    /// Index of the object in [Scene::objects].
+    pub object_index: usize,
+    /// Object name.
+    pub name: String,
+    /// Number of `ObjectInstance` directives referencing the object.
+    pub instance_count: usize,
+    /// Number of shapes in the object.
+    pub shape_count: usize,
+    /// Number of primitives (triangles for meshes, 1 for analytic shapes) in the object.
+    pub primitive_count: usize,
+    /// Approximate size of the object's geometry data in bytes.
+    pub geometry_size: usize,
+}
+
+/// Summary of how a scene uses object instancing, see [Scene::instancing_report].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InstancingReport {
+    /// Per-object usage, in the order of [Scene::objects].
+    pub objects: Vec<ObjectUsage>,
+    /// Number of primitives stored once: shapes outside of objects plus the contents of each object.
+    pub unique_primitives: usize,
+    /// Number of primitives after expanding all instances, as seen by the renderer.
+    pub instanced_primitives: usize,
+    /// Approximate number of bytes saved by instancing compared to copying geometry for each instance.
+    pub memory_saved: usize,
+}
+
+impl InstancingReport {
+    /// Build an instancing report for `scene`.
+    pub fn new(scene: &Scene) -> Self {
+        let mut objects = scene
+            .objects
+            .iter()
+            .enumerate()
+            .map(|(object_index, object)| {
+                // Shapes are not available if the scene was loaded with a shape callback.
+                let shapes = scene.object_shapes(object_index);
+
+                ObjectUsage {
+                    object_index,
+                    name: object.name.clone(),
+                    instance_count: 0,
+                    shape_count: object.shape_count,
+                    primitive_count: shapes.iter().map(|s| primitive_count(&s.params)).sum(),
+                    geometry_size: shapes.iter().map(|s| geometry_size(&s.params)).sum(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        for instance in &scene.instances {
+            objects[instance.object_index].instance_count += 1;
+        }
+
+        let total_primitives: usize = scene
+            .shapes
+            .iter()
+            .map(|s| primitive_count(&s.params))
+            .sum();
+        let object_primitives: usize = objects.iter().map(|o| o.primitive_count).sum();
+
+        // Shapes inside objects are only rendered through instances.
+        let standalone_primitives = total_primitives - object_primitives;
+
+        let instanced_primitives = standalone_primitives
+            + objects
+                .iter()
+                .map(|o| o.primitive_count * o.instance_count)
+                .sum::<usize>();
+
+        let memory_saved = objects
+            .iter()
+            .map(|o| o.geometry_size * o.instance_count.saturating_sub(1))
+            .sum();
+
+        InstancingReport {
+            objects,
+            unique_primitives: total_primitives,
+            instanced_primitives,
+            memory_saved,
+        }
+    }
+}
+
+impl fmt::Display for InstancingReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Objects: {}", self.objects.len())?;
+
+        for object in &self.objects {
+            writeln!(
+                f,
+                "  {:?}: {} instance(s), {} shape(s), {} primitive(s)",
+                object.name, object.instance_count, object.shape_count, object.primitive_count
+            )?;
+        }
+
+        writeln!(f, "Unique primitives: {}", self.unique_primitives)?;
+        writeln!(f, "Instanced primitives: {}", self.instanced_primitives)?;
+        write!(f, "Memory saved: {} bytes", self.memory_saved)
+    }
+}
+
+impl Scene {
+    /// Summarize the scene: resolution, integrator, sampler, camera, entity counts and bounds.
+    ///
+    /// The summary implements [fmt::Display], producing a compact multi-line report.
+    pub fn summary(&self) -> SceneSummary {
+        SceneSummary::new(self)
+    }
+
+    /// Summarize per-object instance counts and the effect of instancing on
+    /// primitive counts and memory usage.
+    pub fn instancing_report(&self) -> InstancingReport {
+        InstancingReport::new(self)
+    }
+}
+
+/// Number of primitives in a shape.
+///
+/// PLY meshes which were not loaded count as a single primitive.
+fn primitive_count(shape: &Shape) -> usize {
+    match shape {
+        Shape::TriangleMesh { indices, .. } => indices.len() / 3,
+        // Each level splits triangles in four.
+        Shape::LoopSubdiv {
+            levels, indices, ..
+        } => (indices.len() / 3) << (2 * (*levels).clamp(0, 16)),
+        Shape::BilinearMesh {
+            indices, positions, ..
+        } => (indices.len() / 4).max((positions.len() == 12) as usize),
+        #[cfg(feature = "ply")]
+        Shape::PlyMesh {
+            mesh: Some(mesh), ..
+        } => mesh.triangle_count(),
+        _ => 1,
+    }
+}
+
+/// Approximate size of shape's geometry data in bytes.
+fn geometry_size(shape: &Shape) -> usize {
+    const F32: usize = mem::size_of::<f32>();
+    const I32: usize = mem::size_of::<i32>();
+
+    match shape {
+        Shape::TriangleMesh {
+            indices,
+            positions,
+            normals,
+            tangents,
+            uvs,
+            face_indices,
+            ..
+        } => {
+            (indices.len() + face_indices.len()) * I32
+                + positions.len() * mem::size_of::<Float>()
+                + (normals.len() + tangents.len() + uvs.len()) * F32
+        }
+        Shape::LoopSubdiv {
+            indices, positions, ..
+        } => indices.len() * I32 + positions.len() * mem::size_of::<Float>(),
+        Shape::BilinearMesh {
+            indices,
+            positions,
+            normals,
+            uvs,
+            face_indices,
+            ..
+        } => {
+            (indices.len() + face_indices.len()) * I32
+                + positions.len() * mem::size_of::<Float>()
+                + (normals.len() + uvs.len()) * F32
+        }
+        #[cfg(feature = "ply")]
+        Shape::PlyMesh {
+            mesh: Some(mesh), ..
+        } => {
+            (mesh.indices.len() + mesh.face_indices.len()) * I32
+                + mesh.positions.len() * mem::size_of::<Float>()
+                + (mesh.normals.len() + mesh.uvs.len()) * F32
+                + mesh.attributes.values().map(Vec::len).sum::<usize>() * F32
+        }
+        _ => mem::size_of::<Shape>(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::Result;
+
+    #[test]
+    fn summary() -> Result<()> {
+        let data = r#"
+Film "rgb" "integer xresolution" 400 "integer yresolution" 300
+Camera "perspective"
+Integrator "path"
+WorldBegin
+MakeNamedMedium "smoke" "string type" "nanovdb" "string filename" "smoke.nvdb"
+LightSource "infinite" "string filename" "sky.exr"
+Texture "sky" "spectrum" "imagemap" "string filename" "sky.exr"
+Material "diffuse"
+Shape "plymesh" "string filename" "mesh.ply"
+Shape "plymesh" "string filename" "mesh.ply"
+ObjectBegin "ball"
+Shape "sphere"
+ObjectEnd
+Translate 10 0 0
+ObjectInstance "ball"
+        "#;
+
+        let scene = Scene::load(data, None)?;
+        let summary = scene.summary();
+
+        assert_eq!(summary.resolution, Some((400, 300)));
+        assert_eq!(summary.camera, Some("perspective"));
+        assert_eq!(summary.integrator, Some("path"));
+        assert_eq!(summary.sampler, None);
+        assert_eq!(summary.shapes, 3);
+        assert_eq!(summary.external_files, 3);
+
+        // Only the instanced sphere has known bounds.
+        let bounds = summary.bounds.unwrap();
+        assert_eq!(bounds.min.x, 9.0);
+        assert_eq!(bounds.max.x, 11.0);
+
+        let text = summary.to_string();
+        assert!(text.starts_with("Resolution: 400x300\nCamera: perspective\n"));
+        assert!(text.contains("Shapes: 3, objects: 1, instances: 1"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn instancing_report() -> Result<()> {
+        let data = r#"
+WorldBegin
+
+ObjectBegin "tri"
+Shape "trianglemesh" "point3 P" [ 0 0 0 1 0 0 0 1 0 ] "integer indices" [ 0 1 2 ]
+ObjectEnd
+
+ObjectBegin "unused"
+Shape "sphere"
+ObjectEnd
+
+ObjectInstance "tri"
+ObjectInstance "tri"
+ObjectInstance "tri"
+
+Shape "sphere"
+        "#;
+
+        let scene = Scene::load(data, None)?;
+        let report = scene.instancing_report();
+
+        assert_eq!(report.objects.len(), 2);
+        assert_eq!(report.objects[0].instance_count, 3);
+        assert_eq!(report.objects[0].primitive_count, 1);
+        assert_eq!(report.objects[1].instance_count, 0);
+
+        // Triangle + sphere in objects, plus one standalone sphere.
+        assert_eq!(report.unique_primitives, 3);
+        // Three triangle instances plus the standalone sphere.
+        assert_eq!(report.instanced_primitives, 4);
+
+        let tri_size = 3 * 4 + 9 * mem::size_of::<Float>();
+        assert_eq!(report.memory_saved, 2 * tri_size);
+
+        Ok(())
+    }
+}
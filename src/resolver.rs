@@ -0,0 +1,134 @@
+//! Reading of included and imported scene files.
+
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    future::Future,
+    io,
+    path::{Path, PathBuf},
+    pin::Pin,
+    sync::Mutex,
+};
+
+use crate::{Error, Result};
+
+/// Reads the contents of files referenced by `Include` and `Import`.
+///
+/// By default files are read from disk, a custom resolver (see [crate::LoadOptions::resolver])
+/// allows loading scenes from in-memory maps, archives or network stores.
+///
+/// Implemented for closures and for maps of file contents:
+///
+/// ```
+/// # use std::{collections::HashMap, path::{Path, PathBuf}};
+/// # use pbrt4::Scene;
+/// let files = HashMap::from([(
+///     PathBuf::from("geometry.pbrt"),
+///     "Shape \"sphere\"".to_string(),
+/// )]);
+///
+/// let scene = Scene::load_with_resolver("WorldBegin Include \"geometry.pbrt\"", None, files)?;
+/// assert_eq!(scene.shapes.len(), 1);
+/// # Ok::<(), pbrt4::Error>(())
+/// ```
+pub trait IncludeResolver: Send + Sync {
+    /// Read the file at `path`, which is already resolved against the working directory.
+    fn resolve(&self, path: &Path) -> Result<String>;
+}
+
+impl fmt::Debug for dyn IncludeResolver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("IncludeResolver")
+    }
+}
+
+/// Reads files from disk.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FsResolver;
+
+impl IncludeResolver for FsResolver {
+    fn resolve(&self, path: &Path) -> Result<String> {
+        Ok(fs::read_to_string(path)?)
+    }
+}
+
+impl<F> IncludeResolver for F
+where
+    F: Fn(&Path) -> Result<String> + Send + Sync,
+{
+    fn resolve(&self, path: &Path) -> Result<String> {
+        self(path)
+    }
+}
+
+impl IncludeResolver for HashMap<PathBuf, String> {
+    fn resolve(&self, path: &Path) -> Result<String> {
+        match self.get(path) {
+            Some(contents) => Ok(contents.clone()),
+            None => Err(not_found(path)),
+        }
+    }
+}
+
+/// Future returned by [AsyncIncludeResolver::resolve].
+pub type ResolveFuture<'a> = Pin<Box<dyn Future<Output = Result<String>> + Send + 'a>>;
+
+/// Asynchronous counterpart of [IncludeResolver], see [crate::Scene::load_async].
+///
+/// Implemented for closures taking a path and returning a future:
+///
+/// ```
+/// # use std::path::PathBuf;
+/// # use pbrt4::{AsyncIncludeResolver, Result};
+/// let resolver = |path: PathBuf| async move {
+///     // E.g. fetch the file from a remote store.
+///     Result::Ok(format!("# {}", path.display()))
+/// };
+/// # fn check(_: &dyn AsyncIncludeResolver) {}
+/// # check(&resolver);
+/// ```
+pub trait AsyncIncludeResolver: Send + Sync {
+    /// Read the file at `path`, which is already resolved against the working directory.
+    fn resolve<'a>(&'a self, path: &'a Path) -> ResolveFuture<'a>;
+}
+
+impl<F, Fut> AsyncIncludeResolver for F
+where
+    F: Fn(PathBuf) -> Fut + Send + Sync,
+    Fut: Future<Output = Result<String>> + Send + 'static,
+{
+    fn resolve<'a>(&'a self, path: &'a Path) -> ResolveFuture<'a> {
+        Box::pin(self(path.to_path_buf()))
+    }
+}
+
+/// Files read ahead of loading, by [crate::Scene::load_async].
+///
+/// Read errors are returned (once) when the file is resolved, so they get the include's location.
+pub(crate) struct Prefetched(Mutex<HashMap<PathBuf, Result<String>>>);
+
+impl Prefetched {
+    pub fn new(files: HashMap<PathBuf, Result<String>>) -> Self {
+        Self(Mutex::new(files))
+    }
+}
+
+impl IncludeResolver for Prefetched {
+    fn resolve(&self, path: &Path) -> Result<String> {
+        let mut files = self.0.lock().unwrap();
+
+        match files.get(path) {
+            Some(Ok(contents)) => Ok(contents.clone()),
+            Some(Err(_)) => files.remove(path).unwrap_or_else(|| Err(not_found(path))),
+            None => Err(not_found(path)),
+        }
+    }
+}
+
+fn not_found(path: &Path) -> Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{} is not found", path.display()),
+    )
+    .into()
+}
@@ -1,16 +1,38 @@
 //! Scene loader
 
-use std::{collections::HashMap, env, fs, path::Path, slice, str};
-
-use glam::{Mat4, Vec3};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    env, fs, mem,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+};
 
+#[cfg(any(feature = "ply", feature = "nanovdb"))]
+use crate::cache::GeometryCache;
+#[cfg(feature = "ply")]
+use crate::ply::PlyMesh;
 use crate::{
-    param::ParamList,
+    decode_bytes,
+    dependencies::element_dependencies,
+    document::Document,
+    math::{Bounds3, Decomposition, Mat4, Vec3},
+    param::{FromTypedParams, Param, ParamList, Spectrum},
+    preprocess,
+    resolver::Prefetched,
+    scan_directives,
     types::{
-        Accelerator, AreaLight, Camera, Film, Integrator, Light, Material, Medium, Options,
-        Sampler, Shape, Texture,
+        Accelerator, AreaLight, Camera, ColorSpace, Film, FilmType, Filter, Integrator, Light,
+        Material, Medium, Options, Sampler, Shape, Texture,
     },
-    Element, Error, Parser, Result,
+    validate::{self, Diagnostic, EntityRef, Severity, ValidationIssue},
+    AsyncIncludeResolver, Dependency, Directive, Element, Error, FsResolver, IncludeResolver,
+    Location, ParseOptions, Parser, Result, SceneVisitor, Strictness,
 };
 
 /// A number of directives modify the current graphics state.
@@ -23,16 +45,24 @@ struct State<'a> {
     reverse_orientation: bool,
 
     transform_matrix: Mat4,
+    /// The CTM at the end of the shutter interval, for animated transformations.
+    transform_end: Mat4,
+    /// Which of the CTMs are modified by transformation directives.
+    active_transform: ActiveTransform,
+
+    /// Color space for RGB values of subsequently declared entities.
+    color_space: ColorSpace,
 
-    current_inside_medium: Option<&'a str>,
-    current_outside_medium: Option<&'a str>,
+    current_inside_medium: Option<Cow<'a, str>>,
+    current_outside_medium: Option<Cow<'a, str>>,
 
     material_index: Option<usize>,
+    /// Name passed to `NamedMaterial` if no such material is defined.
+    missing_material: Option<Cow<'a, str>>,
     area_light_index: Option<usize>,
 
     /// Between `ObjectBegin` and `ObjectEnd` if `Some`.
     active_object: Option<usize>,
-    shape_count: usize,
 
     shape_params: ParamList<'a>,
     light_params: ParamList<'a>,
@@ -41,42 +71,344 @@ struct State<'a> {
     texture_params: ParamList<'a>,
 }
 
+/// Selects which CTMs are modified by transformation directives, see `ActiveTransform`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum ActiveTransform {
+    Start,
+    End,
+    #[default]
+    All,
+}
+
+impl<'a> State<'a> {
+    /// Apply `f` to the active CTMs.
+    fn update_transform(&mut self, f: impl Fn(&mut Mat4)) {
+        if self.active_transform != ActiveTransform::End {
+            f(&mut self.transform_matrix);
+        }
+
+        if self.active_transform != ActiveTransform::Start {
+            f(&mut self.transform_end);
+        }
+    }
+}
+
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct CameraEntity {
     pub params: Camera,
+    /// Camera to world transformation at the start of the shutter interval ([Scene::start_time]).
     pub transform: Mat4,
+    /// Camera to world transformation at the end of the shutter interval ([Scene::end_time]).
+    ///
+    /// Same as `transform` unless the camera is animated.
+    pub transform_end: Mat4,
+    /// Index in [Scene::mediums] of the medium the camera is in (the current exterior medium),
+    /// `None` for vacuum.
+    pub exterior_medium_index: Option<usize>,
+}
+
+impl CameraEntity {
+    /// Camera position, orientation and scale in world space at the start of the
+    /// shutter interval, see [Mat4::decompose].
+    ///
+    /// The camera looks along +z of the rotated frame, with +y up.
+    pub fn decompose(&self) -> Decomposition {
+        self.transform.decompose()
+    }
+
+    /// Camera position in world space at the start of the shutter interval.
+    pub fn position(&self) -> Vec3 {
+        self.transform.transform_point3(Vec3::ZERO)
+    }
+
+    /// Unit vector in world space the camera looks along.
+    pub fn forward(&self) -> Vec3 {
+        self.transform
+            .transform_vector3(Vec3::new(0.0, 0.0, 1.0))
+            .normalize()
+    }
+
+    /// Unit vector in world space pointing up in the image.
+    pub fn up(&self) -> Vec3 {
+        self.transform
+            .transform_vector3(Vec3::new(0.0, 1.0, 0.0))
+            .normalize()
+    }
+
+    /// World to normalized device coordinates transformation for a film with the given
+    /// aspect ratio (width / height), see [Camera::projection].
+    pub fn view_projection(&self, aspect: f32) -> Option<Mat4> {
+        Some(self.params.projection(aspect)? * self.transform.inverse())
+    }
 }
 
+/// A light source along with its transformation.
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LightEntity {
+    pub params: Light,
+    /// Light to world transformation at the start of the shutter interval ([Scene::start_time]).
+    pub transform: Mat4,
+    /// Light to world transformation at the end of the shutter interval ([Scene::end_time]).
+    ///
+    /// Same as `transform` unless the light is animated.
+    pub transform_end: Mat4,
+    /// Color space active when the light was declared.
+    pub color_space: ColorSpace,
+    /// Index in [Scene::mediums] of the medium rays leaving the light travel through
+    /// (the current exterior medium), `None` for vacuum.
+    pub exterior_medium_index: Option<usize>,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ShapeEntity {
     pub params: Shape,
     /// If shape is a part of [Object], transform matrix defines the transformation from
     /// object space to the instance's coordinate space.
     pub transform: Mat4,
+    /// Transformation at the end of the shutter interval ([Scene::end_time]).
+    ///
+    /// Same as `transform` unless the shape is animated.
+    pub transform_end: Mat4,
     pub reverse_orientation: bool,
     pub material_index: Option<usize>,
     pub area_light_index: Option<usize>,
+    /// Index in [Scene::objects] if the shape is defined inside an `ObjectBegin` / `ObjectEnd` block.
+    pub object_index: Option<usize>,
+    /// Index in [Scene::mediums] of the medium inside the shape, `None` for vacuum.
+    pub interior_medium_index: Option<usize>,
+    /// Index in [Scene::mediums] of the medium outside the shape, `None` for vacuum.
+    pub exterior_medium_index: Option<usize>,
+    /// Shape bounds after applying [ShapeEntity::transform], computed by [Scene::finalize].
+    pub bounds: Option<Bounds3>,
+}
+
+impl ShapeEntity {
+    /// Decompose [ShapeEntity::transform] into translation, rotation and scale,
+    /// see [Mat4::decompose].
+    pub fn decompose(&self) -> Decomposition {
+        self.transform.decompose()
+    }
+
+    /// Bounds of the shape after applying its transformation, see [Shape::bounds].
+    ///
+    /// Uses [ShapeEntity::bounds] if computed by [Scene::finalize]. Animated shapes are
+    /// bounded at both ends of the shutter interval.
+    /// For shapes of objects the bounds are in the instance's coordinate space,
+    /// see [Scene::world_bounds] to account for instances.
+    pub fn world_bounds(&self) -> Option<Bounds3> {
+        let bounds = match self.bounds {
+            Some(bounds) => bounds,
+            None => self.params.bounds()?.transform(&self.transform),
+        };
+
+        if self.transform_end == self.transform {
+            return Some(bounds);
+        }
+
+        let end = self.params.bounds()?.transform(&self.transform_end);
+        Some(bounds.union(end))
+    }
 }
 
+/// A named collection of shapes defined between `ObjectBegin` and `ObjectEnd`,
+/// rendered only through [InstanceEntity] records.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Object {
     pub name: String,
+    /// Index of the object's first shape in [Scene::shapes], `None` if the object is empty.
     pub shape_start: Option<usize>,
     pub shape_count: usize,
+    /// The CTM at `ObjectBegin`.
     pub object_to_instance: Mat4,
 }
 
+impl Object {
+    /// Range of the object's shapes in [Scene::shapes], `None` if the object is empty.
+    pub fn shape_range(&self) -> Option<Range<usize>> {
+        self.shape_start
+            .map(|start| start..start + self.shape_count)
+    }
+}
+
+/// An `ObjectInstance` of an [Object].
 #[derive(Debug)]
-pub struct Instance {
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InstanceEntity {
+    /// The CTM at `ObjectInstance`, transforms object shapes to world space.
     pub instance_to_world: Mat4,
+    /// Instance to world transformation at the end of the shutter interval ([Scene::end_time]).
+    ///
+    /// Same as `instance_to_world` unless the instance is animated.
+    pub instance_to_world_end: Mat4,
+    /// Index of the instanced object in [Scene::objects].
     pub object_index: usize,
     pub area_light_index: Option<usize>,
     pub reverse_orientation: bool,
 }
 
+#[deprecated(note = "Renamed to `InstanceEntity`")]
+pub type Instance = InstanceEntity;
+
+/// Options that control how scenes are loaded.
+#[derive(Debug, Default, Clone)]
+pub struct LoadOptions {
+    /// When set, `"plymesh"` shapes and grids of `"nanovdb"` media ([Medium::grids]) are
+    /// resolved and loaded through the cache, so geometry shared between scenes is parsed only once.
+    #[cfg(any(feature = "ply", feature = "nanovdb"))]
+    pub geometry_cache: Option<Arc<GeometryCache>>,
+    /// Load `"plymesh"` shapes while loading the scene, even without a [LoadOptions::geometry_cache].
+    ///
+    /// Otherwise meshes can be loaded on demand with [Shape::load_mesh].
+    #[cfg(feature = "ply")]
+    pub load_meshes: bool,
+    /// Read `.spd` files referenced by light spectra while loading the scene.
+    ///
+    /// Otherwise they're kept as [Spectrum::File] and can be read with [Spectrum::load].
+    pub load_spectra: bool,
+    /// Report references to undefined named materials and coordinate systems as warnings
+    /// in [Scene::issues] instead of failing to load the scene.
+    ///
+    /// Shapes get no material, and `CoordSysTransform` resets the CTM to identity.
+    pub lenient: bool,
+    /// Maximum number of threads used by [Scene::load_many].
+    ///
+    /// Defaults to the available parallelism when `None`.
+    pub max_threads: Option<usize>,
+    /// Variables to substitute for `{$name}` placeholders in the scene and included files.
+    ///
+    /// Substitution is disabled when `None`, see [crate::preprocess::substitute].
+    pub variables: Option<HashMap<String, String>>,
+    /// Names considered defined by `#ifdef` / `#ifndef` sections in the scene and included files.
+    ///
+    /// Conditional sections are ignored (treated as regular comments) when `None`,
+    /// see [crate::preprocess::conditionals].
+    pub defines: Option<HashSet<String>>,
+    /// Handling of malformed input, like unknown directives or missing included files.
+    pub parse: ParseOptions,
+    /// Reads files referenced by `Include` and `Import`, [FsResolver] is used when `None`.
+    pub resolver: Option<Arc<dyn IncludeResolver>>,
+    /// Memory-map included and imported files instead of reading them, see [Scene::from_file_mmap].
+    ///
+    /// Ignored when a [LoadOptions::resolver] is set.
+    #[cfg(feature = "mmap")]
+    pub mmap: Option<crate::MmapOptIn>,
+    /// Maximum nesting of included and imported files, loading fails with
+    /// [Error::IncludeDepthExceeded] beyond it.
+    ///
+    /// Defaults to 64 when `None`. Files including themselves fail with [Error::IncludeCycle]
+    /// regardless of the limit.
+    pub max_include_depth: Option<usize>,
+    /// Keep the statements of the scene and included files in [Scene::document],
+    /// so the scene can be edited and written back with its file structure intact.
+    pub keep_ast: bool,
+    /// Receives [Progress] reports while the scene is parsed.
+    pub progress: Option<Arc<ProgressCallback>>,
+    /// Checked between directives, loading stops with [Error::Cancelled] once it's set.
+    ///
+    /// Can be set from another thread (or from the [LoadOptions::progress] callback)
+    /// to abort loading huge scenes.
+    pub cancel: Option<Arc<AtomicBool>>,
+}
+
+/// Default [LoadOptions::max_include_depth].
+const MAX_INCLUDE_DEPTH: usize = 64;
+
+/// Number of parsed bytes between [Progress] reports.
+const PROGRESS_INTERVAL: usize = 1 << 20;
+
+/// Loading progress, see [LoadOptions::progress].
+///
+/// Reported when loading starts, when an included or imported file is entered or left,
+/// about every megabyte of parsed data and when parsing is done.
+#[derive(Debug, Default, Clone)]
+pub struct Progress {
+    /// Bytes of the scene and included files parsed so far.
+    pub bytes_parsed: usize,
+    /// Size of the scene and included files.
+    ///
+    /// Files included more than once are only counted once, so `bytes_parsed` may exceed it.
+    pub total_bytes: usize,
+    /// File being parsed, `None` for the scene itself if it's loaded from a string.
+    pub file: Option<PathBuf>,
+    pub shapes: usize,
+    pub lights: usize,
+    pub materials: usize,
+    pub textures: usize,
+    pub instances: usize,
+}
+
+/// Callback receiving loading progress, see [LoadOptions::progress].
+///
+/// ```
+/// # use std::sync::Arc;
+/// # use pbrt4::{LoadOptions, ProgressCallback, Scene};
+/// let options = LoadOptions {
+///     progress: Some(ProgressCallback::new(|progress| {
+///         println!("{} / {}", progress.bytes_parsed, progress.total_bytes);
+///     })),
+///     ..Default::default()
+/// };
+///
+/// Scene::load_with_options("WorldBegin Shape \"sphere\"", None, &options)?;
+/// # Ok::<(), pbrt4::Error>(())
+/// ```
+pub struct ProgressCallback(Mutex<Box<dyn FnMut(Progress) + Send>>);
+
+impl ProgressCallback {
+    pub fn new(callback: impl FnMut(Progress) + Send + 'static) -> Arc<Self> {
+        Arc::new(Self(Mutex::new(Box::new(callback))))
+    }
+
+    fn report(&self, progress: Progress) {
+        // A panicking callback doesn't make the next report invalid.
+        let mut callback = self.0.lock().unwrap_or_else(|err| err.into_inner());
+        callback(progress)
+    }
+}
+
+impl std::fmt::Debug for ProgressCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("ProgressCallback")
+    }
+}
+
+impl LoadOptions {
+    /// Read an included or imported file with the [LoadOptions::resolver].
+    pub(crate) fn read_include(&self, path: &Path) -> Result<String> {
+        match &self.resolver {
+            Some(resolver) => resolver.resolve(path),
+            None => FsResolver.resolve(path),
+        }
+    }
+
+    /// Apply enabled preprocessing passes to a scene file's contents.
+    pub(crate) fn preprocess<'a>(&self, data: &'a str) -> Result<Cow<'a, str>> {
+        // Evaluate conditionals first, so disabled sections may reference undefined variables.
+        let data = match &self.defines {
+            Some(defines) => preprocess::conditionals(data, defines)?,
+            None => Cow::Borrowed(data),
+        };
+
+        match &self.variables {
+            Some(variables) => match preprocess::substitute(&data, variables)? {
+                Cow::Borrowed(_) => Ok(data),
+                Cow::Owned(substituted) => Ok(Cow::Owned(substituted)),
+            },
+            None => Ok(data),
+        }
+    }
+}
+
 #[derive(Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Scene {
+    /// Shutter open time, set by `TransformTimes` (0 by default).
     pub start_time: f32,
+    /// Shutter close time, set by `TransformTimes` (1 by default).
     pub end_time: f32,
     pub options: Options,
     pub camera: Option<CameraEntity>,
@@ -84,25 +416,229 @@ pub struct Scene {
     pub integrator: Option<Integrator>,
     pub accelerator: Option<Accelerator>,
     pub sampler: Option<Sampler>,
+    pub filter: Option<Filter>,
     pub textures: Vec<Texture>,
     pub materials: Vec<Material>,
-    pub lights: Vec<Light>,
+    pub lights: Vec<LightEntity>,
     pub area_lights: Vec<AreaLight>,
     pub mediums: Vec<Medium>,
     pub shapes: Vec<ShapeEntity>,
     pub objects: Vec<Object>,
-    pub instances: Vec<Instance>,
+    pub instances: Vec<InstanceEntity>,
+    /// External files referenced by the scene (includes, meshes, images, ...),
+    /// in the order they are first referenced.
+    pub dependencies: Vec<Dependency>,
+    /// Source structure of the scene files, if loaded with [LoadOptions::keep_ast].
+    pub document: Option<Document>,
+    /// Problems found while loading which don't prevent it (e.g. references to undefined
+    /// named materials), see [Scene::validate].
+    pub issues: Vec<ValidationIssue>,
 }
 
 impl Scene {
+    /// Names of objects defined with `ObjectBegin`, in the order of [Scene::objects].
+    pub fn object_names(&self) -> impl Iterator<Item = &str> {
+        self.objects.iter().map(|object| object.name.as_str())
+    }
+
+    /// Find an object by name.
+    pub fn object_by_name(&self, name: &str) -> Option<(usize, &Object)> {
+        self.objects
+            .iter()
+            .enumerate()
+            .find(|(_, object)| object.name == name)
+    }
+
+    /// Shapes defined inside the object at `object_index`.
+    ///
+    /// Returns an empty slice if the object is empty or if shapes were not stored
+    /// (see [Scene::load_with_shape_callback]).
+    pub fn object_shapes(&self, object_index: usize) -> &[ShapeEntity] {
+        self.objects
+            .get(object_index)
+            .and_then(Object::shape_range)
+            .and_then(|range| self.shapes.get(range))
+            .unwrap_or_default()
+    }
+
+    /// Union of world space bounds of all shapes, expanding object instances.
+    ///
+    /// Shapes with unknown bounds (e.g. PLY meshes which were not loaded) are ignored,
+    /// returns `None` if no shape has known bounds.
+    pub fn world_bounds(&self) -> Option<Bounds3> {
+        let mut bounds = Bounds3::EMPTY;
+
+        for shape in self.shapes.iter().filter(|s| s.object_index.is_none()) {
+            if let Some(b) = shape.world_bounds() {
+                bounds = bounds.union(b);
+            }
+        }
+
+        for instance in &self.instances {
+            for shape in self.object_shapes(instance.object_index) {
+                if let Some(b) = shape.world_bounds() {
+                    bounds = bounds.union(b.transform(&instance.instance_to_world));
+
+                    if instance.instance_to_world_end != instance.instance_to_world {
+                        bounds = bounds.union(b.transform(&instance.instance_to_world_end));
+                    }
+                }
+            }
+        }
+
+        (!bounds.is_empty()).then_some(bounds)
+    }
+
+    /// Replace object instances with copies of the objects' shapes, transformed to world space,
+    /// for consumers which don't support instancing.
+    ///
+    /// Shapes of object definitions are removed from [Scene::shapes], copies are appended and
+    /// [Scene::objects] and [Scene::instances] are cleared, so the result has no instancing.
+    /// Copies get the instance's area light if they have none. Shape indices in [Scene::issues]
+    /// are updated, issues of removed shapes, objects and instances are dropped.
+    ///
+    /// `limit` is the maximum number of shapes to create, if flattening would exceed it
+    /// [Error::TooManyInstancedShapes] is returned and the scene is left unchanged.
+    /// Returns the number of created shapes.
+    pub fn flatten_instances(&mut self, limit: Option<usize>) -> Result<usize> {
+        let count = self
+            .instances
+            .iter()
+            .map(|instance| self.object_shapes(instance.object_index).len())
+            .sum::<usize>();
+
+        if let Some(limit) = limit {
+            if count > limit {
+                return Err(Error::TooManyInstancedShapes { count, limit });
+            }
+        }
+
+        let mut flattened = Vec::with_capacity(count);
+        for instance in mem::take(&mut self.instances) {
+            for shape in self.object_shapes(instance.object_index) {
+                let transform = instance.instance_to_world * shape.transform;
+
+                flattened.push(ShapeEntity {
+                    transform,
+                    transform_end: instance.instance_to_world_end * shape.transform_end,
+                    area_light_index: shape.area_light_index.or(instance.area_light_index),
+                    object_index: None,
+                    bounds: shape
+                        .bounds
+                        .and_then(|_| shape.params.bounds())
+                        .map(|bounds| bounds.transform(&transform)),
+                    ..shape.clone()
+                });
+            }
+        }
+
+        // New index of each shape, `None` for removed object shapes.
+        let mut next = 0;
+        let indices = self
+            .shapes
+            .iter()
+            .map(|shape| {
+                shape.object_index.is_none().then(|| {
+                    next += 1;
+                    next - 1
+                })
+            })
+            .collect::<Vec<_>>();
+
+        self.shapes.retain(|shape| shape.object_index.is_none());
+        self.shapes.extend(flattened);
+        self.objects.clear();
+
+        self.issues.retain_mut(|issue| match &mut issue.entity {
+            EntityRef::Shape(index) => match indices.get(*index).copied().flatten() {
+                Some(new) => {
+                    *index = new;
+                    true
+                }
+                None => false,
+            },
+            EntityRef::Object(_) | EntityRef::Instance(_) => false,
+            _ => true,
+        });
+
+        Ok(count)
+    }
+
     /// Load a scene from a file at path.
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Scene> {
+        Self::from_file_with_options(path, &LoadOptions::default())
+    }
+
+    /// Load a scene from a file at path using custom load options.
+    pub fn from_file_with_options<P: AsRef<Path>>(path: P, options: &LoadOptions) -> Result<Scene> {
         let path = path.as_ref();
 
-        let working_directory = path.parent();
+        let data = fs::read(path)?;
+        Self::load_bytes(&data, path.parent(), Some(path), options, None)
+    }
+
+    /// Load a PBRT v4 scene from bytes which may contain invalid UTF-8, see [crate::decode_bytes].
+    ///
+    /// Invalid sequences inside quoted strings are handled according to
+    /// [ParseOptions::invalid_utf8], so by default they are errors. [Scene::from_file] and
+    /// included files are decoded the same way.
+    ///
+    /// See [Scene::load] for arguments description.
+    pub fn from_bytes(data: &[u8], working_directory: Option<&Path>) -> Result<Scene> {
+        Self::from_bytes_with_options(data, working_directory, &LoadOptions::default())
+    }
+
+    /// Load a PBRT v4 scene from bytes using custom load options.
+    ///
+    /// See [Scene::from_bytes].
+    pub fn from_bytes_with_options(
+        data: &[u8],
+        working_directory: Option<&Path>,
+        options: &LoadOptions,
+    ) -> Result<Scene> {
+        Self::load_bytes(data, working_directory, None, options, None)
+    }
+
+    /// Load multiple scene files in parallel.
+    ///
+    /// All loads share the same `options` (and therefore the same geometry cache).
+    /// Results are returned in the same order as `paths`, so a failure to load one
+    /// scene doesn't affect the others.
+    pub fn load_many<P: AsRef<Path> + Sync>(
+        paths: &[P],
+        options: &LoadOptions,
+    ) -> Vec<Result<Scene>> {
+        let threads = options
+            .max_threads
+            .unwrap_or_else(|| thread::available_parallelism().map_or(1, |n| n.get()))
+            .clamp(1, paths.len().max(1));
+
+        let next = AtomicUsize::new(0);
+        let results = paths.iter().map(|_| Mutex::new(None)).collect::<Vec<_>>();
+
+        thread::scope(|scope| {
+            for _ in 0..threads {
+                scope.spawn(|| loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(path) = paths.get(index) else {
+                        break;
+                    };
 
-        let data = fs::read_to_string(path)?;
-        Self::load(&data, working_directory)
+                    let result = Self::from_file_with_options(path, options);
+                    *results[index].lock().unwrap() = Some(result);
+                });
+            }
+        });
+
+        results
+            .into_iter()
+            .map(|result| {
+                result
+                    .into_inner()
+                    .unwrap()
+                    .expect("All scenes must be processed")
+            })
+            .collect()
     }
 
     /// Load a PBRT v4 scene from a string slice.
@@ -110,145 +646,571 @@ impl Scene {
     /// # Arguments
     /// - `data` is a string buffer with the file data.
     /// - `working_directory` is a file's directory path which required for includes
-    /// with relative paths to work.
+    ///   with relative paths to work.
     pub fn load(data: &str, working_directory: Option<&Path>) -> Result<Scene> {
-        let mut scene = Scene::default();
-
-        let mut parsers = Vec::new();
-        parsers.push(Parser::new(data));
-
-        let mut current_state = State::default();
-        let mut states_stack = Vec::new();
-        let mut is_world_block = false;
-
-        let mut named_coord_systems: HashMap<String, Mat4> = HashMap::default();
-
-        // Texture name to index.
-        let mut named_textures: HashMap<String, usize> = HashMap::default();
-        let mut named_materials: HashMap<String, usize> = HashMap::default();
-        let mut named_mediums: HashMap<String, usize> = HashMap::default();
-        let mut named_objects: HashMap<String, usize> = HashMap::default();
-
-        // Because data from included files might end up in cached parameters,
-        // we should keep the file data around until scene loading is done.
-        let mut includes = Vec::new();
-
-        while let Some(parser) = parsers.last_mut() {
-            // Fetch next element.
-            let element = match parser.parse_next() {
-                Ok(element) => element,
-                Err(err) if matches!(err, Error::EndOfFile) => {
-                    // Remove parser from the stack.
-                    parsers.pop();
+        Self::load_with_options(data, working_directory, &LoadOptions::default())
+    }
+
+    /// Load a PBRT v4 scene from a string slice using custom load options.
+    ///
+    /// See [Scene::load] for arguments description.
+    pub fn load_with_options(
+        data: &str,
+        working_directory: Option<&Path>,
+        options: &LoadOptions,
+    ) -> Result<Scene> {
+        Self::load_impl(data, working_directory, None, options, None)
+    }
+
+    /// Load a PBRT v4 scene along with the problems found while loading it, like unused
+    /// parameters, overridden `Film` directives or recovered parse errors.
+    ///
+    /// Diagnostics are moved out of [Scene::issues] and are located at the statement
+    /// they were found at.
+    ///
+    /// ```
+    /// # use pbrt4::{LoadOptions, Scene};
+    /// let data = "Film \"rgb\" \"integer xres\" 400\nFilm \"rgb\"\nWorldBegin";
+    /// let (scene, diagnostics) = Scene::load_with_diagnostics(data, None, &LoadOptions::default())?;
+    ///
+    /// assert!(scene.issues.is_empty());
+    /// assert_eq!(diagnostics.len(), 2);
+    /// assert_eq!(diagnostics[1].location.as_ref().unwrap().line, 2);
+    /// # Ok::<(), pbrt4::Error>(())
+    /// ```
+    pub fn load_with_diagnostics(
+        data: &str,
+        working_directory: Option<&Path>,
+        options: &LoadOptions,
+    ) -> Result<(Scene, Vec<Diagnostic>)> {
+        let mut scene = Self::load_with_options(data, working_directory, options)?;
+        let diagnostics = mem::take(&mut scene.issues);
+
+        Ok((scene, diagnostics))
+    }
+
+    /// Load a PBRT v4 scene, reading included and imported files with `resolver`.
+    ///
+    /// Relative paths are joined with `working_directory` (if any) before they're passed
+    /// to the resolver, so without a working directory the resolver gets paths as written.
+    pub fn load_with_resolver<R: IncludeResolver + 'static>(
+        data: &str,
+        working_directory: Option<&Path>,
+        resolver: R,
+    ) -> Result<Scene> {
+        let options = LoadOptions {
+            resolver: Some(Arc::new(resolver)),
+            ..Default::default()
+        };
+
+        let working_directory = working_directory.unwrap_or(Path::new(""));
+        Self::load_impl(data, Some(working_directory), None, &options, None)
+    }
+
+    /// Load a PBRT v4 scene, reading included and imported files asynchronously with `resolver`.
+    ///
+    /// All referenced files are read before parsing starts, the parsing itself is synchronous.
+    /// The returned future doesn't depend on a particular async runtime.
+    ///
+    /// `working_directory` is handled like in [Scene::load_with_resolver], [LoadOptions::resolver]
+    /// is ignored.
+    pub async fn load_async(
+        data: &str,
+        working_directory: Option<&Path>,
+        options: &LoadOptions,
+        resolver: &dyn AsyncIncludeResolver,
+    ) -> Result<Scene> {
+        Self::load_async_impl(data, working_directory, None, options, resolver).await
+    }
+
+    /// Load a scene file asynchronously, the file itself is read with `resolver` as well.
+    ///
+    /// See [Scene::load_async].
+    pub async fn from_file_async<P: AsRef<Path>>(
+        path: P,
+        options: &LoadOptions,
+        resolver: &dyn AsyncIncludeResolver,
+    ) -> Result<Scene> {
+        let path = path.as_ref();
+        let data = resolver.resolve(path).await?;
+
+        Self::load_async_impl(&data, path.parent(), Some(path), options, resolver).await
+    }
+
+    async fn load_async_impl(
+        data: &str,
+        working_directory: Option<&Path>,
+        file: Option<&Path>,
+        options: &LoadOptions,
+        resolver: &dyn AsyncIncludeResolver,
+    ) -> Result<Scene> {
+        let working_directory = working_directory.unwrap_or(Path::new(""));
+
+        let mut files = HashMap::new();
+        let mut pending = vec![options.preprocess(data)?.into_owned()];
+
+        while let Some(data) = pending.pop() {
+            // Invalid includes are reported with their location while loading.
+            let paths = includes(&data, Some(working_directory))
+                .filter_map(|(path, _)| path.ok())
+                .collect::<Vec<_>>();
+
+            for path in paths {
+                if files.contains_key(&path) {
                     continue;
                 }
-                Err(err) => return Err(err),
+
+                let contents = resolver.resolve(&path).await;
+                if let Ok(contents) = &contents {
+                    if let Ok(contents) = options.preprocess(contents) {
+                        pending.push(contents.into_owned());
+                    }
+                }
+
+                files.insert(path, contents);
+            }
+        }
+
+        let options = LoadOptions {
+            resolver: Some(Arc::new(Prefetched::new(files))),
+            ..options.clone()
+        };
+
+        Self::load_impl(data, Some(working_directory), file, &options, None)
+    }
+
+    /// Load a scene file, handing each shape to `on_shape` instead of storing it in [Scene::shapes].
+    ///
+    /// See [Scene::load_with_shape_callback].
+    pub fn from_file_with_shape_callback<P, F>(
+        path: P,
+        options: &LoadOptions,
+        mut on_shape: F,
+    ) -> Result<Scene>
+    where
+        P: AsRef<Path>,
+        F: FnMut(usize, ShapeEntity) -> Result<()>,
+    {
+        let path = path.as_ref();
+        let data = fs::read(path)?;
+
+        Self::load_bytes(
+            &data,
+            path.parent(),
+            Some(path),
+            options,
+            Some(&mut on_shape),
+        )
+    }
+
+    /// Load a PBRT v4 scene, handing each shape to `on_shape` as soon as it's parsed.
+    ///
+    /// Shapes are never accumulated in [Scene::shapes] (which remains empty), so
+    /// converters can process arbitrarily large scenes with flat memory usage.
+    ///
+    /// The callback receives the shape's sequential index, which is what [Object::shape_start]
+    /// refers to in this mode. Returning an error from the callback aborts loading.
+    ///
+    /// See [Scene::load] for arguments description.
+    pub fn load_with_shape_callback<F>(
+        data: &str,
+        working_directory: Option<&Path>,
+        options: &LoadOptions,
+        mut on_shape: F,
+    ) -> Result<Scene>
+    where
+        F: FnMut(usize, ShapeEntity) -> Result<()>,
+    {
+        Self::load_impl(data, working_directory, None, options, Some(&mut on_shape))
+    }
+
+    /// Decode and load a scene, warnings about invalid UTF-8 come first in [Scene::issues].
+    fn load_bytes(
+        data: &[u8],
+        working_directory: Option<&Path>,
+        file: Option<&Path>,
+        options: &LoadOptions,
+        on_shape: Option<&mut dyn FnMut(usize, ShapeEntity) -> Result<()>>,
+    ) -> Result<Scene> {
+        let (data, issues) = decode(data, file, options)?;
+
+        let mut scene = Self::load_impl(&data, working_directory, file, options, on_shape)?;
+        scene.issues.splice(0..0, issues);
+
+        Ok(scene)
+    }
+
+    /// Load a scene, `file` is the path of the main scene file (if any) used for error locations.
+    pub(crate) fn load_impl(
+        data: &str,
+        working_directory: Option<&Path>,
+        file: Option<&Path>,
+        options: &LoadOptions,
+        on_shape: Option<&mut dyn FnMut(usize, ShapeEntity) -> Result<()>>,
+    ) -> Result<Scene> {
+        let mut collector = SceneCollector {
+            scene: Scene {
+                end_time: 1.0,
+                ..Default::default()
+            },
+            on_shape,
+            dependencies: HashSet::new(),
+        };
+
+        if options.keep_ast {
+            collector.scene.document = Some(Document::new(data, working_directory, options)?);
+        }
+
+        visit_impl(data, working_directory, file, options, &mut collector)?;
+
+        Ok(collector.scene)
+    }
+}
+
+/// Run the graphics state machinery over the scene, handing entities to `visitor`.
+pub(crate) fn visit_impl(
+    data: &str,
+    working_directory: Option<&Path>,
+    file: Option<&Path>,
+    options: &LoadOptions,
+    visitor: &mut dyn SceneVisitor,
+) -> Result<()> {
+    let mut scene_options = Options::default();
+
+    // Number of entities visited so far.
+    let mut shape_count = 0;
+    let mut texture_count = 0;
+    let mut material_count = 0;
+    let mut light_count = 0;
+    let mut area_light_count = 0;
+    let mut medium_count = 0;
+    let mut object_count = 0;
+    let mut instance_count = 0;
+
+    // Object being defined between ObjectBegin and ObjectEnd.
+    let mut current_object: Option<Object> = None;
+
+    // Types of textures by index, to check material parameters.
+    let mut texture_types = Vec::new();
+
+    let data = options.preprocess(data)?;
+
+    // Because data from included files might end up in cached parameters,
+    // we should keep the file data around until scene loading is done.
+    let mut sources = Sources::default();
+    sources.load(&data, file, working_directory, options, &mut Vec::new())?;
+
+    for warning in mem::take(&mut sources.warnings) {
+        visitor.on_issue(parse_warning(warning))?;
+    }
+
+    let mut parsers = Vec::new();
+    parsers.push(Parser::with_options(&data, options.parse));
+
+    // Bytes of files parsed to the end, and the number of parsed bytes at the last report.
+    let total_bytes = data.len() + sources.files.values().map(|s| s.len()).sum::<usize>();
+    let mut finished_bytes = 0;
+    let mut reported_bytes = None;
+
+    // Paths of the files being parsed, one per parser.
+    let mut files = vec![file.map(Path::to_path_buf)];
+
+    let mut current_state = State::default();
+    let mut states_stack = Vec::new();
+    let mut is_world_block = false;
+
+    // Start and end CTMs by name.
+    let mut named_coord_systems: HashMap<String, (Mat4, Mat4)> = HashMap::default();
+
+    let mut names = Names::default();
+
+    // Files being imported, innermost last.
+    let mut imports: Vec<ImportFrame> = Vec::new();
+
+    // Directives which may appear once, to report overridden ones.
+    let mut defined = HashSet::new();
+
+    loop {
+        if let Some(callback) = &options.progress {
+            let bytes_parsed = finished_bytes + parsers.iter().map(Parser::offset).sum::<usize>();
+
+            // Report when a file is entered or left (the number of parsers changes).
+            let due = match reported_bytes {
+                Some((bytes, depth)) => {
+                    depth != parsers.len() || bytes_parsed >= bytes + PROGRESS_INTERVAL
+                }
+                None => true,
             };
 
+            if due {
+                reported_bytes = Some((bytes_parsed, parsers.len()));
+                callback.report(Progress {
+                    bytes_parsed,
+                    total_bytes,
+                    file: files.last().cloned().flatten(),
+                    shapes: shape_count,
+                    lights: light_count,
+                    materials: material_count,
+                    textures: texture_count,
+                    instances: instance_count,
+                });
+            }
+        }
+
+        if let Some(cancel) = &options.cancel {
+            if cancel.load(Ordering::Relaxed) {
+                return Err(Error::Cancelled);
+            }
+        }
+
+        let Some(parser) = parsers.last_mut() else {
+            break;
+        };
+
+        // Fetch next element.
+        let element = match parser.parse_next() {
+            Ok(element) => element,
+            Err(Error::EndOfFile) => {
+                // Remove parser from the stack.
+                finished_bytes += parser.offset();
+                parsers.pop();
+                files.pop();
+
+                // Leave the imported file's scope and publish its named entities.
+                if imports.last().map(|frame| frame.depth) == Some(parsers.len()) {
+                    let frame = imports.pop().expect("Import frame must exist");
+
+                    if !states_stack.is_empty() {
+                        return Err(locate(Error::ElementNotAllowed, &parsers, &files));
+                    }
+
+                    current_state = frame.state;
+                    states_stack = frame.states_stack;
+                    named_coord_systems = frame.named_coord_systems;
+
+                    let imported = mem::replace(&mut names, frame.names);
+                    names
+                        .merge(imported)
+                        .map_err(|err| locate(err, &parsers, &files))?;
+                }
+
+                continue;
+            }
+            Err(err) => return Err(locate(err, &parsers, &files)),
+        };
+
+        for warning in parser.take_warnings() {
+            visitor.on_issue(parse_warning(locate(warning, &parsers, &files)))?;
+        }
+
+        // Includes push new parsers, issues are located at the statement of the current one.
+        let depth = parsers.len();
+        let mut issues = Vec::new();
+
+        // Errors are located at the current statement, so handle the element in a closure.
+        let result = (|| -> Result<()> {
+            for dependency in element_dependencies(&element, working_directory)? {
+                visitor.on_dependency(dependency)?;
+            }
+
             match element {
                 Element::AttributeBegin => {
+                    visitor.on_attribute_begin(
+                        current_state.transform_matrix,
+                        current_state.material_index,
+                    )?;
                     states_stack.push(current_state.clone());
                 }
                 Element::AttributeEnd => match states_stack.pop() {
-                    Some(state) => current_state = state,
+                    Some(state) => {
+                        current_state = state;
+                        visitor.on_attribute_end()?;
+                    }
                     None => return Err(Error::TooManyEndAttributes),
                 },
-                Element::Attribute { target, params } => match target {
+                Element::Attribute { target, params } => match &*target {
                     "shape" => current_state.shape_params.extend(&params),
                     "light" => current_state.light_params.extend(&params),
                     "material" => current_state.material_params.extend(&params),
                     "medium" => current_state.medium_params.extend(&params),
                     "texture" => current_state.texture_params.extend(&params),
-                    _ => unimplemented!(),
+                    // Targets added by future pbrt versions shouldn't prevent loading the scene.
+                    _ => issues.push(ValidationIssue::new(
+                        Severity::Warning,
+                        EntityRef::Scene,
+                        "Attribute",
+                        format!(
+                            "unknown attribute target \"{}\", parameters are ignored",
+                            target
+                        ),
+                    )),
                 },
                 Element::ReverseOrientation => {
                     current_state.reverse_orientation = !current_state.reverse_orientation;
                 }
                 Element::Translate { v } => {
-                    current_state.transform_matrix *= Mat4::from_translation(Vec3::from(v))
+                    let m = Mat4::from_translation(Vec3::from(v));
+                    current_state.update_transform(|ctm| *ctm *= m);
                 }
                 Element::Identity => {
-                    current_state.transform_matrix = Mat4::IDENTITY;
+                    current_state.update_transform(|ctm| *ctm = Mat4::IDENTITY);
                 }
                 // Transform resets the CTM to the specified matrix.
                 Element::Transform { m } => {
-                    current_state.transform_matrix = Mat4::from_cols_array(&m);
+                    let m = Mat4::from_cols_array(&m);
+                    current_state.update_transform(|ctm| *ctm = m);
                 }
                 // An arbitrary transformation to multiply the CTM with can be specified using ConcatTransform
                 Element::ConcatTransform { m } => {
-                    current_state.transform_matrix *= Mat4::from_cols_array(&m);
+                    let m = Mat4::from_cols_array(&m);
+                    current_state.update_transform(|ctm| *ctm *= m);
                 }
                 Element::Scale { v } => {
-                    current_state.transform_matrix *= Mat4::from_scale(Vec3::from(v));
+                    let m = Mat4::from_scale(Vec3::from(v));
+                    current_state.update_transform(|ctm| *ctm *= m);
                 }
+                // The angle is in degrees.
                 Element::Rotate { angle, v } => {
-                    current_state.transform_matrix *= Mat4::from_axis_angle(Vec3::from(v), angle);
+                    let m = Mat4::from_axis_angle(Vec3::from(v), angle.to_radians());
+                    current_state.update_transform(|ctm| *ctm *= m);
                 }
                 Element::LookAt { eye, look_at, up } => {
-                    current_state.transform_matrix *=
-                        Mat4::look_at_lh(Vec3::from(eye), Vec3::from(look_at), Vec3::from(up));
+                    let m = Mat4::look_at_lh(Vec3::from(eye), Vec3::from(look_at), Vec3::from(up));
+                    current_state.update_transform(|ctm| *ctm *= m);
                 }
                 // A name can be associated with the CTM using the CoordinateSystem directive.
                 Element::CoordinateSystem { name } => {
-                    named_coord_systems.insert(name.to_string(), current_state.transform_matrix);
+                    named_coord_systems.insert(
+                        name.to_string(),
+                        (current_state.transform_matrix, current_state.transform_end),
+                    );
                 }
                 // The CTM can later be reset to the recorded transformation using CoordSysTransform.
                 Element::CoordSysTransform { name } => {
-                    match named_coord_systems.get(name).copied() {
-                        Some(mat) => current_state.transform_matrix = mat,
-                        None => {
-                            // TODO: Material not found, return error.
-                            unimplemented!()
+                    match named_coord_systems.get(&*name).copied() {
+                        Some((start, end)) => {
+                            current_state.transform_matrix = start;
+                            current_state.transform_end = end;
+                        }
+                        None if options.lenient => {
+                            current_state.transform_matrix = Mat4::IDENTITY;
+                            current_state.transform_end = Mat4::IDENTITY;
+
+                            issues.push(ValidationIssue::new(
+                                Severity::Warning,
+                                EntityRef::Scene,
+                                "CoordSysTransform",
+                                format!(
+                                    "coordinate system \"{}\" is not defined, identity is used",
+                                    name
+                                ),
+                            ));
                         }
+                        None => return Err(Error::UnknownCoordinateSystem(name.to_string())),
                     }
                 }
                 // The Camera directive specifies the camera used for viewing the scene.
                 Element::Camera { ty, params } => {
-                    let camera_from_world = current_state.transform_matrix;
-                    // TODO: Support transformStartTime and transformEndTime
-                    let world_from_camera = camera_from_world.inverse();
+                    let world_from_camera = current_state.transform_matrix.inverse();
+                    let world_from_camera_end = current_state.transform_end.inverse();
 
                     // pbrt automatically records the camera transformation matrix in the "camera" named coordinate system.
                     // This can be useful for placing light sources with respect to the camera, for example.
-
-                    // TODO: Fix key
-                    named_coord_systems.insert("camera".to_string(), world_from_camera);
-
-                    let camera = Camera::new(ty, params)?;
+                    named_coord_systems.insert(
+                        "camera".to_string(),
+                        (world_from_camera, world_from_camera_end),
+                    );
+
+                    warn_duplicate(
+                        &mut defined,
+                        Directive::Camera,
+                        EntityRef::Camera,
+                        &mut issues,
+                    );
+                    let camera = Camera::new(&ty, params)?;
 
                     let entity = CameraEntity {
                         params: camera,
                         transform: world_from_camera,
+                        transform_end: world_from_camera_end,
+                        exterior_medium_index: names.medium(
+                            current_state.current_outside_medium.as_deref(),
+                            EntityRef::Camera,
+                            &mut issues,
+                        ),
                     };
 
-                    scene.camera = Some(entity);
+                    visitor.on_camera(entity)?;
                 }
                 Element::Film { ty, params } => {
-                    debug_assert!(scene.film.is_none());
-                    let film = Film::new(ty, params)?;
-                    scene.film = Some(film);
+                    warn_duplicate(&mut defined, Directive::Film, EntityRef::Film, &mut issues);
+
+                    let film_type = FilmType::param_names(&ty).unwrap_or_default();
+                    let unused = params.unused::<Film>();
+                    let unused = unused
+                        .into_iter()
+                        .filter(|param| !film_type.contains(&param.name));
+                    warn_unused(unused, &ty, EntityRef::Film, &mut issues);
+
+                    let film = Film::new(&ty, params)?;
+                    visitor.on_film(film)?;
                 }
                 Element::Integrator { ty, params } => {
-                    debug_assert!(scene.integrator.is_none());
-                    let integrator = Integrator::new(ty, params)?;
-                    scene.integrator = Some(integrator);
+                    warn_duplicate(
+                        &mut defined,
+                        Directive::Integrator,
+                        EntityRef::Integrator,
+                        &mut issues,
+                    );
+                    let integrator = Integrator::new(&ty, params)?;
+                    visitor.on_integrator(integrator)?;
                 }
                 Element::Accelerator { ty, params } => {
-                    debug_assert!(scene.accelerator.is_none());
-                    let accelerator = Accelerator::new(ty, params)?;
-                    scene.accelerator = Some(accelerator);
+                    warn_duplicate(
+                        &mut defined,
+                        Directive::Accelerator,
+                        EntityRef::Scene,
+                        &mut issues,
+                    );
+                    warn_unused(
+                        params.unused_typed::<Accelerator>(&ty),
+                        &ty,
+                        EntityRef::Scene,
+                        &mut issues,
+                    );
+
+                    let accelerator = Accelerator::new(&ty, params)?;
+                    visitor.on_accelerator(accelerator)?;
                 }
-                Element::PixelFilter { .. } => {
-                    todo!("Implement pixel filter");
+                Element::PixelFilter { ty, params } => {
+                    warn_duplicate(
+                        &mut defined,
+                        Directive::PixelFilter,
+                        EntityRef::Scene,
+                        &mut issues,
+                    );
+                    warn_unused(
+                        params.unused_typed::<Filter>(&ty),
+                        &ty,
+                        EntityRef::Scene,
+                        &mut issues,
+                    );
+
+                    let filter = Filter::new(&ty, params)?;
+                    visitor.on_filter(filter)?;
                 }
-                Element::ColorSpace { .. } => {
-                    todo!("Support color space");
+                Element::ColorSpace { ty } => {
+                    current_state.color_space = ty.parse()?;
                 }
                 Element::Sampler { ty, params } => {
-                    let sampler = Sampler::new(ty, params)?;
-
-                    debug_assert!(scene.sampler.is_none());
-                    scene.sampler = Some(sampler);
+                    warn_duplicate(
+                        &mut defined,
+                        Directive::Sampler,
+                        EntityRef::Scene,
+                        &mut issues,
+                    );
+                    let sampler = Sampler::new(&ty, params)?;
+                    visitor.on_sampler(sampler)?;
                 }
                 // pbrt supports animated transformations by allowing two transformation
                 // matrices to be specified at different times.
@@ -258,71 +1220,68 @@ impl Scene {
                         return Err(Error::WorldAlreadyStarted);
                     }
 
-                    scene.start_time = start;
-                    scene.end_time = end;
+                    visitor.on_transform_times(start, end)?;
                 }
                 // ActiveTransform directive indicates whether subsequent directives that modify the CTM should
                 // apply to the transformation at the starting time, the transformation at the ending time, or both.
-                Element::ActiveTransform { .. } => {
-                    todo!("Support animated transformations")
+                Element::ActiveTransform { ty } => {
+                    current_state.active_transform = match &*ty {
+                        "StartTime" => ActiveTransform::Start,
+                        "EndTime" => ActiveTransform::End,
+                        "All" => ActiveTransform::All,
+                        _ => return Err(Error::UnknownActiveTransform(ty.to_string())),
+                    };
                 }
                 // Include behaves similarly to the #include directive in C++: parsing of the current file is suspended,
                 // the specified file is parsed in its entirety, and only then does parsing of the current file resume.
                 // Its effect is equivalent to direct text substitution of the included file.
-                Element::Include(path) => {
-                    // If the filename given to a Include or Import statement is not an absolute path,
-                    // its path is interpreted as being relative to the directory of the initial file being parsed as
-                    // specified with pbrt's command-line arguments.
-                    let path = Path::new(path);
-
-                    let full_path;
-
-                    let path = if path.is_absolute() {
-                        path
-                    } else {
-                        full_path = match working_directory {
-                            Some(directory) => directory.join(path),
-                            // Use current working directory if not provided
-                            None => env::current_dir()?.join(path),
-                        };
-
-                        full_path.as_path()
-                    };
+                //
+                // Import is similar, but the imported file gets its own graphics state scope,
+                // and only its named entities are visible to the importing file.
+                Element::Include(ref path) | Element::Import(ref path) => {
+                    let is_import = matches!(element, Element::Import(..));
+
+                    // Import is only allowed in the world block.
+                    if is_import && !is_world_block {
+                        return Err(Error::ElementNotAllowed);
+                    }
 
-                    let data = fs::read_to_string(path)?;
+                    let path = resolve_path(working_directory, path)?;
 
-                    // Included files may be compressed using gzip.
-                    // If a scene file name has a ".gz" suffix, then pbrt will automatically decompress it as it is read from disk.
-                    if let Some(ext) = path.extension().and_then(|ext| ext.to_str()) {
-                        if ext.ends_with(".gz") {
-                            todo!("Gzip compression");
-                        }
+                    if let Some(start) = files.iter().position(|file| file.as_ref() == Some(&path))
+                    {
+                        let chain = files[start..].iter().flatten().cloned();
+                        return Err(Error::IncludeCycle(chain.chain([path]).collect()));
+                    }
+
+                    let max_depth = options.max_include_depth.unwrap_or(MAX_INCLUDE_DEPTH);
+                    if parsers.len() > max_depth {
+                        return Err(Error::IncludeDepthExceeded(max_depth));
                     }
 
-                    // In Rust, String is heap allocated type, so it's safe to keep a pointer to
-                    // the raw data and move the String object (like push it to the vector).
-                    let raw = data.as_bytes();
-                    let raw_len = raw.len();
-                    let raw_ptr = raw.as_ptr();
+                    let parser = Parser::with_options(sources.get(&path)?, options.parse);
 
-                    includes.push(data);
+                    if is_import {
+                        imports.push(ImportFrame {
+                            depth: parsers.len(),
+                            state: mem::take(&mut current_state),
+                            states_stack: mem::take(&mut states_stack),
+                            named_coord_systems: named_coord_systems.clone(),
+                            names: names.clone(),
+                        });
+                    }
 
-                    // TODO: is there a better way?
-                    let parser = Parser::new(unsafe {
-                        let byte_slice = slice::from_raw_parts(raw_ptr, raw_len);
-                        str::from_utf8_unchecked(byte_slice)
-                    });
                     parsers.push(parser);
-                }
-                Element::Import(..) => {
-                    todo!("Support imports")
+                    files.push(Some(path));
                 }
                 Element::WorldBegin => {
                     is_world_block = true;
                     current_state.transform_matrix = Mat4::IDENTITY;
+                    current_state.transform_end = Mat4::IDENTITY;
                 }
                 Element::Option(param) => {
-                    scene.options.apply(param)?;
+                    scene_options.apply(param)?;
+                    visitor.on_options(&scene_options)?;
                 }
                 Element::Texture {
                     name,
@@ -331,58 +1290,137 @@ impl Scene {
                     mut params,
                 } => {
                     params.extend(&current_state.texture_params);
-                    let texture = Texture::new(name, ty, class, params)?;
+                    let mut texture = Texture::new(&name, &ty, &class, params, &names.textures)?;
+                    texture.transform = current_state.transform_matrix;
+                    texture.color_space = current_state.color_space;
+
+                    if let (Some(directory), Some(filename)) =
+                        (working_directory, texture.class.filename_mut())
+                    {
+                        *filename = resolve_path(Some(directory), filename)?
+                            .to_string_lossy()
+                            .into_owned();
+                    }
+                    texture_types.push(texture.ty);
 
-                    let index = scene.textures.len();
-                    scene.textures.push(texture);
+                    let index = texture_count;
+                    texture_count += 1;
+                    visitor.on_texture(index, texture)?;
 
-                    named_textures.insert(name.to_string(), index);
+                    names.textures.insert(name.to_string(), index);
                 }
                 // The Material directive specifies the current material, which then applies for all subsequent
                 // shape definitions (until the end of the current attribute scope or until a new material is defined.
                 Element::Material { ty, mut params } => {
                     params.extend(&current_state.material_params);
-                    let material = Material::new(ty, params, &named_textures)?;
 
-                    let index = scene.materials.len();
-                    scene.materials.push(material);
+                    issues.extend(validate::material_textures(
+                        material_count,
+                        &params,
+                        &names.textures,
+                        &texture_types,
+                    ));
+
+                    let mut material = Material::new(&ty, params, &names.textures)?;
+                    material.color_space = current_state.color_space;
+
+                    let index = material_count;
+                    material_count += 1;
+                    visitor.on_material(index, material)?;
 
                     current_state.material_index = Some(index);
+                    current_state.missing_material = None;
                 }
                 Element::MakeNamedMaterial { name, mut params } => {
                     params.extend(&current_state.material_params);
-                    let material = Material::new(name, params, &named_textures)?;
 
-                    let index = scene.materials.len();
-                    scene.materials.push(material);
+                    issues.extend(validate::material_textures(
+                        material_count,
+                        &params,
+                        &names.textures,
+                        &texture_types,
+                    ));
+
+                    // Named materials specify their type with a parameter.
+                    let ty = params
+                        .string("type")
+                        .ok_or(Error::MissingRequiredParameter)?;
+
+                    let mut material = Material::new(&ty, params, &names.textures)?;
+                    material.name = Some(name.to_string());
+                    material.color_space = current_state.color_space;
 
-                    named_materials.insert(name.to_string(), index);
+                    let index = material_count;
+                    material_count += 1;
+                    visitor.on_material(index, material)?;
+
+                    names.materials.insert(name.to_string(), index);
                 }
                 Element::NamedMaterial { name } => {
-                    // TODO: handle material not found case.
-                    current_state.material_index = named_materials.get(name).copied();
+                    current_state.material_index = names.materials.get(&*name).copied();
+
+                    if current_state.material_index.is_none() && !options.lenient {
+                        return Err(Error::MaterialNotFound(name.to_string()));
+                    }
+
+                    // In lenient mode, shapes using an undefined material are reported and get no material.
+                    current_state.missing_material =
+                        current_state.material_index.is_none().then_some(name);
                 }
-                Element::LightSource { ty, params } => {
+                Element::LightSource { ty, mut params } => {
                     // When a light source is created, the current exterior medium is used for rays leaving the light
                     // when bidirectional light transport algorithms are used.
                     //
                     // The user is responsible for specifying media in a way such that rays reaching lights are in the same medium
                     // as rays leaving those lights.
 
-                    // TODO: Handle current_outside_medium
+                    params.extend(&current_state.light_params);
+                    let mut light = Light::new(&ty, params)?;
+                    if let Some(spectrum) = light.spectrum_mut() {
+                        load_spectrum(spectrum, working_directory, options)?;
+                    }
+
+                    // Images are resolved against the scene's directory, like included files.
+                    if let (Some(directory), Some(filename)) =
+                        (working_directory, light.filename_mut())
+                    {
+                        *filename = resolve_path(Some(directory), filename)?
+                            .to_string_lossy()
+                            .into_owned();
+                    }
 
-                    let light = Light::new(ty, params)?;
-                    scene.lights.push(light);
+                    let exterior_medium_index = names.medium(
+                        current_state.current_outside_medium.as_deref(),
+                        EntityRef::Light(light_count),
+                        &mut issues,
+                    );
+
+                    visitor.on_light(LightEntity {
+                        params: light,
+                        transform: current_state.transform_matrix,
+                        transform_end: current_state.transform_end,
+                        color_space: current_state.color_space,
+                        exterior_medium_index,
+                    })?;
+                    light_count += 1;
                 }
                 // After an AreaLightSource directive, all subsequent shapes emit light
                 // from their surfaces according to the distribution defined by the given
                 // area light implementation.
                 Element::AreaLightSource { ty, mut params } => {
                     params.extend(&current_state.light_params);
-                    let area_light = AreaLight::new(ty, params)?;
+                    let mut area_light = AreaLight::new(&ty, params)?;
+                    if let AreaLight::Diffuse {
+                        spectrum: Some(spectrum),
+                        ..
+                    } = &mut area_light
+                    {
+                        load_spectrum(spectrum, working_directory, options)?;
+                    }
 
-                    let index = scene.area_lights.len();
-                    scene.area_lights.push(area_light);
+                    let index = area_light_count;
+                    area_light_count += 1;
+                    visitor.on_area_light(index, area_light)?;
 
                     // The current area light is saved and restored inside attribute blocks;
                     // typically area light definitions are inside an AttributeBegin/AttributeEnd
@@ -394,25 +1432,67 @@ impl Scene {
                     mut params,
                 } => {
                     params.extend(&current_state.shape_params);
-                    let shape = Shape::new(ty, params)?;
+                    #[allow(unused_mut)]
+                    let mut shape = Shape::new(&ty, params)?;
+
+                    #[cfg(feature = "ply")]
+                    if let Shape::PlyMesh { filename, mesh } = &mut shape {
+                        match &options.geometry_cache {
+                            Some(cache) => {
+                                let path = resolve_path(working_directory, filename)?;
+                                *mesh = Some(cache.ply(path)?);
+                            }
+                            None if options.load_meshes => {
+                                let path = resolve_path(working_directory, filename)?;
+                                *mesh = Some(Arc::new(PlyMesh::from_file(path)?));
+                            }
+                            None => {}
+                        }
+                    }
 
                     // When a shape is created, the current interior medium is assumed to be the medium inside the shape,
                     // and the current exterior medium is assumed to be the medium outside the shape.
-                    // TODO: handle mediums
+                    let entity_ref = EntityRef::Shape(shape_count);
+                    let interior_medium_index = names.medium(
+                        current_state.current_inside_medium.as_deref(),
+                        entity_ref,
+                        &mut issues,
+                    );
+                    let exterior_medium_index = names.medium(
+                        current_state.current_outside_medium.as_deref(),
+                        entity_ref,
+                        &mut issues,
+                    );
 
                     let entity = ShapeEntity {
                         params: shape,
                         transform: current_state.transform_matrix,
+                        transform_end: current_state.transform_end,
                         reverse_orientation: current_state.reverse_orientation,
                         material_index: current_state.material_index,
                         area_light_index: current_state.area_light_index,
+                        object_index: current_state.active_object,
+                        interior_medium_index,
+                        exterior_medium_index,
+                        bounds: None,
                     };
 
-                    scene.shapes.push(entity);
+                    if let Some(name) = &current_state.missing_material {
+                        issues.push(ValidationIssue::new(
+                            Severity::Warning,
+                            EntityRef::Shape(shape_count),
+                            "material",
+                            format!("named material \"{}\" is not defined", name),
+                        ));
+                    }
+
+                    visitor.on_shape(shape_count, entity)?;
+                    shape_count += 1;
 
-                    // If inside of ObjectBegin/ObjectEnd, count the number of shapes.
-                    if current_state.active_object.is_some() {
-                        current_state.shape_count += 1;
+                    // If inside of ObjectBegin/ObjectEnd, count the number of shapes. The count
+                    // is kept on the object, as attribute blocks inside it restore the state.
+                    if let Some(object) = &mut current_object {
+                        object.shape_count += 1;
                     }
                 }
                 Element::ObjectBegin { name } => {
@@ -421,6 +1501,10 @@ impl Scene {
                         return Err(Error::NestedObjects);
                     }
 
+                    visitor.on_attribute_begin(
+                        current_state.transform_matrix,
+                        current_state.material_index,
+                    )?;
                     states_stack.push(current_state.clone());
 
                     let object = Object {
@@ -430,11 +1514,12 @@ impl Scene {
                         object_to_instance: current_state.transform_matrix,
                     };
 
-                    let index = scene.objects.len();
-                    scene.objects.push(object);
+                    let index = object_count;
+                    object_count += 1;
+                    current_object = Some(object);
 
                     current_state.active_object = Some(index);
-                    named_objects.insert(name.to_string(), index);
+                    names.objects.insert(name.to_string(), index);
                 }
                 Element::ObjectEnd => {
                     let object_index = current_state
@@ -442,46 +1527,79 @@ impl Scene {
                         .take()
                         .ok_or(Error::ElementNotAllowed)?;
 
-                    let object = &mut scene.objects[object_index];
-
-                    object.shape_count = current_state.shape_count;
+                    let mut object = current_object.take().expect("Object must be defined");
 
                     if object.shape_count > 0 {
-                        object.shape_start = Some(scene.shapes.len() - object.shape_count)
+                        object.shape_start = Some(shape_count - object.shape_count)
                     }
 
-                    current_state.shape_count = 0;
+                    visitor.on_object(object_index, object)?;
+
                     current_state.active_object = None;
 
                     match states_stack.pop() {
                         Some(state) => current_state = state,
                         None => return Err(Error::ElementNotAllowed),
                     }
+
+                    visitor.on_attribute_end()?;
                 }
                 Element::ObjectInstance { name } => {
-                    let Some(object_index) = named_objects.get(name).copied() else {
-                        return Err(Error::NotFound)
+                    // Instances can't be used inside of object definitions.
+                    if current_state.active_object.is_some() {
+                        return Err(Error::ElementNotAllowed);
+                    }
+
+                    let Some(object_index) = names.objects.get(&*name).copied() else {
+                        return Err(Error::NotFound);
                     };
 
-                    let instance = Instance {
+                    let instance = InstanceEntity {
                         // The current transformation matrix defines the world from instance space transformation.
                         instance_to_world: current_state.transform_matrix,
+                        instance_to_world_end: current_state.transform_end,
                         object_index,
                         area_light_index: current_state.area_light_index,
                         reverse_orientation: current_state.reverse_orientation,
                     };
 
-                    scene.instances.push(instance);
+                    visitor.on_instance(instance)?;
+                    instance_count += 1;
                 }
                 // MakeNamedMedium associates a user-specified name with medium scattering characteristics.
                 Element::MakeNamedMedium { name, mut params } => {
                     params.extend(&current_state.medium_params);
-                    let medium = Medium::new(params)?;
 
-                    let index = scene.mediums.len();
-                    scene.mediums.push(medium);
+                    // Media specify their type with a parameter, like named materials.
+                    let ty = params
+                        .string("type")
+                        .ok_or(Error::MissingRequiredParameter)?;
+
+                    let mut medium = Medium::new(&name, &ty, params)?;
+                    medium.transform = current_state.transform_matrix;
+
+                    if let (Some(directory), Some(filename)) =
+                        (working_directory, medium.filename_mut())
+                    {
+                        *filename = resolve_path(Some(directory), filename)?
+                            .to_string_lossy()
+                            .into_owned();
+                    }
+
+                    #[cfg(feature = "nanovdb")]
+                    if let (Some(cache), Some(filename)) =
+                        (&options.geometry_cache, medium.filename())
+                    {
+                        let grids = cache.nanovdb(resolve_path(working_directory, filename)?)?;
+                        crate::types::check_grids(filename, &grids)?;
+                        medium.grids = Some(grids);
+                    }
+
+                    let index = medium_count;
+                    medium_count += 1;
+                    visitor.on_medium(index, medium)?;
 
-                    named_mediums.insert(name.to_string(), index);
+                    names.mediums.insert(name.to_string(), index);
                 }
                 // MediumInterface directive can be used to specify the current "interior" and "exterior" media.
                 // A vacuum—no participating media—is represented by empty string "".
@@ -490,18 +1608,584 @@ impl Scene {
                     current_state.current_outside_medium = Some(exterior);
                 }
             }
-        }
 
-        debug_assert!(states_stack.is_empty());
-        debug_assert!(is_world_block);
+            Ok(())
+        })();
 
-        Ok(scene)
+        if let Err(err) = result {
+            return Err(locate(err, &parsers, &files));
+        }
+
+        let location = statement_location(&parsers[..depth], &files[..depth]);
+        for issue in issues {
+            visitor.on_issue(ValidationIssue {
+                location: location.clone(),
+                ..issue
+            })?;
+        }
     }
-}
 
-#[cfg(test)]
-mod tests {
+    // pbrt refuses to render scenes with unclosed blocks, report them but keep the loaded data.
+    let mut unclosed = states_stack.len();
+
+    if let Some(mut object) = current_object.take() {
+        unclosed -= 1;
+
+        visitor.on_issue(ValidationIssue::new(
+            Severity::Error,
+            EntityRef::Object(object_count - 1),
+            "ObjectBegin",
+            format!("object \"{}\" is missing ObjectEnd", object.name),
+        ))?;
+
+        // Finish the object, so its shapes refer to an existing object.
+        if object.shape_count > 0 {
+            object.shape_start = Some(shape_count - object.shape_count);
+        }
+        visitor.on_object(object_count - 1, object)?;
+    }
+
+    if unclosed > 0 {
+        visitor.on_issue(ValidationIssue::new(
+            Severity::Error,
+            EntityRef::Scene,
+            "AttributeBegin",
+            format!("{} AttributeBegin without matching AttributeEnd", unclosed),
+        ))?;
+    }
+
+    // Header-only files and truncated streams never reach the world block.
+    if !is_world_block {
+        visitor.on_issue(ValidationIssue::new(
+            Severity::Error,
+            EntityRef::Scene,
+            "WorldBegin",
+            "scene has no WorldBegin, nothing is rendered".to_string(),
+        ))?;
+    }
+
+    Ok(())
+}
+
+/// Stores visited entities in a [Scene].
+struct SceneCollector<'a> {
+    scene: Scene,
+    /// Dependencies added to [Scene::dependencies] so far.
+    dependencies: HashSet<Dependency>,
+    /// Receives shapes instead of [Scene::shapes] if set.
+    on_shape: Option<&'a mut dyn FnMut(usize, ShapeEntity) -> Result<()>>,
+}
+
+impl SceneVisitor for SceneCollector<'_> {
+    fn on_options(&mut self, options: &Options) -> Result<()> {
+        self.scene.options = options.clone();
+        Ok(())
+    }
+
+    fn on_transform_times(&mut self, start: f32, end: f32) -> Result<()> {
+        self.scene.start_time = start;
+        self.scene.end_time = end;
+        Ok(())
+    }
+
+    fn on_camera(&mut self, camera: CameraEntity) -> Result<()> {
+        self.scene.camera = Some(camera);
+        Ok(())
+    }
+
+    fn on_film(&mut self, film: Film) -> Result<()> {
+        self.scene.film = Some(film);
+        Ok(())
+    }
+
+    fn on_sampler(&mut self, sampler: Sampler) -> Result<()> {
+        self.scene.sampler = Some(sampler);
+        Ok(())
+    }
+
+    fn on_filter(&mut self, filter: Filter) -> Result<()> {
+        self.scene.filter = Some(filter);
+        Ok(())
+    }
+
+    fn on_integrator(&mut self, integrator: Integrator) -> Result<()> {
+        self.scene.integrator = Some(integrator);
+        Ok(())
+    }
+
+    fn on_accelerator(&mut self, accelerator: Accelerator) -> Result<()> {
+        self.scene.accelerator = Some(accelerator);
+        Ok(())
+    }
+
+    fn on_texture(&mut self, _index: usize, texture: Texture) -> Result<()> {
+        self.scene.textures.push(texture);
+        Ok(())
+    }
+
+    fn on_material(&mut self, _index: usize, material: Material) -> Result<()> {
+        self.scene.materials.push(material);
+        Ok(())
+    }
+
+    fn on_medium(&mut self, _index: usize, medium: Medium) -> Result<()> {
+        self.scene.mediums.push(medium);
+        Ok(())
+    }
+
+    fn on_light(&mut self, light: LightEntity) -> Result<()> {
+        self.scene.lights.push(light);
+        Ok(())
+    }
+
+    fn on_area_light(&mut self, _index: usize, area_light: AreaLight) -> Result<()> {
+        self.scene.area_lights.push(area_light);
+        Ok(())
+    }
+
+    fn on_shape(&mut self, index: usize, shape: ShapeEntity) -> Result<()> {
+        match self.on_shape.as_mut() {
+            Some(on_shape) => on_shape(index, shape),
+            None => {
+                self.scene.shapes.push(shape);
+                Ok(())
+            }
+        }
+    }
+
+    fn on_object(&mut self, _index: usize, object: Object) -> Result<()> {
+        self.scene.objects.push(object);
+        Ok(())
+    }
+
+    fn on_instance(&mut self, instance: InstanceEntity) -> Result<()> {
+        self.scene.instances.push(instance);
+        Ok(())
+    }
+
+    fn on_dependency(&mut self, dependency: Dependency) -> Result<()> {
+        if self.dependencies.insert(dependency.clone()) {
+            self.scene.dependencies.push(dependency);
+        }
+        Ok(())
+    }
+
+    fn on_issue(&mut self, issue: ValidationIssue) -> Result<()> {
+        self.scene.issues.push(issue);
+        Ok(())
+    }
+}
+
+/// Contents of included and imported files.
+///
+/// Files are read (and preprocessed) before parsing, so parsers and the graphics state
+/// can borrow from them for the whole duration of loading.
+#[derive(Default)]
+struct Sources {
+    files: HashMap<PathBuf, Source>,
+    /// Files which couldn't be read, see [ParseOptions::missing_includes].
+    warnings: Vec<Error>,
+}
+
+/// Contents of a scene file.
+enum Source {
+    Owned(String),
+    #[cfg(feature = "mmap")]
+    Mapped(crate::mmap::MappedStr),
+}
+
+impl Source {
+    /// Read a file, files read from disk which are not valid UTF-8 are returned as bytes
+    /// to be decoded with [decode_bytes].
+    fn read(path: &Path, options: &LoadOptions) -> Result<std::result::Result<Source, Vec<u8>>> {
+        #[cfg(feature = "mmap")]
+        if options.mmap.is_some() && options.resolver.is_none() {
+            return Ok(Ok(Source::Mapped(crate::mmap::MappedStr::open(path)?)));
+        }
+
+        if options.resolver.is_some() {
+            return Ok(Ok(Source::Owned(options.read_include(path)?)));
+        }
+
+        Ok(String::from_utf8(fs::read(path)?)
+            .map(Source::Owned)
+            .map_err(|err| err.into_bytes()))
+    }
+
+    /// Apply preprocessing passes, mapped files are only copied when they change.
+    fn preprocess(self, options: &LoadOptions) -> Result<Source> {
+        let processed = match options.preprocess(&self)? {
+            Cow::Borrowed(_) => None,
+            Cow::Owned(processed) => Some(processed),
+        };
+
+        Ok(processed.map_or(self, Source::Owned))
+    }
+}
+
+impl std::ops::Deref for Source {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        match self {
+            Source::Owned(data) => data,
+            #[cfg(feature = "mmap")]
+            Source::Mapped(data) => data,
+        }
+    }
+}
+
+impl Sources {
+    /// Read files included or imported (recursively) by `data`.
+    ///
+    /// `file` is the path of `data` (if any) and `stack` are the files including it,
+    /// both are used for error locations.
+    fn load(
+        &mut self,
+        data: &str,
+        file: Option<&Path>,
+        working_directory: Option<&Path>,
+        options: &LoadOptions,
+        stack: &mut Vec<PathBuf>,
+    ) -> Result<()> {
+        for (path, offset) in includes(data, working_directory) {
+            let locate = |err: Error| {
+                err.at(|| Location {
+                    file: file.map(Path::to_path_buf),
+                    include_stack: stack.clone(),
+                    ..Location::new(data, offset)
+                })
+            };
+
+            let path = path.map_err(locate)?;
+
+            // Files can be included more than once.
+            if self.files.contains_key(&path) {
+                continue;
+            }
+
+            // Included files may be compressed using gzip.
+            // If a scene file name has a ".gz" suffix, then pbrt will automatically decompress it as it is read from disk.
+            if path.extension().map_or(false, |ext| ext == "gz") {
+                return Err(locate(Error::CompressedInclude(path)));
+            }
+
+            let contents = match Source::read(&path, options) {
+                Ok(Ok(contents)) => contents.preprocess(options)?,
+                Ok(Err(bytes)) => {
+                    // Decoding problems are located in the included file.
+                    let locate = |err: Error| match err {
+                        Error::Located {
+                            mut location,
+                            source,
+                        } => {
+                            location.file = Some(path.clone());
+                            location.include_stack = stack
+                                .iter()
+                                .cloned()
+                                .chain(file.map(Path::to_path_buf))
+                                .collect();
+                            Error::Located { location, source }
+                        }
+                        err => err,
+                    };
+
+                    let (data, warnings) =
+                        decode_bytes(&bytes, options.parse.invalid_utf8).map_err(locate)?;
+                    self.warnings.extend(warnings.into_iter().map(locate));
+
+                    Source::Owned(data.into_owned()).preprocess(options)?
+                }
+                Err(err) => {
+                    match options.parse.missing_includes {
+                        Strictness::Error => return Err(locate(err)),
+                        Strictness::Warn => self.warnings.push(locate(err)),
+                        Strictness::Ignore => {}
+                    }
+
+                    self.files.insert(path, Source::Owned(String::new()));
+                    continue;
+                }
+            };
+
+            // Mark as loaded before recursing, so recursive includes terminate.
+            self.files
+                .insert(path.clone(), Source::Owned(String::new()));
+
+            if let Some(file) = file {
+                stack.push(file.to_path_buf());
+            }
+
+            self.load(&contents, Some(&path), working_directory, options, stack)?;
+
+            if file.is_some() {
+                stack.pop();
+            }
+
+            self.files.insert(path, contents);
+        }
+
+        Ok(())
+    }
+
+    fn get(&self, path: &Path) -> Result<&str> {
+        self.files
+            .get(path)
+            .map(|source| &**source)
+            .ok_or(Error::NotFound)
+    }
+}
+
+/// Named entities, visible to all subsequent directives.
+#[derive(Debug, Default, Clone)]
+struct Names {
+    textures: HashMap<String, usize>,
+    materials: HashMap<String, usize>,
+    mediums: HashMap<String, usize>,
+    objects: HashMap<String, usize>,
+}
+
+impl Names {
+    /// Index of a medium set by `MediumInterface`, `None` for vacuum (an empty name).
+    ///
+    /// Undefined media are reported as issues of `entity` and treated as vacuum.
+    fn medium(
+        &self,
+        name: Option<&str>,
+        entity: EntityRef,
+        issues: &mut Vec<ValidationIssue>,
+    ) -> Option<usize> {
+        let name = name.filter(|name| !name.is_empty())?;
+
+        let index = self.mediums.get(name).copied();
+        if index.is_none() {
+            issues.push(ValidationIssue::new(
+                Severity::Error,
+                entity,
+                "MediumInterface",
+                format!("named medium \"{}\" is not defined", name),
+            ));
+        }
+
+        index
+    }
+
+    /// Merge names defined by an imported file.
+    ///
+    /// `imported` starts as a copy of `self`, so only the entries that differ are new.
+    /// Returns [Error::DuplicatedName] if the imported file redefined an existing name.
+    fn merge(&mut self, imported: Names) -> Result<()> {
+        fn merge_map(
+            existing: &mut HashMap<String, usize>,
+            imported: HashMap<String, usize>,
+        ) -> Result<()> {
+            for (name, index) in imported {
+                match existing.get(&name) {
+                    Some(existing) if *existing == index => {}
+                    Some(_) => return Err(Error::DuplicatedName(name)),
+                    None => {
+                        existing.insert(name, index);
+                    }
+                }
+            }
+
+            Ok(())
+        }
+
+        merge_map(&mut self.textures, imported.textures)?;
+        merge_map(&mut self.materials, imported.materials)?;
+        merge_map(&mut self.mediums, imported.mediums)?;
+        merge_map(&mut self.objects, imported.objects)?;
+
+        Ok(())
+    }
+}
+
+/// Importing file's scope, restored when the imported file is done.
+struct ImportFrame<'a> {
+    /// Number of parsers on the stack before the imported file.
+    depth: usize,
+    state: State<'a>,
+    states_stack: Vec<State<'a>>,
+    named_coord_systems: HashMap<String, (Mat4, Mat4)>,
+    /// Names before the import.
+    names: Names,
+}
+
+/// Report `directive` if it was already specified, only the last one takes effect.
+fn warn_duplicate(
+    defined: &mut HashSet<Directive>,
+    directive: Directive,
+    entity: EntityRef,
+    issues: &mut Vec<ValidationIssue>,
+) {
+    if !defined.insert(directive) {
+        issues.push(ValidationIssue::new(
+            Severity::Warning,
+            entity,
+            &directive.to_string(),
+            format!("multiple {} directives, the last one is used", directive),
+        ));
+    }
+}
+
+/// Report parameters which are not read by the `ty` entity, e.g. misspelled ones.
+fn warn_unused<'a>(
+    unused: impl IntoIterator<Item = &'a Param<'a>>,
+    ty: &str,
+    entity: EntityRef,
+    issues: &mut Vec<ValidationIssue>,
+) {
+    // Parameter lists are unordered, keep the output stable.
+    let mut names = unused
+        .into_iter()
+        .map(|param| param.name)
+        .collect::<Vec<_>>();
+    names.sort_unstable();
+
+    issues.extend(names.into_iter().map(|name| {
+        ValidationIssue::new(
+            Severity::Warning,
+            entity,
+            name,
+            format!("parameter is not used by \"{}\"", ty),
+        )
+    }));
+}
+
+/// Issue for a problem the parser recovered from.
+fn parse_warning(warning: Error) -> ValidationIssue {
+    let (location, warning) = match warning {
+        Error::Located { location, source } => (Some(*location), *source),
+        warning => (None, warning),
+    };
+
+    ValidationIssue {
+        location,
+        ..ValidationIssue::new(Severity::Warning, EntityRef::Scene, "", warning.to_string())
+    }
+}
+
+/// Attach the location of the current statement to `err`, along with the file path
+/// and the include stack.
+fn locate(err: Error, parsers: &[Parser], files: &[Option<PathBuf>]) -> Error {
+    let mut err = match parsers.last() {
+        Some(parser) => err.at(|| parser.statement_location()),
+        None => return err,
+    };
+
+    if let Error::Located { location, .. } = &mut err {
+        set_file(location, files);
+    }
+
+    err
+}
+
+/// Location of the current statement of the innermost file.
+fn statement_location(parsers: &[Parser], files: &[Option<PathBuf>]) -> Option<Location> {
+    let mut location = parsers.last()?.statement_location();
+    set_file(&mut location, files);
+
+    Some(location)
+}
+
+/// Decode a scene file's contents according to [ParseOptions::invalid_utf8],
+/// warnings are returned as issues.
+pub(crate) fn decode<'a>(
+    data: &'a [u8],
+    file: Option<&Path>,
+    options: &LoadOptions,
+) -> Result<(Cow<'a, str>, Vec<ValidationIssue>)> {
+    let files = [file.map(Path::to_path_buf)];
+    let in_file = |err: Error| match err {
+        Error::Located {
+            mut location,
+            source,
+        } => {
+            set_file(&mut location, &files);
+            Error::Located { location, source }
+        }
+        err => err,
+    };
+
+    let (data, warnings) = decode_bytes(data, options.parse.invalid_utf8).map_err(in_file)?;
+    let issues = warnings
+        .into_iter()
+        .map(|warning| parse_warning(in_file(warning)))
+        .collect();
+
+    Ok((data, issues))
+}
+
+fn set_file(location: &mut Location, files: &[Option<PathBuf>]) {
+    if let Some((file, stack)) = files.split_last() {
+        location.file = file.clone();
+        location.include_stack = stack.iter().flatten().cloned().collect();
+    }
+}
+
+/// Resolved paths of the files included or imported by `data`, along with the statement offsets.
+fn includes<'a>(
+    data: &'a str,
+    working_directory: Option<&'a Path>,
+) -> impl Iterator<Item = (Result<PathBuf>, usize)> + 'a {
+    // Avoid scanning files which can't reference other files (like large meshes).
+    let may_include = data.contains("Include") || data.contains("Import");
+
+    scan_directives(if may_include { data } else { "" })
+        .filter(|(directive, _)| matches!(directive, Directive::Include | Directive::Import))
+        .map(move |(_, span)| {
+            let path = match Parser::new(&data[span.clone()]).parse_next() {
+                Ok(Element::Include(path) | Element::Import(path)) => {
+                    resolve_path(working_directory, &path)
+                }
+                Ok(_) => Err(Error::UnexpectedToken),
+                // Report parse errors at the right position.
+                Err(err) => Err(err.into_inner()),
+            };
+
+            (path, span.start)
+        })
+}
+
+/// Replace a [Spectrum::File] with its contents if [LoadOptions::load_spectra] is set.
+fn load_spectrum(
+    spectrum: &mut Spectrum,
+    working_directory: Option<&Path>,
+    options: &LoadOptions,
+) -> Result<()> {
+    if options.load_spectra {
+        *spectrum = spectrum.load(working_directory)?;
+    }
+
+    Ok(())
+}
+
+/// Resolve a file path referenced from a scene file.
+///
+/// If the filename given to a `Include` or `Import` statement is not an absolute path,
+/// its path is interpreted as being relative to the directory of the initial file being parsed as
+/// specified with pbrt's command-line arguments.
+pub(crate) fn resolve_path(working_directory: Option<&Path>, path: &str) -> Result<PathBuf> {
+    let path = Path::new(path);
+
+    if path.is_absolute() {
+        return Ok(path.to_path_buf());
+    }
+
+    let full_path = match working_directory {
+        Some(directory) => directory.join(path),
+        // Use current working directory if not provided
+        None => env::current_dir()?.join(path),
+    };
+
+    Ok(full_path)
+}
+
+#[cfg(test)]
+mod tests {
     use super::*;
+    use crate::animation::AnimatedTransform;
 
     use tempdir::TempDir;
 
@@ -520,15 +2204,920 @@ mod tests {
             r#"
 WorldBegin
 
-Include "4.pbrt" # Include file with nexted includes
-Include "1.pbrt" # Include shap directly
+Include "4.pbrt" # Include file with nexted includes
+Include "1.pbrt" # Include shap directly
+
+        "#,
+        )?;
+
+        let scene = Scene::from_file(temp_path.join("main.pbrt"))?;
+
+        debug_assert_eq!(scene.shapes.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_compressed_include() -> Result<()> {
+        let temp_dir = TempDir::new("pbrt-compressed-include-")?;
+        let temp_path = temp_dir.path();
+
+        fs::write(temp_path.join("geometry.pbrt.gz"), [0x1f, 0x8b, 0x08, 0x00])?;
+        fs::write(
+            temp_path.join("main.pbrt"),
+            "WorldBegin\nInclude \"geometry.pbrt.gz\"",
+        )?;
+
+        let Err(err) = Scene::from_file(temp_path.join("main.pbrt")) else {
+            panic!("Expected error");
+        };
+
+        assert!(
+            matches!(err.inner(), Error::CompressedInclude(path) if *path == temp_path.join("geometry.pbrt.gz"))
+        );
+        assert_eq!(err.location().unwrap().line, 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_include_cycle() -> Result<()> {
+        let temp_dir = TempDir::new("pbrt-include-cycle-")?;
+        let temp_path = temp_dir.path();
+
+        fs::write(temp_path.join("a.pbrt"), "WorldBegin\nInclude \"b.pbrt\"")?;
+        fs::write(
+            temp_path.join("b.pbrt"),
+            "Shape \"sphere\"\nInclude \"a.pbrt\"",
+        )?;
+
+        let Err(err) = Scene::from_file(temp_path.join("a.pbrt")) else {
+            panic!("Expected error");
+        };
+
+        let Error::IncludeCycle(chain) = err.inner() else {
+            panic!("Unexpected error: {}", err);
+        };
+        assert_eq!(
+            chain,
+            &[
+                temp_path.join("a.pbrt"),
+                temp_path.join("b.pbrt"),
+                temp_path.join("a.pbrt")
+            ]
+        );
+
+        let location = err.location().unwrap();
+        assert_eq!(location.file.as_deref(), Some(&*temp_path.join("b.pbrt")));
+        assert_eq!(location.line, 2);
+
+        // Nesting limit.
+        for (i, data) in [
+            "Shape \"sphere\"",
+            "Include \"0.pbrt\"",
+            "Include \"1.pbrt\"",
+        ]
+        .iter()
+        .enumerate()
+        {
+            fs::write(temp_path.join(format!("{}.pbrt", i)), data)?;
+        }
+
+        let data = "WorldBegin\nInclude \"2.pbrt\"";
+        let options = |depth| LoadOptions {
+            max_include_depth: Some(depth),
+            ..Default::default()
+        };
+
+        let scene = Scene::load_with_options(data, Some(temp_path), &options(3))?;
+        assert_eq!(scene.shapes.len(), 1);
+
+        let Err(err) = Scene::load_with_options(data, Some(temp_path), &options(2)) else {
+            panic!("Expected error");
+        };
+        assert!(matches!(err.inner(), Error::IncludeDepthExceeded(2)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_include_resolver() -> Result<()> {
+        let resolver = |path: &Path| -> Result<String> {
+            match path.to_str() {
+                Some("assets/geometry.pbrt") => {
+                    Ok("Import \"lights.pbrt\" Shape \"sphere\"".into())
+                }
+                Some("assets/lights.pbrt") => Ok("LightSource \"point\"".into()),
+                _ => Err(Error::NotFound),
+            }
+        };
+
+        let data = "WorldBegin\nInclude \"geometry.pbrt\"";
+        let scene = Scene::load_with_resolver(data, Some(Path::new("assets")), resolver)?;
+
+        assert_eq!(scene.shapes.len(), 1);
+        assert_eq!(scene.lights.len(), 1);
+
+        // Errors of the resolver are located at the include.
+        let Err(err) = Scene::load_with_resolver(data, None, resolver) else {
+            panic!("Expected error");
+        };
+        assert!(matches!(err.inner(), Error::NotFound));
+        assert_eq!(err.location().unwrap().line, 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_async() -> Result<()> {
+        let resolver = |path: PathBuf| async move {
+            match path.to_str() {
+                Some("assets/scene.pbrt") => Ok("WorldBegin\nInclude \"geometry.pbrt\"".into()),
+                Some("assets/geometry.pbrt") => {
+                    Ok("Import \"lights.pbrt\" Shape \"sphere\"".into())
+                }
+                Some("assets/lights.pbrt") => Ok("LightSource \"point\"".into()),
+                _ => Err(Error::NotFound),
+            }
+        };
+
+        let options = LoadOptions::default();
+        let future = Scene::from_file_async("assets/scene.pbrt", &options, &resolver);
+        assert_send(&future);
+
+        let scene = block_on(future)?;
+        assert_eq!(scene.shapes.len(), 1);
+        assert_eq!(scene.lights.len(), 1);
+
+        // Errors of the resolver are located at the include.
+        let data = "WorldBegin\nShape \"sphere\"\nInclude \"missing.pbrt\"";
+        let Err(err) = block_on(Scene::load_async(data, None, &options, &resolver)) else {
+            panic!("Expected error");
+        };
+        assert!(matches!(err.inner(), Error::NotFound));
+        assert_eq!(err.location().unwrap().line, 3);
+
+        Ok(())
+    }
+
+    fn assert_send<T: Send>(_: &T) {}
+
+    /// Run a future to completion on the current thread, without a runtime.
+    fn block_on<F: std::future::Future>(future: F) -> F::Output {
+        use std::task::{Context, Poll, Wake, Waker};
+
+        struct Noop;
+
+        impl Wake for Noop {
+            fn wake(self: Arc<Self>) {}
+        }
+
+        let waker = Waker::from(Arc::new(Noop));
+        let mut context = Context::from_waker(&waker);
+        let mut future = std::pin::pin!(future);
+
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut context) {
+                return output;
+            }
+        }
+    }
+
+    #[test]
+    fn test_missing_include() -> Result<()> {
+        let temp_dir = TempDir::new("pbrt-includes-")?;
+        let temp_path = temp_dir.path();
+
+        fs::write(temp_path.join("scene.pbrt"), "\n  Include \"missing.pbrt\"")?;
+
+        let data = String::from("WorldBegin\nInclude \"scene.pbrt\"");
+
+        let Err(err) = Scene::load(&data, Some(temp_path)) else {
+            panic!("Expected error");
+        };
+
+        assert!(matches!(err.inner(), Error::Io(_)));
+
+        let location = err.location().unwrap();
+        assert_eq!(location.file, Some(temp_path.join("scene.pbrt")));
+        assert_eq!((location.line, location.column), (2, 3));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_options() -> Result<()> {
+        let temp_dir = TempDir::new("pbrt-parse-options-")?;
+        let temp_path = temp_dir.path();
+
+        fs::write(
+            temp_path.join("scene.pbrt"),
+            "Shape \"sphere\"\nInclude \"missing.pbrt\"\nFoo 1 2 3\nShape \"disk\"",
+        )?;
+
+        let data = "WorldBegin\nInclude \"scene.pbrt\"";
+        let options = LoadOptions {
+            parse: ParseOptions::lenient(),
+            ..Default::default()
+        };
+
+        let scene = Scene::load_with_options(data, Some(temp_path), &options)?;
+        assert_eq!(scene.shapes.len(), 2);
+
+        let warnings = scene
+            .issues
+            .iter()
+            .map(|issue| {
+                let location = issue.location.as_ref().unwrap();
+                assert!(location.file.as_ref().unwrap().ends_with("scene.pbrt"));
+                assert_eq!(issue.severity, Severity::Warning);
+
+                (location.line, issue.message.as_str())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            warnings,
+            [(2, "Failed to read file"), (3, "Unsupported directive")]
+        );
+
+        let options = LoadOptions {
+            parse: ParseOptions {
+                missing_includes: Strictness::Ignore,
+                unknown_directives: Strictness::Ignore,
+                ..Default::default()
+            },
+            keep_ast: true,
+            ..Default::default()
+        };
+
+        let scene = Scene::load_with_options(data, Some(temp_path), &options)?;
+        assert_eq!(scene.shapes.len(), 2);
+        assert!(scene.issues.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_utf8() -> Result<()> {
+        let temp_dir = TempDir::new("pbrt-invalid-utf8-")?;
+        let temp_path = temp_dir.path();
+
+        fs::write(
+            temp_path.join("scene.pbrt"),
+            b"# Exported by \xa9 Tool\nWorldBegin\nInclude \"shapes.pbrt\"\n",
+        )?;
+        fs::write(
+            temp_path.join("shapes.pbrt"),
+            b"Shape \"sphere\"\nMakeNamedMaterial \"gr\xfcn\" \"string type\" \"diffuse\"\n",
+        )?;
+
+        let Err(err) = Scene::from_file(temp_path.join("scene.pbrt")) else {
+            panic!("Expected error");
+        };
+        assert!(matches!(err.inner(), Error::InvalidUtf8));
+
+        let location = err.location().unwrap();
+        assert!(location.file.as_ref().unwrap().ends_with("shapes.pbrt"));
+        assert_eq!((location.line, location.column), (2, 22));
+        assert!(location.include_stack[0].ends_with("scene.pbrt"));
+
+        let options = LoadOptions {
+            parse: ParseOptions {
+                invalid_utf8: Strictness::Warn,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        let scene = Scene::from_file_with_options(temp_path.join("scene.pbrt"), &options)?;
+        assert_eq!(scene.shapes.len(), 1);
+        assert_eq!(scene.materials[0].name.as_deref(), Some("gr\u{fffd}n"));
+        assert_eq!(scene.issues.len(), 1);
+        assert_eq!(scene.issues[0].location.as_ref().unwrap().line, 2);
+
+        // Comments don't matter.
+        let scene = Scene::from_bytes(b"# \xff\xfe\nWorldBegin\nShape \"disk\"", None)?;
+        assert_eq!(scene.shapes.len(), 1);
+        assert!(scene.issues.is_empty());
+
+        let data = b"WorldBegin\nNamedMaterial \"\xff\"";
+        let Err(err) = Scene::from_bytes(data, None) else {
+            panic!("Expected error");
+        };
+        assert_eq!(err.location().unwrap().line, 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_variables() -> Result<()> {
+        let temp_dir = TempDir::new("pbrt-variables-")?;
+        let temp_path = temp_dir.path();
+
+        fs::write(
+            temp_path.join("shape.pbrt"),
+            "Shape \"sphere\" \"float radius\" {$radius}",
+        )?;
+        fs::write(
+            temp_path.join("main.pbrt"),
+            "WorldBegin\nInclude \"{$shape}.pbrt\"\n",
+        )?;
+
+        let options = LoadOptions {
+            variables: Some(HashMap::from([
+                ("shape".to_string(), "shape".to_string()),
+                ("radius".to_string(), "2.5".to_string()),
+            ])),
+            ..Default::default()
+        };
+
+        let scene = Scene::from_file_with_options(temp_path.join("main.pbrt"), &options)?;
+
+        assert!(matches!(
+            scene.shapes[0].params,
+            Shape::Sphere { radius, .. } if radius == 2.5
+        ));
+
+        // Undefined variables are reported.
+        let options = LoadOptions {
+            variables: Some(HashMap::new()),
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            Scene::from_file_with_options(temp_path.join("main.pbrt"), &options),
+            Err(Error::UndefinedVariable(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resolve_filenames() -> Result<()> {
+        let temp_dir = TempDir::new("pbrt-filenames-")?;
+        let temp_path = temp_dir.path();
+
+        let data = r#"
+            Attribute "light" "float scale" 4
+            WorldBegin
+            LightSource "projection" "string filename" "slide.png"
+            LightSource "point"
+            Texture "wood" "spectrum" "imagemap" "string filename" "textures/wood.png"
+        "#;
+
+        let scene = Scene::load(data, Some(temp_path))?;
+        assert_eq!(
+            scene.lights[0].params.filename(),
+            temp_path.join("slide.png").to_str()
+        );
+        assert!(matches!(
+            scene.lights[1].params,
+            Light::Point { scale, .. } if scale == 4.0
+        ));
+
+        assert_eq!(
+            scene.textures[0].class.filename(),
+            temp_path.join("textures/wood.png").to_str()
+        );
+
+        let scene = Scene::load(data, None)?;
+        assert_eq!(scene.lights[0].params.filename(), Some("slide.png"));
+        assert_eq!(
+            scene.textures[0].class.filename(),
+            Some("textures/wood.png")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_defines() -> Result<()> {
+        let data = r#"
+            WorldBegin
+            #ifdef PREVIEW
+            Shape "sphere" "float radius" {$radius}
+            #else
+            Shape "disk"
+            #endif
+        "#;
+
+        let options = LoadOptions {
+            variables: Some(HashMap::new()),
+            defines: Some(HashSet::new()),
+            ..Default::default()
+        };
+
+        let scene = Scene::load_with_options(data, None, &options)?;
+        assert_eq!(scene.shapes.len(), 1);
+        assert!(matches!(scene.shapes[0].params, Shape::Disk { .. }));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_shape_callback() -> Result<()> {
+        let data = r#"
+WorldBegin
+Shape "sphere"
+ObjectBegin "foo"
+Shape "disk"
+Shape "disk"
+ObjectEnd
+        "#;
+
+        let mut streamed = Vec::new();
+        let scene = Scene::load_with_shape_callback(
+            data,
+            None,
+            &LoadOptions::default(),
+            |index, shape| {
+                streamed.push((index, shape.params));
+                Ok(())
+            },
+        )?;
+
+        assert!(scene.shapes.is_empty());
+        assert_eq!(streamed.len(), 3);
+        assert!(matches!(streamed[0], (0, Shape::Sphere { .. })));
+        assert!(matches!(streamed[2], (2, Shape::Disk { .. })));
+
+        assert_eq!(scene.objects[0].shape_start, Some(1));
+        assert_eq!(scene.objects[0].shape_count, 2);
+
+        // Errors abort loading.
+        let result =
+            Scene::load_with_shape_callback(data, None, &LoadOptions::default(), |_, _| {
+                Err(Error::NotFound)
+            });
+        assert!(matches!(
+            result.map_err(Error::into_inner),
+            Err(Error::NotFound)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_motion_transforms() -> Result<()> {
+        let data = r#"
+TransformTimes 0 1
+ActiveTransform EndTime
+Translate 0 0 1
+ActiveTransform All
+Camera "perspective"
+WorldBegin
+ActiveTransform StartTime
+Translate 1 0 0
+ActiveTransform EndTime
+Translate 2 0 0
+ActiveTransform All
+Shape "sphere"
+LightSource "point"
+        "#;
+
+        let scene = Scene::load(data, None)?;
+
+        let Err(err) = Scene::load("ActiveTransform Later", None) else {
+            panic!("Expected error");
+        };
+        assert!(matches!(err.inner(), Error::UnknownActiveTransform(ty) if ty == "Later"));
+
+        let camera = scene.camera.as_ref().unwrap();
+        assert_eq!(camera.transform, Mat4::IDENTITY);
+        assert_eq!(
+            camera.transform_end,
+            Mat4::from_translation(Vec3::new(0.0, 0.0, -1.0))
+        );
+
+        let shape = &scene.shapes[0];
+        assert_eq!(
+            shape.transform,
+            Mat4::from_translation(Vec3::new(1.0, 0.0, 0.0))
+        );
+        assert_eq!(
+            shape.transform_end,
+            Mat4::from_translation(Vec3::new(2.0, 0.0, 0.0))
+        );
+
+        let light = &scene.lights[0];
+        assert_eq!(light.transform, shape.transform);
+        assert_eq!(light.transform_end, shape.transform_end);
+
+        let animated = AnimatedTransform::new(
+            shape.transform,
+            scene.start_time,
+            shape.transform_end,
+            scene.end_time,
+        );
+        assert_eq!(
+            animated.interpolate(0.5),
+            Mat4::from_translation(Vec3::new(1.5, 0.0, 0.0))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_many() -> Result<()> {
+        let temp_dir = TempDir::new("pbrt-load-many-")?;
+        let temp_path = temp_dir.path();
+
+        let mut paths = Vec::new();
+        for i in 0..5 {
+            let path = temp_path.join(format!("{}.pbrt", i));
+            fs::write(&path, "WorldBegin\nShape \"sphere\"\n".repeat(i + 1))?;
+            paths.push(path);
+        }
+
+        // Broken scene shouldn't affect the others.
+        paths.insert(2, temp_path.join("missing.pbrt"));
+
+        let scenes = Scene::load_many(&paths, &LoadOptions::default());
+        assert_eq!(scenes.len(), 6);
+
+        assert!(scenes[2].is_err());
+
+        let counts = scenes
+            .iter()
+            .filter_map(|scene| scene.as_ref().ok())
+            .map(|scene| scene.shapes.len())
+            .collect::<Vec<_>>();
+
+        assert_eq!(counts, vec![1, 2, 3, 4, 5]);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "ply")]
+    #[test]
+    fn test_geometry_cache() -> Result<()> {
+        let temp_dir = TempDir::new("pbrt-cache-")?;
+        let temp_path = temp_dir.path();
+
+        fs::write(
+            temp_path.join("mesh.ply"),
+            "ply
+format ascii 1.0
+element vertex 3
+property float x
+property float y
+property float z
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0
+1 0 0
+0 1 0
+3 0 1 2
+",
+        )?;
+
+        fs::write(
+            temp_path.join("main.pbrt"),
+            r#"
+WorldBegin
+Shape "plymesh" "string filename" "mesh.ply"
+        "#,
+        )?;
+
+        let options = LoadOptions {
+            geometry_cache: Some(Arc::new(GeometryCache::new())),
+            ..Default::default()
+        };
+
+        let first = Scene::from_file_with_options(temp_path.join("main.pbrt"), &options)?;
+        let second = Scene::from_file_with_options(temp_path.join("main.pbrt"), &options)?;
+
+        let (
+            Shape::PlyMesh {
+                mesh: Some(first), ..
+            },
+            Shape::PlyMesh {
+                mesh: Some(second), ..
+            },
+        ) = (&first.shapes[0].params, &second.shapes[0].params)
+        else {
+            panic!("Expected loaded PLY meshes");
+        };
+
+        assert_eq!(first.triangle_count(), 1);
+        assert!(Arc::ptr_eq(first, second));
+
+        Ok(())
+    }
+
+    #[cfg(feature = "nanovdb")]
+    #[test]
+    fn test_geometry_cache_grids() -> Result<()> {
+        use crate::nanovdb::tests::write_file;
+
+        let temp_dir = TempDir::new("pbrt-cache-")?;
+        let temp_path = temp_dir.path();
+
+        fs::write(temp_path.join("smoke.nvdb"), write_file(&[("density", 1)]))?;
+        fs::write(
+            temp_path.join("main.pbrt"),
+            r#"
+MakeNamedMedium "smoke" "string type" "nanovdb" "string filename" "smoke.nvdb"
+WorldBegin
+        "#,
+        )?;
+
+        let options = LoadOptions {
+            geometry_cache: Some(Arc::new(GeometryCache::new())),
+            ..Default::default()
+        };
+
+        let first = Scene::from_file_with_options(temp_path.join("main.pbrt"), &options)?;
+        let second = Scene::from_file_with_options(temp_path.join("main.pbrt"), &options)?;
+
+        let (Some(first), Some(second)) = (&first.mediums[0].grids, &second.mediums[0].grids)
+        else {
+            panic!("Expected loaded grids");
+        };
+        assert!(first.grid("density").is_some());
+        assert!(Arc::ptr_eq(first, second));
+
+        // Media are not loaded without a cache.
+        let scene = Scene::from_file(temp_path.join("main.pbrt"))?;
+        assert!(scene.mediums[0].grids.is_none());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "ply")]
+    #[test]
+    fn test_load_meshes() -> Result<()> {
+        let temp_dir = TempDir::new("pbrt-meshes-")?;
+        let temp_path = temp_dir.path();
+
+        fs::write(
+            temp_path.join("mesh.ply"),
+            "ply
+format ascii 1.0
+element vertex 3
+property float x
+property float y
+property float z
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0
+1 0 0
+0 1 0
+3 0 1 2
+",
+        )?;
+
+        let data = r#"
+WorldBegin
+Shape "plymesh" "string filename" "mesh.ply"
+        "#;
+
+        // Meshes are not loaded by default, but can be loaded on demand.
+        let scene = Scene::load(data, Some(temp_path))?;
+        let shape = &scene.shapes[0].params;
+        assert!(matches!(shape, Shape::PlyMesh { mesh: None, .. }));
+
+        let mesh = shape.load_mesh(Some(temp_path))?.unwrap();
+        assert_eq!(mesh.vertex_count(), 3);
+
+        let options = LoadOptions {
+            load_meshes: true,
+            ..Default::default()
+        };
+
+        let scene = Scene::load_with_options(data, Some(temp_path), &options)?;
+        let Shape::PlyMesh {
+            mesh: Some(mesh), ..
+        } = &scene.shapes[0].params
+        else {
+            panic!("Expected loaded PLY mesh");
+        };
+        assert_eq!(mesh.triangle_count(), 1);
+
+        // Missing files are reported.
+        assert!(Scene::load_with_options(data, None, &options).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_spectra() -> Result<()> {
+        let temp_dir = TempDir::new("pbrt-spectra-")?;
+        let temp_path = temp_dir.path();
+
+        fs::write(temp_path.join("light.spd"), "400 1\n700 0.5\n")?;
+
+        let data = r#"
+WorldBegin
+AttributeBegin
+  AreaLightSource "diffuse" "spectrum L" "light.spd"
+  Shape "sphere"
+AttributeEnd
+        "#;
+
+        let spectrum = |scene: &Scene| match &scene.area_lights[0] {
+            AreaLight::Diffuse { spectrum, .. } => spectrum.clone(),
+        };
+
+        // Files are not read by default.
+        let scene = Scene::load(data, Some(temp_path))?;
+        assert_eq!(
+            spectrum(&scene),
+            Some(Spectrum::File("light.spd".to_string()))
+        );
+
+        let options = LoadOptions {
+            load_spectra: true,
+            ..Default::default()
+        };
+
+        let scene = Scene::load_with_options(data, Some(temp_path), &options)?;
+        assert_eq!(
+            spectrum(&scene),
+            Some(Spectrum::Sampled(vec![(400.0, 1.0), (700.0, 0.5)]))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_coord_sys_transform() -> Result<()> {
+        let data = r#"
+WorldBegin
+Translate 1 0 0
+CoordinateSystem "moved"
+Translate 0 1 0
+CoordSysTransform "moved"
+Shape "sphere"
+CoordSysTransform "missing"
+Shape "sphere"
+        "#;
+
+        let Err(err) = Scene::load(data, None) else {
+            panic!("Expected error");
+        };
+        assert_eq!(err.location().unwrap().line, 8);
+        assert!(matches!(err.inner(), Error::UnknownCoordinateSystem(name) if name == "missing"));
+
+        let options = LoadOptions {
+            lenient: true,
+            ..Default::default()
+        };
+
+        let scene = Scene::load_with_options(data, None, &options)?;
+        assert_eq!(
+            scene.shapes[0].transform,
+            Mat4::from_translation(Vec3::new(1.0, 0.0, 0.0))
+        );
+        assert_eq!(scene.shapes[1].transform, Mat4::IDENTITY);
+
+        assert_eq!(scene.issues.len(), 1);
+        assert_eq!(scene.issues[0].severity, Severity::Warning);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_diagnostics() -> Result<()> {
+        let data = r#"
+PixelFilter "gaussian" "float sigma" 0.7 "float xradius" 2 "float x_radius" 2 "float alpha" 1
+Film "rgb" "string filename" "a.exr" "integer xres" 400
+Film "gbuffer" "string coordinatesystem" "world"
+Sampler "halton"
+Sampler "sobol"
+WorldBegin
+        "#;
+
+        let (scene, diagnostics) =
+            Scene::load_with_diagnostics(data, None, &LoadOptions::default())?;
+        assert!(matches!(scene.film.unwrap().ty, FilmType::GBuffer { .. }));
+        assert!(scene.issues.is_empty());
+
+        let diagnostics = diagnostics
+            .iter()
+            .map(|diagnostic| {
+                assert_eq!(diagnostic.severity, Severity::Warning);
+
+                let line = diagnostic.location.as_ref().unwrap().line;
+                (
+                    line,
+                    diagnostic.parameter.as_str(),
+                    diagnostic.message.as_str(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            diagnostics,
+            [
+                (2, "alpha", "parameter is not used by \"gaussian\""),
+                (2, "x_radius", "parameter is not used by \"gaussian\""),
+                (3, "xres", "parameter is not used by \"rgb\""),
+                (4, "Film", "multiple Film directives, the last one is used"),
+                (
+                    6,
+                    "Sampler",
+                    "multiple Sampler directives, the last one is used"
+                ),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_attribute_target() -> Result<()> {
+        let data = r#"
+WorldBegin
+Attribute "volume" "float density" 2
+Attribute "shape" "float radius" 2
+Shape "sphere"
+        "#;
+
+        let scene = Scene::load(data, None)?;
+        assert!(matches!(
+            scene.shapes[0].params,
+            Shape::Sphere { radius, .. } if radius == 2.0
+        ));
+
+        assert_eq!(scene.issues.len(), 1);
+        assert_eq!(
+            scene.issues[0].to_string(),
+            "3:1: warning: scene \"Attribute\": unknown attribute target \"volume\", parameters are ignored"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_light_transform() -> Result<()> {
+        let data = r#"
+WorldBegin
+AttributeBegin
+  LookAt 0 5 0  0 0 0  0 0 1
+  LightSource "spot"
+AttributeEnd
+LightSource "point"
+        "#;
+
+        let scene = Scene::load(data, None)?;
 
-        "#,
-        )?;
+        let look_at = Mat4::look_at_lh(
+            Vec3::new(0.0, 5.0, 0.0),
+            Vec3::ZERO,
+            Vec3::new(0.0, 0.0, 1.0),
+        );
+        assert_eq!(scene.lights[0].transform, look_at);
+        assert_eq!(scene.lights[0].transform_end, look_at);
+        assert_eq!(scene.lights[1].transform, Mat4::IDENTITY);
 
-        let scene = Scene::from_file(temp_path.join("main.pbrt"))?;
+        Ok(())
+    }
 
-        debug_assert_eq!(scene.shapes.len(), 2);
+    #[test]
+    fn test_mediums() -> Result<()> {
+        let data = r#"
+MakeNamedMedium "fog" "string type" "homogeneous"
+MediumInterface "" "fog"
+Camera "perspective"
+WorldBegin
+Translate 0 1 0
+MakeNamedMedium "smoke" "string type" "homogeneous"
+Identity
+LightSource "point"
+AttributeBegin
+  MediumInterface "smoke" "fog"
+  Shape "sphere"
+  MediumInterface "smoke" "missing"
+  Shape "sphere"
+AttributeEnd
+Shape "sphere"
+        "#;
+
+        let scene = Scene::load(data, None)?;
+
+        assert_eq!(scene.camera.unwrap().exterior_medium_index, Some(0));
+        assert_eq!(
+            scene.mediums[1].transform,
+            Mat4::from_translation(Vec3::new(0.0, 1.0, 0.0))
+        );
+        assert_eq!(scene.lights[0].exterior_medium_index, Some(0));
+
+        let media = scene
+            .shapes
+            .iter()
+            .map(|shape| (shape.interior_medium_index, shape.exterior_medium_index))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            media,
+            [(Some(1), Some(0)), (Some(1), None), (None, Some(0))]
+        );
+
+        // Undefined media are reported.
+        assert_eq!(scene.issues.len(), 1);
+        assert_eq!(scene.issues[0].entity, EntityRef::Shape(1));
 
         Ok(())
     }
@@ -573,4 +3162,674 @@ ObjectInstance "foo"
 
         Ok(())
     }
+
+    #[test]
+    fn test_object_nested_attributes() -> Result<()> {
+        let data = r#"
+WorldBegin
+Shape "disk"
+ObjectBegin "foo"
+AttributeBegin
+  Shape "sphere"
+  Shape "sphere"
+AttributeEnd
+Shape "cylinder"
+ObjectEnd
+Shape "disk"
+        "#;
+
+        let scene = Scene::load(data, None)?;
+
+        let object = &scene.objects[0];
+        assert_eq!(object.shape_start, Some(1));
+        assert_eq!(object.shape_count, 3);
+
+        let shapes = scene.object_shapes(0);
+        assert_eq!(shapes.len(), 3);
+        assert!(shapes.iter().all(|shape| shape.object_index == Some(0)));
+        assert!(matches!(shapes[2].params, Shape::Cylinder { .. }));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_import() -> Result<()> {
+        let temp_dir = TempDir::new("pbrt-import-")?;
+        let temp_path = temp_dir.path();
+
+        fs::write(
+            temp_path.join("assets.pbrt"),
+            r#"
+Translate 0 0 5
+ReverseOrientation
+MakeNamedMaterial "red" "string type" "diffuse"
+ObjectBegin "ball"
+Shape "sphere"
+ObjectEnd
+NamedMaterial "red"
+Shape "disk"
+            "#,
+        )?;
+
+        let data = r#"
+WorldBegin
+Translate 1 0 0
+Material "conductor"
+Import "assets.pbrt"
+Shape "cylinder"
+NamedMaterial "red"
+ObjectInstance "ball"
+        "#;
+
+        let scene = Scene::load(data, Some(temp_path))?;
+
+        assert_eq!(scene.shapes.len(), 3);
+
+        // Imported file starts with a fresh graphics state.
+        let disk = &scene.shapes[1];
+        assert_eq!(
+            disk.transform,
+            Mat4::from_translation(Vec3::new(0.0, 0.0, 5.0))
+        );
+        assert!(disk.reverse_orientation);
+
+        // And doesn't modify the importer's state.
+        let cylinder = &scene.shapes[2];
+        assert_eq!(
+            cylinder.transform,
+            Mat4::from_translation(Vec3::new(1.0, 0.0, 0.0))
+        );
+        assert!(!cylinder.reverse_orientation);
+        assert_eq!(cylinder.material_index, Some(0));
+
+        // Named entities are merged.
+        assert_eq!(scene.instances.len(), 1);
+        assert_eq!(scene.instances[0].object_index, 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_nested_import() -> Result<()> {
+        let temp_dir = TempDir::new("pbrt-import-")?;
+        let temp_path = temp_dir.path();
+
+        fs::write(
+            temp_path.join("materials.pbrt"),
+            r#"
+Scale 2 2 2
+MakeNamedMaterial "red" "string type" "diffuse"
+            "#,
+        )?;
+
+        // Includes share the importing file's scope.
+        fs::write(temp_path.join("translate.pbrt"), "Translate 0 0 5")?;
+
+        fs::write(
+            temp_path.join("assets.pbrt"),
+            r#"
+Include "translate.pbrt"
+Import "materials.pbrt"
+NamedMaterial "red"
+Shape "sphere"
+            "#,
+        )?;
+
+        let data = r#"
+WorldBegin
+Import "assets.pbrt"
+NamedMaterial "red"
+Shape "disk"
+        "#;
+
+        let scene = Scene::load(data, Some(temp_path))?;
+
+        assert_eq!(scene.shapes.len(), 2);
+
+        let sphere = &scene.shapes[0];
+        assert_eq!(
+            sphere.transform,
+            Mat4::from_translation(Vec3::new(0.0, 0.0, 5.0))
+        );
+        assert_eq!(sphere.material_index, Some(0));
+
+        // Names propagate through all import levels.
+        let disk = &scene.shapes[1];
+        assert_eq!(disk.transform, Mat4::IDENTITY);
+        assert_eq!(disk.material_index, Some(0));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_conflict() -> Result<()> {
+        let temp_dir = TempDir::new("pbrt-import-")?;
+        let temp_path = temp_dir.path();
+
+        fs::write(
+            temp_path.join("assets.pbrt"),
+            r#"MakeNamedMaterial "red" "string type" "diffuse""#,
+        )?;
+
+        let data = r#"
+WorldBegin
+MakeNamedMaterial "red" "string type" "coateddiffuse"
+Import "assets.pbrt"
+        "#;
+
+        assert!(matches!(
+            Scene::load(data, Some(temp_path)).map_err(Error::into_inner),
+            Err(Error::DuplicatedName(name)) if name == "red"
+        ));
+
+        // Import is only allowed in the world block.
+        assert!(matches!(
+            Scene::load("Import \"assets.pbrt\"\nWorldBegin", Some(temp_path))
+                .map_err(Error::into_inner),
+            Err(Error::ElementNotAllowed)
+        ));
+
+        // Attribute scopes can't cross import boundaries.
+        fs::write(temp_path.join("unbalanced.pbrt"), "AttributeEnd")?;
+        assert!(matches!(
+            Scene::load(
+                "WorldBegin\nAttributeBegin\nImport \"unbalanced.pbrt\"",
+                Some(temp_path)
+            )
+            .map_err(Error::into_inner),
+            Err(Error::TooManyEndAttributes)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_color_space() -> Result<()> {
+        let data = r#"
+ColorSpace "rec2020"
+WorldBegin
+Texture "checks" "spectrum" "checkerboard"
+AttributeBegin
+  ColorSpace "aces2065-1"
+  LightSource "point"
+  Material "diffuse"
+AttributeEnd
+MakeNamedMaterial "red" "string type" "diffuse"
+        "#;
+
+        let scene = Scene::load(data, None)?;
+
+        assert_eq!(scene.textures[0].color_space, ColorSpace::Rec2020);
+        assert_eq!(scene.lights[0].color_space, ColorSpace::Aces2065_1);
+        assert_eq!(scene.materials[0].color_space, ColorSpace::Aces2065_1);
+
+        // Color space is a part of the graphics state.
+        assert_eq!(scene.materials[1].color_space, ColorSpace::Rec2020);
+
+        assert!(matches!(
+            Scene::load("ColorSpace \"xyz\"", None).map_err(Error::into_inner),
+            Err(Error::UnknownColorSpace)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_error_location() -> Result<()> {
+        let temp_dir = TempDir::new("pbrt-errors-")?;
+        let temp_path = temp_dir.path();
+
+        fs::write(
+            temp_path.join("geometry.pbrt"),
+            "Shape \"sphere\"\n\n  Translate 1 x 2\n",
+        )?;
+        fs::write(temp_path.join("scene.pbrt"), "Include \"geometry.pbrt\"")?;
+        fs::write(
+            temp_path.join("main.pbrt"),
+            "WorldBegin\nInclude \"scene.pbrt\"\n",
+        )?;
+
+        let Err(err) = Scene::from_file(temp_path.join("main.pbrt")) else {
+            panic!("Expected error");
+        };
+        assert!(matches!(err.inner(), Error::ParseFloat(_)));
+
+        let location = err.location().unwrap();
+        assert_eq!(location.file, Some(temp_path.join("geometry.pbrt")));
+        assert_eq!((location.line, location.column), (3, 15));
+        assert_eq!(
+            location.include_stack,
+            [temp_path.join("main.pbrt"), temp_path.join("scene.pbrt")]
+        );
+
+        // Semantic errors point to the statement.
+        let Err(err) = Scene::load("WorldBegin\n\n   AttributeEnd", None) else {
+            panic!("Expected error");
+        };
+        assert!(matches!(err.inner(), Error::TooManyEndAttributes));
+        assert_eq!(err.to_string(), "3:4: Too many AttributeEnd");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_instancing() {
+        let nested = r#"
+WorldBegin
+ObjectBegin "a"
+ObjectBegin "b"
+ObjectEnd
+ObjectEnd
+        "#;
+        assert!(matches!(
+            Scene::load(nested, None).map_err(Error::into_inner),
+            Err(Error::NestedObjects)
+        ));
+
+        let instance_in_object = r#"
+WorldBegin
+ObjectBegin "a"
+ObjectEnd
+ObjectBegin "b"
+ObjectInstance "a"
+ObjectEnd
+        "#;
+        assert!(matches!(
+            Scene::load(instance_in_object, None).map_err(Error::into_inner),
+            Err(Error::ElementNotAllowed)
+        ));
+
+        let unknown = "WorldBegin\nObjectInstance \"missing\"";
+        assert!(matches!(
+            Scene::load(unknown, None).map_err(Error::into_inner),
+            Err(Error::NotFound)
+        ));
+    }
+
+    #[test]
+    fn test_missing_world_begin() -> Result<()> {
+        let scene = Scene::load(
+            "LookAt 0 0 -5  0 0 0  0 1 0\nCamera \"perspective\"\n",
+            None,
+        )?;
+
+        assert!(scene.camera.is_some());
+        assert_eq!(scene.issues.len(), 1);
+        assert_eq!(scene.issues[0].severity, Severity::Error);
+        assert_eq!(scene.issues[0].entity, EntityRef::Scene);
+        assert_eq!(scene.issues[0].parameter, "WorldBegin");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_instancing_nested_attributes() -> Result<()> {
+        let data = r#"
+WorldBegin
+ObjectBegin "tree"
+  AttributeBegin
+    Translate 0 1 0
+    Shape "sphere"
+  AttributeEnd
+  Shape "cylinder"
+ObjectEnd
+ObjectBegin "rock"
+  AttributeBegin
+    Shape "disk"
+  AttributeEnd
+ObjectEnd
+ObjectInstance "rock"
+ObjectInstance "tree"
+        "#;
+
+        let scene = Scene::load(data, None)?;
+        assert_eq!(scene.instances.len(), 2);
+
+        let rock = scene.object_shapes(scene.instances[0].object_index);
+        assert_eq!(rock.len(), 1);
+        assert!(matches!(rock[0].params, Shape::Disk { .. }));
+
+        let tree = scene.object_shapes(scene.instances[1].object_index);
+        assert_eq!(tree.len(), 2);
+        assert!(matches!(tree[0].params, Shape::Sphere { .. }));
+        assert!(matches!(tree[1].params, Shape::Cylinder { .. }));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_object_shapes() -> Result<()> {
+        let data = r#"
+WorldBegin
+Shape "sphere"
+ObjectBegin "empty"
+ObjectEnd
+ObjectBegin "disks"
+Shape "disk"
+Shape "disk"
+ObjectEnd
+Shape "cylinder"
+        "#;
+
+        let scene = Scene::load(data, None)?;
+
+        assert_eq!(scene.object_names().collect::<Vec<_>>(), ["empty", "disks"]);
+
+        let (index, object) = scene.object_by_name("disks").unwrap();
+        assert_eq!(object.shape_range(), Some(1..3));
+
+        let shapes = scene.object_shapes(index);
+        assert_eq!(shapes.len(), 2);
+        assert!(shapes.iter().all(|s| s.object_index == Some(index)));
+        assert!(scene.object_shapes(0).is_empty());
+
+        assert_eq!(scene.shapes[0].object_index, None);
+        assert_eq!(scene.shapes[3].object_index, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_world_bounds() -> Result<()> {
+        let data = r#"
+WorldBegin
+Translate 10 0 0
+Shape "sphere" "float radius" 2
+ObjectBegin "disk"
+Shape "disk" "float height" 1
+ObjectEnd
+AttributeBegin
+Identity
+ActiveTransform EndTime
+Translate 0 5 0
+ActiveTransform All
+ObjectInstance "disk"
+AttributeEnd
+Shape "curve" "point3 P" [ 0 0 0 1 0 0 2 0 0 3 0 0 ] "float width" 0.5
+Shape "plymesh" "string filename" "missing.ply"
+        "#;
+
+        let scene = Scene::load(data, None)?;
+
+        assert_eq!(
+            scene.shapes[0].world_bounds(),
+            Some(Bounds3::new(
+                Vec3::new(8.0, -2.0, -2.0),
+                Vec3::new(12.0, 2.0, 2.0)
+            ))
+        );
+        assert_eq!(
+            scene.shapes[2].world_bounds(),
+            Some(Bounds3::new(
+                Vec3::new(9.75, -0.25, -0.25),
+                Vec3::new(13.25, 0.25, 0.25)
+            ))
+        );
+        assert_eq!(scene.shapes[3].world_bounds(), None);
+
+        // The instance moves from y = 0 to y = 5, the disk keeps the translation
+        // of the object definition.
+        assert_eq!(
+            scene.world_bounds(),
+            Some(Bounds3::new(
+                Vec3::new(8.0, -2.0, -2.0),
+                Vec3::new(13.25, 6.0, 2.0)
+            ))
+        );
+        assert_eq!(Scene::default().world_bounds(), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_progress() -> Result<()> {
+        let geometry = "Shape \"sphere\"\nShape \"disk\"\n";
+        let files = HashMap::from([(PathBuf::from("/scenes/geometry.pbrt"), geometry.to_string())]);
+
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let options = LoadOptions {
+            resolver: Some(Arc::new(files)),
+            progress: Some(ProgressCallback::new({
+                let reports = reports.clone();
+                move |progress| reports.lock().unwrap().push(progress)
+            })),
+            ..Default::default()
+        };
+
+        let data = "WorldBegin\nInclude \"geometry.pbrt\"\nLightSource \"point\"\n";
+        Scene::load_with_options(data, Some(Path::new("/scenes")), &options)?;
+
+        let reports = reports.lock().unwrap();
+        let total = data.len() + geometry.len();
+
+        // Start, entering and leaving the included file, done.
+        assert_eq!(reports.len(), 4);
+        assert!(reports.iter().all(|p| p.total_bytes == total));
+        assert_eq!(reports[0].bytes_parsed, 0);
+        assert_eq!(
+            reports[1].file,
+            Some(PathBuf::from("/scenes/geometry.pbrt"))
+        );
+        assert_eq!(reports[1].shapes, 0);
+        assert_eq!(reports[2].file, None);
+        assert_eq!(reports[2].shapes, 2);
+
+        let done = &reports[3];
+        assert_eq!(done.bytes_parsed, total);
+        assert_eq!(done.lights, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cancel() {
+        let files = HashMap::from([(PathBuf::from("/scenes/a.pbrt"), String::new())]);
+        let cancel = Arc::new(AtomicBool::new(false));
+
+        let options = LoadOptions {
+            resolver: Some(Arc::new(files)),
+            cancel: Some(cancel.clone()),
+            progress: Some(ProgressCallback::new(move |progress| {
+                if progress.bytes_parsed > 0 {
+                    cancel.store(true, Ordering::Relaxed);
+                }
+            })),
+            ..Default::default()
+        };
+
+        // Entering the included file is reported, so loading stops before the light.
+        let data = "WorldBegin\nInclude \"a.pbrt\"\nLightSource \"point\"\n";
+        let result = Scene::load_with_options(data, Some(Path::new("/scenes")), &options);
+        assert!(matches!(result, Err(Error::Cancelled)));
+    }
+
+    #[test]
+    fn test_flatten_instances() -> Result<()> {
+        let data = r#"
+WorldBegin
+ObjectBegin "pair"
+Translate 1 0 0
+Shape "sphere"
+Shape "disk"
+ObjectEnd
+Shape "cylinder"
+Translate 0 5 0
+ObjectInstance "pair"
+AttributeBegin
+AreaLightSource "diffuse"
+Translate 0 0 5
+ObjectInstance "pair"
+AttributeEnd
+        "#;
+
+        let mut scene = Scene::load(data, None)?;
+        assert!(matches!(
+            scene.flatten_instances(Some(3)),
+            Err(Error::TooManyInstancedShapes { count: 4, limit: 3 })
+        ));
+        assert_eq!(scene.instances.len(), 2);
+        assert_eq!(scene.shapes.len(), 3);
+
+        assert_eq!(scene.flatten_instances(None)?, 4);
+        assert!(scene.instances.is_empty());
+        assert!(scene.objects.is_empty());
+        assert_eq!(scene.shapes.len(), 5);
+        assert!(scene
+            .shapes
+            .iter()
+            .all(|shape| shape.object_index.is_none()));
+        assert!(matches!(scene.shapes[0].params, Shape::Cylinder { .. }));
+
+        let sphere = &scene.shapes[1];
+        assert!(matches!(sphere.params, Shape::Sphere { .. }));
+        assert_eq!(sphere.object_index, None);
+        assert_eq!(sphere.area_light_index, None);
+        assert_eq!(
+            sphere.transform,
+            Mat4::from_translation(Vec3::new(1.0, 5.0, 0.0))
+        );
+
+        let disk = &scene.shapes[4];
+        assert!(matches!(disk.params, Shape::Disk { .. }));
+        assert_eq!(disk.area_light_index, Some(0));
+        assert_eq!(
+            disk.transform_end,
+            Mat4::from_translation(Vec3::new(1.0, 5.0, 5.0))
+        );
+
+        // Bounds don't change, the object definition was not rendered.
+        assert_eq!(
+            scene.world_bounds(),
+            Some(Bounds3::new(
+                Vec3::new(-1.0, -1.0, -1.0),
+                Vec3::new(2.0, 6.0, 6.0)
+            ))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_camera_frame() -> Result<()> {
+        let data = r#"
+LookAt 3 0 0  0 0 0  0 0 1
+Camera "perspective" "float fov" 90
+WorldBegin
+        "#;
+
+        let scene = Scene::load(data, None)?;
+        let camera = scene.camera.as_ref().unwrap();
+
+        assert!((camera.position() - Vec3::new(3.0, 0.0, 0.0)).length() < 1e-5);
+        assert!((camera.forward() - Vec3::new(-1.0, 0.0, 0.0)).length() < 1e-5);
+        assert!((camera.up() - Vec3::new(0.0, 0.0, 1.0)).length() < 1e-5);
+
+        // The look at point is in the center of the image, points 1 unit up at the same
+        // distance are on the edge of the 90 degrees field of view.
+        let view_projection = camera.view_projection(1.0).unwrap();
+        let center = view_projection.transform_point3(Vec3::ZERO);
+        assert!(center.x.abs() < 1e-5 && center.y.abs() < 1e-5);
+        let top = view_projection.transform_point3(Vec3::new(0.0, 0.0, 3.0));
+        assert!((top.y - 1.0).abs() < 1e-5);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decompose() -> Result<()> {
+        let data = r#"
+LookAt 0 0 -5  0 0 0  0 1 0
+Camera "perspective"
+WorldBegin
+Translate 1 2 3
+Rotate 90 0 0 1
+Scale 2 2 -2
+Shape "sphere"
+        "#;
+
+        let scene = Scene::load(data, None)?;
+
+        let camera = scene.camera.as_ref().unwrap().decompose();
+        assert!((camera.translation - Vec3::new(0.0, 0.0, -5.0)).length() < 1e-5);
+        assert!((camera.scale - Vec3::ONE).length() < 1e-5);
+        assert!(!camera.shear);
+
+        let shape = scene.shapes[0].decompose();
+        assert_eq!(shape.translation, Vec3::new(1.0, 2.0, 3.0));
+        assert!(!shape.shear);
+        // The shape is mirrored, and composing it back gives the original transform.
+        assert!(shape.scale.x < 0.0);
+        let m = shape.to_mat4().to_cols_array();
+        for (a, b) in m.iter().zip(scene.shapes[0].transform.to_cols_array()) {
+            assert!((a - b).abs() < 1e-5);
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "f64")]
+    #[test]
+    fn test_double_precision() -> Result<()> {
+        // Not representable as f32.
+        let data = r#"
+            WorldBegin
+            Translate 16777217 0 0
+            Shape "trianglemesh" "point3 P" [ 16777217.5 0 0 0 1 0 1 0 0 ] "integer indices" [ 0 1 2 ]
+        "#;
+
+        let scene = Scene::load(data, None)?;
+
+        let shape = &scene.shapes[0];
+        assert_eq!(shape.transform.get(0, 3), 16777217.0);
+
+        let Shape::TriangleMesh { positions, .. } = &shape.params else {
+            panic!("Unexpected shape type");
+        };
+        assert_eq!(positions[0], 16777217.5);
+
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() -> Result<()> {
+        let data = r#"
+LookAt 0 0 5  0 0 0  0 1 0
+Camera "perspective" "float fov" 45
+Film "rgb" "integer xresolution" 640 "integer yresolution" 480
+PixelFilter "mitchell"
+WorldBegin
+LightSource "infinite" "spectrum L" "stdillum-D65"
+Texture "checks" "spectrum" "checkerboard" "float uscale" 4
+Material "diffuse" "texture reflectance" "checks"
+AttributeBegin
+  AreaLightSource "diffuse" "blackbody L" 6500
+  Translate 1 0 0
+  Shape "sphere" "float radius" 0.5
+AttributeEnd
+ObjectBegin "tri"
+  Shape "trianglemesh" "point3 P" [ 0 0 0  1 0 0  0 1 0 ] "integer indices" [ 0 1 2 ]
+ObjectEnd
+ObjectInstance "tri"
+        "#;
+
+        let scene = Scene::load(data, None)?;
+
+        let json = serde_json::to_string(&scene).unwrap();
+        let restored: Scene = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(restored.shapes.len(), 2);
+        assert_eq!(restored.shapes[0].transform, scene.shapes[0].transform);
+        assert!(matches!(
+            restored.shapes[0].params,
+            Shape::Sphere { radius, .. } if radius == 0.5
+        ));
+        assert_eq!(restored.filter, scene.filter);
+        assert!(matches!(restored.filter, Some(Filter::Mitchell { .. })));
+
+        // Serializing again gives the same document.
+        assert_eq!(serde_json::to_string(&restored).unwrap(), json);
+
+        Ok(())
+    }
 }
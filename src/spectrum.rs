@@ -0,0 +1,236 @@
+//! Conversion of spectra to colors.
+//!
+//! Spectra are integrated against the CIE 1931 2° color matching functions,
+//! approximated with the multi-lobe fit from "Simple Analytic Approximations to the CIE XYZ
+//! Color Matching Functions" (Wyman, Sloan and Shirley, 2013).
+//!
+//! ```
+//! # use pbrt4::param::Spectrum;
+//! let rgb = Spectrum::Blackbody(2700).srgb().unwrap();
+//! assert!(rgb[0] > rgb[1] && rgb[1] > rgb[2]);
+//! ```
+
+use crate::param::Spectrum;
+
+/// Shortest wavelength (in nanometers) taken into account.
+pub const LAMBDA_MIN: f32 = 360.0;
+/// Longest wavelength (in nanometers) taken into account.
+pub const LAMBDA_MAX: f32 = 830.0;
+
+/// CIE xy chromaticities of the built-in illuminants.
+const ILLUMINANTS: &[(&str, [f32; 2])] = &[
+    ("stdillum-A", [0.44757, 0.40745]),
+    ("stdillum-D50", [0.34567, 0.35850]),
+    ("stdillum-D65", [0.31271, 0.32902]),
+    ("stdillum-F1", [0.31310, 0.33727]),
+    ("stdillum-F2", [0.37208, 0.37529]),
+    ("stdillum-F3", [0.40910, 0.39430]),
+    ("stdillum-F4", [0.44018, 0.40329]),
+    ("stdillum-F5", [0.31379, 0.34531]),
+    ("stdillum-F6", [0.37790, 0.38835]),
+    ("stdillum-F7", [0.31292, 0.32933]),
+    ("stdillum-F8", [0.34588, 0.35875]),
+    ("stdillum-F9", [0.37417, 0.37281]),
+    ("stdillum-F10", [0.34609, 0.35986]),
+    ("stdillum-F11", [0.38052, 0.37713]),
+    ("stdillum-F12", [0.43695, 0.40441]),
+    ("illum-acesD60", [0.32168, 0.33767]),
+];
+
+/// CIE 1931 color matching functions `[x̄, ȳ, z̄]` at wavelength `lambda` (in nanometers).
+pub fn cie_xyz(lambda: f32) -> [f32; 3] {
+    // Piecewise Gaussian with different widths on each side of the peak.
+    let g = |mu: f32, sigma_low: f32, sigma_high: f32| {
+        let sigma = if lambda < mu { sigma_low } else { sigma_high };
+        let t = (lambda - mu) / sigma;
+        (-0.5 * t * t).exp()
+    };
+
+    let x =
+        1.056 * g(599.8, 37.9, 31.0) + 0.362 * g(442.0, 16.0, 26.7) - 0.065 * g(501.1, 20.4, 26.2);
+    let y = 0.821 * g(568.8, 46.9, 40.5) + 0.286 * g(530.9, 16.3, 31.1);
+    let z = 1.217 * g(437.0, 11.8, 36.0) + 0.681 * g(459.0, 26.0, 13.8);
+
+    [x, y, z]
+}
+
+/// Spectral radiance of a blackbody at wavelength `lambda` (in nanometers), Planck's law.
+///
+/// `temperature` is in Kelvin, the result is in W/(sr·m²·m).
+pub fn blackbody(lambda: f32, temperature: f32) -> f32 {
+    const C: f64 = 299792458.0;
+    const H: f64 = 6.62606957e-34;
+    const KB: f64 = 1.3806488e-23;
+
+    if temperature <= 0.0 {
+        return 0.0;
+    }
+
+    let l = lambda as f64 * 1e-9;
+    let t = temperature as f64;
+
+    let radiance = (2.0 * H * C * C) / (l.powi(5) * (((H * C) / (l * KB * t)).exp() - 1.0));
+    radiance as f32
+}
+
+/// Convert CIE XYZ to linear sRGB (D65 white point).
+pub fn xyz_to_srgb([x, y, z]: [f32; 3]) -> [f32; 3] {
+    [
+        3.2404542 * x - 1.5371385 * y - 0.4985314 * z,
+        -0.969266 * x + 1.8760108 * y + 0.041556 * z,
+        0.0556434 * x - 0.2040259 * y + 1.0572252 * z,
+    ]
+}
+
+/// Convert linear sRGB to CIE XYZ.
+pub fn srgb_to_xyz([r, g, b]: [f32; 3]) -> [f32; 3] {
+    [
+        0.4124564 * r + 0.3575761 * g + 0.1804375 * b,
+        0.2126729 * r + 0.7151522 * g + 0.072175 * b,
+        0.0193339 * r + 0.119192 * g + 0.9503041 * b,
+    ]
+}
+
+/// Integrate `f` against the matching functions, normalized so a constant spectrum of 1 has Y = 1.
+fn integrate(f: impl Fn(f32) -> f32) -> [f32; 3] {
+    let mut xyz = [0.0; 3];
+    let mut y_integral = 0.0;
+
+    // 1nm steps, like pbrt's tabulated matching functions.
+    let mut lambda = LAMBDA_MIN;
+    while lambda <= LAMBDA_MAX {
+        let value = f(lambda);
+        let cmf = cie_xyz(lambda);
+
+        for (xyz, cmf) in xyz.iter_mut().zip(cmf) {
+            *xyz += value * cmf;
+        }
+        y_integral += cmf[1];
+
+        lambda += 1.0;
+    }
+
+    xyz.map(|v| v / y_integral)
+}
+
+/// Evaluate a piecewise-linear spectrum, zero outside of the sampled range.
+fn sampled(samples: &[(f32, f32)], lambda: f32) -> f32 {
+    let index = samples.partition_point(|&(l, _)| l <= lambda);
+
+    match (samples.get(index.wrapping_sub(1)), samples.get(index)) {
+        (Some(&(l0, v0)), Some(&(l1, v1))) => v0 + (v1 - v0) * (lambda - l0) / (l1 - l0),
+        // Exactly at the last sample.
+        (Some(&(l0, v0)), None) if l0 == lambda => v0,
+        _ => 0.0,
+    }
+}
+
+/// `X`, `Y`, `Z` from xy chromaticity, with `Y` = 1.
+fn chromaticity_to_xyz([x, y]: [f32; 2]) -> [f32; 3] {
+    [x / y, 1.0, (1.0 - x - y) / y]
+}
+
+impl Spectrum {
+    /// CIE XYZ color of the spectrum.
+    ///
+    /// - [Spectrum::Rgb] is interpreted as linear sRGB;
+    /// - [Spectrum::Blackbody] and named illuminants describe the color of an emitter,
+    ///   they're normalized to `Y` = 1 (pbrt scales lights by their luminance as well);
+    /// - [Spectrum::Sampled] is integrated as is, so a reflectance of 1 has `Y` = 1.
+    ///
+    /// Returns `None` for spectra which are not available: files that were not loaded
+    /// and measured glass and metal spectra, which are not included in the crate.
+    pub fn xyz(&self) -> Option<[f32; 3]> {
+        let xyz = match self {
+            Spectrum::Rgb(rgb) => srgb_to_xyz(*rgb),
+            Spectrum::Blackbody(temperature) => {
+                let xyz = integrate(|lambda| blackbody(lambda, *temperature as f32));
+                if xyz[1] <= 0.0 {
+                    return None;
+                }
+
+                xyz.map(|v| v / xyz[1])
+            }
+            Spectrum::Sampled(samples) => integrate(|lambda| sampled(samples, lambda)),
+            Spectrum::Named(name) => {
+                let (_, xy) = ILLUMINANTS.iter().find(|(known, _)| known == name)?;
+                chromaticity_to_xyz(*xy)
+            }
+            Spectrum::File(_) => return None,
+        };
+
+        Some(xyz)
+    }
+
+    /// Linear sRGB color of the spectrum, see [Spectrum::xyz].
+    pub fn srgb(&self) -> Option<[f32; 3]> {
+        match self {
+            Spectrum::Rgb(rgb) => Some(*rgb),
+            spectrum => spectrum.xyz().map(xyz_to_srgb),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(a: [f32; 3], b: [f32; 3], epsilon: f32) {
+        assert!(
+            a.iter().zip(&b).all(|(a, b)| (a - b).abs() < epsilon),
+            "{a:?} != {b:?}"
+        );
+    }
+
+    #[test]
+    fn illuminants() {
+        // D65 is the sRGB white point.
+        let white = Spectrum::Named("stdillum-D65").srgb().unwrap();
+        assert_near(white, [1.0, 1.0, 1.0], 1e-3);
+
+        let a = Spectrum::Named("stdillum-A").srgb().unwrap();
+        assert!(a[0] > a[1] && a[1] > a[2]);
+
+        assert_eq!(Spectrum::Named("metal-Au-eta").xyz(), None);
+        assert_eq!(Spectrum::File("light.spd".to_string()).xyz(), None);
+    }
+
+    #[test]
+    fn blackbody_color() {
+        // Illuminant A is a 2856K blackbody.
+        let xyz = Spectrum::Blackbody(2856).xyz().unwrap();
+        let a = Spectrum::Named("stdillum-A").xyz().unwrap();
+        assert_near(xyz, a, 1e-2);
+
+        let hot = Spectrum::Blackbody(10000).srgb().unwrap();
+        assert!(hot[2] > hot[0]);
+
+        assert_eq!(Spectrum::Blackbody(0).xyz(), None);
+    }
+
+    #[test]
+    fn sampled_color() {
+        // Constant spectrum.
+        let xyz = Spectrum::Sampled(vec![(LAMBDA_MIN, 1.0), (LAMBDA_MAX, 1.0)])
+            .xyz()
+            .unwrap();
+        assert!((xyz[1] - 1.0).abs() < 1e-4);
+
+        // Long wavelengths only.
+        let red = Spectrum::Sampled(vec![(600.0, 0.0), (610.0, 1.0), (700.0, 1.0)])
+            .srgb()
+            .unwrap();
+        assert!(red[0] > red[1] && red[0] > red[2]);
+
+        assert_eq!(sampled(&[(400.0, 1.0), (500.0, 2.0)], 450.0), 1.5);
+        assert_eq!(sampled(&[(400.0, 1.0), (500.0, 2.0)], 500.0), 2.0);
+        assert_eq!(sampled(&[(400.0, 1.0), (500.0, 2.0)], 300.0), 0.0);
+    }
+
+    #[test]
+    fn srgb_round_trip() {
+        let rgb = [0.2, 0.5, 0.9];
+        assert_near(xyz_to_srgb(srgb_to_xyz(rgb)), rgb, 1e-5);
+        assert_eq!(Spectrum::Rgb(rgb).srgb(), Some(rgb));
+    }
+}
@@ -0,0 +1,424 @@
+//! Parsing from readers.
+
+use std::{
+    io::{self, Read},
+    mem,
+    str::FromStr,
+};
+
+use crate::{decode_bytes, Directive, Element, Error, Location, ParseOptions, Parser, Result};
+
+/// Number of bytes read at once.
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Parses directives from a [Read] (pipes, network streams), without loading the whole input.
+///
+/// Input is buffered one statement at a time: the buffer is refilled until the next directive
+/// is found, so memory usage depends on the largest statement rather than the file size.
+/// Elements borrow the buffer, so only one element can be alive at a time.
+///
+/// ```
+/// # use pbrt4::{Element, StreamParser};
+/// let input = "WorldBegin\nShape \"sphere\" \"float radius\" 2".as_bytes();
+/// let mut parser = StreamParser::new(input);
+///
+/// assert_eq!(parser.parse_next()?, Element::WorldBegin);
+/// assert!(matches!(parser.parse_next()?, Element::Shape { .. }));
+/// # Ok::<(), pbrt4::Error>(())
+/// ```
+pub struct StreamParser<R> {
+    reader: R,
+    options: ParseOptions,
+    buf: Vec<u8>,
+    /// Offset in `buf` of the first byte which was not parsed yet.
+    start: usize,
+    /// Offset in `buf` scanning for the end of the statement continues at.
+    scan: usize,
+    /// Whether the statement's directive was scanned.
+    has_directive: bool,
+    /// Whether the reader is exhausted.
+    eof: bool,
+    /// Bytes dropped from the buffer so far.
+    dropped: usize,
+    /// Location of `start`, line and column starting from 1.
+    line: usize,
+    column: usize,
+    /// Statement decoded with replaced invalid UTF-8.
+    decoded: String,
+    /// Problems the parser recovered from.
+    warnings: Vec<Error>,
+    /// Error in the tail of the last statement, returned by the next call.
+    pending: Option<Error>,
+}
+
+impl<R: Read> StreamParser<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_options(reader, ParseOptions::default())
+    }
+
+    /// Create a parser which recovers from malformed input according to `options`,
+    /// see [Parser::with_options].
+    pub fn with_options(reader: R, options: ParseOptions) -> Self {
+        Self {
+            reader,
+            options,
+            buf: Vec::new(),
+            start: 0,
+            scan: 0,
+            has_directive: false,
+            eof: false,
+            dropped: 0,
+            line: 1,
+            column: 1,
+            decoded: String::new(),
+            warnings: Vec::new(),
+            pending: None,
+        }
+    }
+
+    /// Take warnings about problems the parser recovered from so far, see [Parser::take_warnings].
+    pub fn take_warnings(&mut self) -> Vec<Error> {
+        mem::take(&mut self.warnings)
+    }
+
+    /// Number of bytes parsed so far.
+    pub fn offset(&self) -> usize {
+        self.dropped + self.start
+    }
+
+    /// Parse next element, see [Parser::parse_next].
+    ///
+    /// Returns [Error::EndOfFile] when the reader is exhausted. Input which is not valid UTF-8
+    /// is handled as in [decode_bytes].
+    pub fn parse_next(&mut self) -> Result<Element<'_>> {
+        if let Some(err) = self.pending.take() {
+            return Err(err);
+        }
+
+        // Skipped statements (e.g. unknown directives) are parsed here, the element of the first
+        // statement which produces one is parsed again from `buf` or `decoded` after the loop.
+        let (range, is_decoded, line, column) = loop {
+            let end = self.next_statement()?;
+            if end == self.start {
+                return Err(Error::EndOfFile);
+            }
+
+            let (line, column) = (self.line, self.column);
+            let range = self.start..end;
+            let bytes = &self.buf[range.clone()];
+
+            let (src, is_decoded) = match std::str::from_utf8(bytes) {
+                Ok(src) => (src, false),
+                Err(_) => {
+                    let (decoded, warnings) = decode_bytes(bytes, self.options.invalid_utf8)
+                        .map_err(|err| shift(err, line, column))?;
+
+                    self.warnings
+                        .extend(warnings.into_iter().map(|err| shift(err, line, column)));
+                    self.decoded = decoded.into_owned();
+                    (self.decoded.as_str(), true)
+                }
+            };
+
+            // Advance the location past the statement.
+            match src.rfind('\n') {
+                Some(pos) => {
+                    self.line += src.matches('\n').count();
+                    self.column = src[pos + 1..].chars().count() + 1;
+                }
+                None => self.column += src.chars().count(),
+            }
+
+            self.start = end;
+            self.has_directive = false;
+
+            let mut parser = Parser::with_options(src, self.options);
+            let result = parser.parse_next();
+
+            // The statement has no other directives, but may end with junk (e.g. an unknown
+            // directive), which `Parser` reports on the next call.
+            if result.is_ok() {
+                match parser.parse_next() {
+                    Ok(_) | Err(Error::EndOfFile) => {}
+                    Err(err) => self.pending = Some(shift(err, line, column)),
+                }
+            }
+
+            self.warnings.extend(
+                parser
+                    .take_warnings()
+                    .into_iter()
+                    .map(|err| shift(err, line, column)),
+            );
+
+            match result {
+                Ok(_) => break (range, is_decoded, line, column),
+                // Statement was skipped (e.g. an unknown directive).
+                Err(Error::EndOfFile) => continue,
+                Err(err) => return Err(shift(err, line, column)),
+            }
+        };
+
+        let src = if is_decoded {
+            self.decoded.as_str()
+        } else {
+            std::str::from_utf8(&self.buf[range]).expect("Statement was checked to be UTF-8")
+        };
+
+        // Warnings of this statement were collected above.
+        Parser::with_options(src, self.options)
+            .parse_next()
+            .map_err(|err| shift(err, line, column))
+    }
+
+    /// Find the end of the next statement (the beginning of the following directive or
+    /// the end of input), reading more data as needed.
+    fn next_statement(&mut self) -> Result<usize> {
+        loop {
+            if let Some(end) = self.scan_statement() {
+                return Ok(end);
+            }
+
+            if self.eof {
+                return Ok(self.buf.len());
+            }
+
+            self.fill()?;
+        }
+    }
+
+    /// Scan complete tokens, returns the end of the statement if it's found.
+    fn scan_statement(&mut self) -> Option<usize> {
+        let buf = &self.buf;
+        let eof = self.eof;
+        let mut pos = self.scan;
+
+        // Tokens touching the end of the buffer may be incomplete.
+        let complete = |end: Option<usize>| match end {
+            Some(end) => Some(end),
+            None if eof => Some(buf.len()),
+            None => None,
+        };
+
+        while pos < buf.len() {
+            let start = pos;
+
+            let end = match buf[pos] {
+                b' ' | b'\n' | b'\t' | b'\r' => {
+                    pos += 1;
+                    continue;
+                }
+                b'[' | b']' => start + 1,
+                b'#' => {
+                    let end = buf[start..]
+                        .iter()
+                        .position(|&byte| byte == b'\n' || byte == b'\r')
+                        .map(|len| start + len);
+
+                    match complete(end) {
+                        Some(end) => {
+                            pos = end;
+                            continue;
+                        }
+                        None => break,
+                    }
+                }
+                b'"' => {
+                    let mut end = None;
+                    let mut i = start + 1;
+                    while i < buf.len() {
+                        match buf[i] {
+                            b'\\' => i += 2,
+                            b'"' => {
+                                end = Some(i + 1);
+                                break;
+                            }
+                            _ => i += 1,
+                        }
+                    }
+
+                    match complete(end) {
+                        Some(end) => end,
+                        None => break,
+                    }
+                }
+                _ => {
+                    let end = buf[start..]
+                        .iter()
+                        .position(|&byte| {
+                            matches!(byte, b' ' | b'\n' | b'\t' | b'\r' | b'"' | b'[' | b']')
+                        })
+                        .map(|len| start + len);
+
+                    let Some(end) = complete(end) else {
+                        break;
+                    };
+
+                    // Directive names are ASCII, other tokens don't matter.
+                    let is_directive = std::str::from_utf8(&buf[start..end])
+                        .map_or(false, |token| Directive::from_str(token).is_ok());
+
+                    if is_directive {
+                        if self.has_directive {
+                            self.scan = start;
+                            return Some(start);
+                        }
+
+                        self.has_directive = true;
+                    }
+
+                    end
+                }
+            };
+
+            pos = end.min(buf.len());
+        }
+
+        self.scan = pos;
+        None
+    }
+
+    /// Drop parsed data and read the next chunk.
+    fn fill(&mut self) -> Result<()> {
+        self.buf.drain(..self.start);
+        self.scan -= self.start;
+        self.dropped += self.start;
+        self.start = 0;
+
+        let len = self.buf.len();
+        self.buf.resize(len + CHUNK_SIZE, 0);
+
+        let read = loop {
+            match self.reader.read(&mut self.buf[len..]) {
+                Ok(read) => break read,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => {
+                    self.buf.truncate(len);
+                    return Err(err.into());
+                }
+            }
+        };
+
+        self.buf.truncate(len + read);
+        self.eof = read == 0;
+
+        Ok(())
+    }
+}
+
+/// Make a location within a statement starting at `line` and `column` absolute.
+fn shift(err: Error, line: usize, column: usize) -> Error {
+    match err {
+        Error::Located {
+            mut location,
+            source,
+        } => {
+            if location.line == 1 {
+                location.column += column - 1;
+            }
+            location.line += line - 1;
+
+            Error::Located { location, source }
+        }
+        err => err,
+    }
+}
+
+impl<R> std::fmt::Debug for StreamParser<R> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let location = Location {
+            line: self.line,
+            column: self.column,
+            ..Default::default()
+        };
+
+        write!(f, "StreamParser({})", location)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Strictness;
+
+    /// Returns data in small pieces, to split tokens and characters.
+    struct Trickle<'a>(&'a [u8], usize);
+
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let len = self.1.min(self.0.len()).min(buf.len());
+            buf[..len].copy_from_slice(&self.0[..len]);
+            self.0 = &self.0[len..];
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn stream() -> Result<()> {
+        let src = r#"
+# Comment with "quotes" and [brackets]
+LookAt 0 0 -5  0 0 0  0 1 0
+Camera "perspective" "float fov" [ 45 ]
+WorldBegin
+Texture "ch\"ecks" "spectrum" "checkerboard" "string tex1" "Ünïcödé"
+Shape "trianglemesh"
+    "point3 P" [ 0 0 0  1 0 0  0 1 0 ] # ]
+    "integer indices" [ 0 1 2 ]
+AttributeEnd"#;
+
+        for size in [1, 2, 3, 7, CHUNK_SIZE] {
+            let mut parser = Parser::new(src);
+            let mut stream = StreamParser::new(Trickle(src.as_bytes(), size));
+
+            loop {
+                match (parser.parse_next(), stream.parse_next()) {
+                    (Ok(expected), Ok(element)) => assert_eq!(element, expected),
+                    (Err(Error::EndOfFile), Err(Error::EndOfFile)) => break,
+                    result => panic!("Unexpected result: {:?}", result),
+                }
+            }
+
+            assert_eq!(stream.offset(), src.len());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn errors() {
+        let src = "WorldBegin\nShape \"sphere\"\n  Foo 1\nTranslate 1 x 0\n";
+
+        let mut stream = StreamParser::new(Trickle(src.as_bytes(), 3));
+        stream.parse_next().unwrap();
+        stream.parse_next().unwrap();
+
+        let err = stream.parse_next().unwrap_err();
+        assert!(matches!(err.inner(), Error::UnknownDirective));
+        assert_eq!(err.location().unwrap().line, 3);
+        assert_eq!(err.location().unwrap().column, 3);
+
+        let options = ParseOptions {
+            invalid_utf8: Strictness::Warn,
+            ..ParseOptions::lenient()
+        };
+        let src = b"WorldBegin\nShape \"sph\xffere\"\n  Foo 1\nTranslate 1 x 0\n";
+        let mut stream = StreamParser::with_options(Trickle(src, 5), options);
+
+        assert_eq!(stream.parse_next().unwrap(), Element::WorldBegin);
+        assert!(matches!(
+            stream.parse_next().unwrap(),
+            Element::Shape { name, .. } if name == "sph\u{fffd}ere"
+        ));
+        assert!(matches!(stream.parse_next(), Err(Error::EndOfFile)));
+
+        let warnings = stream
+            .take_warnings()
+            .iter()
+            .map(|warning| {
+                let location = warning.location().unwrap();
+                (location.line, location.column)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(warnings, [(2, 11), (3, 3), (4, 13)]);
+    }
+}
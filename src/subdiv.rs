@@ -0,0 +1,276 @@
+//! Loop subdivision of `"loopsubdiv"` shapes.
+//!
+//! Follows pbrt's implementation: each level splits every triangle in four, then the
+//! vertices are moved to their limit positions. Boundary edges use the crease rules,
+//! so open meshes keep their boundary curve.
+
+use std::collections::HashMap;
+
+use crate::math::Float;
+
+/// Triangle mesh produced by [loop_subdivide].
+pub(crate) struct SubdividedMesh {
+    pub indices: Vec<i32>,
+    pub positions: Vec<Float>,
+    pub normals: Vec<f32>,
+}
+
+/// Subdivide a triangle mesh `levels` times.
+///
+/// Returns `None` if the indices are out of range.
+pub(crate) fn loop_subdivide(
+    levels: u32,
+    indices: &[i32],
+    positions: &[Float],
+) -> Option<SubdividedMesh> {
+    #[allow(clippy::unnecessary_cast)]
+    let mut points = positions
+        .chunks_exact(3)
+        .map(|p| [p[0] as f64, p[1] as f64, p[2] as f64])
+        .collect::<Vec<_>>();
+
+    let mut triangles = indices
+        .chunks_exact(3)
+        .map(|t| {
+            let index = |i: i32| usize::try_from(i).ok().filter(|&i| i < points.len());
+            Some([index(t[0])?, index(t[1])?, index(t[2])?])
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    for _ in 0..levels {
+        (points, triangles) = subdivide(&points, &triangles);
+    }
+
+    // Push vertices to the limit surface.
+    let topology = Topology::new(points.len(), &triangles);
+    let points = (0..points.len())
+        .map(|v| match topology.boundary_neighbors(v) {
+            Some([a, b]) => weighted(&points, v, 0.6, &[a, b], 0.2),
+            None if topology.is_boundary(v) => points[v],
+            None => {
+                let ring = topology.ring(v);
+                let n = ring.len() as f64;
+                let gamma = 1.0 / (n + 3.0 / (8.0 * beta(ring.len())));
+                weighted(&points, v, 1.0 - n * gamma, &ring, gamma)
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // Area weighted normals, oriented like the triangles.
+    let mut normals = vec![[0.0f64; 3]; points.len()];
+    for &[a, b, c] in &triangles {
+        let n = cross(sub(points[b], points[a]), sub(points[c], points[a]));
+        for v in [a, b, c] {
+            normals[v] = [
+                normals[v][0] + n[0],
+                normals[v][1] + n[1],
+                normals[v][2] + n[2],
+            ];
+        }
+    }
+
+    Some(SubdividedMesh {
+        indices: triangles.iter().flatten().map(|&v| v as i32).collect(),
+        positions: points.iter().flatten().map(|&x| x as Float).collect(),
+        normals: normals
+            .iter()
+            .flat_map(|n| {
+                let length = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+                let length = if length > 0.0 { length } else { 1.0 };
+                n.map(|x| (x / length) as f32)
+            })
+            .collect(),
+    })
+}
+
+/// Single subdivision step.
+fn subdivide(points: &[[f64; 3]], triangles: &[[usize; 3]]) -> (Vec<[f64; 3]>, Vec<[usize; 3]>) {
+    let topology = Topology::new(points.len(), triangles);
+
+    // Even vertices, at the position of the original ones.
+    let mut new_points = (0..points.len())
+        .map(|v| match topology.boundary_neighbors(v) {
+            Some([a, b]) => weighted(points, v, 0.75, &[a, b], 0.125),
+            // Non-manifold vertices are kept as is.
+            None if topology.is_boundary(v) => points[v],
+            None => {
+                let ring = topology.ring(v);
+                let beta = beta(ring.len());
+                weighted(points, v, 1.0 - ring.len() as f64 * beta, &ring, beta)
+            }
+        })
+        .collect::<Vec<_>>();
+
+    // Odd vertices, one per edge, in the order of triangles to keep the output stable.
+    let mut edge_vertices = HashMap::with_capacity(topology.edges.len());
+    for &[a, b, c] in triangles {
+        for (a, b) in [(a, b), (b, c), (c, a)] {
+            let key = (a.min(b), a.max(b));
+            if edge_vertices.contains_key(&key) {
+                continue;
+            }
+
+            let p = match topology.edges[&key][..] {
+                [c, d] => {
+                    let (a, b, c, d) = (points[a], points[b], points[c], points[d]);
+                    std::array::from_fn(|i| 0.375 * (a[i] + b[i]) + 0.125 * (c[i] + d[i]))
+                }
+                _ => std::array::from_fn(|i| 0.5 * (points[a][i] + points[b][i])),
+            };
+
+            edge_vertices.insert(key, new_points.len());
+            new_points.push(p);
+        }
+    }
+
+    let edge = |a: usize, b: usize| edge_vertices[&(a.min(b), a.max(b))];
+
+    let new_triangles = triangles
+        .iter()
+        .flat_map(|&[a, b, c]| {
+            let (ab, bc, ca) = (edge(a, b), edge(b, c), edge(c, a));
+            [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+        })
+        .collect();
+
+    (new_points, new_triangles)
+}
+
+/// Edge and vertex adjacency of a triangle mesh.
+struct Topology {
+    /// Vertices opposite to each edge `(min, max)`, one for boundary edges.
+    edges: HashMap<(usize, usize), Vec<usize>>,
+    /// Vertices sharing an edge with each vertex.
+    neighbors: Vec<Vec<usize>>,
+}
+
+impl Topology {
+    fn new(vertex_count: usize, triangles: &[[usize; 3]]) -> Self {
+        let mut edges = HashMap::<_, Vec<usize>>::new();
+        let mut neighbors = vec![Vec::new(); vertex_count];
+
+        for &[a, b, c] in triangles {
+            for (a, b, opposite) in [(a, b, c), (b, c, a), (c, a, b)] {
+                let opposites = edges.entry((a.min(b), a.max(b))).or_default();
+                if opposites.is_empty() {
+                    neighbors[a].push(b);
+                    neighbors[b].push(a);
+                }
+                opposites.push(opposite);
+            }
+        }
+
+        Self { edges, neighbors }
+    }
+
+    fn ring(&self, v: usize) -> Vec<usize> {
+        self.neighbors[v].clone()
+    }
+
+    fn is_boundary(&self, v: usize) -> bool {
+        self.neighbors[v]
+            .iter()
+            .any(|&n| self.edges[&(v.min(n), v.max(n))].len() != 2)
+    }
+
+    /// The two neighbors along boundary edges, if `v` is a regular boundary vertex.
+    fn boundary_neighbors(&self, v: usize) -> Option<[usize; 2]> {
+        let mut boundary = self.neighbors[v]
+            .iter()
+            .copied()
+            .filter(|&n| self.edges[&(v.min(n), v.max(n))].len() == 1);
+
+        match (boundary.next(), boundary.next(), boundary.next()) {
+            (Some(a), Some(b), None) => Some([a, b]),
+            _ => None,
+        }
+    }
+}
+
+/// Weight of the neighbors of an interior vertex with `valence` neighbors.
+fn beta(valence: usize) -> f64 {
+    if valence == 3 {
+        3.0 / 16.0
+    } else {
+        3.0 / (8.0 * valence as f64)
+    }
+}
+
+/// `points[v] * weight + sum(points[ring]) * ring_weight`.
+fn weighted(
+    points: &[[f64; 3]],
+    v: usize,
+    weight: f64,
+    ring: &[usize],
+    ring_weight: f64,
+) -> [f64; 3] {
+    std::array::from_fn(|i| {
+        points[v][i] * weight + ring.iter().map(|&n| points[n][i]).sum::<f64>() * ring_weight
+    })
+}
+
+fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+fn cross(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [
+        a[1] * b[2] - a[2] * b[1],
+        a[2] * b[0] - a[0] * b[2],
+        a[0] * b[1] - a[1] * b[0],
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Regular tetrahedron, all faces oriented outwards.
+    fn tetrahedron() -> (Vec<i32>, Vec<Float>) {
+        let indices = vec![0, 2, 1, 0, 1, 3, 0, 3, 2, 1, 2, 3];
+        let positions = vec![
+            1.0, 1.0, 1.0, -1.0, -1.0, 1.0, -1.0, 1.0, -1.0, 1.0, -1.0, -1.0,
+        ];
+        (indices, positions)
+    }
+
+    #[test]
+    fn closed_mesh() {
+        let (indices, positions) = tetrahedron();
+
+        let mesh = loop_subdivide(2, &indices, &positions).unwrap();
+
+        // Each level splits triangles in four, V - E + F = 2 holds.
+        assert_eq!(mesh.indices.len(), 4 * 16 * 3);
+        assert_eq!(mesh.positions.len(), 34 * 3);
+        assert_eq!(mesh.normals.len(), mesh.positions.len());
+
+        // The surface shrinks towards the center, and normals point outwards.
+        for (p, n) in mesh.positions.chunks(3).zip(mesh.normals.chunks(3)) {
+            let length = (p[0] * p[0] + p[1] * p[1] + p[2] * p[2]).sqrt();
+            assert!(length > 0.25 && length < 0.5);
+
+            #[allow(clippy::unnecessary_cast)]
+            let dot = p[0] as f32 * n[0] + p[1] as f32 * n[1] + p[2] as f32 * n[2];
+            assert!(dot > 0.0);
+        }
+    }
+
+    #[test]
+    fn open_mesh() {
+        // Planar quad, the boundary stays in the plane and the corners stay on the boundary.
+        let indices = [0, 1, 2, 0, 2, 3];
+        let positions = [0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 1.0, 0.0, 0.0, 1.0, 0.0];
+
+        let mesh = loop_subdivide(1, &indices, &positions).unwrap();
+        assert_eq!(mesh.indices.len(), 8 * 3);
+        assert_eq!(mesh.positions.len(), 9 * 3);
+
+        for (p, n) in mesh.positions.chunks(3).zip(mesh.normals.chunks(3)) {
+            assert_eq!(p[2], 0.0);
+            assert_eq!(n, [0.0, 0.0, 1.0]);
+        }
+
+        assert!(loop_subdivide(0, &[0, 1, 4], &positions).is_none());
+    }
+}
@@ -1,3 +1,4 @@
+use std::borrow::Cow;
 use std::fmt;
 use std::str::FromStr;
 
@@ -54,7 +55,14 @@ impl<'a> Token<'a> {
     }
 
     /// Return a string without quotes or `None` if the string is not a quoted string.
-    pub fn unquote(&self) -> Option<&'a str> {
+    ///
+    /// Escape sequences (`\"`, `\\` and `\n`) are replaced, the string is only copied if it has any.
+    pub fn unquote(&self) -> Option<Cow<'a, str>> {
+        self.unquote_raw().map(unescape)
+    }
+
+    /// Return a string without quotes, keeping escape sequences as is.
+    pub fn unquote_raw(&self) -> Option<&'a str> {
         if self.is_quote() {
             let len = self.str.len();
             Some(&self.str[1..len - 1])
@@ -95,8 +103,41 @@ impl<'a> Token<'a> {
     }
 }
 
-/// Type of pbrt directive if [Token] is directive.
-#[derive(Debug, PartialEq)]
+/// Replace escape sequences in the contents of a quoted string.
+///
+/// Unknown escape sequences are kept as is.
+pub(crate) fn unescape(str: &str) -> Cow<'_, str> {
+    if !str.contains('\\') {
+        return Cow::Borrowed(str);
+    }
+
+    let mut out = String::with_capacity(str.len());
+    let mut chars = str.chars();
+
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            out.push(ch);
+            continue;
+        }
+
+        match chars.next() {
+            Some('"') => out.push('"'),
+            Some('\\') => out.push('\\'),
+            Some('n') => out.push('\n'),
+            Some(other) => {
+                out.push('\\');
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+
+    Cow::Owned(out)
+}
+
+/// Type of pbrt directive.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Directive {
     Identity,
     Translate,
@@ -205,11 +246,7 @@ impl FromStr for Directive {
 
 #[cfg(test)]
 mod tests {
-    use std::str::FromStr;
-
-    use crate::token::Directive;
-
-    use super::Token;
+    use super::*;
 
     #[test]
     fn is_quote() {
@@ -224,8 +261,8 @@ mod tests {
 
     #[test]
     fn unquote_str() {
-        assert_eq!(Token::new("\"foo\"").unquote(), Some("foo"));
-        assert_eq!(Token::new("\"\"").unquote(), Some(""));
+        assert_eq!(Token::new("\"foo\"").unquote(), Some("foo".into()));
+        assert_eq!(Token::new("\"\"").unquote(), Some("".into()));
 
         assert_eq!(Token::new("").unquote(), None);
         assert_eq!(Token::new("\"").unquote(), None);
@@ -233,6 +270,19 @@ mod tests {
         assert_eq!(Token::new("abc\"").unquote(), None);
     }
 
+    #[test]
+    fn unquote_escaped() {
+        let token = Token::new(r#""say \"hi\"\\n\n\t""#);
+        assert_eq!(token.unquote().unwrap(), "say \"hi\"\\n\n\\t");
+        assert_eq!(token.unquote_raw().unwrap(), r#"say \"hi\"\\n\n\t"#);
+
+        // Strings without escapes are borrowed.
+        assert!(matches!(
+            Token::new("\"foo\"").unquote(),
+            Some(Cow::Borrowed("foo"))
+        ));
+    }
+
     #[test]
     fn parse() {
         assert_eq!(Token::new("32").parse(), Ok(32_u32));
@@ -1,116 +1,134 @@
+use memchr::memchr2;
+
 use crate::token::Token;
 
+/// Bytes ending a bare token.
+const DELIMITERS: [bool; 256] = {
+    let mut table = [false; 256];
+    let mut i = 0;
+    while i < 256 {
+        table[i] = matches!(i as u8, b' ' | b'\n' | b'\t' | b'\r' | b'"' | b'[' | b']');
+        i += 1;
+    }
+    table
+};
+
 /// Tokenizer splits a string into an iterator of tokens.
+///
+/// The input is scanned as bytes: all delimiters are ASCII, so they never split a UTF-8
+/// sequence and tokens are always valid string slices.
 pub(crate) struct Tokenizer<'a> {
     str: &'a str,
     offset: usize,
+    /// Offset of the last returned token.
+    token_start: usize,
 }
 
 impl<'a> Tokenizer<'a> {
     pub fn new(str: &'a str) -> Self {
-        Self { str, offset: 0 }
+        Self {
+            str,
+            offset: 0,
+            token_start: 0,
+        }
     }
 
-    fn rewind_until(&mut self, chars: &[char]) -> usize {
-        let mut offset = 0;
+    fn bytes(&self) -> &'a [u8] {
+        self.str.as_bytes()
+    }
 
-        loop {
-            // Peek next char
-            let Some(ch) = self.peek_char() else {
-                break;
-            };
+    /// Offset of a match found in the string after `start`, or the end of the string.
+    fn found_or_end(&self, start: usize, found: Option<usize>) -> usize {
+        found.map_or(self.str.len(), |pos| start + pos)
+    }
 
-            if chars.contains(&ch) {
-                break;
-            }
+    /// End of a quoted string starting at `start`, after the closing quote.
+    fn quoted_end(&self, start: usize) -> usize {
+        let bytes = self.bytes();
+        let mut pos = start + 1;
 
-            // Take next char
-            if let Some((pos, _)) = self.next_char() {
-                offset = pos;
+        // Escaped characters (like \") don't end the string.
+        while let Some(found) = memchr2(b'"', b'\\', &bytes[pos..]) {
+            let found = pos + found;
+            if bytes[found] == b'"' {
+                return found + 1;
             }
-        }
 
-        offset
-    }
-
-    fn peek_char(&mut self) -> Option<char> {
-        if self.offset > self.str.len() {
-            None
-        } else {
-            self.str[self.offset..].chars().next()
+            pos = (found + 2).min(bytes.len());
         }
-    }
 
-    /// Get current char and step forward.
-    fn next_char(&mut self) -> Option<(usize, char)> {
-        match self.peek_char() {
-            Some(ch) => {
-                let offset = self.offset;
-                self.offset += 1;
-                Some((offset, ch))
-            }
-            None => None,
-        }
+        bytes.len()
     }
 
     /// Get current token without moving forward.
     pub fn peek_token(&mut self) -> Option<Token<'a>> {
         let offset = self.offset;
+        let token_start = self.token_start;
         let token = self.next();
         self.offset = offset;
+        self.token_start = token_start;
 
         token
     }
 
     pub fn token(&self, start: usize, end: usize) -> Token<'a> {
-        let token = Token::new(&self.str[start..end]);
-        dbg!(token)
+        Token::new(&self.str[start..end])
     }
 
     /// Return current offset within string.
     pub fn offset(&self) -> usize {
         self.offset
     }
+
+    /// Continue tokenizing at `offset`.
+    pub fn seek(&mut self, offset: usize) {
+        self.offset = offset;
+    }
+
+    /// Offset of the last token returned by [Tokenizer::next].
+    pub fn token_start(&self) -> usize {
+        self.token_start
+    }
+
+    /// The whole string being tokenized.
+    pub fn source(&self) -> &'a str {
+        self.str
+    }
 }
 
 impl<'a> Iterator for Tokenizer<'a> {
     type Item = Token<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        loop {
-            let Some((start, ch)) = self.next_char() else {
-                return None;
-            };
-
-            let token = match ch {
-                '[' | ']' => self.token(start, start + 1),
-                ' ' | '\n' | '\t' | '\r' => continue,
-                '"' => {
-                    let mut end = self.rewind_until(&['"']);
+        let bytes = self.bytes();
 
-                    // Consume remaining "
-                    if let Some((pos, _)) = self.next_char() {
-                        end = pos;
-                    }
+        loop {
+            let start = self.offset;
 
-                    self.token(start, end + 1)
+            let end = match *bytes.get(start)? {
+                b'[' | b']' => start + 1,
+                b' ' | b'\n' | b'\t' | b'\r' => {
+                    self.offset += 1;
+                    continue;
                 }
-                '#' => {
+                b'"' => self.quoted_end(start),
+                b'#' => {
                     // Skip comment line
-                    self.rewind_until(&['\r', '\n']);
+                    self.offset = self.found_or_end(start, memchr2(b'\r', b'\n', &bytes[start..]));
                     continue;
                 }
                 _ => {
-                    let mut end = self.rewind_until(&[' ', '\r', '\n', '\t', '"', '[', ']']);
-                    if end == 0 {
-                        end = start;
-                    }
-
-                    self.token(start, end + 1)
+                    let end = bytes[start..]
+                        .iter()
+                        .position(|&byte| DELIMITERS[byte as usize]);
+                    self.found_or_end(start, end)
                 }
             };
 
-            return Some(token);
+            self.offset = end;
+            self.token_start = start;
+
+            return Some(self.token(start, end));
         }
     }
 }
@@ -119,6 +137,23 @@ impl<'a> Iterator for Tokenizer<'a> {
 mod tests {
     use super::*;
 
+    #[test]
+    fn token_start() {
+        let mut t = Tokenizer::new("Scale\n  \"float fov\" [ 1 ]");
+
+        t.next();
+        assert_eq!(t.token_start(), 0);
+
+        assert_eq!(t.peek_token(), Some(Token::new("\"float fov\"")));
+        assert_eq!(t.token_start(), 0);
+
+        t.next();
+        assert_eq!(t.token_start(), 8);
+        t.next();
+        t.next();
+        assert_eq!(t.token_start(), 22);
+    }
+
     #[test]
     fn empty_line() {
         let mut t = Tokenizer::new("");
@@ -215,6 +250,15 @@ Scale
         assert_eq!(t.next(), None);
     }
 
+    #[test]
+    fn quotes_escaped() {
+        let mut t = Tokenizer::new(r#" "a \"b\" \\" "c" "#);
+
+        assert_eq!(t.next(), Some(Token::new(r#""a \"b\" \\""#)));
+        assert_eq!(t.next(), Some(Token::new("\"c\"")));
+        assert_eq!(t.next(), None);
+    }
+
     #[test]
     fn single_quote() {
         let mut t = Tokenizer::new("foo \"abc");
@@ -243,6 +287,26 @@ Scale
         assert_eq!(t.next(), None);
     }
 
+    #[test]
+    fn non_ascii() {
+        let mut t = Tokenizer::new("# Коментар\n\"текстура\" ñame[\"\\é\"]");
+
+        assert_eq!(t.next(), Some(Token::new("\"текстура\"")));
+        assert_eq!(t.next(), Some(Token::new("ñame")));
+        assert_eq!(t.next(), Some(Token::new("[")));
+        assert_eq!(t.next(), Some(Token::new("\"\\é\"")));
+        assert_eq!(t.next(), Some(Token::new("]")));
+        assert_eq!(t.next(), None);
+    }
+
+    #[test]
+    fn trailing_escape() {
+        let mut t = Tokenizer::new("\"abc\\");
+
+        assert_eq!(t.next(), Some(Token::new("\"abc\\")));
+        assert_eq!(t.next(), None);
+    }
+
     #[test]
     fn parse_scale() {
         let mut t = Tokenizer::new("Scale -1 1 1");
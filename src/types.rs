@@ -1,14 +1,28 @@
 //! Data structures that can be deserialized from a parameter list.
 
+use std::path::Path;
+#[cfg(any(feature = "ply", feature = "nanovdb"))]
+use std::sync::Arc;
 use std::{collections::HashMap, str::FromStr};
 
+#[cfg(feature = "nanovdb")]
+use crate::nanovdb::{GridType, NanoVdbFile};
+#[cfg(feature = "ply")]
+use crate::ply::PlyMesh;
+#[cfg(any(feature = "ply", feature = "nanovdb"))]
+use crate::scene::resolve_path;
 use crate::{
-    param::{Param, ParamList, Spectrum},
+    math::{float, Bounds3, Float, Mat4, Vec3},
+    param::{
+        FromParam, FromParams, FromTypedParams, Param, ParamList, ParamType, Spectrum, TexRef,
+    },
+    subdiv::loop_subdivide,
     Error, Result,
 };
 
 /// The coordinate system.
-#[derive(Debug, Default, Eq, PartialEq)]
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum CoordinateSystem {
     /// Translate the scene so that the camera is at the origin.
     #[default]
@@ -27,13 +41,51 @@ impl FromStr for CoordinateSystem {
             "cameraworld" => Ok(CoordinateSystem::CameraWorld),
             "camera" => Ok(CoordinateSystem::Camera),
             "world" => Ok(CoordinateSystem::World),
-            _ => Err(Error::UnknownCoordinateSystem),
+            _ => Err(Error::UnknownCoordinateSystem(s.to_string())),
+        }
+    }
+}
+
+/// Color space used to interpret RGB values, set with the `ColorSpace` directive.
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ColorSpace {
+    #[default]
+    Srgb,
+    Rec2020,
+    Aces2065_1,
+    DciP3,
+}
+
+impl ColorSpace {
+    /// Color space name as used in scene files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ColorSpace::Srgb => "srgb",
+            ColorSpace::Rec2020 => "rec2020",
+            ColorSpace::Aces2065_1 => "aces2065-1",
+            ColorSpace::DciP3 => "dci-p3",
+        }
+    }
+}
+
+impl FromStr for ColorSpace {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "srgb" => Ok(ColorSpace::Srgb),
+            "rec2020" => Ok(ColorSpace::Rec2020),
+            "aces2065-1" => Ok(ColorSpace::Aces2065_1),
+            "dci-p3" => Ok(ColorSpace::DciP3),
+            _ => Err(Error::UnknownColorSpace),
         }
     }
 }
 
 /// Scene-wide rendering options.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Options {
     /// Forces all pixel samples to be through the center of the pixel area.
     pub disable_pixel_jitter: bool,
@@ -51,6 +103,14 @@ pub struct Options {
     pub mse_reference_out: Option<String>,
     /// Specifies the coordinate system to use for rendering computation.
     pub render_coord_sys: CoordinateSystem,
+    /// Seed for the random number generators.
+    pub seed: i32,
+    /// Replaces all materials with diffuse ones.
+    pub force_diffuse: bool,
+    /// Write images with per-pixel statistics, like the number of samples and time spent.
+    pub pixel_stats: bool,
+    /// Render on the GPU or with the CPU implementation of the wavefront integrator.
+    pub wavefront: bool,
 }
 
 impl Default for Options {
@@ -63,17 +123,40 @@ impl Default for Options {
             mse_reference_image: None,
             mse_reference_out: None,
             render_coord_sys: CoordinateSystem::CameraWorld,
+            seed: 0,
+            force_diffuse: false,
+            pixel_stats: false,
+            wavefront: false,
         }
     }
 }
 
 impl Options {
-    pub fn apply(&mut self, _option: Param) -> Result<()> {
+    /// Apply an `Option` directive.
+    ///
+    /// Fails with [Error::UnknownOption] if the option is not supported by pbrt.
+    pub fn apply(&mut self, option: Param) -> Result<()> {
+        match option.name {
+            "disablepixeljitter" => self.disable_pixel_jitter = option.single()?,
+            "disabletexturefiltering" => self.disable_texture_filtering = option.single()?,
+            "disablewavelengthjitter" => self.disable_wavelength_jitter = option.single()?,
+            "displacementedgescale" => self.displacement_edge_scale = option.single()?,
+            "msereferenceimage" => self.mse_reference_image = Some(option.string().into_owned()),
+            "msereferenceout" => self.mse_reference_out = Some(option.string().into_owned()),
+            "rendercoordsys" => self.render_coord_sys = option.string().parse()?,
+            "seed" => self.seed = option.single()?,
+            "forcediffuse" => self.force_diffuse = option.single()?,
+            "pixelstats" => self.pixel_stats = option.single()?,
+            "wavefront" => self.wavefront = option.single()?,
+            name => return Err(Error::UnknownOption(name.to_string())),
+        }
+
         Ok(())
     }
 }
 
-#[derive(Default, Debug)]
+#[derive(Default, Debug, FromParams)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum FilmType {
     /// Stores RGB images using the current color space when the [Film] directive is encountered.
     #[default]
@@ -82,46 +165,67 @@ pub enum FilmType {
     GBuffer {
         /// Geometric data is stored in camera space by default.
         /// Alternatively, "world" can be specified for world space.
+        #[param(default = String::from("camera"))]
         coordinate_system: String,
     },
     /// Stores a discretized spectral distribution at each pixel, in addition to RGB.
     Spectral {
         /// Number of buckets that the spectral range is discretized into.
+        #[param(default = 16)]
         nbuckets: i32,
         /// Minimum wavelength for spectral range.
+        #[param(default = 360.0)]
         lambda_min: f32,
         /// Maximum wavelength for spectral range.
+        #[param(default = 830.0)]
         lambda_max: f32,
     },
 }
 
 /// Film specifies the characteristics of the image being generated by the renderer.
-#[derive(Debug)]
+///
+/// Parameters shared by all films are stored here, like pbrt's `FilmBase` and `PixelSensor`,
+/// parameters specific to the film type are stored in [Film::ty].
+#[derive(Debug, FromParams)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Film {
     /// The number of pixels in the x direction.
+    #[param(default = 1280)]
     pub xresolution: i32,
     /// The number of pixels in the y direction.
+    #[param(default = 720)]
     pub yresolution: i32,
     /// The sub-region of the image to render.
+    #[param(default = [0.0, 1.0, 0.0, 1.0])]
     pub crop_window: [f32; 4],
+    /// The sub-region of the image to render in pixels as `[x0, x1, y0, y1]`,
+    /// takes precedence over [Film::crop_window].
+    pub pixel_bounds: Option<[i32; 4]>,
     /// Diagonal length of the film, in mm.
+    #[param(default = 35.0)]
     pub diagonal: f32,
     /// The output filename.
+    #[param(default = String::from("pbrt.exr"))]
     pub filename: String,
     /// Whether 16-bit floating point values (as opposed to 32-bit
     /// floating point values) should be used when saving images in OpenEXR format.
+    #[param(default = true)]
     pub save_fp16: bool,
     /// Film sensitivity to light; final pixel values are scaled by the "iso" value divided by 100.
+    #[param(default = 100.0)]
     pub iso: f32,
     /// If non-zero, this gives a temperature in degrees kelvin
     /// that is used as the reference color temperature used for whitebalancing.
     pub white_balance: f32,
     /// Characterizes the sensor's response for red, green, and blue colors.
     /// The default corresponds to using the CIE 1931 spectral response curves.
+    #[param(default = String::from("cie1931"))]
     pub sensor: String,
     /// Image sample values with luminance greater than this value are clamped to have this luminance.
+    #[param(default = f32::MAX)]
     pub max_component_value: f32,
     /// Film type.
+    #[param(skip)]
     pub ty: FilmType,
 }
 
@@ -131,6 +235,7 @@ impl Default for Film {
             xresolution: 1280,
             yresolution: 720,
             crop_window: [0.0, 1.0, 0.0, 1.0],
+            pixel_bounds: None,
             diagonal: 35.0,
             filename: String::from("pbrt.exr"),
             save_fp16: true,
@@ -145,58 +250,72 @@ impl Default for Film {
 
 impl Film {
     pub fn new(ty: &str, params: ParamList) -> Result<Film> {
-        let ty = match ty {
-            "rgb" => FilmType::Rgb,
-            "gbuffer" => FilmType::GBuffer {
-                coordinate_system: params
-                    .string("coordinatesystem")
-                    .unwrap_or("camera")
-                    .to_owned(),
-            },
-            "spectral" => FilmType::Spectral {
-                nbuckets: params.integer("nbuckets", 16)?,
-                lambda_min: params.float("lambdamin", 360.0)?,
-                lambda_max: params.float("lambdamax", 830.0)?,
-            },
-            _ => unimplemented!(),
-        };
-
-        let film = Film {
-            xresolution: params.integer("xresolution", 1280)?,
-            yresolution: params.integer("yresolution", 720)?,
-            crop_window: params
-                .floats("cropwindow")?
-                .unwrap_or_else(|| vec![0.0, 1.0, 0.0, 1.0])
-                .try_into()
-                .map_err(|_| Error::ParseSlice)?,
-            diagonal: params.float("diagonal", 35.0)?,
-            filename: params.string("filename").unwrap_or("pbrt.exr").to_owned(),
-            save_fp16: params.boolean("savefp16", true)?,
-            iso: params.float("iso", 100.0)?,
-            white_balance: params.float("whitebalance", 0.0)?,
-            sensor: params.string("sensor").unwrap_or("cie1931").to_owned(),
-            max_component_value: params.float("maxcomponentvalue", f32::MAX)?,
-            ty,
-        };
+        Ok(Film {
+            ty: FilmType::from_typed_params(ty, &params)?,
+            ..params.extract()?
+        })
+    }
+}
+
+/// Mapping of directions to the image, used by [Camera::Spherical].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SphericalMapping {
+    /// Area-preserving mapping based on an octahedral encoding of the unit sphere.
+    #[default]
+    EqualArea,
+    /// Latitude-longitude mapping.
+    Equirectangular,
+}
 
-        Ok(film)
+impl FromParam for SphericalMapping {
+    fn from_param(param: &Param) -> Result<Self> {
+        match &*param.string() {
+            "equalarea" => Ok(SphericalMapping::EqualArea),
+            "equirectangular" => Ok(SphericalMapping::Equirectangular),
+            _ => Err(Error::InvalidString),
+        }
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, FromParams)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Camera {
     Orthographic {
         /// The time at which the virtual camera shutter opens.
         shutter_open: f32,
         /// The time at which the virtual camera shutter closes.
+        #[param(default = 1.0)]
         shutter_close: f32,
+        /// Aspect ratio of the film, by default computed from the x and y resolutions.
+        frame_aspect_ratio: Option<f32>,
+        /// Extent of the image plane in screen space as `[x0, x1, y0, y1]`, by default
+        /// `[-1, 1]` along the shorter image axis and proportionally wider along the other one.
+        screen_window: Option<[f32; 4]>,
+        /// The radius of the lens, 0 means a pinhole camera.
+        lens_radius: f32,
+        /// Distance to the plane of focus.
+        #[param(default = 1e6)]
+        focal_distance: f32,
     },
     Perspective {
         /// The time at which the virtual camera shutter opens.
         shutter_open: f32,
         /// The time at which the virtual camera shutter closes.
+        #[param(default = 1.0)]
         shutter_close: f32,
+        /// Aspect ratio of the film, by default computed from the x and y resolutions.
+        frame_aspect_ratio: Option<f32>,
+        /// Extent of the image plane in screen space as `[x0, x1, y0, y1]`, by default
+        /// `[-1, 1]` along the shorter image axis and proportionally wider along the other one.
+        screen_window: Option<[f32; 4]>,
+        /// The radius of the lens, 0 means a pinhole camera.
+        lens_radius: f32,
+        /// Distance to the plane of focus.
+        #[param(default = 1e6)]
+        focal_distance: f32,
         /// Specifies the field of view for the perspective camera.
+        #[param(default = 90.0)]
         fov: f32,
     },
     /// The `RealisticCamera` simulates imaging from light rays passing through complex lens systems.
@@ -204,13 +323,16 @@ pub enum Camera {
         /// The time at which the virtual camera shutter opens.
         shutter_open: f32,
         /// The time at which the virtual camera shutter closes.
+        #[param(default = 1.0)]
         shutter_close: f32,
         /// Specifies the name of a lens description file that gives the collection of lens elements in the lens system.
         lensfile: Option<String>,
         /// Diameter of the lens system's aperture, specified in mm. The smaller the aperture,
         /// the less light reaches the film plane, but the greater the range of distances that are in focus.
+        #[param(default = 1.0)]
         aperture_diameter: f32,
         /// Distance in meters at which the lens system is focused.
+        #[param(default = 10.0)]
         focus_distance: f32,
         /// Allows specifying the shape of the camera aperture, which is circular by default.
         /// The values of "gaussian", "square", "pentagon", and "star" are associated with built-in aperture shapes;
@@ -222,46 +344,144 @@ pub enum Camera {
         /// The time at which the virtual camera shutter opens.
         shutter_open: f32,
         /// The time at which the virtual camera shutter closes.
+        #[param(default = 1.0)]
         shutter_close: f32,
-        /// By default, an area-preserving mapping based on an octahedral encoding of the unit sphere is used.
-        /// Alternatively, an equirectangular mapping can be specified using "equirectangular".
-        mapping: String,
+        /// How directions are mapped to the image.
+        mapping: SphericalMapping,
     },
 }
 
 impl Camera {
     pub fn new(ty: &str, params: ParamList) -> Result<Camera> {
         // Two parameters that set the camera's shutter open times are common to all cameras in pbrt.
-        let shutter_open = params.float("shutteropen", 0.0)?;
-        let shutter_close = params.float("shutterclose", 1.0)?;
-
-        let camera = match ty {
-            "orthographic" => Camera::Orthographic {
-                shutter_open,
-                shutter_close,
-            },
-            "perspective" => Camera::Perspective {
-                shutter_open,
-                shutter_close,
-                fov: params.float("fov", 90.0)?,
-            },
-            "realistic" => Camera::Realistic {
-                shutter_open,
-                shutter_close,
-                lensfile: params.string("lensfile").map(|str| str.to_string()),
-                aperture_diameter: params.float("aperturediameter", 1.0)?,
-                focus_distance: params.float("focusdistance", 10.0)?,
-                aperture: params.string("aperture").map(|str| str.to_string()),
-            },
-            "spherical" => Camera::Spherical {
-                shutter_open,
-                shutter_close,
-                mapping: params.string("mapping").unwrap_or("equalarea").to_string(),
-            },
-            _ => return Err(Error::InvalidCameraType),
-        };
-
-        Ok(camera)
+        Self::from_typed_params(ty, &params).map_err(|err| match err {
+            Error::InvalidObjectType => Error::InvalidCameraType,
+            err => err,
+        })
+    }
+
+    /// The time at which the virtual camera shutter opens.
+    pub fn shutter_open(&self) -> f32 {
+        match *self {
+            Camera::Orthographic { shutter_open, .. }
+            | Camera::Perspective { shutter_open, .. }
+            | Camera::Realistic { shutter_open, .. }
+            | Camera::Spherical { shutter_open, .. } => shutter_open,
+        }
+    }
+
+    /// The time at which the virtual camera shutter closes.
+    pub fn shutter_close(&self) -> f32 {
+        match *self {
+            Camera::Orthographic { shutter_close, .. }
+            | Camera::Perspective { shutter_close, .. }
+            | Camera::Realistic { shutter_close, .. }
+            | Camera::Spherical { shutter_close, .. } => shutter_close,
+        }
+    }
+
+    /// The radius of the thin lens, `None` for cameras without one.
+    pub fn lens_radius(&self) -> Option<f32> {
+        match *self {
+            Camera::Orthographic { lens_radius, .. } | Camera::Perspective { lens_radius, .. } => {
+                Some(lens_radius)
+            }
+            Camera::Realistic { .. } | Camera::Spherical { .. } => None,
+        }
+    }
+
+    /// Distance to the plane of focus, `None` for the spherical camera.
+    pub fn focal_distance(&self) -> Option<f32> {
+        match *self {
+            Camera::Orthographic { focal_distance, .. }
+            | Camera::Perspective { focal_distance, .. } => Some(focal_distance),
+            Camera::Realistic { focus_distance, .. } => Some(focus_distance),
+            Camera::Spherical { .. } => None,
+        }
+    }
+
+    /// Extent of the image plane in screen space as `[x0, x1, y0, y1]` for a film with
+    /// the given aspect ratio (width / height), `None` for cameras without a projection.
+    ///
+    /// `frameaspectratio` takes precedence over `aspect` when computing the default window.
+    pub fn screen_window(&self, aspect: f32) -> Option<[f32; 4]> {
+        let (frame_aspect_ratio, screen_window) = match *self {
+            Camera::Orthographic {
+                frame_aspect_ratio,
+                screen_window,
+                ..
+            }
+            | Camera::Perspective {
+                frame_aspect_ratio,
+                screen_window,
+                ..
+            } => (frame_aspect_ratio, screen_window),
+            Camera::Realistic { .. } | Camera::Spherical { .. } => return None,
+        };
+
+        if screen_window.is_some() {
+            return screen_window;
+        }
+
+        let aspect = frame_aspect_ratio.unwrap_or(aspect);
+        if aspect > 1.0 {
+            Some([-aspect, aspect, -1.0, 1.0])
+        } else {
+            Some([-1.0, 1.0, -1.0 / aspect, 1.0 / aspect])
+        }
+    }
+
+    /// Camera to screen space transformation as defined by pbrt, `None` for cameras
+    /// without a projection.
+    ///
+    /// Depth is mapped to `[0, 1]`, with near and far planes at 0 and 1 for the orthographic
+    /// camera and at 0.01 and 1000 for the perspective camera.
+    pub fn screen_from_camera(&self) -> Option<Mat4> {
+        match *self {
+            Camera::Orthographic { .. } => Some(Mat4::IDENTITY),
+            Camera::Perspective { fov, .. } => {
+                let (near, far): (Float, Float) = (1e-2, 1000.0);
+
+                let persp = Mat4::from_cols_array(&[
+                    1.0,
+                    0.0,
+                    0.0,
+                    0.0,
+                    0.0,
+                    1.0,
+                    0.0,
+                    0.0,
+                    0.0,
+                    0.0,
+                    far / (far - near),
+                    1.0,
+                    0.0,
+                    0.0,
+                    -far * near / (far - near),
+                    0.0,
+                ]);
+
+                // The field of view spans the shorter image axis, which is [-1, 1] in screen space.
+                let inv_tan = 1.0 / (float(fov).to_radians() / 2.0).tan();
+                Some(Mat4::from_scale(Vec3::new(inv_tan, inv_tan, 1.0)) * persp)
+            }
+            Camera::Realistic { .. } | Camera::Spherical { .. } => None,
+        }
+    }
+
+    /// Projection matrix for a film with the given aspect ratio (width / height),
+    /// `None` for cameras without a projection.
+    ///
+    /// Maps camera space to normalized device coordinates: [Camera::screen_window] to `[-1, 1]`
+    /// along x and y, depth to `[0, 1]` as in [Camera::screen_from_camera].
+    /// Camera space is left-handed, looking along +z with +y up.
+    pub fn projection(&self, aspect: f32) -> Option<Mat4> {
+        let [x0, x1, y0, y1] = self.screen_window(aspect)?.map(float);
+
+        let ndc_from_screen = Mat4::from_scale(Vec3::new(2.0 / (x1 - x0), 2.0 / (y1 - y0), 1.0))
+            * Mat4::from_translation(Vec3::new(-(x0 + x1) / 2.0, -(y0 + y1) / 2.0, 0.0));
+
+        Some(ndc_from_screen * self.screen_from_camera()?)
     }
 }
 
@@ -271,47 +491,155 @@ impl Camera {
 /// Many of these integrators are present only for pedagogical purposes or for use in debugging
 /// more complex integrators through computing images using much simpler integration algorithms.
 /// For rendering high quality images, one should almost always use one of `bdpt`, `mlt`, `sppm`, or `volpath`.
-#[derive(Debug)]
+/// Light sampling strategy, used by integrators which sample lights.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LightSampler {
+    /// All lights are sampled with equal probability.
+    Uniform,
+    /// Lights are sampled according to their emitted power.
+    Power,
+    /// Lights are sampled with a BVH that accounts for the lights' spatial and directional extent.
+    #[default]
+    Bvh,
+    /// Like [LightSampler::Bvh], but evaluates all lights (only useful for debugging).
+    Exhaustive,
+}
+
+impl FromParam for LightSampler {
+    fn from_param(param: &Param) -> Result<Self> {
+        match &*param.string() {
+            "uniform" => Ok(LightSampler::Uniform),
+            "power" => Ok(LightSampler::Power),
+            "bvh" => Ok(LightSampler::Bvh),
+            "exhaustive" => Ok(LightSampler::Exhaustive),
+            _ => Err(Error::InvalidString),
+        }
+    }
+}
+
+#[derive(Debug, FromParams)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Integrator {
     /// Ambient occlusion (accessibility over the hemisphere).
-    AmbientOcclusion,
+    AmbientOcclusion {
+        /// Whether to sample the hemisphere with a cosine-weighted distribution.
+        #[param(default = true)]
+        cos_sample: bool,
+        /// Occluders further than this distance are ignored.
+        #[param(default = f32::INFINITY)]
+        max_distance: f32,
+    },
     /// Bidirectional path tracing.
-    Bdpt,
+    Bdpt {
+        /// Maximum length of a light-carrying path sampled by the integrator.
+        #[param(default = 5)]
+        max_depth: i32,
+        #[param(default = LightSampler::Power)]
+        light_sampler: LightSampler,
+        /// Whether to regularize near-specular BSDFs to reduce variance.
+        regularize: bool,
+        /// Write images with the contribution of each sampling strategy.
+        visualize_strategies: bool,
+        /// Write images with the MIS-weighted contribution of each sampling strategy.
+        visualize_weights: bool,
+    },
     /// Path tracing starting from the light sources.
-    LightPath,
+    LightPath {
+        /// Maximum length of a light-carrying path sampled by the integrator.
+        #[param(default = 5)]
+        max_depth: i32,
+    },
     /// Metropolis light transport using bidirectional path tracing.
-    Mlt,
+    Mlt {
+        /// Maximum length of a light-carrying path sampled by the integrator.
+        #[param(default = 5)]
+        max_depth: i32,
+        /// Number of samples used to compute the initial distribution of paths.
+        #[param(default = 100000)]
+        bootstrap_samples: i32,
+        /// Number of Markov chains to run in parallel.
+        #[param(default = 1000)]
+        chains: i32,
+        /// Average number of mutations per image pixel.
+        #[param(default = 100)]
+        mutations_per_pixel: i32,
+        /// Probability of a "large step" mutation, which generates a new path from scratch.
+        #[param(default = 0.3)]
+        large_step_probability: f32,
+        /// Standard deviation of the perturbation applied by "small step" mutations.
+        #[param(default = 0.01)]
+        sigma: f32,
+        /// Whether to regularize near-specular BSDFs to reduce variance.
+        regularize: bool,
+    },
     /// Path tracing.
-    Path,
+    Path {
+        /// Maximum length of a light-carrying path sampled by the integrator.
+        #[param(default = 5)]
+        max_depth: i32,
+        light_sampler: LightSampler,
+        /// Whether to regularize near-specular BSDFs to reduce variance.
+        regularize: bool,
+    },
     /// Rendering using a simple random walk without any explicit light sampling.
-    RandomWalk,
+    RandomWalk {
+        /// Maximum length of a light-carrying path sampled by the integrator.
+        #[param(default = 5)]
+        max_depth: i32,
+    },
     /// Path tracing with very basic sampling algorithms.
-    SimplePath,
+    SimplePath {
+        /// Maximum length of a light-carrying path sampled by the integrator.
+        #[param(default = 5)]
+        max_depth: i32,
+        /// Whether to sample lights at path vertices.
+        #[param(default = true)]
+        sample_lights: bool,
+        /// Whether to sample BSDFs for new path directions (uniform sampling otherwise).
+        #[param(default = true)]
+        sample_bsdf: bool,
+    },
     /// Volumetric path tracing with very basic sampling algorithms.
-    SimpleVolPath,
+    SimpleVolPath {
+        /// Maximum length of a light-carrying path sampled by the integrator.
+        #[param(default = 5)]
+        max_depth: i32,
+    },
     /// Stochastic progressive photon mapping
-    Sppm,
+    Sppm {
+        /// Maximum length of a light-carrying path sampled by the integrator.
+        #[param(default = 5)]
+        max_depth: i32,
+        /// Number of photons to shoot from light sources in each iteration,
+        /// negative means the number of image pixels.
+        #[param(default = -1)]
+        photons_per_iteration: i32,
+        /// Initial photon search radius.
+        #[param(default = 1.0)]
+        radius: f32,
+        /// Seed for the random number generator used for photon paths.
+        seed: i32,
+    },
     /// Volumetric path tracing.
     VolPath {
         /// Maximum length of a light-carrying path sampled by the integrator.
+        #[param(default = 5)]
         max_depth: i32,
+        light_sampler: LightSampler,
+        /// Whether to regularize near-specular BSDFs to reduce variance.
+        regularize: bool,
     },
 }
 
 impl Integrator {
     pub fn new(ty: &str, params: ParamList) -> Result<Integrator> {
-        let integ = match ty {
-            "volpath" => Integrator::VolPath {
-                max_depth: params.integer("maxdepth", 5)?,
-            },
-            _ => unimplemented!(),
-        };
-
-        Ok(integ)
+        Self::from_typed_params(ty, &params)
     }
 }
 
 #[derive(Debug, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BvhSplitMethod {
     /// Denotes the surface area heuristic.
     #[default]
@@ -324,10 +652,24 @@ pub enum BvhSplitMethod {
     Hlbvh,
 }
 
-#[derive(Debug)]
+impl FromParam for BvhSplitMethod {
+    fn from_param(param: &Param) -> Result<Self> {
+        match &*param.string() {
+            "sah" => Ok(BvhSplitMethod::Sah),
+            "middle" => Ok(BvhSplitMethod::Middle),
+            "equal" => Ok(BvhSplitMethod::Equal),
+            "hlbvh" => Ok(BvhSplitMethod::Hlbvh),
+            _ => Err(Error::InvalidString),
+        }
+    }
+}
+
+#[derive(Debug, FromParams)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Accelerator {
     Bvh {
         /// Maximum number of primitives to allow in a node in the tree.
+        #[param(default = 4)]
         max_node_prims: i32,
         /// Method to use to partition the primitives when building the tree.
         split_method: BvhSplitMethod,
@@ -335,116 +677,375 @@ pub enum Accelerator {
     KdTree {
         /// The value of the cost function that estimates the expected cost of
         /// performing a ray-object intersection, for use in building the kd-tree.
+        #[param(default = 5)]
         intersect_cost: i32,
         /// Estimated cost for traversing a ray through a kd-tree node.
+        #[param(default = 1)]
         traversal_cost: i32,
         /// "Bonus" factor for kd-tree nodes that represent empty space.
+        #[param(default = 0.5)]
         empty_bonus: f32,
         /// Maximum number of primitives to store in kd-tree node.
+        #[param(default = 1)]
         max_prims: i32,
         /// Maximum depth of the kd-tree. If negative, the kd-tree chooses a maximum depth
         /// based on the number of primitives to be stored in it.
+        #[param(default = -1)]
         max_depth: i32,
     },
 }
 
 impl Accelerator {
     pub fn new(ty: &str, params: ParamList) -> Result<Accelerator> {
-        let acc = match ty {
-            "bvh" => Accelerator::Bvh {
-                max_node_prims: params.integer("maxnodeprims", 4)?,
-                split_method: match params.string("splitmethod").unwrap_or("sah") {
-                    "sah" => BvhSplitMethod::Sah,
-                    "middle" => BvhSplitMethod::Middle,
-                    "equal" => BvhSplitMethod::Equal,
-                    "hlbvh" => BvhSplitMethod::Hlbvh,
-                    _ => return Err(Error::InvalidString),
-                },
-            },
-            "kdtree" => Accelerator::KdTree {
-                intersect_cost: params.integer("intersectcost", 5)?,
-                traversal_cost: params.integer("traversalcost", 1)?,
-                empty_bonus: params.float("emptybonus", 0.5)?,
-                max_prims: params.integer("maxprims", 1)?,
-                max_depth: params.integer("maxdepth", -1)?,
-            },
-            _ => return Err(Error::InvalidString),
-        };
-
-        Ok(acc)
-    }
-}
-
-// The Sampler generates samples for the image, time, lens, and Monte Carlo integration.
-#[derive(Debug, Default)]
-pub enum Sampler {
-    Halton,
-    Independent,
-    PaddedSobol,
-    Sobol,
-    Stratified,
+        Self::from_typed_params(ty, &params)
+    }
+}
+
+/// Randomization applied to low-discrepancy sample points.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum RandomizeStrategy {
+    None,
+    /// Random permutation of the digits.
+    PermuteDigits,
+    /// Owen scrambling with a fast hash based approximation.
     #[default]
-    ZSobol,
+    FastOwen,
+    /// Owen scrambling.
+    Owen,
+}
+
+impl FromParam for RandomizeStrategy {
+    fn from_param(param: &Param) -> Result<Self> {
+        match &*param.string() {
+            "none" => Ok(RandomizeStrategy::None),
+            "permutedigits" => Ok(RandomizeStrategy::PermuteDigits),
+            "fastowen" => Ok(RandomizeStrategy::FastOwen),
+            "owen" => Ok(RandomizeStrategy::Owen),
+            _ => Err(Error::InvalidString),
+        }
+    }
+}
+
+/// The Sampler generates samples for the image, time, lens, and Monte Carlo integration.
+///
+/// `seed` defaults to 0, pbrt uses the `--seed` command line option instead.
+#[derive(Debug, FromParams)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Sampler {
+    /// Halton sequence.
+    Halton {
+        /// Number of samples taken in each pixel.
+        #[param(default = 16)]
+        pixel_samples: i32,
+        /// [RandomizeStrategy::FastOwen] is not supported by this sampler.
+        #[param(default = RandomizeStrategy::PermuteDigits)]
+        randomization: RandomizeStrategy,
+        seed: i32,
+    },
+    /// Uniform random samples.
+    Independent {
+        /// Number of samples taken in each pixel.
+        #[param(default = 4)]
+        pixel_samples: i32,
+        seed: i32,
+    },
+    /// Independent Sobol' samples for each pair of dimensions.
+    PaddedSobol {
+        /// Number of samples taken in each pixel.
+        #[param(default = 16)]
+        pixel_samples: i32,
+        randomization: RandomizeStrategy,
+        seed: i32,
+    },
+    /// Sobol' sequence.
+    Sobol {
+        /// Number of samples taken in each pixel.
+        #[param(default = 16)]
+        pixel_samples: i32,
+        randomization: RandomizeStrategy,
+        seed: i32,
+    },
+    /// Stratified samples, the number of samples per pixel is `x_samples * y_samples`.
+    Stratified {
+        /// Whether samples are jittered within their strata.
+        #[param(default = true)]
+        jitter: bool,
+        /// Number of strata in x.
+        #[param(default = 4)]
+        x_samples: i32,
+        /// Number of strata in y.
+        #[param(default = 4)]
+        y_samples: i32,
+        seed: i32,
+    },
+    /// Sobol' samples distributed over pixels along a Morton curve, this is the default.
+    ZSobol {
+        /// Number of samples taken in each pixel.
+        #[param(default = 16)]
+        pixel_samples: i32,
+        randomization: RandomizeStrategy,
+        seed: i32,
+    },
+}
+
+impl Default for Sampler {
+    fn default() -> Self {
+        Sampler::ZSobol {
+            pixel_samples: 16,
+            randomization: RandomizeStrategy::FastOwen,
+            seed: 0,
+        }
+    }
 }
 
 impl Sampler {
-    pub fn new(ty: &str, _params: ParamList) -> Result<Sampler> {
-        let sampler = match ty {
-            "halton" => Sampler::Halton,
-            "independent" => Sampler::Independent,
-            "paddedsobol" => Sampler::PaddedSobol,
-            "sobol" => Sampler::Sobol,
-            "stratified" => Sampler::Stratified,
-            "zsobol" => Sampler::ZSobol,
-            _ => return Err(Error::InvalidObjectType),
-        };
+    pub fn new(ty: &str, params: ParamList) -> Result<Sampler> {
+        Self::from_typed_params(ty, &params)
+    }
+
+    /// Number of samples taken in each pixel.
+    pub fn pixel_samples(&self) -> i32 {
+        match *self {
+            Sampler::Halton { pixel_samples, .. }
+            | Sampler::Independent { pixel_samples, .. }
+            | Sampler::PaddedSobol { pixel_samples, .. }
+            | Sampler::Sobol { pixel_samples, .. }
+            | Sampler::ZSobol { pixel_samples, .. } => pixel_samples,
+            Sampler::Stratified {
+                x_samples,
+                y_samples,
+                ..
+            } => x_samples * y_samples,
+        }
+    }
+}
+
+/// The pixel reconstruction filter, specified with `PixelFilter`.
+///
+/// All filters have a radius in x and y, outside of which they are zero.
+#[derive(Debug, Clone, PartialEq, FromParams)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Filter {
+    Box {
+        #[param(default = 0.5)]
+        x_radius: f32,
+        #[param(default = 0.5)]
+        y_radius: f32,
+    },
+    Gaussian {
+        #[param(default = 1.5)]
+        x_radius: f32,
+        #[param(default = 1.5)]
+        y_radius: f32,
+        /// Standard deviation of the Gaussian.
+        #[param(default = 0.5)]
+        sigma: f32,
+    },
+    Mitchell {
+        #[param(default = 2.0)]
+        x_radius: f32,
+        #[param(default = 2.0)]
+        y_radius: f32,
+        #[param(name = "B", default = 1.0 / 3.0)]
+        b: f32,
+        #[param(name = "C", default = 1.0 / 3.0)]
+        c: f32,
+    },
+    /// Windowed sinc filter.
+    Sinc {
+        #[param(default = 4.0)]
+        x_radius: f32,
+        #[param(default = 4.0)]
+        y_radius: f32,
+        /// Number of cycles of the sinc function before it's clamped to zero by the window.
+        #[param(default = 3.0)]
+        tau: f32,
+    },
+    Triangle {
+        #[param(default = 2.0)]
+        x_radius: f32,
+        #[param(default = 2.0)]
+        y_radius: f32,
+    },
+}
 
-        Ok(sampler)
+impl Filter {
+    pub fn new(ty: &str, params: ParamList) -> Result<Filter> {
+        Self::from_typed_params(ty, &params)
+    }
+
+    /// Filter radius in x and y.
+    pub fn radius(&self) -> [f32; 2] {
+        match *self {
+            Filter::Box { x_radius, y_radius }
+            | Filter::Gaussian {
+                x_radius, y_radius, ..
+            }
+            | Filter::Mitchell {
+                x_radius, y_radius, ..
+            }
+            | Filter::Sinc {
+                x_radius, y_radius, ..
+            }
+            | Filter::Triangle { x_radius, y_radius } => [x_radius, y_radius],
+        }
+    }
+}
+
+impl Default for Filter {
+    /// pbrt uses a Gaussian filter unless specified otherwise.
+    fn default() -> Self {
+        Filter::Gaussian {
+            x_radius: 1.5,
+            y_radius: 1.5,
+            sigma: 0.5,
+        }
     }
 }
 
 /// Light sources cast illumination in the scene.
-#[derive(Debug)]
+///
+/// Lights with a `power` emit the given total power, `scale` is ignored then.
+#[derive(Debug, FromParams)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Light {
     /// The "distant" light source represents a directional light source "at infinity";
     /// In other words, it illuminates the scene with light arriving from a single direction.
     Distant,
-    GonioPhotometric,
+    /// Point light with emission defined by a goniometric diagram.
+    #[param(name = "goniometric")]
+    GonioPhotometric {
+        /// Spectral distribution of the light's emitted radiant intensity.
+        #[param(name = "I")]
+        spectrum: Option<Spectrum>,
+        /// Scale factor that modulates the amount of light that the light source emits into the scene.
+        #[param(default = 1.0)]
+        scale: f32,
+        /// Total power emitted by the light.
+        power: Option<f32>,
+        /// Image with the goniometric diagram in an equal-area parameterization.
+        ///
+        /// Resolved against the scene's directory when loading scene files.
+        #[param(required)]
+        filename: String,
+    },
     /// The "infinite" light represents an infinitely far away light source that
     /// potentially casts illumination from all directions.
     Infinite {
         /// The environment map to use for the infinite area light.
         /// If no filename is provided, the light will emit the same amount of radiance from every direction.
+        ///
+        /// Resolved against the scene's directory when loading scene files.
         filename: Option<String>,
         /// The spectral distribution of emission from the light.
+        #[param(name = "L")]
         spectrum: Option<Spectrum>,
     },
-    Point,
-    Projection,
-    Spot,
+    /// Isotropic point light.
+    Point {
+        /// Spectral distribution of the light's emitted radiant intensity.
+        #[param(name = "I")]
+        spectrum: Option<Spectrum>,
+        /// Scale factor that modulates the amount of light that the light source emits into the scene.
+        #[param(default = 1.0)]
+        scale: f32,
+        /// Total power emitted by the light.
+        power: Option<f32>,
+        /// Position of the light.
+        from: [f32; 3],
+    },
+    /// Point light which projects an image into the scene, like a slide projector.
+    Projection {
+        /// Scale factor that modulates the amount of light that the light source emits into the scene.
+        #[param(default = 1.0)]
+        scale: f32,
+        /// Total power emitted by the light.
+        power: Option<f32>,
+        /// Field of view of the projection, in degrees.
+        #[param(default = 90.0)]
+        fov: f32,
+        /// Image to project.
+        ///
+        /// Resolved against the scene's directory when loading scene files.
+        #[param(required)]
+        filename: String,
+    },
+    /// Point light emitting in a cone of directions.
+    Spot {
+        /// Spectral distribution of the light's emitted radiant intensity.
+        #[param(name = "I")]
+        spectrum: Option<Spectrum>,
+        /// Scale factor that modulates the amount of light that the light source emits into the scene.
+        #[param(default = 1.0)]
+        scale: f32,
+        /// Total power emitted by the light.
+        power: Option<f32>,
+        /// Angle between the cone's axis and its outer edge, in degrees.
+        #[param(default = 30.0)]
+        cone_angle: f32,
+        /// Angle at which the falloff towards the edge of the cone starts, measured from the edge.
+        #[param(default = 5.0)]
+        cone_delta_angle: f32,
+        /// Position of the light.
+        from: [f32; 3],
+        /// Point the light is aimed at.
+        #[param(default = [0.0, 0.0, 1.0])]
+        to: [f32; 3],
+    },
 }
 
 impl Light {
     pub fn new(ty: &str, params: ParamList) -> Result<Light> {
-        let light = match ty {
-            "distant" => Light::Distant,
-            "goniometric" => Light::GonioPhotometric,
-            "infinite" => Light::Infinite {
-                filename: params.string("filename").map(|f| f.to_owned()),
-                spectrum: params.get("L").map(|s| s.spectrum()).transpose()?,
-            },
-            "point" => Light::Point,
-            "projection" => Light::Projection,
-            "spot" => Light::Spot,
-            _ => unimplemented!(),
-        };
+        Self::from_typed_params(ty, &params)
+    }
 
-        Ok(light)
+    /// Image file used by the light, if any.
+    pub fn filename(&self) -> Option<&str> {
+        match self {
+            Light::GonioPhotometric { filename, .. } | Light::Projection { filename, .. } => {
+                Some(filename)
+            }
+            Light::Infinite { filename, .. } => filename.as_deref(),
+            Light::Distant | Light::Point { .. } | Light::Spot { .. } => None,
+        }
+    }
+
+    pub(crate) fn filename_mut(&mut self) -> Option<&mut String> {
+        match self {
+            Light::GonioPhotometric { filename, .. } | Light::Projection { filename, .. } => {
+                Some(filename)
+            }
+            Light::Infinite { filename, .. } => filename.as_mut(),
+            Light::Distant | Light::Point { .. } | Light::Spot { .. } => None,
+        }
+    }
+
+    /// Spectral distribution of the emission (`L` or `I`), if any.
+    pub fn spectrum(&self) -> Option<&Spectrum> {
+        match self {
+            Light::GonioPhotometric { spectrum, .. }
+            | Light::Infinite { spectrum, .. }
+            | Light::Point { spectrum, .. }
+            | Light::Spot { spectrum, .. } => spectrum.as_ref(),
+            Light::Distant | Light::Projection { .. } => None,
+        }
+    }
+
+    pub(crate) fn spectrum_mut(&mut self) -> Option<&mut Spectrum> {
+        match self {
+            Light::GonioPhotometric { spectrum, .. }
+            | Light::Infinite { spectrum, .. }
+            | Light::Point { spectrum, .. }
+            | Light::Spot { spectrum, .. } => spectrum.as_mut(),
+            Light::Distant | Light::Projection { .. } => None,
+        }
     }
 }
 
 /// Area lights have geometry associated with them.
-#[derive(Debug)]
+#[derive(Debug, FromParams)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum AreaLight {
     Diffuse {
         /// Filename for an image that describes spatially-varying emission over the surface of the emitter.
@@ -454,8 +1055,10 @@ pub enum AreaLight {
         /// where the surface normal points or both sides.
         two_sided: bool,
         /// Spectral distribution of the light's emitted radiance.
+        #[param(name = "L")]
         spectrum: Option<Spectrum>,
         /// Scale factor that modulates the amount of light that the light source emits into the scene.
+        #[param(default = 1.0)]
         scale: f32,
     },
 }
@@ -474,233 +1077,2598 @@ impl Default for AreaLight {
 impl AreaLight {
     pub fn new(ty: &str, params: ParamList) -> Result<AreaLight> {
         // pbrt currently only includes a single area light implementation, "diffuse".
-        if ty != "diffuse" {
-            return Err(Error::InvalidParamType);
-        }
-        Ok(AreaLight::Diffuse {
-            filename: params.string("filename").map(|s| s.to_string()),
-            two_sided: params.boolean("twosided", false)?,
-            spectrum: params.get("L").map(|l| l.spectrum()).transpose()?,
-            scale: params.float("scale", 1.0)?,
+        Self::from_typed_params(ty, &params).map_err(|err| match err {
+            Error::InvalidObjectType => Error::InvalidParamType,
+            err => err,
         })
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TextureType {
     Float,
     Spectrum,
 }
 
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Texture {
     pub name: String,
     pub ty: TextureType,
-    pub class: String,
+    pub class: TextureClass,
+    /// Mapping of surface points to texture coordinates, used by 2D textures
+    /// (`bilerp`, `checkerboard` with 2 dimensions, `dots`, `imagemap` and `ptex`).
+    pub mapping: TextureMapping,
+    /// Texture to world transformation (the CTM when the texture was declared).
+    ///
+    /// Defines the texture space of 3D textures (`fbm`, `marble`, `windy`, `wrinkled` and
+    /// `checkerboard` with 3 dimensions) and of the spherical, cylindrical and planar mappings.
+    pub transform: Mat4,
+    /// Color space active when the texture was declared.
+    pub color_space: ColorSpace,
 }
 
 impl Texture {
-    pub fn new(name: &str, ty: &str, class: &str, _params: ParamList) -> Result<Texture> {
+    /// Read a texture of type `ty` (`float` or `spectrum`) and class `class`.
+    ///
+    /// References to other textures are resolved with `texture_map` (texture names to
+    /// indices in [crate::Scene::textures]), references to undefined textures are ignored.
+    pub fn new(
+        name: &str,
+        ty: &str,
+        class: &str,
+        mut params: ParamList,
+        texture_map: &HashMap<String, usize>,
+    ) -> Result<Texture> {
         let ty = match ty {
             "spectrum" => TextureType::Spectrum,
             "float" => TextureType::Float,
             _ => return Err(Error::InvalidObjectType),
         };
 
-        // TODO: Parse parameters.
+        params.resolve_textures(texture_map);
+
+        let mapping = params.string("mapping").unwrap_or("uv".into());
+        let mapping =
+            TextureMapping::from_typed_params(&mapping, &params).map_err(|err| match err {
+                Error::InvalidObjectType => Error::InvalidString,
+                err => err,
+            })?;
 
         Ok(Texture {
             name: name.to_string(),
             ty,
-            class: class.to_string(),
+            class: TextureClass::from_typed_params(class, &params)?,
+            mapping,
+            transform: Mat4::IDENTITY,
+            color_space: ColorSpace::default(),
         })
     }
 }
 
-/// Materials specify the light scattering properties of surfaces in the scene.
-#[derive(Debug)]
-pub struct Material {
-    pub ty: String,
+/// Value of a texture parameter.
+///
+/// Float textures take `float` values, spectrum textures take spectra.
+/// Float values are also accepted by spectrum textures as constant spectra.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TextureValue {
+    Float(f32),
+    Spectrum(Spectrum),
 }
 
-impl Material {
-    pub fn new(
-        name: &str,
-        _params: ParamList,
-        _texture_map: &HashMap<String, usize>,
-    ) -> Result<Material> {
-        // Parameters to materials are distinctive in that textures can be used to
-        // specify spatially-varying values for the parameters.
+impl FromParam for TextureValue {
+    fn from_param(param: &Param) -> Result<Self> {
+        match param.ty {
+            ParamType::Float => Ok(TextureValue::Float(param.single()?)),
+            _ => Ok(TextureValue::Spectrum(param.spectrum()?)),
+        }
+    }
+}
 
-        // TODO: Parse material parameters.
+/// Parameter values which may refer to textures.
+trait TextureRefs {
+    fn texture_indices_mut(&mut self, f: &mut dyn FnMut(&mut usize));
+}
 
-        Ok(Material {
-            ty: name.to_string(),
-        })
+impl<T> TextureRefs for TexRef<T> {
+    fn texture_indices_mut(&mut self, f: &mut dyn FnMut(&mut usize)) {
+        if let TexRef::Texture(index) = self {
+            f(index);
+        }
     }
 }
 
-#[derive(Debug)]
-pub enum Shape {
-    /// The "cylinder" is always oriented along the z axis.
-    Cylinder {
-        alpha: f32,
-        /// The cylinder's radius.
-        radius: f32,
-        /// The height of the cylinder's bottom along the z axis.
-        zmin: f32,
-        /// The height of the cylinder's top along the z axis.
-        zmax: f32,
-        /// The maximum extent of the cylinder in phi (in spherical coordinates).
-        phimax: f32,
+impl<T: TextureRefs> TextureRefs for Option<T> {
+    fn texture_indices_mut(&mut self, f: &mut dyn FnMut(&mut usize)) {
+        if let Some(value) = self {
+            value.texture_indices_mut(f);
+        }
+    }
+}
+
+/// Texture parameter with a constant default value.
+const fn constant(value: f32) -> TexRef<TextureValue> {
+    TexRef::Value(TextureValue::Float(value))
+}
+
+/// Texture classes built into pbrt.
+///
+/// Parameters which take textures (like `tex1` and `tex2`) refer to textures by index in
+/// [crate::Scene::textures]. Unless noted otherwise, classes are available both as float
+/// and spectrum textures.
+#[derive(Debug, Clone, PartialEq, FromParams)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TextureClass {
+    /// Bilinear interpolation between four values at the corners of the (u,v) domain.
+    Bilerp {
+        #[param(default = TextureValue::Float(0.0))]
+        v00: TextureValue,
+        #[param(default = TextureValue::Float(1.0))]
+        v01: TextureValue,
+        #[param(default = TextureValue::Float(0.0))]
+        v10: TextureValue,
+        #[param(default = TextureValue::Float(1.0))]
+        v11: TextureValue,
     },
-    /// The "disk" is perpendicular to the z axis in the xy plane, with its object space center at x=0 and y=0.
-    Disk {
-        alpha: f32,
-        /// The position of the disk along the z axis.
-        height: f32,
-        /// The outer radius of the disk.
-        radius: f32,
-        /// The inner radius of the disk (if nonzero, the disk is an annulus).
-        innerradius: f32,
-        /// The maximum extent of the disk in phi (in spherical coordinates).
-        phimax: f32,
+    /// Alternating checks of two textures.
+    Checkerboard {
+        /// 2 for a planar checkerboard, 3 for solid checks.
+        #[param(default = 2)]
+        dimension: i32,
+        #[param(default = constant(1.0))]
+        tex1: TexRef<TextureValue>,
+        #[param(default = constant(0.0))]
+        tex2: TexRef<TextureValue>,
     },
-    /// Spheres are always at the origin in object space.
-    Sphere {
-        alpha: f32,
-        /// The sphere's radius.
-        radius: f32,
-        /// The height of the lower clipping plane along the z axis.
-        zmin: f32,
-        /// The height of the upper clipping plane along the z axis.
-        zmax: f32,
-        /// The maximum extent of the sphere in phi (in spherical coordinates).
-        phimax: f32,
+    /// Constant value everywhere.
+    Constant {
+        #[param(default = TextureValue::Float(1.0))]
+        value: TextureValue,
     },
-    /// A triangle mesh is defined by the "trianglemesh" shape.
-    TriangleMesh {
-        alpha: f32,
-        /// The mesh's topology is defined by the `indices` parameter,
-        /// which is an array of integer indices into the vertex arrays.
-        indices: Vec<i32>,
+    /// Blend of two textures based on the surface normal's alignment with a direction.
+    DirectionMix {
+        #[param(default = constant(0.0))]
+        tex1: TexRef<TextureValue>,
+        #[param(default = constant(1.0))]
+        tex2: TexRef<TextureValue>,
+        /// `tex1` is used where the normal faces `dir`.
+        #[param(default = [0.0, 1.0, 0.0])]
+        dir: [f32; 3],
+    },
+    /// Randomly placed polka dots.
+    Dots {
+        #[param(default = constant(1.0))]
+        inside: TexRef<TextureValue>,
+        #[param(default = constant(0.0))]
+        outside: TexRef<TextureValue>,
+    },
+    /// Fractional Brownian motion noise, float textures only.
+    Fbm {
+        #[param(default = 8)]
+        octaves: i32,
+        #[param(default = 0.5)]
+        roughness: f32,
+    },
+    /// Image texture.
+    ImageMap(ImageMap),
+    /// Marble-like noise, spectrum textures only.
+    Marble {
+        #[param(default = 8)]
+        octaves: i32,
+        #[param(default = 0.5)]
+        roughness: f32,
+        #[param(default = 1.0)]
+        scale: f32,
+        #[param(default = 0.2)]
+        variation: f32,
+    },
+    /// Linear interpolation between two textures.
+    Mix {
+        #[param(default = constant(0.0))]
+        tex1: TexRef<TextureValue>,
+        #[param(default = constant(1.0))]
+        tex2: TexRef<TextureValue>,
+        /// Weight of `tex2`.
+        #[param(default = TexRef::Value(0.5))]
+        amount: TexRef<f32>,
+    },
+    /// Ptex per-face texture.
+    Ptex {
+        /// Resolved against the scene's directory when loading scene files.
+        #[param(required)]
+        filename: String,
+        #[param(default = ColorEncoding::Gamma(2.2))]
+        encoding: ColorEncoding,
+        #[param(default = 1.0)]
+        scale: f32,
+    },
+    /// Product of two textures.
+    Scale {
+        #[param(default = constant(1.0))]
+        tex: TexRef<TextureValue>,
+        #[param(default = TexRef::Value(1.0))]
+        scale: TexRef<f32>,
+    },
+    /// Noise resembling waves on a water surface, float textures only.
+    Windy,
+    /// Fractional Brownian motion noise with sharper creases, float textures only.
+    Wrinkled {
+        #[param(default = 8)]
+        octaves: i32,
+        #[param(default = 0.5)]
+        roughness: f32,
+    },
+}
+
+impl TextureClass {
+    /// Call `f` with the index of each texture the texture refers to.
+    pub(crate) fn texture_indices_mut(&mut self, f: &mut dyn FnMut(&mut usize)) {
+        let refs: Vec<&mut dyn TextureRefs> = match self {
+            TextureClass::Checkerboard { tex1, tex2, .. }
+            | TextureClass::DirectionMix { tex1, tex2, .. } => vec![tex1, tex2],
+            TextureClass::Dots { inside, outside } => vec![inside, outside],
+            TextureClass::Mix { tex1, tex2, amount } => vec![tex1, tex2, amount],
+            TextureClass::Scale { tex, scale } => vec![tex, scale],
+            _ => Vec::new(),
+        };
+
+        for texture_ref in refs {
+            texture_ref.texture_indices_mut(f);
+        }
+    }
+
+    /// Image file used by the texture, if any.
+    pub fn filename(&self) -> Option<&str> {
+        match self {
+            TextureClass::ImageMap(image) => Some(&image.filename),
+            TextureClass::Ptex { filename, .. } => Some(filename),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn filename_mut(&mut self) -> Option<&mut String> {
+        match self {
+            TextureClass::ImageMap(image) => Some(&mut image.filename),
+            TextureClass::Ptex { filename, .. } => Some(filename),
+            _ => None,
+        }
+    }
+}
+
+/// Parameters of `imagemap` textures.
+#[derive(Debug, Clone, PartialEq, FromParams)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ImageMap {
+    /// Image file.
+    ///
+    /// Resolved against the scene's directory when loading scene files.
+    #[param(required)]
+    pub filename: String,
+    /// Handling of texture coordinates outside of the image.
+    pub wrap: WrapMode,
+    /// Encoding of the pixel values, sRGB for PNG images and linear otherwise by default.
+    #[param(default = ColorEncoding::for_filename(&filename))]
+    pub encoding: ColorEncoding,
+    /// Scale applied to the looked up values.
+    #[param(default = 1.0)]
+    pub scale: f32,
+    /// Use `1 - value` instead of the looked up value.
+    pub invert: bool,
+    /// Filter used for texture lookups.
+    pub filter: ImageFilter,
+    /// Maximum anisotropy of the `ewa` filter.
+    #[param(default = 8.0)]
+    pub max_anisotropy: f32,
+}
+
+/// Handling of image lookups outside of the `[0, 1]` range.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum WrapMode {
+    /// Tile the image.
+    #[default]
+    Repeat,
+    /// Return black outside of the image.
+    Black,
+    /// Extend the edge pixels.
+    Clamp,
+    /// Wrap an equal-area octahedral sphere parameterization.
+    OctahedralSphere,
+}
+
+impl WrapMode {
+    /// Name as used in scene files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            WrapMode::Repeat => "repeat",
+            WrapMode::Black => "black",
+            WrapMode::Clamp => "clamp",
+            WrapMode::OctahedralSphere => "octahedralsphere",
+        }
+    }
+}
+
+impl FromParam for WrapMode {
+    fn from_param(param: &Param) -> Result<Self> {
+        match &*param.string() {
+            "repeat" => Ok(WrapMode::Repeat),
+            "black" => Ok(WrapMode::Black),
+            "clamp" => Ok(WrapMode::Clamp),
+            "octahedralsphere" => Ok(WrapMode::OctahedralSphere),
+            _ => Err(Error::InvalidString),
+        }
+    }
+}
+
+/// Encoding of image pixel values.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ColorEncoding {
+    Linear,
+    Srgb,
+    /// Power curve with the given exponent.
+    Gamma(f32),
+}
+
+impl ColorEncoding {
+    /// Default encoding of an image file: sRGB for PNG images, linear otherwise.
+    pub fn for_filename(filename: &str) -> ColorEncoding {
+        let is_png = Path::new(filename)
+            .extension()
+            .map_or(false, |ext| ext.eq_ignore_ascii_case("png"));
+
+        if is_png {
+            ColorEncoding::Srgb
+        } else {
+            ColorEncoding::Linear
+        }
+    }
+
+    /// Encoding as written in scene files, like `"gamma 2.2"`.
+    pub fn name(&self) -> String {
+        match self {
+            ColorEncoding::Linear => "linear".to_string(),
+            ColorEncoding::Srgb => "sRGB".to_string(),
+            ColorEncoding::Gamma(gamma) => format!("gamma {}", gamma),
+        }
+    }
+}
+
+impl FromParam for ColorEncoding {
+    fn from_param(param: &Param) -> Result<Self> {
+        match &*param.string() {
+            "linear" => Ok(ColorEncoding::Linear),
+            "sRGB" => Ok(ColorEncoding::Srgb),
+            value => value
+                .strip_prefix("gamma ")
+                .and_then(|gamma| gamma.trim().parse().ok())
+                .map(ColorEncoding::Gamma)
+                .ok_or(Error::InvalidString),
+        }
+    }
+}
+
+/// Filter used for image texture lookups.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ImageFilter {
+    /// Nearest pixel.
+    Point,
+    #[default]
+    Bilinear,
+    /// Bilinear filtering between MIP map levels.
+    Trilinear,
+    /// Elliptically weighted average.
+    Ewa,
+}
+
+impl ImageFilter {
+    /// Name as used in scene files.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ImageFilter::Point => "point",
+            ImageFilter::Bilinear => "bilinear",
+            ImageFilter::Trilinear => "trilinear",
+            ImageFilter::Ewa => "ewa",
+        }
+    }
+}
+
+impl FromParam for ImageFilter {
+    fn from_param(param: &Param) -> Result<Self> {
+        match &*param.string() {
+            "point" => Ok(ImageFilter::Point),
+            "bilinear" => Ok(ImageFilter::Bilinear),
+            "trilinear" => Ok(ImageFilter::Trilinear),
+            "ewa" | "EWA" => Ok(ImageFilter::Ewa),
+            _ => Err(Error::InvalidString),
+        }
+    }
+}
+
+/// Mapping of surface points to 2D texture coordinates, selected with the `mapping` parameter.
+#[derive(Debug, Clone, PartialEq, FromParams)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum TextureMapping {
+    /// Scaled and offset (u,v) coordinates of the surface.
+    Uv {
+        #[param(default = 1.0)]
+        u_scale: f32,
+        #[param(default = 1.0)]
+        v_scale: f32,
+        u_delta: f32,
+        v_delta: f32,
+    },
+    /// Spherical coordinates around the texture space origin.
+    Spherical,
+    /// Cylindrical coordinates around the texture space z axis.
+    Cylindrical,
+    /// Projection onto the two vectors `v1` and `v2`.
+    Planar {
+        #[param(default = [1.0, 0.0, 0.0])]
+        v1: [f32; 3],
+        #[param(default = [0.0, 1.0, 0.0])]
+        v2: [f32; 3],
+        u_delta: f32,
+        v_delta: f32,
+    },
+}
+
+impl Default for TextureMapping {
+    fn default() -> Self {
+        TextureMapping::Uv {
+            u_scale: 1.0,
+            v_scale: 1.0,
+            u_delta: 0.0,
+            v_delta: 0.0,
+        }
+    }
+}
+
+/// Index of refraction of a dielectric interface.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Eta {
+    /// Same index of refraction for all wavelengths (`"float eta"`).
+    Constant(f32),
+    /// Wavelength dependent index of refraction (`"spectrum eta"`).
+    Spectrum(Spectrum),
+}
+
+impl Default for Eta {
+    fn default() -> Self {
+        Eta::Constant(1.5)
+    }
+}
+
+impl FromParam for Eta {
+    fn from_param(param: &Param) -> Result<Self> {
+        match param.ty {
+            ParamType::Float => Ok(Eta::Constant(param.single()?)),
+            _ => Ok(Eta::Spectrum(param.spectrum()?)),
+        }
+    }
+}
+
+/// Material specific parameters, see [Material::ty].
+///
+/// Parameters which can be textured are stored as [TexRef], unset optional
+/// parameters are `None` when pbrt derives their value from other parameters.
+#[derive(Debug, Clone, PartialEq, FromParams)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MaterialType {
+    /// Diffuse base layer under a dielectric coating.
+    CoatedDiffuse {
+        /// Reflectance of the diffuse base.
+        #[param(default = TexRef::Value(Spectrum::Rgb([0.5; 3])))]
+        reflectance: TexRef<Spectrum>,
+        /// Roughness of the coating, for both directions.
+        #[param(default = TexRef::Value(0.0))]
+        roughness: TexRef<f32>,
+        /// Roughness of the coating in the u direction.
+        #[param(default = roughness.clone())]
+        u_roughness: TexRef<f32>,
+        /// Roughness of the coating in the v direction.
+        #[param(default = roughness.clone())]
+        v_roughness: TexRef<f32>,
+        /// Thickness of the medium between the layers.
+        #[param(default = TexRef::Value(0.01))]
+        thickness: TexRef<f32>,
+        /// Index of refraction of the coating.
+        eta: Eta,
+        /// Maximum number of scattering events between the layers.
+        #[param(default = 10)]
+        max_depth: i32,
+        /// Number of random walks used to evaluate the BSDF.
+        #[param(default = 1)]
+        n_samples: i32,
+        /// Asymmetry parameter of the medium between the layers.
+        #[param(default = TexRef::Value(0.0))]
+        g: TexRef<f32>,
+        /// Albedo of the medium between the layers.
+        #[param(default = TexRef::Value(Spectrum::Rgb([0.0; 3])))]
+        albedo: TexRef<Spectrum>,
+        /// Whether roughness values are remapped from `[0, 1]` to microfacet alpha values.
+        #[param(default = true)]
+        remap_roughness: bool,
+    },
+    /// Conductor base layer under a dielectric coating.
+    CoatedConductor {
+        /// Roughness of the coating, for both directions.
+        #[param(name = "interface.roughness", default = TexRef::Value(0.0))]
+        interface_roughness: TexRef<f32>,
+        #[param(name = "interface.uroughness", default = interface_roughness.clone())]
+        interface_u_roughness: TexRef<f32>,
+        #[param(name = "interface.vroughness", default = interface_roughness.clone())]
+        interface_v_roughness: TexRef<f32>,
+        /// Thickness of the medium between the layers.
+        #[param(default = TexRef::Value(0.01))]
+        thickness: TexRef<f32>,
+        /// Index of refraction of the coating.
+        #[param(name = "interface.eta")]
+        interface_eta: Eta,
+        /// Roughness of the conductor, for both directions.
+        #[param(name = "conductor.roughness", default = TexRef::Value(0.0))]
+        conductor_roughness: TexRef<f32>,
+        #[param(name = "conductor.uroughness", default = conductor_roughness.clone())]
+        conductor_u_roughness: TexRef<f32>,
+        #[param(name = "conductor.vroughness", default = conductor_roughness.clone())]
+        conductor_v_roughness: TexRef<f32>,
+        /// Real part of the conductor's index of refraction, copper by default.
+        #[param(name = "conductor.eta", default = TexRef::Value(Spectrum::Named("metal-Cu-eta")))]
+        conductor_eta: TexRef<Spectrum>,
+        /// Imaginary part of the conductor's index of refraction.
+        #[param(name = "conductor.k", default = TexRef::Value(Spectrum::Named("metal-Cu-k")))]
+        conductor_k: TexRef<Spectrum>,
+        /// Reflectance of the conductor, used instead of `conductor.eta` and `conductor.k` if set.
+        reflectance: Option<TexRef<Spectrum>>,
+        /// Maximum number of scattering events between the layers.
+        #[param(default = 10)]
+        max_depth: i32,
+        /// Number of random walks used to evaluate the BSDF.
+        #[param(default = 1)]
+        n_samples: i32,
+        /// Asymmetry parameter of the medium between the layers.
+        #[param(default = TexRef::Value(0.0))]
+        g: TexRef<f32>,
+        /// Albedo of the medium between the layers.
+        #[param(default = TexRef::Value(Spectrum::Rgb([0.0; 3])))]
+        albedo: TexRef<Spectrum>,
+        /// Whether roughness values are remapped from `[0, 1]` to microfacet alpha values.
+        #[param(default = true)]
+        remap_roughness: bool,
+    },
+    /// Metal, described by its complex index of refraction or by its reflectance.
+    Conductor {
+        /// Real part of the index of refraction, copper by default.
+        #[param(default = TexRef::Value(Spectrum::Named("metal-Cu-eta")))]
+        eta: TexRef<Spectrum>,
+        /// Imaginary part of the index of refraction.
+        #[param(default = TexRef::Value(Spectrum::Named("metal-Cu-k")))]
+        k: TexRef<Spectrum>,
+        /// Reflectance at normal incidence, used instead of `eta` and `k` if set.
+        reflectance: Option<TexRef<Spectrum>>,
+        /// Microfacet roughness, for both directions.
+        #[param(default = TexRef::Value(0.0))]
+        roughness: TexRef<f32>,
+        #[param(default = roughness.clone())]
+        u_roughness: TexRef<f32>,
+        #[param(default = roughness.clone())]
+        v_roughness: TexRef<f32>,
+        /// Whether roughness values are remapped from `[0, 1]` to microfacet alpha values.
+        #[param(default = true)]
+        remap_roughness: bool,
+    },
+    /// Interface between two dielectrics, like glass or water.
+    Dielectric {
+        /// Index of refraction of the inside of the surface.
+        eta: Eta,
+        /// Microfacet roughness, for both directions.
+        #[param(default = TexRef::Value(0.0))]
+        roughness: TexRef<f32>,
+        #[param(default = roughness.clone())]
+        u_roughness: TexRef<f32>,
+        #[param(default = roughness.clone())]
+        v_roughness: TexRef<f32>,
+        /// Whether roughness values are remapped from `[0, 1]` to microfacet alpha values.
+        #[param(default = true)]
+        remap_roughness: bool,
+    },
+    /// Thin dielectric slab, like a window pane.
+    ThinDielectric {
+        /// Index of refraction of the slab.
+        eta: Eta,
+    },
+    /// Lambertian reflection.
+    Diffuse {
+        #[param(default = TexRef::Value(Spectrum::Rgb([0.5; 3])))]
+        reflectance: TexRef<Spectrum>,
+    },
+    /// Lambertian reflection and transmission.
+    DiffuseTransmission {
+        #[param(default = TexRef::Value(Spectrum::Rgb([0.25; 3])))]
+        reflectance: TexRef<Spectrum>,
+        #[param(default = TexRef::Value(Spectrum::Rgb([0.25; 3])))]
+        transmittance: TexRef<Spectrum>,
+        /// Scale factor applied to both `reflectance` and `transmittance`.
+        #[param(default = 1.0)]
+        scale: f32,
+    },
+    /// Scattering from hair fibers.
+    ///
+    /// The color is given by one of `sigma_a`, `color` or the melanin concentrations.
+    /// If none is set, pbrt uses an `eumelanin` concentration of 1.3.
+    Hair {
+        /// Absorption coefficient inside the hair.
+        #[param(name = "sigma_a")]
+        sigma_a: Option<TexRef<Spectrum>>,
+        /// Hair color, the absorption coefficient is derived from it.
+        color: Option<TexRef<Spectrum>>,
+        /// Concentration of the pigment which makes hair brown or black.
+        eumelanin: Option<TexRef<f32>>,
+        /// Concentration of the pigment which makes hair red or blond.
+        pheomelanin: Option<TexRef<f32>>,
+        /// Index of refraction of the hair interior.
+        #[param(default = TexRef::Value(1.55))]
+        eta: TexRef<f32>,
+        /// Longitudinal roughness.
+        #[param(name = "beta_m", default = TexRef::Value(0.3))]
+        beta_m: TexRef<f32>,
+        /// Azimuthal roughness.
+        #[param(name = "beta_n", default = TexRef::Value(0.3))]
+        beta_n: TexRef<f32>,
+        /// Angle of the scales on the hair surface, in degrees.
+        #[param(default = TexRef::Value(2.0))]
+        alpha: TexRef<f32>,
+    },
+    /// Measured BSDF, read from a file.
+    Measured {
+        /// File with the measured data.
+        #[param(required)]
+        filename: String,
+    },
+    /// Stochastic selection between two named materials.
+    Mix {
+        /// Names of the materials.
+        #[param(required)]
+        materials: [String; 2],
+        /// Probability of choosing the second material.
+        #[param(default = TexRef::Value(0.5))]
+        amount: TexRef<f32>,
+    },
+    /// Dielectric interface above a scattering medium.
+    ///
+    /// The medium is given by one of `name`, `reflectance` or `sigma_a` and `sigma_s`.
+    /// If none is set, pbrt uses the coefficients of skin-like scattering.
+    Subsurface {
+        /// Index of refraction of the interface.
+        #[param(default = 1.33)]
+        eta: f32,
+        /// Asymmetry parameter of the medium.
+        g: f32,
+        /// Mean free path of the medium, used with `reflectance`.
+        mfp: Option<TexRef<Spectrum>>,
+        /// Diffuse reflectance, the scattering coefficients are derived from it.
+        reflectance: Option<TexRef<Spectrum>>,
+        /// Absorption coefficient of the medium.
+        #[param(name = "sigma_a")]
+        sigma_a: Option<TexRef<Spectrum>>,
+        /// Scattering coefficient of the medium.
+        #[param(name = "sigma_s")]
+        sigma_s: Option<TexRef<Spectrum>>,
+        /// Scale factor applied to `sigma_a` and `sigma_s`.
+        #[param(default = 1.0)]
+        scale: f32,
+        /// Name of measured scattering coefficients, like `"Skin1"`.
+        name: Option<String>,
+        /// Microfacet roughness of the interface, for both directions.
+        #[param(default = TexRef::Value(0.0))]
+        roughness: TexRef<f32>,
+        #[param(default = roughness.clone())]
+        u_roughness: TexRef<f32>,
+        #[param(default = roughness.clone())]
+        v_roughness: TexRef<f32>,
+        /// Whether roughness values are remapped from `[0, 1]` to microfacet alpha values.
+        #[param(default = true)]
+        remap_roughness: bool,
+    },
+    /// Invisible surface, only marks a boundary between two media.
+    Interface,
+}
+
+impl MaterialType {
+    /// Call `f` with the index of each texture the material refers to.
+    pub(crate) fn texture_indices_mut(&mut self, f: &mut dyn FnMut(&mut usize)) {
+        let refs: Vec<&mut dyn TextureRefs> = match self {
+            MaterialType::CoatedDiffuse {
+                reflectance,
+                roughness,
+                u_roughness,
+                v_roughness,
+                thickness,
+                g,
+                albedo,
+                ..
+            } => vec![
+                reflectance,
+                roughness,
+                u_roughness,
+                v_roughness,
+                thickness,
+                g,
+                albedo,
+            ],
+            MaterialType::CoatedConductor {
+                interface_roughness,
+                interface_u_roughness,
+                interface_v_roughness,
+                thickness,
+                conductor_roughness,
+                conductor_u_roughness,
+                conductor_v_roughness,
+                conductor_eta,
+                conductor_k,
+                reflectance,
+                g,
+                albedo,
+                ..
+            } => vec![
+                interface_roughness,
+                interface_u_roughness,
+                interface_v_roughness,
+                thickness,
+                conductor_roughness,
+                conductor_u_roughness,
+                conductor_v_roughness,
+                conductor_eta,
+                conductor_k,
+                reflectance,
+                g,
+                albedo,
+            ],
+            MaterialType::Conductor {
+                eta,
+                k,
+                reflectance,
+                roughness,
+                u_roughness,
+                v_roughness,
+                ..
+            } => vec![eta, k, reflectance, roughness, u_roughness, v_roughness],
+            MaterialType::Dielectric {
+                roughness,
+                u_roughness,
+                v_roughness,
+                ..
+            } => vec![roughness, u_roughness, v_roughness],
+            MaterialType::Diffuse { reflectance } => vec![reflectance],
+            MaterialType::DiffuseTransmission {
+                reflectance,
+                transmittance,
+                ..
+            } => vec![reflectance, transmittance],
+            MaterialType::Hair {
+                sigma_a,
+                color,
+                eumelanin,
+                pheomelanin,
+                eta,
+                beta_m,
+                beta_n,
+                alpha,
+            } => vec![
+                sigma_a,
+                color,
+                eumelanin,
+                pheomelanin,
+                eta,
+                beta_m,
+                beta_n,
+                alpha,
+            ],
+            MaterialType::Mix { amount, .. } => vec![amount],
+            MaterialType::Subsurface {
+                mfp,
+                reflectance,
+                sigma_a,
+                sigma_s,
+                roughness,
+                u_roughness,
+                v_roughness,
+                ..
+            } => vec![
+                mfp,
+                reflectance,
+                sigma_a,
+                sigma_s,
+                roughness,
+                u_roughness,
+                v_roughness,
+            ],
+            MaterialType::ThinDielectric { .. }
+            | MaterialType::Measured { .. }
+            | MaterialType::Interface => Vec::new(),
+        };
+
+        for texture_ref in refs {
+            texture_ref.texture_indices_mut(f);
+        }
+    }
+}
+
+impl Default for MaterialType {
+    fn default() -> Self {
+        MaterialType::Diffuse {
+            reflectance: TexRef::Value(Spectrum::Rgb([0.5; 3])),
+        }
+    }
+}
+
+/// Materials specify the light scattering properties of surfaces in the scene.
+///
+/// Parameters shared by all materials are stored here,
+/// parameters specific to the material type are stored in [Material::ty].
+#[derive(Debug, Default, FromParams)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Material {
+    /// Material name, if defined with `MakeNamedMaterial`.
+    #[param(skip)]
+    pub name: Option<String>,
+    /// Float texture which offsets the surface along its normal.
+    pub displacement: Option<TexRef<f32>>,
+    /// Image with tangent space shading normals.
+    pub normal_map: Option<String>,
+    /// Material type.
+    #[param(skip)]
+    pub ty: MaterialType,
+    /// Color space active when the material was declared.
+    #[param(skip)]
+    pub color_space: ColorSpace,
+}
+
+impl Material {
+    /// Read a material of type `ty`.
+    ///
+    /// Parameters to materials are distinctive in that textures can be used to
+    /// specify spatially-varying values for the parameters. Texture references are
+    /// resolved with `texture_map` (texture names to indices in [crate::Scene::textures]),
+    /// references to undefined textures are ignored.
+    pub fn new(
+        ty: &str,
+        mut params: ParamList,
+        texture_map: &HashMap<String, usize>,
+    ) -> Result<Material> {
+        params.resolve_textures(texture_map);
+
+        Ok(Material {
+            ty: MaterialType::from_typed_params(ty, &params)?,
+            ..params.extract()?
+        })
+    }
+
+    /// Call `f` with the index of each texture the material refers to, including the displacement.
+    pub(crate) fn texture_indices_mut(&mut self, f: &mut dyn FnMut(&mut usize)) {
+        self.displacement.texture_indices_mut(f);
+        self.ty.texture_indices_mut(f);
+    }
+}
+
+/// Polynomial basis of [Shape::Curve] segments.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CurveBasis {
+    /// Segments share their end points.
+    #[default]
+    Bezier,
+    /// Segments share all but one control points.
+    BSpline,
+}
+
+impl FromParam for CurveBasis {
+    fn from_param(param: &Param) -> Result<Self> {
+        match &*param.string() {
+            "bezier" => Ok(CurveBasis::Bezier),
+            "bspline" => Ok(CurveBasis::BSpline),
+            _ => Err(Error::InvalidString),
+        }
+    }
+}
+
+/// Surface representation of a [Shape::Curve].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum CurveType {
+    /// A flat ribbon always facing the incoming ray.
+    #[default]
+    Flat,
+    /// A ribbon with shading normals that make it look like a cylinder.
+    Cylinder,
+    /// A ribbon oriented by the curve's normals.
+    Ribbon,
+}
+
+impl FromParam for CurveType {
+    fn from_param(param: &Param) -> Result<Self> {
+        match &*param.string() {
+            "flat" => Ok(CurveType::Flat),
+            "cylinder" => Ok(CurveType::Cylinder),
+            "ribbon" => Ok(CurveType::Ribbon),
+            _ => Err(Error::InvalidString),
+        }
+    }
+}
+
+#[derive(Debug, Clone, FromParams)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Shape {
+    // All shapes take an optional "alpha" parameter that can be
+    // used to define a mask that cuts away regions of a surface.
+    /// The "cylinder" is always oriented along the z axis.
+    Cylinder {
+        #[param(default = 1.0)]
+        alpha: f32,
+        /// The cylinder's radius.
+        #[param(default = 1.0)]
+        radius: f32,
+        /// The height of the cylinder's bottom along the z axis.
+        #[param(default = -1.0)]
+        zmin: f32,
+        /// The height of the cylinder's top along the z axis.
+        #[param(default = 1.0)]
+        zmax: f32,
+        /// The maximum extent of the cylinder in phi (in spherical coordinates).
+        #[param(default = 360.0)]
+        phimax: f32,
+    },
+    /// The "disk" is perpendicular to the z axis in the xy plane, with its object space center at x=0 and y=0.
+    Disk {
+        #[param(default = 1.0)]
+        alpha: f32,
+        /// The position of the disk along the z axis.
+        height: f32,
+        /// The outer radius of the disk.
+        #[param(default = 1.0)]
+        radius: f32,
+        /// The inner radius of the disk (if nonzero, the disk is an annulus).
+        innerradius: f32,
+        /// The maximum extent of the disk in phi (in spherical coordinates).
+        #[param(default = 360.0)]
+        phimax: f32,
+    },
+    /// Spheres are always at the origin in object space.
+    Sphere {
+        #[param(default = 1.0)]
+        alpha: f32,
+        /// The sphere's radius.
+        #[param(default = 1.0)]
+        radius: f32,
+        /// The height of the lower clipping plane along the z axis.
+        #[param(default = -radius)]
+        zmin: f32,
+        /// The height of the upper clipping plane along the z axis.
+        #[param(default = radius)]
+        zmax: f32,
+        /// The maximum extent of the sphere in phi (in spherical coordinates).
+        #[param(default = 360.0)]
+        phimax: f32,
+    },
+    /// A triangle mesh is defined by the "trianglemesh" shape.
+    TriangleMesh {
+        #[param(default = 1.0)]
+        alpha: f32,
+        /// The mesh's topology is defined by the `indices` parameter,
+        /// which is an array of integer indices into the vertex arrays.
+        // TODO: Positions and indices are required, return error if not provided.
+        indices: Vec<i32>,
         /// Each successive triplet of indices defines the offsets to
         /// the three vertices of one triangle; thus, the length of the
         /// indices array must be a multiple of three.
-        positions: Vec<f32>,
+        #[param(name = "P")]
+        positions: Vec<Float>,
         /// Per-vertex normals.
+        #[param(name = "N")]
         normals: Vec<f32>,
         /// Per-vertex tangents.
+        #[param(name = "S")]
         tangents: Vec<f32>,
         /// Per-vertex texture coordinates.
+        #[param(name = "uv")]
+        uvs: Vec<f32>,
+        /// Per-triangle face indices, available to textures.
+        #[param(name = "faceIndices")]
+        face_indices: Vec<i32>,
+    },
+    /// Loop subdivision surface, the triangle mesh is the control mesh of the surface.
+    ///
+    /// Use [Shape::tessellate] to get the subdivided triangle mesh.
+    LoopSubdiv {
+        /// Number of subdivision levels.
+        #[param(default = 3)]
+        levels: i32,
+        /// Vertex indices, 3 per triangle.
+        indices: Vec<i32>,
+        /// Vertex positions.
+        #[param(name = "P")]
+        positions: Vec<Float>,
+    },
+    /// A mesh of bilinear patches, the `"bilinearmesh"` shape.
+    ///
+    /// Each patch has 4 vertices `p00`, `p10`, `p01` and `p11`, which are not necessarily coplanar.
+    BilinearMesh {
+        #[param(default = 1.0)]
+        alpha: f32,
+        /// Vertex indices, 4 per patch. May be omitted for a single patch of 4 vertices.
+        indices: Vec<i32>,
+        /// Vertex positions.
+        #[param(name = "P")]
+        positions: Vec<Float>,
+        /// Per-vertex normals.
+        #[param(name = "N")]
+        normals: Vec<f32>,
+        /// Per-vertex texture coordinates.
+        #[param(name = "uv")]
         uvs: Vec<f32>,
+        /// Per-patch face indices, available to textures.
+        #[param(name = "faceIndices")]
+        face_indices: Vec<i32>,
+        /// Image used to sample points on an emissive mesh proportionally to its emission.
+        emission_filename: Option<String>,
+    },
+    /// Thin curves defined by cubic (or quadratic) polynomial segments, mostly used for hair and fur.
+    Curve {
+        #[param(default = 1.0)]
+        alpha: f32,
+        /// Control points, `degree + 1` for a single Bézier segment, each following segment
+        /// adds `degree` (Bézier) or 1 (B-spline) points.
+        #[param(name = "P")]
+        positions: Vec<Float>,
+        /// Curve segments are either Bézier curves or B-splines.
+        basis: CurveBasis,
+        /// Polynomial degree, 2 or 3.
+        #[param(default = 3)]
+        degree: i32,
+        /// How the curve's surface is shaded.
+        #[param(name = "type")]
+        ty: CurveType,
+        /// Normals at the start and end of each segment, for [CurveType::Ribbon] curves.
+        #[param(name = "N")]
+        normals: Vec<f32>,
+        /// Width of the curve.
+        #[param(default = 1.0)]
+        width: f32,
+        /// Width at the start of the curve.
+        #[param(default = width)]
+        width0: f32,
+        /// Width at the end of the curve.
+        #[param(default = width)]
+        width1: f32,
+        /// Number of times each segment is split for intersection, affects accuracy.
+        #[param(default = 3)]
+        split_depth: i32,
     },
     /// pbrt can also directly read triangle meshes specified in the PLY mesh file format, via the "plymesh" shape.
-    /// TODO: Support loading ply files.
-    PlyMesh { filename: String },
+    PlyMesh {
+        /// Path to the PLY file, as specified in the scene file.
+        #[param(required)]
+        filename: String,
+        /// Loaded mesh data, if the scene was loaded with a [crate::cache::GeometryCache].
+        #[cfg(feature = "ply")]
+        #[param(skip)]
+        mesh: Option<Arc<PlyMesh>>,
+    },
 }
 
 impl Shape {
     pub fn new(ty: &str, params: ParamList) -> Result<Self> {
-        // All shapes take an optional "alpha" parameter that can be
-        // used to define a mask that cuts away regions of a surface.
-        let alpha = params.float("alpha", 1.0)?;
+        Self::from_typed_params(ty, &params)
+    }
+
+    /// Get the mesh of a `"plymesh"` shape, reading the PLY file if it was not loaded with the scene.
+    ///
+    /// Relative file names are resolved against `working_directory` (the scene file's directory).
+    /// Returns `None` for other shape types.
+    #[cfg(feature = "ply")]
+    pub fn load_mesh(&self, working_directory: Option<&Path>) -> Result<Option<Arc<PlyMesh>>> {
+        let Shape::PlyMesh { filename, mesh } = self else {
+            return Ok(None);
+        };
+
+        if let Some(mesh) = mesh {
+            return Ok(Some(Arc::clone(mesh)));
+        }
+
+        let path = resolve_path(working_directory, filename)?;
+        Ok(Some(Arc::new(PlyMesh::from_file(path)?)))
+    }
 
-        let shape = match ty {
-            "cylinder" => Shape::Cylinder {
+    /// Compute the shape's bounds in object space.
+    ///
+    /// Returns `None` if shape's geometry is not available (e.g. a PLY mesh which was not loaded).
+    pub fn bounds(&self) -> Option<Bounds3> {
+        // Bounds of analytic shapes around z axis.
+        let around_z = |radius: f32, zmin: f32, zmax: f32| {
+            let (radius, zmin, zmax) = (float(radius), float(zmin), float(zmax));
+            Bounds3::new(
+                Vec3::new(-radius, -radius, zmin),
+                Vec3::new(radius, radius, zmax),
+            )
+        };
+
+        let bounds = match self {
+            Shape::Cylinder {
+                radius, zmin, zmax, ..
+            } => around_z(*radius, *zmin, *zmax),
+            Shape::Disk { height, radius, .. } => around_z(*radius, *height, *height),
+            Shape::Sphere {
+                radius, zmin, zmax, ..
+            } => around_z(*radius, *zmin, *zmax),
+            // Subdivision surfaces are within the convex hull of the control mesh.
+            Shape::TriangleMesh { positions, .. }
+            | Shape::LoopSubdiv { positions, .. }
+            | Shape::BilinearMesh { positions, .. } => Bounds3::from_points(positions),
+            // The curve is within the convex hull of its control points.
+            Shape::Curve {
+                positions,
+                width0,
+                width1,
+                ..
+            } => {
+                let bounds = Bounds3::from_points(positions);
+                let radius = Vec3::ONE * float(width0.max(*width1) / 2.0);
+                Bounds3::new(bounds.min - radius, bounds.max + radius)
+            }
+            #[cfg(feature = "ply")]
+            Shape::PlyMesh {
+                mesh: Some(mesh), ..
+            } => Bounds3::from_points(&mesh.positions),
+            Shape::PlyMesh { .. } => return None,
+        };
+
+        Some(bounds)
+    }
+
+    /// Approximate an analytic shape, a subdivision surface or a bilinear patch mesh
+    /// with a `"trianglemesh"`.
+    ///
+    /// `segments` is the number of subdivisions around the z axis (clamped to at least 3),
+    /// subdivision surfaces are subdivided `levels` times.
+    /// Returns `None` for shapes which are already triangle meshes, for curves and for
+    /// subdivision surfaces with invalid indices.
+    pub fn tessellate(&self, segments: u32) -> Option<Shape> {
+        let segments = segments.max(3);
+
+        let mesh = match *self {
+            Shape::Cylinder {
                 alpha,
-                radius: params.float("radius", 1.0)?,
-                zmin: params.float("zmin", -1.0)?,
-                zmax: params.float("zmax", 1.0)?,
-                phimax: params.float("phimax", 360.0)?,
-            },
-            "disk" => Shape::Disk {
+                radius,
+                zmin,
+                zmax,
+                phimax,
+            } => grid(alpha, segments, 1, |u, v| {
+                let phi = u * phimax.to_radians();
+                let (sin, cos) = phi.sin_cos();
+                let z = zmin + v * (zmax - zmin);
+
+                ([radius * cos, radius * sin, z], [cos, sin, 0.0])
+            }),
+            Shape::Disk {
                 alpha,
-                height: params.float("height", 0.0)?,
-                radius: params.float("radius", 1.0)?,
-                innerradius: params.float("innerradius", 0.0)?,
-                phimax: params.float("phimax", 360.0)?,
-            },
-            "sphere" => {
-                let radius = params.float("radius", 1.0)?;
-
-                let zmin = params.float("zmin", -radius)?;
-                let zmax = params.float("zmax", radius)?;
-
-                Shape::Sphere {
-                    alpha,
-                    radius,
-                    zmin,
-                    zmax,
-                    phimax: params.float("phimax", 360.0)?,
-                }
-            }
-            "trianglemesh" => {
-                // TODO: Positions and indices are required, return error if not provided.
-                let indices = params.integers("indices")?.unwrap_or_default();
-                debug_assert_eq!(indices.len() % 3, 0);
+                height,
+                radius,
+                innerradius,
+                phimax,
+            } => grid(alpha, segments, 1, |u, v| {
+                let phi = u * phimax.to_radians();
+                let (sin, cos) = phi.sin_cos();
+                // Go from the outer to the inner radius, so triangles face +z.
+                let r = radius + v * (innerradius - radius);
 
-                let positions = params.floats("P")?.unwrap_or_default();
+                ([r * cos, r * sin, height], [0.0, 0.0, 1.0])
+            }),
+            Shape::Sphere {
+                alpha,
+                radius,
+                zmin,
+                zmax,
+                phimax,
+            } => {
+                let theta_min = (zmin / radius).clamp(-1.0, 1.0).acos();
+                let theta_max = (zmax / radius).clamp(-1.0, 1.0).acos();
 
-                let normals = params.floats("N")?.unwrap_or_default();
-                let tangents = params.floats("S")?.unwrap_or_default();
+                grid(alpha, segments, (segments / 2).max(1), |u, v| {
+                    let phi = u * phimax.to_radians();
+                    let theta = theta_min + v * (theta_max - theta_min);
+                    let (sin_phi, cos_phi) = phi.sin_cos();
+                    let (sin_theta, cos_theta) = theta.sin_cos();
 
-                let uvs = params.floats("uv")?.unwrap_or_default();
+                    let n = [sin_theta * cos_phi, sin_theta * sin_phi, cos_theta];
+                    ([radius * n[0], radius * n[1], radius * n[2]], n)
+                })
+            }
+            Shape::LoopSubdiv {
+                levels,
+                ref indices,
+                ref positions,
+            } => {
+                let mesh = loop_subdivide(levels.max(0) as u32, indices, positions)?;
 
                 Shape::TriangleMesh {
-                    alpha,
-                    indices,
-                    positions,
-                    normals,
-                    uvs,
-                    tangents,
+                    alpha: 1.0,
+                    indices: mesh.indices,
+                    positions: mesh.positions,
+                    normals: mesh.normals,
+                    tangents: Vec::new(),
+                    uvs: Vec::new(),
+                    face_indices: Vec::new(),
                 }
             }
-            "plymesh" => {
-                let filename = params
-                    .string("filename")
-                    .ok_or(Error::MissingRequiredParameter)?
-                    .to_string();
+            // Patches are split in two triangles, which is exact for planar patches only.
+            Shape::BilinearMesh {
+                alpha,
+                ref indices,
+                ref positions,
+                ref normals,
+                ref uvs,
+                ref face_indices,
+                ..
+            } => {
+                let single = [0, 1, 2, 3];
+                let indices = if indices.is_empty() && positions.len() == 12 {
+                    &single[..]
+                } else {
+                    indices
+                };
 
-                Shape::PlyMesh { filename }
+                Shape::TriangleMesh {
+                    alpha,
+                    indices: indices
+                        .chunks_exact(4)
+                        .flat_map(|p| [p[0], p[1], p[3], p[0], p[3], p[2]])
+                        .collect(),
+                    positions: positions.clone(),
+                    normals: normals.clone(),
+                    tangents: Vec::new(),
+                    uvs: uvs.clone(),
+                    // Both triangles of a patch keep its face index.
+                    face_indices: face_indices.iter().flat_map(|f| [*f, *f]).collect(),
+                }
+            }
+            Shape::TriangleMesh { .. } | Shape::Curve { .. } | Shape::PlyMesh { .. } => {
+                return None
             }
-            _ => return Err(Error::InvalidObjectType),
         };
 
-        Ok(shape)
+        Some(mesh)
     }
 }
 
-#[derive(Debug, Default)]
-pub struct Medium {}
+/// Build a `"trianglemesh"` from a parametric `(u, v)` surface sampled on a `nu` x `nv` grid.
+///
+/// `f` returns position and normal for `u` and `v` in `[0, 1]`.
+fn grid(alpha: f32, nu: u32, nv: u32, f: impl Fn(f32, f32) -> ([f32; 3], [f32; 3])) -> Shape {
+    let mut positions = Vec::new();
+    let mut normals = Vec::new();
+    let mut uvs = Vec::new();
 
-impl Medium {
-    pub fn new(_params: ParamList) -> Result<Self> {
-        // TODO: Handle medium object initialization.
-        Ok(Medium {})
+    for j in 0..=nv {
+        for i in 0..=nu {
+            let (u, v) = (i as f32 / nu as f32, j as f32 / nv as f32);
+            let (p, n) = f(u, v);
+
+            positions.extend(p.map(float));
+            normals.extend(n);
+            uvs.extend([u, v]);
+        }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    let mut indices = Vec::new();
+    let stride = nu as i32 + 1;
 
-    #[test]
-    fn parse_coord_sys() {
-        assert_eq!(
-            CoordinateSystem::from_str("cameraworld").unwrap(),
-            CoordinateSystem::CameraWorld
-        );
-        assert_eq!(
-            CoordinateSystem::from_str("camera").unwrap(),
-            CoordinateSystem::Camera
-        );
-        assert_eq!(
-            CoordinateSystem::from_str("world").unwrap(),
-            CoordinateSystem::World
-        );
+    for j in 0..nv as i32 {
+        for i in 0..nu as i32 {
+            let a = j * stride + i;
+            let b = a + 1;
+            let c = a + stride + 1;
+            let d = a + stride;
 
-        assert!(CoordinateSystem::from_str("").is_err());
-        assert!(CoordinateSystem::from_str("foo").is_err());
+            indices.extend([a, b, c, a, c, d]);
+        }
+    }
+
+    Shape::TriangleMesh {
+        alpha,
+        indices,
+        positions,
+        normals,
+        tangents: Vec::new(),
+        uvs,
+        face_indices: Vec::new(),
+    }
+}
+
+/// Participating medium type and its parameters, selected with `"string type"`.
+///
+/// Scattering coefficients are per unit distance in medium space and multiplied by `scale`,
+/// `g` is the asymmetry parameter of the Henyey-Greenstein phase function.
+#[derive(Debug, Clone, PartialEq, FromParams)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MediumType {
+    /// Medium with constant properties.
+    Homogeneous {
+        /// Absorption coefficient.
+        #[param(name = "sigma_a", default = Spectrum::Rgb([1.0; 3]))]
+        sigma_a: Spectrum,
+        /// Scattering coefficient.
+        #[param(name = "sigma_s", default = Spectrum::Rgb([1.0; 3]))]
+        sigma_s: Spectrum,
+        #[param(default = 1.0)]
+        scale: f32,
+        g: f32,
+        /// Emitted radiance.
+        #[param(name = "Le", default = Spectrum::Rgb([0.0; 3]))]
+        le: Spectrum,
+        #[param(name = "Lescale", default = 1.0)]
+        le_scale: f32,
+        /// Name of measured scattering properties overriding `sigma_a` and `sigma_s`.
+        preset: Option<String>,
+    },
+    /// Density sampled on a regular grid over the `p0` - `p1` box.
+    UniformGrid {
+        #[param(name = "sigma_a", default = Spectrum::Rgb([1.0; 3]))]
+        sigma_a: Spectrum,
+        #[param(name = "sigma_s", default = Spectrum::Rgb([1.0; 3]))]
+        sigma_s: Spectrum,
+        #[param(default = 1.0)]
+        scale: f32,
+        g: f32,
+        /// `nx * ny * nz` density values, x varies fastest.
+        density: Vec<f32>,
+        /// Optional `nx * ny * nz` temperatures (in Kelvin) for blackbody emission.
+        temperature: Vec<f32>,
+        temperature_cutoff: f32,
+        #[param(default = 1.0)]
+        temperature_scale: f32,
+        /// Emitted radiance, scaled by the density (used without `temperature`).
+        #[param(name = "Le", default = Spectrum::Rgb([0.0; 3]))]
+        le: Spectrum,
+        #[param(name = "Lescale", default = 1.0)]
+        le_scale: f32,
+        #[param(default = 1)]
+        nx: i32,
+        #[param(default = 1)]
+        ny: i32,
+        #[param(default = 1)]
+        nz: i32,
+        /// Grid bounds in medium space.
+        p0: [Float; 3],
+        #[param(default = [1.0; 3])]
+        p1: [Float; 3],
+        preset: Option<String>,
+    },
+    /// RGB scattering coefficients and emission sampled on a regular grid.
+    RgbGrid {
+        /// `nx * ny * nz` RGB triples, x varies fastest.
+        #[param(name = "sigma_a")]
+        sigma_a: Vec<f32>,
+        #[param(name = "sigma_s")]
+        sigma_s: Vec<f32>,
+        #[param(default = 1.0)]
+        scale: f32,
+        g: f32,
+        #[param(name = "Le")]
+        le: Vec<f32>,
+        #[param(name = "Lescale", default = 1.0)]
+        le_scale: f32,
+        #[param(default = 1)]
+        nx: i32,
+        #[param(default = 1)]
+        ny: i32,
+        #[param(default = 1)]
+        nz: i32,
+        p0: [Float; 3],
+        #[param(default = [1.0; 3])]
+        p1: [Float; 3],
+    },
+    /// Procedural cloud density over the `p0` - `p1` box.
+    Cloud {
+        #[param(name = "sigma_a", default = Spectrum::Rgb([1.0; 3]))]
+        sigma_a: Spectrum,
+        #[param(name = "sigma_s", default = Spectrum::Rgb([1.0; 3]))]
+        sigma_s: Spectrum,
+        #[param(default = 1.0)]
+        scale: f32,
+        g: f32,
+        #[param(default = 1.0)]
+        density: f32,
+        #[param(default = 1.0)]
+        wispiness: f32,
+        #[param(default = 5.0)]
+        frequency: f32,
+        p0: [Float; 3],
+        #[param(default = [1.0; 3])]
+        p1: [Float; 3],
+    },
+    /// Density (and optionally temperature) grids read from a NanoVDB file.
+    NanoVdb {
+        /// Resolved against the scene's directory when loading scene files.
+        #[param(required)]
+        filename: String,
+        #[param(name = "sigma_a", default = Spectrum::Rgb([1.0; 3]))]
+        sigma_a: Spectrum,
+        #[param(name = "sigma_s", default = Spectrum::Rgb([1.0; 3]))]
+        sigma_s: Spectrum,
+        #[param(default = 1.0)]
+        scale: f32,
+        g: f32,
+        #[param(name = "Lescale", default = 1.0)]
+        le_scale: f32,
+        temperature_cutoff: f32,
+        #[param(default = 1.0)]
+        temperature_scale: f32,
+        preset: Option<String>,
+    },
+}
+
+/// A participating medium defined with `MakeNamedMedium`.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Medium {
+    pub name: String,
+    /// Medium type and its parameters.
+    pub ty: MediumType,
+    /// Medium to world transformation (the CTM at `MakeNamedMedium`), positions grid media
+    /// like `uniformgrid` and `nanovdb`.
+    pub transform: Mat4,
+    /// Grid metadata of a `"nanovdb"` medium, if the scene was loaded with
+    /// a [crate::cache::GeometryCache].
+    #[cfg(feature = "nanovdb")]
+    pub grids: Option<Arc<NanoVdbFile>>,
+}
+
+impl Medium {
+    /// Read a medium of type `ty` (the `"string type"` parameter of `MakeNamedMedium`).
+    pub fn new(name: &str, ty: &str, params: ParamList) -> Result<Medium> {
+        Ok(Medium {
+            name: name.to_string(),
+            ty: MediumType::from_typed_params(ty, &params)?,
+            transform: Mat4::IDENTITY,
+            #[cfg(feature = "nanovdb")]
+            grids: None,
+        })
+    }
+
+    /// File used by the medium, if any.
+    pub fn filename(&self) -> Option<&str> {
+        match &self.ty {
+            MediumType::NanoVdb { filename, .. } => Some(filename),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn filename_mut(&mut self) -> Option<&mut String> {
+        match &mut self.ty {
+            MediumType::NanoVdb { filename, .. } => Some(filename),
+            _ => None,
+        }
+    }
+
+    /// Read the grid metadata of a `"nanovdb"` medium, see [NanoVdbFile].
+    ///
+    /// Fails if the file has no `density` grid, or if `density` or `temperature` grids
+    /// don't store floats, like pbrt does. Relative file names are resolved against
+    /// `working_directory` (the scene file's directory). Returns `None` for other media.
+    #[cfg(feature = "nanovdb")]
+    pub fn load_grids(&self, working_directory: Option<&Path>) -> Result<Option<NanoVdbFile>> {
+        let Some(filename) = self.filename() else {
+            return Ok(None);
+        };
+
+        let file = NanoVdbFile::from_file(resolve_path(working_directory, filename)?)?;
+        check_grids(filename, &file)?;
+
+        Ok(Some(file))
+    }
+}
+
+/// Check that a `"nanovdb"` medium file has grids pbrt can use.
+#[cfg(feature = "nanovdb")]
+pub(crate) fn check_grids(filename: &str, file: &NanoVdbFile) -> Result<()> {
+    if file.grid("density").is_none() {
+        return Err(Error::NanoVdb(format!(
+            "{} has no \"density\" grid",
+            filename
+        )));
+    }
+
+    for grid in ["density", "temperature"]
+        .iter()
+        .filter_map(|&name| file.grid(name))
+    {
+        if grid.grid_type != GridType::Float {
+            return Err(Error::NanoVdb(format!(
+                "\"{}\" grid stores {:?} values, expected Float",
+                grid.name, grid.grid_type
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_coord_sys() {
+        assert_eq!(
+            CoordinateSystem::from_str("cameraworld").unwrap(),
+            CoordinateSystem::CameraWorld
+        );
+        assert_eq!(
+            CoordinateSystem::from_str("camera").unwrap(),
+            CoordinateSystem::Camera
+        );
+        assert_eq!(
+            CoordinateSystem::from_str("world").unwrap(),
+            CoordinateSystem::World
+        );
+
+        assert!(CoordinateSystem::from_str("").is_err());
+        assert!(CoordinateSystem::from_str("foo").is_err());
+    }
+
+    #[test]
+    fn parse_color_space() {
+        for color_space in [
+            ColorSpace::Srgb,
+            ColorSpace::Rec2020,
+            ColorSpace::Aces2065_1,
+            ColorSpace::DciP3,
+        ] {
+            assert_eq!(
+                ColorSpace::from_str(color_space.name()).unwrap(),
+                color_space
+            );
+        }
+
+        assert!(ColorSpace::from_str("acescg").is_err());
+    }
+
+    #[test]
+    fn filter() -> Result<()> {
+        let mut parser =
+            crate::Parser::new(r#"PixelFilter "mitchell" "float xradius" 1.5 "float B" 0.5"#);
+
+        let crate::Element::PixelFilter { ty, params } = parser.parse_next()? else {
+            panic!("Expected PixelFilter");
+        };
+
+        let filter = Filter::new(&ty, params)?;
+        assert_eq!(
+            filter,
+            Filter::Mitchell {
+                x_radius: 1.5,
+                y_radius: 2.0,
+                b: 0.5,
+                c: 1.0 / 3.0,
+            }
+        );
+        assert_eq!(filter.radius(), [1.5, 2.0]);
+
+        assert_eq!(
+            Filter::new("box", ParamList::default())?.radius(),
+            [0.5, 0.5]
+        );
+        assert!(matches!(
+            Filter::new("lanczos", ParamList::default()),
+            Err(Error::InvalidObjectType)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn cameras() -> Result<()> {
+        let camera = |data: &str| -> Result<Camera> {
+            let crate::Element::Camera { ty, params } = crate::Parser::new(data).parse_next()?
+            else {
+                panic!("Expected Camera");
+            };
+
+            Camera::new(&ty, params)
+        };
+
+        // From pbrt-v4-scenes.
+        assert!(matches!(
+            camera(r#"Camera "orthographic" "float screenwindow" [ -2 2 -1.5 1.5 ]"#)?,
+            Camera::Orthographic {
+                screen_window: Some([-2.0, 2.0, -1.5, 1.5]),
+                lens_radius,
+                ..
+            } if lens_radius == 0.0
+        ));
+        assert!(matches!(
+            camera(r#"Camera "perspective" "float fov" 40 "float lensradius" 0.1 "float focaldistance" 5"#)?,
+            Camera::Perspective {
+                screen_window: None,
+                lens_radius,
+                focal_distance,
+                fov,
+                ..
+            } if lens_radius == 0.1 && focal_distance == 5.0 && fov == 40.0
+        ));
+        assert!(matches!(
+            camera(r#"Camera "spherical" "string mapping" "equirectangular""#)?,
+            Camera::Spherical {
+                mapping: SphericalMapping::Equirectangular,
+                ..
+            }
+        ));
+        assert!(matches!(
+            camera(r#"Camera "spherical""#)?,
+            Camera::Spherical {
+                mapping: SphericalMapping::EqualArea,
+                ..
+            }
+        ));
+
+        let Camera::Realistic {
+            lensfile,
+            aperture_diameter,
+            focus_distance,
+            aperture,
+            ..
+        } = camera(
+            r#"Camera "realistic" "string lensfile" "wide.22mm.dat" "float aperturediameter" 8
+                "float focusdistance" 1.5 "string aperture" "pentagon""#,
+        )?
+        else {
+            panic!("Expected realistic camera");
+        };
+        assert_eq!(lensfile.as_deref(), Some("wide.22mm.dat"));
+        assert_eq!(aperture_diameter, 8.0);
+        assert_eq!(focus_distance, 1.5);
+        assert_eq!(aperture.as_deref(), Some("pentagon"));
+
+        let perspective =
+            camera(r#"Camera "perspective" "float fov" 90 "float shutterclose" 0.5"#)?;
+        assert_eq!(perspective.shutter_open(), 0.0);
+        assert_eq!(perspective.shutter_close(), 0.5);
+        assert_eq!(perspective.lens_radius(), Some(0.0));
+        assert_eq!(perspective.focal_distance(), Some(1e6));
+        assert_eq!(perspective.screen_window(2.0), Some([-2.0, 2.0, -1.0, 1.0]));
+        assert_eq!(perspective.screen_window(0.5), Some([-1.0, 1.0, -2.0, 2.0]));
+
+        // 90 degrees field of view spans the shorter axis.
+        let projection = perspective.projection(2.0).unwrap();
+        let corner = projection.transform_point3(Vec3::new(2.0, 1.0, 1.0));
+        assert!((corner.x - 1.0).abs() < 1e-5 && (corner.y - 1.0).abs() < 1e-5);
+        let near = projection.transform_point3(Vec3::new(0.0, 0.0, 0.01));
+        let far = projection.transform_point3(Vec3::new(0.0, 0.0, 1000.0));
+        assert!(near.z.abs() < 1e-5 && (far.z - 1.0).abs() < 1e-5);
+
+        let orthographic = camera(r#"Camera "orthographic" "float screenwindow" [ 0 4 -1 1 ]"#)?;
+        assert_eq!(orthographic.screen_from_camera(), Some(Mat4::IDENTITY));
+        let projection = orthographic.projection(1.0).unwrap();
+        assert_eq!(
+            projection.transform_point3(Vec3::new(4.0, -1.0, 0.5)),
+            Vec3::new(1.0, -1.0, 0.5)
+        );
+
+        let spherical = camera(r#"Camera "spherical""#)?;
+        assert_eq!(spherical.lens_radius(), None);
+        assert!(spherical.projection(1.0).is_none());
+
+        assert!(matches!(
+            camera(r#"Camera "spherical" "string mapping" "cubemap""#),
+            Err(Error::InvalidString)
+        ));
+        assert!(matches!(
+            camera(r#"Camera "orthographic" "float screenwindow" [ 0 1 ]"#),
+            Err(Error::ParseSlice)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn films() -> Result<()> {
+        let film = |data: &str| -> Result<Film> {
+            let crate::Element::Film { ty, params } = crate::Parser::new(data).parse_next()? else {
+                panic!("Expected Film");
+            };
+
+            Film::new(&ty, params)
+        };
+
+        let rgb = film(
+            r#"Film "rgb" "integer xresolution" 400 "integer pixelbounds" [ 0 200 100 300 ]
+                "float maxcomponentvalue" 10 "string sensor" "canon_eos_5d_mkii" "float iso" 400"#,
+        )?;
+        assert!(matches!(rgb.ty, FilmType::Rgb));
+        assert_eq!(rgb.xresolution, 400);
+        assert_eq!(rgb.pixel_bounds, Some([0, 200, 100, 300]));
+        assert_eq!(rgb.max_component_value, 10.0);
+        assert_eq!(rgb.sensor, "canon_eos_5d_mkii");
+        assert_eq!(rgb.iso, 400.0);
+
+        let gbuffer = film(r#"Film "gbuffer" "string coordinatesystem" "world""#)?;
+        assert!(matches!(
+            gbuffer.ty,
+            FilmType::GBuffer { coordinate_system } if coordinate_system == "world"
+        ));
+        assert_eq!(gbuffer.pixel_bounds, None);
+
+        let spectral = film(r#"Film "spectral" "integer nbuckets" 32 "float lambdamax" 780"#)?;
+        assert!(matches!(
+            spectral.ty,
+            FilmType::Spectral {
+                nbuckets: 32,
+                lambda_min,
+                lambda_max,
+            } if lambda_min == 360.0 && lambda_max == 780.0
+        ));
+
+        assert!(matches!(
+            film(r#"Film "exr""#),
+            Err(Error::InvalidObjectType)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn integrators() -> Result<()> {
+        let integrator = |data: &str| -> Result<Integrator> {
+            let crate::Element::Integrator { ty, params } =
+                crate::Parser::new(data).parse_next()?
+            else {
+                panic!("Expected Integrator");
+            };
+
+            Integrator::new(&ty, params)
+        };
+
+        assert!(matches!(
+            integrator(r#"Integrator "volpath" "integer maxdepth" 100 "bool regularize" true"#)?,
+            Integrator::VolPath {
+                max_depth: 100,
+                light_sampler: LightSampler::Bvh,
+                regularize: true,
+            }
+        ));
+        assert!(matches!(
+            integrator(r#"Integrator "bdpt""#)?,
+            Integrator::Bdpt {
+                max_depth: 5,
+                light_sampler: LightSampler::Power,
+                ..
+            }
+        ));
+        assert!(matches!(
+            integrator(r#"Integrator "path" "string lightsampler" "uniform""#)?,
+            Integrator::Path {
+                light_sampler: LightSampler::Uniform,
+                ..
+            }
+        ));
+        assert!(matches!(
+            integrator(r#"Integrator "sppm" "integer photonsperiteration" 1000000 "float radius" 0.05"#)?,
+            Integrator::Sppm {
+                photons_per_iteration: 1000000,
+                radius,
+                seed: 0,
+                ..
+            } if radius == 0.05
+        ));
+        assert!(matches!(
+            integrator(r#"Integrator "mlt" "integer mutationsperpixel" 1000"#)?,
+            Integrator::Mlt {
+                mutations_per_pixel: 1000,
+                chains: 1000,
+                ..
+            }
+        ));
+        assert!(matches!(
+            integrator(r#"Integrator "ambientocclusion" "float maxdistance" 2"#)?,
+            Integrator::AmbientOcclusion {
+                cos_sample: true,
+                max_distance,
+            } if max_distance == 2.0
+        ));
+
+        assert!(matches!(
+            integrator(r#"Integrator "path" "string lightsampler" "random""#),
+            Err(Error::InvalidString)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn options() -> Result<()> {
+        let mut options = Options::default();
+
+        let data = r#"
+            Option "bool disablepixeljitter" true
+            Option "float displacementedgescale" 0.5
+            Option "string msereferenceimage" "reference.exr"
+            Option "string rendercoordsys" "world"
+            Option "integer seed" 42
+            Option "bool wavefront" true
+        "#;
+        let mut parser = crate::Parser::new(data);
+        while let Ok(crate::Element::Option(param)) = parser.parse_next() {
+            options.apply(param)?;
+        }
+
+        assert!(options.disable_pixel_jitter);
+        assert_eq!(options.displacement_edge_scale, 0.5);
+        assert_eq!(
+            options.mse_reference_image.as_deref(),
+            Some("reference.exr")
+        );
+        assert_eq!(options.render_coord_sys, CoordinateSystem::World);
+        assert_eq!(options.seed, 42);
+        assert!(options.wavefront);
+        assert!(!options.force_diffuse);
+
+        let unknown = Param::new("bool fastmode", "true")?;
+        assert!(matches!(
+            options.apply(unknown),
+            Err(Error::UnknownOption(name)) if name == "fastmode"
+        ));
+
+        let invalid = Param::new("string rendercoordsys", "\"screen\"")?;
+        assert!(matches!(
+            options.apply(invalid),
+            Err(Error::UnknownCoordinateSystem(_))
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn samplers() -> Result<()> {
+        let sampler = |data: &str| -> Result<Sampler> {
+            let crate::Element::Sampler { ty, params } = crate::Parser::new(data).parse_next()?
+            else {
+                panic!("Expected Sampler");
+            };
+
+            Sampler::new(&ty, params)
+        };
+
+        let halton = sampler(r#"Sampler "halton" "integer pixelsamples" 1024"#)?;
+        assert!(matches!(
+            halton,
+            Sampler::Halton {
+                pixel_samples: 1024,
+                randomization: RandomizeStrategy::PermuteDigits,
+                seed: 0,
+            }
+        ));
+
+        assert!(matches!(
+            sampler(r#"Sampler "zsobol" "string randomization" "none" "integer seed" 7"#)?,
+            Sampler::ZSobol {
+                pixel_samples: 16,
+                randomization: RandomizeStrategy::None,
+                seed: 7,
+            }
+        ));
+        assert_eq!(sampler(r#"Sampler "independent""#)?.pixel_samples(), 4);
+
+        let stratified =
+            sampler(r#"Sampler "stratified" "bool jitter" false "integer xsamples" 2"#)?;
+        assert!(matches!(
+            stratified,
+            Sampler::Stratified {
+                jitter: false,
+                x_samples: 2,
+                y_samples: 4,
+                ..
+            }
+        ));
+        assert_eq!(stratified.pixel_samples(), 8);
+
+        assert_eq!(Sampler::default().type_name(), "zsobol");
+        assert!(matches!(
+            sampler(r#"Sampler "sobol" "string randomization" "xor""#),
+            Err(Error::InvalidString)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn lights() -> Result<()> {
+        let light = |data: &str| -> Result<Light> {
+            let crate::Element::LightSource { ty, params } =
+                crate::Parser::new(data).parse_next()?
+            else {
+                panic!("Expected LightSource");
+            };
+
+            Light::new(&ty, params)
+        };
+
+        let point = light(r#"LightSource "point" "point3 from" [1 2 3] "float power" 100"#)?;
+        assert!(matches!(
+            point,
+            Light::Point {
+                spectrum: None,
+                scale: 1.0,
+                power: Some(100.0),
+                from: [1.0, 2.0, 3.0],
+            }
+        ));
+
+        let spot = light(r#"LightSource "spot" "float coneangle" 45 "blackbody I" 3000"#)?;
+        assert!(matches!(
+            spot,
+            Light::Spot {
+                spectrum: Some(Spectrum::Blackbody(_)),
+                cone_angle: 45.0,
+                cone_delta_angle: 5.0,
+                from: [0.0, 0.0, 0.0],
+                to: [0.0, 0.0, 1.0],
+                ..
+            }
+        ));
+        assert!(spot.spectrum().is_some());
+        assert_eq!(spot.filename(), None);
+
+        let projection = light(r#"LightSource "projection" "string filename" "slide.png""#)?;
+        assert!(matches!(projection, Light::Projection { fov, .. } if fov == 90.0));
+        assert_eq!(projection.filename(), Some("slide.png"));
+
+        let goniometric =
+            light(r#"LightSource "goniometric" "string filename" "diagram.exr" "float scale" 2"#)?;
+        assert!(matches!(
+            goniometric,
+            Light::GonioPhotometric { scale, power: None, .. } if scale == 2.0
+        ));
+        assert_eq!(goniometric.filename(), Some("diagram.exr"));
+
+        assert!(matches!(
+            light(r#"LightSource "projection" "float fov" 30"#),
+            Err(Error::MissingRequiredParameter)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn mediums() -> Result<()> {
+        let medium = |data: &str| -> Result<Medium> {
+            let crate::Element::MakeNamedMedium { name, params } =
+                crate::Parser::new(data).parse_next()?
+            else {
+                panic!("Expected MakeNamedMedium");
+            };
+
+            let ty = params.string("type").unwrap();
+            Medium::new(&name, &ty, params)
+        };
+
+        let fog = medium(r#"MakeNamedMedium "fog" "string type" "homogeneous" "float g" 0.5"#)?;
+        assert_eq!(fog.name, "fog");
+        assert_eq!(
+            fog.ty,
+            MediumType::Homogeneous {
+                sigma_a: Spectrum::Rgb([1.0; 3]),
+                sigma_s: Spectrum::Rgb([1.0; 3]),
+                scale: 1.0,
+                g: 0.5,
+                le: Spectrum::Rgb([0.0; 3]),
+                le_scale: 1.0,
+                preset: None,
+            }
+        );
+
+        let grid = medium(
+            r#"MakeNamedMedium "smoke" "string type" "uniformgrid"
+                "integer nx" 2 "integer ny" 1 "integer nz" 1 "float density" [ 0.5 1 ]
+                "point3 p1" [ 2 1 1 ] "rgb sigma_a" [ 0.1 0.2 0.3 ] "float Lescale" 2"#,
+        )?;
+        assert!(matches!(
+            grid.ty,
+            MediumType::UniformGrid {
+                nx: 2,
+                ny: 1,
+                sigma_a: Spectrum::Rgb([0.1, 0.2, 0.3]),
+                p0: [0.0, 0.0, 0.0],
+                p1: [2.0, 1.0, 1.0],
+                ref density,
+                le_scale,
+                ..
+            } if density == &[0.5, 1.0] && le_scale == 2.0
+        ));
+
+        let rgb = medium(
+            r#"MakeNamedMedium "rgb" "string type" "rgbgrid" "integer nx" 2
+                "rgb sigma_s" [ 1 0 0  0 1 0 ]"#,
+        )?;
+        assert!(matches!(
+            rgb.ty,
+            MediumType::RgbGrid { ref sigma_s, ref sigma_a, .. }
+                if sigma_s.len() == 6 && sigma_a.is_empty()
+        ));
+
+        let cloud = medium(r#"MakeNamedMedium "sky" "string type" "cloud""#)?;
+        assert!(matches!(
+            cloud.ty,
+            MediumType::Cloud { density, wispiness, frequency, .. }
+                if density == 1.0 && wispiness == 1.0 && frequency == 5.0
+        ));
+        assert_eq!(cloud.filename(), None);
+
+        let vdb = medium(
+            r#"MakeNamedMedium "explosion" "string type" "nanovdb" "string filename" "fire.nvdb""#,
+        )?;
+        assert_eq!(vdb.filename(), Some("fire.nvdb"));
+        assert_eq!(vdb.ty.type_name(), "nanovdb");
+
+        assert!(matches!(
+            medium(r#"MakeNamedMedium "vdb" "string type" "nanovdb""#),
+            Err(Error::MissingRequiredParameter)
+        ));
+        assert!(medium(r#"MakeNamedMedium "x" "string type" "fluid""#).is_err());
+
+        Ok(())
+    }
+
+    #[cfg(feature = "nanovdb")]
+    #[test]
+    fn medium_grids() -> Result<()> {
+        use crate::nanovdb::tests::write_file;
+
+        let temp_dir = tempdir::TempDir::new("pbrt-nanovdb-")?;
+        let temp_path = temp_dir.path();
+
+        std::fs::write(
+            temp_path.join("smoke.nvdb"),
+            write_file(&[("density", 1), ("temperature", 1)]),
+        )?;
+        std::fs::write(temp_path.join("fire.nvdb"), write_file(&[("density", 15)]))?;
+        std::fs::write(temp_path.join("empty.nvdb"), write_file(&[("velocity", 6)]))?;
+
+        let medium = |filename: &str| {
+            let mut params = ParamList::default();
+            let value = format!("\"{}\"", filename);
+            params.add(Param::new("string filename", &value)?)?;
+
+            Medium::new("vdb", "nanovdb", params)?.load_grids(Some(temp_path))
+        };
+
+        let grids = medium("smoke.nvdb")?.unwrap();
+        assert_eq!(grids.grid("density").unwrap().resolution(), [20, 20, 5]);
+
+        assert!(matches!(medium("fire.nvdb"), Err(Error::NanoVdb(_))));
+        assert!(matches!(medium("empty.nvdb"), Err(Error::NanoVdb(_))));
+        assert!(matches!(medium("missing.nvdb"), Err(Error::Io(_))));
+
+        let fog = Medium::new("fog", "homogeneous", ParamList::default())?;
+        assert!(fog.load_grids(None)?.is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn textures() -> Result<()> {
+        let textures = HashMap::from([("grain".to_string(), 0), ("mask".to_string(), 1)]);
+        let texture = |data: &str| -> Result<Texture> {
+            let crate::Element::Texture {
+                name,
+                ty,
+                class,
+                params,
+            } = crate::Parser::new(data).parse_next()?
+            else {
+                panic!("Expected Texture");
+            };
+
+            Texture::new(&name, &ty, &class, params, &textures)
+        };
+
+        let checks = texture(
+            r#"Texture "checks" "spectrum" "checkerboard" "float uscale" 8 "rgb tex1" [1 0 0]
+                "texture tex2" "grain""#,
+        )?;
+        assert_eq!(checks.ty, TextureType::Spectrum);
+        assert_eq!(
+            checks.class,
+            TextureClass::Checkerboard {
+                dimension: 2,
+                tex1: TexRef::Value(TextureValue::Spectrum(Spectrum::Rgb([1.0, 0.0, 0.0]))),
+                tex2: TexRef::Texture(0),
+            }
+        );
+        assert!(matches!(
+            checks.mapping,
+            TextureMapping::Uv { u_scale, v_scale, .. } if u_scale == 8.0 && v_scale == 1.0
+        ));
+
+        let mix = texture(
+            r#"Texture "mix" "float" "mix" "texture tex1" "grain" "texture amount" "mask"
+                "texture tex2" "undefined""#,
+        )?;
+        assert_eq!(
+            mix.class,
+            TextureClass::Mix {
+                tex1: TexRef::Texture(0),
+                tex2: TexRef::Value(TextureValue::Float(1.0)),
+                amount: TexRef::Texture(1),
+            }
+        );
+
+        let scale = texture(r#"Texture "s" "float" "scale" "texture tex" "mask" "float scale" 2"#)?;
+        assert_eq!(
+            scale.class,
+            TextureClass::Scale {
+                tex: TexRef::Texture(1),
+                scale: TexRef::Value(2.0),
+            }
+        );
+
+        assert_eq!(
+            texture(r#"Texture "n" "float" "fbm" "integer octaves" 4"#)?.class,
+            TextureClass::Fbm {
+                octaves: 4,
+                roughness: 0.5,
+            }
+        );
+        assert_eq!(
+            texture(r#"Texture "n" "float" "wrinkled" "float roughness" 0.7"#)?.class,
+            TextureClass::Wrinkled {
+                octaves: 8,
+                roughness: 0.7,
+            }
+        );
+        assert_eq!(
+            texture(r#"Texture "m" "spectrum" "marble" "float variation" 0.5"#)?.class,
+            TextureClass::Marble {
+                octaves: 8,
+                roughness: 0.5,
+                scale: 1.0,
+                variation: 0.5,
+            }
+        );
+
+        let dots = texture(
+            r#"Texture "d" "spectrum" "dots" "string mapping" "planar" "vector3 v2" [0 0 1]
+                "blackbody inside" 2700"#,
+        )?;
+        assert!(matches!(
+            dots.class,
+            TextureClass::Dots {
+                inside: TexRef::Value(TextureValue::Spectrum(Spectrum::Blackbody(_))),
+                outside: TexRef::Value(TextureValue::Float(_)),
+            }
+        ));
+        assert_eq!(
+            dots.mapping,
+            TextureMapping::Planar {
+                v1: [1.0, 0.0, 0.0],
+                v2: [0.0, 0.0, 1.0],
+                u_delta: 0.0,
+                v_delta: 0.0,
+            }
+        );
+
+        assert_eq!(
+            texture(r#"Texture "dm" "float" "directionmix" "vector3 dir" [0 0 1]"#)?.class,
+            TextureClass::DirectionMix {
+                tex1: TexRef::Value(TextureValue::Float(0.0)),
+                tex2: TexRef::Value(TextureValue::Float(1.0)),
+                dir: [0.0, 0.0, 1.0],
+            }
+        );
+
+        let image = texture(r#"Texture "t" "spectrum" "imagemap" "string filename" "wood.png""#)?;
+        assert_eq!(image.class.filename(), Some("wood.png"));
+        assert_eq!(
+            image.class,
+            TextureClass::ImageMap(ImageMap {
+                filename: "wood.png".to_string(),
+                wrap: WrapMode::Repeat,
+                encoding: ColorEncoding::Srgb,
+                scale: 1.0,
+                invert: false,
+                filter: ImageFilter::Bilinear,
+                max_anisotropy: 8.0,
+            })
+        );
+
+        let image = texture(
+            r#"Texture "t" "float" "imagemap" "string filename" "bump.exr" "string wrap" "clamp"
+                "bool invert" true "float scale" 0.5 "string filter" "ewa"
+                "float maxanisotropy" 16"#,
+        )?;
+        assert!(matches!(
+            image.class,
+            TextureClass::ImageMap(ImageMap {
+                wrap: WrapMode::Clamp,
+                encoding: ColorEncoding::Linear,
+                invert: true,
+                filter: ImageFilter::Ewa,
+                ..
+            })
+        ));
+
+        let image = texture(
+            r#"Texture "t" "spectrum" "imagemap" "string filename" "a.tga" "string encoding" "gamma 1.8""#,
+        )?;
+        assert!(matches!(
+            image.class,
+            TextureClass::ImageMap(ImageMap { encoding: ColorEncoding::Gamma(gamma), .. }) if gamma == 1.8
+        ));
+
+        assert!(matches!(
+            texture(r#"Texture "t" "float" "imagemap""#),
+            Err(Error::MissingRequiredParameter)
+        ));
+        assert!(matches!(
+            texture(
+                r#"Texture "t" "float" "imagemap" "string filename" "a.png" "string wrap" "mirror""#
+            ),
+            Err(Error::InvalidString)
+        ));
+        assert!(matches!(
+            texture(r#"Texture "t" "float" "windy" "string mapping" "cubic""#),
+            Err(Error::InvalidString)
+        ));
+        assert!(matches!(
+            texture(r#"Texture "t" "float" "noise""#),
+            Err(Error::InvalidObjectType)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn materials() -> Result<()> {
+        let textures = HashMap::from([
+            ("rust".to_string(), 0),
+            ("bumps".to_string(), 1),
+            ("mask".to_string(), 2),
+        ]);
+
+        let material = |data: &str| -> Result<Material> {
+            let crate::Element::Material { ty, params } = crate::Parser::new(data).parse_next()?
+            else {
+                panic!("Expected Material");
+            };
+
+            Material::new(&ty, params, &textures)
+        };
+
+        let diffuse = material(r#"Material "diffuse""#)?;
+        assert_eq!(diffuse.ty, MaterialType::default());
+        assert_eq!(diffuse.displacement, None);
+        assert_eq!(diffuse.normal_map, None);
+
+        let conductor = material(
+            r#"Material "conductor" "float roughness" 0.2 "float vroughness" 0.4
+                "texture reflectance" "rust" "texture displacement" "bumps" "string normalmap" "n.png""#,
+        )?;
+        assert_eq!(
+            conductor.ty,
+            MaterialType::Conductor {
+                eta: TexRef::Value(Spectrum::Named("metal-Cu-eta")),
+                k: TexRef::Value(Spectrum::Named("metal-Cu-k")),
+                reflectance: Some(TexRef::Texture(0)),
+                roughness: TexRef::Value(0.2),
+                u_roughness: TexRef::Value(0.2),
+                v_roughness: TexRef::Value(0.4),
+                remap_roughness: true,
+            }
+        );
+        assert_eq!(conductor.displacement, Some(TexRef::Texture(1)));
+        assert_eq!(conductor.normal_map.as_deref(), Some("n.png"));
+
+        // The index of refraction is either a float or a spectrum.
+        let dielectric = material(r#"Material "dielectric" "float eta" 1.33"#)?;
+        assert!(matches!(
+            dielectric.ty,
+            MaterialType::Dielectric { eta: Eta::Constant(eta), .. } if eta == 1.33
+        ));
+        let thin = material(r#"Material "thindielectric" "spectrum eta" "glass-BK7""#)?;
+        assert_eq!(
+            thin.ty,
+            MaterialType::ThinDielectric {
+                eta: Eta::Spectrum(Spectrum::Named("glass-BK7"))
+            }
+        );
+
+        let coated = material(
+            r#"Material "coatedconductor" "float interface.roughness" 0.1
+                "spectrum conductor.eta" "metal-Au-eta" "rgb albedo" [ 0.1 0.2 0.3 ] "integer maxdepth" 4"#,
+        )?;
+        let MaterialType::CoatedConductor {
+            interface_u_roughness,
+            interface_eta,
+            conductor_roughness,
+            conductor_eta,
+            conductor_k,
+            albedo,
+            max_depth,
+            thickness,
+            ..
+        } = coated.ty
+        else {
+            panic!("Expected coated conductor");
+        };
+        assert_eq!(interface_u_roughness, TexRef::Value(0.1));
+        assert_eq!(interface_eta, Eta::Constant(1.5));
+        assert_eq!(conductor_roughness, TexRef::Value(0.0));
+        assert_eq!(
+            conductor_eta,
+            TexRef::Value(Spectrum::Named("metal-Au-eta"))
+        );
+        assert_eq!(conductor_k, TexRef::Value(Spectrum::Named("metal-Cu-k")));
+        assert_eq!(albedo, TexRef::Value(Spectrum::Rgb([0.1, 0.2, 0.3])));
+        assert_eq!(max_depth, 4);
+        assert_eq!(thickness, TexRef::Value(0.01));
+
+        let mix = material(
+            r#"Material "mix" "string materials" [ "gold" "glass" ] "texture amount" "mask""#,
+        )?;
+        assert_eq!(
+            mix.ty,
+            MaterialType::Mix {
+                materials: ["gold".to_string(), "glass".to_string()],
+                amount: TexRef::Texture(2),
+            }
+        );
+
+        // Undefined textures are reported while loading, the default value is used.
+        let undefined = material(r#"Material "diffuse" "texture reflectance" "missing""#)?;
+        assert_eq!(undefined.ty, MaterialType::default());
+
+        let hair = material(r#"Material "hair" "float eumelanin" 0.3"#)?;
+        assert!(matches!(
+            hair.ty,
+            MaterialType::Hair {
+                sigma_a: None,
+                color: None,
+                eumelanin: Some(TexRef::Value(_)),
+                eta: TexRef::Value(eta),
+                ..
+            } if eta == 1.55
+        ));
+
+        let skin = material(r#"Material "subsurface" "string name" "Skin1" "float scale" 10"#)?;
+        assert!(matches!(
+            skin.ty,
+            MaterialType::Subsurface { name: Some(name), scale, eta, .. }
+                if name == "Skin1" && scale == 10.0 && eta == 1.33
+        ));
+
+        let transmission = material(r#"Material "diffusetransmission""#)?;
+        assert!(matches!(
+            transmission.ty,
+            MaterialType::DiffuseTransmission { reflectance: TexRef::Value(Spectrum::Rgb(rgb)), .. }
+                if rgb == [0.25; 3]
+        ));
+
+        assert_eq!(
+            material(r#"Material "interface""#)?.ty,
+            MaterialType::Interface
+        );
+        assert!(matches!(
+            material(r#"Material "measured""#),
+            Err(Error::MissingRequiredParameter)
+        ));
+        assert!(matches!(
+            material(r#"Material "plastic""#),
+            Err(Error::InvalidObjectType)
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn curve() -> Result<()> {
+        // Strand from straight-hair.pbrt.
+        let data = r#"Shape "curve" "point3 P" [ -0.0257 0.0917 0.0154 -0.0255 0.0915 0.0151
+            -0.0252 0.0912 0.0148 -0.0248 0.0909 0.0144 ] "float width0" 0.004 "float width1" 0.001
+            "string type" "cylinder""#;
+
+        let crate::Element::Shape { name, params } = crate::Parser::new(data).parse_next()? else {
+            panic!("Expected Shape");
+        };
+
+        let shape = Shape::new(&name, params)?;
+        let Shape::Curve {
+            positions,
+            basis,
+            degree,
+            ty,
+            normals,
+            width,
+            width0,
+            width1,
+            split_depth,
+            ..
+        } = &shape
+        else {
+            panic!("Expected curve");
+        };
+
+        assert_eq!(positions.len(), 12);
+        assert_eq!(*basis, CurveBasis::Bezier);
+        assert_eq!(*degree, 3);
+        assert_eq!(*ty, CurveType::Cylinder);
+        assert!(normals.is_empty());
+        assert_eq!((*width, *width0, *width1), (1.0, 0.004, 0.001));
+        assert_eq!(*split_depth, 3);
+
+        let bounds = shape.bounds().unwrap();
+        assert!((bounds.min.x - (-0.0257 - 0.002)).abs() < 1e-6);
+        assert!((bounds.max.z - (0.0154 + 0.002)).abs() < 1e-6);
+
+        let data = r#"Shape "curve" "float width" 0.5 "string basis" "bspline""#;
+        let crate::Element::Shape { name, params } = crate::Parser::new(data).parse_next()? else {
+            panic!("Expected Shape");
+        };
+        assert!(matches!(
+            Shape::new(&name, params)?,
+            Shape::Curve {
+                basis: CurveBasis::BSpline,
+                width0,
+                width1,
+                ..
+            } if width0 == 0.5 && width1 == 0.5
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn bilinear_mesh() -> Result<()> {
+        let shape = |data: &str| -> Result<Shape> {
+            let crate::Element::Shape { name, params } = crate::Parser::new(data).parse_next()?
+            else {
+                panic!("Expected Shape");
+            };
+
+            Shape::new(&name, params)
+        };
+
+        let quad = shape(
+            r#"Shape "bilinearmesh" "point3 P" [ -1 -1 0  1 -1 0  -1 1 0  1 1 0 ]
+                "point2 uv" [ 0 0  1 0  0 1  1 1 ] "string emissionfilename" "sky.exr""#,
+        )?;
+        let Shape::BilinearMesh {
+            indices,
+            positions,
+            uvs,
+            emission_filename,
+            ..
+        } = &quad
+        else {
+            panic!("Expected bilinear mesh");
+        };
+        assert!(indices.is_empty());
+        assert_eq!(positions.len(), 12);
+        assert_eq!(uvs.len(), 8);
+        assert_eq!(emission_filename.as_deref(), Some("sky.exr"));
+        assert_eq!(crate::finalize::validate_mesh(&quad), Ok(()));
+
+        // Triangles keep the patch orientation (+z).
+        let Some(Shape::TriangleMesh {
+            indices, positions, ..
+        }) = quad.tessellate(0)
+        else {
+            panic!("Bilinear mesh must be tessellated");
+        };
+        assert_eq!(indices, [0, 1, 3, 0, 3, 2]);
+        let p = |i: i32| {
+            Vec3::new(
+                positions[i as usize * 3],
+                positions[i as usize * 3 + 1],
+                0.0,
+            )
+        };
+        assert!((p(1) - p(0)).cross(p(3) - p(0)).z > 0.0);
+
+        let mesh = shape(
+            r#"Shape "bilinearmesh" "point3 P" [ 0 0 0  1 0 0  0 1 0  1 1 0  2 0 0  2 1 0 ]
+                "integer indices" [ 0 1 2 3  1 4 3 5 ] "integer faceIndices" [ 7 8 ]"#,
+        )?;
+        assert!(matches!(
+            &mesh,
+            Shape::BilinearMesh { face_indices, .. } if face_indices == &[7, 8]
+        ));
+        assert_eq!(crate::finalize::validate_mesh(&mesh), Ok(()));
+
+        let invalid = shape(
+            r#"Shape "bilinearmesh" "point3 P" [ 0 0 0  1 0 0  0 1 0 ] "integer indices" [ 0 1 2 ]"#,
+        )?;
+        assert_eq!(
+            crate::finalize::validate_mesh(&invalid),
+            Err(("indices", "number of indices is not a multiple of 4"))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn loop_subdiv() -> Result<()> {
+        let crate::Element::Shape { name, params } = crate::Parser::new(
+            r#"Shape "loopsubdiv" "integer levels" 2 "integer indices" [ 0 1 2  0 2 3 ]
+                "point3 P" [ 0 0 0  1 0 0  1 1 0  0 1 0 ]"#,
+        )
+        .parse_next()?
+        else {
+            panic!("Expected Shape");
+        };
+
+        let shape = Shape::new(&name, params)?;
+        assert!(matches!(
+            &shape,
+            Shape::LoopSubdiv { levels: 2, indices, positions } if indices.len() == 6 && positions.len() == 12
+        ));
+        assert_eq!(crate::finalize::validate_mesh(&shape), Ok(()));
+
+        let Some(Shape::TriangleMesh {
+            indices,
+            positions,
+            normals,
+            ..
+        }) = shape.tessellate(0)
+        else {
+            panic!("Loop subdivision surface must be tessellated");
+        };
+        assert_eq!(indices.len(), 2 * 16 * 3);
+        assert_eq!(normals.len(), positions.len());
+
+        // Default levels.
+        let shape = Shape::new("loopsubdiv", ParamList::default())?;
+        assert!(matches!(shape, Shape::LoopSubdiv { levels: 3, .. }));
+
+        Ok(())
+    }
+
+    #[test]
+    fn tessellate() {
+        let sphere = Shape::Sphere {
+            alpha: 1.0,
+            radius: 2.0,
+            zmin: -2.0,
+            zmax: 2.0,
+            phimax: 360.0,
+        };
+
+        let Some(Shape::TriangleMesh {
+            indices,
+            positions,
+            normals,
+            ..
+        }) = sphere.tessellate(8)
+        else {
+            panic!("Sphere must be tessellated");
+        };
+
+        assert_eq!(positions.len(), 9 * 5 * 3);
+        assert_eq!(normals.len(), positions.len());
+        assert_eq!(indices.len(), 8 * 4 * 6);
+
+        for p in positions.chunks_exact(3) {
+            let length = (p[0] * p[0] + p[1] * p[1] + p[2] * p[2]).sqrt();
+            assert!((length - 2.0).abs() < 1e-4);
+        }
+
+        let bounds = Bounds3::from_points(&positions);
+        let expected = sphere.bounds().unwrap();
+        assert!((bounds.min - expected.min).length() < 1e-4);
+        assert!((bounds.max - expected.max).length() < 1e-4);
+
+        assert!(sphere.tessellate(8).unwrap().tessellate(8).is_none());
     }
 }
@@ -0,0 +1,832 @@
+//! Scene validation.
+//!
+//! Validation is opt-in and never fails scene loading, instead a list of
+//! [ValidationIssue]s is returned for the caller to report. [Scene::validate]
+//! runs all checks at once.
+
+use std::{collections::HashMap, fmt};
+
+use crate::{
+    finalize::validate_mesh,
+    math::{Float, Mat4},
+    param::{ParamList, ParamType, Spectrum},
+    types::{AreaLight, CurveBasis, CurveType, Light, Shape, TextureType},
+    Location, Scene,
+};
+
+/// Issue severity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Severity {
+    /// Scene is likely to render differently than intended.
+    Warning,
+    /// Scene data is invalid (e.g. contains NaNs).
+    Error,
+}
+
+/// Scene entity an issue refers to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum EntityRef {
+    /// The scene as a whole (e.g. unbalanced blocks).
+    Scene,
+    Camera,
+    Film,
+    Integrator,
+    /// Index in [Scene::materials].
+    Material(usize),
+    /// Index in [Scene::shapes].
+    Shape(usize),
+    /// Index in [Scene::lights].
+    Light(usize),
+    /// Index in [Scene::area_lights].
+    AreaLight(usize),
+    /// Index in [Scene::objects].
+    Object(usize),
+    /// Index in [Scene::instances].
+    Instance(usize),
+}
+
+impl fmt::Display for EntityRef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EntityRef::Scene => write!(f, "scene"),
+            EntityRef::Camera => write!(f, "camera"),
+            EntityRef::Film => write!(f, "film"),
+            EntityRef::Integrator => write!(f, "integrator"),
+            EntityRef::Material(index) => write!(f, "material #{}", index),
+            EntityRef::Shape(index) => write!(f, "shape #{}", index),
+            EntityRef::Light(index) => write!(f, "light #{}", index),
+            EntityRef::AreaLight(index) => write!(f, "area light #{}", index),
+            EntityRef::Object(index) => write!(f, "object #{}", index),
+            EntityRef::Instance(index) => write!(f, "instance #{}", index),
+        }
+    }
+}
+
+/// A single problem found by validation.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ValidationIssue {
+    pub severity: Severity,
+    /// Entity with the problem.
+    pub entity: EntityRef,
+    /// Name of the offending parameter (e.g. `"radius"` or `"transform"`) or directive,
+    /// empty if the issue is not specific to one.
+    pub parameter: String,
+    pub message: String,
+    /// Statement the issue was found at, for issues reported while loading.
+    pub location: Option<Location>,
+}
+
+/// Problems reported while loading are validation issues with a location,
+/// see [Scene::load_with_diagnostics].
+pub type Diagnostic = ValidationIssue;
+
+impl ValidationIssue {
+    pub(crate) fn new(
+        severity: Severity,
+        entity: EntityRef,
+        parameter: &str,
+        message: impl Into<String>,
+    ) -> Self {
+        ValidationIssue {
+            severity,
+            entity,
+            parameter: parameter.to_string(),
+            message: message.into(),
+            location: None,
+        }
+    }
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+
+        if let Some(location) = &self.location {
+            write!(f, "{}: ", location)?;
+        }
+
+        if self.parameter.is_empty() {
+            write!(f, "{}: {}: {}", severity, self.entity, self.message)
+        } else {
+            write!(
+                f,
+                "{}: {} \"{}\": {}",
+                severity, self.entity, self.parameter, self.message
+            )
+        }
+    }
+}
+
+/// Collects issues for a single entity.
+struct Checker<'a> {
+    issues: &'a mut Vec<ValidationIssue>,
+    entity: EntityRef,
+}
+
+impl Checker<'_> {
+    fn report(&mut self, severity: Severity, parameter: &str, message: impl Into<String>) {
+        self.issues.push(ValidationIssue::new(
+            severity,
+            self.entity,
+            parameter,
+            message,
+        ));
+    }
+
+    fn finite(&mut self, parameter: &str, value: f32) {
+        if !value.is_finite() {
+            self.report(Severity::Error, parameter, format!("value is {}", value));
+        }
+    }
+
+    fn non_negative(&mut self, parameter: &str, value: f32) {
+        if !value.is_finite() {
+            self.finite(parameter, value);
+        } else if value < 0.0 {
+            self.report(
+                Severity::Warning,
+                parameter,
+                format!("value {} is negative", value),
+            );
+        }
+    }
+
+    fn finite_array<T: Copy + Into<f64>>(&mut self, parameter: &str, values: &[T]) {
+        if let Some(index) = values.iter().position(|v| !(*v).into().is_finite()) {
+            let value: f64 = values[index].into();
+            self.report(
+                Severity::Error,
+                parameter,
+                format!("value at index {} is {}", index, value),
+            );
+        }
+    }
+
+    fn transform(&mut self, parameter: &str, m: &Mat4) {
+        let values: &[Float; 16] = m.as_ref();
+        if !m.is_finite() {
+            self.finite_array(parameter, values);
+        }
+    }
+
+    /// Report singular and zero-scale transforms.
+    ///
+    /// Returns the determinant of the transform's upper 3x3 part, or `None` if the transform is degenerate.
+    fn invertible(&mut self, parameter: &str, m: &Mat4) -> Option<Float> {
+        if !m.is_finite() {
+            // Reported by `check_numeric`.
+            return None;
+        }
+
+        let axis_lengths = (0..3)
+            .map(|col| {
+                (0..3)
+                    .map(|row| m.get(row, col).powi(2))
+                    .sum::<Float>()
+                    .sqrt()
+            })
+            .collect::<Vec<_>>();
+
+        if let Some(axis) = axis_lengths.iter().position(|len| *len == 0.0) {
+            self.report(
+                Severity::Warning,
+                parameter,
+                format!(
+                    "transform has zero scale along the {} axis",
+                    ["x", "y", "z"][axis]
+                ),
+            );
+            return None;
+        }
+
+        // Normalize by the scale, so small but valid scales are not reported.
+        let det = m.determinant();
+        let scale: Float = axis_lengths.iter().product();
+
+        if (det / scale).abs() < 1e-6 {
+            self.report(Severity::Warning, parameter, "transform is not invertible");
+            return None;
+        }
+
+        Some(det)
+    }
+
+    fn spectrum(&mut self, parameter: &str, spectrum: &Spectrum) {
+        match spectrum {
+            Spectrum::Rgb(rgb) => {
+                for value in rgb {
+                    self.non_negative(parameter, *value);
+                }
+            }
+            Spectrum::Blackbody(temperature) => {
+                if *temperature <= 0 {
+                    self.report(
+                        Severity::Warning,
+                        parameter,
+                        format!("blackbody temperature {} is not positive", temperature),
+                    );
+                }
+            }
+            Spectrum::Sampled(samples) => {
+                for &(_, value) in samples {
+                    self.non_negative(parameter, value);
+                }
+
+                if samples.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+                    self.report(
+                        Severity::Error,
+                        parameter,
+                        "wavelengths are not increasing".to_string(),
+                    );
+                }
+            }
+            // Built-in spectra are always valid, files are not loaded.
+            Spectrum::Named(_) | Spectrum::File(_) => {}
+        }
+    }
+}
+
+impl Scene {
+    /// Run all checks and return every issue found, rather than stopping at the first one.
+    ///
+    /// Includes problems recorded while loading ([Scene::issues]), missing camera, film and
+    /// integrator, inconsistent mesh data, and the results of [Scene::check_numeric] and
+    /// [Scene::check_transforms].
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let mut issues = self.issues.clone();
+
+        // pbrt falls back to defaults, which is rarely what was intended.
+        let missing = [
+            (self.camera.is_none(), EntityRef::Camera, "camera"),
+            (self.film.is_none(), EntityRef::Film, "film"),
+            (
+                self.integrator.is_none(),
+                EntityRef::Integrator,
+                "integrator",
+            ),
+        ];
+
+        for (is_missing, entity, name) in missing {
+            if is_missing {
+                issues.push(ValidationIssue::new(
+                    Severity::Warning,
+                    entity,
+                    "",
+                    format!("scene has no {}, the default is used", name),
+                ));
+            }
+        }
+
+        for (index, shape) in self.shapes.iter().enumerate() {
+            if let Err((parameter, reason)) = validate_mesh(&shape.params) {
+                issues.push(ValidationIssue::new(
+                    Severity::Error,
+                    EntityRef::Shape(index),
+                    parameter,
+                    reason,
+                ));
+            }
+        }
+
+        issues.extend(self.check_numeric());
+        issues.extend(self.check_transforms());
+
+        issues
+    }
+
+    /// Scan transforms, positions, radii, spectra and light scales for NaN/Inf
+    /// and negative-where-invalid values.
+    ///
+    /// Exporters occasionally emit NaNs, which otherwise only surface as black renders much later.
+    pub fn check_numeric(&self) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+
+        if let Some(camera) = &self.camera {
+            let mut checker = Checker {
+                issues: &mut issues,
+                entity: EntityRef::Camera,
+            };
+
+            checker.transform("transform", &camera.transform);
+            checker.transform("transform_end", &camera.transform_end);
+        }
+
+        for (index, shape) in self.shapes.iter().enumerate() {
+            let mut checker = Checker {
+                issues: &mut issues,
+                entity: EntityRef::Shape(index),
+            };
+
+            checker.transform("transform", &shape.transform);
+            checker.transform("transform_end", &shape.transform_end);
+            check_shape(&mut checker, &shape.params);
+        }
+
+        for (index, light) in self.lights.iter().enumerate() {
+            let mut checker = Checker {
+                issues: &mut issues,
+                entity: EntityRef::Light(index),
+            };
+
+            checker.transform("transform", &light.transform);
+            checker.transform("transform_end", &light.transform_end);
+
+            if let Some(spectrum) = light.params.spectrum() {
+                let name = match light.params {
+                    Light::Infinite { .. } => "L",
+                    _ => "I",
+                };
+                checker.spectrum(name, spectrum);
+            }
+
+            match &light.params {
+                Light::GonioPhotometric { scale, power, .. }
+                | Light::Point { scale, power, .. }
+                | Light::Projection { scale, power, .. }
+                | Light::Spot { scale, power, .. } => {
+                    checker.non_negative("scale", *scale);
+                    if let Some(power) = power {
+                        checker.non_negative("power", *power);
+                    }
+                }
+                Light::Distant | Light::Infinite { .. } => {}
+            }
+        }
+
+        for (index, light) in self.area_lights.iter().enumerate() {
+            let mut checker = Checker {
+                issues: &mut issues,
+                entity: EntityRef::AreaLight(index),
+            };
+
+            let AreaLight::Diffuse {
+                spectrum, scale, ..
+            } = light;
+
+            if let Some(spectrum) = spectrum {
+                checker.spectrum("L", spectrum);
+            }
+            checker.non_negative("scale", *scale);
+        }
+
+        for (index, object) in self.objects.iter().enumerate() {
+            Checker {
+                issues: &mut issues,
+                entity: EntityRef::Object(index),
+            }
+            .transform("transform", &object.object_to_instance);
+        }
+
+        for (index, instance) in self.instances.iter().enumerate() {
+            let mut checker = Checker {
+                issues: &mut issues,
+                entity: EntityRef::Instance(index),
+            };
+
+            checker.transform("transform", &instance.instance_to_world);
+            checker.transform("transform_end", &instance.instance_to_world_end);
+        }
+
+        issues
+    }
+}
+
+impl Scene {
+    /// Find degenerate transforms: zero scale, non-invertible matrices, and
+    /// area lights with mirroring transforms (which flip the emitting side).
+    pub fn check_transforms(&self) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+
+        // Camera transform is the inverse of the CTM at the Camera directive,
+        // so a singular CTM results in a non-finite camera transform.
+        if let Some(camera) = &self.camera {
+            let mut checker = Checker {
+                issues: &mut issues,
+                entity: EntityRef::Camera,
+            };
+
+            if !camera.transform.is_finite() {
+                checker.report(
+                    Severity::Error,
+                    "transform",
+                    "camera transform is not invertible",
+                );
+            } else {
+                checker.invertible("transform", &camera.transform);
+            }
+        }
+
+        for (index, shape) in self.shapes.iter().enumerate() {
+            let mut checker = Checker {
+                issues: &mut issues,
+                entity: EntityRef::Shape(index),
+            };
+
+            let det = checker.invertible("transform", &shape.transform);
+
+            if let (Some(det), Some(_)) = (det, shape.area_light_index) {
+                if det < 0.0 {
+                    checker.report(
+                        Severity::Warning,
+                        "transform",
+                        "mirroring transform flips the emitting side of the area light",
+                    );
+                }
+            }
+        }
+
+        for (index, instance) in self.instances.iter().enumerate() {
+            let mut checker = Checker {
+                issues: &mut issues,
+                entity: EntityRef::Instance(index),
+            };
+
+            let det = checker.invertible("transform", &instance.instance_to_world);
+
+            if let (Some(det), Some(_)) = (det, instance.area_light_index) {
+                if det < 0.0 {
+                    checker.report(
+                        Severity::Warning,
+                        "transform",
+                        "mirroring transform flips the emitting side of the area light",
+                    );
+                }
+            }
+        }
+
+        issues
+    }
+}
+
+/// Texture type expected by a material parameter, `None` if the parameter doesn't take textures.
+fn material_texture_type(parameter: &str) -> Option<TextureType> {
+    // Coated materials prefix parameters of their layers, e.g. "interface.roughness".
+    let parameter = parameter.rsplit('.').next().unwrap_or(parameter);
+
+    match parameter {
+        "amount" | "displacement" | "g" | "roughness" | "thickness" | "uroughness"
+        | "vroughness" => Some(TextureType::Float),
+        "albedo" | "eta" | "k" | "mfp" | "reflectance" | "sigma_a" | "sigma_s"
+        | "transmittance" => Some(TextureType::Spectrum),
+        _ => None,
+    }
+}
+
+/// Check texture references of the material at `index`.
+///
+/// `textures` maps texture names to indices in `types`.
+pub(crate) fn material_textures(
+    index: usize,
+    params: &ParamList,
+    textures: &HashMap<String, usize>,
+    types: &[TextureType],
+) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+
+    for param in params.iter() {
+        if param.ty != ParamType::Texture {
+            continue;
+        }
+
+        let name = param.string();
+        let mut report = |message: String| {
+            issues.push(ValidationIssue::new(
+                Severity::Error,
+                EntityRef::Material(index),
+                param.name,
+                message,
+            ))
+        };
+
+        let Some(&texture) = textures.get(&*name) else {
+            report(format!("texture \"{}\" is not defined", name));
+            continue;
+        };
+
+        match (material_texture_type(param.name), types.get(texture)) {
+            (Some(expected), Some(&actual)) if expected != actual => {
+                let type_name = |ty| match ty {
+                    TextureType::Float => "float",
+                    TextureType::Spectrum => "spectrum",
+                };
+
+                report(format!(
+                    "parameter requires a {} texture, \"{}\" is a {} texture",
+                    type_name(expected),
+                    name,
+                    type_name(actual)
+                ));
+            }
+            _ => {}
+        }
+    }
+
+    // Parameters are not ordered, keep the output stable.
+    issues.sort_by(|a, b| a.parameter.cmp(&b.parameter));
+    issues
+}
+
+fn check_shape(checker: &mut Checker, shape: &Shape) {
+    match shape {
+        Shape::Cylinder {
+            alpha,
+            radius,
+            zmin,
+            zmax,
+            phimax,
+        }
+        | Shape::Sphere {
+            alpha,
+            radius,
+            zmin,
+            zmax,
+            phimax,
+        } => {
+            checker.finite("alpha", *alpha);
+            checker.non_negative("radius", *radius);
+            checker.finite("zmin", *zmin);
+            checker.finite("zmax", *zmax);
+            checker.finite("phimax", *phimax);
+        }
+        Shape::Disk {
+            alpha,
+            height,
+            radius,
+            innerradius,
+            phimax,
+        } => {
+            checker.finite("alpha", *alpha);
+            checker.finite("height", *height);
+            checker.non_negative("radius", *radius);
+            checker.non_negative("innerradius", *innerradius);
+            checker.finite("phimax", *phimax);
+        }
+        Shape::TriangleMesh {
+            alpha,
+            positions,
+            normals,
+            tangents,
+            uvs,
+            ..
+        } => {
+            checker.finite("alpha", *alpha);
+            checker.finite_array("P", positions);
+            checker.finite_array("N", normals);
+            checker.finite_array("S", tangents);
+            checker.finite_array("uv", uvs);
+        }
+        Shape::LoopSubdiv { positions, .. } => checker.finite_array("P", positions),
+        Shape::BilinearMesh {
+            alpha,
+            positions,
+            normals,
+            uvs,
+            ..
+        } => {
+            checker.finite("alpha", *alpha);
+            checker.finite_array("P", positions);
+            checker.finite_array("N", normals);
+            checker.finite_array("uv", uvs);
+        }
+        Shape::Curve {
+            alpha,
+            positions,
+            basis,
+            degree,
+            ty,
+            normals,
+            width0,
+            width1,
+            ..
+        } => {
+            checker.finite("alpha", *alpha);
+            checker.finite_array("P", positions);
+            checker.finite_array("N", normals);
+            checker.non_negative("width0", *width0);
+            checker.non_negative("width1", *width1);
+
+            if !matches!(degree, 2 | 3) {
+                checker.report(
+                    Severity::Error,
+                    "degree",
+                    format!("degree {} is not supported, must be 2 or 3", degree),
+                );
+            } else if let Some(segments) = curve_segments(positions, *basis, *degree as usize) {
+                if *ty == CurveType::Ribbon && normals.len() != (segments + 1) * 3 {
+                    checker.report(
+                        Severity::Error,
+                        "N",
+                        format!("ribbon curve requires {} normals", segments + 1),
+                    );
+                }
+            } else {
+                checker.report(
+                    Severity::Error,
+                    "P",
+                    "number of control points doesn't match the curve's degree",
+                );
+            }
+        }
+        #[cfg(feature = "ply")]
+        Shape::PlyMesh {
+            mesh: Some(mesh), ..
+        } => {
+            checker.finite_array("P", &mesh.positions);
+            checker.finite_array("N", &mesh.normals);
+            checker.finite_array("uv", &mesh.uvs);
+        }
+        Shape::PlyMesh { .. } => {}
+    }
+}
+
+/// Number of segments of a curve with `positions` control points.
+fn curve_segments(positions: &[Float], basis: CurveBasis, degree: usize) -> Option<usize> {
+    let points = positions.len() / 3;
+    if positions.len() % 3 != 0 || points <= degree {
+        return None;
+    }
+
+    match basis {
+        // Bézier segments share end points.
+        CurveBasis::Bezier => ((points - 1) % degree == 0).then_some((points - 1) / degree),
+        CurveBasis::BSpline => Some(points - degree),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{Error, LoadOptions, Result};
+
+    #[test]
+    fn numeric_issues() -> Result<()> {
+        let data = r#"
+WorldBegin
+AttributeBegin
+AreaLightSource "diffuse" "rgb L" [ 1 -1 1 ]
+Shape "sphere" "float radius" -1
+AttributeEnd
+Shape "trianglemesh" "point3 P" [ 0 0 0 1 NaN 0 0 1 0 ] "integer indices" [ 0 1 2 ]
+Scale 1 inf 1
+Shape "disk"
+        "#;
+
+        let scene = Scene::load(data, None)?;
+        let issues = scene.check_numeric();
+
+        let find = |entity: EntityRef, parameter: &str| {
+            issues
+                .iter()
+                .find(|issue| issue.entity == entity && issue.parameter == parameter)
+        };
+
+        let issue = find(EntityRef::Shape(0), "radius").unwrap();
+        assert_eq!(issue.severity, Severity::Warning);
+
+        let issue = find(EntityRef::Shape(1), "P").unwrap();
+        assert_eq!(issue.severity, Severity::Error);
+        assert_eq!(issue.message, "value at index 4 is NaN");
+
+        assert!(find(EntityRef::Shape(2), "transform").is_some());
+        assert!(find(EntityRef::Shape(2), "transform_end").is_some());
+        assert!(find(EntityRef::AreaLight(0), "L").is_some());
+
+        assert_eq!(issues.len(), 5);
+
+        Ok(())
+    }
+
+    #[test]
+    fn curve_issues() -> Result<()> {
+        let data = r#"
+WorldBegin
+Shape "curve" "point3 P" [ 0 0 0  1 0 0  2 0 0 ]
+Shape "curve" "point3 P" [ 0 0 0  1 0 0  2 0 0  3 0 0 ] "string type" "ribbon"
+Shape "curve" "point3 P" [ 0 0 0  1 0 0  2 0 0  3 0 0  4 0 0 ] "string basis" "bspline"
+    "string type" "ribbon" "normal N" [ 0 1 0  0 1 0  0 1 0 ]
+Shape "curve" "point3 P" [ 0 0 0  1 0 0 ] "integer degree" 1
+        "#;
+
+        let scene = Scene::load(data, None)?;
+        let issues = scene
+            .check_numeric()
+            .into_iter()
+            .map(|issue| (issue.entity, issue.parameter))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            issues,
+            [
+                (EntityRef::Shape(0), "P".to_string()),
+                (EntityRef::Shape(1), "N".to_string()),
+                (EntityRef::Shape(3), "degree".to_string()),
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn transform_issues() -> Result<()> {
+        let data = r#"
+Scale 1 0 1
+Camera "perspective"
+WorldBegin
+AttributeBegin
+Scale -1 1 1
+AreaLightSource "diffuse"
+Shape "sphere"
+AttributeEnd
+Scale 0.001 0.001 0.001
+Shape "sphere"
+ConcatTransform [ 1 0 0 0  1 0 0 0  0 0 1 0  0 0 0 1 ]
+Shape "sphere"
+        "#;
+
+        let scene = Scene::load(data, None)?;
+        let issues = scene.check_transforms();
+
+        assert_eq!(issues.len(), 3);
+
+        assert_eq!(issues[0].entity, EntityRef::Camera);
+        assert_eq!(issues[0].severity, Severity::Error);
+
+        assert_eq!(issues[1].entity, EntityRef::Shape(0));
+        assert!(issues[1].message.contains("area light"));
+
+        // Small scale is fine, collapsed axes are not.
+        assert_eq!(issues[2].entity, EntityRef::Shape(2));
+        assert_eq!(issues[2].message, "transform is not invertible");
+
+        Ok(())
+    }
+
+    #[test]
+    fn validate() -> Result<()> {
+        let data = r#"
+WorldBegin
+Texture "checks" "float" "checkerboard"
+Texture "wood" "spectrum" "imagemap" "string filename" "wood.png"
+Material "diffuse" "texture reflectance" "checks"
+MakeNamedMaterial "metal" "string type" "conductor" "texture roughness" "wood" "texture k" "missing"
+NamedMaterial "gold"
+Shape "sphere"
+NamedMaterial "metal"
+Shape "trianglemesh" "point3 P" [ 0 0 0 1 0 0 0 1 0 ] "integer indices" [ 0 1 3 ]
+Shape "trianglemesh" "point3 P" [ 0 0 0 1 0 0 0 1 0 ] "point2 uv" [ 0 0 1 0 ]
+AttributeBegin
+ObjectBegin "unclosed"
+Shape "sphere"
+        "#;
+
+        let options = LoadOptions {
+            lenient: true,
+            ..Default::default()
+        };
+
+        let scene = Scene::load_with_options(data, None, &options)?;
+        let issues = scene.validate();
+
+        let messages = issues
+            .iter()
+            .map(|issue| issue.to_string())
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            messages,
+            [
+                "5:1: error: material #0 \"reflectance\": parameter requires a spectrum texture, \"checks\" is a float texture",
+                "6:1: error: material #1 \"k\": texture \"missing\" is not defined",
+                "6:1: error: material #1 \"roughness\": parameter requires a float texture, \"wood\" is a spectrum texture",
+                "8:1: warning: shape #0 \"material\": named material \"gold\" is not defined",
+                "error: object #0 \"ObjectBegin\": object \"unclosed\" is missing ObjectEnd",
+                "error: scene \"AttributeBegin\": 1 AttributeBegin without matching AttributeEnd",
+                "warning: camera: scene has no camera, the default is used",
+                "warning: film: scene has no film, the default is used",
+                "warning: integrator: scene has no integrator, the default is used",
+                "error: shape #1 \"indices\": vertex index is out of range",
+                "error: shape #2 \"uv\": number of uvs doesn't match number of positions",
+            ]
+        );
+
+        // The unclosed object is still loaded.
+        assert_eq!(scene.object_shapes(0).len(), 1);
+        assert_eq!(scene.shapes[0].material_index, None);
+
+        // Undefined materials are errors by default.
+        let Err(err) = Scene::load(data, None) else {
+            panic!("Expected error");
+        };
+        assert_eq!(err.location().unwrap().line, 7);
+        assert!(matches!(err.inner(), Error::MaterialNotFound(name) if name == "gold"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,236 @@
+//! Streaming scene loading.
+
+use std::{fs, path::Path};
+
+use crate::{
+    math::Mat4,
+    scene::{decode, visit_impl},
+    types::{
+        Accelerator, AreaLight, Film, Filter, Integrator, Material, Medium, Options, Sampler,
+        Texture,
+    },
+    validate::ValidationIssue,
+    CameraEntity, Dependency, InstanceEntity, LightEntity, LoadOptions, Object, Result, Scene,
+    ShapeEntity,
+};
+
+/// Receives scene entities as they are loaded, see [Scene::visit].
+///
+/// Entities are resolved exactly as in [Scene::load] (transformations, materials, area lights
+/// and other graphics state are applied), but nothing is stored. Indices passed along with
+/// entities are the ones other entities refer to (e.g. [ShapeEntity::material_index]).
+///
+/// All methods do nothing by default. Returning an error aborts loading.
+#[allow(unused_variables)]
+pub trait SceneVisitor {
+    /// Called after each `Option` directive with the updated options.
+    fn on_options(&mut self, options: &Options) -> Result<()> {
+        Ok(())
+    }
+
+    /// Shutter interval, set by `TransformTimes`.
+    fn on_transform_times(&mut self, start: f32, end: f32) -> Result<()> {
+        Ok(())
+    }
+
+    fn on_camera(&mut self, camera: CameraEntity) -> Result<()> {
+        Ok(())
+    }
+
+    fn on_film(&mut self, film: Film) -> Result<()> {
+        Ok(())
+    }
+
+    fn on_sampler(&mut self, sampler: Sampler) -> Result<()> {
+        Ok(())
+    }
+
+    fn on_filter(&mut self, filter: Filter) -> Result<()> {
+        Ok(())
+    }
+
+    fn on_integrator(&mut self, integrator: Integrator) -> Result<()> {
+        Ok(())
+    }
+
+    fn on_accelerator(&mut self, accelerator: Accelerator) -> Result<()> {
+        Ok(())
+    }
+
+    fn on_texture(&mut self, index: usize, texture: Texture) -> Result<()> {
+        Ok(())
+    }
+
+    fn on_material(&mut self, index: usize, material: Material) -> Result<()> {
+        Ok(())
+    }
+
+    fn on_medium(&mut self, index: usize, medium: Medium) -> Result<()> {
+        Ok(())
+    }
+
+    fn on_light(&mut self, light: LightEntity) -> Result<()> {
+        Ok(())
+    }
+
+    fn on_area_light(&mut self, index: usize, area_light: AreaLight) -> Result<()> {
+        Ok(())
+    }
+
+    /// Shapes inside of object definitions are visited too, see [ShapeEntity::object_index].
+    fn on_shape(&mut self, index: usize, shape: ShapeEntity) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called at `ObjectEnd`, after all of the object's shapes were visited.
+    fn on_object(&mut self, index: usize, object: Object) -> Result<()> {
+        Ok(())
+    }
+
+    fn on_instance(&mut self, instance: InstanceEntity) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called at `AttributeBegin` with the CTM and the current material ([Scene::materials] index).
+    ///
+    /// `ObjectBegin` starts an attribute block too, so it's also reported (before
+    /// entering the object definition).
+    fn on_attribute_begin(&mut self, transform: Mat4, material_index: Option<usize>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called at `AttributeEnd`, and at `ObjectEnd` after [SceneVisitor::on_object].
+    fn on_attribute_end(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called for each external file reference, files referenced more than once are visited
+    /// each time.
+    fn on_dependency(&mut self, dependency: Dependency) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called for problems which don't stop loading, see [Scene::issues].
+    fn on_issue(&mut self, issue: ValidationIssue) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl Scene {
+    /// Load a scene from a string slice, handing entities to `visitor` instead of storing them.
+    ///
+    /// Memory usage doesn't depend on the scene size, which makes it possible to convert
+    /// scenes that don't fit in memory. [LoadOptions::keep_ast] is ignored.
+    ///
+    /// See [Scene::load] for arguments description.
+    pub fn visit<V: SceneVisitor>(
+        data: &str,
+        working_directory: Option<&Path>,
+        options: &LoadOptions,
+        visitor: &mut V,
+    ) -> Result<()> {
+        visit_impl(data, working_directory, None, options, visitor)
+    }
+
+    /// Load a scene file, handing entities to `visitor`.
+    ///
+    /// See [Scene::visit].
+    pub fn visit_file<P: AsRef<Path>, V: SceneVisitor>(
+        path: P,
+        options: &LoadOptions,
+        visitor: &mut V,
+    ) -> Result<()> {
+        let path = path.as_ref();
+        let data = fs::read(path)?;
+
+        // Problems with invalid UTF-8 come first.
+        let (data, issues) = decode(&data, Some(path), options)?;
+        for issue in issues {
+            visitor.on_issue(issue)?;
+        }
+
+        visit_impl(&data, path.parent(), Some(path), options, visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::Shape;
+
+    #[derive(Default)]
+    struct Counter {
+        shapes: Vec<(usize, Option<usize>, Option<usize>)>,
+        materials: usize,
+        objects: Vec<(usize, Object)>,
+        instances: usize,
+    }
+
+    impl SceneVisitor for Counter {
+        fn on_material(&mut self, index: usize, _material: Material) -> Result<()> {
+            assert_eq!(index, self.materials);
+            self.materials += 1;
+            Ok(())
+        }
+
+        fn on_shape(&mut self, index: usize, shape: ShapeEntity) -> Result<()> {
+            assert!(matches!(shape.params, Shape::Sphere { .. }));
+            self.shapes
+                .push((index, shape.material_index, shape.object_index));
+            Ok(())
+        }
+
+        fn on_object(&mut self, index: usize, object: Object) -> Result<()> {
+            self.objects.push((index, object));
+            Ok(())
+        }
+
+        fn on_instance(&mut self, instance: InstanceEntity) -> Result<()> {
+            assert_eq!(instance.object_index, 0);
+            self.instances += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn visit() -> Result<()> {
+        let data = r#"
+WorldBegin
+Material "diffuse"
+Shape "sphere"
+AttributeBegin
+  Material "conductor"
+  ObjectBegin "balls"
+    Shape "sphere"
+    Shape "sphere"
+  ObjectEnd
+AttributeEnd
+Shape "sphere"
+ObjectInstance "balls"
+ObjectInstance "balls"
+        "#;
+
+        let mut counter = Counter::default();
+        Scene::visit(data, None, &LoadOptions::default(), &mut counter)?;
+
+        assert_eq!(counter.materials, 2);
+        assert_eq!(
+            counter.shapes,
+            [
+                (0, Some(0), None),
+                (1, Some(1), Some(0)),
+                (2, Some(1), Some(0)),
+                (3, Some(0), None)
+            ]
+        );
+
+        assert_eq!(counter.objects.len(), 1);
+        let (index, object) = &counter.objects[0];
+        assert_eq!(*index, 0);
+        assert_eq!(object.shape_range(), Some(1..3));
+
+        assert_eq!(counter.instances, 2);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,1846 @@
+//! Scene writer.
+
+use std::{fmt::Display, io::Write};
+
+use crate::{
+    math::{float, Float, Mat4, Vec3},
+    param::{FromTypedParams, Spectrum, TexRef},
+    types::{
+        Accelerator, AreaLight, BvhSplitMethod, Camera, ColorSpace, CurveBasis, CurveType, Eta,
+        Film, FilmType, Filter, Integrator, Light, LightSampler, Material, MaterialType, Medium,
+        MediumType, RandomizeStrategy, Sampler, Shape, SphericalMapping, Texture, TextureClass,
+        TextureMapping, TextureType, TextureValue,
+    },
+    Result, Scene, ShapeEntity,
+};
+
+/// Options that control how scenes are written.
+#[derive(Debug, Clone)]
+pub struct WriteOptions {
+    /// Flatten transformations into shape vertex data.
+    ///
+    /// Shapes are written in world space without any transformation directives:
+    /// mesh positions, normals and tangents are transformed, analytic shapes are tessellated,
+    /// and object instances are expanded. Useful for debugging transformation issues and
+    /// for consumers which can't apply per-shape matrices.
+    ///
+    /// Only the transformation at the start of the shutter interval is used.
+    /// PLY meshes that were not loaded keep their transformation, as their vertex data is not available.
+    pub bake_transforms: bool,
+    /// Number of subdivisions around the z axis used to tessellate analytic shapes
+    /// when baking transformations, see [Shape::tessellate].
+    pub tessellation_segments: u32,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        WriteOptions {
+            bake_transforms: false,
+            tessellation_segments: 32,
+        }
+    }
+}
+
+impl Scene {
+    /// Write the scene in the pbrt-v4 format.
+    ///
+    /// Only the data represented by the parsed types is written, so parameters which are not
+    /// retained by [crate::types] are lost.
+    pub fn write<W: Write>(&self, out: W, options: &WriteOptions) -> Result<()> {
+        Writer {
+            out,
+            options,
+            textures: &self.textures,
+            indent: 0,
+        }
+        .scene(self)
+    }
+
+    /// Write the scene to a string, see [Scene::write].
+    pub fn to_pbrt_string(&self, options: &WriteOptions) -> Result<String> {
+        let mut out = Vec::new();
+        self.write(&mut out, options)?;
+
+        Ok(String::from_utf8(out).expect("Writer must produce valid UTF-8"))
+    }
+}
+
+struct Writer<'a, W> {
+    out: W,
+    options: &'a WriteOptions,
+    /// Named textures, to write texture references of materials.
+    textures: &'a [Texture],
+    indent: usize,
+}
+
+impl<'a, W: Write> Writer<'a, W> {
+    fn line(&mut self, line: impl Display) -> Result<()> {
+        writeln!(self.out, "{:indent$}{}", "", line, indent = self.indent * 4)?;
+        Ok(())
+    }
+
+    /// Write a parameter on its own line.
+    fn param<T: Display>(&mut self, ty: &str, name: &str, values: &[T]) -> Result<()> {
+        write!(
+            self.out,
+            "{:indent$}\"{} {}\" [",
+            "",
+            ty,
+            name,
+            indent = (self.indent + 1) * 4
+        )?;
+
+        for value in values {
+            write!(self.out, " {}", value)?;
+        }
+
+        writeln!(self.out, " ]")?;
+        Ok(())
+    }
+
+    fn string(&mut self, name: &str, value: &str) -> Result<()> {
+        self.param("string", name, &[quote(value)])
+    }
+
+    fn spectrum(&mut self, name: &str, spectrum: &Spectrum) -> Result<()> {
+        match spectrum {
+            Spectrum::Rgb(rgb) => self.param("rgb", name, rgb),
+            Spectrum::Blackbody(temperature) => self.param("blackbody", name, &[temperature]),
+            Spectrum::Named(spectrum) => self.param("spectrum", name, &[quote(spectrum)]),
+            Spectrum::Sampled(samples) => {
+                let values = samples
+                    .iter()
+                    .flat_map(|&(lambda, value)| [lambda, value])
+                    .collect::<Vec<_>>();
+                self.param("spectrum", name, &values)
+            }
+            Spectrum::File(filename) => self.param("spectrum", name, &[quote(filename)]),
+        }
+    }
+
+    fn float_texture(&mut self, name: &str, value: &TexRef<f32>) -> Result<()> {
+        match value {
+            TexRef::Value(value) => self.param("float", name, &[value]),
+            TexRef::Texture(index) => self.texture_ref(name, *index),
+        }
+    }
+
+    fn spectrum_texture(&mut self, name: &str, value: &TexRef<Spectrum>) -> Result<()> {
+        match value {
+            TexRef::Value(spectrum) => self.spectrum(name, spectrum),
+            TexRef::Texture(index) => self.texture_ref(name, *index),
+        }
+    }
+
+    fn texture_ref(&mut self, name: &str, index: usize) -> Result<()> {
+        let textures = self.textures;
+        self.param("texture", name, &[quote(&textures[index].name)])
+    }
+
+    fn texture_value(&mut self, name: &str, value: &TextureValue) -> Result<()> {
+        match value {
+            TextureValue::Float(value) => self.param("float", name, &[value]),
+            TextureValue::Spectrum(spectrum) => self.spectrum(name, spectrum),
+        }
+    }
+
+    fn texture_value_ref(&mut self, name: &str, value: &TexRef<TextureValue>) -> Result<()> {
+        match value {
+            TexRef::Value(value) => self.texture_value(name, value),
+            TexRef::Texture(index) => self.texture_ref(name, *index),
+        }
+    }
+
+    fn eta(&mut self, name: &str, eta: &Eta) -> Result<()> {
+        match eta {
+            Eta::Constant(eta) => self.param("float", name, &[eta]),
+            Eta::Spectrum(spectrum) => self.spectrum(name, spectrum),
+        }
+    }
+
+    fn attribute_begin(&mut self) -> Result<()> {
+        self.line("AttributeBegin")?;
+        self.indent += 1;
+        Ok(())
+    }
+
+    fn attribute_end(&mut self) -> Result<()> {
+        self.indent -= 1;
+        self.line("AttributeEnd")
+    }
+
+    /// Write `ColorSpace` unless it's the default one.
+    fn color_space(&mut self, color_space: ColorSpace) -> Result<()> {
+        if color_space == ColorSpace::default() {
+            return Ok(());
+        }
+
+        self.line(format_args!("ColorSpace {}", quote(color_space.name())))
+    }
+
+    fn matrix(&mut self, directive: &str, m: &Mat4) -> Result<()> {
+        let values = m
+            .to_cols_array()
+            .iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>();
+
+        self.line(format_args!("{} [ {} ]", directive, values.join(" ")))
+    }
+
+    /// Write start and end transformations, using `ActiveTransform` if they differ.
+    fn transform(&mut self, start: &Mat4, end: &Mat4) -> Result<()> {
+        if start == end {
+            return self.matrix("Transform", start);
+        }
+
+        self.line("ActiveTransform StartTime")?;
+        self.matrix("Transform", start)?;
+        self.line("ActiveTransform EndTime")?;
+        self.matrix("Transform", end)?;
+        self.line("ActiveTransform All")
+    }
+
+    fn scene(&mut self, scene: &Scene) -> Result<()> {
+        if scene.start_time != 0.0 || scene.end_time != 1.0 {
+            self.line(format_args!(
+                "TransformTimes {} {}",
+                scene.start_time, scene.end_time
+            ))?;
+        }
+
+        // Media are written first, so the camera can be placed in one.
+        for medium in &scene.mediums {
+            self.medium(medium)?;
+        }
+
+        if scene
+            .mediums
+            .iter()
+            .any(|medium| medium.transform != Mat4::IDENTITY)
+        {
+            self.line("Identity")?;
+        }
+
+        if let Some(camera) = &scene.camera {
+            // The CTM at the Camera directive is the world to camera transformation.
+            self.transform(&camera.transform.inverse(), &camera.transform_end.inverse())?;
+            self.medium_interface(scene, None, camera.exterior_medium_index)?;
+            self.camera(&camera.params)?;
+            self.line("Identity")?;
+
+            // The medium interface is not scoped outside of attribute blocks.
+            if camera.exterior_medium_index.is_some() {
+                self.medium_interface_names("", "")?;
+            }
+        }
+
+        if let Some(film) = &scene.film {
+            self.film(film)?;
+        }
+
+        if let Some(sampler) = &scene.sampler {
+            self.sampler(sampler)?;
+        }
+
+        if let Some(filter) = &scene.filter {
+            self.filter(filter)?;
+        }
+
+        if let Some(integrator) = &scene.integrator {
+            self.integrator(integrator)?;
+        }
+
+        if let Some(accelerator) = &scene.accelerator {
+            self.accelerator(accelerator)?;
+        }
+
+        self.line("")?;
+        self.line("WorldBegin")?;
+        self.line("")?;
+
+        for texture in &scene.textures {
+            let ty = match texture.ty {
+                TextureType::Float => "float",
+                TextureType::Spectrum => "spectrum",
+            };
+
+            // Named textures are not scoped, the attribute block only limits the color space
+            // and the transformation.
+            let scoped =
+                texture.color_space != ColorSpace::default() || texture.transform != Mat4::IDENTITY;
+            if scoped {
+                self.attribute_begin()?;
+                self.color_space(texture.color_space)?;
+
+                if texture.transform != Mat4::IDENTITY {
+                    self.matrix("Transform", &texture.transform)?;
+                }
+            }
+
+            self.line(format_args!(
+                "Texture {} \"{}\" {}",
+                quote(&texture.name),
+                ty,
+                quote(texture.class.type_name())
+            ))?;
+            self.texture(texture)?;
+
+            if scoped {
+                self.attribute_end()?;
+            }
+        }
+
+        // Named materials are scoped like textures, other materials are written with shapes.
+        for material in &scene.materials {
+            let Some(name) = &material.name else {
+                continue;
+            };
+
+            let scoped = material.color_space != ColorSpace::default();
+            if scoped {
+                self.attribute_begin()?;
+                self.color_space(material.color_space)?;
+            }
+
+            self.line(format_args!("MakeNamedMaterial {}", quote(name)))?;
+            self.string("type", material.ty.type_name())?;
+            self.material(material)?;
+
+            if scoped {
+                self.attribute_end()?;
+            }
+        }
+
+        for light in &scene.lights {
+            self.attribute_begin()?;
+            self.transform(&light.transform, &light.transform_end)?;
+            self.color_space(light.color_space)?;
+            self.medium_interface(scene, None, light.exterior_medium_index)?;
+            self.light(&light.params)?;
+            self.attribute_end()?;
+        }
+
+        if !self.options.bake_transforms {
+            // Objects without stored shapes are only declared.
+            for index in 0..scene.objects.len() {
+                if scene.object_shapes(index).is_empty() {
+                    self.object(scene, index)?;
+                }
+            }
+        }
+
+        for (index, shape) in scene.shapes.iter().enumerate() {
+            match shape.object_index {
+                // Write the whole object at its first shape to keep the original order.
+                Some(object_index) => {
+                    let is_first = scene.objects[object_index].shape_start == Some(index);
+                    if is_first && !self.options.bake_transforms {
+                        self.object(scene, object_index)?;
+                    }
+                }
+                None => self.shape(scene, shape, None)?,
+            }
+        }
+
+        for instance in &scene.instances {
+            let object = &scene.objects[instance.object_index];
+
+            if self.options.bake_transforms {
+                for shape in scene.object_shapes(instance.object_index) {
+                    self.shape(scene, shape, Some(&instance.instance_to_world))?;
+                }
+            } else {
+                self.attribute_begin()?;
+
+                if instance.reverse_orientation {
+                    self.line("ReverseOrientation")?;
+                }
+
+                self.transform(&instance.instance_to_world, &instance.instance_to_world_end)?;
+                self.line(format_args!("ObjectInstance {}", quote(&object.name)))?;
+                self.attribute_end()?;
+            }
+        }
+
+        self.out.flush()?;
+        Ok(())
+    }
+
+    /// Write a medium along with its transformation, which is not reset.
+    fn medium(&mut self, medium: &Medium) -> Result<()> {
+        if medium.transform != Mat4::IDENTITY {
+            self.matrix("Transform", &medium.transform)?;
+        }
+
+        self.line(format_args!("MakeNamedMedium {}", quote(&medium.name)))?;
+        self.string("type", medium.ty.type_name())?;
+
+        match &medium.ty {
+            MediumType::Homogeneous {
+                sigma_a,
+                sigma_s,
+                scale,
+                g,
+                le,
+                le_scale,
+                preset,
+            } => {
+                self.scattering(sigma_a, sigma_s, *scale, *g, preset.as_deref())?;
+                self.spectrum("Le", le)?;
+                self.param("float", "Lescale", &[le_scale])?;
+            }
+            MediumType::UniformGrid {
+                sigma_a,
+                sigma_s,
+                scale,
+                g,
+                density,
+                temperature,
+                temperature_cutoff,
+                temperature_scale,
+                le,
+                le_scale,
+                nx,
+                ny,
+                nz,
+                p0,
+                p1,
+                preset,
+            } => {
+                self.scattering(sigma_a, sigma_s, *scale, *g, preset.as_deref())?;
+                if !density.is_empty() {
+                    self.param("float", "density", density)?;
+                }
+                if !temperature.is_empty() {
+                    self.param("float", "temperature", temperature)?;
+                }
+                self.param("float", "temperaturecutoff", &[temperature_cutoff])?;
+                self.param("float", "temperaturescale", &[temperature_scale])?;
+                self.spectrum("Le", le)?;
+                self.param("float", "Lescale", &[le_scale])?;
+                self.grid_bounds(*nx, *ny, *nz, p0, p1)?;
+            }
+            MediumType::RgbGrid {
+                sigma_a,
+                sigma_s,
+                scale,
+                g,
+                le,
+                le_scale,
+                nx,
+                ny,
+                nz,
+                p0,
+                p1,
+            } => {
+                if !sigma_a.is_empty() {
+                    self.param("rgb", "sigma_a", sigma_a)?;
+                }
+                if !sigma_s.is_empty() {
+                    self.param("rgb", "sigma_s", sigma_s)?;
+                }
+                self.param("float", "scale", &[scale])?;
+                self.param("float", "g", &[g])?;
+                if !le.is_empty() {
+                    self.param("rgb", "Le", le)?;
+                }
+                self.param("float", "Lescale", &[le_scale])?;
+                self.grid_bounds(*nx, *ny, *nz, p0, p1)?;
+            }
+            MediumType::Cloud {
+                sigma_a,
+                sigma_s,
+                scale,
+                g,
+                density,
+                wispiness,
+                frequency,
+                p0,
+                p1,
+            } => {
+                self.scattering(sigma_a, sigma_s, *scale, *g, None)?;
+                self.param("float", "density", &[density])?;
+                self.param("float", "wispiness", &[wispiness])?;
+                self.param("float", "frequency", &[frequency])?;
+                self.param("point3", "p0", p0)?;
+                self.param("point3", "p1", p1)?;
+            }
+            MediumType::NanoVdb {
+                filename,
+                sigma_a,
+                sigma_s,
+                scale,
+                g,
+                le_scale,
+                temperature_cutoff,
+                temperature_scale,
+                preset,
+            } => {
+                self.string("filename", filename)?;
+                self.scattering(sigma_a, sigma_s, *scale, *g, preset.as_deref())?;
+                self.param("float", "Lescale", &[le_scale])?;
+                self.param("float", "temperaturecutoff", &[temperature_cutoff])?;
+                self.param("float", "temperaturescale", &[temperature_scale])?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Scattering parameters shared by most media.
+    fn scattering(
+        &mut self,
+        sigma_a: &Spectrum,
+        sigma_s: &Spectrum,
+        scale: f32,
+        g: f32,
+        preset: Option<&str>,
+    ) -> Result<()> {
+        self.spectrum("sigma_a", sigma_a)?;
+        self.spectrum("sigma_s", sigma_s)?;
+        self.param("float", "scale", &[scale])?;
+        self.param("float", "g", &[g])?;
+        if let Some(preset) = preset {
+            self.string("preset", preset)?;
+        }
+        Ok(())
+    }
+
+    /// Resolution and bounds of grid media.
+    fn grid_bounds(
+        &mut self,
+        nx: i32,
+        ny: i32,
+        nz: i32,
+        p0: &[Float; 3],
+        p1: &[Float; 3],
+    ) -> Result<()> {
+        self.param("integer", "nx", &[nx])?;
+        self.param("integer", "ny", &[ny])?;
+        self.param("integer", "nz", &[nz])?;
+        self.param("point3", "p0", p0)?;
+        self.param("point3", "p1", p1)
+    }
+
+    /// Write `MediumInterface` with media at indices in [Scene::mediums], unless both are vacuum.
+    fn medium_interface(
+        &mut self,
+        scene: &Scene,
+        interior: Option<usize>,
+        exterior: Option<usize>,
+    ) -> Result<()> {
+        if interior.is_none() && exterior.is_none() {
+            return Ok(());
+        }
+
+        let name = |index: Option<usize>| index.map_or("", |index| &scene.mediums[index].name);
+        self.medium_interface_names(name(interior), name(exterior))
+    }
+
+    fn medium_interface_names(&mut self, interior: &str, exterior: &str) -> Result<()> {
+        self.line(format_args!(
+            "MediumInterface {} {}",
+            quote(interior),
+            quote(exterior)
+        ))
+    }
+
+    fn object(&mut self, scene: &Scene, object_index: usize) -> Result<()> {
+        let name = &scene.objects[object_index].name;
+        self.line(format_args!("ObjectBegin {}", quote(name)))?;
+        self.indent += 1;
+
+        for shape in scene.object_shapes(object_index) {
+            self.shape(scene, shape, None)?;
+        }
+
+        self.indent -= 1;
+        self.line("ObjectEnd")
+    }
+
+    fn camera(&mut self, camera: &Camera) -> Result<()> {
+        self.line(format_args!("Camera \"{}\"", camera.type_name()))?;
+        self.param("float", "shutteropen", &[camera.shutter_open()])?;
+        self.param("float", "shutterclose", &[camera.shutter_close()])?;
+
+        match camera {
+            Camera::Orthographic {
+                frame_aspect_ratio,
+                screen_window,
+                lens_radius,
+                focal_distance,
+                ..
+            } => self.projective(
+                *frame_aspect_ratio,
+                *screen_window,
+                *lens_radius,
+                *focal_distance,
+            )?,
+            Camera::Perspective {
+                frame_aspect_ratio,
+                screen_window,
+                lens_radius,
+                focal_distance,
+                fov,
+                ..
+            } => {
+                self.projective(
+                    *frame_aspect_ratio,
+                    *screen_window,
+                    *lens_radius,
+                    *focal_distance,
+                )?;
+                self.param("float", "fov", &[fov])?;
+            }
+            Camera::Realistic {
+                lensfile,
+                aperture_diameter,
+                focus_distance,
+                aperture,
+                ..
+            } => {
+                if let Some(lensfile) = lensfile {
+                    self.string("lensfile", lensfile)?;
+                }
+                self.param("float", "aperturediameter", &[aperture_diameter])?;
+                self.param("float", "focusdistance", &[focus_distance])?;
+                if let Some(aperture) = aperture {
+                    self.string("aperture", aperture)?;
+                }
+            }
+            Camera::Spherical { mapping, .. } => {
+                let mapping = match mapping {
+                    SphericalMapping::EqualArea => "equalarea",
+                    SphericalMapping::Equirectangular => "equirectangular",
+                };
+                self.string("mapping", mapping)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parameters shared by the orthographic and perspective cameras.
+    fn projective(
+        &mut self,
+        frame_aspect_ratio: Option<f32>,
+        screen_window: Option<[f32; 4]>,
+        lens_radius: f32,
+        focal_distance: f32,
+    ) -> Result<()> {
+        if let Some(frame_aspect_ratio) = frame_aspect_ratio {
+            self.param("float", "frameaspectratio", &[frame_aspect_ratio])?;
+        }
+        if let Some(screen_window) = screen_window {
+            self.param("float", "screenwindow", &screen_window)?;
+        }
+        self.param("float", "lensradius", &[lens_radius])?;
+        self.param("float", "focaldistance", &[focal_distance])
+    }
+
+    fn film(&mut self, film: &Film) -> Result<()> {
+        self.line(format_args!("Film \"{}\"", film.ty.type_name()))?;
+        self.param("integer", "xresolution", &[film.xresolution])?;
+        self.param("integer", "yresolution", &[film.yresolution])?;
+        self.param("float", "cropwindow", &film.crop_window)?;
+        if let Some(pixel_bounds) = &film.pixel_bounds {
+            self.param("integer", "pixelbounds", pixel_bounds)?;
+        }
+        self.param("float", "diagonal", &[film.diagonal])?;
+        self.string("filename", &film.filename)?;
+        self.param("bool", "savefp16", &[film.save_fp16])?;
+        self.param("float", "iso", &[film.iso])?;
+        self.param("float", "whitebalance", &[film.white_balance])?;
+        self.string("sensor", &film.sensor)?;
+        self.param("float", "maxcomponentvalue", &[film.max_component_value])?;
+
+        match &film.ty {
+            FilmType::Rgb => {}
+            FilmType::GBuffer { coordinate_system } => {
+                self.string("coordinatesystem", coordinate_system)?
+            }
+            FilmType::Spectral {
+                nbuckets,
+                lambda_min,
+                lambda_max,
+            } => {
+                self.param("integer", "nbuckets", &[nbuckets])?;
+                self.param("float", "lambdamin", &[lambda_min])?;
+                self.param("float", "lambdamax", &[lambda_max])?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn sampler(&mut self, sampler: &Sampler) -> Result<()> {
+        self.line(format_args!("Sampler \"{}\"", sampler.type_name()))?;
+
+        let (randomization, seed) = match *sampler {
+            Sampler::Halton {
+                randomization,
+                seed,
+                ..
+            }
+            | Sampler::PaddedSobol {
+                randomization,
+                seed,
+                ..
+            }
+            | Sampler::Sobol {
+                randomization,
+                seed,
+                ..
+            }
+            | Sampler::ZSobol {
+                randomization,
+                seed,
+                ..
+            } => (Some(randomization), seed),
+            Sampler::Independent { seed, .. } | Sampler::Stratified { seed, .. } => (None, seed),
+        };
+
+        match *sampler {
+            Sampler::Stratified {
+                jitter,
+                x_samples,
+                y_samples,
+                ..
+            } => {
+                self.param("bool", "jitter", &[jitter])?;
+                self.param("integer", "xsamples", &[x_samples])?;
+                self.param("integer", "ysamples", &[y_samples])?;
+            }
+            _ => self.param("integer", "pixelsamples", &[sampler.pixel_samples()])?,
+        }
+
+        if let Some(randomization) = randomization {
+            let randomization = match randomization {
+                RandomizeStrategy::None => "none",
+                RandomizeStrategy::PermuteDigits => "permutedigits",
+                RandomizeStrategy::FastOwen => "fastowen",
+                RandomizeStrategy::Owen => "owen",
+            };
+            self.string("randomization", randomization)?;
+        }
+
+        self.param("integer", "seed", &[seed])
+    }
+
+    fn filter(&mut self, filter: &Filter) -> Result<()> {
+        self.line(format_args!("PixelFilter \"{}\"", filter.type_name()))?;
+
+        let [x_radius, y_radius] = filter.radius();
+        self.param("float", "xradius", &[x_radius])?;
+        self.param("float", "yradius", &[y_radius])?;
+
+        match filter {
+            Filter::Box { .. } | Filter::Triangle { .. } => {}
+            Filter::Gaussian { sigma, .. } => self.param("float", "sigma", &[sigma])?,
+            Filter::Mitchell { b, c, .. } => {
+                self.param("float", "B", &[b])?;
+                self.param("float", "C", &[c])?;
+            }
+            Filter::Sinc { tau, .. } => self.param("float", "tau", &[tau])?,
+        }
+
+        Ok(())
+    }
+
+    fn integrator(&mut self, integrator: &Integrator) -> Result<()> {
+        self.line(format_args!("Integrator \"{}\"", integrator.type_name()))?;
+
+        match integrator {
+            Integrator::AmbientOcclusion {
+                cos_sample,
+                max_distance,
+            } => {
+                self.param("bool", "cossample", &[cos_sample])?;
+                if max_distance.is_finite() {
+                    self.param("float", "maxdistance", &[max_distance])?;
+                }
+            }
+            Integrator::Bdpt {
+                max_depth,
+                light_sampler,
+                regularize,
+                visualize_strategies,
+                visualize_weights,
+            } => {
+                self.param("integer", "maxdepth", &[max_depth])?;
+                self.light_sampler(*light_sampler)?;
+                self.param("bool", "regularize", &[regularize])?;
+                self.param("bool", "visualizestrategies", &[visualize_strategies])?;
+                self.param("bool", "visualizeweights", &[visualize_weights])?;
+            }
+            Integrator::LightPath { max_depth }
+            | Integrator::RandomWalk { max_depth }
+            | Integrator::SimpleVolPath { max_depth } => {
+                self.param("integer", "maxdepth", &[max_depth])?;
+            }
+            Integrator::Mlt {
+                max_depth,
+                bootstrap_samples,
+                chains,
+                mutations_per_pixel,
+                large_step_probability,
+                sigma,
+                regularize,
+            } => {
+                self.param("integer", "maxdepth", &[max_depth])?;
+                self.param("integer", "bootstrapsamples", &[bootstrap_samples])?;
+                self.param("integer", "chains", &[chains])?;
+                self.param("integer", "mutationsperpixel", &[mutations_per_pixel])?;
+                self.param("float", "largestepprobability", &[large_step_probability])?;
+                self.param("float", "sigma", &[sigma])?;
+                self.param("bool", "regularize", &[regularize])?;
+            }
+            Integrator::Path {
+                max_depth,
+                light_sampler,
+                regularize,
+            }
+            | Integrator::VolPath {
+                max_depth,
+                light_sampler,
+                regularize,
+            } => {
+                self.param("integer", "maxdepth", &[max_depth])?;
+                self.light_sampler(*light_sampler)?;
+                self.param("bool", "regularize", &[regularize])?;
+            }
+            Integrator::SimplePath {
+                max_depth,
+                sample_lights,
+                sample_bsdf,
+            } => {
+                self.param("integer", "maxdepth", &[max_depth])?;
+                self.param("bool", "samplelights", &[sample_lights])?;
+                self.param("bool", "samplebsdf", &[sample_bsdf])?;
+            }
+            Integrator::Sppm {
+                max_depth,
+                photons_per_iteration,
+                radius,
+                seed,
+            } => {
+                self.param("integer", "maxdepth", &[max_depth])?;
+                self.param("integer", "photonsperiteration", &[photons_per_iteration])?;
+                self.param("float", "radius", &[radius])?;
+                self.param("integer", "seed", &[seed])?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn light_sampler(&mut self, light_sampler: LightSampler) -> Result<()> {
+        let light_sampler = match light_sampler {
+            LightSampler::Uniform => "uniform",
+            LightSampler::Power => "power",
+            LightSampler::Bvh => "bvh",
+            LightSampler::Exhaustive => "exhaustive",
+        };
+        self.string("lightsampler", light_sampler)
+    }
+
+    /// Write material parameters, the directive is written by the caller.
+    fn texture(&mut self, texture: &Texture) -> Result<()> {
+        // The default mapping is omitted, to keep the common case short.
+        if texture.mapping != TextureMapping::default() {
+            self.string("mapping", texture.mapping.type_name())?;
+
+            match &texture.mapping {
+                TextureMapping::Uv {
+                    u_scale,
+                    v_scale,
+                    u_delta,
+                    v_delta,
+                } => {
+                    self.param("float", "uscale", &[u_scale])?;
+                    self.param("float", "vscale", &[v_scale])?;
+                    self.param("float", "udelta", &[u_delta])?;
+                    self.param("float", "vdelta", &[v_delta])?;
+                }
+                TextureMapping::Spherical | TextureMapping::Cylindrical => {}
+                TextureMapping::Planar {
+                    v1,
+                    v2,
+                    u_delta,
+                    v_delta,
+                } => {
+                    self.param("vector3", "v1", v1)?;
+                    self.param("vector3", "v2", v2)?;
+                    self.param("float", "udelta", &[u_delta])?;
+                    self.param("float", "vdelta", &[v_delta])?;
+                }
+            }
+        }
+
+        match &texture.class {
+            TextureClass::Bilerp { v00, v01, v10, v11 } => {
+                self.texture_value("v00", v00)?;
+                self.texture_value("v01", v01)?;
+                self.texture_value("v10", v10)?;
+                self.texture_value("v11", v11)?;
+            }
+            TextureClass::Checkerboard {
+                dimension,
+                tex1,
+                tex2,
+            } => {
+                self.param("integer", "dimension", &[dimension])?;
+                self.texture_value_ref("tex1", tex1)?;
+                self.texture_value_ref("tex2", tex2)?;
+            }
+            TextureClass::Constant { value } => self.texture_value("value", value)?,
+            TextureClass::DirectionMix { tex1, tex2, dir } => {
+                self.texture_value_ref("tex1", tex1)?;
+                self.texture_value_ref("tex2", tex2)?;
+                self.param("vector3", "dir", dir)?;
+            }
+            TextureClass::Dots { inside, outside } => {
+                self.texture_value_ref("inside", inside)?;
+                self.texture_value_ref("outside", outside)?;
+            }
+            TextureClass::Fbm { octaves, roughness }
+            | TextureClass::Wrinkled { octaves, roughness } => {
+                self.param("integer", "octaves", &[octaves])?;
+                self.param("float", "roughness", &[roughness])?;
+            }
+            TextureClass::ImageMap(image) => {
+                self.string("filename", &image.filename)?;
+                self.string("wrap", image.wrap.name())?;
+                self.string("encoding", &image.encoding.name())?;
+                self.param("float", "scale", &[image.scale])?;
+                self.param("bool", "invert", &[image.invert])?;
+                self.string("filter", image.filter.name())?;
+                self.param("float", "maxanisotropy", &[image.max_anisotropy])?;
+            }
+            TextureClass::Marble {
+                octaves,
+                roughness,
+                scale,
+                variation,
+            } => {
+                self.param("integer", "octaves", &[octaves])?;
+                self.param("float", "roughness", &[roughness])?;
+                self.param("float", "scale", &[scale])?;
+                self.param("float", "variation", &[variation])?;
+            }
+            TextureClass::Mix { tex1, tex2, amount } => {
+                self.texture_value_ref("tex1", tex1)?;
+                self.texture_value_ref("tex2", tex2)?;
+                self.float_texture("amount", amount)?;
+            }
+            TextureClass::Ptex {
+                filename,
+                encoding,
+                scale,
+            } => {
+                self.string("filename", filename)?;
+                self.string("encoding", &encoding.name())?;
+                self.param("float", "scale", &[scale])?;
+            }
+            TextureClass::Scale { tex, scale } => {
+                self.texture_value_ref("tex", tex)?;
+                self.float_texture("scale", scale)?;
+            }
+            TextureClass::Windy => {}
+        }
+
+        Ok(())
+    }
+
+    fn material(&mut self, material: &Material) -> Result<()> {
+        if let Some(displacement) = &material.displacement {
+            self.float_texture("displacement", displacement)?;
+        }
+        if let Some(normal_map) = &material.normal_map {
+            self.string("normalmap", normal_map)?;
+        }
+
+        match &material.ty {
+            MaterialType::CoatedDiffuse {
+                reflectance,
+                roughness,
+                u_roughness,
+                v_roughness,
+                thickness,
+                eta,
+                max_depth,
+                n_samples,
+                g,
+                albedo,
+                remap_roughness,
+            } => {
+                self.spectrum_texture("reflectance", reflectance)?;
+                self.float_texture("roughness", roughness)?;
+                self.float_texture("uroughness", u_roughness)?;
+                self.float_texture("vroughness", v_roughness)?;
+                self.float_texture("thickness", thickness)?;
+                self.eta("eta", eta)?;
+                self.param("integer", "maxdepth", &[max_depth])?;
+                self.param("integer", "nsamples", &[n_samples])?;
+                self.float_texture("g", g)?;
+                self.spectrum_texture("albedo", albedo)?;
+                self.param("bool", "remaproughness", &[remap_roughness])?;
+            }
+            MaterialType::CoatedConductor {
+                interface_roughness,
+                interface_u_roughness,
+                interface_v_roughness,
+                thickness,
+                interface_eta,
+                conductor_roughness,
+                conductor_u_roughness,
+                conductor_v_roughness,
+                conductor_eta,
+                conductor_k,
+                reflectance,
+                max_depth,
+                n_samples,
+                g,
+                albedo,
+                remap_roughness,
+            } => {
+                self.float_texture("interface.roughness", interface_roughness)?;
+                self.float_texture("interface.uroughness", interface_u_roughness)?;
+                self.float_texture("interface.vroughness", interface_v_roughness)?;
+                self.float_texture("thickness", thickness)?;
+                self.eta("interface.eta", interface_eta)?;
+                self.float_texture("conductor.roughness", conductor_roughness)?;
+                self.float_texture("conductor.uroughness", conductor_u_roughness)?;
+                self.float_texture("conductor.vroughness", conductor_v_roughness)?;
+                match reflectance {
+                    Some(reflectance) => self.spectrum_texture("reflectance", reflectance)?,
+                    None => {
+                        self.spectrum_texture("conductor.eta", conductor_eta)?;
+                        self.spectrum_texture("conductor.k", conductor_k)?;
+                    }
+                }
+                self.param("integer", "maxdepth", &[max_depth])?;
+                self.param("integer", "nsamples", &[n_samples])?;
+                self.float_texture("g", g)?;
+                self.spectrum_texture("albedo", albedo)?;
+                self.param("bool", "remaproughness", &[remap_roughness])?;
+            }
+            MaterialType::Conductor {
+                eta,
+                k,
+                reflectance,
+                roughness,
+                u_roughness,
+                v_roughness,
+                remap_roughness,
+            } => {
+                match reflectance {
+                    Some(reflectance) => self.spectrum_texture("reflectance", reflectance)?,
+                    None => {
+                        self.spectrum_texture("eta", eta)?;
+                        self.spectrum_texture("k", k)?;
+                    }
+                }
+                self.float_texture("roughness", roughness)?;
+                self.float_texture("uroughness", u_roughness)?;
+                self.float_texture("vroughness", v_roughness)?;
+                self.param("bool", "remaproughness", &[remap_roughness])?;
+            }
+            MaterialType::Dielectric {
+                eta,
+                roughness,
+                u_roughness,
+                v_roughness,
+                remap_roughness,
+            } => {
+                self.eta("eta", eta)?;
+                self.float_texture("roughness", roughness)?;
+                self.float_texture("uroughness", u_roughness)?;
+                self.float_texture("vroughness", v_roughness)?;
+                self.param("bool", "remaproughness", &[remap_roughness])?;
+            }
+            MaterialType::ThinDielectric { eta } => self.eta("eta", eta)?,
+            MaterialType::Diffuse { reflectance } => {
+                self.spectrum_texture("reflectance", reflectance)?;
+            }
+            MaterialType::DiffuseTransmission {
+                reflectance,
+                transmittance,
+                scale,
+            } => {
+                self.spectrum_texture("reflectance", reflectance)?;
+                self.spectrum_texture("transmittance", transmittance)?;
+                self.param("float", "scale", &[scale])?;
+            }
+            MaterialType::Hair {
+                sigma_a,
+                color,
+                eumelanin,
+                pheomelanin,
+                eta,
+                beta_m,
+                beta_n,
+                alpha,
+            } => {
+                if let Some(sigma_a) = sigma_a {
+                    self.spectrum_texture("sigma_a", sigma_a)?;
+                }
+                if let Some(color) = color {
+                    self.spectrum_texture("color", color)?;
+                }
+                if let Some(eumelanin) = eumelanin {
+                    self.float_texture("eumelanin", eumelanin)?;
+                }
+                if let Some(pheomelanin) = pheomelanin {
+                    self.float_texture("pheomelanin", pheomelanin)?;
+                }
+                self.float_texture("eta", eta)?;
+                self.float_texture("beta_m", beta_m)?;
+                self.float_texture("beta_n", beta_n)?;
+                self.float_texture("alpha", alpha)?;
+            }
+            MaterialType::Measured { filename } => self.string("filename", filename)?,
+            MaterialType::Mix { materials, amount } => {
+                self.param(
+                    "string",
+                    "materials",
+                    &[quote(&materials[0]), quote(&materials[1])],
+                )?;
+                self.float_texture("amount", amount)?;
+            }
+            MaterialType::Subsurface {
+                eta,
+                g,
+                mfp,
+                reflectance,
+                sigma_a,
+                sigma_s,
+                scale,
+                name,
+                roughness,
+                u_roughness,
+                v_roughness,
+                remap_roughness,
+            } => {
+                self.param("float", "eta", &[eta])?;
+                self.param("float", "g", &[g])?;
+                if let Some(mfp) = mfp {
+                    self.spectrum_texture("mfp", mfp)?;
+                }
+                if let Some(reflectance) = reflectance {
+                    self.spectrum_texture("reflectance", reflectance)?;
+                }
+                if let Some(sigma_a) = sigma_a {
+                    self.spectrum_texture("sigma_a", sigma_a)?;
+                }
+                if let Some(sigma_s) = sigma_s {
+                    self.spectrum_texture("sigma_s", sigma_s)?;
+                }
+                self.param("float", "scale", &[scale])?;
+                if let Some(name) = name {
+                    self.string("name", name)?;
+                }
+                self.float_texture("roughness", roughness)?;
+                self.float_texture("uroughness", u_roughness)?;
+                self.float_texture("vroughness", v_roughness)?;
+                self.param("bool", "remaproughness", &[remap_roughness])?;
+            }
+            MaterialType::Interface => {}
+        }
+
+        Ok(())
+    }
+
+    fn accelerator(&mut self, accelerator: &Accelerator) -> Result<()> {
+        match accelerator {
+            Accelerator::Bvh {
+                max_node_prims,
+                split_method,
+            } => {
+                let split_method = match split_method {
+                    BvhSplitMethod::Sah => "sah",
+                    BvhSplitMethod::Middle => "middle",
+                    BvhSplitMethod::Equal => "equal",
+                    BvhSplitMethod::Hlbvh => "hlbvh",
+                };
+
+                self.line("Accelerator \"bvh\"")?;
+                self.param("integer", "maxnodeprims", &[max_node_prims])?;
+                self.string("splitmethod", split_method)?;
+            }
+            Accelerator::KdTree {
+                intersect_cost,
+                traversal_cost,
+                empty_bonus,
+                max_prims,
+                max_depth,
+            } => {
+                self.line("Accelerator \"kdtree\"")?;
+                self.param("integer", "intersectcost", &[intersect_cost])?;
+                self.param("integer", "traversalcost", &[traversal_cost])?;
+                self.param("float", "emptybonus", &[empty_bonus])?;
+                self.param("integer", "maxprims", &[max_prims])?;
+                self.param("integer", "maxdepth", &[max_depth])?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn light(&mut self, light: &Light) -> Result<()> {
+        self.line(format_args!("LightSource \"{}\"", light.type_name()))?;
+
+        match light {
+            Light::Distant => {}
+            Light::GonioPhotometric {
+                spectrum,
+                scale,
+                power,
+                filename,
+            } => {
+                self.emission(spectrum.as_ref(), *scale, *power)?;
+                self.string("filename", filename)?;
+            }
+            Light::Infinite { filename, spectrum } => {
+                if let Some(filename) = filename {
+                    self.string("filename", filename)?;
+                }
+                if let Some(spectrum) = spectrum {
+                    self.spectrum("L", spectrum)?;
+                }
+            }
+            Light::Point {
+                spectrum,
+                scale,
+                power,
+                from,
+            } => {
+                self.emission(spectrum.as_ref(), *scale, *power)?;
+                self.param("point3", "from", from)?;
+            }
+            Light::Projection {
+                scale,
+                power,
+                fov,
+                filename,
+            } => {
+                self.emission(None, *scale, *power)?;
+                self.param("float", "fov", &[fov])?;
+                self.string("filename", filename)?;
+            }
+            Light::Spot {
+                spectrum,
+                scale,
+                power,
+                cone_angle,
+                cone_delta_angle,
+                from,
+                to,
+            } => {
+                self.emission(spectrum.as_ref(), *scale, *power)?;
+                self.param("float", "coneangle", &[cone_angle])?;
+                self.param("float", "conedeltaangle", &[cone_delta_angle])?;
+                self.param("point3", "from", from)?;
+                self.param("point3", "to", to)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write the emission parameters shared by point lights.
+    fn emission(
+        &mut self,
+        spectrum: Option<&Spectrum>,
+        scale: f32,
+        power: Option<f32>,
+    ) -> Result<()> {
+        if let Some(spectrum) = spectrum {
+            self.spectrum("I", spectrum)?;
+        }
+        self.param("float", "scale", &[scale])?;
+        if let Some(power) = power {
+            self.param("float", "power", &[power])?;
+        }
+
+        Ok(())
+    }
+
+    fn area_light(&mut self, area_light: &AreaLight) -> Result<()> {
+        let AreaLight::Diffuse {
+            filename,
+            two_sided,
+            spectrum,
+            scale,
+        } = area_light;
+
+        self.line("AreaLightSource \"diffuse\"")?;
+
+        if let Some(filename) = filename {
+            self.string("filename", filename)?;
+        }
+        self.param("bool", "twosided", &[two_sided])?;
+        if let Some(spectrum) = spectrum {
+            self.spectrum("L", spectrum)?;
+        }
+        self.param("float", "scale", &[scale])?;
+
+        Ok(())
+    }
+
+    /// Write a shape in its own attribute block.
+    ///
+    /// `instance_to_world` is set when expanding object instances.
+    fn shape(
+        &mut self,
+        scene: &Scene,
+        shape: &ShapeEntity,
+        instance_to_world: Option<&Mat4>,
+    ) -> Result<()> {
+        self.attribute_begin()?;
+
+        if let Some(index) = shape.material_index {
+            let material = &scene.materials[index];
+            match &material.name {
+                Some(name) => self.line(format_args!("NamedMaterial {}", quote(name)))?,
+                None => {
+                    self.color_space(material.color_space)?;
+                    self.line(format_args!("Material \"{}\"", material.ty.type_name()))?;
+                    self.material(material)?;
+                }
+            }
+        }
+
+        if let Some(index) = shape.area_light_index {
+            self.area_light(&scene.area_lights[index])?;
+        }
+
+        self.medium_interface(
+            scene,
+            shape.interior_medium_index,
+            shape.exterior_medium_index,
+        )?;
+
+        let baked = if self.options.bake_transforms {
+            let transform = match instance_to_world {
+                Some(instance_to_world) => *instance_to_world * shape.transform,
+                None => shape.transform,
+            };
+
+            bake(
+                &shape.params,
+                &transform,
+                self.options.tessellation_segments,
+            )
+            .map(|mesh| {
+                // Transformations which swap handedness flip the orientation.
+                let swaps_handedness = transform.determinant() < 0.0;
+                (mesh, swaps_handedness)
+            })
+        } else {
+            None
+        };
+
+        match baked {
+            Some((mesh, swaps_handedness)) => {
+                if shape.reverse_orientation != swaps_handedness {
+                    self.line("ReverseOrientation")?;
+                }
+
+                self.shape_params(&mesh)?;
+            }
+            None => {
+                if shape.reverse_orientation {
+                    self.line("ReverseOrientation")?;
+                }
+
+                match instance_to_world {
+                    Some(instance_to_world) => {
+                        self.matrix("Transform", &(*instance_to_world * shape.transform))?
+                    }
+                    None => self.transform(&shape.transform, &shape.transform_end)?,
+                }
+
+                self.shape_params(&shape.params)?;
+            }
+        }
+
+        self.attribute_end()
+    }
+
+    fn shape_params(&mut self, shape: &Shape) -> Result<()> {
+        match shape {
+            Shape::Cylinder {
+                alpha,
+                radius,
+                zmin,
+                zmax,
+                phimax,
+            } => {
+                self.line("Shape \"cylinder\"")?;
+                self.param("float", "alpha", &[alpha])?;
+                self.param("float", "radius", &[radius])?;
+                self.param("float", "zmin", &[zmin])?;
+                self.param("float", "zmax", &[zmax])?;
+                self.param("float", "phimax", &[phimax])?;
+            }
+            Shape::Disk {
+                alpha,
+                height,
+                radius,
+                innerradius,
+                phimax,
+            } => {
+                self.line("Shape \"disk\"")?;
+                self.param("float", "alpha", &[alpha])?;
+                self.param("float", "height", &[height])?;
+                self.param("float", "radius", &[radius])?;
+                self.param("float", "innerradius", &[innerradius])?;
+                self.param("float", "phimax", &[phimax])?;
+            }
+            Shape::Sphere {
+                alpha,
+                radius,
+                zmin,
+                zmax,
+                phimax,
+            } => {
+                self.line("Shape \"sphere\"")?;
+                self.param("float", "alpha", &[alpha])?;
+                self.param("float", "radius", &[radius])?;
+                self.param("float", "zmin", &[zmin])?;
+                self.param("float", "zmax", &[zmax])?;
+                self.param("float", "phimax", &[phimax])?;
+            }
+            Shape::TriangleMesh {
+                alpha,
+                indices,
+                positions,
+                normals,
+                tangents,
+                uvs,
+                face_indices,
+            } => {
+                self.line("Shape \"trianglemesh\"")?;
+                self.param("float", "alpha", &[alpha])?;
+                self.param("integer", "indices", indices)?;
+                self.param("point3", "P", positions)?;
+
+                if !normals.is_empty() {
+                    self.param("normal", "N", normals)?;
+                }
+                if !tangents.is_empty() {
+                    self.param("vector3", "S", tangents)?;
+                }
+                if !uvs.is_empty() {
+                    self.param("point2", "uv", uvs)?;
+                }
+                if !face_indices.is_empty() {
+                    self.param("integer", "faceIndices", face_indices)?;
+                }
+            }
+            Shape::LoopSubdiv {
+                levels,
+                indices,
+                positions,
+            } => {
+                self.line("Shape \"loopsubdiv\"")?;
+                self.param("integer", "levels", &[levels])?;
+                self.param("integer", "indices", indices)?;
+                self.param("point3", "P", positions)?;
+            }
+            Shape::BilinearMesh {
+                alpha,
+                indices,
+                positions,
+                normals,
+                uvs,
+                face_indices,
+                emission_filename,
+            } => {
+                self.line("Shape \"bilinearmesh\"")?;
+                self.param("float", "alpha", &[alpha])?;
+                if !indices.is_empty() {
+                    self.param("integer", "indices", indices)?;
+                }
+                self.param("point3", "P", positions)?;
+
+                if !normals.is_empty() {
+                    self.param("normal", "N", normals)?;
+                }
+                if !uvs.is_empty() {
+                    self.param("point2", "uv", uvs)?;
+                }
+                if !face_indices.is_empty() {
+                    self.param("integer", "faceIndices", face_indices)?;
+                }
+                if let Some(emission_filename) = emission_filename {
+                    self.string("emissionfilename", emission_filename)?;
+                }
+            }
+            Shape::Curve {
+                alpha,
+                positions,
+                basis,
+                degree,
+                ty,
+                normals,
+                width: _,
+                width0,
+                width1,
+                split_depth,
+            } => {
+                self.line("Shape \"curve\"")?;
+                self.param("float", "alpha", &[alpha])?;
+                self.param("point3", "P", positions)?;
+
+                let basis = match basis {
+                    CurveBasis::Bezier => "bezier",
+                    CurveBasis::BSpline => "bspline",
+                };
+                self.string("basis", basis)?;
+                self.param("integer", "degree", &[degree])?;
+
+                let ty = match ty {
+                    CurveType::Flat => "flat",
+                    CurveType::Cylinder => "cylinder",
+                    CurveType::Ribbon => "ribbon",
+                };
+                self.string("type", ty)?;
+
+                if !normals.is_empty() {
+                    self.param("normal", "N", normals)?;
+                }
+                // `width` only provides the defaults.
+                self.param("float", "width0", &[width0])?;
+                self.param("float", "width1", &[width1])?;
+                self.param("integer", "splitdepth", &[split_depth])?;
+            }
+            Shape::PlyMesh { filename, .. } => {
+                self.line("Shape \"plymesh\"")?;
+                self.string("filename", filename)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Transform shape's geometry to world space.
+///
+/// Returns `None` if vertex data is not available.
+fn bake(shape: &Shape, transform: &Mat4, segments: u32) -> Option<Shape> {
+    if let Some(mesh) = shape.tessellate(segments) {
+        return bake(&mesh, transform, segments);
+    }
+
+    #[allow(clippy::type_complexity)]
+    let (alpha, indices, positions, normals, tangents, uvs, face_indices): (
+        _,
+        &[i32],
+        _,
+        &[f32],
+        &[f32],
+        &[f32],
+        &[i32],
+    ) = match shape {
+        Shape::TriangleMesh {
+            alpha,
+            indices,
+            positions,
+            normals,
+            tangents,
+            uvs,
+            face_indices,
+        } => (
+            *alpha,
+            indices,
+            positions,
+            normals,
+            tangents,
+            uvs,
+            face_indices,
+        ),
+        #[cfg(feature = "ply")]
+        Shape::PlyMesh {
+            mesh: Some(mesh), ..
+        } => (
+            1.0,
+            &mesh.indices,
+            &mesh.positions,
+            &mesh.normals,
+            &[],
+            &mesh.uvs,
+            &mesh.face_indices,
+        ),
+        _ => return None,
+    };
+
+    // Normals transform with the inverse transpose.
+    let normal_transform = transform.inverse().transpose();
+
+    let positions = positions
+        .chunks_exact(3)
+        .flat_map(|p| {
+            transform
+                .transform_point3(Vec3::new(p[0], p[1], p[2]))
+                .to_array()
+        })
+        .collect();
+
+    #[allow(clippy::unnecessary_cast)]
+    let vectors = |values: &[f32], m: &Mat4, normalize: bool| {
+        values
+            .chunks_exact(3)
+            .flat_map(|v| {
+                let v = m.transform_vector3(Vec3::new(float(v[0]), float(v[1]), float(v[2])));
+                let v = if normalize { v.normalize() } else { v };
+
+                v.to_array().map(|v| v as f32)
+            })
+            .collect::<Vec<_>>()
+    };
+
+    Some(Shape::TriangleMesh {
+        alpha,
+        indices: indices.to_vec(),
+        positions,
+        normals: vectors(normals, &normal_transform, true),
+        tangents: vectors(tangents, transform, false),
+        uvs: uvs.to_vec(),
+        face_indices: face_indices.to_vec(),
+    })
+}
+
+/// Quote a string value, escaping quotes, backslashes and line breaks.
+fn quote(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+
+    format!("\"{}\"", escaped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bake_options() -> WriteOptions {
+        WriteOptions {
+            bake_transforms: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn round_trip() -> Result<()> {
+        let data = r#"
+MakeNamedMedium "fog" "string type" "homogeneous" "rgb sigma_a" [ 0.1 0.2 0.3 ] "float g" 0.5
+MediumInterface "" "fog"
+LookAt 0 0 -5  0 0 0  0 1 0
+Camera "perspective" "float fov" [ 45 ] "float screenwindow" [ -1 1 -0.5 0.5 ]
+Film "rgb" "integer xresolution" [ 64 ] "integer yresolution" [ 32 ]
+Sampler "halton" "integer pixelsamples" 64 "string randomization" "owen"
+Integrator "bdpt" "integer maxdepth" 8 "string lightsampler" "uniform"
+PixelFilter "mitchell" "float xradius" [ 1.5 ] "float C" [ 0.5 ]
+WorldBegin
+Texture "bumps" "float" "imagemap" "string filename" "bumps.png"
+AttributeBegin
+  Scale 2 2 2
+  Texture "checks" "float" "checkerboard" "texture tex1" "bumps" "float tex2" 0.25
+    "string mapping" "planar" "vector3 v1" [ 0 0 1 ]
+AttributeEnd
+Texture "tint" "spectrum" "mix" "rgb tex1" [ 1 0 0 ] "texture amount" "checks"
+MakeNamedMaterial "gold" "string type" "conductor" "spectrum eta" "metal-Au-eta"
+    "spectrum k" "metal-Au-k" "float roughness" 0.1
+MakeNamedMaterial "glass" "string type" "dielectric" "spectrum eta" "glass-BK7"
+MakeNamedMaterial "mixed" "string type" "mix" "string materials" [ "gold" "glass" ] "float amount" 0.25
+AttributeBegin
+  ColorSpace "dci-p3"
+  LightSource "infinite" "rgb L" [ 0.5 0.5 0.5 ]
+AttributeEnd
+AttributeBegin
+  Translate 0 1 0
+  MakeNamedMedium "smoke" "string type" "uniformgrid" "integer nx" 2 "integer ny" 1 "integer nz" 1
+    "float density" [ 0.5 1 ] "point3 p1" [ 2 2 2 ]
+AttributeEnd
+AttributeBegin
+  AreaLightSource "diffuse" "blackbody L" 6500
+  NamedMaterial "mixed"
+  MediumInterface "smoke" "fog"
+  Translate 1 2 3
+  Shape "sphere" "float radius" 2
+AttributeEnd
+ObjectBegin "tri"
+Material "coatedconductor" "float interface.roughness" 0.2 "rgb reflectance" [ 0.9 0.5 0.1 ]
+  "texture displacement" "bumps" "string normalmap" "normals.png"
+Shape "trianglemesh" "point3 P" [ 0 0 0 1 0 0 0 1 0 ] "integer indices" [ 0 1 2 ]
+ObjectEnd
+Translate 0 0 10
+ObjectInstance "tri"
+        "#;
+
+        let scene = Scene::load(data, None)?;
+        let out = scene.to_pbrt_string(&WriteOptions::default())?;
+        let reloaded = Scene::load(&out, None)?;
+
+        let camera = reloaded.camera.as_ref().unwrap();
+        assert_eq!(camera.transform, scene.camera.as_ref().unwrap().transform);
+        assert!(matches!(
+            camera.params,
+            Camera::Perspective { fov, screen_window: Some([-1.0, 1.0, -0.5, 0.5]), .. } if fov == 45.0
+        ));
+        assert_eq!(reloaded.film.as_ref().unwrap().xresolution, 64);
+        assert!(matches!(
+            reloaded.sampler,
+            Some(Sampler::Halton {
+                pixel_samples: 64,
+                randomization: RandomizeStrategy::Owen,
+                ..
+            })
+        ));
+        assert!(matches!(
+            reloaded.integrator,
+            Some(Integrator::Bdpt {
+                max_depth: 8,
+                light_sampler: LightSampler::Uniform,
+                ..
+            })
+        ));
+        assert_eq!(reloaded.filter, scene.filter);
+
+        assert_eq!(reloaded.textures.len(), 3);
+        for (reloaded, texture) in reloaded.textures.iter().zip(&scene.textures) {
+            assert_eq!(reloaded.name, texture.name);
+            assert_eq!(reloaded.ty, texture.ty);
+            assert_eq!(reloaded.class, texture.class);
+            assert_eq!(reloaded.mapping, texture.mapping);
+            assert_eq!(reloaded.transform, texture.transform);
+        }
+        assert_eq!(
+            reloaded.textures[1].transform,
+            Mat4::from_scale(Vec3::new(2.0, 2.0, 2.0))
+        );
+
+        assert_eq!(reloaded.materials.len(), scene.materials.len());
+        for (reloaded, material) in reloaded.materials.iter().zip(&scene.materials) {
+            assert_eq!(reloaded.name, material.name);
+            assert_eq!(reloaded.displacement, material.displacement);
+            assert_eq!(reloaded.normal_map, material.normal_map);
+            assert_eq!(reloaded.ty, material.ty);
+        }
+        assert_eq!(reloaded.shapes[0].material_index, Some(2));
+        assert_eq!(reloaded.materials[3].displacement, Some(TexRef::Texture(0)));
+
+        assert_eq!(reloaded.mediums.len(), 2);
+        for (reloaded, medium) in reloaded.mediums.iter().zip(&scene.mediums) {
+            assert_eq!(reloaded.name, medium.name);
+            assert_eq!(reloaded.ty, medium.ty);
+            assert_eq!(reloaded.transform, medium.transform);
+        }
+        assert_eq!(camera.exterior_medium_index, Some(0));
+        assert_eq!(reloaded.shapes[0].interior_medium_index, Some(1));
+
+        assert_eq!(reloaded.lights.len(), 1);
+        assert_eq!(reloaded.lights[0].color_space, ColorSpace::DciP3);
+        assert_eq!(reloaded.lights[0].exterior_medium_index, Some(0));
+        assert_eq!(reloaded.area_lights.len(), 1);
+        assert_eq!(reloaded.shapes.len(), 2);
+        assert_eq!(reloaded.objects.len(), 1);
+        assert_eq!(reloaded.instances.len(), 1);
+
+        for (a, b) in scene.shapes.iter().zip(&reloaded.shapes) {
+            assert_eq!(a.transform, b.transform);
+            assert_eq!(a.area_light_index, b.area_light_index);
+            assert_eq!(a.interior_medium_index, b.interior_medium_index);
+            assert_eq!(a.exterior_medium_index, b.exterior_medium_index);
+        }
+
+        assert_eq!(
+            reloaded.instances[0].instance_to_world,
+            scene.instances[0].instance_to_world
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn escaped_strings() -> Result<()> {
+        let data = r#"
+WorldBegin
+ObjectBegin "the \"best\" \\ tri"
+Shape "sphere"
+ObjectEnd
+ObjectInstance "the \"best\" \\ tri"
+        "#;
+
+        let scene = Scene::load(data, None)?;
+        assert_eq!(scene.objects[0].name, r#"the "best" \ tri"#);
+
+        let out = scene.to_pbrt_string(&WriteOptions::default())?;
+        assert!(out.contains(r#"ObjectBegin "the \"best\" \\ tri""#));
+
+        let reloaded = Scene::load(&out, None)?;
+        assert_eq!(reloaded.objects[0].name, scene.objects[0].name);
+
+        Ok(())
+    }
+
+    #[test]
+    fn bake_transforms() -> Result<()> {
+        let data = r#"
+WorldBegin
+AttributeBegin
+  Translate 1 0 0
+  Scale -1 1 1
+  Shape "trianglemesh" "point3 P" [ 0 0 0 1 0 0 0 1 0 ] "integer indices" [ 0 1 2 ]
+      "normal N" [ 0 0 1 0 0 1 0 0 1 ]
+AttributeEnd
+ObjectBegin "tri"
+Shape "trianglemesh" "point3 P" [ 0 0 0 1 0 0 0 1 0 ] "integer indices" [ 0 1 2 ]
+ObjectEnd
+Translate 0 0 10
+ObjectInstance "tri"
+ObjectInstance "tri"
+Shape "sphere"
+        "#;
+
+        let scene = Scene::load(data, None)?;
+        let out = scene.to_pbrt_string(&bake_options())?;
+
+        assert!(!out.contains("Transform"));
+        assert!(!out.contains("ObjectBegin"));
+        assert!(!out.contains("\"sphere\""));
+
+        let baked = Scene::load(&out, None)?;
+
+        // Mirrored mesh, two expanded instances and the tessellated sphere.
+        assert_eq!(baked.shapes.len(), 4);
+        assert!(baked.objects.is_empty());
+
+        for shape in &baked.shapes {
+            assert_eq!(shape.transform, Mat4::IDENTITY);
+        }
+
+        let Shape::TriangleMesh {
+            positions, normals, ..
+        } = &baked.shapes[0].params
+        else {
+            panic!("Expected triangle mesh");
+        };
+
+        assert_eq!(positions, &[1.0, 0.0, 0.0, 0.0, 0.0, 0.0, 1.0, 1.0, 0.0]);
+        assert_eq!(normals, &[0.0, 0.0, 1.0, 0.0, 0.0, 1.0, 0.0, 0.0, 1.0]);
+        // Mirroring swaps handedness.
+        assert!(baked.shapes[0].reverse_orientation);
+
+        let Shape::TriangleMesh { positions, .. } = &baked.shapes[2].params else {
+            panic!("Expected triangle mesh");
+        };
+        assert_eq!(positions, &[0.0, 0.0, 10.0, 1.0, 0.0, 10.0, 0.0, 1.0, 10.0]);
+
+        Ok(())
+    }
+}
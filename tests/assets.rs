@@ -1,6 +1,6 @@
 use pbrt4::{
-    param::Spectrum,
-    types::{Camera, Light, Shape},
+    param::{FromTypedParams, Spectrum, TexRef},
+    types::{Camera, Light, MaterialType, Shape},
     Scene,
 };
 
@@ -29,27 +29,36 @@ fn disney_cloud() {
 
     // Infinite light
     {
-        let infinite = &scene.lights[0];
+        let infinite = &scene.lights[0].params;
 
-        let Light::Infinite { spectrum , ..} = infinite else {
+        let Light::Infinite { spectrum, .. } = infinite else {
             panic!("Unexpected light type at 0, want Infinite");
         };
 
-        let Spectrum::Rgb(rgb) = spectrum.unwrap() else {
+        let Spectrum::Rgb(rgb) = spectrum.as_ref().unwrap() else {
             panic!("Unexpected spectrum value type");
         };
 
-        assert_eq_f32_arr(rgb, [0.03, 0.07, 0.23]);
+        assert_eq_f32_arr(*rgb, [0.03, 0.07, 0.23]);
     }
 
     // Distant light
     {
-        let distant = &scene.lights[1];
+        let distant = &scene.lights[1].params;
         assert!(matches!(distant, Light::Distant));
     }
 
     assert_eq!(scene.materials.len(), 2);
 
+    // Diffuse floor and the interface of the cloud's medium.
+    assert_eq!(
+        scene.materials[0].ty,
+        MaterialType::Diffuse {
+            reflectance: TexRef::Value(Spectrum::Sampled(vec![(200.0, 0.2), (900.0, 0.2)]))
+        }
+    );
+    assert_eq!(scene.materials[1].ty, MaterialType::Interface);
+
     assert_eq!(scene.shapes.len(), 2);
 
     // Disk shape
@@ -62,7 +71,14 @@ fn disney_cloud() {
     // Sphere
     {
         let sphere = &scene.shapes[1];
-        let Shape::Sphere { alpha, radius, zmin, zmax, phimax } = sphere.params else {
+        let Shape::Sphere {
+            alpha,
+            radius,
+            zmin,
+            zmax,
+            phimax,
+        } = sphere.params
+        else {
             panic!("Unexpected shape at 1, want Sphere");
         };
 
@@ -84,4 +100,86 @@ fn kroken() {
     assert_eq!(scene.instances.len(), 10);
 
     assert_eq!(scene.textures.len(), 74);
+
+    let texture = |name: &str| {
+        scene
+            .textures
+            .iter()
+            .position(|texture| texture.name == name)
+            .unwrap_or_else(|| panic!("Texture {name:?} is not defined"))
+    };
+
+    let material = |name: &str| {
+        scene
+            .materials
+            .iter()
+            .find(|material| material.name.as_deref() == Some(name))
+            .unwrap_or_else(|| panic!("Material {name:?} is not defined"))
+    };
+
+    let MaterialType::CoatedConductor {
+        conductor_u_roughness,
+        conductor_eta,
+        interface_roughness,
+        thickness,
+        albedo,
+        ..
+    } = &material("Metal - Bronze").ty
+    else {
+        panic!("Expected coated conductor");
+    };
+    assert_eq!(*conductor_u_roughness, TexRef::Value(0.025));
+    assert_eq!(
+        *conductor_eta,
+        TexRef::Value(Spectrum::Named("metal-Au-eta"))
+    );
+    assert_eq!(*interface_roughness, TexRef::Value(0.1));
+    assert_eq!(*thickness, TexRef::Value(0.2));
+    assert_eq!(*albedo, TexRef::Value(Spectrum::Rgb([0.5; 3])));
+
+    assert!(matches!(
+        &material("Metal - Dark").ty,
+        MaterialType::CoatedConductor {
+            interface_roughness: TexRef::Texture(index),
+            reflectance: Some(TexRef::Value(Spectrum::Rgb(_))),
+            ..
+        } if *index == texture("float:textures/Metal_Dark_Glossiness")
+    ));
+
+    assert_eq!(
+        material("Fabric - Pillow").ty,
+        MaterialType::Mix {
+            materials: [
+                "Fabric - Pillow - blue".to_string(),
+                "Fabric - Pillow - white".to_string()
+            ],
+            amount: TexRef::Texture(texture("float:textures/Dots_Pillow")),
+        }
+    );
+
+    assert_eq!(
+        material("Fabric - Blanket - Fringe").displacement,
+        Some(TexRef::Texture(texture(
+            "float:textures/Fabric_Blanket_Diffuse&Bump"
+        )))
+    );
+
+    // All material types used by the scene are parsed.
+    for ty in [
+        "coatedconductor",
+        "coateddiffuse",
+        "conductor",
+        "dielectric",
+        "diffuse",
+        "diffusetransmission",
+        "mix",
+    ] {
+        assert!(
+            scene
+                .materials
+                .iter()
+                .any(|material| material.ty.type_name() == ty),
+            "{ty}"
+        );
+    }
 }
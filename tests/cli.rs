@@ -0,0 +1,84 @@
+#![cfg(feature = "cli")]
+
+use std::{fs, process::Command};
+
+use tempdir::TempDir;
+
+fn pbrt4(args: &[&str]) -> (bool, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_pbrt4"))
+        .args(args)
+        .output()
+        .unwrap();
+
+    (
+        output.status.success(),
+        String::from_utf8(output.stdout).unwrap(),
+    )
+}
+
+#[test]
+fn info() {
+    let (ok, out) = pbrt4(&["info", "assets/disney-cloud/disney-cloud.pbrt"]);
+    assert!(ok);
+    assert!(out.contains("Lights: 2"), "{}", out);
+}
+
+#[test]
+fn validate() {
+    let dir = TempDir::new("pbrt4").unwrap();
+    let path = dir.path().join("invalid.pbrt");
+    fs::write(&path, "WorldBegin\nShape \"curve\" \"integer degree\" 4\n").unwrap();
+
+    let (ok, out) = pbrt4(&["validate", path.to_str().unwrap()]);
+    assert!(!ok);
+    assert!(out.contains("degree"), "{}", out);
+}
+
+#[test]
+fn deps() {
+    let dir = TempDir::new("pbrt4").unwrap();
+    let path = dir.path().join("scene.pbrt");
+    fs::write(
+        &path,
+        "WorldBegin\nShape \"plymesh\" \"string filename\" \"mesh.ply\"\n",
+    )
+    .unwrap();
+
+    let (ok, out) = pbrt4(&["deps", path.to_str().unwrap()]);
+    assert!(ok);
+    assert!(out.starts_with("mesh"), "{}", out);
+    assert!(out.trim_end().ends_with("mesh.ply"), "{}", out);
+
+    let (ok, _) = pbrt4(&["deps", "--missing", path.to_str().unwrap()]);
+    assert!(!ok);
+}
+
+#[test]
+fn fmt() {
+    let dir = TempDir::new("pbrt4").unwrap();
+    let path = dir.path().join("scene.pbrt");
+    fs::write(
+        &path,
+        "WorldBegin\nAttributeBegin\nShape   \"sphere\"\nAttributeEnd\n",
+    )
+    .unwrap();
+
+    let (ok, out) = pbrt4(&["fmt", "--indent", "2", path.to_str().unwrap()]);
+    assert!(ok);
+    assert!(out.contains("\n  Shape \"sphere\"\n"), "{}", out);
+
+    let (ok, _) = pbrt4(&["fmt", "--write", path.to_str().unwrap()]);
+    assert!(ok);
+    assert!(fs::read_to_string(&path)
+        .unwrap()
+        .contains("\n    Shape \"sphere\"\n"));
+}
+
+#[test]
+fn usage() {
+    let (ok, _) = pbrt4(&["frobnicate", "scene.pbrt"]);
+    assert!(!ok);
+
+    let (ok, _) = pbrt4(&["info"]);
+    assert!(!ok);
+}
@@ -0,0 +1,151 @@
+//! Golden tests against a local checkout of the official pbrt-v4 scenes repository
+//! (<https://github.com/mmp/pbrt-v4-scenes>).
+//!
+//! These tests are ignored by default, as the scenes repository is too large to be
+//! a part of this crate. To run them:
+//!
+//! `PBRT_V4_SCENES=/path/to/pbrt-v4-scenes cargo test --test scenes -- --ignored --nocapture`
+//!
+//! Every top-level scene file (a `.pbrt` file containing `WorldBegin`) is loaded and its
+//! entity counts are compared against `tests/golden/pbrt-v4-scenes.txt`. Scenes without a
+//! golden entry fail the test.
+//! Set `PBRT_V4_SCENES_BLESS=1` to record the current counts as the new golden values.
+
+use std::{
+    collections::BTreeMap,
+    env, fs,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+};
+
+use pbrt4::Scene;
+
+const GOLDEN_FILE: &str = "tests/golden/pbrt-v4-scenes.txt";
+
+/// Recursively collect top-level scene files.
+fn find_scenes(dir: &Path, scenes: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+
+    for entry in entries.flatten() {
+        let path = entry.path();
+
+        if path.is_dir() {
+            find_scenes(&path, scenes);
+        } else if path.extension().map_or(false, |ext| ext == "pbrt") {
+            // Included files don't contain a world block.
+            let is_scene = fs::read_to_string(&path)
+                .map(|data| data.contains("WorldBegin"))
+                .unwrap_or(false);
+
+            if is_scene {
+                scenes.push(path);
+            }
+        }
+    }
+}
+
+/// Summarize a scene into a single golden line.
+fn entity_counts(scene: &Scene) -> String {
+    format!(
+        "shapes={} lights={} materials={} textures={} mediums={} objects={} instances={}",
+        scene.shapes.len(),
+        scene.lights.len(),
+        scene.materials.len(),
+        scene.textures.len(),
+        scene.mediums.len(),
+        scene.objects.len(),
+        scene.instances.len(),
+    )
+}
+
+fn load_counts(path: &Path) -> Result<String, String> {
+    // Unsupported features may still panic, keep going to report all gaps at once.
+    match panic::catch_unwind(AssertUnwindSafe(|| Scene::from_file(path))) {
+        Ok(Ok(scene)) => Ok(entity_counts(&scene)),
+        Ok(Err(err)) => Err(format!("error: {}", err)),
+        Err(panic) => Err(format!(
+            "panic: {}",
+            panic
+                .downcast_ref::<String>()
+                .cloned()
+                .or_else(|| panic.downcast_ref::<&str>().map(|s| s.to_string()))
+                .unwrap_or_default()
+        )),
+    }
+}
+
+fn read_golden() -> BTreeMap<String, String> {
+    fs::read_to_string(GOLDEN_FILE)
+        .unwrap_or_default()
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .filter_map(|line| line.split_once(": "))
+        .map(|(path, counts)| (path.to_string(), counts.to_string()))
+        .collect()
+}
+
+#[test]
+#[ignore = "requires PBRT_V4_SCENES to point to a pbrt-v4-scenes checkout"]
+fn pbrt_v4_scenes() {
+    let root = PathBuf::from(env::var("PBRT_V4_SCENES").expect("PBRT_V4_SCENES is not set"));
+    let bless = env::var("PBRT_V4_SCENES_BLESS").is_ok();
+
+    let mut scenes = Vec::new();
+    find_scenes(&root, &mut scenes);
+    scenes.sort();
+
+    assert!(!scenes.is_empty(), "No scenes found in {}", root.display());
+
+    let golden = read_golden();
+
+    let mut results = BTreeMap::new();
+    let mut failures = Vec::new();
+
+    for path in &scenes {
+        let name = path
+            .strip_prefix(&root)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .replace('\\', "/");
+
+        match load_counts(path) {
+            Ok(counts) => {
+                if !bless {
+                    match golden.get(&name) {
+                        Some(expected) if *expected != counts => failures
+                            .push(format!("{}: expected {}, got {}", name, expected, counts)),
+                        Some(_) => {}
+                        None => failures.push(format!(
+                            "{}: no golden entry, got {} (run with PBRT_V4_SCENES_BLESS=1)",
+                            name, counts
+                        )),
+                    }
+                }
+
+                results.insert(name, counts);
+            }
+            Err(err) => failures.push(format!("{}: {}", name, err)),
+        }
+    }
+
+    println!("Loaded {} of {} scenes", results.len(), scenes.len());
+
+    if bless {
+        let mut data = String::from("# Generated by tests/scenes.rs with PBRT_V4_SCENES_BLESS=1\n");
+        for (name, counts) in &results {
+            data.push_str(&format!("{}: {}\n", name, counts));
+        }
+
+        fs::create_dir_all(Path::new(GOLDEN_FILE).parent().unwrap()).unwrap();
+        fs::write(GOLDEN_FILE, data).unwrap();
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} scene(s) failed:\n{}",
+        failures.len(),
+        failures.join("\n")
+    );
+}
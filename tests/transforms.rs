@@ -0,0 +1,148 @@
+//! Transformation directives follow pbrt-v4 (`src/pbrt/util/transform.cpp` and
+//! `src/pbrt/scene.cpp`).
+//!
+//! Expected matrices are written row-major, the way pbrt prints `Transform`s.
+
+use pbrt4::{math::Mat4, Scene};
+
+#[allow(clippy::unnecessary_cast)]
+fn assert_matrix(m: &Mat4, expected: [[f64; 4]; 4]) {
+    for (row, values) in expected.iter().enumerate() {
+        for (col, value) in values.iter().enumerate() {
+            let actual = m.get(row, col) as f64;
+            assert!(
+                (actual - value).abs() < 1e-5,
+                "[{}][{}]: {} != {}\n{:?}",
+                row,
+                col,
+                actual,
+                value,
+                m
+            );
+        }
+    }
+}
+
+/// CTM of the first shape after `transforms`.
+fn ctm(transforms: &str) -> Mat4 {
+    let data = format!("WorldBegin\n{}\nShape \"sphere\"", transforms);
+    Scene::load(&data, None).unwrap().shapes[0].transform
+}
+
+#[test]
+fn rotate() {
+    assert_matrix(
+        &ctm("Rotate 90 0 0 1"),
+        [
+            [0.0, -1.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+    );
+
+    // The axis is normalized.
+    assert_matrix(
+        &ctm("Rotate 45 2 0 0"),
+        [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 0.70710677, -0.70710677, 0.0],
+            [0.0, 0.70710677, 0.70710677, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+    );
+
+    assert_matrix(
+        &ctm("Rotate -30 0 1 0"),
+        [
+            [0.8660254, 0.0, -0.5, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.5, 0.0, 0.8660254, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+    );
+}
+
+#[test]
+fn composition() {
+    // Transformations are post-multiplied, so the last one is applied first.
+    assert_matrix(
+        &ctm("Translate 1 2 3\nRotate 90 0 1 0\nScale 2 2 2"),
+        [
+            [0.0, 0.0, 2.0, 1.0],
+            [0.0, 2.0, 0.0, 2.0],
+            [-2.0, 0.0, 0.0, 3.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+    );
+
+    // Matrices are given in column-major order.
+    assert_matrix(
+        &ctm("Transform [ 1 0 0 0  0 1 0 0  0 0 1 0  4 5 6 1 ]\nConcatTransform [ 0 1 0 0  -1 0 0 0  0 0 1 0  0 0 0 1 ]"),
+        [
+            [0.0, -1.0, 0.0, 4.0],
+            [1.0, 0.0, 0.0, 5.0],
+            [0.0, 0.0, 1.0, 6.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+    );
+}
+
+#[test]
+fn look_at() {
+    assert_matrix(
+        &ctm("LookAt 0 0 -5  0 0 0  0 1 0"),
+        [
+            [1.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 5.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+    );
+
+    assert_matrix(
+        &ctm("LookAt 1 1 1  0 0 0  0 0 1"),
+        [
+            [0.70710677, -0.70710677, 0.0, 0.0],
+            [-0.40824829, -0.40824829, 0.81649658, 0.0],
+            [-0.57735027, -0.57735027, -0.57735027, 1.7320508],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+    );
+
+    // Up vector is parallel to the viewing direction.
+    assert_matrix(
+        &ctm("Translate 1 0 0\nLookAt 0 0 0  0 0 1  0 0 1"),
+        [
+            [1.0, 0.0, 0.0, 1.0],
+            [0.0, 1.0, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+    );
+}
+
+#[test]
+fn camera() {
+    let data = r#"
+Scale -1 1 1
+LookAt 1 1 1  0 0 0  0 0 1
+Camera "perspective"
+WorldBegin
+"#;
+
+    let scene = Scene::load(data, None).unwrap();
+    let camera = scene.camera.unwrap();
+
+    // The camera to world transformation is the inverse of the CTM, columns are the
+    // camera's x (mirrored by `Scale -1 1 1`), y and z axes and its position.
+    assert_matrix(
+        &camera.transform,
+        [
+            [-0.70710677, -0.40824829, -0.57735027, 1.0],
+            [0.70710677, -0.40824829, -0.57735027, 1.0],
+            [0.0, 0.81649658, -0.57735027, 1.0],
+            [0.0, 0.0, 0.0, 1.0],
+        ],
+    );
+}